@@ -0,0 +1,67 @@
+// Instanced sprite shader: one unit quad per instance, moved, scaled and rotated in the vertex
+// stage, sampling an atlas region tinted by the instance color, with an optional outline
+
+struct Uniforms {
+    view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var sprite_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    @location(4) rotation: f32,
+    @location(5) outline_width: f32,
+    @location(6) uv_rect: vec4<f32>,
+    @location(7) tint: vec4<f32>,
+    @location(8) outline_color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) quad_coords: vec2<f32>,
+    @location(2) tint: vec4<f32>,
+    @location(3) outline_color: vec4<f32>,
+    @location(4) outline_width: f32,
+};
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let scaled = vertex.position * instance.size;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated = vec2<f32>(scaled.x * c - scaled.y * s, scaled.x * s + scaled.y * c);
+
+    var out: VertexOutput;
+    out.clip_position = uniforms.view_projection * vec4<f32>(rotated + instance.position, 0.0, 1.0);
+    out.tex_coords = instance.uv_rect.xy + vertex.tex_coords * instance.uv_rect.zw;
+    out.quad_coords = vertex.tex_coords;
+    out.tint = instance.tint;
+    out.outline_color = instance.outline_color;
+    out.outline_width = instance.outline_width;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Sample before branching so derivatives stay well defined
+    let color = textureSample(sprite_texture, sprite_sampler, in.tex_coords) * in.tint;
+
+    let edge = min(min(in.quad_coords.x, 1.0 - in.quad_coords.x), min(in.quad_coords.y, 1.0 - in.quad_coords.y));
+    if (in.outline_width > 0.0 && edge < in.outline_width) {
+        return in.outline_color;
+    }
+    return color;
+}
@@ -0,0 +1,39 @@
+// Terrain tile shader: samples the terrain type's texture and tints it with the vertex color
+
+struct Uniforms {
+    view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var terrain_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var terrain_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = uniforms.view_projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(terrain_texture, terrain_sampler, in.tex_coords) * in.color;
+}
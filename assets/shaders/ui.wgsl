@@ -0,0 +1,53 @@
+// Instanced UI shader: one unit quad per instance, placed in logical pixels with y down, rotated
+// about its center and sampling a texture region tinted by the instance color
+
+struct Screen {
+    size: vec2<f32>,
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+
+@group(1) @binding(0)
+var ui_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var ui_sampler: sampler;
+
+struct VertexInput {
+    @location(0) corner: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(1) position: vec2<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) rotation: f32,
+    @location(4) uv_rect: vec4<f32>,
+    @location(5) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let offset = (vertex.corner - vec2<f32>(0.5, 0.5)) * instance.size;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated = vec2<f32>(offset.x * c - offset.y * s, offset.x * s + offset.y * c);
+    let pixel = instance.position + instance.size * 0.5 + rotated;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pixel.x / screen.size.x * 2.0 - 1.0, 1.0 - pixel.y / screen.size.y * 2.0, 0.0, 1.0);
+    out.tex_coords = instance.uv_rect.xy + vertex.corner * instance.uv_rect.zw;
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(ui_texture, ui_sampler, in.tex_coords) * in.color;
+}
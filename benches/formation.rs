@@ -0,0 +1,54 @@
+//! Times spreading a group order into formation slots, which runs once per order but is
+//! quadratic in the number of units selected.
+
+use bevy_ecs::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec2;
+
+#[allow(dead_code)]
+#[path = "../src/game/formation.rs"]
+mod formation;
+
+use formation::{formation_destinations, Formation, FormationMember};
+
+const UNIT_RADIUS: f32 = 4.0;
+const SPREAD: f32 = 256.0;
+
+/// Deterministic pseudo-random members scattered around the origin
+fn members(count: usize) -> Vec<FormationMember> {
+    let mut world = World::new();
+    let mut state: u32 = 0x9E37_79B9;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * SPREAD
+    };
+
+    (0..count)
+        .map(|_| FormationMember {
+            entity: world.spawn_empty().id(),
+            position: Vec2::new(next(), next()),
+            radius: UNIT_RADIUS,
+        })
+        .collect()
+}
+
+fn formation_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("formation_destinations");
+    let target = Vec2::new(1024.0, 768.0);
+
+    for count in [10, 50, 100, 200] {
+        let members = members(count);
+        for formation in [Formation::Box, Formation::Line, Formation::Wedge] {
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", formation), count), &members, |b, members| {
+                b.iter(|| formation_destinations(formation, black_box(members), target))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, formation_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+//! Times the movement, combat and fog-of-war systems per tick of the stress battle, once the
+//! armies have closed in, so a regression in any one shows up apart from the rest of the tick.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::Once;
+use std::time::Duration;
+
+// The game is a binary, so the benchmark builds its modules in itself
+#[allow(dead_code)]
+#[path = "../src/ecs/mod.rs"]
+mod ecs;
+#[allow(dead_code)]
+#[path = "../src/engine/mod.rs"]
+mod engine;
+#[allow(dead_code)]
+#[path = "../src/game/mod.rs"]
+mod game;
+#[allow(dead_code)]
+#[path = "../src/networking/mod.rs"]
+mod networking;
+#[allow(dead_code)]
+#[path = "../src/ui/mod.rs"]
+mod ui;
+
+use ecs::resources::TICK_RATE;
+use engine::profiler;
+use engine::stress::{StressBattle, StressParams};
+use game::data;
+
+/// Ticks run before timing starts, long enough for the armies to meet
+const WARMUP_TICKS: u64 = TICK_RATE as u64 * 8;
+
+const MOVEMENT_SYSTEMS: [&str; 5] = [
+    "pathfinding_obstacle_system",
+    "path_queue_system",
+    "update_movement_system",
+    "spatial_grid_system",
+    "collision_detection_system",
+];
+const COMBAT_SYSTEMS: [&str; 4] = ["building_targeting_system", "combat_system", "damage_system", "death_system"];
+const FOG_SYSTEMS: [&str; 1] = ["fog_of_war_system"];
+
+fn load_data() {
+    static LOAD: Once = Once::new();
+    LOAD.call_once(|| {
+        data::init(data::DEFAULT_DATA_DIR).unwrap_or_else(|e| panic!("Failed to load game data: {}", e));
+    });
+}
+
+/// Time the given systems take per tick of a battle between two armies of each size
+fn bench_systems(c: &mut Criterion, group_name: &str, systems: &[&str]) {
+    load_data();
+    profiler::set_enabled(true);
+    
+    let mut group = c.benchmark_group(group_name);
+    group.sample_size(20);
+    for units in [100, 200] {
+        group.bench_with_input(BenchmarkId::from_parameter(units), &units, |b, &units| {
+            b.iter_custom(|iters| {
+                let mut battle = StressBattle::new(&StressParams { units, enemies: units, ..StressParams::default() });
+                for _ in 0..WARMUP_TICKS {
+                    battle.tick();
+                }
+                
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    battle.tick();
+                    total += profiler::system_times()
+                        .into_iter()
+                        .filter(|(name, _)| systems.contains(&name.as_str()))
+                        .map(|(_, time)| time)
+                        .sum::<Duration>();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+fn movement_benchmark(c: &mut Criterion) {
+    bench_systems(c, "movement", &MOVEMENT_SYSTEMS);
+}
+
+fn combat_benchmark(c: &mut Criterion) {
+    bench_systems(c, "combat", &COMBAT_SYSTEMS);
+}
+
+fn fog_of_war_benchmark(c: &mut Criterion) {
+    bench_systems(c, "fog_of_war", &FOG_SYSTEMS);
+}
+
+criterion_group!(benches, movement_benchmark, combat_benchmark, fog_of_war_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+//! Compares brute-force pairwise proximity checks with the spatial hash grid
+//! used by collision, target acquisition and AOE damage.
+
+use bevy_ecs::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec2;
+
+#[allow(dead_code)]
+#[path = "../src/ecs/spatial.rs"]
+mod spatial;
+
+use spatial::SpatialGrid;
+
+const UNIT_RADIUS: f32 = 4.0;
+const MAP_SIZE: f32 = 2048.0;
+
+/// Deterministic pseudo-random unit positions spread over the map
+fn unit_positions(count: usize) -> Vec<(Entity, Vec2)> {
+    let mut world = World::new();
+    let mut state: u32 = 0x9E37_79B9;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * MAP_SIZE
+    };
+    
+    (0..count)
+        .map(|_| (world.spawn_empty().id(), Vec2::new(next(), next())))
+        .collect()
+}
+
+/// Old approach: test every pair
+fn count_collisions_brute_force(units: &[(Entity, Vec2)]) -> usize {
+    let mut collisions = 0;
+    for i in 0..units.len() {
+        for j in (i + 1)..units.len() {
+            if (units[i].1 - units[j].1).length() < UNIT_RADIUS * 2.0 {
+                collisions += 1;
+            }
+        }
+    }
+    collisions
+}
+
+/// New approach: rebuild the grid, then test only nearby entries
+fn count_collisions_spatial_grid(grid: &mut SpatialGrid, units: &[(Entity, Vec2)]) -> usize {
+    grid.clear();
+    for (entity, position) in units {
+        grid.insert(*entity, *position, UNIT_RADIUS);
+    }
+    
+    let mut collisions = 0;
+    for (entity, position) in units {
+        for neighbor in grid.query_radius(*position, UNIT_RADIUS + grid.max_radius()) {
+            if neighbor.entity > *entity && (neighbor.position - *position).length() < UNIT_RADIUS * 2.0 {
+                collisions += 1;
+            }
+        }
+    }
+    collisions
+}
+
+fn collision_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_detection");
+    
+    for count in [100, 500, 1000, 2000] {
+        let units = unit_positions(count);
+        
+        // Both approaches must agree before their timings mean anything
+        let mut grid = SpatialGrid::default();
+        assert_eq!(
+            count_collisions_brute_force(&units),
+            count_collisions_spatial_grid(&mut grid, &units)
+        );
+        
+        group.bench_with_input(BenchmarkId::new("brute_force", count), &units, |b, units| {
+            b.iter(|| count_collisions_brute_force(black_box(units)))
+        });
+        group.bench_with_input(BenchmarkId::new("spatial_grid", count), &units, |b, units| {
+            b.iter(|| count_collisions_spatial_grid(&mut grid, black_box(units)))
+        });
+    }
+    
+    group.finish();
+}
+
+criterion_group!(benches, collision_benchmark);
+criterion_main!(benches);
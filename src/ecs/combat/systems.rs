@@ -1,11 +1,11 @@
 use bevy_ecs::prelude::*;
 use glam::Vec2;
 use std::collections::{HashMap, VecDeque};
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
+use rand::Rng;
 
 use crate::ecs::components::{UnitType, BuildingType, Transform, Owner, Unit, Building, AttackTarget};
 use crate::ecs::resources::{GameMap, PlayerResources, GameTime};
+use crate::game::simulation::{tick_rng, RNG_STREAM_COMBAT};
 use crate::ecs::combat::components::{DamageTable, AttackCooldown, Projectile, Effect, EffectType};
 
 /// System to process attacks and combat
@@ -32,13 +32,9 @@ pub fn combat_system(
     transform_query: Query<&Transform>,
     mut projectile_query: Query<(Entity, &mut Projectile, &mut Transform)>,
     mut effect_query: Query<(Entity, &mut Effect, &mut Transform)>,
-    mut rng: Local<Option<StdRng>>,
 ) {
-    // Initialize RNG if needed
-    if rng.is_none() {
-        *rng = Some(StdRng::seed_from_u64(time.current_tick));
-    }
-    let rng = rng.as_mut().unwrap();
+    // Rolls come from the game seed and tick so every machine agrees on them
+    let rng = &mut tick_rng(time.seed, time.current_tick, RNG_STREAM_COMBAT);
     
     // Update projectiles
     for (entity, mut projectile, mut transform) in projectile_query.iter_mut() {
@@ -1,8 +1,11 @@
 use bevy_ecs::prelude::*;
 use glam::Vec2;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
 
+use crate::ecs::resources::FlowField;
+
 /// Entity position, rotation, and scale
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transform {
@@ -22,11 +25,16 @@ impl Default for Transform {
 }
 
 /// Entity owner (player id)
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Owner(pub u8);
 
+/// ID of a unit, building or resource node that is the same on every machine and in saves and
+/// replays, unlike its `Entity`; commands refer to entities by it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NetworkId(pub u32);
+
 /// Movement component with path following
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Movement {
     pub path: Vec<Vec2>,
     pub path_index: usize,
@@ -34,8 +42,20 @@ pub struct Movement {
     pub velocity: Vec2,
 }
 
-/// Collision detection component
+/// Unit heading straight for its destination while the path queue works out its route; holds the request's ID
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AwaitingPath(pub u64);
+
+/// Unit of a large group order steering by the group's shared flow field instead of a route of its own,
+/// until it gets close to its own destination
 #[derive(Component, Debug, Clone)]
+pub struct FlowFieldMove {
+    pub destination: Vec2,
+    pub field: Arc<FlowField>,
+}
+
+/// Collision detection component
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Collider {
     pub radius: f32,
     pub collision_layer: u32,
@@ -51,7 +71,7 @@ pub enum ResourceType {
 }
 
 /// Resource component
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
     pub resource_type: ResourceType,
     pub amount: f32,
@@ -65,10 +85,36 @@ pub enum UnitType {
     Scout,
     Tank,
     Healer,
+    Transport,
+}
+
+impl UnitType {
+    /// Every unit type, in declaration order
+    pub const ALL: [UnitType; 6] = [
+        UnitType::Worker,
+        UnitType::Soldier,
+        UnitType::Scout,
+        UnitType::Tank,
+        UnitType::Healer,
+        UnitType::Transport,
+    ];
+    
+    /// Unit type from its wire encoding (the enum discriminant)
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(UnitType::Worker),
+            1 => Some(UnitType::Soldier),
+            2 => Some(UnitType::Scout),
+            3 => Some(UnitType::Tank),
+            4 => Some(UnitType::Healer),
+            5 => Some(UnitType::Transport),
+            _ => None,
+        }
+    }
 }
 
 /// Unit component
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Unit {
     pub unit_type: UnitType,
     pub health: f32,
@@ -90,10 +136,37 @@ pub enum BuildingType {
     ResourceCollector,
     ResearchCenter,
     DefenseTower,
+    SupplyDepot,
+    Fortress,
+    Wall,
+    Gate,
+    Watchtower, // Neutral structure placed by the map; never built, only captured
+}
+
+impl BuildingType {
+    /// Every building type, in declaration order
+    pub const ALL: [BuildingType; 11] = [
+        BuildingType::Headquarters,
+        BuildingType::Barracks,
+        BuildingType::Factory,
+        BuildingType::ResourceCollector,
+        BuildingType::ResearchCenter,
+        BuildingType::DefenseTower,
+        BuildingType::SupplyDepot,
+        BuildingType::Fortress,
+        BuildingType::Wall,
+        BuildingType::Gate,
+        BuildingType::Watchtower,
+    ];
+    
+    /// Whether this is a player's main base: a Headquarters or the Fortress it upgrades into
+    pub fn is_headquarters(self) -> bool {
+        matches!(self, BuildingType::Headquarters | BuildingType::Fortress)
+    }
 }
 
 /// Building component
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Building {
     pub building_type: BuildingType,
     pub health: f32,
@@ -104,16 +177,156 @@ pub struct Building {
     pub rally_point: Option<Vec2>,
 }
 
+/// Upgrade a finished building is working through; production pauses until it is done
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Upgrading {
+    pub target: BuildingType,
+    pub progress: f32, // 0.0 to 1.0
+}
+
 /// Attack target component
 #[derive(Component, Debug)]
 pub struct AttackTarget {
     pub target_entity: Entity,
 }
 
+/// Friendly unit a healer is tending to
+#[derive(Component, Debug)]
+pub struct HealTarget {
+    pub target_entity: Entity,
+    pub healing: bool, // In range and restoring health this tick; drives the heal beam
+}
+
 /// Harvesting target component
 #[derive(Component, Debug)]
 pub struct HarvestTarget {
     pub target_entity: Entity,
+    pub returning: bool, // Walking the load back to a depot rather than to the node
+}
+
+/// Friendly building a worker is repairing
+#[derive(Component, Debug)]
+pub struct RepairTarget {
+    pub target_entity: Entity,
+}
+
+/// Friendly unit another unit keeps close to
+#[derive(Component, Debug)]
+pub struct Follow {
+    pub target_entity: Entity,
+}
+
+/// Transport a unit is walking to so it can board
+#[derive(Component, Debug)]
+pub struct BoardTarget {
+    pub target_entity: Entity,
+}
+
+/// Units carried by a transport, in the order they boarded
+#[derive(Component, Debug, Clone, Default)]
+pub struct Cargo {
+    pub capacity: u32,
+    pub passengers: Vec<Entity>,
+}
+
+/// Unit aboard a transport; it has no Transform until it is unloaded
+#[derive(Component, Debug)]
+pub struct Passenger {
+    pub transport: Entity,
+}
+
+/// Building a unit is walking to so it can shelter inside
+#[derive(Component, Debug)]
+pub struct GarrisonTarget {
+    pub target_entity: Entity,
+}
+
+/// Units sheltering inside a building, in the order they entered
+#[derive(Component, Debug, Clone, Default)]
+pub struct Garrison {
+    pub capacity: u32,
+    pub occupants: Vec<Entity>,
+}
+
+/// Unit sheltering inside a building; it has no Transform until it is ejected
+#[derive(Component, Debug)]
+pub struct Garrisoned {
+    pub building: Entity,
+}
+
+/// Worker with nothing to do, counted by the idle worker button
+#[derive(Component, Debug)]
+pub struct IdleWorker;
+
+/// Resources a worker has harvested and not yet dropped off at a depot
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CarriedResources {
+    pub resource_type: ResourceType,
+    pub amount: f32,
+}
+
+/// Experience a unit has earned from kills and the veterancy rank it has been promoted to
+#[derive(Component, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Experience {
+    pub points: f32,
+    pub rank: u8, // Index into veterancy::RANKS
+}
+
+/// Energy and ability cooldowns of a unit that has abilities
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Caster {
+    pub energy: f32,
+    pub max_energy: f32,
+    pub cooldowns: BTreeMap<u8, f32>, // Ability ID -> seconds until it can be cast again
+}
+
+/// What an ordered ability is cast at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CastTarget {
+    Point(Vec2),
+    Entity(Entity),
+    Caster, // Self-cast and untargeted abilities
+}
+
+/// Ability a unit is moving into range to cast
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CastOrder {
+    pub ability_id: u8,
+    pub target: CastTarget,
+}
+
+/// Unit stat an ability buff changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuffStat {
+    MovementSpeed,
+    AttackDamage,
+}
+
+/// A temporary stat multiplier from an ability
+#[derive(Debug, Clone, Copy)]
+pub struct Buff {
+    pub stat: BuffStat,
+    pub multiplier: f32,
+    pub remaining: f32, // Seconds
+}
+
+/// Buffs currently on a unit
+#[derive(Component, Debug, Clone, Default)]
+pub struct ActiveBuffs {
+    pub buffs: Vec<Buff>,
+}
+
+impl ActiveBuffs {
+    /// Combined multiplier of every buff on the given stat
+    pub fn multiplier(&self, stat: BuffStat) -> f32 {
+        self.buffs.iter().filter(|buff| buff.stat == stat).map(|buff| buff.multiplier).product()
+    }
+}
+
+/// Unit called in by an ability, removed when its time runs out
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Summoned {
+    pub remaining: f32, // Seconds
 }
 
 /// Build target component
@@ -123,6 +336,45 @@ pub struct BuildTarget {
     pub building_type: BuildingType,
 }
 
+/// Attack-move order: walk to the destination, fighting anything hostile on the way
+#[derive(Component, Debug)]
+pub struct AttackMove {
+    pub destination: Vec2,
+}
+
+/// Patrol order: loop between two points, engaging enemies encountered
+#[derive(Component, Debug)]
+pub struct Patrol {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub heading_to_end: bool,
+}
+
+/// Hold-position order: stay put and only fire at enemies already in range
+#[derive(Component, Debug)]
+pub struct HoldPosition;
+
+/// How a unit reacts to enemies when it has no explicit order to fight
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stance {
+    Aggressive, // Engage anything in sight and chase it
+    Defensive,  // Fire at enemies in range, never chase
+    Passive,    // Never fire unless ordered to
+}
+
+impl Default for Stance {
+    fn default() -> Self {
+        Stance::Aggressive
+    }
+}
+
+/// Speed cap that lets a unit in a group order arrive at its formation slot together with the others
+#[derive(Component, Debug)]
+pub struct FormationMove {
+    pub destination: Vec2,
+    pub speed: f32,
+}
+
 /// Minimap marker for entities
 #[derive(Component, Debug)]
 pub struct MinimapMarker {
@@ -161,16 +413,52 @@ pub struct OrderTimer {
     pub timer: f32,
 }
 
-/// Animation component
-#[derive(Component)]
+/// What a unit is doing, which picks the clip its sprite sheet plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AnimationState {
+    Idle,
+    Walk,
+    Attack, // Also healing, for healers
+    Death,
+}
+
+impl Default for AnimationState {
+    fn default() -> Self {
+        AnimationState::Idle
+    }
+}
+
+/// Clip a unit's sprite is playing and how far into it
+#[derive(Component, Debug, Clone, Copy, Default)]
 pub struct Animation {
-    pub current_frame: usize,
-    pub frames: Vec<usize>,
-    pub frame_time: f32,
-    pub timer: f32,
-    pub is_looping: bool,
+    pub state: AnimationState,
+    pub elapsed: f32, // Seconds since the clip started
+}
+
+/// Neutral unit guarding a spot on the map; it gives up chasing anything that lures it too far away
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Creep {
+    pub post: Vec2,
+}
+
+/// Structure that changes hands when one player's units are the only ones around it for long enough
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Capturable {
+    pub claimant: Option<u8>, // Player whose units are taking it
+    pub progress: f32,        // Seconds the claimant has held it
+}
+
+/// Dead unit left on the map while it plays its death clip
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Corpse {
+    pub unit_type: UnitType,
 }
 
+/// Marks a gate building: the pathfinding grid keeps enemies out of it, so it neither collides with
+/// nor steers aside the allied units walking through
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Gate;
+
 /// Construction site marker
 #[derive(Component)]
 pub struct ConstructionSite {
@@ -186,6 +474,12 @@ pub struct ResearchStatus {
     pub total_time: f32,
 }
 
+/// Technologies a research building will work through, front first
+#[derive(Component, Debug, Default)]
+pub struct ResearchQueue {
+    pub queue: VecDeque<crate::ecs::resources::TechType>,
+}
+
 /// Component for fog of war visibility
 #[derive(Component)]
 pub struct FogOfWarVisible {
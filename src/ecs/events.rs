@@ -0,0 +1,39 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+
+use crate::ecs::components::{BuildingType, UnitType};
+use crate::ecs::systems::combat::DamageType;
+
+/// A hit landing on a unit or building; the damage system applies it to the target's health
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub attacker_owner: Option<u8>,
+    pub target: Entity,
+    pub amount: f32,                     // Before armor
+    pub damage_type: Option<DamageType>, // None for ability damage, which ignores armor
+}
+
+/// What a death event's entity was
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Casualty {
+    Unit(UnitType),
+    Building(BuildingType),
+}
+
+/// A unit or building whose health ran out, raised once per entity before it is despawned
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub casualty: Casualty,
+    pub owner: u8,
+    pub position: Vec2,
+    pub killer: Entity,
+    pub killer_owner: Option<u8>,
+}
+
+/// Add the event queues the simulation systems read and write
+pub fn init_events(world: &mut World) {
+    world.init_resource::<Events<DamageEvent>>();
+    world.init_resource::<Events<DeathEvent>>();
+}
@@ -1,5 +1,8 @@
 pub mod components;
+pub mod events;
+pub mod network_id;
 pub mod resources;
+pub mod spatial;
 pub mod systems;
 pub mod combat {
     pub mod components;
@@ -35,7 +38,7 @@ impl Default for GameTime {
 
 pub fn init_world() -> World {
     let mut world = World::new();
-    world.insert_resource(GameTime::default());
+    world.insert_resource(resources::GameTime::default());
     world
 }
 
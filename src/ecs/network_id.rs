@@ -0,0 +1,101 @@
+use bevy_ecs::prelude::*;
+use std::collections::HashMap;
+
+use crate::ecs::components::{Building, NetworkId, Owner, Resource, Transform, Unit};
+
+/// Which entity each network ID stands for on this machine, and the next ID to hand out
+#[derive(Resource, Debug, Default)]
+pub struct NetworkIds {
+    next_id: u32,
+    entities: HashMap<NetworkId, Entity>,
+    ids: HashMap<Entity, NetworkId>,
+}
+
+impl NetworkIds {
+    pub fn entity(&self, id: NetworkId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+    
+    pub fn id(&self, entity: Entity) -> Option<NetworkId> {
+        self.ids.get(&entity).copied()
+    }
+    
+    /// Hand out the next ID to an entity
+    pub fn allocate(&mut self, entity: Entity) -> NetworkId {
+        let id = NetworkId(self.next_id);
+        self.insert(entity, id);
+        id
+    }
+    
+    /// Record an entity's ID, e.g. one restored from a save; later IDs are handed out after it
+    pub fn insert(&mut self, entity: Entity, id: NetworkId) {
+        self.entities.insert(id, entity);
+        self.ids.insert(entity, id);
+        self.next_id = self.next_id.max(id.0 + 1);
+    }
+    
+    /// Forget every entity and start again from ID 0, for a new game
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Network IDs of the entities that have one; the rest are left out
+pub fn network_ids(world: &World, entities: &[Entity]) -> Vec<NetworkId> {
+    entities.iter().filter_map(|&entity| world.get::<NetworkId>(entity).copied()).collect()
+}
+
+/// Key that sorts entities the same way on every machine, unlike their `Entity`: by network ID, with
+/// entities that have none last
+pub fn network_order(world: &World, entity: Entity) -> u32 {
+    world.get::<NetworkId>(entity).map_or(u32::MAX, |id| id.0)
+}
+
+/// Entities behind network IDs; IDs of despawned entities are left out
+pub fn entities_of(world: &World, ids: &[NetworkId]) -> Vec<Entity> {
+    match world.get_resource::<NetworkIds>() {
+        Some(network_ids) => ids.iter().filter_map(|&id| network_ids.entity(id)).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Give units, buildings and resource nodes spawned since the last call their network IDs, and forget
+/// those despawned. Runs after every tick, when all machines have spawned the same entities. Their
+/// `Entity`s may differ between machines, so new ones are numbered in order of what they are, who owns
+/// them and where they stand, never by `Entity`
+pub fn assign_network_ids(world: &mut World) {
+    let mut network_ids = world.remove_resource::<NetworkIds>().unwrap_or_default();
+    let despawned: Vec<Entity> = network_ids.ids.keys().copied().filter(|&entity| world.get_entity(entity).is_none()).collect();
+    for entity in despawned {
+        if let Some(id) = network_ids.ids.remove(&entity) {
+            network_ids.entities.remove(&id);
+        }
+    }
+    
+    let mut query = world.query_filtered::<
+        (Entity, Option<&Unit>, Option<&Building>, Option<&Resource>, Option<&Owner>, Option<&Transform>),
+        (Without<NetworkId>, Or<(With<Unit>, With<Building>, With<Resource>)>),
+    >();
+    let mut new_entities: Vec<_> = query
+        .iter(world)
+        .map(|(entity, unit, building, resource, owner, transform)| {
+            let kind = match (unit, building, resource) {
+                (Some(unit), _, _) => (0, unit.unit_type as u8),
+                (_, Some(building), _) => (1, building.building_type as u8),
+                (_, _, Some(resource)) => (2, resource.resource_type as u8),
+                _ => (3, 0),
+            };
+            let position = transform.map_or((0, 0), |transform| (transform.position.x.to_bits(), transform.position.y.to_bits()));
+            ((kind, owner.map_or(u8::MAX, |owner| owner.0), position), entity)
+        })
+        .collect();
+    // Entities alike in all of these come from one spawn in one tick, e.g. a squad trained together. The
+    // query lists those in the order the tick spawned them on every machine, which a stable sort keeps
+    new_entities.sort_by_key(|(key, _)| *key);
+    
+    for (_, entity) in new_entities {
+        let id = network_ids.allocate(entity);
+        world.entity_mut(entity).insert(id);
+    }
+    world.insert_resource(network_ids);
+}
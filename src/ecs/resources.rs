@@ -3,7 +3,14 @@ use glam::Vec2;
 use std::collections::{HashMap, HashSet};
 use serde::{Serialize, Deserialize};
 
-use crate::ecs::components::ResourceType;
+use crate::ecs::components::{ResourceType, UnitType, BuildingType};
+use crate::engine::renderer::HealthBarMode;
+
+/// Simulation ticks per second
+pub const TICK_RATE: u32 = 20;
+
+/// Fixed simulation timestep in seconds, independent of frame rate
+pub const TICK_DELTA: f32 = 1.0 / TICK_RATE as f32;
 
 /// Game time resource
 #[derive(Resource)]
@@ -11,6 +18,7 @@ pub struct GameTime {
     pub current_tick: u64,
     pub elapsed_time: f32,
     pub delta_time: f32,
+    pub seed: u64, // Game seed every simulation RNG is derived from
 }
 
 impl Default for GameTime {
@@ -18,13 +26,14 @@ impl Default for GameTime {
         Self {
             current_tick: 0,
             elapsed_time: 0.0,
-            delta_time: 1.0 / 60.0, // Default to 60 FPS
+            delta_time: TICK_DELTA,
+            seed: 0,
         }
     }
 }
 
 /// Terrain tile types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TerrainTile {
     Ground,
     Water,
@@ -32,6 +41,30 @@ pub enum TerrainTile {
     Forest,
 }
 
+impl TerrainTile {
+    /// How many times longer crossing this terrain takes than open ground; None where ground units can't go
+    pub fn movement_cost(self) -> Option<f32> {
+        match self {
+            TerrainTile::Ground => Some(1.0),
+            TerrainTile::Forest => Some(2.0),
+            TerrainTile::Water | TerrainTile::Mountain => None,
+        }
+    }
+    
+    /// Whether nothing can be seen past this terrain
+    pub fn blocks_sight(self) -> bool {
+        self == TerrainTile::Mountain
+    }
+    
+    /// Sight range used up looking across one tile of this terrain, in tiles
+    pub fn sight_cost(self) -> f32 {
+        match self {
+            TerrainTile::Forest => 2.0,
+            _ => 1.0,
+        }
+    }
+}
+
 /// Game map resource
 #[derive(Resource)]
 pub struct GameMap {
@@ -42,6 +75,19 @@ pub struct GameMap {
     pub starting_positions: Vec<Vec2>,
     pub pathfinding_grid: Option<PathfindingGrid>,
     pub fog_of_war: HashMap<u8, HashSet<u32>>, // Player ID -> Set of visible tile indices
+    pub occupied_tiles: HashSet<u32>, // Tile indices under building footprints, blocked for pathing and placement
+    pub gate_tiles: HashMap<u32, Vec<u8>>, // Occupied tile index -> players whose units may walk through the gate on it
+    pub gate_grids: HashMap<u8, PathfindingGrid>, // Player ID -> pathfinding grid with the gates they may use open
+    pub neutral_sites: Vec<NeutralSite>, // Creep camps and capturable structures spawned when a game starts
+}
+
+/// Something neutral the map places for players to fight over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NeutralSite {
+    /// Hostile creeps standing guard, usually over a resource
+    Camp { position: Vec2, unit_type: UnitType, count: u32 },
+    /// Structure any player can claim by keeping their units alone around it
+    Structure { position: Vec2, building_type: BuildingType },
 }
 
 impl Default for GameMap {
@@ -54,7 +100,31 @@ impl Default for GameMap {
             starting_positions: Vec::new(),
             pathfinding_grid: None,
             fog_of_war: HashMap::new(),
+            occupied_tiles: HashSet::new(),
+            gate_tiles: HashMap::new(),
+            gate_grids: HashMap::new(),
+            neutral_sites: Vec::new(),
+        }
+    }
+}
+
+impl GameMap {
+    /// Index of the tile at the given coordinates, None off the map
+    pub fn tile_index(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
         }
+        Some(y as u32 * self.width + x as u32)
+    }
+    
+    /// Whether a building stands on the tile at the given index
+    pub fn is_tile_occupied(&self, index: u32) -> bool {
+        self.occupied_tiles.contains(&index)
+    }
+    
+    /// Pathfinding grid a player's units move on: the shared grid, with their own and allied gates open
+    pub fn grid_for(&self, player_id: u8) -> Option<&PathfindingGrid> {
+        self.gate_grids.get(&player_id).or(self.pathfinding_grid.as_ref())
     }
 }
 
@@ -73,11 +143,18 @@ pub struct PathfindingGrid {
     pub nodes: Vec<PathNode>,
 }
 
+/// Direction toward a goal from every grid cell that can reach it, shared by a large group heading there
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    pub directions: HashMap<(i32, i32), Vec2>, // Grid cell -> way to go; none at the goal itself
+}
+
 /// Player resources
 #[derive(Resource)]
 pub struct PlayerResources {
     pub resources: HashMap<(u8, ResourceType), f32>, // (Player ID, Resource Type) -> Amount
-    pub income_rate: HashMap<(u8, ResourceType), f32>, // (Player ID, Resource Type) -> Income per second
+    pub income_rate: HashMap<(u8, ResourceType), f32>, // (Player ID, Resource Type) -> Income per second, averaged over recent drop-offs
+    pub deposited: HashMap<(u8, ResourceType), f32>, // Dropped off at depots since the economy system last ran
 }
 
 impl Default for PlayerResources {
@@ -91,6 +168,7 @@ impl Default for PlayerResources {
         Self {
             resources,
             income_rate: HashMap::new(),
+            deposited: HashMap::new(),
         }
     }
 }
@@ -107,8 +185,35 @@ pub enum TechType {
     ImprovedSpeed,
 }
 
+impl TechType {
+    /// Every tech type, in declaration order
+    pub const ALL: [TechType; 7] = [
+        TechType::ImprovedHarvesting,
+        TechType::ImprovedWeapons,
+        TechType::ImprovedArmor,
+        TechType::AdvancedUnits,
+        TechType::AdvancedBuildings,
+        TechType::ImprovedHealing,
+        TechType::ImprovedSpeed,
+    ];
+    
+    /// Tech type from its wire encoding (the enum discriminant)
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TechType::ImprovedHarvesting),
+            1 => Some(TechType::ImprovedWeapons),
+            2 => Some(TechType::ImprovedArmor),
+            3 => Some(TechType::AdvancedUnits),
+            4 => Some(TechType::AdvancedBuildings),
+            5 => Some(TechType::ImprovedHealing),
+            6 => Some(TechType::ImprovedSpeed),
+            _ => None,
+        }
+    }
+}
+
 /// Technology research state
-#[derive(Resource)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct TechState {
     pub researched: HashMap<(u8, TechType), bool>, // (Player ID, Tech Type) -> Is Researched
     pub in_progress: HashMap<(u8, TechType), f32>, // (Player ID, Tech Type) -> Progress (0.0 to 1.0)
@@ -131,6 +236,8 @@ pub struct GameSettings {
     pub auto_save_enabled: bool,
     pub auto_save_interval: f32,
     pub show_fps: bool,
+    pub health_bars: HealthBarMode,
+    pub shared_vision: bool, // Allies see everything their teammates see
 }
 
 impl Default for GameSettings {
@@ -141,19 +248,55 @@ impl Default for GameSettings {
             auto_save_enabled: false,
             auto_save_interval: 300.0, // 5 minutes
             show_fps: false,
+            health_bars: HealthBarMode::default(),
+            shared_vision: false,
         }
     }
 }
 
+/// Owner of creeps and unclaimed structures; hostile to every player and never in a lobby slot
+pub const NEUTRAL_PLAYER: u8 = 255;
+
 /// Player info resource
 #[derive(Resource)]
 pub struct PlayerInfo {
     pub player_names: HashMap<u8, String>,
     pub player_colors: HashMap<u8, [u8; 4]>,
     pub ai_players: HashSet<u8>,
+    pub teams: HashMap<u8, u8>, // Player ID -> team; players without an entry are on their own
+    pub resource_bonuses: HashMap<u8, f32>, // Player ID -> extra share of every drop-off, for handicapped computer players
     pub local_player_id: u8,
 }
 
+impl PlayerInfo {
+    /// Team a player is on; players not assigned to one form a team by themselves
+    pub fn team_of(&self, player_id: u8) -> u8 {
+        self.teams.get(&player_id).copied().unwrap_or(player_id)
+    }
+    
+    /// Whether two players are the same player or on the same team
+    pub fn are_allies(&self, a: u8, b: u8) -> bool {
+        a == b || self.team_of(a) == self.team_of(b)
+    }
+    
+    /// Extra share of every drop-off a player gets, 0 for most
+    pub fn resource_bonus(&self, player_id: u8) -> f32 {
+        self.resource_bonuses.get(&player_id).copied().unwrap_or(0.0)
+    }
+    
+    /// Players on the given player's team, including the player themself
+    pub fn allies_of(&self, player_id: u8) -> Vec<u8> {
+        let mut allies: Vec<u8> = self.teams
+            .keys()
+            .copied()
+            .filter(|&other| other != player_id && self.are_allies(player_id, other))
+            .collect();
+        allies.push(player_id);
+        allies.sort();
+        allies
+    }
+}
+
 impl Default for PlayerInfo {
     fn default() -> Self {
         let mut player_names = HashMap::new();
@@ -169,15 +312,52 @@ impl Default for PlayerInfo {
             player_names,
             player_colors,
             ai_players: HashSet::new(),
+            teams: HashMap::new(),
+            resource_bonuses: HashMap::new(),
             local_player_id: 0,
         }
     }
 }
 
+/// Building placement preview that follows the cursor
+#[derive(Resource)]
+pub struct PlacementGhost {
+    pub building_type: crate::ecs::components::BuildingType,
+    pub position: Vec2,
+    pub valid: bool,
+    pub segments: Vec<(Vec2, bool)>, // Wall row being dragged out: each segment's position and whether it can be built
+}
+
+/// Drag-select rectangle and the entity under the cursor, highlighted by the renderer
+#[derive(Resource, Default)]
+pub struct SelectionOverlay {
+    pub drag_rect: Option<(Vec2, Vec2)>, // World-space corners of the rectangle being dragged
+    pub hovered: Option<Entity>,
+}
+
+/// Type shared by every entity in a selection subgroup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelectionKind {
+    Unit(UnitType),
+    Building(BuildingType),
+}
+
+impl SelectionKind {
+    /// Units come before buildings, each in declaration order
+    fn sort_key(self) -> (u8, u8) {
+        match self {
+            SelectionKind::Unit(unit_type) => (0, unit_type as u8),
+            SelectionKind::Building(building_type) => (1, building_type as u8),
+        }
+    }
+}
+
 /// Selection state resource
 #[derive(Resource)]
 pub struct SelectionState {
     pub selected_entities: Vec<Entity>,
+    pub subgroups: Vec<(SelectionKind, Vec<Entity>)>,
+    pub active_subgroup: usize,
     pub selection_start: Option<Vec2>,
     pub selection_end: Option<Vec2>,
     pub drag_selecting: bool,
@@ -187,6 +367,8 @@ impl Default for SelectionState {
     fn default() -> Self {
         Self {
             selected_entities: Vec::new(),
+            subgroups: Vec::new(),
+            active_subgroup: 0,
             selection_start: None,
             selection_end: None,
             drag_selecting: false,
@@ -194,10 +376,52 @@ impl Default for SelectionState {
     }
 }
 
+impl SelectionState {
+    /// Replace the selection, grouping it by type; the active type stays active while it is still selected
+    pub fn set_selection(&mut self, mut entities: Vec<(Entity, SelectionKind)>) {
+        entities.sort_by_key(|(entity, kind)| (kind.sort_key(), *entity));
+        let active_kind = self.active_kind();
+
+        self.selected_entities = entities.iter().map(|(entity, _)| *entity).collect();
+        self.selected_entities.sort();
+        self.subgroups.clear();
+        for (entity, kind) in entities {
+            match self.subgroups.last_mut() {
+                Some((last_kind, members)) if *last_kind == kind => members.push(entity),
+                _ => self.subgroups.push((kind, vec![entity])),
+            }
+        }
+
+        self.active_subgroup = active_kind
+            .and_then(|kind| self.subgroups.iter().position(|(group_kind, _)| *group_kind == kind))
+            .unwrap_or(0);
+    }
+
+    /// Make the next subgroup active, wrapping back to the first
+    pub fn cycle_subgroup(&mut self) {
+        if !self.subgroups.is_empty() {
+            self.active_subgroup = (self.active_subgroup + 1) % self.subgroups.len();
+        }
+    }
+
+    /// Type of the subgroup whose commands are shown
+    pub fn active_kind(&self) -> Option<SelectionKind> {
+        self.subgroups.get(self.active_subgroup).map(|(kind, _)| *kind)
+    }
+
+    /// Entities in the active subgroup
+    pub fn active_entities(&self) -> &[Entity] {
+        self.subgroups
+            .get(self.active_subgroup)
+            .map(|(_, members)| members.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
 /// Control groups resource
 #[derive(Resource)]
 pub struct ControlGroups {
-    pub groups: HashMap<u8, Vec<Entity>>, // Group ID -> Entities
+    pub groups: HashMap<(u8, u8), Vec<Entity>>, // (Player ID, Group ID) -> Entities
 }
 
 impl Default for ControlGroups {
@@ -208,6 +432,148 @@ impl Default for ControlGroups {
     }
 }
 
+/// Formation each player's group orders use
+#[derive(Resource)]
+pub struct PlayerFormations {
+    pub formations: HashMap<u8, crate::game::formation::Formation>, // Player ID -> Formation
+}
+
+impl Default for PlayerFormations {
+    fn default() -> Self {
+        Self {
+            formations: HashMap::new(),
+        }
+    }
+}
+
+/// Areas scenarios have lifted the fog from, seen by their player for the rest of the game
+#[derive(Resource)]
+pub struct RevealedAreas {
+    pub areas: Vec<(u8, Vec2, f32)>, // (Player ID, Center, Radius)
+}
+
+impl Default for RevealedAreas {
+    fn default() -> Self {
+        Self {
+            areas: Vec::new(),
+        }
+    }
+}
+
+/// Warnings raised while applying a player's commands, waiting to be shown on their HUD
+#[derive(Resource)]
+pub struct PlayerAlerts {
+    pub alerts: Vec<(u8, String)>, // (Player ID, Message)
+}
+
+impl Default for PlayerAlerts {
+    fn default() -> Self {
+        Self {
+            alerts: Vec::new(),
+        }
+    }
+}
+
+/// Sound triggered by the simulation
+#[derive(Debug, Clone, Copy)]
+pub struct AudioEvent {
+    pub sound: crate::engine::audio::GameSoundType,
+    pub position: Vec2,
+    pub player: Option<u8>, // Only this player hears it; None for everyone
+}
+
+/// Sounds raised this tick, waiting to be played by the engine
+#[derive(Resource)]
+pub struct AudioEvents {
+    pub events: Vec<AudioEvent>,
+}
+
+impl Default for AudioEvents {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+        }
+    }
+}
+
+impl AudioEvents {
+    /// Queue a sound every player can hear
+    pub fn play(&mut self, sound: crate::engine::audio::GameSoundType, position: Vec2) {
+        self.events.push(AudioEvent { sound, position, player: None });
+    }
+    
+    /// Queue a sound only `player` hears, such as their own units' acknowledgements
+    pub fn play_for(&mut self, player: u8, sound: crate::engine::audio::GameSoundType, position: Vec2) {
+        self.events.push(AudioEvent { sound, position, player: Some(player) });
+    }
+}
+
+/// What a floating combat text shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatTextKind {
+    Damage,
+    Critical, // Damage the target's armor is weak against
+    Heal,
+    Miss,
+}
+
+/// Number or word floating up from where a hit or heal landed
+#[derive(Debug, Clone, Copy)]
+pub struct CombatText {
+    pub kind: CombatTextKind,
+    pub target: Entity, // Heals on the same target merge into one floater
+    pub position: Vec2,
+    pub amount: f32, // 0 for misses
+}
+
+/// Combat text raised this tick, waiting to be shown by the engine
+#[derive(Resource)]
+pub struct CombatTextEvents {
+    pub texts: Vec<CombatText>,
+}
+
+impl Default for CombatTextEvents {
+    fn default() -> Self {
+        Self {
+            texts: Vec::new(),
+        }
+    }
+}
+
+impl CombatTextEvents {
+    pub fn push(&mut self, kind: CombatTextKind, target: Entity, position: Vec2, amount: f32) {
+        self.texts.push(CombatText { kind, target, position, amount });
+    }
+}
+
+/// Hits near one another while an alert is waiting raise no further alert
+pub const ATTACK_ALERT_MERGE_DISTANCE: f32 = 96.0;
+
+/// Where a player's units or buildings came under attack
+#[derive(Debug, Clone, Copy)]
+pub struct AttackAlert {
+    pub player_id: u8, // Whose units or buildings were hit
+    pub position: Vec2,
+}
+
+/// Attacks raised this tick, waiting to be pinged on the minimap by the engine
+#[derive(Resource, Default)]
+pub struct AttackAlerts {
+    pub alerts: Vec<AttackAlert>,
+}
+
+impl AttackAlerts {
+    /// Alert a player to an attack, unless one is already waiting for a hit nearby
+    pub fn raise(&mut self, player_id: u8, position: Vec2) {
+        let nearby = self.alerts.iter().any(|alert| {
+            alert.player_id == player_id && alert.position.distance(position) < ATTACK_ALERT_MERGE_DISTANCE
+        });
+        if !nearby {
+            self.alerts.push(AttackAlert { player_id, position });
+        }
+    }
+}
+
 /// Input action queue
 #[derive(Resource)]
 pub struct InputActionQueue {
@@ -0,0 +1,105 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use std::collections::HashMap;
+
+/// Default edge length of a spatial grid cell in world units
+pub const DEFAULT_CELL_SIZE: f32 = 32.0;
+
+/// Entry stored in a spatial grid cell
+#[derive(Debug, Clone, Copy)]
+pub struct SpatialEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// Uniform hash grid of entity positions, rebuilt every tick, for fast proximity queries
+#[derive(Resource)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<SpatialEntry>>,
+    max_radius: f32, // Largest radius inserted, so radius queries can include overlapping colliders
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            max_radius: 0.0,
+        }
+    }
+    
+    /// Remove every entry, keeping cell allocations for the next rebuild
+    pub fn clear(&mut self) {
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+        self.max_radius = 0.0;
+    }
+    
+    /// Add an entity at a position; radius is the entity's collision radius (0 for points)
+    pub fn insert(&mut self, entity: Entity, position: Vec2, radius: f32) {
+        let cell = self.cell_of(position);
+        self.cells.entry(cell).or_default().push(SpatialEntry { entity, position, radius });
+        self.max_radius = self.max_radius.max(radius);
+    }
+    
+    /// Largest radius of any entry in the grid
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+    
+    /// Entries whose position lies within `radius` of `center`
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<SpatialEntry> {
+        let radius_squared = radius * radius;
+        self.query_rect(center - Vec2::splat(radius), center + Vec2::splat(radius))
+            .into_iter()
+            .filter(|entry| (entry.position - center).length_squared() <= radius_squared)
+            .collect()
+    }
+    
+    /// Entries whose position lies inside the axis-aligned rectangle
+    pub fn query_rect(&self, min: Vec2, max: Vec2) -> Vec<SpatialEntry> {
+        let (min_x, min_y) = self.cell_of(min);
+        let (max_x, max_y) = self.cell_of(max);
+        
+        let mut results = Vec::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if let Some(entries) = self.cells.get(&(x, y)) {
+                    results.extend(entries.iter().filter(|entry| {
+                        entry.position.x >= min.x && entry.position.x <= max.x &&
+                        entry.position.y >= min.y && entry.position.y <= max.y
+                    }));
+                }
+            }
+        }
+        results
+    }
+    
+    /// Closest entry to a point within `radius`, if any
+    pub fn nearest(&self, center: Vec2, radius: f32, mut filter: impl FnMut(&SpatialEntry) -> bool) -> Option<SpatialEntry> {
+        self.query_radius(center, radius)
+            .into_iter()
+            .filter(|entry| filter(entry))
+            .min_by(|a, b| {
+                let distance_a = (a.position - center).length_squared();
+                let distance_b = (b.position - center).length_squared();
+                distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+    
+    fn cell_of(&self, position: Vec2) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
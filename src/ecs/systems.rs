@@ -1,19 +1,120 @@
 use bevy_ecs::prelude::*;
+use bevy_tasks::{ComputeTaskPool, TaskPool};
 use glam::Vec2;
+use std::collections::{HashMap, HashSet};
+use tracing::info;
 
 use crate::ecs::components::*;
 use crate::ecs::resources::*;
+use crate::ecs::spatial::SpatialGrid;
+use crate::engine::audio::GameSoundType;
+use crate::game::buildings::{self, BuildingData};
+use crate::game::commands::set_path;
+use crate::game::data;
+use crate::game::environment::{self, DayNightCycle};
+use crate::game::path_queue::FLOW_FIELD_ARRIVAL_DISTANCE;
+use crate::game::pathfinding;
+use crate::game::stats::GameStats;
+use crate::game::tech::{self, TechData};
 
-/// System to update entity positions based on movement components
+pub mod combat;
+
+/// Distance beyond touching at which moving units start steering away from their neighbours
+const AVOIDANCE_MARGIN: f32 = 4.0;
+
+/// How hard neighbours push a moving unit aside, relative to its speed
+const SEPARATION_WEIGHT: f32 = 0.6;
+
+/// Buildings and other static entities push harder since they never make way
+const STATIC_SEPARATION_WEIGHT: f32 = 1.5;
+
+/// Fraction of its speed an idle unit uses to shuffle out of a unit it overlaps
+const IDLE_SEPARATION_SPEED: f32 = 0.3;
+
+/// Share of an overlap the collision system corrects each tick
+const COLLISION_PUSH_FACTOR: f32 = 0.5;
+
+/// System to update entity positions based on movement components, steering around
+/// neighbouring units and buildings while following the path
 pub fn update_movement_system(
-    mut query: Query<(&mut Transform, &mut Movement)>,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Movement,
+        Option<&Unit>,
+        Option<&Owner>,
+        Option<&Collider>,
+        Option<&FormationMove>,
+        Option<&ActiveBuffs>,
+        Option<&AwaitingPath>,
+        Option<&FlowFieldMove>,
+    )>,
+    static_entities: Query<(), Without<Movement>>,
+    gates: Query<(), With<Gate>>,
+    spatial_grid: Res<SpatialGrid>,
+    game_map: Res<GameMap>,
     time: Res<GameTime>,
 ) {
-    for (mut transform, mut movement) in query.iter_mut() {
-        // Skip if no path or at destination
+    // Each unit only changes itself and reads its neighbours from the spatial grid, so units move, and
+    // re-path around new obstacles, on worker threads with the same result as one after another
+    query.par_iter_mut().for_each_mut(|item| {
+        let (entity, mut transform, mut movement, unit, owner, collider, formation_move, buffs, awaiting_path, flow_field_move) = item;
+        let grid = match owner {
+            Some(owner) => game_map.grid_for(owner.0),
+            None => game_map.pathfinding_grid.as_ref(),
+        };
+        let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+        let speed_multiplier = buffs.map_or(1.0, |buffs| buffs.multiplier(BuffStat::MovementSpeed));
+        let base_speed = unit.map(|u| u.movement_speed).unwrap_or(100.0) * speed_multiplier; // Units per second
+        
+        // Idle units only shuffle apart when they overlap, so they don't end up stacked
         if movement.path.is_empty() || movement.path_index >= movement.path.len() {
-            movement.velocity = Vec2::ZERO;
-            continue;
+            let (push, _) = separation(entity, transform.position, unit_radius, 0.0, &spatial_grid, &static_entities, &gates);
+            movement.velocity = push.clamp_length_max(1.0) * base_speed * IDLE_SEPARATION_SPEED;
+            if movement.velocity != Vec2::ZERO {
+                let next_position = transform.position + movement.velocity * time.delta_time;
+                transform.position = slide_on_grid(transform.position, next_position, grid);
+            }
+            return;
+        }
+        
+        // Units of a large group order steer by the group's flow field while they're still far from their destination
+        let flow_field = flow_field_move
+            .filter(|flow_field_move| movement.target == Some(flow_field_move.destination))
+            .filter(|flow_field_move| transform.position.distance(flow_field_move.destination) > FLOW_FIELD_ARRIVAL_DISTANCE)
+            .map(|flow_field_move| &flow_field_move.field);
+        
+        // Re-path if the way to the next waypoint has become blocked (e.g. a building was placed on it).
+        // Units heading straight for a destination while the path queue finds their route wait for it,
+        // and ones on a flow field are already steered around obstacles
+        if let (Some(grid), Some(destination), None, None) = (grid, movement.target, awaiting_path, flow_field) {
+            let waypoint = movement.path[movement.path_index];
+            let standing_on_walkable = pathfinding::is_position_walkable(
+                transform.position, grid, pathfinding::PATH_GRID_SIZE);
+            let waypoint_blocked = !pathfinding::is_position_walkable(waypoint, grid, pathfinding::PATH_GRID_SIZE)
+                || (standing_on_walkable && !pathfinding::has_line_of_sight(
+                    transform.position, waypoint, grid, pathfinding::PATH_GRID_SIZE, unit_radius));
+            
+            if waypoint_blocked {
+                match pathfinding::request_path(transform.position, destination, grid, unit_radius) {
+                    Some(path) => {
+                        movement.path = path;
+                        movement.path_index = 0;
+                    }
+                    None => {
+                        // No route left - give up on the order
+                        movement.path.clear();
+                        movement.path_index = 0;
+                        movement.target = None;
+                        movement.velocity = Vec2::ZERO;
+                        return;
+                    }
+                }
+                
+                if movement.path.is_empty() {
+                    return;
+                }
+            }
         }
         
         // Get current target position from path
@@ -32,41 +133,204 @@ pub fn update_movement_system(
             // If we've reached the end of the path
             if movement.path_index >= movement.path.len() {
                 movement.velocity = Vec2::ZERO;
-                continue;
+                movement.target = None;
+                return;
             }
         }
         
-        // Otherwise, move toward the target
+        // Otherwise, move toward the target, or the way the flow field points from here
         if distance > 0.1 {
-            let direction = to_target.normalize();
-            let speed = 100.0; // Units per second
-            movement.velocity = direction * speed;
+            let cell = pathfinding::world_to_grid(current_pos, pathfinding::PATH_GRID_SIZE);
+            let direction = flow_field
+                .and_then(|field| field.directions.get(&cell).copied())
+                .unwrap_or_else(|| to_target.normalize());
+            let mut speed = base_speed / terrain_movement_cost(transform.position, &game_map);
+            
+            // Keep pace with the rest of the group while heading for the formation slot
+            if let Some(formation_move) = formation_move {
+                if movement.target == Some(formation_move.destination) {
+                    speed = speed.min(formation_move.speed);
+                }
+            }
+            
+            // Steer away from crowding neighbours while following the path
+            let (push, static_normals) = separation(
+                entity, transform.position, unit_radius, AVOIDANCE_MARGIN, &spatial_grid, &static_entities, &gates);
+            let mut velocity = direction * speed + push * speed * SEPARATION_WEIGHT;
+            
+            // Slide along buildings instead of pressing into them
+            for normal in static_normals {
+                let into = -velocity.dot(normal);
+                if into > 0.0 {
+                    velocity += normal * into;
+                }
+            }
+            movement.velocity = velocity.clamp_length_max(speed);
             
             // Update position
-            transform.position += movement.velocity * time.delta_time;
+            let next_position = transform.position + movement.velocity * time.delta_time;
+            transform.position = slide_on_grid(transform.position, next_position, grid);
             
             // Update rotation to face movement direction
-            transform.rotation = direction.y.atan2(direction.x);
+            let heading = movement.velocity.try_normalize().unwrap_or(direction);
+            transform.rotation = heading.y.atan2(heading.x);
+        }
+    });
+}
+
+/// Push away from neighbours within `margin` of touching, weighted by how close they are.
+/// Also returns the directions away from nearby static entities so movement can slide along them
+fn separation(
+    entity: Entity,
+    position: Vec2,
+    radius: f32,
+    margin: f32,
+    spatial_grid: &SpatialGrid,
+    static_entities: &Query<(), Without<Movement>>,
+    gates: &Query<(), With<Gate>>,
+) -> (Vec2, Vec<Vec2>) {
+    let mut push = Vec2::ZERO;
+    let mut static_normals = Vec::new();
+    
+    for neighbor in spatial_grid.query_radius(position, radius + margin + spatial_grid.max_radius()) {
+        // Gates only keep out enemies, which the pathfinding grid already does
+        if neighbor.entity == entity || gates.contains(neighbor.entity) {
+            continue;
         }
+        
+        let range = radius + neighbor.radius + margin;
+        let offset = position - neighbor.position;
+        let distance = offset.length();
+        if distance >= range || range <= 0.0 {
+            continue;
+        }
+        
+        // Units standing on the exact same spot split in opposite directions
+        let normal = if distance > 0.0 {
+            offset / distance
+        } else if entity < neighbor.entity {
+            Vec2::X
+        } else {
+            Vec2::NEG_X
+        };
+        let strength = 1.0 - distance / range;
+        
+        if static_entities.contains(neighbor.entity) {
+            push += normal * strength * STATIC_SEPARATION_WEIGHT;
+            static_normals.push(normal);
+        } else {
+            push += normal * strength;
+        }
+    }
+    
+    (push, static_normals)
+}
+
+/// Move from `from` towards `to`, sliding along blocked tiles rather than entering them
+fn slide_on_grid(from: Vec2, to: Vec2, grid: Option<&PathfindingGrid>) -> Vec2 {
+    let grid = match grid {
+        Some(grid) => grid,
+        None => return to,
+    };
+    let walkable = |position: Vec2| pathfinding::is_position_walkable(position, grid, pathfinding::PATH_GRID_SIZE);
+    
+    // Units already stuck inside a blocked tile may walk out of it
+    if walkable(to) || !walkable(from) {
+        return to;
+    }
+    
+    // Keep whichever axis of the move is still free
+    [Vec2::new(to.x, from.y), Vec2::new(from.x, to.y)]
+        .into_iter()
+        .find(|position| walkable(*position))
+        .unwrap_or(from)
+}
+
+/// Movement cost of the terrain at a position; off the map and impassable tiles count as open ground,
+/// since the pathfinding grid already keeps units out of them
+fn terrain_movement_cost(position: Vec2, game_map: &GameMap) -> f32 {
+    pathfinding::terrain_at(position, game_map)
+        .and_then(|terrain| terrain.movement_cost())
+        .unwrap_or(1.0)
+}
+
+/// System to keep building footprints marked on the map's tiles and the pathfinding grid
+pub fn pathfinding_obstacle_system(
+    mut game_map: ResMut<GameMap>,
+    buildings: Query<(&Transform, &Building, &Owner)>,
+    added_buildings: Query<(), Added<Building>>,
+    mut removed_buildings: RemovedComponents<Building>,
+    player_info: Option<Res<PlayerInfo>>,
+) {
+    // Only rebuild when the set of buildings actually changed
+    let removed_any = removed_buildings.iter().count() > 0;
+    if added_buildings.is_empty() && !removed_any {
+        return;
+    }
+    
+    // Construction sites block their tiles from the moment they're placed until the building is destroyed
+    let footprints: Vec<(BuildingType, Vec2)> = buildings
+        .iter()
+        .map(|(transform, building, _)| (building.building_type, transform.position))
+        .collect();
+    buildings::occupy_footprints(&mut game_map, &footprints);
+    
+    let gates: Vec<(u8, Vec2)> = buildings
+        .iter()
+        .filter(|(_, building, _)| building.building_type == BuildingType::Gate)
+        .map(|(transform, _, owner)| (owner.0, transform.position))
+        .collect();
+    buildings::open_gates(&mut game_map, &gates, player_info.as_deref());
+    
+    pathfinding::rebuild_pathfinding_grid(&mut game_map);
+}
+
+/// System to rebuild the spatial grid from current entity positions
+pub fn spatial_grid_system(
+    query: Query<(Entity, &Transform, Option<&Collider>), Or<(With<Collider>, With<Unit>, With<Building>)>>,
+    mut spatial_grid: ResMut<SpatialGrid>,
+) {
+    spatial_grid.clear();
+    
+    for (entity, transform, collider) in query.iter() {
+        spatial_grid.insert(entity, transform.position, collider.map(|c| c.radius).unwrap_or(0.0));
     }
 }
 
-/// System to handle collision detection and resolution
+/// System to push apart entities that still overlap after steering; static entities never move
 pub fn collision_detection_system(
-    mut query: Query<(Entity, &Transform, &Collider, Option<&mut Movement>)>,
+    mut query: Query<(Entity, &mut Transform, &Collider, Option<&Movement>), Without<Gate>>,
+    spatial_grid: Res<SpatialGrid>,
 ) {
     // Collect all entities with colliders
-    let entities: Vec<(Entity, Transform, Collider, bool)> = query
+    let entities: Vec<(Entity, Vec2, Collider, bool)> = query
         .iter()
         .map(|(entity, transform, collider, movement)| 
-            (entity, *transform, collider.clone(), movement.is_some()))
+            (entity, transform.position, collider.clone(), movement.is_some()))
+        .collect();
+    let colliders: HashMap<Entity, (Collider, bool)> = entities
+        .iter()
+        .map(|(entity, _, collider, has_movement)| (*entity, (collider.clone(), *has_movement)))
         .collect();
     
-    // Check for collisions between all pairs
-    for i in 0..entities.len() {
-        for j in (i+1)..entities.len() {
-            let (entity_a, transform_a, collider_a, has_movement_a) = &entities[i];
-            let (entity_b, transform_b, collider_b, has_movement_b) = &entities[j];
+    // Corrections are applied afterwards, in a fixed order, so every machine ends up in the same place
+    let mut corrections: Vec<(Entity, Vec2)> = Vec::new();
+    
+    // Check each entity only against neighbors close enough to touch it
+    for (entity_a, position_a, collider_a, has_movement_a) in &entities {
+        let search_radius = collider_a.radius + spatial_grid.max_radius();
+        
+        for neighbor in spatial_grid.query_radius(*position_a, search_radius) {
+            let entity_b = neighbor.entity;
+            
+            // Handle each pair once, from its lower entity
+            if entity_b <= *entity_a {
+                continue;
+            }
+            let (collider_b, has_movement_b) = match colliders.get(&entity_b) {
+                Some(entry) => entry,
+                None => continue,
+            };
             
             // Skip if entities are not set to collide with each other
             if (collider_a.collision_layer & collider_b.collision_mask == 0) &&
@@ -75,45 +339,90 @@ pub fn collision_detection_system(
             }
             
             // Calculate distance between entities
-            let distance = (transform_a.position - transform_b.position).length();
+            let offset = *position_a - neighbor.position;
+            let distance = offset.length();
             let min_distance = collider_a.radius + collider_b.radius;
             
             // Check for collision
-            if distance < min_distance {
-                // Handle collision for entities with movement components
-                if *has_movement_a || *has_movement_b {
-                    // Get the entities again but with mutable references
-                    if let Ok([(_, _, _, Some(mut movement_a)), (_, _, _, Some(mut movement_b))]) = 
-                        query.get_many_mut([*entity_a, *entity_b]) {
-                        
-                        // Simple collision resolution - stop movement
-                        if *has_movement_a {
-                            movement_a.velocity = Vec2::ZERO;
-                        }
-                        
-                        if *has_movement_b {
-                            movement_b.velocity = Vec2::ZERO;
-                        }
+            if distance < min_distance && (*has_movement_a || *has_movement_b) {
+                let normal = if distance > 0.0 { offset / distance } else { Vec2::X };
+                let push = normal * (min_distance - distance) * COLLISION_PUSH_FACTOR;
+                
+                // Two movers share the correction; against a static entity the mover takes all of it
+                match (*has_movement_a, *has_movement_b) {
+                    (true, true) => {
+                        corrections.push((*entity_a, push * 0.5));
+                        corrections.push((entity_b, -push * 0.5));
                     }
+                    (true, false) => corrections.push((*entity_a, push)),
+                    _ => corrections.push((entity_b, -push)),
                 }
-                
-                // Additional collision effects could be implemented here
-                // (damage, knockback, etc.)
             }
         }
     }
+    
+    for (entity, correction) in corrections {
+        if let Ok((_, mut transform, _, _)) = query.get_mut(entity) {
+            transform.position += correction;
+        }
+    }
 }
 
 /// System to handle unit behavior
 pub fn unit_behavior_system(
-    mut query: Query<(Entity, &Unit, &Transform, &Owner, Option<&AttackTarget>, Option<&mut Movement>)>,
-    transform_query: Query<&Transform>,
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &Unit,
+        &Transform,
+        &Owner,
+        Option<&AttackTarget>,
+        Option<&AttackMove>,
+        Option<&mut Patrol>,
+        Option<&Collider>,
+        Option<&mut Movement>,
+        Option<&HealTarget>,
+        Option<&FormationMove>,
+        Option<&HoldPosition>,
+        Option<&Stance>,
+    )>,
+    // Capturable structures are claimed, not destroyed, so nobody targets them
+    target_query: Query<(Entity, &Transform, &Owner), (Or<(With<Unit>, With<Building>)>, Without<Capturable>)>,
+    spatial_grid: Res<SpatialGrid>,
+    game_map: Res<GameMap>,
     time: Res<GameTime>,
+    player_info: Res<PlayerInfo>,
+    day_night: Option<Res<DayNightCycle>>,
 ) {
-    for (entity, unit, transform, owner, attack_target, movement) in query.iter_mut() {
+    // Units spot enemies to engage only as far as they can see at this time of day
+    let sight_multiplier = environment::sight_multiplier(day_night.as_deref(), time.current_tick);
+    
+    for (entity, unit, transform, owner, attack_target, attack_move, patrol, collider, movement, heal_target, formation_move, hold, stance) in query.iter_mut() {
+        // Healers busy with a patient are driven by the healing system
+        if heal_target.is_some() {
+            continue;
+        }
+        
+        let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+        
+        // Where the unit's standing order wants it to go
+        let order_destination = attack_move.map(|order| order.destination).or_else(|| {
+            patrol.as_ref().map(|patrol| if patrol.heading_to_end { patrol.end } else { patrol.start })
+        });
+        
+        // Orders to fight override the stance; otherwise only aggressive units not holding may chase
+        let stance = stance.copied().unwrap_or_default();
+        let may_chase = order_destination.is_some() || (hold.is_none() && stance == Stance::Aggressive);
+        
+        // Whether the current leg of the order is finished
+        let arrived = movement
+            .as_ref()
+            .map(|movement| movement.path_index >= movement.path.len())
+            .unwrap_or(true);
+        
         // Handle attack behavior if unit has a target
         if let Some(attack_target) = attack_target {
-            if let Ok(target_transform) = transform_query.get(attack_target.target_entity) {
+            if let Ok((_, target_transform, _)) = target_query.get(attack_target.target_entity) {
                 // Check if target is in range
                 let distance = (transform.position - target_transform.position).length();
                 
@@ -125,6 +434,9 @@ pub fn unit_behavior_system(
                         movement.velocity = Vec2::ZERO;
                         movement.path.clear();
                     }
+                } else if !may_chase {
+                    // Holding or defensive units let targets that leave range go
+                    commands.entity(entity).remove::<AttackTarget>();
                 } else {
                     // Target not in range, move toward it if we can
                     if let Some(mut movement) = movement {
@@ -139,6 +451,206 @@ pub fn unit_behavior_system(
                         }
                     }
                 }
+            } else {
+                // Target is gone - resume the standing order, if any
+                commands.entity(entity).remove::<AttackTarget>();
+                
+                if let (Some(destination), Some(mut movement), Some(grid)) =
+                    (order_destination, movement, game_map.grid_for(owner.0)) {
+                    set_path(&mut movement, transform.position, destination, grid, unit_radius);
+                }
+            }
+            continue;
+        }
+        
+        // Units on attack-move or patrol engage anything hostile they spot, idle and holding units
+        // respond according to their stance (workers stay on their jobs); healers never do
+        let sight_range = unit.sight_range * sight_multiplier;
+        let engage_range = if order_destination.is_some() {
+            Some(sight_range)
+        } else if (arrived || hold.is_some()) && unit.unit_type != UnitType::Worker {
+            match stance {
+                Stance::Aggressive if hold.is_none() => Some(sight_range),
+                Stance::Aggressive | Stance::Defensive => Some(unit.attack_range),
+                Stance::Passive => None,
+            }
+        } else {
+            None
+        };
+        let can_fight = unit.attack_damage > 0.0 && unit.unit_type != UnitType::Healer;
+        if let Some(engage_range) = engage_range.filter(|_| can_fight) {
+            let closest_enemy = spatial_grid.nearest(transform.position, engage_range, |candidate| {
+                matches!(target_query.get(candidate.entity), Ok((_, _, target_owner)) if !player_info.are_allies(target_owner.0, owner.0))
+            });
+            
+            if let Some(enemy) = closest_enemy {
+                commands.entity(entity).insert(AttackTarget { target_entity: enemy.entity });
+                
+                // Fighting breaks formation
+                if formation_move.is_some() {
+                    commands.entity(entity).remove::<FormationMove>();
+                }
+                continue;
+            }
+        }
+        
+        if !arrived {
+            continue;
+        }
+        
+        if formation_move.is_some() {
+            commands.entity(entity).remove::<FormationMove>();
+        }
+        
+        if attack_move.is_some() {
+            // Attack-move complete
+            commands.entity(entity).remove::<AttackMove>();
+        } else if let Some(mut patrol) = patrol {
+            // Turn around and walk the other leg
+            patrol.heading_to_end = !patrol.heading_to_end;
+            let next_point = if patrol.heading_to_end { patrol.end } else { patrol.start };
+            
+            if let (Some(mut movement), Some(grid)) = (movement, game_map.grid_for(owner.0)) {
+                set_path(&mut movement, transform.position, next_point, grid, unit_radius);
+            }
+        }
+    }
+}
+
+/// Health restored per second by a healer with a heal speed of 1.0
+const HEAL_RATE: f32 = 10.0;
+
+/// What a healer decided to do this tick
+enum HealAction {
+    Heal { patient: Entity, amount: f32 },
+    Approach { patient: Entity, position: Vec2 },
+    Release,
+}
+
+/// System that has healers find the most damaged friendly unit in sight, walk into range and heal it
+pub fn healing_system(
+    mut commands: Commands,
+    mut units: Query<(
+        Entity,
+        &mut Unit,
+        &Transform,
+        &Owner,
+        Option<&HealTarget>,
+        Option<&AttackTarget>,
+        Option<&AttackMove>,
+        Option<&Patrol>,
+        Option<&Collider>,
+        Option<&mut Movement>,
+    )>,
+    spatial_grid: Res<SpatialGrid>,
+    game_map: Res<GameMap>,
+    time: Res<GameTime>,
+    player_info: Res<PlayerInfo>,
+    mut combat_text: ResMut<CombatTextEvents>,
+    day_night: Option<Res<DayNightCycle>>,
+) {
+    let sight_multiplier = environment::sight_multiplier(day_night.as_deref(), time.current_tick);
+    
+    // Decide first, then apply, since healers and patients live in the same query
+    let mut actions = Vec::new();
+    for (entity, unit, transform, owner, heal_target, attack_target, attack_move, patrol, _, movement) in units.iter() {
+        if unit.unit_type != UnitType::Healer {
+            continue;
+        }
+        let sight_range = unit.sight_range * sight_multiplier;
+        
+        // Healers never fight
+        if attack_target.is_some() {
+            commands.entity(entity).remove::<AttackTarget>();
+        }
+        
+        // Only look for patients when idle or on attack-move/patrol, not mid-way through a plain move
+        let idle = movement.map(|movement| movement.path_index >= movement.path.len()).unwrap_or(true);
+        if heal_target.is_none() && attack_move.is_none() && patrol.is_none() && !idle {
+            continue;
+        }
+        
+        let needs_healing = |candidate: Entity| -> Option<(f32, Vec2)> {
+            let (_, patient, patient_transform, patient_owner, ..) = units.get(candidate).ok()?;
+            let in_sight = (patient_transform.position - transform.position).length() <= sight_range;
+            if candidate == entity || !player_info.are_allies(patient_owner.0, owner.0) || !in_sight ||
+               patient.health <= 0.0 || patient.health >= patient.max_health {
+                return None;
+            }
+            Some((patient.health / patient.max_health, patient_transform.position))
+        };
+        
+        // Stick with the current patient while it still needs us, otherwise pick the worst-off friend
+        let patient = heal_target
+            .and_then(|heal_target| {
+                needs_healing(heal_target.target_entity).map(|(_, position)| (heal_target.target_entity, position))
+            })
+            .or_else(|| {
+                spatial_grid.query_radius(transform.position, sight_range)
+                    .into_iter()
+                    .filter_map(|candidate| {
+                        needs_healing(candidate.entity).map(|(ratio, position)| (candidate.entity, ratio, position))
+                    })
+                    .min_by(|a, b| {
+                        let distance_a = (a.2 - transform.position).length_squared();
+                        let distance_b = (b.2 - transform.position).length_squared();
+                        a.1.partial_cmp(&b.1)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                            .then(distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal))
+                    })
+                    .map(|(candidate, _, position)| (candidate, position))
+            });
+        
+        let action = match patient {
+            Some((patient, position)) if (position - transform.position).length() <= unit.attack_range => {
+                // Heal speed is stored in attack_speed, heal range in attack_range
+                HealAction::Heal { patient, amount: HEAL_RATE * unit.attack_speed * time.delta_time }
+            }
+            Some((patient, position)) => HealAction::Approach { patient, position },
+            None if heal_target.is_some() => HealAction::Release,
+            None => continue,
+        };
+        actions.push((entity, action));
+    }
+    
+    for (healer, action) in actions {
+        match action {
+            HealAction::Heal { patient, amount } => {
+                if let Ok((_, mut patient_unit, patient_transform, ..)) = units.get_mut(patient) {
+                    let healed = amount.min(patient_unit.max_health - patient_unit.health);
+                    patient_unit.health += healed;
+                    combat_text.push(CombatTextKind::Heal, patient, patient_transform.position, healed);
+                }
+                if let Ok((.., Some(mut movement))) = units.get_mut(healer) {
+                    movement.velocity = Vec2::ZERO;
+                    movement.path.clear();
+                }
+                commands.entity(healer).insert(HealTarget { target_entity: patient, healing: true });
+            }
+            HealAction::Approach { patient, position } => {
+                if let Ok((.., Some(mut movement))) = units.get_mut(healer) {
+                    // Re-path only when the patient has wandered off from where we were heading
+                    if movement.path.is_empty() ||
+                       movement.path.last().map(|end| (*end - position).length_squared() > 100.0).unwrap_or(true) {
+                        movement.path = vec![position];
+                        movement.path_index = 0;
+                    }
+                }
+                commands.entity(healer).insert(HealTarget { target_entity: patient, healing: false });
+            }
+            HealAction::Release => {
+                commands.entity(healer).remove::<HealTarget>();
+                
+                // Go back to the standing order, if any
+                if let Ok((_, _, transform, owner, _, _, attack_move, patrol, collider, Some(mut movement))) = units.get_mut(healer) {
+                    let destination = attack_move.map(|order| order.destination).or_else(|| {
+                        patrol.map(|patrol| if patrol.heading_to_end { patrol.end } else { patrol.start })
+                    });
+                    if let (Some(destination), Some(grid)) = (destination, game_map.grid_for(owner.0)) {
+                        let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+                        set_path(&mut movement, transform.position, destination, grid, unit_radius);
+                    }
+                }
             }
         }
     }
@@ -148,35 +660,33 @@ pub fn unit_behavior_system(
 pub fn building_production_system(
     mut commands: Commands,
     time: Res<GameTime>,
-    mut query: Query<(Entity, &mut Building, &Transform, &Owner)>,
+    mut query: Query<(Entity, &mut Building, &Transform, &Owner, Option<&Upgrading>)>,
     game_state: Option<Res<crate::game::GameState>>,
+    mut stats: ResMut<GameStats>,
 ) {
-    for (entity, mut building, transform, owner) in query.iter_mut() {
-        // Skip buildings that are still under construction
+    for (entity, mut building, transform, owner, upgrading) in query.iter_mut() {
+        // Skip buildings that are still under construction (workers advance them in construction_system)
         if building.construction_progress.is_some() {
-            // Update construction progress
-            let progress = building.construction_progress.as_mut().unwrap();
-            *progress += time.delta_time * 0.1; // Adjust rate as needed
-            
-            if *progress >= 1.0 {
-                // Construction complete
-                building.construction_progress = None;
-            }
-            
-            // Skip production logic if still under construction
+            continue;
+        }
+        
+        // Production is on hold while the building upgrades
+        if upgrading.is_some() {
             continue;
         }
         
         // Process building production queue
-        if let Some(progress) = &mut building.production_progress {
-            // Building is currently producing something
-            *progress += time.delta_time * 0.1; // Adjust rate as needed
+        let train_time = building.production_queue.front().map(|&unit_type| data::game_data().unit(unit_type).train_time);
+        if let (Some(progress), Some(train_time)) = (&mut building.production_progress, train_time) {
+            // Building is currently producing the front of the queue
+            *progress += time.delta_time / train_time;
             
             if *progress >= 1.0 {
                 // Production complete
                 if let Some(unit_type) = building.production_queue.pop_front() {
                     // Spawn the produced unit
                     spawn_unit(&mut commands, unit_type, transform.position, owner.0);
+                    stats.unit_produced(owner.0);
                 }
                 
                 // Check if there's another unit in the queue
@@ -195,89 +705,627 @@ pub fn building_production_system(
     }
 }
 
-/// System to handle resource collection by worker units
+/// System to advance research at research buildings and record finished technologies
+pub fn tech_research_system(
+    time: Res<GameTime>,
+    mut tech_state: ResMut<TechState>,
+    mut query: Query<(&Building, &Owner, &mut ResearchQueue, Option<&Upgrading>)>,
+) {
+    let mut queued = HashSet::new();
+    
+    for (building, owner, mut research_queue, upgrading) in query.iter_mut() {
+        queued.extend(research_queue.queue.iter().map(|&tech_type| (owner.0, tech_type)));
+        
+        // Buildings still under construction or upgrading can't research
+        if building.construction_progress.is_some() || upgrading.is_some() {
+            continue;
+        }
+        let tech_type = match research_queue.queue.front() {
+            Some(&tech_type) => tech_type,
+            None => continue,
+        };
+        
+        let research_time = tech::apply_tech_effect(
+            &tech_state,
+            owner.0,
+            TechData::get(tech_type).research_time,
+            tech::TechEffectType::ResearchTime,
+        );
+        let progress = tech_state.in_progress.entry((owner.0, tech_type)).or_insert(0.0);
+        *progress += time.delta_time / research_time;
+        
+        if *progress >= 1.0 {
+            research_queue.queue.pop_front();
+            queued.remove(&(owner.0, tech_type));
+            tech_state.in_progress.remove(&(owner.0, tech_type));
+            tech_state.researched.insert((owner.0, tech_type), true);
+            info!("Player {} finished researching {:?}", owner.0, tech_type);
+        }
+    }
+    
+    // Research queued at buildings that have since been destroyed is lost
+    tech_state.in_progress.retain(|key, _| queued.contains(key));
+}
+
+/// Resources per second a worker harvests while standing at a node
+const GATHER_RATE: f32 = 2.0;
+
+/// Most a worker can carry back to a depot in one trip
+pub const CARRY_CAPACITY: f32 = 10.0;
+
+/// Extra distance beyond touching a node at which a worker can harvest it
+const HARVEST_RANGE: f32 = 10.0;
+
+/// Extra distance beyond touching a depot at which a worker can drop off its load
+const DROP_OFF_RANGE: f32 = 10.0;
+
+/// Idle workers this close to a node start harvesting it without being told to
+const AUTO_HARVEST_RANGE: f32 = 50.0;
+
+/// How far from itself a worker looks for another node once its own runs out
+const NODE_SEARCH_RADIUS: f32 = 300.0;
+
+/// System that runs worker gather trips: harvest at a node up to the carry capacity,
+/// walk the load to the nearest Headquarters or ResourceCollector, drop it off and head back
 pub fn resource_collection_system(
     mut commands: Commands,
     time: Res<GameTime>,
-    mut query: Query<(Entity, &Unit, &mut Transform, &Owner, Option<&mut Movement>)>,
-    mut resource_query: Query<(Entity, &mut Resource, &Transform)>,
+    mut workers: Query<(
+        Entity,
+        &Unit,
+        &Transform,
+        &Owner,
+        Option<&Collider>,
+        Option<&HarvestTarget>,
+        Option<&CarriedResources>,
+        Option<&mut Movement>,
+    ), Without<BuildTarget>>,
+    mut nodes: Query<(Entity, &mut Resource, &Transform, Option<&Collider>)>,
+    depots: Query<(&Building, &Transform, &Owner, Option<&Collider>)>,
+    game_map: Res<GameMap>,
+    tech_state: Res<TechState>,
+    player_info: Option<Res<PlayerInfo>>,
     mut player_resources: ResMut<PlayerResources>,
+    mut stats: ResMut<GameStats>,
 ) {
-    // For each worker unit
-    for (entity, unit, mut transform, owner, movement) in query.iter_mut() {
-        // Skip non-worker units
+    // Gas can only be harvested from under a finished Resource Collector
+    let extractors: Vec<(u8, Vec2)> = depots
+        .iter()
+        .filter(|(building, ..)| {
+            building.building_type == BuildingType::ResourceCollector &&
+            building.construction_progress.is_none() &&
+            building.health > 0.0
+        })
+        .map(|(_, depot_transform, owner, _)| (owner.0, depot_transform.position))
+        .collect();
+    
+    for (entity, unit, transform, owner, collider, harvest_target, carried, mut movement) in workers.iter_mut() {
         if unit.unit_type != UnitType::Worker {
             continue;
         }
+        let worker_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+        
+        // Idle workers next to a node start working it on their own
+        let (target, returning) = match harvest_target {
+            Some(harvest_target) => (harvest_target.target_entity, harvest_target.returning),
+            None => {
+                let idle = movement.as_ref().map_or(true, |movement| movement.path_index >= movement.path.len());
+                if !idle {
+                    continue;
+                }
+                match nearest_node(&nodes, &extractors, owner.0, transform.position, AUTO_HARVEST_RANGE, None) {
+                    Some(node) => (node, false),
+                    None => continue,
+                }
+            }
+        };
         
-        // Find nearest resource within gathering range
-        let mut nearest_resource = None;
-        let mut nearest_distance = f32::MAX;
+        // Fall back to the closest node of the same kind once ours has run out or lost its extractor
+        let carried_type = carried.map(|carried| carried.resource_type);
+        let node = Some(target)
+            .filter(|&node| {
+                nodes.get(node).map_or(false, |(_, resource, node_transform, _)| {
+                    resource.amount > 0.0 &&
+                    buildings::can_harvest(resource.resource_type, node_transform.position, owner.0, &extractors)
+                })
+            })
+            .or_else(|| nearest_node(&nodes, &extractors, owner.0, transform.position, NODE_SEARCH_RADIUS, carried_type));
+        let node_type = node.and_then(|node| nodes.get(node).ok()).map(|(_, resource, ..)| resource.resource_type);
         
-        for (resource_entity, resource, resource_transform) in resource_query.iter() {
-            let distance = (resource_transform.position - transform.position).length();
+        // Head back with a full load, a load of another kind than the node, or whatever is left once the nodes are gone
+        let carried_amount = carried.map_or(0.0, |carried| carried.amount);
+        let load = carried.filter(|carried| {
+            carried.amount > 0.0 && (returning || carried.amount >= CARRY_CAPACITY || node_type != Some(carried.resource_type))
+        });
+        if let Some(load) = load {
+            let target_entity = node.unwrap_or(target);
             
-            // Check if within gathering range
-            if distance < 50.0 && distance < nearest_distance {
-                nearest_resource = Some((resource_entity, resource.resource_type.clone(), distance));
-                nearest_distance = distance;
+            match nearest_depot(&depots, owner.0, transform.position) {
+                Some((position, radius)) if (position - transform.position).length() <= radius + worker_radius + DROP_OFF_RANGE => {
+                    // Computer players given a resource bonus get more out of every load
+                    let bonus = player_info.as_ref().map_or(0.0, |info| info.resource_bonus(owner.0));
+                    let amount = load.amount * (1.0 + bonus);
+                    let key = (owner.0, load.resource_type);
+                    *player_resources.resources.entry(key).or_insert(0.0) += amount;
+                    *player_resources.deposited.entry(key).or_insert(0.0) += amount;
+                    stats.resources_gathered(owner.0, amount);
+                    
+                    commands.entity(entity).remove::<CarriedResources>();
+                    match node {
+                        // Back to the node next tick
+                        Some(node) => {
+                            commands.entity(entity).insert(HarvestTarget { target_entity: node, returning: false });
+                        }
+                        None => {
+                            commands.entity(entity).remove::<HarvestTarget>();
+                            if let Some(movement) = movement.as_mut() {
+                                stop_movement(movement);
+                            }
+                        }
+                    }
+                }
+                Some((position, _)) => {
+                    if let (Some(movement), Some(grid)) = (movement.as_mut(), game_map.grid_for(owner.0)) {
+                        walk_to(movement, transform.position, position, grid, worker_radius);
+                    }
+                    commands.entity(entity).insert(HarvestTarget { target_entity, returning: true });
+                }
+                // Nowhere to drop off - hold on to the load until a depot is built
+                None => {
+                    if let Some(movement) = movement.as_mut() {
+                        stop_movement(movement);
+                    }
+                    commands.entity(entity).insert(HarvestTarget { target_entity, returning: true });
+                }
             }
+            continue;
         }
         
-        // If a resource is found within range, gather it
-        if let Some((resource_entity, resource_type, distance)) = nearest_resource {
-            // If we're close enough, gather the resource
-            if distance < 10.0 {
-                // Update worker animation/state
-                // ...
-                
-                // Add resources to player
-                let gather_rate = 1.0; // Resources per second
-                let amount = gather_rate * time.delta_time;
+        let node = match node {
+            Some(node) => node,
+            None => {
+                commands.entity(entity).remove::<HarvestTarget>();
+                continue;
+            }
+        };
+        let (_, mut resource, node_transform, node_collider) = match nodes.get_mut(node) {
+            Ok(node) => node,
+            Err(_) => continue,
+        };
+        commands.entity(entity).insert(HarvestTarget { target_entity: node, returning: false });
+        
+        // Walk up to the node
+        let node_radius = node_collider.map(|c| c.radius).unwrap_or(0.0);
+        if (node_transform.position - transform.position).length() > node_radius + worker_radius + HARVEST_RANGE {
+            if let (Some(movement), Some(grid)) = (movement.as_mut(), game_map.grid_for(owner.0)) {
+                walk_to(movement, transform.position, node_transform.position, grid, worker_radius);
+            }
+            continue;
+        }
+        
+        // Harvest into the worker's load; the player only gets it once it reaches a depot
+        if let Some(movement) = movement.as_mut() {
+            stop_movement(movement);
+        }
+        let gather_rate = tech::apply_tech_effect(
+            &tech_state,
+            owner.0,
+            GATHER_RATE,
+            tech::TechEffectType::ResourceGathering,
+        );
+        let amount = (gather_rate * time.delta_time)
+            .min(CARRY_CAPACITY - carried_amount)
+            .min(resource.amount);
+        
+        resource.amount -= amount;
+        if resource.amount <= 0.0 {
+            commands.entity(node).despawn();
+        }
+        commands.entity(entity).insert(CarriedResources {
+            resource_type: resource.resource_type,
+            amount: carried_amount + amount,
+        });
+    }
+}
+
+/// Closest node within `radius` the player can harvest, optionally only of one kind
+fn nearest_node(
+    nodes: &Query<(Entity, &mut Resource, &Transform, Option<&Collider>)>,
+    extractors: &[(u8, Vec2)],
+    player_id: u8,
+    position: Vec2,
+    radius: f32,
+    resource_type: Option<ResourceType>,
+) -> Option<Entity> {
+    nodes
+        .iter()
+        .filter(|(_, resource, node_transform, _)| {
+            resource.amount > 0.0 &&
+            resource_type.map_or(true, |kind| resource.resource_type == kind) &&
+            buildings::can_harvest(resource.resource_type, node_transform.position, player_id, extractors)
+        })
+        .map(|(node, _, node_transform, _)| (node, (node_transform.position - position).length()))
+        .filter(|&(_, distance)| distance <= radius)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)))
+        .map(|(node, _)| node)
+}
+
+/// Position and radius of the player's closest finished Headquarters or ResourceCollector
+fn nearest_depot(
+    depots: &Query<(&Building, &Transform, &Owner, Option<&Collider>)>,
+    player_id: u8,
+    position: Vec2,
+) -> Option<(Vec2, f32)> {
+    depots
+        .iter()
+        .filter(|(building, _, owner, _)| {
+            owner.0 == player_id &&
+            (building.building_type.is_headquarters() || building.building_type == BuildingType::ResourceCollector) &&
+            building.construction_progress.is_none() &&
+            building.health > 0.0
+        })
+        .map(|(_, depot_transform, _, collider)| (depot_transform.position, collider.map(|c| c.radius).unwrap_or(0.0)))
+        .min_by(|a, b| {
+            let distance_a = (a.0 - position).length_squared();
+            let distance_b = (b.0 - position).length_squared();
+            distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Route a unit to a destination unless it's already on its way there
+pub fn walk_to(movement: &mut Movement, start: Vec2, destination: Vec2, grid: &PathfindingGrid, unit_radius: f32) {
+    let heading_there = movement.target.map_or(false, |target| (target - destination).length_squared() <= 1.0);
+    if !heading_there || movement.path_index >= movement.path.len() {
+        set_path(movement, start, destination, grid, unit_radius);
+    }
+}
+
+pub fn stop_movement(movement: &mut Movement) {
+    movement.path.clear();
+    movement.path_index = 0;
+    movement.target = None;
+    movement.velocity = Vec2::ZERO;
+}
+
+/// Extra distance beyond touching a site at which a worker can build it
+const BUILD_RANGE: f32 = 10.0;
+
+/// Share of a full worker's build rate contributed by each additional worker
+const EXTRA_WORKER_EFFICIENCY: f32 = 0.75;
+
+/// How far from a finished wall segment its builders look for the next unfinished one
+const WALL_CHAIN_RANGE: f32 = pathfinding::PATH_GRID_SIZE * 2.0;
+
+/// System to advance construction sites using the workers assigned to them
+pub fn construction_system(
+    mut commands: Commands,
+    mut sites: Query<(Entity, &mut Building, &Transform, &Owner, Option<&Collider>)>,
+    mut workers: Query<(Entity, &Unit, &Transform, &Owner, &BuildTarget, Option<&Collider>, Option<&mut Movement>)>,
+    network_ids: Query<&NetworkId>,
+    game_map: Res<GameMap>,
+    time: Res<GameTime>,
+    mut audio_events: ResMut<AudioEvents>,
+    mut stats: ResMut<GameStats>,
+) {
+    // Track which workers found their site this tick
+    let mut assigned_workers = std::collections::HashSet::new();
+    
+    // Unfinished wall segments, where builders move on to once theirs is done
+    let mut wall_sites: Vec<(Entity, u8, Vec2)> = sites
+        .iter()
+        .filter(|(_, building, ..)| building.building_type == BuildingType::Wall && building.construction_progress.is_some())
+        .map(|(entity, _, transform, owner, _)| (entity, owner.0, transform.position))
+        .collect();
+    wall_sites.sort_by_key(|&(entity, ..)| network_ids.get(entity).ok().copied());
+    
+    for (site_entity, mut building, site_transform, site_owner, site_collider) in sites.iter_mut() {
+        if building.construction_progress.is_none() {
+            continue;
+        }
+        
+        let site_radius = site_collider.map(|c| c.radius).unwrap_or(0.0);
+        let mut builders = Vec::new();
+        
+        for (worker_entity, unit, worker_transform, worker_owner, build_target, worker_collider, movement) in workers.iter_mut() {
+            if unit.unit_type != UnitType::Worker || worker_owner.0 != site_owner.0 {
+                continue;
+            }
+            
+            // Worker must be assigned to this site
+            if build_target.building_type != building.building_type
+                || (build_target.position - site_transform.position).length() > 1.0 {
+                continue;
+            }
+            assigned_workers.insert(worker_entity);
+            
+            // Only workers standing next to the site contribute
+            let worker_radius = worker_collider.map(|c| c.radius).unwrap_or(0.0);
+            let distance = (worker_transform.position - site_transform.position).length();
+            if distance <= site_radius + worker_radius + BUILD_RANGE {
+                builders.push(worker_entity);
                 
-                let key = (owner.0, resource_type);
-                if let Some(current) = player_resources.resources.get_mut(&key) {
-                    *current += amount;
-                } else {
-                    player_resources.resources.insert(key, amount);
+                // Stop walking once we've arrived
+                if let Some(mut movement) = movement {
+                    movement.path.clear();
+                    movement.path_index = 0;
+                    movement.target = None;
+                    movement.velocity = Vec2::ZERO;
                 }
-                
-                // Also update income rate
-                *player_resources.income_rate.entry(key).or_insert(0.0) = gather_rate;
-                
-                // Deplete the resource
-                if let Ok((_, mut resource, _)) = resource_query.get_mut(resource_entity) {
-                    resource.amount -= amount;
-                    
-                    // Remove the resource if depleted
-                    if resource.amount <= 0.0 {
-                        commands.entity(resource_entity).despawn();
+            }
+        }
+        
+        // No workers on site - construction is paused
+        if builders.is_empty() {
+            continue;
+        }
+        
+        // Each additional worker helps less than the previous one
+        let worker_count = builders.len() as i32;
+        let effective_workers = (1.0 - EXTRA_WORKER_EFFICIENCY.powi(worker_count)) / (1.0 - EXTRA_WORKER_EFFICIENCY);
+        
+        let build_time = BuildingData::get(building.building_type).build_time;
+        let step = time.delta_time / build_time * effective_workers;
+        let progress = building.construction_progress.unwrap_or(0.0);
+        let new_progress = (progress + step).min(1.0);
+        
+        // Health grows alongside construction
+        building.health = (building.health + building.max_health * 0.9 * (new_progress - progress)).min(building.max_health);
+        
+        if new_progress >= 1.0 {
+            // Construction complete - release the workers, or send them on to the next segment of a wall
+            building.construction_progress = None;
+            commands.entity(site_entity).remove::<ConstructionSite>();
+            wall_sites.retain(|&(entity, ..)| entity != site_entity);
+            let next_segment = if building.building_type == BuildingType::Wall {
+                wall_sites
+                    .iter()
+                    .filter(|&&(_, owner, position)| {
+                        owner == site_owner.0 && (position - site_transform.position).length() <= WALL_CHAIN_RANGE
+                    })
+                    .min_by(|a, b| {
+                        let distance_a = (a.2 - site_transform.position).length();
+                        let distance_b = (b.2 - site_transform.position).length();
+                        distance_a.total_cmp(&distance_b)
+                    })
+                    .map(|&(_, _, position)| position)
+            } else {
+                None
+            };
+            for worker in builders {
+                match next_segment {
+                    Some(position) => {
+                        commands.entity(worker).insert(BuildTarget { position, building_type: BuildingType::Wall });
+                        if let Ok((_, _, transform, owner, _, collider, Some(mut movement))) = workers.get_mut(worker) {
+                            if let Some(grid) = game_map.grid_for(owner.0) {
+                                let radius = collider.map(|c| c.radius).unwrap_or(0.0);
+                                walk_to(&mut movement, transform.position, position, grid, radius);
+                            }
+                        }
+                    }
+                    None => {
+                        commands.entity(worker).remove::<BuildTarget>();
                     }
-                }
-            } else if let Some(mut movement) = movement {
-                // Move toward the resource if not close enough
-                if movement.path.is_empty() {
-                    // Set path to resource
-                    let resource_transform = resource_query.get(resource_entity).unwrap().2;
-                    movement.path = vec![resource_transform.position];
-                    movement.path_index = 0;
                 }
             }
+            audio_events.play_for(site_owner.0, GameSoundType::ConstructionComplete, site_transform.position);
+            stats.building_constructed(site_owner.0);
+        } else {
+            building.construction_progress = Some(new_progress);
+            commands.entity(site_entity).insert(ConstructionSite {
+                building_type: building.building_type,
+                progress: new_progress,
+            });
+        }
+    }
+    
+    // Workers whose site no longer exists (destroyed or cancelled) go idle
+    for (worker_entity, _, _, _, _, _, _) in workers.iter() {
+        if !assigned_workers.contains(&worker_entity) {
+            commands.entity(worker_entity).remove::<BuildTarget>();
+        }
+    }
+}
+
+/// System to advance building upgrades and turn finished ones into their new type, keeping
+/// position, rally point and queue
+pub fn building_upgrade_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Building, &Transform, &Owner, &mut Upgrading)>,
+    time: Res<GameTime>,
+    mut audio_events: ResMut<AudioEvents>,
+    mut player_alerts: ResMut<PlayerAlerts>,
+) {
+    for (entity, mut building, transform, owner, mut upgrading) in query.iter_mut() {
+        let target_data = BuildingData::get(upgrading.target);
+        upgrading.progress = (upgrading.progress + time.delta_time / target_data.build_time).min(1.0);
+        if upgrading.progress < 1.0 {
+            continue;
         }
+        
+        // Damage taken so far carries over; the extra health comes with the upgrade
+        building.health += target_data.health - building.max_health;
+        building.max_health = target_data.health;
+        building.building_type = upgrading.target;
+        commands.entity(entity).remove::<Upgrading>();
+        
+        audio_events.play_for(owner.0, GameSoundType::ConstructionComplete, transform.position);
+        player_alerts.alerts.push((owner.0, format!("{} upgrade complete", target_data.name)));
     }
 }
 
-/// System to handle economy updates
+/// Extra distance beyond touching a building at which a worker can repair it
+const REPAIR_RANGE: f32 = 10.0;
+
+/// Health a worker restores per second
+const REPAIR_RATE: f32 = 10.0;
+
+/// Share of a building's cost paid to repair it from nothing to full health
+const REPAIR_COST_FACTOR: f32 = 0.25;
+
+/// System that walks workers to the damaged buildings they were ordered to repair and restores their health for a share of the building's cost
+pub fn repair_system(
+    mut commands: Commands,
+    mut buildings: Query<(&mut Building, &Transform, &Owner, Option<&Collider>)>,
+    mut workers: Query<(Entity, &Transform, &Owner, &RepairTarget, Option<&Collider>, Option<&mut Movement>)>,
+    network_ids: Query<&NetworkId>,
+    game_map: Res<GameMap>,
+    time: Res<GameTime>,
+    mut player_resources: ResMut<PlayerResources>,
+    mut stats: ResMut<GameStats>,
+) {
+    // Sorted so that workers draining a bank run dry in the same order on every machine
+    let mut repairers: Vec<_> = workers.iter_mut().collect();
+    repairers.sort_by_key(|(entity, ..)| network_ids.get(*entity).ok().copied());
+    
+    for (worker, worker_transform, worker_owner, repair_target, worker_collider, movement) in repairers {
+        let (mut building, building_transform, building_owner, building_collider) = match buildings.get_mut(repair_target.target_entity) {
+            Ok(building) => building,
+            Err(_) => {
+                commands.entity(worker).remove::<RepairTarget>();
+                continue;
+            }
+        };
+        
+        // Done, or no longer ours to repair
+        let repairable = building.construction_progress.is_none() && building.health > 0.0 && building.health < building.max_health;
+        if !repairable || building_owner.0 != worker_owner.0 {
+            commands.entity(worker).remove::<RepairTarget>();
+            continue;
+        }
+        
+        // Walk over until standing next to the building
+        let worker_radius = worker_collider.map(|c| c.radius).unwrap_or(0.0);
+        let building_radius = building_collider.map(|c| c.radius).unwrap_or(0.0);
+        let distance = (worker_transform.position - building_transform.position).length();
+        if distance > worker_radius + building_radius + REPAIR_RANGE {
+            if let (Some(mut movement), Some(grid)) = (movement, game_map.grid_for(worker_owner.0)) {
+                walk_to(&mut movement, worker_transform.position, building_transform.position, grid, worker_radius);
+            }
+            continue;
+        }
+        if let Some(mut movement) = movement {
+            stop_movement(&mut movement);
+        }
+        
+        // Each point of health costs the same share of every resource the building costs
+        let amount = (REPAIR_RATE * time.delta_time).min(building.max_health - building.health);
+        let costs: Vec<(ResourceType, f32)> = BuildingData::get(building.building_type)
+            .costs
+            .iter()
+            .map(|(&resource_type, &cost)| (resource_type, cost * REPAIR_COST_FACTOR * amount / building.max_health))
+            .collect();
+        let affordable = costs.iter().all(|(resource_type, cost)| {
+            player_resources.resources.get(&(worker_owner.0, *resource_type)).copied().unwrap_or(0.0) >= *cost
+        });
+        if !affordable {
+            commands.entity(worker).remove::<RepairTarget>();
+            continue;
+        }
+        
+        for (resource_type, cost) in costs {
+            if let Some(banked) = player_resources.resources.get_mut(&(worker_owner.0, resource_type)) {
+                *banked -= cost;
+            }
+            stats.resources_spent(worker_owner.0, cost);
+        }
+        building.health += amount;
+    }
+}
+
+/// Distance a following unit keeps from the unit it follows
+const FOLLOW_DISTANCE: f32 = 30.0;
+
+/// System that keeps units with a follow order close behind the friendly unit they follow
+pub fn follow_system(
+    mut commands: Commands,
+    mut followers: Query<(Entity, &Transform, &Owner, &Follow, Option<&Collider>, &mut Movement), Without<AttackTarget>>,
+    targets: Query<(&Unit, &Transform)>,
+    game_map: Res<GameMap>,
+) {
+    for (entity, transform, owner, follow, collider, mut movement) in followers.iter_mut() {
+        let target_position = match targets.get(follow.target_entity) {
+            Ok((unit, target_transform)) if unit.health > 0.0 => target_transform.position,
+            _ => {
+                commands.entity(entity).remove::<Follow>();
+                continue;
+            }
+        };
+        
+        // Close enough: wait rather than crowd the target
+        if (target_position - transform.position).length() <= FOLLOW_DISTANCE {
+            if movement.path_index < movement.path.len() {
+                stop_movement(&mut movement);
+            }
+            continue;
+        }
+        
+        // Re-path only once the target has wandered off from where we were heading
+        let heading_near = movement.target.map_or(false, |target| (target - target_position).length() <= FOLLOW_DISTANCE);
+        if !heading_near || movement.path_index >= movement.path.len() {
+            if let Some(grid) = game_map.grid_for(owner.0) {
+                let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+                set_path(&mut movement, transform.position, target_position, grid, unit_radius);
+            }
+        }
+    }
+}
+
+/// System that flags workers with no path to walk and no harvest, build, repair or other order as idle
+pub fn idle_worker_system(
+    mut commands: Commands,
+    workers: Query<(
+        Entity,
+        &Unit,
+        Option<&Movement>,
+        Option<&HarvestTarget>,
+        Option<&BuildTarget>,
+        Option<&RepairTarget>,
+        Option<&Follow>,
+        Option<&AttackTarget>,
+        Option<&AttackMove>,
+        Option<&Patrol>,
+        Option<&CastOrder>,
+        Option<&IdleWorker>,
+    ), (Without<Passenger>, Without<Garrisoned>)>,
+) {
+    for (entity, unit, movement, harvest, build, repair, follow, attack, attack_move, patrol, cast, flagged) in workers.iter() {
+        if unit.unit_type != UnitType::Worker {
+            continue;
+        }
+        
+        let walking = movement.map_or(false, |movement| movement.path_index < movement.path.len());
+        let has_order = harvest.is_some() || build.is_some() || repair.is_some() || follow.is_some() ||
+            attack.is_some() || attack_move.is_some() || patrol.is_some() || cast.is_some();
+        let idle = !walking && !has_order && unit.health > 0.0;
+        
+        if idle && flagged.is_none() {
+            commands.entity(entity).insert(IdleWorker);
+        } else if !idle && flagged.is_some() {
+            commands.entity(entity).remove::<IdleWorker>();
+        }
+    }
+}
+
+/// Seconds of drop-offs a player's income rate is averaged over
+const INCOME_AVERAGING_TIME: f32 = 30.0;
+
+/// System that keeps each player's income rate as a running average of what workers drop off at depots
 pub fn economy_system(
     mut player_resources: ResMut<PlayerResources>,
     time: Res<GameTime>,
 ) {
-    // Apply income rates
-    for (&(player_id, resource_type), &rate) in player_resources.income_rate.iter() {
-        let amount = rate * time.delta_time;
-        if let Some(current) = player_resources.resources.get_mut(&(player_id, resource_type)) {
-            *current += amount;
-        }
+    if time.delta_time <= 0.0 {
+        return;
+    }
+    
+    let deposited = std::mem::take(&mut player_resources.deposited);
+    let keys: HashSet<(u8, ResourceType)> = deposited.keys().chain(player_resources.income_rate.keys()).copied().collect();
+    let weight = (time.delta_time / INCOME_AVERAGING_TIME).min(1.0);
+    
+    for key in keys {
+        let delivered_rate = deposited.get(&key).copied().unwrap_or(0.0) / time.delta_time;
+        let rate = player_resources.income_rate.entry(key).or_insert(0.0);
+        *rate += (delivered_rate - *rate) * weight;
     }
 }
 
@@ -286,43 +1334,80 @@ pub fn fog_of_war_system(
     query: Query<(&Transform, &Unit, &Owner)>,
     building_query: Query<(&Transform, &Building, &Owner)>,
     mut game_map: ResMut<GameMap>,
+    player_info: Res<PlayerInfo>,
+    settings: Option<Res<GameSettings>>,
+    time: Res<GameTime>,
+    day_night: Option<Res<DayNightCycle>>,
+    revealed_areas: Option<Res<RevealedAreas>>,
 ) {
+    // Night shortens every unit's and building's sight
+    let sight_multiplier = environment::sight_multiplier(day_night.as_deref(), time.current_tick);
+    
     // Clear existing visibility
     for visibility_set in game_map.fog_of_war.values_mut() {
         visibility_set.clear();
     }
     
-    // Calculate visible tiles for each player's units
-    for player_id in 0..8 {
-        let mut unit_positions = Vec::new();
-        
-        // Add units
-        for (transform, unit, owner) in query.iter() {
-            if owner.0 == player_id {
-                unit_positions.push((transform.position, unit.sight_range));
-            }
+    // Gather what each player sees from, by player ID
+    let mut sight_sources: Vec<Vec<(Vec2, f32)>> = vec![Vec::new(); 8];
+    
+    // Add units
+    for (transform, unit, owner) in query.iter() {
+        if let Some(sources) = sight_sources.get_mut(owner.0 as usize) {
+            sources.push((transform.position, unit.sight_range * sight_multiplier));
         }
-        
-        // Add buildings
-        for (transform, building, owner) in building_query.iter() {
-            if owner.0 == player_id {
-                // Different building types have different sight ranges
-                let sight_range = match building.building_type {
-                    BuildingType::Headquarters => 120.0,
-                    BuildingType::DefenseTower => 150.0,
-                    _ => 80.0,
-                };
-                
-                unit_positions.push((transform.position, sight_range));
+    }
+    
+    // Add buildings
+    for (transform, building, owner) in building_query.iter() {
+        if let Some(sources) = sight_sources.get_mut(owner.0 as usize) {
+            // Different building types have different sight ranges
+            let sight_range = match building.building_type {
+                BuildingType::Headquarters | BuildingType::Fortress => 120.0,
+                BuildingType::DefenseTower => 150.0,
+                BuildingType::Watchtower => 250.0,
+                _ => 80.0,
+            };
+            
+            sources.push((transform.position, sight_range * sight_multiplier));
+        }
+    }
+    
+    // Areas a scenario revealed stay in sight whatever the time of day
+    if let Some(revealed_areas) = &revealed_areas {
+        for &(owner, center, radius) in revealed_areas.areas.iter() {
+            if let Some(sources) = sight_sources.get_mut(owner as usize) {
+                sources.push((center, radius));
             }
         }
-        
-        // Calculate visible tiles
-        let visible_tiles = crate::game::pathfinding::calculate_visible_tiles(&game_map, &unit_positions, 8.0);
-        
-        // Update fog of war for this player
+    }
+    
+    // Calculate visible tiles for every player at once on worker threads; results come back in player order
+    let map: &GameMap = &game_map;
+    let visible_tiles = ComputeTaskPool::init(TaskPool::default).scope(|scope| {
+        for sources in &sight_sources {
+            scope.spawn(async move { crate::game::map::calculate_visible_tiles(map, sources, pathfinding::PATH_GRID_SIZE) });
+        }
+    });
+    
+    // Update fog of war for each player
+    for (player_id, visible_tiles) in (0..8).zip(visible_tiles) {
         game_map.fog_of_war.insert(player_id, visible_tiles);
     }
+    
+    // With shared vision every player also sees what their allies see
+    if settings.map_or(false, |settings| settings.shared_vision) {
+        let own_vision = game_map.fog_of_war.clone();
+        for player_id in 0..8 {
+            let mut shared = HashSet::new();
+            for ally in player_info.allies_of(player_id) {
+                if let Some(visible_tiles) = own_vision.get(&ally) {
+                    shared.extend(visible_tiles.iter().copied());
+                }
+            }
+            game_map.fog_of_war.insert(player_id, shared);
+        }
+    }
 }
 
 /// Helper function to spawn a new unit
@@ -332,49 +1417,16 @@ fn spawn_unit(
     position: Vec2,
     owner: u8,
 ) {
-    // Get unit stats based on type
-    let (health, attack_damage, attack_range, attack_speed, movement_speed, sight_range) = match unit_type {
-        UnitType::Worker => (
-            30.0,   // Health
-            3.0,    // Attack damage
-            10.0,   // Attack range
-            1.0,    // Attack speed
-            80.0,   // Movement speed
-            100.0,  // Sight range
-        ),
-        UnitType::Soldier => (
-            60.0,   // Health
-            10.0,   // Attack damage
-            50.0,   // Attack range
-            0.8,    // Attack speed
-            60.0,   // Movement speed
-            120.0,  // Sight range
-        ),
-        UnitType::Scout => (
-            40.0,   // Health
-            6.0,    // Attack damage
-            40.0,   // Attack range
-            0.5,    // Attack speed
-            120.0,  // Movement speed
-            150.0,  // Sight range
-        ),
-        UnitType::Tank => (
-            120.0,  // Health
-            30.0,   // Attack damage
-            70.0,   // Attack range
-            2.0,    // Attack speed
-            40.0,   // Movement speed
-            100.0,  // Sight range
-        ),
-        UnitType::Healer => (
-            40.0,   // Health
-            0.0,    // Attack damage
-            60.0,   // Heal range
-            1.0,    // Heal speed
-            50.0,   // Movement speed
-            120.0,  // Sight range
-        ),
-    };
+    // Get unit stats from the loaded definitions
+    let unit_data = data::game_data().unit(unit_type);
+    let (health, attack_damage, attack_range, attack_speed, movement_speed, sight_range) = (
+        unit_data.health,
+        unit_data.attack_damage,
+        unit_data.attack_range,  // Heal range for healers
+        unit_data.attack_speed,  // Heal speed for healers
+        unit_data.movement_speed,
+        unit_data.sight_range,
+    );
     
     // Spawn unit entity with components
     commands.spawn((
@@ -402,11 +1454,7 @@ fn spawn_unit(
             velocity: Vec2::ZERO,
         },
         Collider {
-            radius: match unit_type {
-                UnitType::Tank => 8.0,
-                UnitType::Worker | UnitType::Healer => 4.0,
-                _ => 5.0,
-            },
+            radius: unit_data.collider_radius,
             collision_layer: 1, // Unit layer
             collision_mask: 1 | 2, // Collide with units and buildings
         },
@@ -421,9 +1469,120 @@ fn spawn_unit(
             shape: match unit_type {
                 UnitType::Worker => MinimapShape::Circle,
                 UnitType::Tank => MinimapShape::Square,
+                UnitType::Transport => MinimapShape::Diamond,
                 _ => MinimapShape::Triangle,
             },
         },
         Selectable,
     ));
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::SystemState;
+    
+    const RADIUS: f32 = 5.0;
+    
+    fn moving() -> Movement {
+        Movement { path: Vec::new(), path_index: 0, target: None, velocity: Vec2::ZERO }
+    }
+    
+    /// What `separation` gives `entity` among everything spawned in `world`, found through a fresh grid
+    fn separation_of(world: &mut World, entity: Entity, placed: &[(Entity, Vec2)]) -> (Vec2, Vec<Vec2>) {
+        let mut spatial_grid = SpatialGrid::default();
+        for &(placed_entity, position) in placed {
+            spatial_grid.insert(placed_entity, position, RADIUS);
+        }
+        let position = placed.iter().find(|(placed_entity, _)| *placed_entity == entity).unwrap().1;
+        
+        let mut state: SystemState<(Query<(), Without<Movement>>, Query<(), With<Gate>>)> = SystemState::new(world);
+        let (static_entities, gates) = state.get(world);
+        separation(entity, position, RADIUS, AVOIDANCE_MARGIN, &spatial_grid, &static_entities, &gates)
+    }
+    
+    /// A grid of open cells with the given cells blocked
+    fn grid_with_blocked(blocked: &[(usize, usize)]) -> PathfindingGrid {
+        let (width, height) = (4, 4);
+        let mut nodes = vec![PathNode { walkable: true, cost: 1.0 }; width * height];
+        for &(x, y) in blocked {
+            nodes[y * width + x].walkable = false;
+        }
+        PathfindingGrid { width, height, nodes }
+    }
+    
+    fn cell_center(x: f32, y: f32) -> Vec2 {
+        Vec2::new(x + 0.5, y + 0.5) * pathfinding::PATH_GRID_SIZE
+    }
+    
+    #[test]
+    fn units_on_the_same_spot_split_apart() {
+        let mut world = World::new();
+        let a = world.spawn(moving()).id();
+        let b = world.spawn(moving()).id();
+        let placed = [(a, Vec2::new(50.0, 50.0)), (b, Vec2::new(50.0, 50.0))];
+        
+        let (push_a, normals_a) = separation_of(&mut world, a, &placed);
+        let (push_b, _) = separation_of(&mut world, b, &placed);
+        assert_eq!(push_a, -push_b);
+        assert_eq!(push_a.length(), 1.0);
+        assert!(normals_a.is_empty());
+    }
+    
+    #[test]
+    fn static_entities_push_harder_and_are_slid_along() {
+        let mut world = World::new();
+        let unit = world.spawn(moving()).id();
+        let other_unit = world.spawn(moving()).id();
+        let building = world.spawn_empty().id();
+        let offset = Vec2::new(RADIUS, 0.0);
+        
+        let (unit_push, unit_normals) = separation_of(&mut world, unit, &[(unit, Vec2::ZERO), (other_unit, offset)]);
+        let (static_push, static_normals) = separation_of(&mut world, unit, &[(unit, Vec2::ZERO), (building, offset)]);
+        assert!(unit_push.x < 0.0 && unit_normals.is_empty());
+        assert_eq!(static_push, unit_push * STATIC_SEPARATION_WEIGHT);
+        assert_eq!(static_normals, vec![Vec2::NEG_X]);
+    }
+    
+    #[test]
+    fn gates_and_distant_neighbours_are_ignored() {
+        let mut world = World::new();
+        let unit = world.spawn(moving()).id();
+        let gate = world.spawn(Gate).id();
+        let distant = world.spawn(moving()).id();
+        let placed = [
+            (unit, Vec2::ZERO),
+            (gate, Vec2::new(1.0, 0.0)),
+            (distant, Vec2::new(RADIUS * 2.0 + AVOIDANCE_MARGIN, 0.0)),
+        ];
+        
+        assert_eq!(separation_of(&mut world, unit, &placed), (Vec2::ZERO, Vec::new()));
+    }
+    
+    #[test]
+    fn moves_slide_along_a_blocked_tile() {
+        let grid = grid_with_blocked(&[(2, 1)]);
+        let from = cell_center(1.0, 1.0);
+        
+        // Heading diagonally into the blocked cell keeps the free part of the move
+        let to = from + Vec2::new(pathfinding::PATH_GRID_SIZE, 2.0);
+        assert_eq!(slide_on_grid(from, to, Some(&grid)), Vec2::new(from.x, to.y));
+        
+        // Straight into it goes nowhere
+        let to = from + Vec2::new(pathfinding::PATH_GRID_SIZE, 0.0);
+        assert_eq!(slide_on_grid(from, to, Some(&grid)), from);
+        
+        // Open ground and a missing grid don't get in the way
+        let to = from + Vec2::new(-2.0, 2.0);
+        assert_eq!(slide_on_grid(from, to, Some(&grid)), to);
+        assert_eq!(slide_on_grid(from, cell_center(2.0, 1.0), None), cell_center(2.0, 1.0));
+    }
+    
+    #[test]
+    fn units_stuck_in_a_blocked_tile_can_walk_out() {
+        let grid = grid_with_blocked(&[(1, 1), (2, 1)]);
+        let from = cell_center(1.0, 1.0);
+        let to = cell_center(2.0, 1.0);
+        assert_eq!(slide_on_grid(from, to, Some(&grid)), to);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::VecDeque;
 
 use crate::ecs::components::{Building, BuildingType, UnitType, Transform, Owner};
 use crate::ecs::resources::{GameTime, PlayerResources, TechState};
-use crate::game::{buildings::BuildingData, units::spawn_unit};
+use crate::game::{buildings::BuildingData, data, units::spawn_unit};
 
 pub fn building_production_system(
     mut commands: Commands,
@@ -33,13 +33,7 @@ pub fn building_production_system(
             // Building is producing something
             if let Some(&unit_type) = building.production_queue.front() {
                 // Calculate training time
-                let base_train_time = match unit_type {
-                    UnitType::Worker => 15.0,
-                    UnitType::Soldier => 25.0,
-                    UnitType::Scout => 20.0,
-                    UnitType::Tank => 40.0,
-                    UnitType::Healer => 30.0,
-                };
+                let base_train_time = data::game_data().unit(unit_type).train_time;
                 
                 // Update progress
                 *progress += time.delta_time / base_train_time;
@@ -1,14 +1,33 @@
 use bevy_ecs::prelude::*;
 use glam::Vec2;
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use tracing::debug;
 
 use crate::ecs::components::{
-    Transform, Unit, Building, Owner, AttackTarget, Movement,
-    UnitType, BuildingType,
+    Transform, Unit, Building, Owner, AttackTarget, Experience,
+    ActiveBuffs, UnitType, BuildingType, Cargo, Passenger, Garrison, Garrisoned,
 };
-use crate::ecs::resources::{GameTime};
+use crate::ecs::events::{Casualty, DamageEvent, DeathEvent};
+use crate::ecs::resources::{
+    AttackAlerts, AudioEvents, CombatTextEvents, CombatTextKind, GameMap, GameSettings, GameTime, PlayerAlerts, PlayerInfo, TerrainTile,
+};
+use crate::ecs::spatial::SpatialGrid;
+use crate::engine::audio::GameSoundType;
+use crate::game::{abilities, data, garrison, map, pathfinding};
+use crate::game::veterancy;
+use crate::game::simulation::{tick_rng, RNG_STREAM_COMBAT};
+
+/// Distance from its destination at which a projectile lands
+const PROJECTILE_HIT_DISTANCE: f32 = 5.0;
+
+/// Share of a weapon's damage splash deals at the centre of the blast, falling to nothing at its edge
+pub const SPLASH_DAMAGE_SHARE: f32 = 0.5;
+
+/// Cooldown left over that still counts as ready. Subtracting a tick at a time leaves float error
+/// behind, which would otherwise hold a 2 second cooldown to 41 ticks instead of 40
+const COOLDOWN_TOLERANCE: f32 = 1e-4;
 
 /// Component for tracking attack cooldown
 #[derive(Component, Debug)]
@@ -17,6 +36,19 @@ pub struct AttackCooldown {
     pub base_cooldown: f32,
 }
 
+impl AttackCooldown {
+    /// Count down a tick; true when the weapon is ready, which starts the next cooldown
+    pub fn tick(&mut self, delta_time: f32) -> bool {
+        self.remaining -= delta_time;
+        if self.remaining <= COOLDOWN_TOLERANCE {
+            self.remaining = self.base_cooldown;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 /// Component for projectiles
 #[derive(Component, Debug)]
 pub struct Projectile {
@@ -27,6 +59,8 @@ pub struct Projectile {
     pub max_distance: f32,
     pub traveled_distance: f32,
     pub aoe_radius: Option<f32>,
+    pub homing: bool,      // Follows the target; otherwise flies at `destination` and hits whatever is there
+    pub destination: Vec2, // Impact point, or the target's last known position while homing
 }
 
 /// Component for effects like explosions
@@ -49,13 +83,18 @@ pub enum EffectType {
 }
 
 /// Damage type for combat calculations
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DamageType {
     Physical,
     Energy,
     Explosive,
 }
 
+impl DamageType {
+    /// Every damage type, in declaration order
+    pub const ALL: [DamageType; 3] = [DamageType::Physical, DamageType::Energy, DamageType::Explosive];
+}
+
 /// Armor type for damage reduction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArmorType {
@@ -65,8 +104,13 @@ pub enum ArmorType {
     Building,
 }
 
+impl ArmorType {
+    /// Every armor type, in declaration order
+    pub const ALL: [ArmorType; 4] = [ArmorType::Light, ArmorType::Medium, ArmorType::Heavy, ArmorType::Building];
+}
+
 /// Weapon type for combat calculations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeaponData {
     pub damage: f32,
     pub range: f32,
@@ -75,9 +119,12 @@ pub struct WeaponData {
     pub projectile_speed: Option<f32>, // None for instant-hit weapons
     pub splash_radius: Option<f32>,    // None for single-target weapons
     pub accuracy: f32,                 // 0.0 to 1.0
+    #[serde(default)]
+    pub ballistic: bool,               // Shells fly at where the target stood when fired, so they can be dodged
 }
 
 /// Data mapping armor types to damage multipliers for each damage type
+#[derive(Resource)]
 pub struct DamageTable {
     pub multipliers: HashMap<(DamageType, ArmorType), f32>,
 }
@@ -108,63 +155,32 @@ impl Default for DamageTable {
     }
 }
 
+impl DamageTable {
+    /// How hard a hit of the given type lands on the given armor. Damage with no type ignores armor
+    pub fn multiplier(&self, damage_type: Option<DamageType>, armor_type: ArmorType) -> f32 {
+        damage_type.map_or(1.0, |damage_type| self.multipliers.get(&(damage_type, armor_type)).copied().unwrap_or(1.0))
+    }
+}
+
+/// Whether a shot with the given accuracy hits, rolled from the tick's combat RNG
+pub fn accuracy_roll_hits(rng: &mut impl Rng, accuracy: f32) -> bool {
+    rng.gen_range(0.0..1.0) <= accuracy
+}
+
+/// Damage splash deals to something `distance` from the centre of a blast of the given radius
+pub fn splash_damage(base_damage: f32, distance: f32, radius: f32) -> f32 {
+    let falloff = 1.0 - (distance / radius).min(1.0);
+    base_damage * falloff * SPLASH_DAMAGE_SHARE
+}
+
 /// Get the weapon data for a unit type
 pub fn get_weapon_data(unit_type: UnitType) -> Option<WeaponData> {
-    match unit_type {
-        UnitType::Worker => Some(WeaponData {
-            damage: 3.0,
-            range: 10.0,
-            cooldown: 1.0,
-            damage_type: DamageType::Physical,
-            projectile_speed: None,
-            splash_radius: None,
-            accuracy: 0.9,
-        }),
-        UnitType::Soldier => Some(WeaponData {
-            damage: 10.0,
-            range: 50.0,
-            cooldown: 0.8,
-            damage_type: DamageType::Physical,
-            projectile_speed: Some(200.0),
-            splash_radius: None,
-            accuracy: 0.85,
-        }),
-        UnitType::Scout => Some(WeaponData {
-            damage: 6.0,
-            range: 40.0,
-            cooldown: 0.5,
-            damage_type: DamageType::Physical,
-            projectile_speed: Some(250.0),
-            splash_radius: None,
-            accuracy: 0.95,
-        }),
-        UnitType::Tank => Some(WeaponData {
-            damage: 30.0,
-            range: 70.0,
-            cooldown: 2.0,
-            damage_type: DamageType::Explosive,
-            projectile_speed: Some(150.0),
-            splash_radius: Some(20.0),
-            accuracy: 0.8,
-        }),
-        UnitType::Healer => None, // No weapon, healing ability instead
-    }
+    data::game_data().unit(unit_type).weapon.clone()
 }
 
 /// Get the weapon data for a building type
 pub fn get_building_weapon(building_type: BuildingType) -> Option<WeaponData> {
-    match building_type {
-        BuildingType::DefenseTower => Some(WeaponData {
-            damage: 15.0,
-            range: 100.0,
-            cooldown: 1.0,
-            damage_type: DamageType::Energy,
-            projectile_speed: Some(300.0),
-            splash_radius: None,
-            accuracy: 0.9,
-        }),
-        _ => None, // Other buildings don't have weapons
-    }
+    data::game_data().building(building_type).weapon.clone()
 }
 
 /// Get the armor type for a unit type
@@ -175,6 +191,7 @@ pub fn get_unit_armor_type(unit_type: UnitType) -> ArmorType {
         UnitType::Soldier => ArmorType::Medium,
         UnitType::Tank => ArmorType::Heavy,
         UnitType::Healer => ArmorType::Light,
+        UnitType::Transport => ArmorType::Heavy,
     }
 }
 
@@ -183,11 +200,10 @@ pub fn get_building_armor_type(_building_type: BuildingType) -> ArmorType {
     ArmorType::Building
 }
 
-/// System to process attacks and combat
+/// System to process attacks and combat; hits are raised as damage events for damage_system to apply
 pub fn combat_system(
     mut commands: Commands,
     time: Res<GameTime>,
-    damage_table: Res<DamageTable>,
     mut unit_query: Query<(
         Entity,
         &mut Unit,
@@ -205,15 +221,21 @@ pub fn combat_system(
         Option<&mut AttackCooldown>,
     )>,
     transform_query: Query<&Transform>,
+    experience_query: Query<&Experience>,
+    buff_query: Query<&ActiveBuffs>,
+    garrison_query: Query<&Garrison>,
+    occupant_query: Query<&Unit, Without<Transform>>,
     mut projectile_query: Query<(Entity, &mut Projectile, &mut Transform)>,
     mut effect_query: Query<(Entity, &mut Effect, &mut Transform)>,
-    mut rng: Local<Option<StdRng>>,
+    spatial_grid: Res<SpatialGrid>,
+    game_map: Res<GameMap>,
+    // Paired up to stay within the number of parameters a system can take
+    (mut audio_events, mut combat_text): (ResMut<AudioEvents>, ResMut<CombatTextEvents>),
+    player_info: Res<PlayerInfo>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
-    // Initialize RNG if needed
-    if rng.is_none() {
-        *rng = Some(StdRng::seed_from_u64(time.current_tick));
-    }
-    let rng = rng.as_mut().unwrap();
+    // Rolls come from the game seed and tick so every machine agrees on them
+    let rng = &mut tick_rng(time.seed, time.current_tick, RNG_STREAM_COMBAT);
     
     // Update cooldowns and process attacks for units
     for (entity, mut unit, transform, owner, attack_target, cooldown) in unit_query.iter_mut() {
@@ -235,8 +257,11 @@ pub fn combat_system(
         let target_position = target_transform.position;
         let unit_position = transform.position;
         
-        // Get weapon data
-        if let Some(weapon) = get_weapon_data(unit.unit_type) {
+        // Get weapon data, with the veterancy and ability damage bonuses
+        if let Some(mut weapon) = get_weapon_data(unit.unit_type) {
+            weapon.damage *= veterancy::damage_multiplier(experience_query.get(entity).ok());
+            weapon.damage *= abilities::damage_multiplier(buff_query.get(entity).ok());
+            
             // Calculate distance to target
             let distance = (target_position - unit_position).length();
             
@@ -244,19 +269,7 @@ pub fn combat_system(
             if distance <= weapon.range {
                 // Check attack cooldown
                 let can_attack = match cooldown {
-                    Some(mut cooldown) => {
-                        // Update cooldown
-                        cooldown.remaining -= time.delta_time;
-                        
-                        // Check if we can attack
-                        if cooldown.remaining <= 0.0 {
-                            // Reset cooldown
-                            cooldown.remaining = cooldown.base_cooldown;
-                            true
-                        } else {
-                            false
-                        }
-                    }
+                    Some(mut cooldown) => cooldown.tick(time.delta_time),
                     None => {
                         // No cooldown component, create one
                         commands.entity(entity).insert(AttackCooldown {
@@ -271,8 +284,7 @@ pub fn combat_system(
                 
                 if can_attack {
                     // Check for accuracy hit/miss
-                    let accuracy_roll = rng.gen_range(0.0..1.0);
-                    if accuracy_roll <= weapon.accuracy {
+                    if accuracy_roll_hits(rng, weapon.accuracy) {
                         // Hit successful - process attack
                         process_attack(
                             &mut commands,
@@ -288,8 +300,7 @@ pub fn combat_system(
                             rng.gen_range(-5.0..5.0),
                             rng.gen_range(-5.0..5.0),
                         );
-                        
-                        // Could spawn a "miss" effect here
+                        combat_text.push(CombatTextKind::Miss, target_entity, miss_position, 0.0);
                     }
                 }
             }
@@ -303,7 +314,16 @@ pub fn combat_system(
             continue;
         }
         
-        if let Some(weapon) = get_building_weapon(building.building_type) {
+        // Occupants fire from inside through the building's weapon
+        let occupants: Vec<UnitType> = garrison_query.get(entity).map_or(Vec::new(), |garrison| {
+            garrison
+                .occupants
+                .iter()
+                .filter_map(|&occupant| occupant_query.get(occupant).ok().map(|unit| unit.unit_type))
+                .collect()
+        });
+        
+        if let Some(weapon) = garrison::garrison_weapon(building.building_type, &occupants) {
             // Get target entity
             let target_entity = attack_target.unwrap().target_entity;
             
@@ -324,19 +344,7 @@ pub fn combat_system(
             if distance <= weapon.range {
                 // Check attack cooldown
                 let can_attack = match cooldown {
-                    Some(mut cooldown) => {
-                        // Update cooldown
-                        cooldown.remaining -= time.delta_time;
-                        
-                        // Check if we can attack
-                        if cooldown.remaining <= 0.0 {
-                            // Reset cooldown
-                            cooldown.remaining = cooldown.base_cooldown;
-                            true
-                        } else {
-                            false
-                        }
-                    }
+                    Some(mut cooldown) => cooldown.tick(time.delta_time),
                     None => {
                         // No cooldown component, create one
                         commands.entity(entity).insert(AttackCooldown {
@@ -366,21 +374,17 @@ pub fn combat_system(
     
     // Update projectiles
     for (entity, mut projectile, mut transform) in projectile_query.iter_mut() {
-        // Skip if target no longer exists
-        if !transform_query.contains(projectile.target_entity) {
-            commands.entity(entity).despawn();
-            continue;
+        // Homing projectiles follow their target; if it dies they carry on to where it was last seen
+        let target_position = transform_query.get(projectile.target_entity).ok().map(|target| target.position);
+        let following = projectile.homing && target_position.is_some();
+        if let Some(target_position) = target_position.filter(|_| projectile.homing) {
+            projectile.destination = target_position;
         }
         
-        // Get target position
-        let target_transform = transform_query.get(projectile.target_entity).unwrap();
-        let target_position = target_transform.position;
-        
-        // Calculate direction to target
-        let direction = (target_position - transform.position).normalize_or_zero();
-        
-        // Move projectile
-        let distance_to_move = projectile.speed * time.delta_time;
+        // Move toward the destination without overshooting it
+        let to_destination = projectile.destination - transform.position;
+        let direction = to_destination.normalize_or_zero();
+        let distance_to_move = (projectile.speed * time.delta_time).min(to_destination.length());
         transform.position += direction * distance_to_move;
         
         // Update rotation to face direction
@@ -391,25 +395,39 @@ pub fn combat_system(
         // Update traveled distance
         projectile.traveled_distance += distance_to_move;
         
-        // Check if projectile has reached target or max distance
-        let distance_to_target = (target_position - transform.position).length();
-        if distance_to_target < 5.0 || projectile.traveled_distance >= projectile.max_distance {
-            // Apply damage
+        // Projectiles not following a target burst on mountains in their way
+        let hit_terrain = !following && pathfinding::terrain_at(transform.position, &game_map) == Some(TerrainTile::Mountain);
+        let arrived = (projectile.destination - transform.position).length() < PROJECTILE_HIT_DISTANCE;
+        if arrived || hit_terrain || projectile.traveled_distance >= projectile.max_distance {
+            // A followed target takes the hit; otherwise whatever enemy stands at the impact point does, if any
+            let struck = if following {
+                Some(projectile.target_entity)
+            } else if hit_terrain {
+                None
+            } else {
+                entity_at(transform.position, projectile.source_entity, &unit_query, &building_query, &spatial_grid, &player_info)
+            };
+            
+            // Apply damage, splashing around the impact point even when nothing was struck
             apply_damage(
                 &mut commands,
-                &damage_table,
+                &mut damage_events,
                 projectile.source_entity,
-                projectile.target_entity,
+                struck,
                 projectile.damage,
                 projectile.aoe_radius,
                 &unit_query,
                 &building_query,
                 &transform_query,
+                &spatial_grid,
+                &player_info,
                 transform.position,
             );
             
             // Spawn hit effect
             spawn_hit_effect(&mut commands, transform.position, 0.5);
+            let sound = if projectile.aoe_radius.is_some() { GameSoundType::Explosion } else { GameSoundType::CombatHit };
+            audio_events.play(sound, transform.position);
             
             // Despawn projectile
             commands.entity(entity).despawn();
@@ -446,6 +464,107 @@ pub fn combat_system(
     }
 }
 
+/// How urgently a tower wants to shoot a candidate; higher wins, ties go to the closest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TargetPriority {
+    Building,
+    Unit,
+    Attacker, // Unit attacking the tower or something else of its team
+}
+
+/// System that points armed buildings at the most threatening enemy in weapon range,
+/// switching when something more urgent shows up and dropping targets that leave range or sight
+pub fn building_targeting_system(
+    mut commands: Commands,
+    towers: Query<(Entity, &Building, &Transform, &Owner, Option<&AttackTarget>)>,
+    units: Query<(&Unit, &Owner, Option<&AttackTarget>)>,
+    owners: Query<&Owner>,
+    garrisons: Query<&Garrison>,
+    spatial_grid: Res<SpatialGrid>,
+    game_map: Res<GameMap>,
+    settings: Option<Res<GameSettings>>,
+    player_info: Res<PlayerInfo>,
+) {
+    let fog_enabled = settings.map_or(true, |settings| settings.fog_of_war_enabled);
+    
+    for (entity, building, transform, owner, attack_target) in towers.iter() {
+        let occupants: Vec<UnitType> = garrisons.get(entity).map_or(Vec::new(), |garrison| {
+            garrison
+                .occupants
+                .iter()
+                .filter_map(|&occupant| units.get(occupant).ok().map(|(unit, ..)| unit.unit_type))
+                .collect()
+        });
+        let weapon = match garrison::garrison_weapon(building.building_type, &occupants) {
+            Some(weapon) if building.construction_progress.is_none() && building.health > 0.0 => weapon,
+            _ => continue,
+        };
+        
+        // Living enemy the tower's owner can see, with how urgent it is
+        let priority_of = |candidate: Entity, position: Vec2| -> Option<TargetPriority> {
+            let candidate_owner = owners.get(candidate).ok()?;
+            if player_info.are_allies(candidate_owner.0, owner.0) {
+                return None;
+            }
+            if fog_enabled && !map::is_position_visible(&game_map, owner.0, position, pathfinding::PATH_GRID_SIZE) {
+                return None;
+            }
+            
+            if let Ok((unit, _, unit_target)) = units.get(candidate) {
+                if unit.health <= 0.0 {
+                    return None;
+                }
+                let attacking_us = unit_target
+                    .and_then(|target| owners.get(target.target_entity).ok())
+                    .map_or(false, |target_owner| player_info.are_allies(target_owner.0, owner.0));
+                return Some(if attacking_us { TargetPriority::Attacker } else { TargetPriority::Unit });
+            }
+            match towers.get(candidate) {
+                Ok((_, target_building, ..)) if target_building.health > 0.0 => Some(TargetPriority::Building),
+                _ => None,
+            }
+        };
+        
+        // Everything in range, best first: priority, then distance, then entity for a stable order
+        let best = spatial_grid
+            .query_radius(transform.position, weapon.range + spatial_grid.max_radius())
+            .into_iter()
+            .filter(|candidate| candidate.entity != entity)
+            .filter(|candidate| (candidate.position - transform.position).length() <= weapon.range + candidate.radius)
+            .filter_map(|candidate| {
+                let priority = priority_of(candidate.entity, candidate.position)?;
+                Some((priority, (candidate.position - transform.position).length_squared(), candidate.entity))
+            })
+            .max_by(|a, b| {
+                a.0.cmp(&b.0)
+                    .then(b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal))
+                    .then(b.2.cmp(&a.2))
+            });
+        
+        // Stay on the current target unless it left range or sight, or something more urgent turned up
+        let current = attack_target.and_then(|attack_target| {
+            let candidate = spatial_grid
+                .query_radius(transform.position, weapon.range + spatial_grid.max_radius())
+                .into_iter()
+                .find(|candidate| candidate.entity == attack_target.target_entity)
+                .filter(|candidate| (candidate.position - transform.position).length() <= weapon.range + candidate.radius)?;
+            priority_of(candidate.entity, candidate.position).map(|priority| (priority, candidate.entity))
+        });
+        
+        match (current, best) {
+            (Some((current_priority, _)), Some((best_priority, ..))) if current_priority >= best_priority => {}
+            (_, Some((_, _, target))) => {
+                commands.entity(entity).insert(AttackTarget { target_entity: target });
+            }
+            (_, None) => {
+                if attack_target.is_some() {
+                    commands.entity(entity).remove::<AttackTarget>();
+                }
+            }
+        }
+    }
+}
+
 /// Process an attack from one entity to another
 fn process_attack(
     commands: &mut Commands,
@@ -469,6 +588,8 @@ fn process_attack(
                 max_distance: weapon.range * 1.5, // Allow some extra distance
                 traveled_distance: 0.0,
                 aoe_radius: weapon.splash_radius,
+                homing: !weapon.ballistic,
+                destination: target_pos,
             },
             Transform {
                 position: attacker_pos,
@@ -484,12 +605,49 @@ fn process_attack(
     }
 }
 
-/// Apply damage to a target and nearby entities if AOE
+/// Enemy unit or building whose collider covers a ballistic shell's impact point, nearest first
+fn entity_at(
+    position: Vec2,
+    attacker: Entity,
+    unit_query: &Query<(
+        Entity,
+        &mut Unit,
+        &Transform,
+        &Owner,
+        Option<&AttackTarget>,
+        Option<&mut AttackCooldown>,
+    )>,
+    building_query: &Query<(
+        Entity,
+        &mut Building,
+        &Transform,
+        &Owner,
+        Option<&AttackTarget>,
+        Option<&mut AttackCooldown>,
+    )>,
+    spatial_grid: &SpatialGrid,
+    player_info: &PlayerInfo,
+) -> Option<Entity> {
+    let attacker_owner = get_entity_owner(attacker, unit_query, building_query);
+    spatial_grid
+        .nearest(position, spatial_grid.max_radius(), |candidate| {
+            if candidate.entity == attacker || (candidate.position - position).length() > candidate.radius {
+                return false;
+            }
+            match get_entity_owner(candidate.entity, unit_query, building_query) {
+                Some(owner) => !attacker_owner.map_or(false, |attacker_owner| player_info.are_allies(owner, attacker_owner)),
+                None => false,
+            }
+        })
+        .map(|entry| entry.entity)
+}
+
+/// Raise damage events for a target and nearby entities if AOE
 fn apply_damage(
     commands: &mut Commands,
-    damage_table: &DamageTable,
+    damage_events: &mut EventWriter<DamageEvent>,
     attacker: Entity,
-    target: Entity,
+    target: Option<Entity>, // None when a shell landed on empty ground
     base_damage: f32,
     aoe_radius: Option<f32>,
     unit_query: &Query<(
@@ -509,50 +667,43 @@ fn apply_damage(
         Option<&mut AttackCooldown>,
     )>,
     transform_query: &Query<&Transform>,
+    spatial_grid: &SpatialGrid,
+    player_info: &PlayerInfo,
     impact_position: Vec2,
 ) {
     // First, apply damage to the direct target
-    apply_damage_to_entity(commands, damage_table, attacker, target, base_damage, unit_query, building_query);
+    if let Some(target) = target {
+        apply_damage_to_entity(damage_events, attacker, target, base_damage, unit_query, building_query);
+    }
     
     // If this is an AOE attack, apply reduced damage to nearby entities
     if let Some(radius) = aoe_radius {
         // Get attacker owner to avoid friendly fire
         let attacker_owner = get_entity_owner(attacker, unit_query, building_query);
         
-        // Find nearby units
-        for (entity, unit, transform, owner, _, _) in unit_query.iter() {
-            // Skip the main target and friendly units
-            if entity == target || Some(owner.0) == attacker_owner {
+        // Only entities near the impact can be caught in the blast
+        for nearby in spatial_grid.query_radius(impact_position, radius) {
+            let entity = nearby.entity;
+            
+            // Skip the main target
+            if Some(entity) == target {
                 continue;
             }
             
-            // Check distance
-            let distance = (transform.position - impact_position).length();
-            if distance <= radius {
-                // Calculate damage falloff based on distance
-                let damage_multiplier = 1.0 - (distance / radius).min(1.0);
-                let aoe_damage = base_damage * damage_multiplier * 0.5; // AOE deals 50% at most
-                
-                apply_damage_to_entity(commands, damage_table, attacker, entity, aoe_damage, unit_query, building_query);
-            }
-        }
-        
-        // Find nearby buildings
-        for (entity, building, transform, owner, _, _) in building_query.iter() {
-            // Skip the main target and friendly buildings
-            if entity == target || Some(owner.0) == attacker_owner {
+            // Skip friendly and allied units and buildings
+            let owner = match get_entity_owner(entity, unit_query, building_query) {
+                Some(owner) => owner,
+                None => continue,
+            };
+            if attacker_owner.map_or(false, |attacker_owner| player_info.are_allies(owner, attacker_owner)) {
                 continue;
             }
             
-            // Check distance
-            let distance = (transform.position - impact_position).length();
-            if distance <= radius {
-                // Calculate damage falloff based on distance
-                let damage_multiplier = 1.0 - (distance / radius).min(1.0);
-                let aoe_damage = base_damage * damage_multiplier * 0.5; // AOE deals 50% at most
-                
-                apply_damage_to_entity(commands, damage_table, attacker, entity, aoe_damage, unit_query, building_query);
-            }
+            // Less damage the further from the impact
+            let distance = (nearby.position - impact_position).length();
+            let aoe_damage = splash_damage(base_damage, distance, radius);
+            
+            apply_damage_to_entity(damage_events, attacker, entity, aoe_damage, unit_query, building_query);
         }
         
         // Spawn explosion effect
@@ -560,10 +711,9 @@ fn apply_damage(
     }
 }
 
-/// Apply damage to a specific entity
+/// Raise a damage event for a specific entity
 fn apply_damage_to_entity(
-    commands: &mut Commands,
-    damage_table: &DamageTable,
+    damage_events: &mut EventWriter<DamageEvent>,
     attacker: Entity,
     target: Entity,
     base_damage: f32,
@@ -584,57 +734,130 @@ fn apply_damage_to_entity(
         Option<&mut AttackCooldown>,
     )>,
 ) {
-    // Get attacker damage type
-    let damage_type = get_attacker_damage_type(attacker, unit_query, building_query);
-    
-    // Try to apply damage to a unit
-    if let Ok((_, mut unit, transform, _, _, _)) = unit_query.get_mut(target) {
-        // Get armor type
-        let armor_type = get_unit_armor_type(unit.unit_type);
-        
-        // Calculate actual damage
-        let damage_multiplier = damage_table.multipliers
-            .get(&(damage_type, armor_type))
-            .copied()
-            .unwrap_or(1.0);
-        
-        let actual_damage = base_damage * damage_multiplier;
+    damage_events.send(DamageEvent {
+        attacker,
+        attacker_owner: get_entity_owner(attacker, unit_query, building_query),
+        target,
+        amount: base_damage,
+        damage_type: Some(get_attacker_damage_type(attacker, unit_query, building_query)),
+    });
+}
+
+/// System that applies damage events to health, after armor, and raises a death event for
+/// each unit or building that drops to zero
+pub fn damage_system(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    damage_table: Res<DamageTable>,
+    mut units: Query<(&mut Unit, &Owner, &Transform, Option<&Cargo>), (Without<Passenger>, Without<Garrisoned>)>,
+    passengers: Query<(&Unit, &Owner), With<Passenger>>,
+    mut occupants: Query<(&mut Unit, &Owner), (With<Garrisoned>, Without<Passenger>)>,
+    mut buildings: Query<(&mut Building, &Owner, &Transform, Option<&Garrison>)>,
+    mut combat_text: ResMut<CombatTextEvents>,
+    mut attack_alerts: ResMut<AttackAlerts>,
+) {
+    for damage in damage_events.iter() {
+        // Only hits from another player warn the owner; splash on one's own side doesn't
+        let hostile = |owner: u8| damage.attacker_owner.map_or(false, |attacker| attacker != owner);
         
-        // Apply damage
-        unit.health -= actual_damage;
+        // Armor only softens weapon damage
+        let multiplier = |armor_type: ArmorType| damage_table.multiplier(damage.damage_type, armor_type);
+        // Hits the armor is weak against stand out as critical
+        let kind = |multiplier: f32| if multiplier > 1.0 { CombatTextKind::Critical } else { CombatTextKind::Damage };
         
-        // Check if unit is destroyed
-        if unit.health <= 0.0 {
-            // Spawn death effect
-            spawn_death_effect(commands, transform.position, 1.0);
+        if let Ok((mut unit, owner, transform, cargo)) = units.get_mut(damage.target) {
+            let was_alive = unit.health > 0.0;
+            let armor_multiplier = multiplier(get_unit_armor_type(unit.unit_type));
+            unit.health -= damage.amount * armor_multiplier;
+            combat_text.push(kind(armor_multiplier), damage.target, transform.position, damage.amount * armor_multiplier);
+            if hostile(owner.0) {
+                attack_alerts.raise(owner.0, transform.position);
+            }
             
-            // Despawn unit
-            commands.entity(target).despawn();
+            // Count the death once, even if more hits land before the despawn
+            if was_alive && unit.health <= 0.0 {
+                debug!(target: "combat", "Player {}'s {:?} was killed", owner.0, unit.unit_type);
+                death_events.send(DeathEvent {
+                    entity: damage.target,
+                    casualty: Casualty::Unit(unit.unit_type),
+                    owner: owner.0,
+                    position: transform.position,
+                    killer: damage.attacker,
+                    killer_owner: damage.attacker_owner,
+                });
+                
+                // Everyone aboard goes down with the transport
+                for &passenger in cargo.map_or(&[][..], |cargo| &cargo.passengers[..]) {
+                    if let Ok((passenger_unit, passenger_owner)) = passengers.get(passenger) {
+                        death_events.send(DeathEvent {
+                            entity: passenger,
+                            casualty: Casualty::Unit(passenger_unit.unit_type),
+                            owner: passenger_owner.0,
+                            position: transform.position,
+                            killer: damage.attacker,
+                            killer_owner: damage.attacker_owner,
+                        });
+                    }
+                }
+            }
+        } else if let Ok((mut building, owner, transform, garrison)) = buildings.get_mut(damage.target) {
+            let was_standing = building.health > 0.0;
+            let armor_multiplier = multiplier(get_building_armor_type(building.building_type));
+            building.health -= damage.amount * armor_multiplier;
+            combat_text.push(kind(armor_multiplier), damage.target, transform.position, damage.amount * armor_multiplier);
+            if hostile(owner.0) {
+                attack_alerts.raise(owner.0, transform.position);
+            }
+            
+            if was_standing && building.health <= 0.0 {
+                debug!(target: "combat", "Player {}'s {:?} was destroyed", owner.0, building.building_type);
+                death_events.send(DeathEvent {
+                    entity: damage.target,
+                    casualty: Casualty::Building(building.building_type),
+                    owner: owner.0,
+                    position: transform.position,
+                    killer: damage.attacker,
+                    killer_owner: damage.attacker_owner,
+                });
+                
+                // Everyone inside is hurt as it comes down; garrison_collapse_system sets the survivors down
+                for &occupant in garrison.map_or(&[][..], |garrison| &garrison.occupants[..]) {
+                    if let Ok((mut occupant_unit, occupant_owner)) = occupants.get_mut(occupant) {
+                        occupant_unit.health -= occupant_unit.max_health * garrison::COLLAPSE_DAMAGE;
+                        if occupant_unit.health <= 0.0 {
+                            death_events.send(DeathEvent {
+                                entity: occupant,
+                                casualty: Casualty::Unit(occupant_unit.unit_type),
+                                owner: occupant_owner.0,
+                                position: transform.position,
+                                killer: damage.attacker,
+                                killer_owner: damage.attacker_owner,
+                            });
+                        }
+                    }
+                }
+            }
         }
     }
-    // Try to apply damage to a building
-    else if let Ok((_, mut building, transform, _, _, _)) = building_query.get_mut(target) {
-        // Get armor type
-        let armor_type = get_building_armor_type(building.building_type);
-        
-        // Calculate actual damage
-        let damage_multiplier = damage_table.multipliers
-            .get(&(damage_type, armor_type))
-            .copied()
-            .unwrap_or(1.0);
-        
-        let actual_damage = base_damage * damage_multiplier;
-        
-        // Apply damage
-        building.health -= actual_damage;
-        
-        // Check if building is destroyed
-        if building.health <= 0.0 {
-            // Spawn destruction effect
-            spawn_building_destruction_effect(commands, transform.position, 2.0);
-            
-            // Despawn building
-            commands.entity(target).despawn();
+}
+
+/// System that leaves wreckage where units and buildings died and despawns them
+pub fn death_system(mut commands: Commands, mut death_events: EventReader<DeathEvent>) {
+    for death in death_events.iter() {
+        match death.casualty {
+            Casualty::Unit(_) => spawn_death_effect(&mut commands, death.position, 1.0),
+            Casualty::Building(_) => spawn_building_destruction_effect(&mut commands, death.position, 2.0),
+        }
+        commands.entity(death.entity).despawn();
+    }
+}
+
+/// System that tells players when one of their buildings is destroyed
+pub fn building_lost_alert_system(mut death_events: EventReader<DeathEvent>, mut player_alerts: ResMut<PlayerAlerts>) {
+    for death in death_events.iter() {
+        if let Casualty::Building(building_type) = death.casualty {
+            let name = &data::game_data().building(building_type).name;
+            player_alerts.alerts.push((death.owner, format!("Our {} has been destroyed", name)));
         }
     }
 }
@@ -780,4 +1003,125 @@ fn spawn_building_destruction_effect(commands: &mut Commands, position: Vec2, sc
         },
         // Would also add a Sprite component in a real implementation
     ));
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::events::init_events;
+    use crate::ecs::resources::TICK_DELTA;
+    
+    const SEED: u64 = 1234;
+    
+    #[test]
+    fn damage_multipliers_per_damage_and_armor_type() {
+        let expected = [
+            (DamageType::Physical, [1.25, 1.0, 0.75, 0.5]),
+            (DamageType::Energy, [1.0, 1.25, 1.0, 0.75]),
+            (DamageType::Explosive, [1.0, 0.75, 1.25, 1.5]),
+        ];
+        let table = DamageTable::default();
+        for (damage_type, multipliers) in expected {
+            for (armor_type, multiplier) in ArmorType::ALL.into_iter().zip(multipliers) {
+                assert_eq!(table.multiplier(Some(damage_type), armor_type), multiplier, "{:?} against {:?}", damage_type, armor_type);
+            }
+        }
+        
+        // Ability damage has no type and ignores armor
+        for armor_type in ArmorType::ALL {
+            assert_eq!(table.multiplier(None, armor_type), 1.0);
+        }
+    }
+    
+    #[test]
+    fn splash_damage_falls_off_to_nothing_at_the_edge() {
+        assert_eq!(splash_damage(40.0, 0.0, 20.0), 40.0 * SPLASH_DAMAGE_SHARE);
+        assert_eq!(splash_damage(40.0, 10.0, 20.0), 40.0 * SPLASH_DAMAGE_SHARE * 0.5);
+        assert_eq!(splash_damage(40.0, 20.0, 20.0), 0.0);
+        assert_eq!(splash_damage(40.0, 35.0, 20.0), 0.0);
+    }
+    
+    #[test]
+    fn cooldowns_fire_once_per_cooldown_worth_of_ticks() {
+        for seconds in [0.5, 0.8, 1.0, 1.5, 2.0] {
+            let mut cooldown = AttackCooldown { remaining: seconds, base_cooldown: seconds };
+            let shots: Vec<u64> = (1..=200).filter(|_| cooldown.tick(TICK_DELTA)).collect();
+            
+            let interval = (seconds / TICK_DELTA).round() as u64;
+            assert_eq!(shots[0], interval, "{} s cooldown", seconds);
+            for pair in shots.windows(2) {
+                assert_eq!(pair[1] - pair[0], interval, "{} s cooldown", seconds);
+            }
+        }
+    }
+    
+    #[test]
+    fn accuracy_rolls_repeat_for_the_same_seed_and_tick() {
+        let rolls = |tick, accuracy| {
+            let mut rng = tick_rng(SEED, tick, RNG_STREAM_COMBAT);
+            (0..1000).map(|_| accuracy_roll_hits(&mut rng, accuracy)).collect::<Vec<bool>>()
+        };
+        assert_eq!(rolls(10, 0.85), rolls(10, 0.85));
+        assert_ne!(rolls(10, 0.85), rolls(11, 0.85));
+        
+        assert!(rolls(10, 1.0).into_iter().all(|hit| hit));
+        let hits = rolls(10, 0.85).into_iter().filter(|&hit| hit).count();
+        assert!((800..900).contains(&hits), "{} hits in 1000 at 85% accuracy", hits);
+    }
+    
+    #[test]
+    fn lethal_damage_raises_one_death_event_and_despawns() {
+        let mut world = World::new();
+        world.insert_resource(DamageTable::default());
+        world.insert_resource(CombatTextEvents::default());
+        world.insert_resource(AttackAlerts::default());
+        init_events(&mut world);
+        
+        let attacker = world.spawn_empty().id();
+        let target = world
+            .spawn((
+                Unit {
+                    unit_type: UnitType::Soldier,
+                    health: 30.0,
+                    max_health: 100.0,
+                    attack_damage: 10.0,
+                    attack_range: 30.0,
+                    attack_speed: 1.0,
+                    movement_speed: 60.0,
+                    sight_range: 80.0,
+                    buildable: false,
+                },
+                Transform {
+                    position: Vec2::new(50.0, 50.0),
+                    rotation: 0.0,
+                    scale: Vec2::ONE,
+                },
+                Owner(1),
+            ))
+            .id();
+        
+        // Soldiers wear medium armor, which physical damage hits at full strength: the first hit
+        // leaves 10 health, the second kills and the third lands on a corpse
+        for _ in 0..3 {
+            world.send_event(DamageEvent {
+                attacker,
+                attacker_owner: Some(0),
+                target,
+                amount: 20.0,
+                damage_type: Some(DamageType::Physical),
+            });
+        }
+        let mut schedule = Schedule::default();
+        schedule.add_systems((damage_system, apply_deferred, death_system).chain());
+        schedule.run(&mut world);
+        
+        let deaths: Vec<DeathEvent> = world.resource_mut::<Events<DeathEvent>>().drain().collect();
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].entity, target);
+        assert_eq!(deaths[0].casualty, Casualty::Unit(UnitType::Soldier));
+        assert_eq!(deaths[0].owner, 1);
+        assert_eq!(deaths[0].killer, attacker);
+        assert_eq!(deaths[0].killer_owner, Some(0));
+        assert!(world.get_entity(target).is_none());
+    }
+}
@@ -1,12 +1,13 @@
 use bevy_ecs::prelude::*;
 use glam::Vec2;
-use rand::{Rng, thread_rng};
+use rand::Rng;
 
 use crate::ecs::components::{
     Unit, UnitType, Transform, Owner, 
     Movement, AttackTarget, Collider
 };
 use crate::ecs::resources::GameTime;
+use crate::game::simulation::{tick_rng, RNG_STREAM_BEHAVIOR};
 
 /// Auto-combat unit behavior system for autobattler
 pub fn autobattler_unit_behavior_system(
@@ -22,7 +23,7 @@ pub fn autobattler_unit_behavior_system(
     enemy_query: Query<(Entity, &Transform, &Owner, &Unit, &Collider)>,
     time: Res<GameTime>,
 ) {
-    let mut rng = thread_rng();
+    let mut rng = tick_rng(time.seed, time.current_tick, RNG_STREAM_BEHAVIOR);
 
     for (entity, unit, transform, owner, mut movement, attack_target) in query.iter_mut() {
         // Skip units that are already attacking
@@ -87,6 +88,7 @@ fn advanced_targeting(
                     UnitType::Healer => 2,
                     UnitType::Soldier => 3,
                     UnitType::Tank => 4,
+                    UnitType::Transport => 5,
                 })
                 .map(|&(entity, _, _, _)| entity)
         }
@@ -99,6 +101,7 @@ fn advanced_targeting(
                     UnitType::Scout => 2,
                     UnitType::Soldier => 3,
                     UnitType::Tank => 4,
+                    UnitType::Transport => 0,
                 })
                 .map(|&(entity, _, _, _)| entity)
         }
@@ -111,6 +114,7 @@ fn advanced_targeting(
                     UnitType::Scout => 2,
                     UnitType::Soldier => 3,
                     UnitType::Tank => 4,
+                    UnitType::Transport => 5,
                 })
                 .map(|&(entity, _, _, _)| entity)
         }
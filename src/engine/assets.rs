@@ -2,7 +2,7 @@ use anyhow::Result;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use image::{GenericImageView, RgbaImage};
+use image::GenericImageView;
 use wgpu::{Device, Queue, Texture, TextureView, Sampler, TextureFormat};
 
 /// Asset type enum
@@ -129,10 +129,11 @@ impl AssetManager {
 }
 
 /// Helper function to create a texture from an image
-fn create_texture(
+/// An RGBA texture filled with the given pixels, row by row
+pub fn create_texture(
     device: &Device,
     queue: &Queue,
-    rgba_image: &RgbaImage,
+    rgba_image: &[u8],
     width: u32,
     height: u32,
     label: Option<&str>,
@@ -188,4 +189,28 @@ fn create_texture(
         width,
         height,
     })
+}
+
+/// Replace every pixel of a texture, e.g. one redrawn on the CPU such as the minimap
+pub fn update_texture(queue: &Queue, texture: &TextureAsset, rgba: &[u8]) {
+    let size = wgpu::Extent3d {
+        width: texture.width,
+        height: texture.height,
+        depth_or_array_layers: 1,
+    };
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture.texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * texture.width),
+            rows_per_image: Some(texture.height),
+        },
+        size,
+    );
 }
\ No newline at end of file
@@ -1,19 +1,52 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use tracing::debug;
 
 // This is a simplified audio engine for the RTS game
 // In a real implementation, you would use an audio library like rodio
 
+/// Positional sounds farther than this from the listener are inaudible
+const MAX_HEARING_DISTANCE: f32 = 600.0;
+
 /// Audio system for managing game sounds and music
 pub struct AudioSystem {
     sounds: HashMap<String, Arc<Sound>>,
     music_tracks: HashMap<String, Arc<Music>>,
+    master_volume: f32,
     sound_volume: f32,
     music_volume: f32,
     current_music: Option<String>,
+    master_enabled: bool,
     sound_enabled: bool,
     music_enabled: bool,
+    listener: AudioListener,
+}
+
+/// Volume and mute options chosen in the settings menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub master_muted: bool,
+    pub music_muted: bool,
+    pub sfx_muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.7,
+            sfx_volume: 0.8,
+            master_muted: false,
+            music_muted: false,
+            sfx_muted: false,
+        }
+    }
 }
 
 /// A sound effect that can be played
@@ -39,11 +72,14 @@ impl AudioSystem {
         Self {
             sounds: HashMap::new(),
             music_tracks: HashMap::new(),
+            master_volume: 1.0,
             sound_volume: 0.7,
             music_volume: 0.5,
             current_music: None,
+            master_enabled: true,
             sound_enabled: true,
             music_enabled: true,
+            listener: AudioListener::default(),
         }
     }
     
@@ -75,14 +111,21 @@ impl AudioSystem {
     
     /// Play a sound effect
     pub fn play_sound(&self, name: &str, volume_scale: f32, pitch: f32, spatial_pos: Option<(f32, f32)>) -> Result<()> {
-        if !self.sound_enabled {
+        if !self.master_enabled || !self.sound_enabled {
+            return Ok(());
+        }
+        
+        // Fade positional sounds out with distance from the listener
+        let attenuation = spatial_pos.map_or(1.0, |pos| self.attenuation(pos));
+        let volume = self.effective_sound_volume() * volume_scale * attenuation;
+        if volume <= 0.0 {
             return Ok(());
         }
         
         if let Some(sound) = self.sounds.get(name) {
             // In a real implementation, this would play the sound
             // using an audio library like rodio
-            println!("Playing sound: {}", name);
+            debug!("Playing sound: {} (volume {:.2})", name, volume);
         }
         
         Ok(())
@@ -90,14 +133,16 @@ impl AudioSystem {
     
     /// Play a music track
     pub fn play_music(&mut self, name: &str, fade_in: Option<f32>, loop_music: bool) -> Result<()> {
-        if !self.music_enabled {
+        if !self.master_enabled || !self.music_enabled {
+            // Remember the track so it starts when music is unmuted
+            self.current_music = Some(name.to_string());
             return Ok(());
         }
         
         if let Some(music) = self.music_tracks.get(name) {
             // In a real implementation, this would play the music
             // using an audio library like rodio
-            println!("Playing music: {}", name);
+            debug!("Playing music: {}", name);
             self.current_music = Some(name.to_string());
         }
         
@@ -123,6 +168,11 @@ impl AudioSystem {
         Ok(())
     }
     
+    /// Set the volume applied on top of both music and sound effects
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0).min(1.0);
+    }
+    
     /// Set sound effect volume
     pub fn set_sound_volume(&mut self, volume: f32) {
         self.sound_volume = volume.max(0.0).min(1.0);
@@ -133,6 +183,15 @@ impl AudioSystem {
         self.music_volume = volume.max(0.0).min(1.0);
     }
     
+    /// Mute or unmute all audio
+    pub fn set_master_enabled(&mut self, enabled: bool) {
+        if self.master_enabled == enabled {
+            return;
+        }
+        self.master_enabled = enabled;
+        self.refresh_music();
+    }
+    
     /// Enable or disable sound effects
     pub fn set_sound_enabled(&mut self, enabled: bool) {
         self.sound_enabled = enabled;
@@ -140,17 +199,65 @@ impl AudioSystem {
     
     /// Enable or disable music
     pub fn set_music_enabled(&mut self, enabled: bool) {
+        if self.music_enabled == enabled {
+            return;
+        }
         self.music_enabled = enabled;
-        
-        if !enabled {
-            // Stop current music if disabling
-            let _ = self.stop_music(Some(0.5));
-        } else if let Some(track) = &self.current_music {
-            // Resume current music if enabling
-            let _ = self.play_music(track, Some(0.5), true);
+        self.refresh_music();
+    }
+    
+    /// Stop or resume the current track after a mute change
+    fn refresh_music(&mut self) {
+        if let Some(track) = self.current_music.clone() {
+            if self.master_enabled && self.music_enabled {
+                // Resume current music if enabling
+                let _ = self.play_music(&track, Some(0.5), true);
+            } else {
+                // Stop current music if disabling, but keep the track to resume later
+                let _ = self.stop_music(Some(0.5));
+                self.current_music = Some(track);
+            }
         }
     }
     
+    /// Apply the volume and mute options from the settings menu
+    pub fn apply_settings(&mut self, settings: &AudioSettings) {
+        self.set_master_volume(settings.master_volume);
+        self.set_music_volume(settings.music_volume);
+        self.set_sound_volume(settings.sfx_volume);
+        self.set_master_enabled(!settings.master_muted);
+        self.set_music_enabled(!settings.music_muted);
+        self.set_sound_enabled(!settings.sfx_muted);
+    }
+    
+    /// Move the listener, usually to the camera center
+    pub fn set_listener_position(&mut self, position: (f32, f32)) {
+        self.listener.position = position;
+    }
+    
+    /// Sound effect volume after the master volume is applied
+    pub fn effective_sound_volume(&self) -> f32 {
+        self.master_volume * self.sound_volume
+    }
+    
+    /// Music volume after the master volume is applied
+    pub fn effective_music_volume(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+    
+    /// Volume multiplier for a sound played at `position`
+    fn attenuation(&self, position: (f32, f32)) -> f32 {
+        let dx = position.0 - self.listener.position.0;
+        let dy = position.1 - self.listener.position.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+        (1.0 - distance / MAX_HEARING_DISTANCE).max(0.0)
+    }
+    
+    /// Get the current master volume
+    pub fn get_master_volume(&self) -> f32 {
+        self.master_volume
+    }
+    
     /// Get the current sound volume
     pub fn get_sound_volume(&self) -> f32 {
         self.sound_volume
@@ -174,7 +281,7 @@ impl AudioSystem {
     /// Play a UI sound (button click, menu navigation, etc.)
     pub fn play_ui_sound(&self, sound_type: UiSoundType) -> Result<()> {
         match sound_type {
-            UiSoundType::ButtonClick => self.play_sound("ui_click", 1.0, 1.0, None),
+            UiSoundType::ButtonClick => self.play_sound("sfx_click", 1.0, 1.0, None),
             UiSoundType::ButtonHover => self.play_sound("ui_hover", 0.7, 1.0, None),
             UiSoundType::MenuOpen => self.play_sound("ui_open", 1.0, 1.0, None),
             UiSoundType::MenuClose => self.play_sound("ui_close", 1.0, 1.0, None),
@@ -185,12 +292,15 @@ impl AudioSystem {
     /// Play a game sound at a specific position
     pub fn play_game_sound(&self, sound_type: GameSoundType, position: (f32, f32)) -> Result<()> {
         match sound_type {
-            GameSoundType::UnitSelect => self.play_sound("unit_select", 1.0, 1.0, Some(position)),
-            GameSoundType::UnitMove => self.play_sound("unit_move", 1.0, 1.0, Some(position)),
-            GameSoundType::UnitAttack => self.play_sound("unit_attack", 1.0, 1.0, Some(position)),
-            GameSoundType::BuildingPlace => self.play_sound("building_place", 1.0, 1.0, Some(position)),
+            // Selection, order and construction acknowledgements are heard wherever the units are
+            GameSoundType::UnitSelect => self.play_sound("sfx_select", 1.0, 1.0, None),
+            GameSoundType::UnitMove => self.play_sound("sfx_move", 1.0, 1.0, None),
+            GameSoundType::UnitAttack => self.play_sound("sfx_attack", 1.0, 1.0, None),
+            GameSoundType::CombatHit => self.play_sound("sfx_attack", 0.6, 1.0, Some(position)),
+            GameSoundType::BuildingPlace => self.play_sound("sfx_build", 1.0, 1.0, Some(position)),
+            GameSoundType::ConstructionComplete => self.play_sound("sfx_build", 1.0, 1.2, None),
             GameSoundType::ResourceCollect => self.play_sound("resource_collect", 0.8, 1.0, Some(position)),
-            GameSoundType::Explosion => self.play_sound("explosion", 1.0, 1.0, Some(position)),
+            GameSoundType::Explosion => self.play_sound("sfx_explosion", 1.0, 1.0, Some(position)),
         }
     }
     
@@ -202,6 +312,7 @@ impl AudioSystem {
 }
 
 /// Types of UI sounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UiSoundType {
     ButtonClick,
     ButtonHover,
@@ -211,11 +322,14 @@ pub enum UiSoundType {
 }
 
 /// Types of game sounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameSoundType {
     UnitSelect,
-    UnitMove,
-    UnitAttack,
+    UnitMove,       // Move order acknowledged
+    UnitAttack,     // Attack order acknowledged
+    CombatHit,
     BuildingPlace,
+    ConstructionComplete,
     ResourceCollect,
     Explosion,
 }
@@ -1,10 +1,37 @@
 use glam::Vec2;
+use serde::{Serialize, Deserialize};
 use crate::ecs::resources::CameraState;
 
+/// Half the width of the view at zoom 1, in world units; matches the renderer's projection
+pub const BASE_HALF_VIEW_WIDTH: f32 = 400.0;
+
+/// How quickly the zoom closes in on its target, per second
+const ZOOM_SMOOTHING: f32 = 12.0;
+
+/// Camera scrolling options chosen in the settings menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub edge_scroll_enabled: bool,
+    pub edge_scroll_margin: f32, // Pixels from the window edge that start scrolling
+    pub scroll_speed: f32,       // World units per second at zoom 1
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            edge_scroll_enabled: true,
+            edge_scroll_margin: 20.0,
+            scroll_speed: 400.0,
+        }
+    }
+}
+
 /// Camera controller for the game view
 pub struct CameraController {
     pub position: Vec2,
     pub zoom: f32,
+    pub target_zoom: f32,
     pub view_width: f32,
     pub view_height: f32,
     pub world_width: f32,
@@ -13,78 +40,160 @@ pub struct CameraController {
     pub zoom_speed: f32,
     pub min_zoom: f32,
     pub max_zoom: f32,
+    pub edge_scroll_enabled: bool,
+    pub edge_scroll_margin: f32,
+    zoom_anchor: Vec2,         // Screen point kept still while zooming
+    drag_anchor: Option<Vec2>, // World point held under the cursor while drag panning
 }
 
 impl CameraController {
     pub fn new(world_width: f32, world_height: f32, view_width: f32, view_height: f32) -> Self {
+        let settings = CameraSettings::default();
+        
         Self {
             position: Vec2::new(world_width / 2.0, world_height / 2.0),
             zoom: 1.0,
+            target_zoom: 1.0,
             view_width,
             view_height,
             world_width,
             world_height,
-            movement_speed: 200.0,
+            movement_speed: settings.scroll_speed,
             zoom_speed: 0.1,
             min_zoom: 0.5,
             max_zoom: 2.0,
+            edge_scroll_enabled: settings.edge_scroll_enabled,
+            edge_scroll_margin: settings.edge_scroll_margin,
+            zoom_anchor: Vec2::new(view_width / 2.0, view_height / 2.0),
+            drag_anchor: None,
         }
     }
     
+    /// Use the player's scrolling options
+    pub fn apply_settings(&mut self, settings: &CameraSettings) {
+        self.edge_scroll_enabled = settings.edge_scroll_enabled;
+        self.edge_scroll_margin = settings.edge_scroll_margin;
+        self.movement_speed = settings.scroll_speed;
+    }
+    
+    /// Set the size of the map the camera is kept over
+    pub fn set_world_size(&mut self, world_width: f32, world_height: f32) {
+        self.world_width = world_width;
+        self.world_height = world_height;
+        self.clamp_to_world();
+    }
+    
     /// Update camera position and zoom
     pub fn update(&mut self, delta_time: f32) {
-        // Add any physics or smoothing update here if needed
-        // Clamp position to world bounds
-        let half_view_width = self.view_width / (2.0 * self.zoom);
-        let half_view_height = self.view_height / (2.0 * self.zoom);
-        
-        self.position.x = self.position.x.clamp(
-            half_view_width,
-            self.world_width - half_view_width,
-        );
+        // Ease towards the target zoom, keeping the point under the cursor still
+        if (self.target_zoom - self.zoom).abs() > 0.001 {
+            let blend = 1.0 - (-ZOOM_SMOOTHING * delta_time).exp();
+            let zoom = self.zoom + (self.target_zoom - self.zoom) * blend;
+            self.set_zoom_at(zoom, self.zoom_anchor);
+        } else if self.zoom != self.target_zoom {
+            self.set_zoom_at(self.target_zoom, self.zoom_anchor);
+        }
         
-        self.position.y = self.position.y.clamp(
-            half_view_height,
-            self.world_height - half_view_height,
-        );
+        self.clamp_to_world();
     }
     
-    /// Move camera by direction vector
-    pub fn move_camera(&mut self, direction: Vec2) {
+    /// Keep the view center on the map, so its edges can still be brought to the middle of the screen
+    fn clamp_to_world(&mut self) {
+        self.position.x = self.position.x.clamp(0.0, self.world_width.max(0.0));
+        self.position.y = self.position.y.clamp(0.0, self.world_height.max(0.0));
+    }
+    
+    /// Move camera by direction vector over `delta_time` seconds
+    pub fn move_camera(&mut self, direction: Vec2, delta_time: f32) {
         let speed = self.movement_speed / self.zoom; // Adjust speed based on zoom level
-        self.position += direction * speed;
+        self.position += direction * speed * delta_time;
+        self.clamp_to_world();
     }
     
-    /// Zoom camera by delta amount
-    pub fn zoom_camera(&mut self, delta: f32) {
-        self.zoom = (self.zoom + delta * self.zoom_speed).clamp(self.min_zoom, self.max_zoom);
+    /// Scroll direction for a cursor resting near the window edges, in world axes
+    pub fn edge_scroll_direction(&self, mouse_position: Vec2) -> Vec2 {
+        if !self.edge_scroll_enabled || self.drag_anchor.is_some() {
+            return Vec2::ZERO;
+        }
+        
+        let mut direction = Vec2::ZERO;
+        if mouse_position.x < self.edge_scroll_margin {
+            direction.x = -1.0;
+        } else if mouse_position.x > self.view_width - self.edge_scroll_margin {
+            direction.x = 1.0;
+        }
+        
+        // Screen y grows downwards, world y upwards
+        if mouse_position.y < self.edge_scroll_margin {
+            direction.y = 1.0;
+        } else if mouse_position.y > self.view_height - self.edge_scroll_margin {
+            direction.y = -1.0;
+        }
+        
+        direction
+    }
+    
+    /// Zoom camera by delta amount, towards the given screen position
+    pub fn zoom_camera(&mut self, delta: f32, screen_pos: Vec2) {
+        self.target_zoom = (self.target_zoom * (1.0 + delta * self.zoom_speed)).clamp(self.min_zoom, self.max_zoom);
+        self.zoom_anchor = screen_pos;
+    }
+    
+    /// Change zoom immediately while keeping the world point under `screen_pos` in place
+    fn set_zoom_at(&mut self, zoom: f32, screen_pos: Vec2) {
+        let before = self.screen_to_world(screen_pos);
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+        let after = self.screen_to_world(screen_pos);
+        self.position += before - after;
+    }
+    
+    /// Grab the world point under the cursor for middle-mouse panning
+    pub fn begin_drag(&mut self, screen_pos: Vec2) {
+        self.drag_anchor = Some(self.screen_to_world(screen_pos));
+    }
+    
+    /// Move the camera so the grabbed point follows the cursor
+    pub fn drag_to(&mut self, screen_pos: Vec2) {
+        if let Some(anchor) = self.drag_anchor {
+            self.position += anchor - self.screen_to_world(screen_pos);
+            self.clamp_to_world();
+        }
+    }
+    
+    pub fn end_drag(&mut self) {
+        self.drag_anchor = None;
+    }
+    
+    pub fn is_dragging(&self) -> bool {
+        self.drag_anchor.is_some()
+    }
+    
+    /// Half the size of the visible area in world units
+    fn half_view_extent(&self) -> Vec2 {
+        let half_width = BASE_HALF_VIEW_WIDTH / self.zoom;
+        Vec2::new(half_width, half_width * self.view_height / self.view_width)
     }
     
     /// Convert screen coordinates to world coordinates
     pub fn screen_to_world(&self, screen_pos: Vec2) -> Vec2 {
-        let half_view_width = self.view_width / 2.0;
-        let half_view_height = self.view_height / 2.0;
-        
-        let screen_center = Vec2::new(half_view_width, half_view_height);
-        let screen_to_center = screen_pos - screen_center;
+        let half_extent = self.half_view_extent();
+        let normalized = screen_pos / Vec2::new(self.view_width, self.view_height); // 0..1, y down
         
-        // Scale by zoom and add camera position
-        let world_pos = self.position + screen_to_center / self.zoom;
-        
-        world_pos
+        Vec2::new(
+            self.position.x + (normalized.x * 2.0 - 1.0) * half_extent.x,
+            self.position.y + (1.0 - normalized.y * 2.0) * half_extent.y,
+        )
     }
     
     /// Convert world coordinates to screen coordinates
     pub fn world_to_screen(&self, world_pos: Vec2) -> Vec2 {
-        let half_view_width = self.view_width / 2.0;
-        let half_view_height = self.view_height / 2.0;
-        
-        let world_to_camera = world_pos - self.position;
+        let half_extent = self.half_view_extent();
+        let relative = (world_pos - self.position) / half_extent; // -1..1, y up
         
-        // Scale by zoom and add screen center
-        let screen_pos = Vec2::new(half_view_width, half_view_height) + world_to_camera * self.zoom;
-        
-        screen_pos
+        Vec2::new(
+            (relative.x + 1.0) / 2.0 * self.view_width,
+            (1.0 - relative.y) / 2.0 * self.view_height,
+        )
     }
     
     /// Resize the view
@@ -108,13 +217,15 @@ impl CameraController {
     
     /// Calculate the view-projection matrix for rendering
     pub fn calculate_view_projection_matrix(&self) -> glam::Mat4 {
-        // First, create orthographic projection matrix
-        let left = -self.view_width / (2.0 * self.zoom);
-        let right = self.view_width / (2.0 * self.zoom);
-        let bottom = self.view_height / (2.0 * self.zoom);
-        let top = -self.view_height / (2.0 * self.zoom);
-        
-        let ortho = glam::Mat4::orthographic_rh(left, right, bottom, top, -1.0, 1.0);
+        let half_extent = self.half_view_extent();
+        let ortho = glam::Mat4::orthographic_rh(
+            -half_extent.x,
+            half_extent.x,
+            -half_extent.y,
+            half_extent.y,
+            -100.0,
+            100.0,
+        );
         
         // Then, create view matrix (camera transform)
         let view = glam::Mat4::from_translation(glam::Vec3::new(-self.position.x, -self.position.y, 0.0));
@@ -125,19 +236,7 @@ impl CameraController {
     
     /// Get visible world bounds
     pub fn get_visible_bounds(&self) -> (Vec2, Vec2) {
-        let half_view_width = self.view_width / (2.0 * self.zoom);
-        let half_view_height = self.view_height / (2.0 * self.zoom);
-        
-        let min = Vec2::new(
-            self.position.x - half_view_width,
-            self.position.y - half_view_height,
-        );
-        
-        let max = Vec2::new(
-            self.position.x + half_view_width,
-            self.position.y + half_view_height,
-        );
-        
-        (min, max)
+        let half_extent = self.half_view_extent();
+        (self.position - half_extent, self.position + half_extent)
     }
-}
\ No newline at end of file
+}
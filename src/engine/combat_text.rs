@@ -0,0 +1,132 @@
+use bevy_ecs::entity::Entity;
+use glam::Vec2;
+
+use crate::ecs::resources::{CombatText, CombatTextKind};
+use crate::engine::sprites::SpriteInstance;
+
+/// Glyph atlas combat text is drawn from: one row of equal cells holding these characters in order
+pub const COMBAT_TEXT_TEXTURE: &str = "combat_text";
+const GLYPHS: &str = "0123456789+-!MIS";
+
+/// Seconds a floater stays on screen
+const FLOATER_LIFETIME: f32 = 1.0;
+
+/// World units a floater rises over its lifetime
+const FLOATER_RISE: f32 = 16.0;
+
+/// Heals landing on a target this soon after its last heal floater appeared add to it instead of
+/// starting another, so a healer's steady trickle reads as one number
+const HEAL_MERGE_TIME: f32 = 0.5;
+
+/// World size of one glyph; critical hits are drawn larger
+const GLYPH_SIZE: Vec2 = Vec2::new(3.0, 4.0);
+const CRITICAL_SCALE: f32 = 1.5;
+
+/// Combat text on its way up from where it landed
+#[derive(Debug, Clone)]
+pub struct Floater {
+    pub kind: CombatTextKind,
+    pub target: Entity,
+    pub position: Vec2, // Where it started
+    pub amount: f32,
+    pub age: f32, // Seconds
+}
+
+impl Floater {
+    /// What the floater reads; fractions of a point still show as 1
+    pub fn text(&self) -> String {
+        let amount = self.amount.ceil().max(1.0) as u32;
+        match self.kind {
+            CombatTextKind::Damage => format!("{}", amount),
+            CombatTextKind::Critical => format!("{}!", amount),
+            CombatTextKind::Heal => format!("+{}", amount),
+            CombatTextKind::Miss => "MISS".to_string(),
+        }
+    }
+    
+    /// Color, fading out over the second half of the floater's life
+    pub fn color(&self) -> [f32; 4] {
+        let alpha = (2.0 * (1.0 - self.age / FLOATER_LIFETIME)).clamp(0.0, 1.0);
+        match self.kind {
+            CombatTextKind::Damage => [1.0, 1.0, 1.0, alpha],
+            CombatTextKind::Critical => [1.0, 0.6, 0.1, alpha],
+            CombatTextKind::Heal => [0.3, 1.0, 0.3, alpha],
+            CombatTextKind::Miss => [0.7, 0.7, 0.7, alpha],
+        }
+    }
+    
+    /// Center of the text this frame
+    pub fn current_position(&self) -> Vec2 {
+        self.position + Vec2::new(0.0, FLOATER_RISE * self.age / FLOATER_LIFETIME)
+    }
+    
+    /// One sprite per character, centered on the floater, sampling the glyph atlas
+    pub fn glyphs(&self) -> Vec<SpriteInstance> {
+        let text = self.text();
+        let size = if self.kind == CombatTextKind::Critical { GLYPH_SIZE * CRITICAL_SCALE } else { GLYPH_SIZE };
+        let cell = Vec2::new(1.0 / GLYPHS.len() as f32, 1.0);
+        let center = self.current_position();
+        let left = center.x - (text.len() as f32 - 1.0) * size.x * 0.5;
+        let color = self.color();
+        
+        text.chars()
+            .enumerate()
+            .filter_map(|(index, character)| {
+                let glyph = GLYPHS.find(character)?;
+                let position = Vec2::new(left + index as f32 * size.x, center.y);
+                let instance = SpriteInstance::new(position, size, 0.0, color);
+                Some(instance.with_region(Vec2::new(glyph as f32 * cell.x, 0.0), cell))
+            })
+            .collect()
+    }
+}
+
+/// Floating combat text currently on screen
+#[derive(Debug, Default)]
+pub struct FloatingText {
+    floaters: Vec<Floater>,
+}
+
+impl FloatingText {
+    /// Start floaters for combat text raised by the simulation
+    pub fn add(&mut self, texts: impl IntoIterator<Item = CombatText>) {
+        for text in texts {
+            if text.kind == CombatTextKind::Heal {
+                if text.amount <= 0.0 {
+                    continue;
+                }
+                let recent = self.floaters.iter_mut().find(|floater| {
+                    floater.kind == CombatTextKind::Heal && floater.target == text.target && floater.age < HEAL_MERGE_TIME
+                });
+                if let Some(floater) = recent {
+                    floater.amount += text.amount;
+                    continue;
+                }
+            }
+            
+            self.floaters.push(Floater {
+                kind: text.kind,
+                target: text.target,
+                position: text.position,
+                amount: text.amount,
+                age: 0.0,
+            });
+        }
+    }
+    
+    /// Age every floater by a frame, dropping those that have faded out
+    pub fn update(&mut self, delta_time: f32) {
+        for floater in &mut self.floaters {
+            floater.age += delta_time;
+        }
+        self.floaters.retain(|floater| floater.age < FLOATER_LIFETIME);
+    }
+    
+    pub fn clear(&mut self) {
+        self.floaters.clear();
+    }
+    
+    pub fn floaters(&self) -> &[Floater] {
+        &self.floaters
+    }
+}
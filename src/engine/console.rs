@@ -0,0 +1,283 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::CommandQueue;
+use glam::Vec2;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Engine;
+use super::logging;
+use crate::ecs::components::{Building, BuildingType, Owner, Resource, ResourceType, Selected, Unit, UnitType};
+use crate::ecs::resources::{GameMap, GameTime, PlayerResources, TechState};
+use crate::game::stats::GameStats;
+use crate::game::units;
+
+/// Most units one spawn command creates
+const MAX_SPAWN_COUNT: u32 = 50;
+
+/// Spacing between units spawned by one command
+const SPAWN_SPACING: f32 = 12.0;
+
+/// A developer console command
+pub struct ConsoleCommand {
+    pub name: &'static str,
+    pub usage: &'static str, // Arguments, shown by help
+    pub description: &'static str,
+    run: fn(&mut Engine, &[&str]) -> Result<String>,
+}
+
+/// Every console command, in the order help lists them
+pub const COMMANDS: &[ConsoleCommand] = &[
+    ConsoleCommand {
+        name: "help",
+        usage: "",
+        description: "List the console commands",
+        run: help,
+    },
+    ConsoleCommand {
+        name: "spawn",
+        usage: "<unit> [count] [player]",
+        description: "Spawn units at the mouse cursor",
+        run: spawn,
+    },
+    ConsoleCommand {
+        name: "give",
+        usage: "<amount> [mineral|gas|energy] [player]",
+        description: "Grant resources, of every type unless one is named",
+        run: give,
+    },
+    ConsoleCommand {
+        name: "reveal",
+        usage: "",
+        description: "Mark the whole map as explored",
+        run: reveal,
+    },
+    ConsoleCommand {
+        name: "fog",
+        usage: "",
+        description: "Turn fog of war on or off",
+        run: toggle_fog,
+    },
+    ConsoleCommand {
+        name: "speed",
+        usage: "<multiplier>",
+        description: "Set the game speed, beyond the limits of the speed keys",
+        run: set_speed,
+    },
+    ConsoleCommand {
+        name: "kill",
+        usage: "",
+        description: "Destroy the selected units and buildings",
+        run: kill_selection,
+    },
+    ConsoleCommand {
+        name: "stats",
+        usage: "",
+        description: "Show entity and archetype counts",
+        run: ecs_stats,
+    },
+    ConsoleCommand {
+        name: "log",
+        usage: "[net|ai|combat|render|all]",
+        description: "Show recent warnings and errors, from one subsystem or all of them",
+        run: show_log,
+    },
+];
+
+/// Run a line typed into the console and return its output
+pub fn execute(engine: &mut Engine, line: &str) -> String {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name.to_lowercase(),
+        None => return String::new(),
+    };
+    let args: Vec<&str> = words.collect();
+    
+    match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => (command.run)(engine, &args).unwrap_or_else(|e| format!("Error: {}", e)),
+        None => format!("Unknown command '{}', type help for a list of commands", name),
+    }
+}
+
+fn help(_engine: &mut Engine, _args: &[&str]) -> Result<String> {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| format!("{} {} - {}", command.name, command.usage, command.description))
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn spawn(engine: &mut Engine, args: &[&str]) -> Result<String> {
+    let name = args.first().ok_or_else(|| anyhow::anyhow!("usage: spawn <unit> [count] [player]"))?;
+    let unit_type = parse_unit_type(name)?;
+    let count: u32 = args.get(1).map_or(Ok(1), |count| count.parse())?;
+    let count = count.clamp(1, MAX_SPAWN_COUNT);
+    let player_id = parse_player(engine, args.get(2))?;
+    
+    // Rows of ten centered on the cursor
+    let center = engine.input_handler.get_world_mouse_position();
+    let mut queue = CommandQueue::default();
+    {
+        let tech_state = engine.world.resource::<TechState>();
+        let mut commands = Commands::new(&mut queue, &engine.world);
+        for index in 0..count {
+            let row_length = count.min(10);
+            let column = (index % 10) as f32 - (row_length as f32 - 1.0) / 2.0;
+            let row = (index / 10) as f32;
+            units::spawn_unit(
+                &mut commands,
+                units::UnitSpawnParams {
+                    unit_type,
+                    owner: player_id,
+                    position: center + Vec2::new(column, row) * SPAWN_SPACING,
+                },
+                tech_state,
+            );
+        }
+    }
+    queue.apply(&mut engine.world);
+    
+    Ok(format!("Spawned {} {:?} for player {}", count, unit_type, player_id))
+}
+
+fn give(engine: &mut Engine, args: &[&str]) -> Result<String> {
+    let amount: f32 = args
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("usage: give <amount> [mineral|gas|energy] [player]"))?
+        .parse()?;
+    let resource_types = match args.get(1) {
+        Some(name) => vec![parse_resource_type(name)?],
+        None => vec![ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy],
+    };
+    let player_id = parse_player(engine, args.get(2))?;
+    
+    let mut player_resources = engine.world.get_resource_or_insert_with(PlayerResources::default);
+    for &resource_type in &resource_types {
+        *player_resources.resources.entry((player_id, resource_type)).or_insert(0.0) += amount;
+    }
+    
+    Ok(format!("Gave player {} {} of {:?}", player_id, amount, resource_types))
+}
+
+fn reveal(engine: &mut Engine, _args: &[&str]) -> Result<String> {
+    let map = engine.world.get_resource::<GameMap>().ok_or_else(|| anyhow::anyhow!("No map is loaded"))?;
+    engine.renderer.reveal_map(map);
+    engine.ui_manager.reveal_minimap(map);
+    Ok("Map revealed".to_string())
+}
+
+fn toggle_fog(engine: &mut Engine, _args: &[&str]) -> Result<String> {
+    // apply_settings hands the change to the renderer and minimap next frame
+    let settings = &mut engine.game_state.settings;
+    settings.fog_of_war_enabled = !settings.fog_of_war_enabled;
+    Ok(format!("Fog of war {}", if settings.fog_of_war_enabled { "on" } else { "off" }))
+}
+
+fn set_speed(engine: &mut Engine, args: &[&str]) -> Result<String> {
+    let speed: f32 = args.first().ok_or_else(|| anyhow::anyhow!("usage: speed <multiplier>"))?.parse()?;
+    if !(0.1..=10.0).contains(&speed) {
+        return Err(anyhow::anyhow!("Speed must be between 0.1 and 10"));
+    }
+    
+    engine.game_state.settings.game_speed = speed;
+    Ok(format!("Game speed: {}x", speed))
+}
+
+fn kill_selection(engine: &mut Engine, _args: &[&str]) -> Result<String> {
+    let mut query = engine.world.query_filtered::<(Entity, &Owner, Option<&Unit>, Option<&Building>), With<Selected>>();
+    let mut selected: Vec<(Entity, u8, Option<UnitType>, Option<BuildingType>)> = query
+        .iter(&engine.world)
+        .map(|(entity, owner, unit, building)| {
+            (entity, owner.0, unit.map(|unit| unit.unit_type), building.map(|building| building.building_type))
+        })
+        .collect();
+    selected.sort_by_key(|(entity, ..)| *entity);
+    
+    let mut stats = engine.world.get_resource_or_insert_with(GameStats::default);
+    for &(_, owner, unit_type, building_type) in &selected {
+        if let Some(unit_type) = unit_type {
+            stats.unit_destroyed(owner, unit_type, None);
+        } else if let Some(building_type) = building_type {
+            stats.building_destroyed(owner, building_type, None);
+        }
+    }
+    
+    for &(entity, ..) in &selected {
+        engine.world.despawn(entity);
+    }
+    Ok(format!("Destroyed {} entities", selected.len()))
+}
+
+fn ecs_stats(engine: &mut Engine, _args: &[&str]) -> Result<String> {
+    let tick = engine.world.get_resource::<GameTime>().map_or(0, |time| time.current_tick);
+    let mut lines = vec![
+        format!("Tick {}", tick),
+        format!("Entities: {}", engine.world.entities().len()),
+        format!("Archetypes: {}", engine.world.archetypes().len()),
+    ];
+    
+    let mut units: BTreeMap<u8, usize> = BTreeMap::new();
+    let mut unit_query = engine.world.query::<(&Unit, &Owner)>();
+    for (_, owner) in unit_query.iter(&engine.world) {
+        *units.entry(owner.0).or_insert(0) += 1;
+    }
+    let mut buildings: BTreeMap<u8, usize> = BTreeMap::new();
+    let mut building_query = engine.world.query::<(&Building, &Owner)>();
+    for (_, owner) in building_query.iter(&engine.world) {
+        *buildings.entry(owner.0).or_insert(0) += 1;
+    }
+    for player_id in units.keys().chain(buildings.keys()).copied().collect::<BTreeSet<u8>>() {
+        lines.push(format!(
+            "Player {}: {} units, {} buildings",
+            player_id,
+            units.get(&player_id).copied().unwrap_or(0),
+            buildings.get(&player_id).copied().unwrap_or(0),
+        ));
+    }
+    
+    let mut resource_query = engine.world.query::<&Resource>();
+    lines.push(format!("Resource nodes: {}", resource_query.iter(&engine.world).count()));
+    
+    Ok(lines.join("\n"))
+}
+
+fn show_log(engine: &mut Engine, args: &[&str]) -> Result<String> {
+    let target = match args.first().map(|arg| arg.to_lowercase()) {
+        None => None,
+        Some(arg) if arg == "all" => None,
+        Some(arg) if logging::TARGETS.contains(&arg.as_str()) => Some(arg),
+        Some(arg) => return Err(anyhow::anyhow!("Unknown subsystem '{}', expected one of {}", arg, logging::TARGETS.join(", "))),
+    };
+    
+    let message = match &target {
+        Some(target) => format!("Showing warnings and errors from {}", target),
+        None => "Showing warnings and errors from every subsystem".to_string(),
+    };
+    engine.ui_manager.set_log_viewer_target(target);
+    engine.ui_manager.set_log_viewer_visible(true);
+    Ok(message)
+}
+
+/// Unit type from its name, e.g. "tank"
+fn parse_unit_type(name: &str) -> Result<UnitType> {
+    UnitType::ALL
+        .iter()
+        .copied()
+        .find(|unit_type| format!("{:?}", unit_type).eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow::anyhow!("Unknown unit type '{}'", name))
+}
+
+/// Resource type from its name, e.g. "gas"
+fn parse_resource_type(name: &str) -> Result<ResourceType> {
+    [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy]
+        .into_iter()
+        .find(|resource_type| format!("{:?}", resource_type).eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow::anyhow!("Unknown resource type '{}'", name))
+}
+
+/// Player ID argument, defaulting to the local player
+fn parse_player(engine: &Engine, arg: Option<&&str>) -> Result<u8> {
+    match arg {
+        Some(arg) => Ok(arg.parse()?),
+        None => Ok(engine.local_player_id()),
+    }
+}
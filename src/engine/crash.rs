@@ -0,0 +1,70 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game::savegame::{self, AUTOSAVE_SLOT};
+
+/// Where crash logs are written, next to the save directory
+const CRASH_DIRECTORY: &str = "crashes";
+
+/// The latest autosave, encoded as a save file, for the panic hook to write out
+static LAST_AUTOSAVE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// Keep the latest autosave so a crash can write it out even if autosaving to disk is off
+pub fn remember_autosave(data: Vec<u8>) {
+    if let Ok(mut last_autosave) = LAST_AUTOSAVE.lock() {
+        *last_autosave = Some(data);
+    }
+}
+
+/// Write a crash log, and the latest autosave to the autosave slot, when anything panics. The default
+/// hook still prints the panic to stderr afterwards
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let location = info.location().map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()));
+        match write_crash_log(&message, location.as_deref()) {
+            Ok(path) => eprintln!("Wrote a crash log to {}", path.display()),
+            Err(e) => eprintln!("Failed to write a crash log: {}", e),
+        }
+        
+        // The panic may have struck while the autosave was being replaced, leaving it locked
+        let autosave = LAST_AUTOSAVE.try_lock().ok().and_then(|mut last_autosave| last_autosave.take());
+        if let Some(data) = autosave {
+            match savegame::write_save_file(AUTOSAVE_SLOT, &data) {
+                Ok(()) => eprintln!("Saved the last autosave to {}", savegame::save_slot_path(AUTOSAVE_SLOT).display()),
+                Err(e) => eprintln!("Failed to save the last autosave: {}", e),
+            }
+        }
+        
+        default_hook(info);
+    }));
+}
+
+/// The panic message, where it happened and the backtrace, in a file named after the time of the crash
+fn write_crash_log(message: &str, location: Option<&str>) -> std::io::Result<PathBuf> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut log = String::new();
+    let _ = writeln!(log, "Rusty Autobattler {} crashed at {} (seconds since 1970)", env!("CARGO_PKG_VERSION"), seconds);
+    let _ = writeln!(log, "Thread: {}", std::thread::current().name().unwrap_or("unnamed"));
+    let _ = writeln!(log, "Panic: {}", message);
+    if let Some(location) = location {
+        let _ = writeln!(log, "At: {}", location);
+    }
+    let _ = writeln!(log, "\n{}", Backtrace::force_capture());
+    
+    fs::create_dir_all(CRASH_DIRECTORY)?;
+    let path = PathBuf::from(CRASH_DIRECTORY).join(format!("crash_{}.log", seconds));
+    fs::write(&path, log)?;
+    Ok(path)
+}
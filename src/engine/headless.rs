@@ -0,0 +1,205 @@
+use anyhow::Result;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::time::TimeSystem;
+use super::{build_network_game, init_game_world, print_game_stats};
+use crate::ecs::resources::{AttackAlerts, AudioEvents, CombatTextEvents, GameTime, PlayerAlerts, TICK_RATE};
+use crate::game::data;
+use crate::game::savegame;
+use crate::game::map::MapGenerationParams;
+use crate::game::settings;
+use crate::game::simulation::Simulation;
+use crate::game::victory::{VictoryOutcome, VictoryState};
+use crate::game::{GamePhase, GameState};
+use crate::networking::lockstep::{LockstepNetwork, CHECKSUM_INTERVAL};
+use crate::networking::pause::PauseState;
+use crate::networking::TransportKind;
+
+/// How long the host sleeps between polls when there is nothing to do
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// Dedicated server: hosts a lobby and runs the lockstep simulation with no window, renderer, UI or audio.
+/// The game starts as soon as every player in the lobby is ready, and the host exits when it ends.
+pub struct HeadlessHost {
+    simulation: Simulation,
+    game_state: GameState,
+    network: LockstepNetwork,
+    time_system: TimeSystem,
+    stalled: bool, // Waiting on players' lockstep input
+}
+
+impl HeadlessHost {
+    /// Load the game data and open a lobby on the given port and transport, forwarding the port with
+    /// UPnP if asked to; lobby options come from the settings file
+    pub fn new(port: u16, transport: TransportKind, upnp: bool) -> Result<Self> {
+        data::init(data::DEFAULT_DATA_DIR)?;
+        
+        let mut game_state = GameState::new();
+        settings::load_settings(&mut game_state.settings);
+        game_state.settings.network_transport = transport;
+        game_state.settings.upnp = upnp;
+        
+        let mut network = LockstepNetwork::new(game_state.settings.network_transport);
+        network.host_dedicated(port, game_state.settings.player_name.clone())?;
+        game_state.is_multiplayer = true;
+        
+        info!(target: "net", "Dedicated host listening on port {}", port);
+        if game_state.settings.upnp {
+            match network.open_port_mapping() {
+                Ok(address) => info!(target: "net", "Players outside the local network can join at {}", address),
+                Err(e) => warn!(target: "net", "Could not forward port {} with UPnP: {}", port, e),
+            }
+        }
+        Ok(Self {
+            simulation: Simulation::new(init_game_world()),
+            game_state,
+            network,
+            time_system: TimeSystem::new(TICK_RATE as f64),
+            stalled: false,
+        })
+    }
+    
+    /// Serve the lobby and the game until it ends or every player has left
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            self.network.process_messages()?;
+            for message in self.network.take_new_chat() {
+                info!(target: "net", "[chat] player {}: {}", message.player_id, message.text);
+            }
+            
+            if !self.network.in_game() {
+                self.start_when_ready()?;
+                std::thread::sleep(IDLE_SLEEP);
+                continue;
+            }
+            
+            if let Some(start) = self.network.take_game_start() {
+                build_network_game(&mut self.simulation.world, &mut self.game_state, &start);
+                self.time_system.reset();
+                info!("Started game with {} players (seed {})", start.slots.len(), start.seed);
+            }
+            
+            // Nobody left to simulate for
+            if self.network.lobby_slots().is_empty() {
+                info!("All players left, shutting down");
+                return self.network.leave();
+            }
+            self.resync_if_desynced()?;
+            self.follow_pause();
+            
+            while self.time_system.should_tick() {
+                if self.game_state.phase == GamePhase::Playing && !self.tick()? {
+                    break;
+                }
+                self.time_system.tick_completed();
+            }
+            
+            if self.game_state.phase == GamePhase::GameOver {
+                print_game_stats(&self.simulation.world);
+                return self.network.leave();
+            }
+            
+            std::thread::sleep(IDLE_SLEEP);
+        }
+    }
+    
+    /// Start the game with the settings file's options once every player in the lobby is ready
+    fn start_when_ready(&mut self) -> Result<()> {
+        if !self.network.all_ready() {
+            return Ok(());
+        }
+        
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let settings = &self.game_state.settings;
+        self.network.start_game(
+            seed,
+            MapGenerationParams {
+                symmetry: settings.map_symmetry,
+                ..MapGenerationParams::preset(settings.map_preset)
+            },
+            settings.shared_vision,
+            settings.day_night_cycle,
+            settings.victory_condition,
+        )
+    }
+    
+    /// Hold the simulation while a player has the game paused, and until the resume countdown runs out
+    fn follow_pause(&mut self) {
+        match (self.network.pause_state(), self.game_state.phase) {
+            (PauseState::Paused { player_id, pauses_left }, GamePhase::Playing) => {
+                info!("Player {} paused the game ({} pauses left)", player_id, pauses_left);
+                self.game_state.pause();
+            }
+            (PauseState::Running, GamePhase::Paused) => {
+                info!("Game resumed");
+                self.game_state.resume();
+            }
+            _ => {}
+        }
+    }
+    
+    /// After a desync, send every player our world and carry on from it ourselves; only fails if that can't be done
+    fn resync_if_desynced(&mut self) -> Result<()> {
+        if let Some(desync) = self.network.desync() {
+            warn!(target: "net", "{}; sending our world to every player", desync);
+            let snapshot = savegame::capture_snapshot(&mut self.simulation.world, &self.game_state)?;
+            self.network.send_resync(snapshot)?;
+        }
+        
+        if let Some(snapshot) = self.network.take_resync() {
+            savegame::restore_snapshot(&mut self.simulation.world, &mut self.game_state, &snapshot.data)?;
+            self.simulation.world.resource_mut::<GameTime>().current_tick = snapshot.tick;
+            info!(target: "net", "Resynced every player to our world at tick {} (resync {})", snapshot.tick, snapshot.epoch);
+        }
+        Ok(())
+    }
+    
+    /// Advance the simulation by one lockstep tick; returns false while stalled on players' input
+    fn tick(&mut self) -> Result<bool> {
+        let network_commands = match self.network.receive_commands() {
+            Some(network_commands) => network_commands,
+            None => {
+                if !self.stalled {
+                    info!(target: "net", "Waiting for players {:?}...", self.network.waiting_on());
+                    self.stalled = true;
+                }
+                return Ok(false);
+            }
+        };
+        self.stalled = false;
+        
+        // Keep every RNG roll tied to this game's seed
+        self.simulation.world.resource_mut::<GameTime>().seed = self.game_state.seed;
+        self.simulation.tick(&network_commands);
+        
+        // Nobody is here to hear sounds, read warnings or watch combat text
+        self.simulation.world.resource_mut::<AudioEvents>().events.clear();
+        self.simulation.world.resource_mut::<PlayerAlerts>().alerts.clear();
+        self.simulation.world.resource_mut::<CombatTextEvents>().texts.clear();
+        self.simulation.world.resource_mut::<AttackAlerts>().alerts.clear();
+        
+        // The host's checksum lets players tell which of them has diverged
+        let tick = self.simulation.current_tick();
+        if tick % CHECKSUM_INTERVAL == 0 {
+            let checksum = self.simulation.checksum();
+            self.network.submit_checksum(tick, checksum)?;
+        }
+        self.resync_if_desynced()?;
+        
+        self.game_state.update();
+        
+        if let Some(outcome) = self.simulation.world.get_resource::<VictoryState>().and_then(|victory| victory.outcome()) {
+            self.game_state.end_game(outcome);
+            match outcome {
+                VictoryOutcome::Winner { team, .. } => info!("Game over: team {} wins", team),
+                VictoryOutcome::Draw => info!("Game over: draw"),
+            }
+        }
+        
+        Ok(true)
+    }
+}
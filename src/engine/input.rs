@@ -1,7 +1,27 @@
-use bevy_ecs::prelude::*;
+use anyhow::Result;
 use glam::Vec2;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use winit::window::CursorIcon;
+use tracing::{error, info, warn};
+
+use crate::ecs::components::{BuildingType, NetworkId, Stance};
+use crate::ecs::resources::GameMap;
+use crate::engine::camera::{CameraController, CameraSettings};
+use crate::game::formation::Formation;
+
+/// Maximum gap between two presses of a group key to count as a double-tap
+const GROUP_DOUBLE_TAP_WINDOW: Duration = Duration::from_millis(300);
+
+/// Maximum gap between two clicks to count as a double-click
+const DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(300);
+
+/// How far, in pixels, the second click of a double-click may land from the first
+const DOUBLE_CLICK_DISTANCE: f32 = 5.0;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Command {
@@ -9,28 +29,61 @@ pub enum Command {
     ZoomCamera(f32),
     Select(Vec2),
     MultiSelect(Vec2, Vec2),
+    SelectSameType {
+        position: Vec2,
+        view_min: Vec2, // Visible world area to search
+        view_max: Vec2,
+    },
     Move(Vec2),
+    SmartOrder(Vec2), // Right click: gather, repair, attack, follow, board, garrison or move depending on what is there
     Attack(Vec2),
     Build(BuildingCommand),
     CancelBuild,
     Train(UnitCommand),
-    CancelTrain,
+    CancelTrain(usize), // Production queue slot of the selected building
+    Research(u8),       // Tech type to queue at a research building
+    UpgradeBuilding,    // Start upgrading the selected building into its improved variant
     UseAbility(AbilityCommand),
     Gather(Vec2),
     Patrol(Vec2, Vec2),
     Stop,
+    HoldPosition,
+    SetStance(Stance),
     SetRallyPoint(Vec2),
     GroupAssign(u8),
     GroupSelect(u8),
+    SelectIdleWorker, // Select the player's next idle worker
+    SelectUnits(Vec<NetworkId>), // Select exactly these units, as computer players do to order a squad
+    SetFormation(Formation),
+    Load,                  // Selected units board the selected transports
+    Unload,                // Selected transports drop off everyone aboard
+    UnloadSlot(usize),     // Cargo slot of the selected transport to drop off
+    Ungarrison,            // Selected buildings send everyone inside back out
+    UngarrisonSlot(usize), // Garrison slot of the selected building to send out
     Pause,
     Resume,
+    Surrender, // Give up; allies take over the player's forces
     
     // New commands for enhanced worker control
-    RepairBuilding(Entity),
+    RepairBuilding(NetworkId),
     BuildBuilding {
         building_type: BuildingType,
         position: Vec2,
     },
+    BuildWall {
+        start: Vec2, // Where the drag began; segments run from here to the end
+        end: Vec2,
+    },
+}
+
+/// Order waiting for a target click after pressing its hotkey or HUD button
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetMode {
+    AttackMove,
+    /// Patrol needs two clicks; holds the first point once it has been picked
+    Patrol(Option<Vec2>),
+    /// Point or entity target of an ability
+    Ability(u8),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,12 +101,12 @@ pub struct UnitCommand {
 pub struct AbilityCommand {
     pub ability_id: u8,
     pub target_position: Option<Vec2>,
-    pub target_entity_id: Option<u32>,
+    pub target_entity_id: Option<NetworkId>,
 }
 
 pub struct InputHandler {
-    camera_position: Vec2,
-    camera_zoom: f32,
+    camera: CameraController,
+    last_camera_update: Instant,
     mouse_position: Vec2,
     left_mouse_down: bool,
     right_mouse_down: bool,
@@ -63,13 +116,38 @@ pub struct InputHandler {
     shift_pressed: bool,
     ctrl_pressed: bool,
     alt_pressed: bool,
+    placement: Option<BuildingType>,
+    wall_start: Option<Vec2>, // World position where a wall drag began
+    target_mode: Option<TargetMode>,
+    has_selection: bool, // Whether the player has anything selected to give an order to
+    last_group_recall: Option<(u8, Instant)>,
+    group_focus: Option<u8>,
+    formation: Formation,
+    last_click: Option<(Vec2, Instant)>,
+    subgroup_cycle: bool,
+    health_bar_cycle: bool,
+    idle_worker_cycle: bool,
+    pause_toggle: bool,
+    speed_steps: i32, // Faster minus slower game speed presses since the engine last looked
+    console_toggle: bool,
+    perf_overlay_toggle: bool,
+    log_viewer_toggle: bool,
+    minimap_terrain_toggle: bool,
+    edge_scroll_suspended: bool, // While the minimap is dragged, which sits inside the scrolling margin
+    keybindings: Keybindings,
+    rebinding: Option<KeyAction>, // Action waiting for its new key
 }
 
 impl InputHandler {
     pub fn new() -> Self {
         Self {
-            camera_position: Vec2::ZERO,
-            camera_zoom: 1.0,
+            camera: CameraController::new(
+                GameMap::default().width as f32,
+                GameMap::default().height as f32,
+                1024.0,
+                768.0,
+            ),
+            last_camera_update: Instant::now(),
             mouse_position: Vec2::ZERO,
             left_mouse_down: false,
             right_mouse_down: false,
@@ -79,6 +157,26 @@ impl InputHandler {
             shift_pressed: false,
             ctrl_pressed: false,
             alt_pressed: false,
+            placement: None,
+            wall_start: None,
+            target_mode: None,
+            has_selection: false,
+            last_group_recall: None,
+            group_focus: None,
+            formation: Formation::default(),
+            last_click: None,
+            subgroup_cycle: false,
+            health_bar_cycle: false,
+            idle_worker_cycle: false,
+            pause_toggle: false,
+            speed_steps: 0,
+            console_toggle: false,
+            perf_overlay_toggle: false,
+            log_viewer_toggle: false,
+            minimap_terrain_toggle: false,
+            edge_scroll_suspended: false,
+            keybindings: Keybindings::load_or_default(),
+            rebinding: None,
         }
     }
     
@@ -92,27 +190,8 @@ impl InputHandler {
                     // Update UI for selection rectangle, but don't issue command yet
                 }
                 
-                // Camera movement via edge scrolling
-                let edge_scroll_margin = 20.0;
-                let scroll_speed = 5.0;
-                let mut scroll_dir = Vec2::ZERO;
-                
-                if self.mouse_position.x < edge_scroll_margin {
-                    scroll_dir.x = -1.0;
-                } else if self.mouse_position.x > 1024.0 - edge_scroll_margin {
-                    scroll_dir.x = 1.0;
-                }
-                
-                if self.mouse_position.y < edge_scroll_margin {
-                    scroll_dir.y = -1.0;
-                } else if self.mouse_position.y > 768.0 - edge_scroll_margin {
-                    scroll_dir.y = 1.0;
-                }
-                
-                if scroll_dir != Vec2::ZERO {
-                    self.camera_position += scroll_dir * scroll_speed;
-                    self.pending_commands.push(Command::MoveCamera(scroll_dir * scroll_speed));
-                }
+                // Middle-mouse drag keeps the grabbed point under the cursor
+                self.camera.drag_to(self.mouse_position);
             }
             
             WindowEvent::MouseInput { state, button, .. } => {
@@ -121,20 +200,54 @@ impl InputHandler {
                         match state {
                             ElementState::Pressed => {
                                 self.left_mouse_down = true;
-                                self.selection_start = Some(self.mouse_position);
+                                
+                                // No drag selection while placing a building; walls are dragged out instead
+                                match self.placement {
+                                    Some(BuildingType::Wall) => self.wall_start = Some(self.get_world_mouse_position()),
+                                    Some(_) => {}
+                                    None => self.selection_start = Some(self.mouse_position),
+                                }
                             }
                             ElementState::Released => {
                                 self.left_mouse_down = false;
                                 
+                                // Left click confirms building placement
+                                if let Some(building_type) = self.placement {
+                                    let position = self.get_world_mouse_position();
+                                    if building_type == BuildingType::Wall {
+                                        self.pending_commands.push(Command::BuildWall {
+                                            start: self.wall_start.take().unwrap_or(position),
+                                            end: position,
+                                        });
+                                    } else {
+                                        self.pending_commands.push(Command::BuildBuilding { building_type, position });
+                                    }
+                                    
+                                    // Shift keeps placement mode active to queue more buildings
+                                    if !self.shift_pressed {
+                                        self.placement = None;
+                                    }
+                                    return;
+                                }
+                                
+                                // Left click picks the target of a pending order
+                                if self.target_at(self.get_world_mouse_position()) {
+                                    self.selection_start = None;
+                                    return;
+                                }
+                                
                                 if let Some(start) = self.selection_start {
                                     // Check if this was a click or a drag
                                     let drag_threshold = 5.0;
                                     if (start - self.mouse_position).length_squared() < drag_threshold * drag_threshold {
                                         // This was a click
-                                        self.pending_commands.push(Command::Select(self.mouse_position));
+                                        self.click_select();
                                     } else {
                                         // This was a drag - multi-select
-                                        self.pending_commands.push(Command::MultiSelect(start, self.mouse_position));
+                                        self.pending_commands.push(Command::MultiSelect(
+                                            self.screen_to_world(start),
+                                            self.get_world_mouse_position(),
+                                        ));
                                     }
                                 }
                                 
@@ -150,43 +263,30 @@ impl InputHandler {
                             }
                             ElementState::Released => {
                                 self.right_mouse_down = false;
-                                
-                                // Right click gives move or attack command depending on context
-                                if self.shift_pressed {
-                                    // Queue command
-                                    if self.alt_pressed {
-                                        // Alt+right click = attack move
-                                        self.pending_commands.push(Command::Attack(self.mouse_position));
-                                    } else {
-                                        // Shift+right click = queue move
-                                        self.pending_commands.push(Command::Move(self.mouse_position));
-                                    }
-                                } else {
-                                    // Direct command
-                                    if self.alt_pressed {
-                                        // Alt+right click = attack move
-                                        self.pending_commands.push(Command::Attack(self.mouse_position));
-                                    } else {
-                                        // Right click = move or gather depending on target
-                                        self.pending_commands.push(Command::Move(self.mouse_position));
-                                    }
-                                }
+                                self.right_click_at(self.get_world_mouse_position());
                             }
                         }
                     }
                     
+                    MouseButton::Middle => {
+                        match state {
+                            ElementState::Pressed => self.camera.begin_drag(self.mouse_position),
+                            ElementState::Released => self.camera.end_drag(),
+                        }
+                    }
+                    
                     _ => {}
                 }
             }
             
             WindowEvent::MouseWheel { delta, .. } => {
                 let zoom_delta = match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => *y * 0.1,
-                    winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.001,
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => *y,
+                    winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.01,
                 };
                 
-                self.camera_zoom = (self.camera_zoom + zoom_delta).max(0.5).min(2.0);
-                self.pending_commands.push(Command::ZoomCamera(zoom_delta));
+                // Zoom towards whatever is under the cursor
+                self.camera.zoom_camera(zoom_delta, self.mouse_position);
             }
             
             WindowEvent::KeyboardInput { input, .. } => {
@@ -212,33 +312,52 @@ impl InputHandler {
                         _ => {}
                     }
                     
-                    // Process key presses
-                    match keycode {
-                        // Camera controls
-                        VirtualKeyCode::W => self.pending_commands.push(Command::MoveCamera(Vec2::new(0.0, -10.0))),
-                        VirtualKeyCode::S => self.pending_commands.push(Command::MoveCamera(Vec2::new(0.0, 10.0))),
-                        VirtualKeyCode::A => self.pending_commands.push(Command::MoveCamera(Vec2::new(-10.0, 0.0))),
-                        VirtualKeyCode::D => self.pending_commands.push(Command::MoveCamera(Vec2::new(10.0, 0.0))),
-                        
-                        // Group controls
-                        VirtualKeyCode::Key1 if self.ctrl_pressed => self.pending_commands.push(Command::GroupAssign(0)),
-                        VirtualKeyCode::Key2 if self.ctrl_pressed => self.pending_commands.push(Command::GroupAssign(1)),
-                        VirtualKeyCode::Key3 if self.ctrl_pressed => self.pending_commands.push(Command::GroupAssign(2)),
-                        VirtualKeyCode::Key4 if self.ctrl_pressed => self.pending_commands.push(Command::GroupAssign(3)),
-                        VirtualKeyCode::Key5 if self.ctrl_pressed => self.pending_commands.push(Command::GroupAssign(4)),
+                    // The next key after picking an action in the controls menu becomes its binding
+                    if let Some(action) = self.rebinding.take() {
+                        self.finish_rebind(action, keycode);
+                        return;
+                    }
+                    
+                    match self.keybindings.action_for(keycode, self.ctrl_pressed) {
+                        // Control groups: Ctrl+key assigns, key recalls
+                        Some(KeyAction::ControlGroup(group)) => {
+                            if self.ctrl_pressed {
+                                self.pending_commands.push(Command::GroupAssign(group));
+                            } else {
+                                self.recall_group(group);
+                            }
+                        }
                         
-                        VirtualKeyCode::Key1 if !self.ctrl_pressed => self.pending_commands.push(Command::GroupSelect(0)),
-                        VirtualKeyCode::Key2 if !self.ctrl_pressed => self.pending_commands.push(Command::GroupSelect(1)),
-                        VirtualKeyCode::Key3 if !self.ctrl_pressed => self.pending_commands.push(Command::GroupSelect(2)),
-                        VirtualKeyCode::Key4 if !self.ctrl_pressed => self.pending_commands.push(Command::GroupSelect(3)),
-                        VirtualKeyCode::Key5 if !self.ctrl_pressed => self.pending_commands.push(Command::GroupSelect(4)),
+                        // Order hotkeys
+                        Some(KeyAction::AttackMove) if self.has_selection => self.begin_targeting(TargetMode::AttackMove),
+                        Some(KeyAction::Patrol) => self.begin_targeting(TargetMode::Patrol(None)),
+                        Some(KeyAction::Stop) => self.pending_commands.push(Command::Stop),
+                        Some(KeyAction::HoldPosition) => self.pending_commands.push(Command::HoldPosition),
+                        Some(KeyAction::CycleFormation) => self.cycle_formation(),
+                        Some(KeyAction::CycleSubgroup) => self.subgroup_cycle = true,
+                        Some(KeyAction::CycleHealthBars) => self.health_bar_cycle = true,
+                        Some(KeyAction::SelectIdleWorker) => self.idle_worker_cycle = true,
                         
                         // Game commands
-                        VirtualKeyCode::Escape => self.pending_commands.push(Command::CancelBuild),
-                        VirtualKeyCode::Space => self.pending_commands.push(Command::Pause),
-                        VirtualKeyCode::S if self.ctrl_pressed => self.pending_commands.push(Command::Stop),
+                        Some(KeyAction::Cancel) => {
+                            self.wall_start = None;
+                            if self.placement.take().is_none() && self.target_mode.take().is_none() {
+                                self.pending_commands.push(Command::CancelBuild);
+                            }
+                        }
+                        Some(KeyAction::Pause) => self.pause_toggle = true,
+                        Some(KeyAction::SpeedUp) => self.speed_steps += 1,
+                        Some(KeyAction::SpeedDown) => self.speed_steps -= 1,
+                        Some(KeyAction::ToggleConsole) => self.console_toggle = true,
+                        Some(KeyAction::TogglePerfOverlay) => self.perf_overlay_toggle = true,
+                        Some(KeyAction::ToggleLogViewer) => self.log_viewer_toggle = true,
+                        Some(KeyAction::ToggleMinimapTerrain) => self.minimap_terrain_toggle = true,
                         
-                        _ => {}
+                        // Camera keys scroll while held, see update_camera. With nothing selected the
+                        // attack-move key is left to scrolling, as both default to A
+                        Some(KeyAction::AttackMove) |
+                        Some(KeyAction::CameraUp) | Some(KeyAction::CameraDown) |
+                        Some(KeyAction::CameraLeft) | Some(KeyAction::CameraRight) | None => {}
                     }
                 }
                 
@@ -267,11 +386,61 @@ impl InputHandler {
     }
     
     pub fn get_camera_position(&self) -> Vec2 {
-        self.camera_position
+        self.camera.position
     }
     
     pub fn get_camera_zoom(&self) -> f32 {
-        self.camera_zoom
+        self.camera.zoom
+    }
+    
+    /// Scroll the camera from held camera keys and the cursor at the window edges, and ease its zoom
+    pub fn update_camera(&mut self) {
+        let now = Instant::now();
+        let delta_time = now.duration_since(self.last_camera_update).as_secs_f32();
+        self.last_camera_update = now;
+        
+        let mut direction = if self.edge_scroll_suspended {
+            Vec2::ZERO
+        } else {
+            self.camera.edge_scroll_direction(self.mouse_position)
+        };
+        for (action, key_direction) in [
+            (KeyAction::CameraUp, Vec2::Y),
+            (KeyAction::CameraDown, -Vec2::Y),
+            (KeyAction::CameraLeft, -Vec2::X),
+            (KeyAction::CameraRight, Vec2::X),
+        ] {
+            // A key shared with an order doesn't scroll while that order waits for its target
+            let aiming = self.target_mode.is_some() && self.keybindings.shares_key(action);
+            if self.keybindings.is_held(action, &self.keys_down) && !aiming {
+                direction += key_direction;
+            }
+        }
+        
+        if direction != Vec2::ZERO {
+            self.camera.move_camera(direction.normalize(), delta_time);
+        }
+        self.camera.update(delta_time);
+    }
+    
+    /// Match the camera's view to a new window size
+    pub fn resize_view(&mut self, width: f32, height: f32) {
+        self.camera.resize(width, height);
+    }
+    
+    /// Keep the camera over a map of the given size
+    pub fn set_camera_bounds(&mut self, width: f32, height: f32) {
+        self.camera.set_world_size(width, height);
+    }
+    
+    /// Stop the cursor at the window edges scrolling the camera, e.g. while the minimap moves it instead
+    pub fn set_edge_scroll_suspended(&mut self, suspended: bool) {
+        self.edge_scroll_suspended = suspended;
+    }
+    
+    /// Use the player's edge scrolling options
+    pub fn apply_camera_settings(&mut self, settings: &CameraSettings) {
+        self.camera.apply_settings(settings);
     }
     
     pub fn is_selection_active(&self) -> bool {
@@ -289,4 +458,615 @@ impl InputHandler {
     pub fn handle_command(&mut self, command: Command) {
         self.pending_commands.push(command);
     }
-}
\ No newline at end of file
+    
+    /// Order the selection to a world position, as a right click there would
+    /// Give a pending attack-move, patrol or ability its target; returns false if none is pending
+    pub fn target_at(&mut self, position: Vec2) -> bool {
+        let target_mode = match self.target_mode {
+            Some(target_mode) => target_mode,
+            None => return false,
+        };
+        match target_mode {
+            TargetMode::AttackMove => {
+                self.pending_commands.push(Command::Attack(position));
+                self.target_mode = None;
+            }
+            TargetMode::Patrol(None) => {
+                // First click sets the patrol origin
+                self.target_mode = Some(TargetMode::Patrol(Some(position)));
+            }
+            TargetMode::Patrol(Some(origin)) => {
+                self.pending_commands.push(Command::Patrol(origin, position));
+                self.target_mode = None;
+            }
+            TargetMode::Ability(ability_id) => {
+                // The simulation picks the entity under the click for entity-targeted abilities
+                self.pending_commands.push(Command::UseAbility(AbilityCommand {
+                    ability_id,
+                    target_position: Some(position),
+                    target_entity_id: None,
+                }));
+                self.target_mode = None;
+            }
+        }
+        true
+    }
+    
+    /// Right click at a world position, in the world or on the minimap: cancels building placement or
+    /// a pending order if there is one, and otherwise orders the selection there
+    pub fn right_click_at(&mut self, position: Vec2) {
+        self.wall_start = None;
+        if self.placement.take().is_some() || self.target_mode.take().is_some() {
+            return;
+        }
+        self.order_at(position);
+    }
+    
+    fn order_at(&mut self, position: Vec2) {
+        // Right click gives an order that depends on what was clicked
+        if self.shift_pressed {
+            // Queue command
+            if self.alt_pressed {
+                // Alt+right click = attack move
+                self.pending_commands.push(Command::Attack(position));
+            } else {
+                // Shift+right click = queue move
+                self.pending_commands.push(Command::Move(position));
+            }
+        } else {
+            // Direct command
+            if self.alt_pressed {
+                // Alt+right click = attack move
+                self.pending_commands.push(Command::Attack(position));
+            } else {
+                // Right click = gather, repair, attack, follow or move depending on target
+                self.pending_commands.push(Command::SmartOrder(position));
+            }
+        }
+    }
+    
+    /// Select a control group; a second press in quick succession also centers the camera on it
+    fn recall_group(&mut self, group: u8) {
+        let now = Instant::now();
+        let double_tap = matches!(
+            self.last_group_recall,
+            Some((last_group, last_time)) if last_group == group && now.duration_since(last_time) <= GROUP_DOUBLE_TAP_WINDOW
+        );
+        
+        self.pending_commands.push(Command::GroupSelect(group));
+        
+        if double_tap {
+            self.group_focus = Some(group);
+            self.last_group_recall = None;
+        } else {
+            self.last_group_recall = Some((group, now));
+        }
+    }
+    
+    /// Select under the cursor; a double-click selects every visible unit of that type instead
+    fn click_select(&mut self) {
+        let now = Instant::now();
+        let double_click = matches!(
+            self.last_click,
+            Some((last_position, last_time))
+                if (last_position - self.mouse_position).length() <= DOUBLE_CLICK_DISTANCE
+                    && now.duration_since(last_time) <= DOUBLE_CLICK_WINDOW
+        );
+        
+        let position = self.get_world_mouse_position();
+        if double_click {
+            let (view_min, view_max) = self.visible_world_bounds();
+            self.pending_commands.push(Command::SelectSameType { position, view_min, view_max });
+            self.last_click = None;
+        } else {
+            self.pending_commands.push(Command::Select(position));
+            self.last_click = Some((self.mouse_position, now));
+        }
+    }
+    
+    /// Current key bindings
+    pub fn keybindings(&self) -> &Keybindings {
+        &self.keybindings
+    }
+    
+    /// Wait for the next key press and bind it to `action`; Escape cancels
+    pub fn begin_rebind(&mut self, action: KeyAction) {
+        self.rebinding = Some(action);
+    }
+    
+    /// Action waiting for a new key, if a rebind is in progress
+    pub fn rebinding(&self) -> Option<KeyAction> {
+        self.rebinding
+    }
+    
+    /// Restore the default bindings and save them
+    pub fn reset_keybindings(&mut self) {
+        self.keybindings = Keybindings::default();
+        self.save_keybindings();
+    }
+    
+    fn finish_rebind(&mut self, action: KeyAction, keycode: VirtualKeyCode) {
+        match keycode {
+            // Keep waiting while a modifier is pressed on its own
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift | VirtualKeyCode::LControl |
+            VirtualKeyCode::RControl | VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => {
+                self.rebinding = Some(action);
+            }
+            VirtualKeyCode::Escape => {}
+            _ if key_name(keycode).is_none() => warn!("{:?} can't be bound", keycode),
+            _ => {
+                // Control groups take the plain key; Ctrl is what assigns them
+                let ctrl = self.ctrl_pressed && !matches!(action, KeyAction::ControlGroup(_));
+                let binding = KeyBinding { key: keycode, ctrl };
+                self.keybindings.bind(action, binding);
+                info!("Bound {} to {}", action.name(), binding.label());
+                self.save_keybindings();
+            }
+        }
+    }
+    
+    fn save_keybindings(&self) {
+        if let Err(e) = self.keybindings.save(KEYBINDINGS_FILE) {
+            error!("Failed to save key bindings: {}", e);
+        }
+    }
+    
+    /// Whether the subgroup key was pressed to switch the active selection subgroup since the last call
+    pub fn take_subgroup_cycle(&mut self) -> bool {
+        std::mem::take(&mut self.subgroup_cycle)
+    }
+    
+    /// Whether the health bar key was pressed to switch when health bars are shown since the last call
+    pub fn take_health_bar_cycle(&mut self) -> bool {
+        std::mem::take(&mut self.health_bar_cycle)
+    }
+    
+    /// Whether the idle worker key was pressed since the last call
+    pub fn take_idle_worker_cycle(&mut self) -> bool {
+        std::mem::take(&mut self.idle_worker_cycle)
+    }
+    
+    /// Whether the pause key was pressed since the last call
+    pub fn take_pause_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.pause_toggle)
+    }
+    
+    /// Whether the console key was pressed since the last call
+    pub fn take_console_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.console_toggle)
+    }
+    
+    /// Whether the performance overlay key was pressed since the last call
+    pub fn take_perf_overlay_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.perf_overlay_toggle)
+    }
+    
+    /// Whether the log viewer key was pressed since the last call
+    pub fn take_log_viewer_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.log_viewer_toggle)
+    }
+    
+    /// Whether the minimap terrain-only key was pressed since the last call
+    pub fn take_minimap_terrain_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.minimap_terrain_toggle)
+    }
+    
+    /// Net presses of the faster and slower game speed keys since the last call
+    pub fn take_speed_steps(&mut self) -> i32 {
+        std::mem::take(&mut self.speed_steps)
+    }
+    
+    /// Control group the camera should jump to after a double-tap, if any
+    pub fn take_group_focus(&mut self) -> Option<u8> {
+        self.group_focus.take()
+    }
+    
+    pub fn set_camera_position(&mut self, position: Vec2) {
+        self.camera.position = position;
+    }
+    
+    /// Enter building placement mode for the given building type
+    pub fn begin_placement(&mut self, building_type: BuildingType) {
+        self.placement = Some(building_type);
+        self.wall_start = None;
+        self.target_mode = None;
+        self.selection_start = None;
+    }
+    
+    /// Wait for a target click for the given order
+    pub fn begin_targeting(&mut self, target_mode: TargetMode) {
+        self.target_mode = Some(target_mode);
+        self.placement = None;
+        self.selection_start = None;
+    }
+    
+    /// Order currently waiting for a target click, if any
+    pub fn get_target_mode(&self) -> Option<TargetMode> {
+        self.target_mode
+    }
+    
+    /// Tell the handler whether the player has a selection, which the attack-move key needs
+    pub fn set_has_selection(&mut self, has_selection: bool) {
+        self.has_selection = has_selection;
+    }
+    
+    /// Cursor to show: a crosshair while an order or ability waits for its target
+    pub fn cursor_icon(&self) -> CursorIcon {
+        match self.target_mode {
+            Some(_) => CursorIcon::Crosshair,
+            None => CursorIcon::Default,
+        }
+    }
+    
+    /// Switch group orders to the next formation shape
+    pub fn cycle_formation(&mut self) {
+        self.formation = self.formation.next();
+        self.pending_commands.push(Command::SetFormation(self.formation));
+    }
+    
+    /// Leave building placement mode without placing anything
+    pub fn cancel_placement(&mut self) {
+        self.placement = None;
+        self.wall_start = None;
+    }
+    
+    /// Building currently being placed, if any
+    pub fn get_placement(&self) -> Option<BuildingType> {
+        self.placement
+    }
+    
+    /// World position where the wall being dragged out began, while the button is held
+    pub fn get_wall_start(&self) -> Option<Vec2> {
+        self.wall_start
+    }
+    
+    /// Convert a screen position to world coordinates using the current camera
+    pub fn screen_to_world(&self, screen_pos: Vec2) -> Vec2 {
+        self.camera.screen_to_world(screen_pos)
+    }
+    
+    /// Mouse position in world coordinates
+    pub fn get_world_mouse_position(&self) -> Vec2 {
+        self.screen_to_world(self.mouse_position)
+    }
+    
+    /// World-space rectangle covered by the camera, as (min, max)
+    pub fn visible_world_bounds(&self) -> (Vec2, Vec2) {
+        self.camera.get_visible_bounds()
+    }
+}
+
+/// File the player's key bindings are kept in
+pub const KEYBINDINGS_FILE: &str = "config/keybindings.ron";
+
+/// Something a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyAction {
+    CameraUp,
+    CameraDown,
+    CameraLeft,
+    CameraRight,
+    AttackMove,
+    Patrol,
+    Stop,
+    HoldPosition,
+    CycleFormation,
+    CycleSubgroup,
+    CycleHealthBars,
+    SelectIdleWorker,
+    Cancel,
+    Pause,
+    SpeedUp,
+    SpeedDown,
+    ToggleConsole,
+    TogglePerfOverlay,
+    ToggleLogViewer,
+    ToggleMinimapTerrain,
+    ControlGroup(u8), // 1-9; held with Ctrl it assigns the group instead of recalling it
+}
+
+impl KeyAction {
+    /// Whether the action scrolls the camera
+    pub fn is_camera(self) -> bool {
+        matches!(self, KeyAction::CameraUp | KeyAction::CameraDown | KeyAction::CameraLeft | KeyAction::CameraRight)
+    }
+    
+    /// Every bindable action, in the order the controls menu lists them
+    pub fn all() -> Vec<KeyAction> {
+        let mut actions = vec![
+            KeyAction::CameraUp,
+            KeyAction::CameraDown,
+            KeyAction::CameraLeft,
+            KeyAction::CameraRight,
+            KeyAction::AttackMove,
+            KeyAction::Patrol,
+            KeyAction::Stop,
+            KeyAction::HoldPosition,
+            KeyAction::CycleFormation,
+            KeyAction::CycleSubgroup,
+            KeyAction::CycleHealthBars,
+            KeyAction::SelectIdleWorker,
+            KeyAction::Cancel,
+            KeyAction::Pause,
+            KeyAction::SpeedUp,
+            KeyAction::SpeedDown,
+            KeyAction::ToggleConsole,
+            KeyAction::TogglePerfOverlay,
+            KeyAction::ToggleLogViewer,
+            KeyAction::ToggleMinimapTerrain,
+        ];
+        actions.extend((1..=9).map(KeyAction::ControlGroup));
+        actions
+    }
+    
+    /// Name shown in the controls menu
+    pub fn name(self) -> String {
+        match self {
+            KeyAction::CameraUp => "Scroll Up".to_string(),
+            KeyAction::CameraDown => "Scroll Down".to_string(),
+            KeyAction::CameraLeft => "Scroll Left".to_string(),
+            KeyAction::CameraRight => "Scroll Right".to_string(),
+            KeyAction::AttackMove => "Attack Move".to_string(),
+            KeyAction::Patrol => "Patrol".to_string(),
+            KeyAction::Stop => "Stop".to_string(),
+            KeyAction::HoldPosition => "Hold Position".to_string(),
+            KeyAction::CycleFormation => "Cycle Formation".to_string(),
+            KeyAction::CycleSubgroup => "Next Subgroup".to_string(),
+            KeyAction::CycleHealthBars => "Health Bars".to_string(),
+            KeyAction::SelectIdleWorker => "Idle Worker".to_string(),
+            KeyAction::Cancel => "Cancel".to_string(),
+            KeyAction::Pause => "Pause".to_string(),
+            KeyAction::SpeedUp => "Faster Game Speed".to_string(),
+            KeyAction::SpeedDown => "Slower Game Speed".to_string(),
+            KeyAction::ToggleConsole => "Developer Console".to_string(),
+            KeyAction::TogglePerfOverlay => "Performance Overlay".to_string(),
+            KeyAction::ToggleLogViewer => "Log Viewer".to_string(),
+            KeyAction::ToggleMinimapTerrain => "Minimap Terrain Only".to_string(),
+            KeyAction::ControlGroup(group) => format!("Control Group {}", group),
+        }
+    }
+    
+    /// Identifier used for the action's controls menu elements
+    pub fn id(self) -> String {
+        match self {
+            KeyAction::CameraUp => "camera_up".to_string(),
+            KeyAction::CameraDown => "camera_down".to_string(),
+            KeyAction::CameraLeft => "camera_left".to_string(),
+            KeyAction::CameraRight => "camera_right".to_string(),
+            KeyAction::AttackMove => "attack_move".to_string(),
+            KeyAction::Patrol => "patrol".to_string(),
+            KeyAction::Stop => "stop".to_string(),
+            KeyAction::HoldPosition => "hold_position".to_string(),
+            KeyAction::CycleFormation => "cycle_formation".to_string(),
+            KeyAction::CycleSubgroup => "cycle_subgroup".to_string(),
+            KeyAction::CycleHealthBars => "cycle_health_bars".to_string(),
+            KeyAction::SelectIdleWorker => "select_idle_worker".to_string(),
+            KeyAction::Cancel => "cancel".to_string(),
+            KeyAction::Pause => "pause".to_string(),
+            KeyAction::SpeedUp => "speed_up".to_string(),
+            KeyAction::SpeedDown => "speed_down".to_string(),
+            KeyAction::ToggleConsole => "toggle_console".to_string(),
+            KeyAction::TogglePerfOverlay => "toggle_perf_overlay".to_string(),
+            KeyAction::ToggleLogViewer => "toggle_log_viewer".to_string(),
+            KeyAction::ToggleMinimapTerrain => "toggle_minimap_terrain".to_string(),
+            KeyAction::ControlGroup(group) => format!("control_group_{}", group),
+        }
+    }
+}
+
+/// A key, and whether Ctrl must be held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key: VirtualKeyCode) -> Self {
+        Self { key, ctrl: false }
+    }
+    
+    pub fn with_ctrl(key: VirtualKeyCode) -> Self {
+        Self { key, ctrl: true }
+    }
+    
+    /// Label shown in the controls menu, e.g. "Ctrl+S"
+    pub fn label(self) -> String {
+        let name = key_name(self.key).unwrap_or("?");
+        if self.ctrl {
+            format!("Ctrl+{}", name)
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// Binding as written to the config file; winit key codes are stored by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedBinding {
+    action: KeyAction,
+    key: String,
+    ctrl: bool,
+}
+
+/// Maps bindable actions to keys
+#[derive(Debug, Clone)]
+pub struct Keybindings {
+    bindings: HashMap<KeyAction, KeyBinding>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        bindings.insert(KeyAction::CameraUp, KeyBinding::new(VirtualKeyCode::W));
+        bindings.insert(KeyAction::CameraDown, KeyBinding::new(VirtualKeyCode::S));
+        bindings.insert(KeyAction::CameraLeft, KeyBinding::new(VirtualKeyCode::A));
+        bindings.insert(KeyAction::CameraRight, KeyBinding::new(VirtualKeyCode::D));
+        bindings.insert(KeyAction::AttackMove, KeyBinding::new(VirtualKeyCode::A));
+        bindings.insert(KeyAction::Patrol, KeyBinding::new(VirtualKeyCode::P));
+        bindings.insert(KeyAction::Stop, KeyBinding::with_ctrl(VirtualKeyCode::S));
+        bindings.insert(KeyAction::HoldPosition, KeyBinding::new(VirtualKeyCode::H));
+        bindings.insert(KeyAction::CycleFormation, KeyBinding::new(VirtualKeyCode::F));
+        bindings.insert(KeyAction::CycleSubgroup, KeyBinding::new(VirtualKeyCode::Tab));
+        bindings.insert(KeyAction::CycleHealthBars, KeyBinding::new(VirtualKeyCode::V));
+        bindings.insert(KeyAction::SelectIdleWorker, KeyBinding::new(VirtualKeyCode::F1));
+        bindings.insert(KeyAction::Cancel, KeyBinding::new(VirtualKeyCode::Escape));
+        bindings.insert(KeyAction::Pause, KeyBinding::new(VirtualKeyCode::Space));
+        bindings.insert(KeyAction::SpeedUp, KeyBinding::new(VirtualKeyCode::Equals));
+        bindings.insert(KeyAction::SpeedDown, KeyBinding::new(VirtualKeyCode::Minus));
+        bindings.insert(KeyAction::ToggleConsole, KeyBinding::new(VirtualKeyCode::Grave));
+        bindings.insert(KeyAction::TogglePerfOverlay, KeyBinding::new(VirtualKeyCode::F3));
+        bindings.insert(KeyAction::ToggleLogViewer, KeyBinding::new(VirtualKeyCode::F4));
+        bindings.insert(KeyAction::ToggleMinimapTerrain, KeyBinding::new(VirtualKeyCode::M));
+        
+        let group_keys = [
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7,
+            VirtualKeyCode::Key8,
+            VirtualKeyCode::Key9,
+        ];
+        for (group, key) in (1..=9).zip(group_keys) {
+            bindings.insert(KeyAction::ControlGroup(group), KeyBinding::new(key));
+        }
+        
+        Self { bindings }
+    }
+}
+
+impl Keybindings {
+    /// Read bindings from `path`; actions missing from the file keep their default key
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let saved: Vec<SavedBinding> = ron::from_str(&text)?;
+        
+        let mut keybindings = Self::default();
+        for binding in saved {
+            let key = key_from_name(&binding.key)
+                .ok_or_else(|| anyhow::anyhow!("Unknown key '{}' bound to {:?}", binding.key, binding.action))?;
+            keybindings.bindings.insert(binding.action, KeyBinding { key, ctrl: binding.ctrl });
+        }
+        Ok(keybindings)
+    }
+    
+    /// Bindings from the config file, or the defaults if there is none or it can't be read
+    pub fn load_or_default() -> Self {
+        if !Path::new(KEYBINDINGS_FILE).exists() {
+            return Self::default();
+        }
+        
+        match Self::load(KEYBINDINGS_FILE) {
+            Ok(keybindings) => keybindings,
+            Err(e) => {
+                warn!("Failed to load key bindings, using defaults: {}", e);
+                Self::default()
+            }
+        }
+    }
+    
+    /// Write the bindings to `path`, creating its directory if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let saved: Vec<SavedBinding> = KeyAction::all()
+            .into_iter()
+            .filter_map(|action| {
+                let binding = self.bindings.get(&action)?;
+                Some(SavedBinding {
+                    action,
+                    key: key_name(binding.key)?.to_string(),
+                    ctrl: binding.ctrl,
+                })
+            })
+            .collect();
+        
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, ron::ser::to_string_pretty(&saved, ron::ser::PrettyConfig::default())?)?;
+        Ok(())
+    }
+    
+    /// Key currently bound to an action, if any
+    pub fn binding(&self, action: KeyAction) -> Option<KeyBinding> {
+        self.bindings.get(&action).copied()
+    }
+    
+    /// Bind an action to a key, unbinding any other action that used the same key
+    pub fn bind(&mut self, action: KeyAction, binding: KeyBinding) {
+        self.bindings.retain(|other, existing| *other == action || *existing != binding);
+        self.bindings.insert(action, binding);
+    }
+    
+    /// Action triggered by a key press; control group keys also fire with Ctrl held, to assign the group.
+    /// Camera keys scroll while held rather than on a press, so they never hide an action sharing their key.
+    pub fn action_for(&self, key: VirtualKeyCode, ctrl: bool) -> Option<KeyAction> {
+        let actions = KeyAction::all();
+        actions
+            .iter()
+            .copied()
+            .filter(|action| !action.is_camera())
+            .find(|action| self.bindings.get(action) == Some(&KeyBinding { key, ctrl }))
+            .or_else(|| {
+                actions.iter().copied().find(|action| {
+                    matches!(action, KeyAction::ControlGroup(_)) &&
+                        self.bindings.get(action) == Some(&KeyBinding::new(key))
+                })
+            })
+    }
+    
+    /// Keys that trigger an action when pressed without Ctrl
+    pub fn plain_keys(&self) -> HashSet<VirtualKeyCode> {
+        self.bindings.values().filter(|binding| !binding.ctrl).map(|binding| binding.key).collect()
+    }
+    
+    /// Whether another action is bound to the same key as this one
+    pub fn shares_key(&self, action: KeyAction) -> bool {
+        let binding = self.bindings.get(&action);
+        binding.is_some() && self.bindings.iter().any(|(other, existing)| *other != action && Some(existing) == binding)
+    }
+    
+    /// Whether the key bound to an action is among `keys_down`
+    pub fn is_held(&self, action: KeyAction, keys_down: &HashSet<VirtualKeyCode>) -> bool {
+        self.bindings.get(&action).map(|binding| keys_down.contains(&binding.key)).unwrap_or(false)
+    }
+}
+
+/// Keys that can be bound, with the names used in the config file and menus
+const KEY_NAMES: &[(VirtualKeyCode, &str)] = &[
+    (VirtualKeyCode::A, "A"), (VirtualKeyCode::B, "B"), (VirtualKeyCode::C, "C"), (VirtualKeyCode::D, "D"),
+    (VirtualKeyCode::E, "E"), (VirtualKeyCode::F, "F"), (VirtualKeyCode::G, "G"), (VirtualKeyCode::H, "H"),
+    (VirtualKeyCode::I, "I"), (VirtualKeyCode::J, "J"), (VirtualKeyCode::K, "K"), (VirtualKeyCode::L, "L"),
+    (VirtualKeyCode::M, "M"), (VirtualKeyCode::N, "N"), (VirtualKeyCode::O, "O"), (VirtualKeyCode::P, "P"),
+    (VirtualKeyCode::Q, "Q"), (VirtualKeyCode::R, "R"), (VirtualKeyCode::S, "S"), (VirtualKeyCode::T, "T"),
+    (VirtualKeyCode::U, "U"), (VirtualKeyCode::V, "V"), (VirtualKeyCode::W, "W"), (VirtualKeyCode::X, "X"),
+    (VirtualKeyCode::Y, "Y"), (VirtualKeyCode::Z, "Z"),
+    (VirtualKeyCode::Key0, "0"), (VirtualKeyCode::Key1, "1"), (VirtualKeyCode::Key2, "2"), (VirtualKeyCode::Key3, "3"),
+    (VirtualKeyCode::Key4, "4"), (VirtualKeyCode::Key5, "5"), (VirtualKeyCode::Key6, "6"), (VirtualKeyCode::Key7, "7"),
+    (VirtualKeyCode::Key8, "8"), (VirtualKeyCode::Key9, "9"),
+    (VirtualKeyCode::F1, "F1"), (VirtualKeyCode::F2, "F2"), (VirtualKeyCode::F3, "F3"), (VirtualKeyCode::F4, "F4"),
+    (VirtualKeyCode::F5, "F5"), (VirtualKeyCode::F6, "F6"), (VirtualKeyCode::F7, "F7"), (VirtualKeyCode::F8, "F8"),
+    (VirtualKeyCode::F9, "F9"), (VirtualKeyCode::F10, "F10"), (VirtualKeyCode::F11, "F11"), (VirtualKeyCode::F12, "F12"),
+    (VirtualKeyCode::Up, "Up"), (VirtualKeyCode::Down, "Down"), (VirtualKeyCode::Left, "Left"), (VirtualKeyCode::Right, "Right"),
+    (VirtualKeyCode::Space, "Space"), (VirtualKeyCode::Tab, "Tab"), (VirtualKeyCode::Escape, "Escape"),
+    (VirtualKeyCode::Return, "Enter"), (VirtualKeyCode::Back, "Backspace"), (VirtualKeyCode::Delete, "Delete"),
+    (VirtualKeyCode::Insert, "Insert"), (VirtualKeyCode::Home, "Home"), (VirtualKeyCode::End, "End"),
+    (VirtualKeyCode::PageUp, "PageUp"), (VirtualKeyCode::PageDown, "PageDown"),
+    (VirtualKeyCode::Grave, "`"), (VirtualKeyCode::Minus, "-"), (VirtualKeyCode::Equals, "="),
+    (VirtualKeyCode::LBracket, "["), (VirtualKeyCode::RBracket, "]"), (VirtualKeyCode::Backslash, "\\"),
+    (VirtualKeyCode::Semicolon, ";"), (VirtualKeyCode::Apostrophe, "'"), (VirtualKeyCode::Comma, ","),
+    (VirtualKeyCode::Period, "."), (VirtualKeyCode::Slash, "/"),
+];
+
+/// Config file and menu name of a key, if it can be bound
+pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(code, _)| *code == key).map(|(_, name)| *name)
+}
+
+/// Key with the given config file name
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES.iter().find(|(_, key_name)| *key_name == name).map(|(code, _)| *code)
+}
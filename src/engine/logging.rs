@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Targets the subsystems log under, whichever module the line comes from, so each can be filtered
+/// on its own, e.g. `RUST_LOG=info,net=debug,ai=trace`. Anything else logs under its module path
+pub const TARGETS: [&str; 4] = ["net", "ai", "combat", "render"];
+
+/// What is logged when RUST_LOG isn't set; wgpu is very chatty at info
+const DEFAULT_FILTER: &str = "info,wgpu_core=warn,wgpu_hal=warn,naga=warn";
+
+/// Where log files are written, next to the save directory
+const LOG_DIRECTORY: &str = "logs";
+
+/// The current log is `game.log`; older ones are `game.1.log`, `game.2.log`, ... oldest last
+const LOG_FILE_STEM: &str = "game";
+
+/// Size at which the log file is rotated, so a long session can't fill the disk
+const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Rotated log files kept besides the current one
+const KEPT_LOG_FILES: usize = 4;
+
+/// Warnings and errors kept for the in-game log viewer
+const RECENT_ENTRIES: usize = 200;
+
+/// A warning or error, as the log viewer lists it
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub seconds: f32, // Since the game started
+}
+
+static RECENT: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Log to stderr and a rotating file under logs/, both filtered by RUST_LOG, and keep recent warnings
+/// and errors for the log viewer whatever the filter. Lines from crates using `log` are included
+pub fn init() {
+    STARTED.get_or_init(Instant::now);
+    let filter = || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    
+    let file_layer = match RotatingFile::open() {
+        Ok(file) => Some(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .with_filter(filter()),
+        ),
+        Err(e) => {
+            eprintln!("Failed to open a log file, logging to stderr only: {}", e);
+            None
+        }
+    };
+    
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stderr).with_filter(filter()))
+        .with(file_layer)
+        .with(RecentEntries.with_filter(LevelFilter::WARN))
+        .init();
+}
+
+/// Warnings and errors logged lately, oldest first
+pub fn recent_entries() -> Vec<LogEntry> {
+    RECENT.lock().map(|recent| recent.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Keeps the latest warnings and errors for the log viewer
+struct RecentEntries;
+
+impl<S: Subscriber> Layer<S> for RecentEntries {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        
+        let entry = LogEntry {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: message.0,
+            seconds: STARTED.get_or_init(Instant::now).elapsed().as_secs_f32(),
+        };
+        
+        // Logging from inside a panic while the buffer is locked must not deadlock
+        if let Ok(mut recent) = RECENT.try_lock() {
+            if recent.len() >= RECENT_ENTRIES {
+                recent.pop_front();
+            }
+            recent.push_back(entry);
+        }
+    }
+}
+
+/// The event's message followed by its other fields, e.g. "Dropped packet addr=1.2.3.4:5"
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        use std::fmt::Write as _;
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// The log file, moved aside to make room for a fresh one at startup and whenever it grows too large
+struct RotatingFile {
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open() -> io::Result<Self> {
+        fs::create_dir_all(LOG_DIRECTORY)?;
+        rotate()?;
+        Ok(Self { file: File::create(log_path(0))?, written: 0 })
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > MAX_LOG_BYTES {
+            self.file.flush()?;
+            rotate()?;
+            self.file = File::create(log_path(0))?;
+            self.written = 0;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// `game.log` for the current file, `game.<n>.log` for the nth newest rotated one
+fn log_path(index: usize) -> PathBuf {
+    let name = match index {
+        0 => format!("{}.log", LOG_FILE_STEM),
+        index => format!("{}.{}.log", LOG_FILE_STEM, index),
+    };
+    PathBuf::from(LOG_DIRECTORY).join(name)
+}
+
+/// Shift every log file one place older, dropping the oldest
+fn rotate() -> io::Result<()> {
+    for index in (0..KEPT_LOG_FILES).rev() {
+        let from = log_path(index);
+        if from.exists() {
+            fs::rename(&from, log_path(index + 1))?;
+        }
+    }
+    Ok(())
+}
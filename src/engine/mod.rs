@@ -3,21 +3,99 @@ pub mod input;
 pub mod time;
 pub mod audio;
 pub mod assets;
+pub mod terrain;
+pub mod sprites;
+pub mod combat_text;
+pub mod camera;
+pub mod console;
+pub mod profiler;
+pub mod crash;
+pub mod logging;
+pub mod headless;
+pub mod stress;
 
 use anyhow::Result;
 use bevy_ecs::prelude::*;
+use bevy_ecs::system::CommandQueue;
+use glam::Vec2;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use winit::{
     event::{Event, WindowEvent},
+    dpi::PhysicalSize,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{CursorIcon, Fullscreen, Window, WindowBuilder},
 };
+use tracing::{error, info, warn};
 
 use crate::ecs;
-use crate::ecs::resources::DamageTable;
-use crate::ecs::systems::combat::combat_system;
+use crate::ecs::components::{
+    Building, BuildingType, Cargo, Caster, Collider, Experience, Garrison, Owner, ResourceType, Selectable, Selected,
+    Transform, Unit, UnitType, Upgrading,
+};
+use crate::ecs::resources::{
+    AttackAlerts, AudioEvents, CombatTextEvents, ControlGroups, GameMap, GameTime, PlacementGhost, PlayerAlerts,
+    PlayerInfo, SelectionKind, SelectionOverlay, SelectionState, TechState, TerrainTile, TICK_RATE,
+};
+use crate::ecs::spatial::SpatialGrid;
+use crate::ecs::systems::combat::DamageTable;
 use crate::game::GameState;
-use crate::networking::lockstep::LockstepNetwork;
+use crate::game::abilities::{self, AbilityTarget};
+use crate::game::ai::{AiController, AiPlayer, AiPlayers, AiSlotConfig, BotFactory};
+use crate::game::buildings;
+use crate::game::campaign::{self, Campaign, CampaignMission, CampaignProgress};
+use crate::game::commands::{
+    control_group_center, entity_at, existing_building_footprints, idle_workers, next_idle_worker, order_context,
+    process_commands, selected_control_groups, selection_kind, OrderContext,
+};
+use crate::game::data;
+use crate::game::environment;
+use crate::game::map::{self, MapGenerationParams};
+use crate::game::map_files;
+use crate::game::neutral;
+use crate::game::savegame;
+use crate::game::scenario::{self, ScenarioData, ScenarioWorld};
+use crate::game::scripting::{self, ScriptBot, Scripts};
+use crate::game::settings;
+use crate::game::simulation::{self, apply_tick_commands};
+use crate::game::tech;
+use crate::game::threat::ThreatMaps;
+use crate::game::stats::GameStats;
+use crate::game::units;
+use crate::game::victory::{VictoryConditionType, VictoryOutcome, VictoryState};
+use crate::networking::chat::{ChatChannel, ChatMessage};
+use crate::networking::checksum::world_checksum;
+use crate::networking::discovery::LanBrowser;
+use crate::networking::lockstep::{
+    GameStartInfo, LockstepNetwork, ResyncSnapshot, CHECKSUM_INTERVAL, DEFAULT_PORT, MAX_CATCH_UP_TICKS_PER_FRAME,
+    PLAYER_COLORS,
+};
+use crate::networking::pause::{PauseState, OTHERS_RESUME_AFTER};
 use crate::ui::UiManager;
+use crate::ui::i18n::{self, tr, tr_args};
+use crate::ui::menus::{KeybindingRequest, LobbyRequest, MenuCallbacks, SaveRequest};
+use crate::ui::hud::{AbilityInfo, BuildingInfo, HudRequest, TechNode, UnitInfo, UpgradeOption, TECH_NODE_SIZE};
+use crate::ui::minimap::MinimapRequest;
+use crate::ui::perf_overlay::FrameStats;
+
+/// Save slot used by the quick save / quick load keys
+const QUICKSAVE_SLOT: u8 = 0;
+
+/// Workers each player starts a multiplayer game with, next to their headquarters
+const STARTING_WORKERS: usize = 4;
+
+/// Sound effects loaded at startup (name, file under assets/audio)
+const SOUND_FILES: [(&str, &str); 6] = [
+    ("sfx_click", "sfx/click.wav"),
+    ("sfx_select", "sfx/select.wav"),
+    ("sfx_move", "sfx/move.wav"),
+    ("sfx_attack", "sfx/attack.wav"),
+    ("sfx_build", "sfx/build.wav"),
+    ("sfx_explosion", "sfx/explosion.wav"),
+];
 
 /// Main engine struct that coordinates all subsystems
 pub struct Engine {
@@ -26,10 +104,23 @@ pub struct Engine {
     input_handler: input::InputHandler,
     time_system: time::TimeSystem,
     asset_manager: assets::AssetManager,
+    audio: audio::AudioSystem,
     world: World,
-    game_state: GameState,
+    game_state: Rc<RefCell<GameState>>, // Shared with the menu callbacks, which change settings and start games
     network: Option<LockstepNetwork>,
+    lan_browser: Option<LanBrowser>, // Listens for hosts on the local network while the multiplayer menu shows
+    browsing_lan: bool,              // The multiplayer menu showed last frame
     ui_manager: UiManager,
+    menu_callbacks: MenuCallbacks, // Requests from menu controls, carried out every frame
+    ai_players: AiPlayers,
+    stalled: bool, // Waiting on other players' lockstep input
+    network_paused: bool, // The game is paused because a player paused the multiplayer game
+    schedule: Schedule, // Simulation systems, built once and reused every tick
+    applied_video: Option<renderer::VideoSettings>, // Video options the window and surface currently use
+    modifiers: winit::event::ModifiersState, // Held modifier keys, for text field shortcuts
+    debug: bool, // Developer console and cheats are available, in singleplayer only
+    last_frame: Instant, // When the previous frame started, for the performance overlay
+    last_autosave_tick: u64,
 }
 
 impl Engine {
@@ -43,7 +134,7 @@ impl Engine {
         // Initialize subsystems
         let renderer = renderer::Renderer::new(&window).await?;
         let input_handler = input::InputHandler::new();
-        let time_system = time::TimeSystem::new(20.0); // 20 ticks per second
+        let time_system = time::TimeSystem::new(TICK_RATE as f64);
         
         // Initialize asset manager
         let asset_manager = assets::AssetManager::new(
@@ -53,23 +144,33 @@ impl Engine {
         );
         
         // Initialize ECS world
-        let mut world = ecs::init_world();
+        let world = init_game_world();
         
-        // Add combat-specific resources
-        world.insert_resource(DamageTable::default());
+        // Create game state with the settings saved from the last session
+        let mut game_state = GameState::new();
+        settings::load_settings(&mut game_state.settings);
         
-        // Create game state
-        let game_state = GameState::new();
+        // Initialize audio with the player's volume settings
+        let mut audio = audio::AudioSystem::new();
+        audio.apply_settings(&game_state.settings.audio);
         
-        // Initialize UI manager
-        let ui_manager = UiManager::new(
+        // Initialize UI manager, laid out in logical pixels for the window's DPI
+        let window_size = window.inner_size();
+        let mut ui_manager = UiManager::new(
             renderer.get_device().clone(),
             renderer.get_queue().clone(),
-            width,
-            height,
+            window_size.width,
+            window_size.height,
+            window.scale_factor() as f32,
             renderer.get_surface_format(),
         )?;
         
+        // Menu controls change the game state directly, and leave their screen changes and requests for
+        // the frame loop to carry out
+        let game_state = Rc::new(RefCell::new(game_state));
+        let menu_callbacks = MenuCallbacks::new(Rc::clone(&game_state));
+        menu_callbacks.attach_callbacks(&mut ui_manager);
+        
         Ok((
             Self {
                 window,
@@ -77,39 +178,123 @@ impl Engine {
                 input_handler,
                 time_system,
                 asset_manager,
+                audio,
                 world,
                 game_state,
                 network: None,
+                lan_browser: None,
+                browsing_lan: false,
                 ui_manager,
+                menu_callbacks,
+                ai_players: AiPlayers::new(),
+                stalled: false,
+                network_paused: false,
+                schedule: simulation::build_schedule(),
+                applied_video: None,
+                modifiers: winit::event::ModifiersState::empty(),
+                debug: false,
+                last_frame: Instant::now(),
+                last_autosave_tick: 0,
             },
             event_loop,
         ))
     }
     
     pub fn enable_networking(&mut self, is_host: bool, address: Option<&str>) -> Result<()> {
-        let mut network = LockstepNetwork::new();
+        let mut network = LockstepNetwork::new(self.game_state.borrow().settings.network_transport);
         
+        let player_name = self.game_state.borrow().settings.player_name.clone();
         if is_host {
-            network.host_game(12345, "Host".to_string())?;
+            network.host_game(DEFAULT_PORT, player_name)?;
+            
+            // Without a forwarded port only players on our own network can join, so failing is no reason to stop hosting
+            if self.game_state.borrow().settings.upnp {
+                let notice = match network.open_port_mapping() {
+                    Ok(address) => format!("Players outside your network can join at {}", address),
+                    Err(e) => format!("Could not forward port {} with UPnP: {}", DEFAULT_PORT, e),
+                };
+                network.post_system_message(notice);
+            }
         } else if let Some(addr) = address {
-            network.join_game(addr, "Client".to_string())?;
+            // Players usually type just the host's IP
+            let addr = if addr.contains(':') { addr.to_string() } else { format!("{}:{}", addr, DEFAULT_PORT) };
+            network.join_game(&addr, player_name)?;
         } else {
             return Err(anyhow::anyhow!("Client mode requires a host address"));
         }
         
         self.network = Some(network);
-        self.game_state.is_multiplayer = true;
+        self.game_state.borrow_mut().is_multiplayer = true;
+        
+        // Cheats would break lockstep
+        self.ui_manager.close_console();
         
         Ok(())
     }
     
+    /// Make the developer console available in singleplayer games
+    pub fn enable_debug_console(&mut self) {
+        self.debug = true;
+    }
+    
+    /// Carry out a lobby action chosen in the multiplayer menus
+    pub fn handle_lobby_request(&mut self, request: LobbyRequest) -> Result<()> {
+        match request {
+            LobbyRequest::Host => self.enable_networking(true, None),
+            LobbyRequest::Join(address) => self.enable_networking(false, Some(&address)),
+            LobbyRequest::RefreshGames => {
+                // Also another try at listening, if another program held the port before
+                match &mut self.lan_browser {
+                    Some(browser) => browser.refresh(),
+                    None => self.lan_browser = Some(LanBrowser::new()?),
+                }
+                self.ui_manager.set_discovered_games(Vec::new());
+                Ok(())
+            }
+            LobbyRequest::Leave => {
+                if let Some(mut network) = self.network.take() {
+                    network.leave()?;
+                }
+                self.game_state.borrow_mut().is_multiplayer = false;
+                Ok(())
+            }
+            LobbyRequest::SetReady(ready) => self.lobby_network()?.set_ready(ready),
+            LobbyRequest::SetTeam(team) => self.lobby_network()?.set_team(team),
+            LobbyRequest::SetColor(color) => self.lobby_network()?.set_color(color),
+            LobbyRequest::Chat(text) => self.lobby_network()?.send_chat(text, ChatChannel::All),
+            LobbyRequest::StartGame => {
+                let seed = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let shared_vision = self.game_state.borrow().settings.shared_vision;
+                let day_night_cycle = self.game_state.borrow().settings.day_night_cycle;
+                let victory = self.game_state.borrow().settings.victory_condition;
+                let map_params = MapGenerationParams {
+                    symmetry: self.game_state.borrow().settings.map_symmetry,
+                    ..MapGenerationParams::preset(self.game_state.borrow().settings.map_preset)
+                };
+                self.lobby_network()?.start_game(seed, map_params, shared_vision, day_night_cycle, victory)
+            }
+        }
+    }
+    
+    /// The lobby's network session, if we have hosted or joined one
+    pub fn lobby_network(&mut self) -> Result<&mut LockstepNetwork> {
+        self.network.as_mut().ok_or_else(|| anyhow::anyhow!("Not in a multiplayer lobby"))
+    }
+    
     pub fn load_assets(&mut self) -> Result<()> {
+        // Load unit, building and tech definitions before anything reads them
+        data::init(data::DEFAULT_DATA_DIR)?;
+        
         // Load textures
         self.asset_manager.load_texture("unit_worker", "units/worker.png")?;
         self.asset_manager.load_texture("unit_soldier", "units/soldier.png")?;
         self.asset_manager.load_texture("unit_scout", "units/scout.png")?;
         self.asset_manager.load_texture("unit_tank", "units/tank.png")?;
         self.asset_manager.load_texture("unit_healer", "units/healer.png")?;
+        self.asset_manager.load_texture("unit_transport", "units/transport.png")?;
         
         self.asset_manager.load_texture("building_hq", "buildings/headquarters.png")?;
         self.asset_manager.load_texture("building_barracks", "buildings/barracks.png")?;
@@ -117,32 +302,64 @@ impl Engine {
         self.asset_manager.load_texture("building_resource", "buildings/resource_collector.png")?;
         self.asset_manager.load_texture("building_research", "buildings/research_center.png")?;
         self.asset_manager.load_texture("building_defense", "buildings/defense_tower.png")?;
+        self.asset_manager.load_texture("building_supply", "buildings/supply_depot.png")?;
+        self.asset_manager.load_texture("building_fortress", "buildings/fortress.png")?;
+        self.asset_manager.load_texture("building_wall", "buildings/wall.png")?;
+        self.asset_manager.load_texture("building_gate", "buildings/gate.png")?;
         
         self.asset_manager.load_texture("terrain_ground", "terrain/ground.png")?;
         self.asset_manager.load_texture("terrain_water", "terrain/water.png")?;
         self.asset_manager.load_texture("terrain_mountain", "terrain/mountain.png")?;
         self.asset_manager.load_texture("terrain_forest", "terrain/forest.png")?;
         
+        for (terrain, name) in [
+            (TerrainTile::Ground, "terrain_ground"),
+            (TerrainTile::Water, "terrain_water"),
+            (TerrainTile::Mountain, "terrain_mountain"),
+            (TerrainTile::Forest, "terrain_forest"),
+        ] {
+            if let Some(texture) = self.asset_manager.get_texture(name) {
+                self.renderer.set_terrain_texture(terrain, &texture);
+            }
+        }
+        
         self.asset_manager.load_texture("resource_mineral", "resources/mineral.png")?;
         self.asset_manager.load_texture("resource_gas", "resources/gas.png")?;
         self.asset_manager.load_texture("resource_energy", "resources/energy.png")?;
         
+        // Units, buildings and resources are drawn from their textures once loaded
+        for name in [
+            "unit_worker", "unit_soldier", "unit_scout", "unit_tank", "unit_healer", "unit_transport",
+            "building_hq", "building_barracks", "building_factory", "building_resource",
+            "building_research", "building_defense", "building_supply", "building_fortress",
+            "building_wall", "building_gate",
+            "resource_mineral", "resource_gas", "resource_energy",
+        ] {
+            if let Some(texture) = self.asset_manager.get_texture(name) {
+                self.renderer.set_sprite_texture(name, &texture);
+            }
+        }
+        
         self.asset_manager.load_texture("effect_explosion", "effects/explosion.png")?;
         self.asset_manager.load_texture("effect_fire", "effects/fire.png")?;
         self.asset_manager.load_texture("effect_smoke", "effects/smoke.png")?;
         
+        // Digits and letters for floating combat text
+        self.asset_manager.load_texture(combat_text::COMBAT_TEXT_TEXTURE, "effects/combat_text.png")?;
+        if let Some(texture) = self.asset_manager.get_texture(combat_text::COMBAT_TEXT_TEXTURE) {
+            self.renderer.set_sprite_texture(combat_text::COMBAT_TEXT_TEXTURE, &texture);
+        }
+        
         self.asset_manager.load_texture("ui_panel", "ui/panel.png")?;
         self.asset_manager.load_texture("ui_button", "ui/button.png")?;
         self.asset_manager.load_texture("ui_icons", "ui/icons.png")?;
         self.asset_manager.load_texture("ui_minimap_frame", "ui/minimap_frame.png")?;
         
-        // Load sounds
-        self.asset_manager.load_sound("sfx_click", "sfx/click.wav")?;
-        self.asset_manager.load_sound("sfx_select", "sfx/select.wav")?;
-        self.asset_manager.load_sound("sfx_move", "sfx/move.wav")?;
-        self.asset_manager.load_sound("sfx_attack", "sfx/attack.wav")?;
-        self.asset_manager.load_sound("sfx_build", "sfx/build.wav")?;
-        self.asset_manager.load_sound("sfx_explosion", "sfx/explosion.wav")?;
+        // Load sounds and hand them to the audio system
+        for (name, path) in SOUND_FILES {
+            let sound = self.asset_manager.load_sound(name, path)?;
+            self.audio.load_sound(name, sound.data.clone(), sound.sample_rate, 2)?;
+        }
         
         Ok(())
     }
@@ -153,74 +370,573 @@ impl Engine {
             
             match event {
                 Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                    // Let the other players know rather than leaving them waiting on our input
+                    if let Some(network) = &mut self.network {
+                        if let Err(e) = network.leave() {
+                            error!(target: "net", "Network error: {}", e);
+                        }
+                    }
                     *control_flow = ControlFlow::Exit;
                 }
                 
                 Event::WindowEvent { event: WindowEvent::Resized(new_size), .. } => {
-                    self.renderer.resize(new_size);
-                    self.ui_manager.resize(new_size.width, new_size.height);
+                    self.resize(new_size);
+                }
+                
+                Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { new_inner_size, .. }, .. } => {
+                    self.resize(*new_inner_size);
                 }
                 
                 Event::WindowEvent { event, .. } => {
+                    // Typing goes to the focused text field instead of the game
+                    match event {
+                        WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+                        WindowEvent::ReceivedCharacter(c) => {
+                            self.ui_manager.handle_char(c);
+                        }
+                        WindowEvent::KeyboardInput { input: winit::event::KeyboardInput { state: winit::event::ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } => {
+                            if self.ui_manager.handle_key(key, self.modifiers) {
+                                return;
+                            }
+                            
+                            // Keys in the hotkey grid press the action button in their slot unless a binding uses
+                            // them; Ctrl and Alt combinations, and a key being bound in the controls menu, pass through
+                            let plain = !self.modifiers.ctrl() && !self.modifiers.alt();
+                            if plain && self.input_handler.rebinding().is_none() && self.ui_manager.handle_hotkey(key) {
+                                self.play_ui_click();
+                                return;
+                            }
+                        }
+                        _ => {}
+                    }
+                    
+                    // Quick save / quick load
+                    if let WindowEvent::KeyboardInput { input: winit::event::KeyboardInput { state: winit::event::ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } = event {
+                        match key {
+                            winit::event::VirtualKeyCode::F5 => {
+                                if let Err(e) = self.save_game(QUICKSAVE_SLOT) {
+                                    error!("Failed to save game: {}", e);
+                                }
+                            }
+                            winit::event::VirtualKeyCode::F9 => {
+                                if let Err(e) = self.load_game(QUICKSAVE_SLOT) {
+                                    error!("Failed to load game: {}", e);
+                                }
+                            }
+                            // Enter opens the chat box, Shift+Enter for allies only
+                            winit::event::VirtualKeyCode::Return if self.game_state.borrow().phase == crate::game::GamePhase::Playing => {
+                                let channel = if self.modifiers.shift() { ChatChannel::Allies } else { ChatChannel::All };
+                                self.ui_manager.open_chat(channel);
+                                return;
+                            }
+                            _ => {}
+                        }
+                    }
+                    
+                    // UI elements show hover and press states and sliders follow drags; clicks are handled on release below
+                    match event {
+                        WindowEvent::CursorMoved { position, .. } => {
+                            self.ui_manager.handle_mouse_move(Vec2::new(position.x as f32, position.y as f32));
+                        }
+                        WindowEvent::MouseInput { state, button: winit::event::MouseButton::Left, .. } => {
+                            let mouse_pos = self.input_handler.get_mouse_position();
+                            match state {
+                                winit::event::ElementState::Pressed => {
+                                    self.ui_manager.handle_press(mouse_pos);
+                                }
+                                winit::event::ElementState::Released => self.ui_manager.handle_release(mouse_pos),
+                            }
+                        }
+                        _ => {}
+                    }
+                    
+                    // Dragging on the minimap keeps moving the camera, even once the cursor leaves it
+                    if let WindowEvent::CursorMoved { position, .. } = event {
+                        self.ui_manager.drag_minimap(Vec2::new(position.x as f32, position.y as f32));
+                    }
+                    if let WindowEvent::MouseInput { state: winit::event::ElementState::Released, button: winit::event::MouseButton::Left, .. } = event {
+                        if self.ui_manager.end_minimap_drag() {
+                            return;
+                        }
+                    }
+                    
+                    // Clicks on the minimap move the camera, target a pending order or order the selection
+                    // instead of reaching the world, unless they end a drag that started in the world
+                    let mouse_pos = self.input_handler.get_mouse_position();
+                    if let WindowEvent::MouseInput { state, button, .. } = event {
+                        if self.ui_manager.is_over_minimap(mouse_pos) && !self.input_handler.is_selection_active() {
+                            match (state, button) {
+                                (winit::event::ElementState::Pressed, winit::event::MouseButton::Left) => {
+                                    let clicked = if self.input_handler.get_target_mode().is_some() {
+                                        self.ui_manager.handle_minimap_target_click(mouse_pos)
+                                    } else {
+                                        self.ui_manager.begin_minimap_drag(mouse_pos)
+                                    };
+                                    if clicked {
+                                        self.play_ui_click();
+                                    }
+                                }
+                                (winit::event::ElementState::Released, winit::event::MouseButton::Right) => {
+                                    self.ui_manager.handle_right_click(mouse_pos);
+                                }
+                                _ => {}
+                            }
+                            return;
+                        }
+                    }
+                    
                     // Forward window events to input handler
                     self.input_handler.handle_window_event(&event);
                     
                     // Handle UI input
                     if let WindowEvent::MouseInput { state: winit::event::ElementState::Released, button: winit::event::MouseButton::Left, .. } = event {
-                        let mouse_pos = self.input_handler.get_mouse_position();
                         if self.ui_manager.handle_input(mouse_pos) {
                             // UI handled the click, no need to forward to game
+                            self.play_ui_click();
                             continue;
                         }
                     }
                 }
                 
                 Event::MainEventsCleared => {
+                    let frame_start = Instant::now();
+                    let frame_time = frame_start.duration_since(self.last_frame);
+                    self.last_frame = frame_start;
+                    
+                    // Carry out requests raised by HUD buttons
+                    for request in self.ui_manager.take_hud_requests() {
+                        match request {
+                            HudRequest::PlaceBuilding(building_type) => {
+                                self.input_handler.begin_placement(building_type);
+                            }
+                            HudRequest::AttackMove => {
+                                self.input_handler.begin_targeting(input::TargetMode::AttackMove);
+                            }
+                            HudRequest::Patrol => {
+                                self.input_handler.begin_targeting(input::TargetMode::Patrol(None));
+                            }
+                            HudRequest::Stop => {
+                                self.input_handler.handle_command(input::Command::Stop);
+                            }
+                            HudRequest::HoldPosition => {
+                                self.input_handler.handle_command(input::Command::HoldPosition);
+                            }
+                            HudRequest::SetStance(stance) => {
+                                self.input_handler.handle_command(input::Command::SetStance(stance));
+                            }
+                            HudRequest::Train(unit_type) => {
+                                self.input_handler.handle_command(input::Command::Train(input::UnitCommand {
+                                    unit_type: unit_type as u8,
+                                }));
+                            }
+                            HudRequest::CancelTrain(slot) => {
+                                self.input_handler.handle_command(input::Command::CancelTrain(slot));
+                            }
+                            HudRequest::Research(tech_type) => {
+                                self.input_handler.handle_command(input::Command::Research(tech_type as u8));
+                            }
+                            HudRequest::UseAbility(ability_id) => {
+                                let needs_target = data::game_data().ability(ability_id).map_or(false, |ability| {
+                                    matches!(ability.target, AbilityTarget::Point | AbilityTarget::Entity)
+                                });
+                                if needs_target {
+                                    self.input_handler.begin_targeting(input::TargetMode::Ability(ability_id));
+                                } else {
+                                    self.input_handler.handle_command(input::Command::UseAbility(input::AbilityCommand {
+                                        ability_id,
+                                        target_position: None,
+                                        target_entity_id: None,
+                                    }));
+                                }
+                            }
+                            HudRequest::SelectIdleWorker => self.select_idle_worker(),
+                            HudRequest::Load => {
+                                self.input_handler.handle_command(input::Command::Load);
+                            }
+                            HudRequest::Unload => {
+                                self.input_handler.handle_command(input::Command::Unload);
+                            }
+                            HudRequest::UnloadSlot(slot) => {
+                                self.input_handler.handle_command(input::Command::UnloadSlot(slot));
+                            }
+                            HudRequest::Ungarrison => {
+                                self.input_handler.handle_command(input::Command::Ungarrison);
+                            }
+                            HudRequest::UngarrisonSlot(slot) => {
+                                self.input_handler.handle_command(input::Command::UngarrisonSlot(slot));
+                            }
+                            HudRequest::Upgrade => {
+                                self.input_handler.handle_command(input::Command::UpgradeBuilding);
+                            }
+                            HudRequest::Surrender => {
+                                self.input_handler.handle_command(input::Command::Surrender);
+                            }
+                        }
+                    }
+                    
+                    // Carry out requests raised by minimap clicks
+                    for request in self.ui_manager.take_minimap_requests() {
+                        match request {
+                            MinimapRequest::JumpCamera(position) => {
+                                self.input_handler.set_camera_position(position);
+                            }
+                            MinimapRequest::Order(position) => {
+                                self.input_handler.right_click_at(position);
+                            }
+                            MinimapRequest::Target(position) => {
+                                self.input_handler.target_at(position);
+                            }
+                        }
+                    }
+                    
+                    // Switch screens and read the menus' text fields as their controls asked, then act on
+                    // the multiplayer menus' requests
+                    self.menu_callbacks.run_ui_actions(&mut self.ui_manager);
+                    if let Some(request) = self.menu_callbacks.take_lobby_request() {
+                        if let Err(e) = self.handle_lobby_request(request) {
+                            error!(target: "net", "Lobby action failed: {}", e);
+                            self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
+                        }
+                    }
+                    
+                    // The controls menu's key buttons wait for the next key press, which the input handler binds
+                    match self.menu_callbacks.take_keybinding_request() {
+                        Some(KeybindingRequest::Rebind(action)) => {
+                            self.input_handler.begin_rebind(action);
+                            self.ui_manager.show_warning(tr_args("warning.press_key", &[("action", &action.name())]));
+                        }
+                        Some(KeybindingRequest::ResetDefaults) => {
+                            self.input_handler.reset_keybindings();
+                            self.ui_manager.show_warning(tr("warning.keys_reset"));
+                        }
+                        None => {}
+                    }
+                    
+                    // The pause menu's save and load buttons
+                    match self.menu_callbacks.take_save_request() {
+                        Some(SaveRequest::Save(slot)) => {
+                            if let Err(e) = self.save_game(slot) {
+                                error!("Failed to save game: {}", e);
+                                self.ui_manager.show_warning(tr_args("warning.save_failed", &[("error", &e.to_string())]));
+                            }
+                        }
+                        Some(SaveRequest::Load(slot)) => {
+                            if let Err(e) = self.load_game(slot) {
+                                error!("Failed to load game: {}", e);
+                                self.ui_manager.show_warning(tr_args("warning.load_failed", &[("error", &e.to_string())]));
+                            }
+                        }
+                        None => {}
+                    }
+                    
+                    // The tutorial button starts its scenario
+                    if let Some(path) = self.menu_callbacks.take_scenario_request() {
+                        match self.start_scenario(&path) {
+                            Ok(()) => self.ui_manager.set_active_screen("game"),
+                            Err(e) => {
+                                error!("Failed to start scenario {}: {}", path.display(), e);
+                                self.ui_manager.show_warning(tr_args("warning.scenario_failed", &[("error", &e.to_string())]));
+                            }
+                        }
+                    }
+                    
+                    // A briefing's start button starts its campaign mission
+                    if let Some(index) = self.menu_callbacks.take_campaign_request() {
+                        match self.start_campaign_mission(index) {
+                            Ok(()) => self.ui_manager.set_active_screen("game"),
+                            Err(e) => {
+                                error!("Failed to start campaign mission {}: {}", index + 1, e);
+                                self.ui_manager.show_warning(tr_args("warning.scenario_failed", &[("error", &e.to_string())]));
+                            }
+                        }
+                    }
+                    
+                    // Settings may have changed in the menu since the last frame, and the settings
+                    // menu's save button has already written them out
+                    self.apply_settings();
+                    
+                    // Scroll and zoom the camera, keeping it over the map
+                    if let Some(map) = self.world.get_resource::<GameMap>() {
+                        self.input_handler.set_camera_bounds(map.width as f32 * terrain::TILE_SIZE, map.height as f32 * terrain::TILE_SIZE);
+                    }
+                    self.input_handler.apply_camera_settings(&self.game_state.borrow().settings.camera);
+                    self.input_handler.set_edge_scroll_suspended(self.ui_manager.is_dragging_minimap());
+                    self.input_handler.update_camera();
+                    
+                    // Keep the placement ghost under the cursor
+                    self.update_placement_ghost();
+                    self.update_selection_overlay();
+                    self.update_cursor();
+                    
+                    // Double-tapping a group key jumps the camera to the group
+                    if let Some(group) = self.input_handler.take_group_focus() {
+                        if let Some(center) = control_group_center(&self.world, self.local_player_id(), group) {
+                            self.input_handler.set_camera_position(center);
+                        }
+                    }
+                    
+                    // Tab switches which type in a mixed selection the HUD offers commands for
+                    if self.input_handler.take_subgroup_cycle() {
+                        if let Some(mut selection) = self.world.get_resource_mut::<SelectionState>() {
+                            selection.cycle_subgroup();
+                        }
+                        self.refresh_selection_hud(self.local_player_id());
+                    }
+                    
+                    // Any game can be paused, multiplayer ones for everyone; only singleplayer ones change speed
+                    if self.input_handler.take_pause_toggle() {
+                        self.toggle_pause();
+                    }
+                    let speed_steps = self.input_handler.take_speed_steps();
+                    if speed_steps != 0 {
+                        self.change_game_speed(speed_steps);
+                    }
+                    
+                    // Orders given while paused are applied now and carried out once the game resumes
+                    if self.game_state.borrow().phase == crate::game::GamePhase::Paused && self.network.is_none() {
+                        let commands = self.input_handler.get_commands();
+                        if !commands.is_empty() {
+                            let local_player_id = self.local_player_id();
+                            process_commands(&mut self.world, local_player_id, &commands);
+                            self.ui_manager.set_control_groups(selected_control_groups(&self.world, local_player_id));
+                            self.refresh_selection_hud(local_player_id);
+                        }
+                    }
+                    
+                    // Backtick drops the developer console down, and commands typed into it run right away
+                    if self.input_handler.take_console_toggle() {
+                        self.toggle_console();
+                    }
+                    for line in self.ui_manager.take_console_commands() {
+                        self.run_console_command(&line);
+                    }
+                    
+                    // F1 selects the next idle worker
+                    if self.input_handler.take_idle_worker_cycle() {
+                        self.select_idle_worker();
+                    }
+                    
+                    // Switch between always, damaged-only and no health bars, and remember the choice
+                    if self.input_handler.take_health_bar_cycle() {
+                        let mut game_state = self.game_state.borrow_mut();
+                        let settings = &mut game_state.settings;
+                        settings.health_bars = settings.health_bars.next();
+                        if let Err(e) = settings::save_settings(settings) {
+                            error!("Failed to save settings: {}", e);
+                        }
+                        
+                        let message = tr_args("warning.health_bars", &[("mode", settings.health_bars.name())]);
+                        self.ui_manager.show_warning(message);
+                    }
+                    
+                    // F3 shows or hides the performance overlay, the same option as the settings checkbox
+                    if self.input_handler.take_perf_overlay_toggle() {
+                        let mut game_state = self.game_state.borrow_mut();
+                        let settings = &mut game_state.settings;
+                        settings.show_fps = !settings.show_fps;
+                        if let Err(e) = settings::save_settings(settings) {
+                            error!("Failed to save settings: {}", e);
+                        }
+                        drop(game_state);
+                        self.apply_perf_overlay();
+                    }
+                    
+                    // F4 shows or hides the latest warnings and errors
+                    if self.input_handler.take_log_viewer_toggle() {
+                        let visible = self.ui_manager.is_log_viewer_visible();
+                        self.ui_manager.set_log_viewer_visible(!visible);
+                    }
+                    
+                    // M hides units, buildings and resources on the minimap to read the terrain
+                    if self.input_handler.take_minimap_terrain_toggle() {
+                        let key = if self.ui_manager.toggle_minimap_terrain_only() { "warning.minimap_terrain" } else { "warning.minimap_full" };
+                        self.ui_manager.show_warning(tr(key));
+                    }
+                    
                     // Process network messages if networking is enabled
                     if let Some(network) = &mut self.network {
                         if let Err(e) = network.process_messages() {
-                            eprintln!("Network error: {}", e);
+                            error!(target: "net", "Network error: {}", e);
+                            self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
+                        }
+                        
+                        // The host assigns our player ID when we join
+                        if let Some(mut player_info) = self.world.get_resource_mut::<PlayerInfo>() {
+                            player_info.local_player_id = network.local_player_id();
                         }
                     }
                     
-                    // Tick game logic at fixed rate
-                    while self.time_system.should_tick() {
+                    // Ping bars in the HUD show how our connection to the game is doing
+                    let connection_quality = self.network.as_ref().and_then(|network| network.local_connection_quality());
+                    self.ui_manager.set_connection_quality(connection_quality);
+                    
+                    // A player's pause holds every machine's ticks until the resume countdown runs out
+                    self.sync_network_pause();
+                    
+                    // Games hosted on the local network show up in the multiplayer menu
+                    self.update_lan_browser();
+                    
+                    // Chat typed into the chat box goes out to the other players
+                    self.update_chat();
+                    
+                    // The host's Start Game puts every peer into the same game
+                    if let Some(start) = self.network.as_mut().and_then(|network| network.take_game_start()) {
+                        self.start_network_game(start);
+                    }
+                    self.check_desync();
+                    
+                    // Tick game logic at fixed rate; after rejoining a game, the ticks missed are replayed
+                    // as fast as the frame allows
+                    let mut tick_time = Duration::ZERO;
+                    let mut ticks = 0;
+                    let mut catch_up_ticks = self.network
+                        .as_ref()
+                        .map_or(0, |network| network.ticks_behind().min(MAX_CATCH_UP_TICKS_PER_FRAME));
+                    while catch_up_ticks > 0 || self.time_system.should_tick() {
+                        let catching_up = catch_up_ticks > 0;
+                        catch_up_ticks = catch_up_ticks.saturating_sub(1);
+                        
                         // Only update if game is playing
-                        if self.game_state.phase == crate::game::GamePhase::Playing {
+                        if self.game_state.borrow().phase == crate::game::GamePhase::Playing {
                             // Process inputs
                             let commands = self.input_handler.get_commands();
+                            let local_player_id = self.local_player_id();
                             
-                            // Send commands to network if multiplayer
                             if let Some(network) = &mut self.network {
+                                // Local input joins the lockstep schedule like everyone else's
                                 if let Err(e) = network.send_commands(&commands) {
-                                    eprintln!("Error sending commands: {}", e);
+                                    error!(target: "net", "Error sending commands: {}", e);
+                                    self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
                                 }
                                 
-                                // Get commands from other players
-                                let network_commands = network.receive_commands();
+                                // Stall until every player's input for this tick has arrived
+                                let network_commands = match network.receive_commands() {
+                                    Some(network_commands) => network_commands,
+                                    None => {
+                                        let players = format!("{:?}", network.waiting_on());
+                                        self.ui_manager.show_alert(tr_args("alert.waiting", &[("players", &players)]));
+                                        self.stalled = true;
+                                        break;
+                                    }
+                                };
+                                if self.stalled {
+                                    self.ui_manager.clear_alert();
+                                    self.stalled = false;
+                                }
                                 
-                                // Process network commands
-                                // (In a real implementation, you'd merge these with local commands)
+                                apply_tick_commands(&mut self.world, &network_commands);
+                            } else {
+                                // Apply local player commands
+                                process_commands(&mut self.world, local_player_id, &commands);
+                            }
+                            self.ui_manager.set_control_groups(selected_control_groups(&self.world, local_player_id));
+                            
+                            // Let computer opponents issue their orders through the same path
+                            let (elapsed_time, delta_time) = self.world
+                                .get_resource::<GameTime>()
+                                .map(|time| (time.elapsed_time, time.delta_time))
+                                .unwrap_or((0.0, 0.0));
+                            for (player_id, ai_commands) in self.ai_players.update(&self.world, elapsed_time, delta_time) {
+                                process_commands(&mut self.world, player_id, &ai_commands);
                             }
                             
                             // Run ECS systems including combat
+                            let tick_start = Instant::now();
                             self.run_game_systems();
+                            tick_time += tick_start.elapsed();
+                            ticks += 1;
+                            
+                            // Show the selection, including production progress of the selected building
+                            self.refresh_selection_hud(local_player_id);
+                            
+                            // Refresh research progress in the tech tree
+                            let tech_tree = self.tech_tree_nodes(local_player_id);
+                            self.ui_manager.set_tech_tree(tech_tree);
+                            
+                            let idle_worker_count = idle_workers(&mut self.world, local_player_id).len();
+                            self.ui_manager.set_idle_workers(idle_worker_count);
+                            
+                            // Mirror supply into the game state for the HUD
+                            let mut game_state = self.game_state.borrow_mut();
+                            for player_id in 0..game_state.player_count {
+                                let supply = units::player_supply(&mut self.world, player_id);
+                                game_state.player_supply.insert(player_id, supply);
+                            }
+                            if let Some(stats) = self.world.get_resource::<GameStats>() {
+                                game_state.player_scores.extend(stats.scores());
+                            }
+                            drop(game_state);
+                            
+                            // Surface warnings raised by the local player's commands (e.g. supply blocked)
+                            if let Some(mut player_alerts) = self.world.get_resource_mut::<PlayerAlerts>() {
+                                for (player_id, message) in player_alerts.alerts.drain(..) {
+                                    if player_id == local_player_id {
+                                        self.ui_manager.show_warning(message);
+                                    }
+                                }
+                            }
+                            
+                            // Periodically compare world state with the other players
+                            if let Some(network) = &mut self.network {
+                                let tick = self.world.resource::<GameTime>().current_tick;
+                                if tick % CHECKSUM_INTERVAL == 0 {
+                                    let checksum = world_checksum(&mut self.world);
+                                    if let Err(e) = network.submit_checksum(tick, checksum) {
+                                        error!(target: "net", "Error sending checksum: {}", e);
+                                        self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
+                                    }
+                                }
+                            }
+                            self.check_desync();
                             
                             // Update game state
-                            self.game_state.update();
+                            self.game_state.borrow_mut().update();
+                            
+                            // The victory condition decides when the game is over
+                            if let Some(outcome) = self.world.get_resource::<VictoryState>().and_then(|victory| victory.outcome()) {
+                                self.end_game(outcome);
+                            }
                             
                             // Update UI
-                            self.ui_manager.update(&self.game_state);
+                            self.ui_manager.set_bound_keys(self.input_handler.keybindings().plain_keys());
+                            self.ui_manager.update(&self.game_state.borrow(), self.local_player_id());
                         }
                         
-                        // Update time system
-                        self.time_system.tick_completed();
+                        // Replayed ticks don't use up real time
+                        if !catching_up {
+                            self.time_system.tick_completed();
+                        }
+                    }
+                    
+                    // Play sounds raised by this frame's ticks
+                    self.play_audio_events();
+                    self.show_combat_text(frame_time.as_secs_f32());
+                    
+                    // Keep a recent save for the crash handler, and in the autosave slot if the player wants one
+                    if ticks > 0 {
+                        self.autosave_if_due();
                     }
                     
-                    // Render current game state
-                    self.render().unwrap_or_else(|e| {
-                        eprintln!("Render error: {}", e);
-                    });
+                    // Render current game state. A lost or outdated surface, e.g. after the window was
+                    // minimized or moved to another display, is set up again and drawn to next frame
+                    if let Err(e) = self.render() {
+                        match e.downcast_ref::<wgpu::SurfaceError>() {
+                            Some(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => self.renderer.reconfigure_surface(),
+                            Some(wgpu::SurfaceError::Timeout) => {}
+                            Some(wgpu::SurfaceError::OutOfMemory) => {
+                                error!(target: "render", "Render error: {}; exiting", e);
+                                if let Err(e) = self.save_game(savegame::AUTOSAVE_SLOT) {
+                                    error!("Failed to save game: {}", e);
+                                }
+                                *control_flow = ControlFlow::Exit;
+                            }
+                            None => error!(target: "render", "Render error: {}", e),
+                        }
+                    }
+                    
+                    if self.ui_manager.is_perf_overlay_visible() {
+                        self.record_frame_stats(frame_time, tick_time, ticks);
+                    }
                 }
                 
                 _ => {}
@@ -228,36 +944,1175 @@ impl Engine {
         })
     }
     
-    fn run_game_systems(&mut self) {
-        let mut schedule = Schedule::default();
+    /// Click feedback for buttons and other UI elements
+    fn play_ui_click(&self) {
+        if let Err(e) = self.audio.play_ui_sound(audio::UiSoundType::ButtonClick) {
+            error!("Audio error: {}", e);
+        }
+    }
+    
+    /// Drive the window, renderer, clock and world from the current game settings
+    fn apply_settings(&mut self) {
+        // Changing window mode or present mode is expensive, so only do it when they change
+        let video = self.game_state.borrow().settings.video.clone();
+        if self.applied_video.as_ref() != Some(&video) {
+            self.apply_window_mode(&video);
+            self.renderer.set_vsync(video.vsync);
+            self.applied_video = Some(video);
+        }
+        
+        let game_state = self.game_state.borrow();
+        let settings = &game_state.settings;
+        
+        // Lockstep peers must all run at the same rate, so game speed only applies offline
+        if self.network.is_none() {
+            self.time_system.set_time_scale(settings.game_speed as f64);
+        }
+        
+        // The renderer and minimap read gameplay options from the world
+        self.world.insert_resource(ecs::resources::GameSettings {
+            fog_of_war_enabled: settings.fog_of_war_enabled,
+            game_speed: settings.game_speed,
+            auto_save_enabled: settings.auto_save_enabled,
+            auto_save_interval: settings.auto_save_interval,
+            show_fps: settings.show_fps,
+            health_bars: settings.health_bars,
+            shared_vision: settings.shared_vision,
+        });
+        drop(game_state);
+        
+        self.apply_perf_overlay();
+        
+        // Recoloring every element is cheap, but only needed when the theme changes
+        let theme = self.game_state.borrow().settings.ui_theme.clone();
+        if self.ui_manager.theme_name() != theme {
+            self.ui_manager.set_theme(&theme);
+        }
+        
+        // Text is looked up when it is built, so what is showing is rebuilt in the new language
+        let language = self.game_state.borrow().settings.language.clone();
+        if i18n::current_language() != language {
+            i18n::set_language(&language);
+            self.ui_manager.retranslate();
+        }
+    }
+    
+    /// Show the performance overlay and time the simulation systems while show_fps is on
+    fn apply_perf_overlay(&mut self) {
+        let show_fps = self.game_state.borrow().settings.show_fps;
+        if self.ui_manager.is_perf_overlay_visible() != show_fps {
+            self.ui_manager.set_perf_overlay_visible(show_fps);
+            profiler::set_enabled(show_fps);
+        }
+    }
+    
+    /// Hand a frame's timings and the world's entity and draw call counts to the performance overlay
+    fn record_frame_stats(&mut self, frame_time: Duration, tick_time: Duration, ticks: u32) {
+        let units = self.world.query::<&Unit>().iter(&self.world).count() as u32;
+        let buildings = self.world.query::<&Building>().iter(&self.world).count() as u32;
+        
+        self.ui_manager.record_frame_stats(FrameStats {
+            frame_time: frame_time.as_secs_f32() * 1000.0,
+            tick_time: (ticks > 0).then(|| tick_time.as_secs_f32() * 1000.0),
+            ticks,
+            entities: self.world.entities().len(),
+            units,
+            buildings,
+            draw_calls: self.renderer.draw_calls(),
+            culling: self.renderer.cull_stats(),
+            system_times: profiler::system_times()
+                .into_iter()
+                .map(|(name, time)| (name, time.as_secs_f32() * 1000.0))
+                .collect(),
+            network: self.network.as_ref().map(|network| network.bandwidth()),
+        });
+    }
+    
+    /// Put the window into the chosen display mode; the resize event that follows updates the view
+    fn apply_window_mode(&mut self, video: &renderer::VideoSettings) {
+        let (width, height) = video.resolution;
+        match video.window_mode {
+            renderer::WindowMode::Windowed => {
+                self.window.set_fullscreen(None);
+                self.window.set_inner_size(PhysicalSize::new(width, height));
+            }
+            renderer::WindowMode::Borderless => {
+                self.window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+            }
+            renderer::WindowMode::Exclusive => {
+                // Closest display mode to the chosen resolution, preferring higher refresh rates
+                let video_mode = self.window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().min_by_key(|mode| {
+                        let size = mode.size();
+                        let distance = (size.width as i64 - width as i64).abs() + (size.height as i64 - height as i64).abs();
+                        (distance, std::cmp::Reverse(mode.refresh_rate_millihertz()))
+                    })
+                });
+                match video_mode {
+                    Some(video_mode) => self.window.set_fullscreen(Some(Fullscreen::Exclusive(video_mode))),
+                    None => {
+                        warn!(target: "render", "No exclusive fullscreen mode available, using borderless fullscreen");
+                        self.window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Fit the renderer, UI layout and camera to a new window size or DPI scale
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        // Minimized windows report a zero size; keep the last layout until restored
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+        
+        self.renderer.resize(new_size);
+        self.ui_manager.resize(new_size.width, new_size.height, self.window.scale_factor() as f32);
+        self.input_handler.resize_view(new_size.width as f32, new_size.height as f32);
+    }
+    
+    /// Play the sounds the simulation queued that the local player should hear
+    fn play_audio_events(&mut self) {
+        // Settings may have changed in the menu since the last frame
+        self.audio.apply_settings(&self.game_state.borrow().settings.audio);
+        
+        let camera = self.input_handler.get_camera_position();
+        self.audio.set_listener_position((camera.x, camera.y));
+        
+        let local_player_id = self.local_player_id();
+        let events = match self.world.get_resource_mut::<AudioEvents>() {
+            Some(mut audio_events) => std::mem::take(&mut audio_events.events),
+            None => return,
+        };
+        for event in events {
+            if event.player.map_or(true, |player| player == local_player_id) {
+                if let Err(e) = self.audio.play_game_sound(event.sound, (event.position.x, event.position.y)) {
+                    error!("Audio error: {}", e);
+                }
+            }
+        }
+        
+        self.audio.update();
+    }
+    
+    /// Float the damage, heal and miss text this frame's ticks raised, while the setting is on
+    fn show_combat_text(&mut self, delta_time: f32) {
+        let texts = match self.world.get_resource_mut::<CombatTextEvents>() {
+            Some(mut combat_text) => std::mem::take(&mut combat_text.texts),
+            None => return,
+        };
+        if self.game_state.borrow().settings.combat_text {
+            self.renderer.add_combat_text(texts);
+        } else {
+            self.renderer.clear_combat_text();
+        }
+        self.renderer.update_combat_text(delta_time);
+    }
+    
+    /// Recover when the simulation has diverged between machines: the host sends its world to everyone
+    /// and every machine, the host included, restores it. The game only stops if that fails
+    fn check_desync(&mut self) {
+        if self.game_state.borrow().phase != crate::game::GamePhase::Playing {
+            return;
+        }
+        let network = match &mut self.network {
+            Some(network) => network,
+            None => return,
+        };
+        
+        // Other players carry on until the host's world arrives
+        if let Some(desync) = network.desync().filter(|_| network.is_host()).map(|desync| desync.to_string()) {
+            warn!(target: "net", "{}; sending our world to every player", desync);
+            let sent = savegame::capture_snapshot(&mut self.world, &self.game_state.borrow())
+                .and_then(|snapshot| network.send_resync(snapshot));
+            if let Err(e) = sent {
+                self.stop_for_desync(format!("{}; resync failed: {}", desync, e));
+                return;
+            }
+        }
+        
+        if let Some(snapshot) = network.take_resync() {
+            if let Err(e) = self.restore_resync(&snapshot) {
+                self.stop_for_desync(format!("Failed to restore the host's world at tick {}: {}", snapshot.tick, e));
+            }
+        }
+    }
+    
+    /// Replace the world with the host's after a desync, and warn the player it happened
+    fn restore_resync(&mut self, snapshot: &ResyncSnapshot) -> Result<()> {
+        savegame::restore_snapshot(&mut self.world, &mut self.game_state.borrow_mut(), &snapshot.data)?;
+        
+        // Computer players forget what they knew about the old world. Statistics and victory progress carry on
+        self.start_ai_players();
+        if let Some(mut game_time) = self.world.get_resource_mut::<GameTime>() {
+            game_time.current_tick = snapshot.tick;
+        }
+        
+        info!(target: "net", "Resynced to the host's world at tick {} (resync {})", snapshot.tick, snapshot.epoch);
+        let message = tr_args("warning.resynced", &[("tick", &snapshot.tick.to_string())]);
+        if let Some(network) = &mut self.network {
+            network.post_system_message(message.clone());
+        }
+        self.ui_manager.show_warning(message);
+        Ok(())
+    }
+    
+    /// Stop the game and tell the player the simulation diverged beyond recovery
+    fn stop_for_desync(&mut self, message: String) {
+        error!(target: "net", "{}", message);
+        if let Some(network) = &mut self.network {
+            network.post_system_message(message.clone());
+        }
+        self.ui_manager.show_alert(message);
+        self.game_state.borrow_mut().phase = crate::game::GamePhase::Paused;
+    }
+    
+    /// Listen for LAN games only while the player is picking one to join, and keep the menu's list current
+    fn update_lan_browser(&mut self) {
+        let browsing = self.network.is_none() && self.ui_manager.active_screen() == "multiplayer";
+        if !browsing {
+            self.lan_browser = None;
+            self.browsing_lan = false;
+            return;
+        }
+        
+        // Just opened the menu; start from an empty list
+        if !self.browsing_lan {
+            self.browsing_lan = true;
+            match LanBrowser::new() {
+                Ok(browser) => self.lan_browser = Some(browser),
+                Err(e) => warn!(target: "net", "Failed to listen for games on the local network: {}", e),
+            }
+            self.ui_manager.set_discovered_games(Vec::new());
+        }
+        
+        if let Some(browser) = &mut self.lan_browser {
+            if browser.update() {
+                self.ui_manager.set_discovered_games(browser.games());
+            }
+        }
+    }
+    
+    /// Send the chat box's lines, and show every new chat line in the HUD message log
+    fn update_chat(&mut self) {
+        let local_player_id = self.local_player_id();
+        let mut messages = Vec::new();
+        for (channel, text) in self.ui_manager.take_outgoing_chat() {
+            match &mut self.network {
+                Some(network) => {
+                    if let Err(e) = network.send_chat(text, channel) {
+                        error!(target: "net", "Error sending chat: {}", e);
+                        self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
+                    }
+                }
+                // Nobody to send to, but the player still sees what they wrote
+                None => messages.push(ChatMessage { player_id: local_player_id, channel, text }),
+            }
+        }
+        
+        if let Some(network) = &mut self.network {
+            messages.extend(network.take_new_chat());
+        }
+        
+        for message in messages {
+            let system = message.channel == ChatChannel::System;
+            let line = self.chat_line(&message);
+            self.ui_manager.add_chat_line(line, system);
+        }
+    }
+    
+    /// Message log text for a chat line, prefixed with its sender
+    fn chat_line(&self, message: &ChatMessage) -> String {
+        let sender = self.world
+            .get_resource::<PlayerInfo>()
+            .and_then(|info| info.player_names.get(&message.player_id).cloned())
+            .unwrap_or_else(|| format!("Player {}", message.player_id + 1));
+        
+        match message.channel {
+            ChatChannel::All => format!("{}: {}", sender, message.text),
+            ChatChannel::Allies => format!("[Allies] {}: {}", sender, message.text),
+            ChatChannel::System => message.text.clone(),
+        }
+    }
+    
+    /// Player controlled from this machine
+    fn local_player_id(&self) -> u8 {
+        self.world
+            .get_resource::<PlayerInfo>()
+            .map(|info| info.local_player_id)
+            .unwrap_or(0)
+    }
+    
+    /// Regroup the player's selection by type and show it, and the active subgroup's commands, on the HUD
+    fn refresh_selection_hud(&mut self, player_id: u8) {
+        let mut query = self.world.query_filtered::<(Entity, &Owner), With<Selected>>();
+        let mut selected: Vec<Entity> = query
+            .iter(&self.world)
+            .filter(|(_, owner)| owner.0 == player_id)
+            .map(|(entity, _)| entity)
+            .collect();
+        selected.sort();
+        self.input_handler.set_has_selection(!selected.is_empty());
+        
+        let kinds: Vec<(Entity, SelectionKind)> = selected
+            .iter()
+            .filter_map(|entity| selection_kind(&self.world, *entity).map(|kind| (*entity, kind)))
+            .collect();
+        let (active_kind, active_entities) = {
+            let mut selection = self.world.get_resource_or_insert_with(SelectionState::default);
+            selection.set_selection(kinds);
+            (selection.active_kind(), selection.active_entities().to_vec())
+        };
+        
+        let units: Vec<UnitInfo> = selected.iter().filter_map(|entity| self.unit_info(*entity)).collect();
+        let active_unit_type = match active_kind {
+            Some(SelectionKind::Unit(unit_type)) => Some(unit_type),
+            _ => None,
+        };
+        
+        // The cargo slots follow the first transport of the active subgroup
+        let (capacity, passengers) = match active_entities.first().and_then(|entity| self.world.get::<Cargo>(*entity)) {
+            Some(cargo) => (cargo.capacity, cargo.passengers.iter().filter_map(|entity| self.unit_info(*entity)).collect()),
+            None => (0, Vec::new()),
+        };
+        self.ui_manager.set_cargo(capacity, passengers);
+        self.ui_manager.set_selected_units(units, active_unit_type);
+        let abilities = active_unit_type.map_or_else(Vec::new, |unit_type| self.ability_infos(unit_type, &active_entities));
+        self.ui_manager.set_abilities(abilities);
+        
+        // The building panel follows the first building of the active subgroup
+        let selected_building = match active_kind {
+            Some(SelectionKind::Building(_)) => active_entities.first().and_then(|entity| self.building_info(*entity)),
+            _ => None,
+        };
+        self.ui_manager.set_selected_building(selected_building);
+    }
+    
+    /// Command card entries for a unit type's abilities, as cast by the given selected units
+    fn ability_infos(&self, unit_type: UnitType, casters: &[Entity]) -> Vec<AbilityInfo> {
+        let casters: Vec<&Caster> = casters.iter().filter_map(|entity| self.world.get::<Caster>(*entity)).collect();
         
-        schedule.add_system(update_movement_system);
-        schedule.add_system(collision_detection_system);
-        schedule.add_system(unit_behavior_system);
-        schedule.add_system(building_production_system);
-        schedule.add_system(resource_collection_system);
-        schedule.add_system(economy_system);
-        schedule.add_system(tech_research_system);  // Add this system if not already present
-        schedule.add_system(fog_of_war_system);
-        schedule.add_system(combat_system);
-        schedule.add_system(repair_system);  // Add repair system
+        data::game_data()
+            .unit_abilities(unit_type)
+            .into_iter()
+            .map(|ability| AbilityInfo {
+                ability_id: ability.id,
+                name: ability.name.clone(),
+                energy_cost: ability.energy_cost,
+                cooldown: casters
+                    .iter()
+                    .map(|caster| caster.cooldowns.get(&ability.id).copied().unwrap_or(0.0))
+                    .reduce(f32::min)
+                    .unwrap_or(0.0),
+                ready: casters.iter().any(|caster| abilities::cast_blocker(caster, ability).is_none()),
+            })
+            .collect()
+    }
+    
+    /// HUD summary of a unit
+    fn unit_info(&self, entity: Entity) -> Option<UnitInfo> {
+        self.world.get::<Unit>(entity).map(|unit| UnitInfo {
+            unit_type: unit.unit_type,
+            health: unit.health,
+            max_health: unit.max_health,
+            entity_id: entity.index(),
+            rank: self.world.get::<Experience>(entity).map_or(0, |experience| experience.rank),
+            energy: self.world.get::<Caster>(entity).map(|caster| caster.energy),
+        })
+    }
+    
+    /// HUD summary of a building
+    fn building_info(&self, entity: Entity) -> Option<BuildingInfo> {
+        let garrison = self.world.get::<Garrison>(entity);
+        self.world.get::<Building>(entity).map(|building| BuildingInfo {
+            building_type: building.building_type,
+            health: building.health,
+            max_health: building.max_health,
+            entity_id: entity.index(),
+            production_progress: building.production_progress,
+            construction_progress: building.construction_progress,
+            production_queue: building.production_queue.iter().copied().collect(),
+            upgrade_progress: self.world.get::<Upgrading>(entity).map(|upgrading| upgrading.progress),
+            upgrade: self.upgrade_option(entity, building),
+            garrison_capacity: garrison.map_or(0, |garrison| garrison.capacity),
+            garrison: garrison.map_or_else(Vec::new, |garrison| {
+                garrison.occupants.iter().filter_map(|occupant| self.unit_info(*occupant)).collect()
+            }),
+        })
+    }
+    
+    /// The upgrade a finished building offers its owner, if any
+    fn upgrade_option(&self, entity: Entity, building: &Building) -> Option<UpgradeOption> {
+        if building.construction_progress.is_some() {
+            return None;
+        }
+        let target = buildings::BuildingData::get(building.building_type).upgrades_to?;
+        let target_data = buildings::BuildingData::get(target);
         
-        // Run the schedule
-        schedule.run(&mut self.world);
+        let cost: Vec<String> = [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy]
+            .into_iter()
+            .filter_map(|res_type| target_data.costs.get(&res_type).map(|cost| format!("{} {:?}", cost, res_type)))
+            .collect();
+        let owner = self.world.get::<Owner>(entity)?.0;
+        let missing_tech = self
+            .world
+            .get_resource::<TechState>()
+            .and_then(|tech_state| buildings::missing_tech(target, tech_state, owner))
+            .map(|tech_type| tech::TechData::get(tech_type).name.clone());
         
-        // Update global resources
-        let mut game_time = self.world.resource_mut::<GameTime>();
-        game_time.current_tick += 1;
-        game_time.elapsed_time += game_time.delta_time;
+        Some(UpgradeOption {
+            name: target_data.name.clone(),
+            cost: cost.join(", "),
+            missing_tech,
+        })
+    }
+    
+    /// Tech tree nodes for the HUD, laid out in columns by prerequisite depth
+    fn tech_tree_nodes(&self, player_id: u8) -> Vec<TechNode> {
+        let tech_state = match self.world.get_resource::<TechState>() {
+            Some(tech_state) => tech_state,
+            None => return Vec::new(),
+        };
+        
+        let mut rows_per_column: HashMap<usize, usize> = HashMap::new();
+        tech::get_tech_tree()
+            .into_iter()
+            .map(|tech_data| {
+                let column = tech::tech_depth(tech_data.tech_type);
+                let row = rows_per_column.entry(column).or_insert(0);
+                let position = Vec2::new(
+                    column as f32 * (TECH_NODE_SIZE.x + 16.0),
+                    *row as f32 * (TECH_NODE_SIZE.y + 8.0),
+                );
+                *row += 1;
+                
+                TechNode {
+                    tech_type: tech_data.tech_type,
+                    status: tech::tech_status(tech_data.tech_type, tech_state, player_id),
+                    name: tech_data.name.clone(),
+                    position,
+                }
+            })
+            .collect()
+    }
+    
+    /// Update the building placement preview from the input handler's placement mode
+    fn update_placement_ghost(&mut self) {
+        let building_type = match self.input_handler.get_placement() {
+            Some(building_type) => building_type,
+            None => {
+                self.world.remove_resource::<PlacementGhost>();
+                self.ui_manager.set_placement_cost(None);
+                return;
+            }
+        };
+        
+        let mut position = self.input_handler.get_world_mouse_position();
+        let local_player_id = self.world
+            .get_resource::<PlayerInfo>()
+            .map(|info| info.local_player_id)
+            .unwrap_or(0);
+        
+        // Walls show every segment of the row being dragged out, or the one under the cursor
+        if buildings::is_wall_piece(building_type) {
+            position = buildings::snap_wall_piece(position);
+        }
+        let segment_positions = if building_type == BuildingType::Wall {
+            let start = self.input_handler.get_wall_start().unwrap_or(position);
+            buildings::wall_segments(start, position)
+        } else {
+            Vec::new()
+        };
+        
+        let existing = existing_building_footprints(&mut self.world);
+        let (valid, segments) = match self.world.get_resource::<GameMap>() {
+            Some(map) => {
+                let is_valid = |position| {
+                    buildings::is_valid_placement(building_type, position, local_player_id, map, &existing)
+                };
+                let segments: Vec<(Vec2, bool)> = segment_positions
+                    .into_iter()
+                    .map(|position| (position, is_valid(position)))
+                    .collect();
+                let valid = if segments.is_empty() {
+                    is_valid(position)
+                } else {
+                    segments.iter().any(|&(_, valid)| valid)
+                };
+                (valid, segments)
+            }
+            None => (false, Vec::new()),
+        };
+        
+        // Only the segments that can be built are paid for
+        let count = if segments.is_empty() { 1 } else { segments.iter().filter(|&&(_, valid)| valid).count() };
+        let costs = &buildings::BuildingData::get(building_type).costs;
+        let cost: Vec<String> = [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy]
+            .into_iter()
+            .filter_map(|res_type| costs.get(&res_type).map(|cost| format!("{} {:?}", cost * count as f32, res_type)))
+            .collect();
+        self.ui_manager.set_placement_cost(Some(cost.join(", ")));
+        
+        self.world.insert_resource(PlacementGhost {
+            building_type,
+            position,
+            valid,
+            segments,
+        });
+    }
+    
+    /// Pause or resume a singleplayer game. In multiplayer games this asks the host to pause for
+    /// everyone, or to start the countdown to resume
+    fn toggle_pause(&mut self) {
+        if let Some(network) = &mut self.network {
+            if !network.in_game() {
+                return;
+            }
+            
+            let local_player_id = network.local_player_id();
+            let requested = match network.pause_state() {
+                PauseState::Running | PauseState::Resuming { .. } if network.pauses_left(local_player_id) == 0 => {
+                    self.ui_manager.show_warning(tr("warning.no_pauses_left"));
+                    return;
+                }
+                PauseState::Running | PauseState::Resuming { .. } => network.request_pause(),
+                PauseState::Paused { .. } if !network.can_resume() => {
+                    let seconds = OTHERS_RESUME_AFTER.as_secs().to_string();
+                    self.ui_manager.show_warning(tr_args("warning.resume_wait", &[("seconds", &seconds)]));
+                    return;
+                }
+                PauseState::Paused { .. } => network.request_resume(),
+            };
+            if let Err(e) = requested {
+                error!(target: "net", "Failed to request a pause: {}", e);
+                self.ui_manager.show_warning(tr_args("warning.network_error", &[("error", &e.to_string())]));
+            }
+            return;
+        }
+
+
+        let phase = self.game_state.borrow().phase;
+        match phase {
+            crate::game::GamePhase::Playing => {
+                self.game_state.borrow_mut().pause();
+                self.ui_manager.show_alert(tr("alert.paused"));
+            }
+            crate::game::GamePhase::Paused => {
+                self.game_state.borrow_mut().resume();
+                self.ui_manager.clear_alert();
+            }
+            _ => {}
+        }
+    }
+    
+    /// Follow the multiplayer game's pause state: hold the ticks while a player has it paused or the
+    /// resume countdown runs, and show who paused it or how long until it carries on
+    fn sync_network_pause(&mut self) {
+        let state = match &self.network {
+            Some(network) if network.in_game() => network.pause_state(),
+            _ => PauseState::Running,
+        };
+        
+        let message = match state {
+            PauseState::Running => {
+                if self.network_paused {
+                    self.network_paused = false;
+                    self.game_state.borrow_mut().resume();
+                    self.ui_manager.clear_alert();
+                }
+                return;
+            }
+            PauseState::Paused { player_id, pauses_left } => {
+                let name = self.world
+                    .get_resource::<PlayerInfo>()
+                    .and_then(|info| info.player_names.get(&player_id).cloned())
+                    .unwrap_or_else(|| format!("Player {}", player_id + 1));
+                tr_args("alert.paused_by", &[("player", &name), ("pauses", &pauses_left.to_string())])
+            }
+            PauseState::Resuming { remaining_ms, .. } => {
+                let seconds = ((remaining_ms + 999) / 1000).to_string();
+                tr_args("alert.resuming", &[("seconds", &seconds)])
+            }
+        };
+        
+        // A game stopped for another reason, such as a failed resync, stays stopped
+        if !self.network_paused && self.game_state.borrow().phase != crate::game::GamePhase::Playing {
+            return;
+        }
+        self.network_paused = true;
+        self.game_state.borrow_mut().pause();
+        self.ui_manager.show_alert(message);
+    }
+    
+    /// Step the singleplayer game speed up or down and remember it; apply_settings hands it to the time system
+    fn change_game_speed(&mut self, steps: i32) {
+        if self.network.is_some() {
+            self.ui_manager.show_warning(tr("warning.no_multiplayer_speed"));
+            return;
+        }
+        
+        let mut game_state = self.game_state.borrow_mut();
+        let settings = &mut game_state.settings;
+        settings.game_speed = settings::step_game_speed(settings.game_speed, steps);
+        if let Err(e) = settings::save_settings(settings) {
+            error!("Failed to save settings: {}", e);
+        }
+        let message = tr_args("warning.game_speed", &[("speed", &settings.game_speed.to_string())]);
+        self.ui_manager.show_warning(message);
+    }
+    
+    /// Open or close the developer console, if this is a singleplayer game started in debug mode
+    fn toggle_console(&mut self) {
+        if self.ui_manager.is_console_open() {
+            self.ui_manager.close_console();
+        } else if self.network.is_some() {
+            self.ui_manager.show_warning(tr("warning.no_multiplayer_console"));
+        } else if !self.debug {
+            self.ui_manager.show_warning(tr("warning.console_needs_debug"));
+        } else {
+            self.ui_manager.open_console();
+        }
+    }
+    
+    /// Run a line entered into the developer console and show its output there
+    fn run_console_command(&mut self, line: &str) {
+        // The console can't be opened in multiplayer, but don't trust what was queued before joining
+        let output = if self.network.is_some() {
+            "The console is disabled in multiplayer".to_string()
+        } else {
+            console::execute(self, line)
+        };
+        for output_line in output.lines() {
+            self.ui_manager.console_print(output_line.to_string());
+        }
+    }
+    
+    /// Select the local player's next idle worker and center the camera on it
+    fn select_idle_worker(&mut self) {
+        let worker = match next_idle_worker(&mut self.world, self.local_player_id()) {
+            Some(worker) => worker,
+            None => return,
+        };
+        
+        if let Some(transform) = self.world.get::<Transform>(worker) {
+            self.input_handler.set_camera_position(transform.position);
+        }
+        self.input_handler.handle_command(input::Command::SelectIdleWorker);
+    }
+    
+    /// Track the drag-select rectangle and the entity under the mouse for the renderer
+    fn update_selection_overlay(&mut self) {
+        let drag_rect = self.input_handler
+            .get_selection_rectangle()
+            .filter(|_| self.input_handler.is_selection_active())
+            .map(|(start, end)| (self.input_handler.screen_to_world(start), self.input_handler.screen_to_world(end)));
+        
+        // No hover highlight while dragging or placing a building
+        let hovered = if drag_rect.is_none() && self.input_handler.get_placement().is_none() {
+            entity_at(&self.world, self.local_player_id(), self.input_handler.get_world_mouse_position())
+        } else {
+            None
+        };
+        
+        self.world.insert_resource(SelectionOverlay { drag_rect, hovered });
+    }
+    
+    /// Show what a right-click would order the selection to do under the mouse
+    fn update_cursor(&mut self) {
+        let icon = if self.input_handler.get_target_mode().is_some() || self.input_handler.get_placement().is_some() {
+            self.input_handler.cursor_icon()
+        } else {
+            let position = self.input_handler.get_world_mouse_position();
+            match order_context(&mut self.world, self.local_player_id(), position) {
+                OrderContext::Attack(_) => CursorIcon::Crosshair,
+                OrderContext::Gather(_) => CursorIcon::Grab,
+                OrderContext::Repair(_) => CursorIcon::Hand,
+                OrderContext::Follow(_) => CursorIcon::Alias,
+                OrderContext::Board(_) => CursorIcon::Move,
+                OrderContext::Garrison(_) => CursorIcon::Move,
+                OrderContext::Move => CursorIcon::Default,
+            }
+        };
+        self.window.set_cursor_icon(icon);
+    }
+    
+    /// Have a custom bot play an AI slot in games started from now on
+    pub fn register_bot(&mut self, player_id: u8, factory: BotFactory) {
+        self.ai_players.register_bot(player_id, factory);
+    }
+    
+    /// Have a bot script from the scripts directory play an AI slot in games started from now on.
+    /// A script that fails to load leaves the slot to the built-in controller.
+    pub fn register_script_bot(&mut self, player_id: u8, file_name: &str) {
+        let file_name = file_name.to_string();
+        self.register_bot(player_id, Box::new(move |slot: &AiSlotConfig, seed: u64| -> Box<dyn AiPlayer> {
+            match ScriptBot::load(&file_name, slot.player_id) {
+                Ok(bot) => Box::new(bot),
+                Err(e) => {
+                    error!(target: "ai", "Failed to load bot script: {}", e);
+                    Box::new(AiController::new(slot.player_id, slot.difficulty, slot.personality, seed))
+                }
+            }
+        }));
+    }
+    
+    /// Create AI opponents for the slots chosen in the game setup
+    pub fn start_ai_players(&mut self) {
+        let slots = self.game_state.borrow().settings.ai_slots.clone();
+        self.ai_players.setup(&slots, self.game_state.borrow().seed);
+        
+        // Keep player info in sync so the UI knows which players are computer controlled
+        if !self.world.contains_resource::<PlayerInfo>() {
+            self.world.insert_resource(PlayerInfo::default());
+        }
+        let mut player_info = self.world.resource_mut::<PlayerInfo>();
+        player_info.ai_players = slots.iter().map(|slot| slot.player_id).collect();
+        player_info.resource_bonuses = slots
+            .iter()
+            .filter(|slot| slot.resource_bonus > 0.0)
+            .map(|slot| (slot.player_id, slot.resource_bonus))
+            .collect();
+        
+        // Lobby games take their teams from the lobby slots instead
+        if !self.game_state.borrow().is_multiplayer {
+            let local_team = self.game_state.borrow().settings.player_team.map(|team| (0, team));
+            player_info.teams = slots
+                .iter()
+                .filter_map(|slot| slot.team.map(|team| (slot.player_id, team)))
+                .chain(local_team)
+                .collect();
+        }
+    }
+    
+    /// Start judging the game by the victory condition chosen in the game setup, with fresh statistics
+    pub fn start_victory_condition(&mut self) {
+        start_victory_condition(&mut self.world, &self.game_state.borrow());
+    }
+    
+    /// Start the day and night cycle if the game setup or the map asks for one
+    pub fn start_day_night_cycle(&mut self) {
+        let game_state = self.game_state.borrow();
+        let settings = &game_state.settings;
+        let enabled = settings.day_night_cycle || map_files::map_has_day_night(&settings.map);
+        environment::start_day_night_cycle(&mut self.world, enabled);
+    }
+    
+    /// Statistics of the current or just finished game, for the post-game summary
+    pub fn game_stats(&self) -> Option<&GameStats> {
+        self.world.get_resource::<GameStats>()
+    }
+    
+    /// Stop the game and tell the player how it ended
+    fn end_game(&mut self, outcome: VictoryOutcome) {
+        self.game_state.borrow_mut().end_game(outcome);
+        
+        let won = match outcome {
+            VictoryOutcome::Winner { team, .. } => {
+                let local_player_id = self.local_player_id();
+                let local_team = self.world
+                    .get_resource::<PlayerInfo>()
+                    .map_or(local_player_id, |info| info.team_of(local_player_id));
+                Some(team == local_team)
+            }
+            VictoryOutcome::Draw => None,
+        };
+        let message = match won {
+            Some(true) => tr("result.victory"),
+            Some(false) => tr("result.defeat"),
+            None => tr("result.draw"),
+        };
+        info!("Game over: {}", message);
+        self.ui_manager.show_warning(message);
+        
+        // Winning a campaign mission unlocks the next one
+        if won == Some(true) {
+            if let Some(campaign_mission) = self.world.get_resource::<CampaignMission>() {
+                campaign::record_victory(&campaign_mission.mission);
+            }
+        }
+        
+        print_game_stats(&self.world);
+    }
+    
+    /// Save the running game into a save slot
+    pub fn save_game(&mut self, slot: u8) -> Result<()> {
+        let metadata = savegame::save_game(&mut self.world, &self.game_state.borrow(), slot)?;
+        info!("Saved game to slot {} at tick {}", metadata.slot, metadata.tick);
+        Ok(())
+    }
+    
+    /// Every autosave interval of game time, save the game in memory for the crash handler to write
+    /// out, and to the autosave slot when autosaving is turned on
+    fn autosave_if_due(&mut self) {
+        let tick = match self.world.get_resource::<GameTime>() {
+            Some(game_time) => game_time.current_tick,
+            None => return,
+        };
+        
+        // A new or loaded game starts counting again
+        if tick < self.last_autosave_tick {
+            self.last_autosave_tick = tick;
+        }
+        let interval = (self.game_state.borrow().settings.auto_save_interval * TICK_RATE as f32).max(1.0) as u64;
+        if tick - self.last_autosave_tick < interval {
+            return;
+        }
+        self.last_autosave_tick = tick;
+        
+        let data = match savegame::encode_save(&mut self.world, &self.game_state.borrow(), savegame::AUTOSAVE_SLOT) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to autosave: {}", e);
+                return;
+            }
+        };
+        if self.game_state.borrow().settings.auto_save_enabled {
+            if let Err(e) = savegame::write_save_file(savegame::AUTOSAVE_SLOT, &data) {
+                error!("Failed to autosave: {}", e);
+            }
+        }
+        crash::remember_autosave(data);
+    }
+    
+    /// Replace the running game with the contents of a save slot
+    pub fn load_game(&mut self, slot: u8) -> Result<()> {
+        let metadata = savegame::load_game(&mut self.world, &mut self.game_state.borrow_mut(), slot)?;
+        
+        // AI controllers are not saved; recreate them for the restored game
+        self.start_ai_players();
+        
+        // Neither is the victory condition's progress, such as how long points have been held, nor the statistics
+        self.start_victory_condition();
+        self.start_day_night_cycle();
+        
+        // Explored areas belong to the previous game
+        self.renderer.reset_fog_of_war();
+        self.ui_manager.reset_minimap();
+        
+        // Keep the ECS clock in step with the restored game
+        if let Some(mut game_time) = self.world.get_resource_mut::<GameTime>() {
+            game_time.current_tick = metadata.tick;
+        }
+        self.time_system.reset();
+        
+        info!("Loaded game from slot {} (tick {})", metadata.slot, metadata.tick);
+        Ok(())
+    }
+    
+    /// Start a game against the AI slots from the game setup, on the map chosen there
+    pub fn start_skirmish(&mut self) -> Result<()> {
+        self.start_local_game(None)
+    }
+    
+    /// Set up a singleplayer game from the game settings. Players start with a headquarters and
+    /// workers each, unless a scenario's starting world is placed instead
+    fn start_local_game(&mut self, scenario_world: Option<&ScenarioWorld>) -> Result<()> {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let player_ids: Vec<u8> = std::iter::once(0)
+            .chain(self.game_state.borrow().settings.ai_slots.iter().map(|slot| slot.player_id))
+            .collect();
+        
+        // Fails before touching the running world if a custom map is missing or too small
+        let game_map = {
+            let game_state = self.game_state.borrow();
+            let settings = &game_state.settings;
+            map_files::create_map(&settings.map, settings.map_preset, settings.map_symmetry, seed, player_ids.len() as u8)?
+        };
+        let starting_positions = game_map.starting_positions.clone();
+        
+        savegame::clear_game_entities(&mut self.world);
+        self.world.insert_resource(game_map);
+        map::spawn_resource_nodes(&mut self.world);
+        neutral::spawn_neutral_sites(&mut self.world);
+        
+        {
+            let player_name = self.game_state.borrow().settings.player_name.clone();
+            let mut player_info = self.world.get_resource_or_insert_with(PlayerInfo::default);
+            player_info.local_player_id = 0;
+            player_info.player_names = player_ids
+                .iter()
+                .map(|&player_id| {
+                    let name = if player_id == 0 { player_name.clone() } else { format!("Computer {}", player_id) };
+                    (player_id, name)
+                })
+                .collect();
+            player_info.player_colors = player_ids
+                .iter()
+                .map(|&player_id| (player_id, PLAYER_COLORS[player_id as usize % PLAYER_COLORS.len()]))
+                .collect();
+        }
+        
+        match scenario_world {
+            Some(scenario_world) => scenario::spawn_scenario_world(&mut self.world, scenario_world),
+            None => {
+                let bases: Vec<(u8, Vec2)> = player_ids.iter().copied().zip(starting_positions.iter().copied()).collect();
+                spawn_starting_bases(&mut self.world, &bases);
+            }
+        }
+        
+        self.game_state.borrow_mut().start_game(false, player_ids.len() as u8, seed);
+        self.start_ai_players();
+        self.start_victory_condition();
+        self.start_day_night_cycle();
+        
+        // Explored areas belong to the previous game
+        self.renderer.reset_fog_of_war();
+        self.ui_manager.reset_minimap();
+        self.ui_manager.set_surrender_available(false);
+        
+        if let Some(mut game_time) = self.world.get_resource_mut::<GameTime>() {
+            game_time.current_tick = 0;
+            game_time.seed = seed;
+        }
+        self.time_system.reset();
+        
+        let camera_position = scenario_world
+            .and_then(|scenario_world| scenario::player_world_position(scenario_world, 0))
+            .or_else(|| starting_positions.first().copied());
+        if let Some(position) = camera_position {
+            self.input_handler.set_camera_position(position);
+        }
+        
+        info!("Started game on {} with {} players (seed {})", self.game_state.borrow().settings.map.name(), player_ids.len(), seed);
+        Ok(())
+    }
+    
+    /// Start the game a scenario file sets up, with its triggers running on top
+    pub fn start_scenario(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let scenario = ScenarioData::load(path)?;
+        
+        {
+            let mut game_state = self.game_state.borrow_mut();
+            let settings = &mut game_state.settings;
+            settings.map = scenario.map.clone();
+            settings.map_preset = scenario.map_preset;
+            settings.map_symmetry = scenario.map_symmetry;
+            settings.ai_slots = scenario.ai_slots.clone();
+            settings.player_team = None;
+        }
+        self.start_local_game(scenario.world.as_ref())?;
+        
+        // Only the triggers end a scenario, whatever victory condition the game setup last picked
+        self.world.insert_resource(VictoryState::new(VictoryConditionType::Scripted));
+        scenario::start_scenario(&mut self.world, &scenario);
+        Ok(())
+    }
+    
+    /// Start a campaign mission the player has reached, with the techs earlier victories unlocked
+    pub fn start_campaign_mission(&mut self, index: usize) -> Result<()> {
+        let campaign = Campaign::load(campaign::CAMPAIGN_FILE)?;
+        let progress = CampaignProgress::load_or_default();
+        let (mission, path) = match (campaign.missions.get(index), campaign.scenario_path(index)) {
+            (Some(mission), Some(path)) => (mission.clone(), path),
+            _ => return Err(anyhow::anyhow!("The campaign has no mission {}", index + 1)),
+        };
+        if !progress.is_available(&campaign, index) {
+            return Err(anyhow::anyhow!("Mission {} is still locked", mission.title));
+        }
+        
+        // In place before the starting world is spawned, so its units already have the unlocked upgrades
+        self.world.insert_resource(progress.starting_tech_state(0));
+        self.start_scenario(path)?;
+        self.world.insert_resource(CampaignMission { index, mission });
+        Ok(())
+    }
+    
+    /// Build the world every peer agreed on in the lobby and start playing it
+    fn start_network_game(&mut self, start: GameStartInfo) {
+        let starting_positions = build_network_game(&mut self.world, &mut self.game_state.borrow_mut(), &start);
+        self.start_ai_players();
+        
+        // Explored areas belong to the previous game
+        self.renderer.reset_fog_of_war();
+        self.ui_manager.reset_minimap();
+        self.ui_manager.set_surrender_available(true);
+        self.network_paused = false;
+        self.time_system.reset();
+        
+        let local_player_id = self.local_player_id();
+        if let Some(&position) = start.slots
+            .iter()
+            .position(|slot| slot.player_id == local_player_id)
+            .and_then(|index| starting_positions.get(index))
+        {
+            self.input_handler.set_camera_position(position);
+        }
+        
+        info!(target: "net", "Started multiplayer game with {} players (seed {})", start.slots.len(), start.seed);
+    }
+    
+    fn run_game_systems(&mut self) {
+        // Keep every RNG roll tied to this game's seed
+        self.world.resource_mut::<GameTime>().seed = self.game_state.borrow().seed;
+        
+        simulation::step(&mut self.world, &mut self.schedule);
     }
     
     fn render(&mut self) -> Result<()> {
+        // Keep the render camera in sync with input-driven camera movement
+        self.renderer.update_camera(
+            self.input_handler.get_camera_position(),
+            self.input_handler.get_camera_zoom(),
+        );
+        
         // Render game world
         self.renderer.render(&self.world)?;
         
+        // Redraw the minimap with this frame's units and camera view
+        self.ui_manager.update_minimap(&mut self.world, self.input_handler.visible_world_bounds());
+        
         // Render UI on top
         self.renderer.render_ui(&self.ui_manager)?;
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// World with every resource the simulation and the engine read, before any game is set up
+pub fn init_game_world() -> World {
+    let mut world = ecs::init_world();
+    
+    // Add combat-specific resources
+    world.insert_resource(DamageTable::default());
+    world.insert_resource(ControlGroups::default());
+    world.insert_resource(PlayerAlerts::default());
+    world.insert_resource(AudioEvents::default());
+    world.insert_resource(CombatTextEvents::default());
+    world.insert_resource(AttackAlerts::default());
+    world.insert_resource(SelectionState::default());
+    world.insert_resource(SpatialGrid::default());
+    world.insert_resource(PlayerInfo::default());
+    world.insert_resource(GameStats::default());
+    world.insert_resource(ThreatMaps::default());
+    world.insert_resource(Scripts { scenario: None, abilities: scripting::load_ability_script() });
+    ecs::events::init_events(&mut world);
+    world
+}
+
+/// Start judging the game by the victory condition chosen in the game setup, with fresh statistics
+pub fn start_victory_condition(world: &mut World, game_state: &GameState) {
+    let condition = game_state.settings.victory_condition;
+    world.insert_resource(VictoryState::new(condition));
+    world.insert_resource(GameStats::default());
+    info!("Victory condition: {}", condition.name());
+}
+
+/// Build the world every peer agreed on in the lobby; returns the starting positions, in slot order
+pub fn build_network_game(world: &mut World, game_state: &mut GameState, start: &GameStartInfo) -> Vec<Vec2> {
+    savegame::clear_game_entities(world);
+    
+    // Same parameters and seed everywhere give the same map everywhere
+    let game_map = map::generate_map(&start.map);
+    let starting_positions = game_map.starting_positions.clone();
+    world.insert_resource(game_map);
+    map::spawn_resource_nodes(world);
+    neutral::spawn_neutral_sites(world);
+    
+    {
+        let mut player_info = world.get_resource_or_insert_with(PlayerInfo::default);
+        player_info.player_names = start.slots.iter().map(|slot| (slot.player_id, slot.name.clone())).collect();
+        player_info.player_colors = start.slots
+            .iter()
+            .map(|slot| (slot.player_id, PLAYER_COLORS[slot.color as usize % PLAYER_COLORS.len()]))
+            .collect();
+        player_info.teams = start.slots.iter().map(|slot| (slot.player_id, slot.team)).collect();
+    }
+    
+    // Each player gets a headquarters and a few workers at their starting position
+    let bases: Vec<(u8, Vec2)> = start.slots
+        .iter()
+        .map(|slot| slot.player_id)
+        .zip(starting_positions.iter().copied())
+        .collect();
+    spawn_starting_bases(world, &bases);
+    
+    // Lobby games are between people only
+    game_state.settings.ai_slots.clear();
+    game_state.settings.shared_vision = start.shared_vision;
+    game_state.settings.day_night_cycle = start.day_night_cycle;
+    game_state.settings.victory_condition = start.victory;
+    game_state.start_game(true, start.slots.len() as u8, start.seed);
+    start_victory_condition(world, game_state);
+    environment::start_day_night_cycle(world, start.day_night_cycle);
+    
+    // Every peer counts ticks from the host's start tick
+    if let Some(mut game_time) = world.get_resource_mut::<GameTime>() {
+        game_time.current_tick = start.start_tick;
+        game_time.seed = start.seed;
+    }
+    
+    starting_positions
+}
+
+/// Give each player a headquarters and a few workers at their starting position
+pub fn spawn_starting_bases(world: &mut World, bases: &[(u8, Vec2)]) {
+    let hq_data = data::game_data().building(BuildingType::Headquarters).clone();
+    let mut queue = CommandQueue::default();
+    {
+        let tech_state = world.resource::<TechState>();
+        let mut commands = Commands::new(&mut queue, world);
+        for &(player_id, position) in bases {
+            commands.spawn((
+                Building {
+                    building_type: BuildingType::Headquarters,
+                    health: hq_data.health,
+                    max_health: hq_data.health,
+                    production_queue: std::collections::VecDeque::new(),
+                    production_progress: None,
+                    construction_progress: None,
+                    rally_point: None,
+                },
+                Transform {
+                    position,
+                    rotation: 0.0,
+                    scale: hq_data.size,
+                },
+                Owner(player_id),
+                Collider {
+                    radius: hq_data.size.max_element() * 0.5,
+                    collision_layer: 2, // Building layer
+                    collision_mask: 1 | 2,
+                },
+                Selectable,
+            ));
+            
+            for index in 0..STARTING_WORKERS {
+                let offset = Vec2::new(index as f32 - (STARTING_WORKERS as f32 - 1.0) / 2.0, -1.0) * 2.0;
+                units::spawn_unit(
+                    &mut commands,
+                    units::UnitSpawnParams {
+                        unit_type: UnitType::Worker,
+                        owner: player_id,
+                        position: position + hq_data.size * Vec2::new(0.0, -0.5) + offset,
+                    },
+                    tech_state,
+                );
+            }
+        }
+    }
+    queue.apply(world);
+}
+
+/// Print each player's statistics at the end of a game
+pub fn print_game_stats(world: &World) {
+    if let (Some(stats), Some(player_info)) = (world.get_resource::<GameStats>(), world.get_resource::<PlayerInfo>()) {
+        for (player_id, player_stats) in stats.players() {
+            let name = player_info.player_names.get(&player_id).cloned().unwrap_or_else(|| format!("Player {}", player_id + 1));
+            info!(
+                "  {}: score {}, units {}/{}/{}/{} (produced/lost/killed/promoted), buildings {}/{}/{} (built/lost/razed)",
+                name,
+                player_stats.score(),
+                player_stats.units_produced,
+                player_stats.units_lost,
+                player_stats.units_killed,
+                player_stats.units_promoted,
+                player_stats.buildings_constructed,
+                player_stats.buildings_lost,
+                player_stats.buildings_razed,
+            );
+        }
+    }
+}
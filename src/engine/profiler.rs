@@ -0,0 +1,143 @@
+use bevy_ecs::archetype::ArchetypeComponentId;
+use bevy_ecs::component::{ComponentId, Tick};
+use bevy_ecs::prelude::*;
+use bevy_ecs::query::Access;
+use bevy_ecs::schedule::SystemSet;
+use bevy_ecs::system::{IntoSystem, System};
+use bevy_ecs::world::unsafe_world_cell::UnsafeWorldCell;
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Whether timed systems record how long they run; off unless the performance overlay is showing
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Time each timed system spent in its latest run, by system name
+static SYSTEM_TIMES: Mutex<BTreeMap<Cow<'static, str>, Duration>> = Mutex::new(BTreeMap::new());
+
+/// Turn system timing on or off
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        SYSTEM_TIMES.lock().unwrap().clear();
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Time each timed system spent in its latest run, slowest first
+pub fn system_times() -> Vec<(String, Duration)> {
+    let mut times: Vec<(String, Duration)> = SYSTEM_TIMES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, duration)| (short_name(name).to_string(), *duration))
+        .collect();
+    times.sort_by(|a, b| b.1.cmp(&a.1));
+    times
+}
+
+/// Wrap a system so every run is timed while profiling is enabled
+pub fn timed<M, S: IntoSystem<(), (), M>>(system: S) -> TimedSystem<S::System> {
+    TimedSystem { system: IntoSystem::into_system(system) }
+}
+
+/// System that passes everything through to the system it wraps, recording how long each run takes
+pub struct TimedSystem<S> {
+    system: S,
+}
+
+impl<S: System<In = (), Out = ()>> TimedSystem<S> {
+    fn record(&self, start: Instant) {
+        let elapsed = start.elapsed();
+        SYSTEM_TIMES.lock().unwrap().insert(self.system.name(), elapsed);
+    }
+}
+
+impl<S: System<In = (), Out = ()>> System for TimedSystem<S> {
+    type In = ();
+    type Out = ();
+    
+    fn name(&self) -> Cow<'static, str> {
+        self.system.name()
+    }
+    
+    fn type_id(&self) -> TypeId {
+        self.system.type_id()
+    }
+    
+    fn component_access(&self) -> &Access<ComponentId> {
+        self.system.component_access()
+    }
+    
+    fn archetype_component_access(&self) -> &Access<ArchetypeComponentId> {
+        self.system.archetype_component_access()
+    }
+    
+    fn is_send(&self) -> bool {
+        self.system.is_send()
+    }
+    
+    fn is_exclusive(&self) -> bool {
+        self.system.is_exclusive()
+    }
+    
+    unsafe fn run_unsafe(&mut self, input: (), world: UnsafeWorldCell) {
+        if !is_enabled() {
+            return self.system.run_unsafe(input, world);
+        }
+        
+        let start = Instant::now();
+        self.system.run_unsafe(input, world);
+        self.record(start);
+    }
+    
+    fn run(&mut self, input: (), world: &mut World) {
+        if !is_enabled() {
+            return self.system.run(input, world);
+        }
+        
+        let start = Instant::now();
+        self.system.run(input, world);
+        self.record(start);
+    }
+    
+    fn apply_deferred(&mut self, world: &mut World) {
+        self.system.apply_deferred(world);
+    }
+    
+    fn initialize(&mut self, world: &mut World) {
+        self.system.initialize(world);
+    }
+    
+    fn update_archetype_component_access(&mut self, world: UnsafeWorldCell) {
+        self.system.update_archetype_component_access(world);
+    }
+    
+    fn check_change_tick(&mut self, change_tick: Tick) {
+        self.system.check_change_tick(change_tick);
+    }
+    
+    // Keeps ordering against the bare function, e.g. `.after(combat_system)`, working
+    fn default_system_sets(&self) -> Vec<Box<dyn SystemSet>> {
+        self.system.default_system_sets()
+    }
+    
+    fn get_last_run(&self) -> Tick {
+        self.system.get_last_run()
+    }
+    
+    fn set_last_run(&mut self, last_run: Tick) {
+        self.system.set_last_run(last_run);
+    }
+}
+
+/// System name without its module path, e.g. "combat_system"
+fn short_name(name: &str) -> &str {
+    name.rsplit("::").next().unwrap_or(name)
+}
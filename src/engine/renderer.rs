@@ -6,21 +6,68 @@ use wgpu::{
     TextureFormat, PresentMode, Buffer, BindGroup,
 };
 use winit::window::Window;
+use bevy_ecs::query::With;
 use bevy_ecs::world::World;
 use glam::{Vec2, Vec4, Mat4};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use wgpu::util::DeviceExt;
+use serde::{Serialize, Deserialize};
 
-use crate::ecs::components::{Transform, Unit, Building, Owner, Resource, MinimapMarker, UnitType, BuildingType, ResourceType, Selected};
+use crate::ecs::components::{
+    Transform, Unit, Building, Owner, Resource, MinimapMarker, UnitType, BuildingType, ResourceType, Selected, HealTarget,
+    CarriedResources, Experience, Collider, Caster, ActiveBuffs, ResearchStatus, Animation, Corpse,
+};
+use crate::ecs::resources::{
+    CombatText, GameMap, GameSettings, GameTime, PlacementGhost, NEUTRAL_PLAYER, PlayerInfo, SelectionOverlay, TerrainTile,
+};
+use crate::ecs::systems::CARRY_CAPACITY;
+use crate::engine::assets::TextureAsset;
+use crate::engine::combat_text::{FloatingText, COMBAT_TEXT_TEXTURE};
+use crate::engine::sprites::{SpriteInstance, SpriteLayer, SpriteRenderer, RING_TEXTURE, SELECTION_OUTLINE_WIDTH};
+use crate::engine::terrain::TerrainRenderer;
+use crate::game::abilities;
+use crate::game::buildings::{self, BuildingData};
+use crate::game::data;
+use crate::game::environment::DayNightCycle;
+use crate::game::map::is_position_visible;
+use crate::game::pathfinding::PATH_GRID_SIZE;
 use crate::ui::UiManager;
 
-// Vertex format for entities (sprites)
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-    color: [f32; 4],
+/// Outline color of selected units and buildings
+const SELECTION_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+
+/// Ring under the hovered entity, by its relation to the local player
+const HOVER_OWN_COLOR: [f32; 4] = [0.8, 1.0, 0.8, 0.6];
+const HOVER_ALLY_COLOR: [f32; 4] = [1.0, 0.9, 0.3, 0.6];
+const HOVER_ENEMY_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 0.7];
+
+/// Fill and edge of the drag-select rectangle
+const DRAG_FILL_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 0.12];
+const DRAG_EDGE_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 0.9];
+
+/// Height of the health, energy and progress bars drawn over entities
+const BAR_HEIGHT: f32 = 2.0;
+
+/// Gap between stacked status bars
+const BAR_SPACING: f32 = 1.0;
+
+/// Edge length of the ability and buff pips drawn under a unit's bars
+const PIP_SIZE: f32 = 2.0;
+
+/// Zoomed out further than this, units are drawn as flat markers in their owner's color, and corpses,
+/// carried resources, rank chevrons and ability pips are left out
+const MARKER_ZOOM: f32 = 0.75;
+
+/// Edge length of a unit's marker at far zoom, in world units
+const MARKER_SIZE: f32 = 8.0;
+
+/// Entities the last frame drew and left out, for the performance overlay
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CullStats {
+    pub drawn: u32,
+    pub off_screen: u32, // Outside the camera's view
+    pub fogged: u32,     // Hidden from the local player by fog of war
+    pub markers: u32,    // Of those drawn, how many as markers at far zoom
 }
 
 // Uniforms for camera and transforms
@@ -30,25 +77,127 @@ struct Uniforms {
     view_projection: [[f32; 4]; 4],
 }
 
+/// Resolutions offered in the settings menu
+pub const RESOLUTIONS: [(u32, u32); 6] = [
+    (1024, 768),
+    (1280, 720),
+    (1366, 768),
+    (1600, 900),
+    (1920, 1080),
+    (2560, 1440),
+];
+
+/// How the game window occupies the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    /// Fullscreen window at the desktop resolution
+    Borderless,
+    /// Fullscreen with the display switched to the chosen resolution
+    Exclusive,
+}
+
+impl WindowMode {
+    /// Every mode, in the order the settings menu lists them
+    pub const ALL: [WindowMode; 3] = [WindowMode::Windowed, WindowMode::Borderless, WindowMode::Exclusive];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+            WindowMode::Exclusive => "Fullscreen",
+        }
+    }
+}
+
+/// When health bars are drawn over units and buildings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthBarMode {
+    Always,
+    /// Only entities that have taken damage, plus the selection
+    DamagedOnly,
+    Never,
+}
+
+impl Default for HealthBarMode {
+    fn default() -> Self {
+        HealthBarMode::DamagedOnly
+    }
+}
+
+impl HealthBarMode {
+    /// Mode the health bar hotkey switches to next
+    pub fn next(self) -> Self {
+        match self {
+            HealthBarMode::Always => HealthBarMode::DamagedOnly,
+            HealthBarMode::DamagedOnly => HealthBarMode::Never,
+            HealthBarMode::Never => HealthBarMode::Always,
+        }
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            HealthBarMode::Always => "Always",
+            HealthBarMode::DamagedOnly => "Damaged Only",
+            HealthBarMode::Never => "Never",
+        }
+    }
+    
+    fn shows(self, health: f32, max_health: f32, selected: bool) -> bool {
+        match self {
+            HealthBarMode::Always => true,
+            HealthBarMode::DamagedOnly => health < max_health || selected,
+            HealthBarMode::Never => false,
+        }
+    }
+}
+
+/// Display options chosen in the settings menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoSettings {
+    pub window_mode: WindowMode,
+    pub resolution: (u32, u32), // Window size when windowed, display mode when exclusive
+    pub vsync: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowMode::Windowed,
+            resolution: (1024, 768),
+            vsync: true,
+        }
+    }
+}
+
 pub struct Renderer {
     surface: Surface,
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
-    sprite_pipeline: RenderPipeline,
     camera_uniform_buffer: Buffer,
     camera_bind_group: BindGroup,
     view_projection: Mat4,
     camera_position: Vec2,
     camera_zoom: f32,
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
+    terrain_renderer: TerrainRenderer,
+    sprite_renderer: SpriteRenderer,
     
-    // Placeholder colored rectangles for different entity types
+    // Flat colors for entities whose texture isn't loaded
     unit_colors: HashMap<UnitType, [f32; 4]>,
     building_colors: HashMap<BuildingType, [f32; 4]>,
     resource_colors: HashMap<ResourceType, [f32; 4]>,
     player_colors: HashMap<u8, [f32; 4]>,
+    
+    // Fog tiles the local player has seen at least once
+    explored_tiles: HashSet<u32>,
+    
+    // Combat text over units, aged by frame time rather than simulation ticks
+    floating_text: FloatingText,
+    
+    draw_calls: u32, // Issued by the last frame, for the performance overlay
+    cull_stats: CullStats,
 }
 
 impl Renderer {
@@ -93,7 +242,7 @@ impl Renderer {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
-            
+        
         let config = SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format,
@@ -159,95 +308,11 @@ impl Renderer {
             ],
         });
         
-        // Load shaders
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Sprite Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/shaders/sprite.wgsl").into()),
-        });
-        
-        // Create pipeline layout
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Sprite Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-        
-        // Create sprite pipeline
-        let sprite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Sprite Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    wgpu::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &[
-                            // Position
-                            wgpu::VertexAttribute {
-                                offset: 0,
-                                shader_location: 0,
-                                format: wgpu::VertexFormat::Float32x3,
-                            },
-                            // Texture coordinates
-                            wgpu::VertexAttribute {
-                                offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                                shader_location: 1,
-                                format: wgpu::VertexFormat::Float32x2,
-                            },
-                            // Color
-                            wgpu::VertexAttribute {
-                                offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
-                                shader_location: 2,
-                                format: wgpu::VertexFormat::Float32x4,
-                            },
-                        ],
-                    },
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
-        
-        // Create vertex buffer with placeholder quad
-        let vertices = create_quad_vertices();
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        // Create terrain renderer sharing the camera bind group layout
+        let terrain_renderer = TerrainRenderer::new(&device, &queue, format, &bind_group_layout);
         
-        // Create index buffer with quad indices
-        let indices = create_quad_indices();
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        // Create instanced sprite renderer for entities and overlays
+        let sprite_renderer = SpriteRenderer::new(&device, &queue, format, &bind_group_layout);
         
         // Set up entity color placeholders
         let mut unit_colors = HashMap::new();
@@ -256,6 +321,7 @@ impl Renderer {
         unit_colors.insert(UnitType::Scout, [0.0, 0.0, 0.8, 1.0]); // Blue
         unit_colors.insert(UnitType::Tank, [0.8, 0.8, 0.0, 1.0]); // Yellow
         unit_colors.insert(UnitType::Healer, [0.8, 0.0, 0.8, 1.0]); // Purple
+        unit_colors.insert(UnitType::Transport, [0.0, 0.8, 0.8, 1.0]); // Cyan
         
         let mut building_colors = HashMap::new();
         building_colors.insert(BuildingType::Headquarters, [0.7, 0.7, 0.7, 1.0]); // Light Gray
@@ -264,6 +330,11 @@ impl Renderer {
         building_colors.insert(BuildingType::ResourceCollector, [0.3, 0.6, 0.3, 1.0]); // Dark Green
         building_colors.insert(BuildingType::ResearchCenter, [0.3, 0.3, 0.6, 1.0]); // Dark Blue
         building_colors.insert(BuildingType::DefenseTower, [0.6, 0.6, 0.3, 1.0]); // Brown Yellow
+        building_colors.insert(BuildingType::SupplyDepot, [0.5, 0.5, 0.2, 1.0]); // Olive
+        building_colors.insert(BuildingType::Fortress, [0.5, 0.5, 0.55, 1.0]); // Slate
+        building_colors.insert(BuildingType::Wall, [0.55, 0.5, 0.45, 1.0]); // Stone
+        building_colors.insert(BuildingType::Gate, [0.45, 0.35, 0.25, 1.0]); // Timber
+        building_colors.insert(BuildingType::Watchtower, [0.6, 0.55, 0.45, 1.0]); // Weathered stone
         
         let mut resource_colors = HashMap::new();
         resource_colors.insert(ResourceType::Mineral, [0.0, 0.5, 1.0, 1.0]); // Light Blue
@@ -275,28 +346,53 @@ impl Renderer {
         player_colors.insert(1, [1.0, 0.0, 0.0, 1.0]); // Red
         player_colors.insert(2, [0.0, 1.0, 0.0, 1.0]); // Green
         player_colors.insert(3, [1.0, 1.0, 0.0, 1.0]); // Yellow
+        player_colors.insert(NEUTRAL_PLAYER, [0.6, 0.6, 0.6, 1.0]); // Gray
         
         Ok(Self {
             surface,
             device,
             queue,
             config,
-            sprite_pipeline,
             camera_uniform_buffer,
             camera_bind_group,
             view_projection,
             camera_position: Vec2::new(128.0, 128.0),
             camera_zoom: 1.0,
-            vertex_buffer,
-            index_buffer,
+            terrain_renderer,
+            sprite_renderer,
             unit_colors,
             building_colors,
             resource_colors,
             player_colors,
+            explored_tiles: HashSet::new(),
+            floating_text: FloatingText::default(),
+            draw_calls: 0,
+            cull_stats: CullStats::default(),
         })
     }
     
     pub fn render(&mut self, world: &World, ui_manager: &UiManager) -> Result<()> {
+        // Remember everything the local player can currently see
+        self.update_explored_tiles(world);
+        
+        // Rebuild terrain chunks whose tiles changed
+        if let Some(map) = world.get_resource::<GameMap>() {
+            self.terrain_renderer.update(&self.device, map);
+        }
+        
+        // Gather this frame's sprites and upload them in one instance buffer
+        self.queue_world_sprites(world);
+        self.queue_selection(world);
+        self.queue_heal_beams(world);
+        self.queue_carried_resources(world);
+        self.queue_rank_chevrons(world);
+        self.queue_lighting(world);
+        self.queue_fog_of_war(world);
+        self.queue_status_overlays(world);
+        self.queue_combat_text(world);
+        self.queue_placement_ghost(world);
+        self.sprite_renderer.prepare(&self.device, &self.queue);
+        
         // Get a frame to render to
         let output = self.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -306,6 +402,7 @@ impl Renderer {
             label: Some("Render Encoder"),
         });
         
+        let terrain_draw_calls;
         {
             // Begin render pass
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -326,13 +423,19 @@ impl Renderer {
                 depth_stencil_attachment: None,
             });
             
-            // Render game world entities
-            self.render_world(&mut render_pass, world);
+            // Render terrain under everything else
+            let (view_min, view_max) = self.visible_world_bounds();
+            terrain_draw_calls = self.terrain_renderer.render(&mut render_pass, &self.camera_bind_group, view_min, view_max);
+            
+            // Render entities, heal beams, fog and the placement preview, one draw per layer and texture
+            self.sprite_renderer.render(&mut render_pass, &self.camera_bind_group);
             
             // Render UI
             ui_manager.render(&mut render_pass);
         }
         
+        self.draw_calls = terrain_draw_calls + self.sprite_renderer.draw_count();
+        
         // Submit commands
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
@@ -340,102 +443,602 @@ impl Renderer {
         Ok(())
     }
     
-    fn render_world<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, world: &'a World) {
-        // Set the pipeline
-        render_pass.set_pipeline(&self.sprite_pipeline);
-        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    /// Draw calls the last frame issued for the world
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+    
+    /// Entities the last frame drew and left out
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+    
+    /// Whether units are drawn as markers at the current zoom
+    fn shows_markers(&self) -> bool {
+        self.camera_zoom < MARKER_ZOOM
+    }
+    
+    /// Whether a sprite of this size overlaps the camera's view, however it is rotated
+    fn is_on_screen(&self, position: Vec2, size: Vec2) -> bool {
+        let (view_min, view_max) = self.visible_world_bounds();
+        let reach = Vec2::splat(size.max_element() * std::f32::consts::FRAC_1_SQRT_2);
+        (position + reach).cmpge(view_min).all() && (position - reach).cmple(view_max).all()
+    }
+    
+    /// Whether to draw an entity: on screen, and for other players' entities inside the local player's
+    /// vision. Counts what is left out
+    fn should_draw(&mut self, world: &World, owner: Option<u8>, position: Vec2, size: Vec2) -> bool {
+        if !self.is_on_screen(position, size) {
+            self.cull_stats.off_screen += 1;
+            return false;
+        }
+        if owner.map_or(false, |owner| !self.is_visible_to_local_player(world, owner, position)) {
+            self.cull_stats.fogged += 1;
+            return false;
+        }
+        self.cull_stats.drawn += 1;
+        true
+    }
+    
+    /// Queue resources, buildings and units as sprites, tinted with their owner's color. Only what is on
+    /// screen and in sight is queued; zoomed far out, units become markers
+    fn queue_world_sprites(&mut self, world: &World) {
+        self.cull_stats = CullStats::default();
         
-        // Render all entities
-        // First, render resources
+        // First, resources
         let mut resource_query = world.query::<(&Resource, &Transform)>();
         for (resource, transform) in resource_query.iter(world) {
-            let color = self.resource_colors.get(&resource.resource_type).unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
-            let model = self.calculate_model_matrix(transform, 0.8); // Smaller size for resources
+            if !self.should_draw(world, None, transform.position, transform.scale * 0.8) {
+                continue;
+            }
             
-            // Set push constants (in real implementation, would use instance rendering)
-            // For now, just render a colored quad
-            render_pass.draw_indexed(0..6, 0, 0..1);
+            let texture = resource_texture(resource.resource_type);
+            let color = if self.sprite_renderer.has_texture(texture) {
+                [1.0, 1.0, 1.0, 1.0]
+            } else {
+                self.resource_colors.get(&resource.resource_type).copied().unwrap_or([1.0, 1.0, 1.0, 1.0])
+            };
+            let instance = SpriteInstance::new(transform.position, transform.scale * 0.8, transform.rotation, color); // Smaller size for resources
+            self.sprite_renderer.push(SpriteLayer::Resources, Some(texture), instance);
         }
         
-        // Render buildings
+        // Buildings
         let mut building_query = world.query::<(&Building, &Transform, &Owner, Option<&Selected>)>();
         for (building, transform, owner, selected) in building_query.iter(world) {
-            let base_color = self.building_colors.get(&building.building_type).unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
-            let player_color = self.player_colors.get(&owner.0).unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
+            if !self.should_draw(world, Some(owner.0), transform.position, transform.scale * 2.0) {
+                continue;
+            }
             
-            // Mix base color with player color
-            let color = [
-                base_color[0] * 0.5 + player_color[0] * 0.5,
-                base_color[1] * 0.5 + player_color[1] * 0.5,
-                base_color[2] * 0.5 + player_color[2] * 0.5,
-                1.0,
-            ];
+            let texture = BuildingData::get(building.building_type).texture_name.as_str();
+            let base_color = self.building_colors.get(&building.building_type).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]);
+            let color = self.team_tint(texture, base_color, owner.0, 0.5);
             
-            // Scale for building size - headquarters bigger than other buildings
-            let scale = if building.building_type == BuildingType::Headquarters {
+            // Scale for building size - headquarters bigger than other buildings,
+            // wall pieces exactly their footprint so neighbouring segments join up
+            let scale = if building.building_type.is_headquarters() {
                 2.0
+            } else if buildings::is_wall_piece(building.building_type) {
+                1.0
             } else {
                 1.5
             };
             
-            let model = self.calculate_model_matrix(transform, scale);
-            
-            // Draw the building
-            render_pass.draw_indexed(0..6, 0, 0..1);
-            
-            // Draw selection indicator if selected
+            let mut instance = SpriteInstance::new(transform.position, transform.scale * scale, transform.rotation, color);
             if selected.is_some() {
-                // Draw outline
-                render_pass.draw_indexed(0..6, 0, 0..1);
+                instance = instance.with_outline(SELECTION_COLOR, SELECTION_OUTLINE_WIDTH);
+            }
+            self.sprite_renderer.push(SpriteLayer::Buildings, Some(texture), instance);
+        }
+        
+        // Corpses, queued first so living units are drawn over them
+        if !self.shows_markers() {
+            let mut corpse_query = world.query::<(&Corpse, &Transform, &Owner, &Animation)>();
+            for (corpse, transform, owner, animation) in corpse_query.iter(world) {
+                if !self.should_draw(world, Some(owner.0), transform.position, transform.scale * 0.5) {
+                    continue;
+                }
+                self.queue_unit_sprite(corpse.unit_type, transform, owner.0, animation);
+            }
+        }
+        
+        // Units; ones spawned this tick have no animation yet and show their first idle frame
+        let mut unit_query = world.query::<(&Unit, &Transform, &Owner, Option<&Animation>)>();
+        for (unit, transform, owner, animation) in unit_query.iter(world) {
+            if !self.should_draw(world, Some(owner.0), transform.position, transform.scale * 0.5) {
+                continue;
             }
+            if self.shows_markers() {
+                let color = self.player_colors.get(&owner.0).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]);
+                let marker = SpriteInstance::new(transform.position, Vec2::splat(MARKER_SIZE), 0.0, color);
+                self.sprite_renderer.push(SpriteLayer::Units, None, marker);
+                self.cull_stats.markers += 1;
+                continue;
+            }
+            self.queue_unit_sprite(unit.unit_type, transform, owner.0, &animation.copied().unwrap_or_default());
         }
+    }
+    
+    /// Queue a unit's sprite showing the sheet frame its animation is on
+    fn queue_unit_sprite(&mut self, unit_type: UnitType, transform: &Transform, owner: u8, animation: &Animation) {
+        let texture = unit_texture(unit_type);
+        let base_color = self.unit_colors.get(&unit_type).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        let color = self.team_tint(texture, base_color, owner, 0.7);
+        
+        let (frame_offset, frame_size) = data::game_data().animation(unit_type).frame_region(animation);
+        let instance = SpriteInstance::new(transform.position, transform.scale * 0.5, transform.rotation, color) // Units are smaller
+            .with_region(frame_offset, frame_size);
+        self.sprite_renderer.push(SpriteLayer::Units, Some(texture), instance);
+    }
+    
+    /// Ellipses under the local player's selection, a ring under the hovered entity and the drag-select rectangle
+    fn queue_selection(&mut self, world: &World) {
+        let local_player_id = Self::local_player_id(world);
         
-        // Render units
-        let mut unit_query = world.query::<(&Unit, &Transform, &Owner, Option<&Selected>)>();
-        for (unit, transform, owner, selected) in unit_query.iter(world) {
-            let base_color = self.unit_colors.get(&unit.unit_type).unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
-            let player_color = self.player_colors.get(&owner.0).unwrap_or(&[1.0, 1.0, 1.0, 1.0]);
+        let mut selected_query = world.query_filtered::<(&Transform, &Owner, Option<&Collider>), With<Selected>>();
+        for (transform, owner, collider) in selected_query.iter(world) {
+            if owner.0 != local_player_id {
+                continue;
+            }
+            let ellipse = self.ground_ellipse(transform.position, collider, 1.0, SELECTION_COLOR);
+            self.sprite_renderer.push(SpriteLayer::Ground, Some(RING_TEXTURE), ellipse);
+        }
+        
+        let overlay = match world.get_resource::<SelectionOverlay>() {
+            Some(overlay) => overlay,
+            None => return,
+        };
+        
+        if let Some(hovered) = overlay.hovered {
+            let owner = world.get::<Owner>(hovered).map(|owner| owner.0);
+            if let (Some(transform), Some(owner)) = (world.get::<Transform>(hovered), owner) {
+                let allied = world.get_resource::<PlayerInfo>()
+                    .map_or(owner == local_player_id, |info| info.are_allies(owner, local_player_id));
+                let color = if owner == local_player_id {
+                    HOVER_OWN_COLOR
+                } else if allied {
+                    HOVER_ALLY_COLOR
+                } else {
+                    HOVER_ENEMY_COLOR
+                };
+                
+                // Slightly wider than the selection ellipse so both show on a selected entity
+                let ring = self.ground_ellipse(transform.position, world.get::<Collider>(hovered), 1.2, color);
+                self.sprite_renderer.push(SpriteLayer::Ground, Some(RING_TEXTURE), ring);
+            }
+        }
+        
+        if let Some((start, end)) = overlay.drag_rect {
+            let min = start.min(end);
+            let max = start.max(end);
+            let center = (min + max) * 0.5;
+            let size = max - min;
+            self.sprite_renderer.push(SpriteLayer::Overlay, None, SpriteInstance::new(center, size, 0.0, DRAG_FILL_COLOR));
             
-            // Mix base color with player color
-            let color = [
-                base_color[0] * 0.3 + player_color[0] * 0.7,
-                base_color[1] * 0.3 + player_color[1] * 0.7,
-                base_color[2] * 0.3 + player_color[2] * 0.7,
-                1.0,
+            // Edges stay one screen pixel thick whatever the zoom
+            let (view_min, view_max) = self.visible_world_bounds();
+            let edge = (view_max.x - view_min.x) / self.config.width.max(1) as f32;
+            let edges = [
+                (Vec2::new(center.x, min.y), Vec2::new(size.x + edge, edge)),
+                (Vec2::new(center.x, max.y), Vec2::new(size.x + edge, edge)),
+                (Vec2::new(min.x, center.y), Vec2::new(edge, size.y + edge)),
+                (Vec2::new(max.x, center.y), Vec2::new(edge, size.y + edge)),
             ];
+            for (position, size) in edges {
+                self.sprite_renderer.push(SpriteLayer::Overlay, None, SpriteInstance::new(position, size, 0.0, DRAG_EDGE_COLOR));
+            }
+        }
+    }
+    
+    /// Flattened ring around an entity's feet, sized from its collider
+    fn ground_ellipse(&self, position: Vec2, collider: Option<&Collider>, scale: f32, color: [f32; 4]) -> SpriteInstance {
+        let radius = collider.map(|c| c.radius).unwrap_or(8.0) * scale;
+        let size = Vec2::new(radius * 2.6, radius * 1.6);
+        SpriteInstance::new(position - Vec2::new(0.0, radius * 0.4), size, 0.0, color)
+    }
+    
+    /// Mix an entity's color with its owner's color. Textured sprites keep their own colors
+    /// and only take a lighter team tint.
+    fn team_tint(&self, texture: &str, base_color: [f32; 4], owner: u8, player_weight: f32) -> [f32; 4] {
+        let player_color = self.player_colors.get(&owner).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        let (base_color, player_weight) = if self.sprite_renderer.has_texture(texture) {
+            ([1.0, 1.0, 1.0, 1.0], player_weight * 0.5)
+        } else {
+            (base_color, player_weight)
+        };
+        
+        [
+            base_color[0] * (1.0 - player_weight) + player_color[0] * player_weight,
+            base_color[1] * (1.0 - player_weight) + player_color[1] * player_weight,
+            base_color[2] * (1.0 - player_weight) + player_color[2] * player_weight,
+            1.0,
+        ]
+    }
+    
+    fn queue_heal_beams(&mut self, world: &World) {
+        let color = [0.3, 1.0, 0.4, 0.7];
+        
+        let mut healer_query = world.query::<(&Transform, &Owner, &HealTarget)>();
+        for (transform, owner, heal_target) in healer_query.iter(world) {
+            if !heal_target.healing || !self.is_visible_to_local_player(world, owner.0, transform.position) {
+                continue;
+            }
+            let patient_position = match world.get::<Transform>(heal_target.target_entity) {
+                Some(patient_transform) => patient_transform.position,
+                None => continue,
+            };
             
-            let model = self.calculate_model_matrix(transform, 0.5); // Units are smaller
+            // Stretch a thin quad from the healer to the patient
+            let to_patient = patient_position - transform.position;
+            if !self.is_on_screen((transform.position + patient_position) * 0.5, Vec2::splat(to_patient.length())) {
+                continue;
+            }
+            let beam = SpriteInstance::new(
+                (transform.position + patient_position) * 0.5,
+                Vec2::new(to_patient.length(), 1.5),
+                to_patient.y.atan2(to_patient.x),
+                color,
+            );
+            self.sprite_renderer.push(SpriteLayer::Effects, None, beam);
+        }
+    }
+    
+    /// Draw a small chunk of the resource a worker is carrying above it, growing as the load fills up
+    fn queue_carried_resources(&mut self, world: &World) {
+        if self.shows_markers() {
+            return;
+        }
+        
+        let mut carrier_query = world.query::<(&Transform, &Owner, &CarriedResources)>();
+        for (transform, owner, carried) in carrier_query.iter(world) {
+            if carried.amount <= 0.0 ||
+                !self.is_on_screen(transform.position, transform.scale) ||
+                !self.is_visible_to_local_player(world, owner.0, transform.position)
+            {
+                continue;
+            }
             
-            // Draw the unit
-            render_pass.draw_indexed(0..6, 0, 0..1);
+            let texture = resource_texture(carried.resource_type);
+            let color = if self.sprite_renderer.has_texture(texture) {
+                [1.0, 1.0, 1.0, 1.0]
+            } else {
+                self.resource_colors.get(&carried.resource_type).copied().unwrap_or([1.0, 1.0, 1.0, 1.0])
+            };
+            let fill = (carried.amount / CARRY_CAPACITY).clamp(0.0, 1.0);
+            let instance = SpriteInstance::new(
+                transform.position + Vec2::new(0.0, transform.scale.y * 0.5),
+                transform.scale * (0.15 + 0.15 * fill),
+                0.0,
+                color,
+            );
+            self.sprite_renderer.push(SpriteLayer::Effects, Some(texture), instance);
+        }
+    }
+    
+    /// Stack a chevron above each promoted unit per veterancy rank
+    fn queue_rank_chevrons(&mut self, world: &World) {
+        if self.shows_markers() {
+            return;
+        }
+        
+        let color = [1.0, 0.85, 0.2, 1.0];
+        
+        let mut veteran_query = world.query::<(&Transform, &Owner, &Experience)>();
+        for (transform, owner, experience) in veteran_query.iter(world) {
+            if experience.rank == 0 ||
+                !self.is_on_screen(transform.position, transform.scale) ||
+                !self.is_visible_to_local_player(world, owner.0, transform.position)
+            {
+                continue;
+            }
             
-            // Draw selection indicator if selected
-            if selected.is_some() {
-                // Draw outline
-                render_pass.draw_indexed(0..6, 0, 0..1);
+            for index in 0..experience.rank {
+                let offset = Vec2::new(0.0, transform.scale.y * 0.5 + 4.0 + index as f32 * 2.5);
+                let chevron = SpriteInstance::new(transform.position + offset, Vec2::new(5.0, 1.5), 0.0, color);
+                self.sprite_renderer.push(SpriteLayer::Effects, None, chevron);
             }
         }
     }
     
-    fn calculate_model_matrix(&self, transform: &Transform, scale_multiplier: f32) -> Mat4 {
-        // Calculate model matrix from transform
-        let translate = Mat4::from_translation(glam::Vec3::new(
-            transform.position.x,
-            transform.position.y,
-            0.0,
-        ));
+    /// Health bars, construction, production and research progress, energy and ability pips over the entities in view
+    fn queue_status_overlays(&mut self, world: &World) {
+        let mode = world.get_resource::<GameSettings>()
+            .map(|settings| settings.health_bars)
+            .unwrap_or_default();
+        let local_player_id = Self::local_player_id(world);
         
-        let rotate = Mat4::from_rotation_z(transform.rotation);
+        // Leave some slack so bars of entities at the screen edge don't pop in late
+        let (view_min, view_max) = self.visible_world_bounds();
+        let margin = Vec2::splat(32.0);
+        let in_view = |position: Vec2| {
+            position.cmpge(view_min - margin).all() && position.cmple(view_max + margin).all()
+        };
         
-        let scale = Mat4::from_scale(glam::Vec3::new(
-            transform.scale.x * scale_multiplier,
-            transform.scale.y * scale_multiplier,
-            1.0,
-        ));
+        let mut building_query = world.query::<(
+            &Building, &Transform, &Owner, Option<&Collider>, Option<&Selected>, Option<&ResearchStatus>,
+        )>();
+        for (building, transform, owner, collider, selected, research) in building_query.iter(world) {
+            if !in_view(transform.position) || !self.is_visible_to_local_player(world, owner.0, transform.position) {
+                continue;
+            }
+            
+            let radius = collider.map(|c| c.radius).unwrap_or(16.0);
+            let width = radius * 1.5;
+            let mut row = transform.position + Vec2::new(0.0, radius + 4.0);
+            
+            if mode.shows(building.health, building.max_health, selected.is_some()) {
+                self.queue_health_bar(row, width, building.health / building.max_health, owner.0);
+                row.y += BAR_HEIGHT + BAR_SPACING;
+            }
+            
+            // Only one of these runs at a time: construction, then training or research
+            let progress = building.construction_progress
+                .or(building.production_progress)
+                .or(research.map(|research| research.progress / research.total_time.max(f32::EPSILON)));
+            if let Some(progress) = progress {
+                let color = if building.construction_progress.is_some() {
+                    [0.9, 0.7, 0.2, 1.0]
+                } else {
+                    [0.3, 0.7, 1.0, 1.0]
+                };
+                self.queue_bar(row, width, progress, color, [0.0, 0.0, 0.0, 0.6]);
+            }
+        }
+        
+        let mut unit_query = world.query::<(
+            &Unit, &Transform, &Owner, Option<&Collider>, Option<&Selected>, Option<&Caster>, Option<&ActiveBuffs>,
+        )>();
+        for (unit, transform, owner, collider, selected, caster, buffs) in unit_query.iter(world) {
+            if !in_view(transform.position) || !self.is_visible_to_local_player(world, owner.0, transform.position) {
+                continue;
+            }
+            
+            let radius = collider.map(|c| c.radius).unwrap_or(5.0);
+            let width = (radius * 2.5).max(10.0);
+            let mut row = transform.position + Vec2::new(0.0, radius + 3.0);
+            
+            if mode.shows(unit.health, unit.max_health, selected.is_some()) {
+                self.queue_health_bar(row, width, unit.health / unit.max_health, owner.0);
+                row.y += BAR_HEIGHT + BAR_SPACING;
+            }
+            
+            // Energy and ability readiness are only shown for the local player's own casters
+            if let Some(caster) = caster.filter(|_| owner.0 == local_player_id) {
+                if selected.is_some() || mode == HealthBarMode::Always {
+                    let energy = caster.energy / caster.max_energy.max(f32::EPSILON);
+                    self.queue_bar(row, width, energy, [0.7, 0.3, 1.0, 1.0], [0.0, 0.0, 0.0, 0.6]);
+                    row.y += BAR_HEIGHT + BAR_SPACING;
+                }
+            }
+            
+            // One pip per ability, lit when it can be cast, then one per active buff
+            if self.shows_markers() {
+                continue;
+            }
+            let mut pips: Vec<[f32; 4]> = Vec::new();
+            if let Some(caster) = caster.filter(|_| owner.0 == local_player_id) {
+                for ability in data::game_data().unit_abilities(unit.unit_type) {
+                    let ready = abilities::cast_blocker(caster, ability).is_none();
+                    pips.push(if ready { [0.3, 0.9, 1.0, 1.0] } else { [0.25, 0.25, 0.3, 1.0] });
+                }
+            }
+            if let Some(buffs) = buffs {
+                pips.extend(buffs.buffs.iter().map(|_| [1.0, 0.6, 0.1, 1.0]));
+            }
+            
+            let stride = PIP_SIZE + BAR_SPACING;
+            let left = transform.position.x - (pips.len() as f32 - 1.0) * stride * 0.5;
+            for (index, color) in pips.into_iter().enumerate() {
+                let pip = SpriteInstance::new(Vec2::new(left + index as f32 * stride, row.y), Vec2::splat(PIP_SIZE), 0.0, color);
+                self.sprite_renderer.push(SpriteLayer::Overlay, None, pip);
+            }
+        }
+    }
+    
+    /// Health bar colored from green to red by the health left, on a backing in the owner's color
+    fn queue_health_bar(&mut self, center: Vec2, width: f32, fraction: f32, owner: u8) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let fill = if fraction > 0.5 {
+            [2.0 * (1.0 - fraction), 1.0, 0.1, 1.0]
+        } else {
+            [1.0, 2.0 * fraction, 0.1, 1.0]
+        };
         
-        translate * rotate * scale
+        let player_color = self.player_colors.get(&owner).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        let background = [player_color[0] * 0.35, player_color[1] * 0.35, player_color[2] * 0.35, 0.9];
+        self.queue_bar(center, width, fraction, fill, background);
+    }
+    
+    /// Bar filled from the left up to `fraction` of its width
+    fn queue_bar(&mut self, center: Vec2, width: f32, fraction: f32, color: [f32; 4], background: [f32; 4]) {
+        let backing = SpriteInstance::new(center, Vec2::new(width + 1.0, BAR_HEIGHT + 1.0), 0.0, background);
+        self.sprite_renderer.push(SpriteLayer::Overlay, None, backing);
+        
+        let fraction = fraction.clamp(0.0, 1.0);
+        if fraction <= 0.0 {
+            return;
+        }
+        let fill_width = width * fraction;
+        let fill_center = Vec2::new(center.x - (width - fill_width) * 0.5, center.y);
+        let fill = SpriteInstance::new(fill_center, Vec2::new(fill_width, BAR_HEIGHT), 0.0, color);
+        self.sprite_renderer.push(SpriteLayer::Overlay, None, fill);
+    }
+    
+    /// Floating combat text inside the local player's vision, all from one glyph atlas so it batches into one draw
+    fn queue_combat_text(&mut self, world: &World) {
+        if !self.sprite_renderer.has_texture(COMBAT_TEXT_TEXTURE) {
+            return;
+        }
+        
+        let local_player_id = Self::local_player_id(world);
+        let fog = world.get_resource::<GameMap>().filter(|_| Self::fog_enabled(world));
+        for floater in self.floating_text.floaters() {
+            if fog.map_or(false, |map| !is_position_visible(map, local_player_id, floater.position, PATH_GRID_SIZE)) {
+                continue;
+            }
+            for glyph in floater.glyphs() {
+                self.sprite_renderer.push(SpriteLayer::Overlay, Some(COMBAT_TEXT_TEXTURE), glyph);
+            }
+        }
+    }
+    
+    /// Whether fog of war applies to this frame
+    fn fog_enabled(world: &World) -> bool {
+        world.get_resource::<GameSettings>()
+            .map(|settings| settings.fog_of_war_enabled)
+            .unwrap_or(true)
+    }
+    
+    fn local_player_id(world: &World) -> u8 {
+        world.get_resource::<PlayerInfo>()
+            .map(|info| info.local_player_id)
+            .unwrap_or(0)
+    }
+    
+    /// Own entities are always drawn; everyone else's only inside the local player's vision
+    fn is_visible_to_local_player(&self, world: &World, owner: u8, position: Vec2) -> bool {
+        let local_player_id = Self::local_player_id(world);
+        if owner == local_player_id || !Self::fog_enabled(world) {
+            return true;
+        }
+        
+        match world.get_resource::<GameMap>() {
+            Some(map) => is_position_visible(map, local_player_id, position, PATH_GRID_SIZE),
+            None => true,
+        }
+    }
+    
+    /// Merge the local player's currently visible tiles into the explored set
+    fn update_explored_tiles(&mut self, world: &World) {
+        let local_player_id = Self::local_player_id(world);
+        if let Some(visible_tiles) = world.get_resource::<GameMap>()
+            .and_then(|map| map.fog_of_war.get(&local_player_id)) {
+            self.explored_tiles.extend(visible_tiles.iter().copied());
+        }
+    }
+    
+    /// Mark every tile of the map as explored
+    pub fn reveal_map(&mut self, map: &GameMap) {
+        self.explored_tiles.extend(0..map.width * map.height);
+    }
+    
+    /// Forget explored tiles, e.g. when a new game or save is loaded
+    pub fn reset_fog_of_war(&mut self) {
+        self.explored_tiles.clear();
+    }
+    
+    /// Darken the view by the time of day; drawn under the fog so unexplored areas stay black
+    fn queue_lighting(&mut self, world: &World) {
+        let (cycle, time) = match (world.get_resource::<DayNightCycle>(), world.get_resource::<GameTime>()) {
+            (Some(cycle), Some(time)) => (cycle, time),
+            _ => return,
+        };
+        let tint = cycle.tint(time.current_tick);
+        if tint[3] <= 0.0 {
+            return;
+        }
+        
+        let (view_min, view_max) = self.visible_world_bounds();
+        let overlay = SpriteInstance::new((view_min + view_max) * 0.5, view_max - view_min, 0.0, tint);
+        self.sprite_renderer.push(SpriteLayer::Lighting, None, overlay);
+    }
+    
+    fn queue_fog_of_war(&mut self, world: &World) {
+        if !Self::fog_enabled(world) {
+            return;
+        }
+        
+        let map = match world.get_resource::<GameMap>() {
+            Some(map) => map,
+            None => return,
+        };
+        let visible_tiles = match map.fog_of_war.get(&Self::local_player_id(world)) {
+            Some(visible_tiles) => visible_tiles,
+            None => return, // Fog hasn't been computed yet
+        };
+        
+        // Only cover the tiles inside the camera view
+        let (view_min, view_max) = self.visible_world_bounds();
+        let min = view_min / PATH_GRID_SIZE;
+        let max = view_max / PATH_GRID_SIZE;
+        
+        let min_x = min.x.floor().max(0.0) as u32;
+        let min_y = min.y.floor().max(0.0) as u32;
+        let max_x = (max.x.ceil().max(0.0) as u32).min(map.width.saturating_sub(1));
+        let max_y = (max.y.ceil().max(0.0) as u32).min(map.height.saturating_sub(1));
+        
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let tile_index = y * map.width + x;
+                
+                // Visible tiles are left clear; explored ones are dimmed, the rest blacked out
+                let color = if visible_tiles.contains(&tile_index) {
+                    continue;
+                } else if self.explored_tiles.contains(&tile_index) {
+                    [0.0, 0.0, 0.0, 0.5]
+                } else {
+                    [0.0, 0.0, 0.0, 1.0]
+                };
+                
+                // Cover the fog tile over the world
+                let tile = SpriteInstance::new(
+                    Vec2::new((x as f32 + 0.5) * PATH_GRID_SIZE, (y as f32 + 0.5) * PATH_GRID_SIZE),
+                    Vec2::splat(PATH_GRID_SIZE),
+                    0.0,
+                    color,
+                );
+                self.sprite_renderer.push(SpriteLayer::Fog, None, tile);
+            }
+        }
+    }
+    
+    /// World-space rectangle covered by the camera
+    fn visible_world_bounds(&self) -> (Vec2, Vec2) {
+        // Matches create_view_projection_matrix
+        let half_width = 400.0 / self.camera_zoom;
+        let half_height = half_width * self.config.height as f32 / self.config.width as f32;
+        let half_extent = Vec2::new(half_width, half_height);
+        (self.camera_position - half_extent, self.camera_position + half_extent)
+    }
+    
+    fn queue_placement_ghost(&mut self, world: &World) {
+        let ghost = match world.get_resource::<PlacementGhost>() {
+            Some(ghost) => ghost,
+            None => return,
+        };
+        
+        // Translucent green when the spot is buildable, red otherwise
+        let color = if ghost.valid {
+            [0.2, 0.9, 0.2, 0.4]
+        } else {
+            [0.9, 0.2, 0.2, 0.4]
+        };
+        
+        // Draw the ghost on top of the world; a wall row shows each segment, red where it can't be built
+        let size = BuildingData::get(ghost.building_type).size;
+        if ghost.segments.is_empty() {
+            let instance = SpriteInstance::new(ghost.position, size, 0.0, color);
+            self.sprite_renderer.push(SpriteLayer::Overlay, None, instance);
+        }
+        for &(position, valid) in &ghost.segments {
+            let color = if valid { [0.2, 0.9, 0.2, 0.4] } else { [0.9, 0.2, 0.2, 0.4] };
+            let instance = SpriteInstance::new(position, size, 0.0, color);
+            self.sprite_renderer.push(SpriteLayer::Overlay, None, instance);
+        }
+    }
+    
+    /// Wait for the display's refresh before presenting, or present as soon as a frame is ready
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.config.present_mode = if vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        self.surface.configure(&self.device, &self.config);
+    }
+    
+    /// Set the surface up again at its current size, after it was lost or went out of date
+    pub fn reconfigure_surface(&mut self) {
+        self.surface.configure(&self.device, &self.config);
     }
     
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -485,6 +1088,30 @@ impl Renderer {
         );
     }
     
+    /// Start floaters for combat text raised by the simulation
+    pub fn add_combat_text(&mut self, texts: Vec<CombatText>) {
+        self.floating_text.add(texts);
+    }
+    
+    /// Age the floating combat text by a frame
+    pub fn update_combat_text(&mut self, delta_time: f32) {
+        self.floating_text.update(delta_time);
+    }
+    
+    pub fn clear_combat_text(&mut self) {
+        self.floating_text.clear();
+    }
+    
+    /// Use a loaded texture for a terrain type
+    pub fn set_terrain_texture(&mut self, terrain: TerrainTile, texture: &TextureAsset) {
+        self.terrain_renderer.set_texture(&self.device, terrain, texture);
+    }
+    
+    /// Use a loaded texture (or atlas) for sprites that reference it by name
+    pub fn set_sprite_texture(&mut self, name: &str, texture: &TextureAsset) {
+        self.sprite_renderer.set_texture(&self.device, name, texture);
+    }
+    
     pub fn get_device(&self) -> &Device {
         &self.device
     }
@@ -498,35 +1125,25 @@ impl Renderer {
     }
 }
 
-// Helper functions for creating basic geometry
-
-fn create_quad_vertices() -> [Vertex; 4] {
-    [
-        Vertex {
-            position: [-0.5, -0.5, 0.0],
-            tex_coords: [0.0, 1.0],
-            color: [1.0, 1.0, 1.0, 1.0],
-        },
-        Vertex {
-            position: [0.5, -0.5, 0.0],
-            tex_coords: [1.0, 1.0],
-            color: [1.0, 1.0, 1.0, 1.0],
-        },
-        Vertex {
-            position: [0.5, 0.5, 0.0],
-            tex_coords: [1.0, 0.0],
-            color: [1.0, 1.0, 1.0, 1.0],
-        },
-        Vertex {
-            position: [-0.5, 0.5, 0.0],
-            tex_coords: [0.0, 0.0],
-            color: [1.0, 1.0, 1.0, 1.0],
-        },
-    ]
+/// Texture a unit type is drawn with
+fn unit_texture(unit_type: UnitType) -> &'static str {
+    match unit_type {
+        UnitType::Worker => "unit_worker",
+        UnitType::Soldier => "unit_soldier",
+        UnitType::Scout => "unit_scout",
+        UnitType::Tank => "unit_tank",
+        UnitType::Healer => "unit_healer",
+        UnitType::Transport => "unit_transport",
+    }
 }
 
-fn create_quad_indices() -> [u16; 6] {
-    [0, 1, 2, 0, 2, 3]
+/// Texture a resource node is drawn with
+fn resource_texture(resource_type: ResourceType) -> &'static str {
+    match resource_type {
+        ResourceType::Mineral => "resource_mineral",
+        ResourceType::Gas => "resource_gas",
+        ResourceType::Energy => "resource_energy",
+    }
 }
 
 fn create_view_projection_matrix(position: Vec2, zoom: f32, aspect_ratio: f32) -> Mat4 {
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, TextureFormat};
+
+use crate::engine::assets::TextureAsset;
+
+/// Width of the selection outline as a fraction of the sprite size
+pub const SELECTION_OUTLINE_WIDTH: f32 = 0.08;
+
+/// Built-in white ring texture, tinted for selection circles and hover highlights
+pub const RING_TEXTURE: &str = "ring";
+
+/// Edge length in pixels of the ring texture
+const RING_TEXTURE_SIZE: u32 = 64;
+
+/// Thickness of the ring as a fraction of its radius
+const RING_THICKNESS: f32 = 0.15;
+
+/// Draw order of sprites; later layers are drawn on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpriteLayer {
+    Ground, // Selection circles and other markings under everything standing on the map
+    Resources,
+    Buildings,
+    Units,
+    Effects,
+    Lighting, // Time of day tint over the whole view
+    Fog,
+    Overlay,
+}
+
+// Vertex format for the shared unit quad
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct QuadVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+/// Per-instance data for one sprite
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub rotation: f32,
+    pub outline_width: f32,     // Fraction of the sprite size, 0 for no outline
+    pub uv_rect: [f32; 4],      // Atlas region: offset in xy, size in zw
+    pub tint: [f32; 4],
+    pub outline_color: [f32; 4],
+}
+
+impl SpriteInstance {
+    /// Sprite covering the whole texture, centered on a world position
+    pub fn new(position: Vec2, size: Vec2, rotation: f32, tint: [f32; 4]) -> Self {
+        Self {
+            position: position.to_array(),
+            size: size.to_array(),
+            rotation,
+            outline_width: 0.0,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            tint,
+            outline_color: [0.0; 4],
+        }
+    }
+    
+    /// Sample only a region of a texture atlas (offset and size in 0..1 texture coordinates)
+    pub fn with_region(mut self, offset: Vec2, size: Vec2) -> Self {
+        self.uv_rect = [offset.x, offset.y, size.x, size.y];
+        self
+    }
+    
+    /// Draw a colored border around the sprite, e.g. for selection
+    pub fn with_outline(mut self, color: [f32; 4], width: f32) -> Self {
+        self.outline_color = color;
+        self.outline_width = width;
+        self
+    }
+}
+
+/// Sprite queued for the current frame
+struct QueuedSprite {
+    layer: SpriteLayer,
+    texture: Option<&'static str>,
+    instance: SpriteInstance,
+}
+
+/// Instanced sprite renderer: sprites are queued each frame, uploaded in one instance buffer
+/// and drawn with one call per run of sprites sharing a texture
+pub struct SpriteRenderer {
+    pipeline: RenderPipeline,
+    texture_bind_group_layout: BindGroupLayout,
+    texture_bind_groups: HashMap<String, BindGroup>,
+    white_bind_group: BindGroup, // Untextured sprites are plain quads colored by their tint
+    quad_vertex_buffer: Buffer,
+    quad_index_buffer: Buffer,
+    instance_buffer: Option<Buffer>,
+    instance_capacity: usize,
+    queued: Vec<QueuedSprite>,
+    draws: Vec<(Option<&'static str>, Range<u32>)>,
+}
+
+impl SpriteRenderer {
+    pub fn new(device: &Device, queue: &Queue, format: TextureFormat, camera_bind_group_layout: &BindGroupLayout) -> Self {
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/shaders/sprite.wgsl").into()),
+        });
+        
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<QuadVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x2, // Position
+                            1 => Float32x2, // Texture coordinates
+                        ],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            2 => Float32x2, // Position
+                            3 => Float32x2, // Size
+                            4 => Float32,   // Rotation
+                            5 => Float32,   // Outline width
+                            6 => Float32x4, // Atlas region
+                            7 => Float32x4, // Tint
+                            8 => Float32x4, // Outline color
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None, // Sprites may be mirrored with a negative size
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        
+        let white_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Sprite Fallback Texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            &[255, 255, 255, 255],
+        );
+        let white_view = white_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let white_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let white_bind_group = create_texture_bind_group(device, &texture_bind_group_layout, &white_view, &white_sampler);
+        
+        // Smoothly filtered so rings stay round at any size
+        let ring_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Sprite Ring Texture"),
+                size: wgpu::Extent3d {
+                    width: RING_TEXTURE_SIZE,
+                    height: RING_TEXTURE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            &ring_pixels(),
+        );
+        let ring_view = ring_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let ring_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let mut texture_bind_groups = HashMap::new();
+        texture_bind_groups.insert(
+            RING_TEXTURE.to_string(),
+            create_texture_bind_group(device, &texture_bind_group_layout, &ring_view, &ring_sampler),
+        );
+        
+        let quad_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Quad Vertex Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let quad_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Quad Index Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        
+        Self {
+            pipeline,
+            texture_bind_group_layout,
+            texture_bind_groups,
+            white_bind_group,
+            quad_vertex_buffer,
+            quad_index_buffer,
+            instance_buffer: None,
+            instance_capacity: 0,
+            queued: Vec::new(),
+            draws: Vec::new(),
+        }
+    }
+    
+    /// Use a loaded texture (or texture atlas) for sprites that name it
+    pub fn set_texture(&mut self, device: &Device, name: &str, texture: &TextureAsset) {
+        let bind_group = create_texture_bind_group(device, &self.texture_bind_group_layout, &texture.view, &texture.sampler);
+        self.texture_bind_groups.insert(name.to_string(), bind_group);
+    }
+    
+    /// Whether a texture has been loaded under this name
+    pub fn has_texture(&self, name: &str) -> bool {
+        self.texture_bind_groups.contains_key(name)
+    }
+    
+    /// Queue a sprite for this frame; sprites naming a missing texture are drawn untextured
+    pub fn push(&mut self, layer: SpriteLayer, texture: Option<&'static str>, instance: SpriteInstance) {
+        let texture = texture.filter(|name| self.has_texture(name));
+        self.queued.push(QueuedSprite { layer, texture, instance });
+    }
+    
+    /// Upload the queued sprites, grouped by layer and texture, and plan one draw per run of sprites
+    /// sharing a texture. A layer's last group and the next layer's first draw together when their
+    /// textures match, as untextured bars and effects often do
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) {
+        self.draws.clear();
+        if self.queued.is_empty() {
+            return;
+        }
+        
+        // Stable sort keeps queue order within a group
+        self.queued.sort_by_key(|sprite| (sprite.layer, sprite.texture));
+        
+        let instances: Vec<SpriteInstance> = self.queued.iter().map(|sprite| sprite.instance).collect();
+        for (i, sprite) in self.queued.iter().enumerate() {
+            let i = i as u32;
+            match self.draws.last_mut() {
+                Some((texture, range)) if *texture == sprite.texture => range.end = i + 1,
+                _ => self.draws.push((sprite.texture, i..i + 1)),
+            }
+        }
+        self.queued.clear();
+        
+        // Grow the instance buffer when needed; otherwise reuse it
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Sprite Instance Buffer"),
+                size: (self.instance_capacity * std::mem::size_of::<SpriteInstance>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        if let Some(instance_buffer) = &self.instance_buffer {
+            queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(&instances));
+        }
+    }
+    
+    /// Draw calls the next render will issue, one per run of sprites sharing a texture
+    pub fn draw_count(&self) -> u32 {
+        self.draws.len() as u32
+    }
+    
+    /// Draw the sprites uploaded by the last prepare
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a BindGroup) {
+        let instance_buffer = match &self.instance_buffer {
+            Some(instance_buffer) if !self.draws.is_empty() => instance_buffer,
+            _ => return,
+        };
+        
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.quad_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        
+        for (texture, range) in &self.draws {
+            let bind_group = texture
+                .and_then(|name| self.texture_bind_groups.get(name))
+                .unwrap_or(&self.white_bind_group);
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, range.clone());
+        }
+    }
+}
+
+// Unit quad centered on the origin; instances scale, rotate and move it
+const QUAD_VERTICES: [QuadVertex; 4] = [
+    QuadVertex { position: [-0.5, -0.5], tex_coords: [0.0, 1.0] },
+    QuadVertex { position: [0.5, -0.5], tex_coords: [1.0, 1.0] },
+    QuadVertex { position: [0.5, 0.5], tex_coords: [1.0, 0.0] },
+    QuadVertex { position: [-0.5, 0.5], tex_coords: [0.0, 0.0] },
+];
+
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+/// White ring touching the texture edges, with a one pixel soft edge inside and out
+fn ring_pixels() -> Vec<u8> {
+    let size = RING_TEXTURE_SIZE as f32;
+    let radius = size * 0.5;
+    let pixel = 1.0 / radius;
+    
+    let mut pixels = Vec::with_capacity((RING_TEXTURE_SIZE * RING_TEXTURE_SIZE * 4) as usize);
+    for y in 0..RING_TEXTURE_SIZE {
+        for x in 0..RING_TEXTURE_SIZE {
+            let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - Vec2::splat(radius);
+            let distance = offset.length() / radius;
+            let outer = ((1.0 - distance) / pixel).clamp(0.0, 1.0);
+            let inner = ((distance - (1.0 - RING_THICKNESS)) / pixel).clamp(0.0, 1.0);
+            pixels.extend_from_slice(&[255, 255, 255, (outer.min(inner) * 255.0) as u8]);
+        }
+    }
+    pixels
+}
+
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Sprite Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
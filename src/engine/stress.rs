@@ -0,0 +1,238 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::CommandQueue;
+use glam::Vec2;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use super::init_game_world;
+use super::profiler;
+use crate::ecs::components::{Owner, Transform, Unit, UnitType};
+use crate::ecs::network_id::{assign_network_ids, network_ids};
+use crate::ecs::resources::{GameMap, GameTime, PlayerResources, TechState, TICK_RATE};
+use crate::engine::input::Command;
+use crate::game::data;
+use crate::game::map::{self, MapGenerationParams};
+use crate::game::pathfinding::{self, PATH_GRID_SIZE};
+use crate::game::simulation::Simulation;
+use crate::game::units::{self, UnitSpawnParams};
+
+/// Unit types an army is made of, repeated in this order
+const ARMY_MIX: [UnitType; 5] = [UnitType::Soldier, UnitType::Soldier, UnitType::Soldier, UnitType::Tank, UnitType::Healer];
+
+/// Distance between neighbouring units when the armies are drawn up
+const ARMY_SPACING: f32 = 12.0;
+
+/// Ticks between each side being ordered to attack-move on the other's position again
+const ORDER_INTERVAL: u64 = TICK_RATE as u64 * 5;
+
+/// Systems listed in the report, slowest first
+const REPORTED_SYSTEMS: usize = 8;
+
+/// A headless battle to measure simulation performance with
+#[derive(Debug, Clone)]
+pub struct StressParams {
+    pub units: usize,   // Player 1's army
+    pub enemies: usize, // Player 2's army
+    pub map_size: u32,  // Cells across and down
+    pub ticks: u64,
+    pub seed: u64,
+}
+
+impl Default for StressParams {
+    fn default() -> Self {
+        Self {
+            units: 200,
+            enemies: 200,
+            map_size: 128,
+            ticks: TICK_RATE as u64 * 60,
+            seed: 1,
+        }
+    }
+}
+
+/// How long ticks took, overall and per system
+pub struct StressReport {
+    pub ticks: Vec<Duration>,
+    pub systems: BTreeMap<String, Vec<Duration>>,
+    pub survivors: BTreeMap<u8, usize>, // Player ID -> units left at the end
+}
+
+impl StressReport {
+    /// Write the report to stdout. This is the stress command's output, not a log line, so it goes
+    /// there on purpose rather than through tracing, where filters and the log file would take it
+    pub fn print(&self) {
+        println!("{} ticks", self.ticks.len());
+        for (player_id, survivors) in &self.survivors {
+            println!("  Player {}: {} units left", player_id + 1, survivors);
+        }
+        
+        println!("Tick time: {}", summarize(&self.ticks));
+        
+        let mut systems: Vec<(&String, &Vec<Duration>)> = self.systems.iter().collect();
+        systems.sort_by_key(|(_, times)| std::cmp::Reverse(percentile(times, 0.99)));
+        for (name, times) in systems.into_iter().take(REPORTED_SYSTEMS) {
+            println!("  {}: {}", name, summarize(times));
+        }
+    }
+}
+
+/// Spawn the armies, have them fight it out for the given number of ticks and time every tick
+pub fn run(params: &StressParams) -> Result<StressReport> {
+    data::init(data::DEFAULT_DATA_DIR)?;
+    
+    let mut battle = StressBattle::new(params);
+    profiler::set_enabled(true);
+    let mut report = StressReport { ticks: Vec::new(), systems: BTreeMap::new(), survivors: BTreeMap::new() };
+    for _ in 0..params.ticks {
+        report.ticks.push(battle.tick());
+        for (name, time) in profiler::system_times() {
+            report.systems.entry(name).or_default().push(time);
+        }
+    }
+    profiler::set_enabled(false);
+    
+    report.survivors = battle.survivors();
+    Ok(report)
+}
+
+/// Both armies on their map, ordered to attack each other's side every ORDER_INTERVAL ticks.
+/// The benchmarks drive one of these too, to time single systems mid-battle.
+pub struct StressBattle {
+    simulation: Simulation,
+    fronts: [(u8, Vec2); 2], // Where each side attacks
+    tick: u64,
+}
+
+impl StressBattle {
+    /// Draw up the armies; the game data must already be loaded
+    pub fn new(params: &StressParams) -> Self {
+        let map_width = params.map_size as f32 * PATH_GRID_SIZE;
+        Self {
+            simulation: Simulation::new(build_world(params)),
+            fronts: [
+                (0u8, Vec2::new(map_width * 0.8, map_width * 0.5)),
+                (1u8, Vec2::new(map_width * 0.2, map_width * 0.5)),
+            ],
+            tick: 0,
+        }
+    }
+    
+    /// Run one tick, re-issuing the attack orders when they're due, and return how long it took
+    pub fn tick(&mut self) -> Duration {
+        let mut commands: HashMap<u8, Vec<Command>> = HashMap::new();
+        if self.tick % ORDER_INTERVAL == 0 {
+            for (player_id, front) in self.fronts {
+                let army = army_of(&mut self.simulation.world, player_id);
+                let army = network_ids(&self.simulation.world, &army);
+                commands.insert(player_id, vec![Command::SelectUnits(army), Command::Attack(front)]);
+            }
+        }
+        self.tick += 1;
+        
+        let start = Instant::now();
+        self.simulation.tick(&commands);
+        start.elapsed()
+    }
+    
+    /// Units each side has left
+    pub fn survivors(&mut self) -> BTreeMap<u8, usize> {
+        self.fronts
+            .iter()
+            .map(|&(player_id, _)| (player_id, army_of(&mut self.simulation.world, player_id).len()))
+            .collect()
+    }
+}
+
+/// A map of the given size with both armies drawn up on opposite sides, skipping ground they can't stand on
+fn build_world(params: &StressParams) -> World {
+    let mut world = init_game_world();
+    world.insert_resource(GameTime { seed: params.seed, ..GameTime::default() });
+    world.insert_resource(map::generate_map(&MapGenerationParams {
+        width: params.map_size,
+        height: params.map_size,
+        seed: params.seed,
+        ..MapGenerationParams::default()
+    }));
+    world.insert_resource(PlayerResources::default());
+    world.insert_resource(TechState::default());
+    
+    let map_width = params.map_size as f32 * PATH_GRID_SIZE;
+    let armies = [
+        (0u8, params.units, Vec2::new(map_width * 0.2, map_width * 0.5)),
+        (1u8, params.enemies, Vec2::new(map_width * 0.8, map_width * 0.5)),
+    ];
+    
+    let mut queue = CommandQueue::default();
+    {
+        let game_map = world.resource::<GameMap>();
+        let tech_state = world.resource::<TechState>();
+        let mut commands = Commands::new(&mut queue, &world);
+        for (player_id, count, center) in armies {
+            let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+            let mut spawned = 0;
+            let mut slot = 0;
+            
+            // Give up on slots once they run well past the army's square, so a flooded map can't stall this
+            while spawned < count && slot < count * 4 {
+                let (row, column) = (slot / columns, slot % columns);
+                let offset = Vec2::new(column as f32 - columns as f32 / 2.0, row as f32 - columns as f32 / 2.0);
+                let position = center + offset * ARMY_SPACING;
+                slot += 1;
+                
+                let standable = game_map
+                    .pathfinding_grid
+                    .as_ref()
+                    .map_or(true, |grid| pathfinding::is_position_walkable(position, grid, PATH_GRID_SIZE));
+                if !standable {
+                    continue;
+                }
+                units::spawn_unit(
+                    &mut commands,
+                    UnitSpawnParams { unit_type: ARMY_MIX[spawned % ARMY_MIX.len()], owner: player_id, position },
+                    tech_state,
+                );
+                spawned += 1;
+            }
+        }
+    }
+    queue.apply(&mut world);
+    
+    // Orders name units by network ID, so they need theirs before the first tick
+    assign_network_ids(&mut world);
+    world
+}
+
+/// A player's units still standing, sorted so every run orders them alike
+fn army_of(world: &mut World, player_id: u8) -> Vec<Entity> {
+    let mut army: Vec<Entity> = world
+        .query_filtered::<(Entity, &Owner), (With<Unit>, With<Transform>)>()
+        .iter(world)
+        .filter(|(_, owner)| owner.0 == player_id)
+        .map(|(entity, _)| entity)
+        .collect();
+    army.sort();
+    army
+}
+
+/// Time below which the given share of the samples fall
+fn percentile(times: &[Duration], share: f64) -> Duration {
+    let mut sorted = times.to_vec();
+    sorted.sort();
+    match sorted.len() {
+        0 => Duration::ZERO,
+        len => sorted[((len - 1) as f64 * share).round() as usize],
+    }
+}
+
+/// e.g. "p50 1.20 ms, p90 2.05 ms, p99 4.81 ms, max 7.33 ms"
+fn summarize(times: &[Duration]) -> String {
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    format!(
+        "p50 {:.2} ms, p90 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+        ms(percentile(times, 0.5)),
+        ms(percentile(times, 0.9)),
+        ms(percentile(times, 0.99)),
+        ms(percentile(times, 1.0)),
+    )
+}
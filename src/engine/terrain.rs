@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, TextureFormat};
+
+use crate::ecs::resources::{GameMap, TerrainTile};
+use crate::engine::assets::TextureAsset;
+
+/// Number of tiles along each side of a terrain chunk
+const CHUNK_SIZE: u32 = 32;
+
+/// World units covered by one terrain tile
+pub const TILE_SIZE: f32 = 1.0;
+
+/// Draw order of terrain types; each type is drawn with its own texture
+const TERRAIN_TYPES: [TerrainTile; 4] = [
+    TerrainTile::Ground,
+    TerrainTile::Water,
+    TerrainTile::Mountain,
+    TerrainTile::Forest,
+];
+
+// Vertex format for terrain meshes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TerrainVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+/// GPU mesh for a square block of tiles, with indices grouped by terrain type
+struct TerrainChunk {
+    tiles: Vec<TerrainTile>, // Tiles the mesh was built from, used to spot changes
+    vertex_buffer: Option<Buffer>,
+    index_buffer: Option<Buffer>,
+    index_ranges: HashMap<TerrainTile, Range<u32>>,
+    bounds_min: Vec2,
+    bounds_max: Vec2,
+    dirty: bool,
+}
+
+/// Renders the map's terrain as chunked tile meshes
+pub struct TerrainRenderer {
+    pipeline: RenderPipeline,
+    texture_bind_group_layout: BindGroupLayout,
+    texture_bind_groups: HashMap<TerrainTile, BindGroup>,
+    textured: HashSet<TerrainTile>,
+    chunks: Vec<TerrainChunk>,
+    chunks_x: u32,
+    map_size: (u32, u32),
+}
+
+impl TerrainRenderer {
+    pub fn new(device: &Device, queue: &Queue, format: TextureFormat, camera_bind_group_layout: &BindGroupLayout) -> Self {
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Terrain Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Terrain Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/shaders/terrain.wgsl").into()),
+        });
+        
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Terrain Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Terrain Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<TerrainVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            // Position
+                            wgpu::VertexAttribute {
+                                offset: 0,
+                                shader_location: 0,
+                                format: wgpu::VertexFormat::Float32x2,
+                            },
+                            // Texture coordinates
+                            wgpu::VertexAttribute {
+                                offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                                shader_location: 1,
+                                format: wgpu::VertexFormat::Float32x2,
+                            },
+                            // Color
+                            wgpu::VertexAttribute {
+                                offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                                shader_location: 2,
+                                format: wgpu::VertexFormat::Float32x4,
+                            },
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        
+        // Until real textures are loaded every terrain type samples a plain white texture
+        // and gets its color from the vertices
+        let white_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Terrain Fallback Texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            &[255, 255, 255, 255],
+        );
+        let white_view = white_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let white_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        
+        let mut texture_bind_groups = HashMap::new();
+        for terrain in TERRAIN_TYPES {
+            texture_bind_groups.insert(
+                terrain,
+                create_texture_bind_group(device, &texture_bind_group_layout, &white_view, &white_sampler),
+            );
+        }
+        
+        Self {
+            pipeline,
+            texture_bind_group_layout,
+            texture_bind_groups,
+            textured: HashSet::new(),
+            chunks: Vec::new(),
+            chunks_x: 0,
+            map_size: (0, 0),
+        }
+    }
+    
+    /// Use a loaded texture for a terrain type
+    pub fn set_texture(&mut self, device: &Device, terrain: TerrainTile, texture: &TextureAsset) {
+        let bind_group = create_texture_bind_group(device, &self.texture_bind_group_layout, &texture.view, &texture.sampler);
+        self.texture_bind_groups.insert(terrain, bind_group);
+        self.textured.insert(terrain);
+        
+        // Vertex colors depend on whether a type is textured
+        self.mark_all_dirty();
+    }
+    
+    /// Force the chunk containing a tile to be rebuilt on the next update
+    pub fn mark_tile_dirty(&mut self, tile_x: u32, tile_y: u32) {
+        let index = (tile_y / CHUNK_SIZE * self.chunks_x + tile_x / CHUNK_SIZE) as usize;
+        if let Some(chunk) = self.chunks.get_mut(index) {
+            chunk.dirty = true;
+        }
+    }
+    
+    /// Force every chunk to be rebuilt on the next update
+    pub fn mark_all_dirty(&mut self) {
+        for chunk in &mut self.chunks {
+            chunk.dirty = true;
+        }
+    }
+    
+    /// Bring chunk meshes in line with the map, rebuilding only chunks whose tiles changed
+    pub fn update(&mut self, device: &Device, map: &GameMap) {
+        if self.map_size != (map.width, map.height) {
+            self.create_chunks(map.width, map.height);
+        }
+        
+        for chunk_index in 0..self.chunks.len() {
+            let chunk_x = chunk_index as u32 % self.chunks_x;
+            let chunk_y = chunk_index as u32 / self.chunks_x;
+            let tiles = chunk_tiles(map, chunk_x, chunk_y);
+            
+            let chunk = &mut self.chunks[chunk_index];
+            if !chunk.dirty && chunk.tiles == tiles {
+                continue;
+            }
+            
+            chunk.tiles = tiles;
+            build_chunk_mesh(device, chunk, map.width, chunk_x, chunk_y, &self.textured);
+            chunk.dirty = false;
+        }
+    }
+    
+    /// Draw the chunks that overlap the visible world rectangle; returns the number of draw calls issued
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a BindGroup,
+        view_min: Vec2,
+        view_max: Vec2,
+    ) -> u32 {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        
+        let visible_chunks: Vec<&TerrainChunk> = self.chunks
+            .iter()
+            .filter(|chunk| {
+                chunk.bounds_max.x >= view_min.x && chunk.bounds_min.x <= view_max.x &&
+                chunk.bounds_max.y >= view_min.y && chunk.bounds_min.y <= view_max.y
+            })
+            .collect();
+        
+        // Group draws by terrain type so each texture is bound once
+        let mut draw_calls = 0;
+        for terrain in TERRAIN_TYPES {
+            render_pass.set_bind_group(1, &self.texture_bind_groups[&terrain], &[]);
+            
+            for chunk in &visible_chunks {
+                let (vertex_buffer, index_buffer) = match (&chunk.vertex_buffer, &chunk.index_buffer) {
+                    (Some(vertex_buffer), Some(index_buffer)) => (vertex_buffer, index_buffer),
+                    _ => continue,
+                };
+                let range = match chunk.index_ranges.get(&terrain) {
+                    Some(range) if !range.is_empty() => range.clone(),
+                    _ => continue,
+                };
+                
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(range, 0, 0..1);
+                draw_calls += 1;
+            }
+        }
+        draw_calls
+    }
+    
+    fn create_chunks(&mut self, width: u32, height: u32) {
+        self.chunks_x = (width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunks_y = (height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        self.map_size = (width, height);
+        
+        self.chunks = (0..chunks_y)
+            .flat_map(|chunk_y| (0..self.chunks_x).map(move |chunk_x| (chunk_x, chunk_y)))
+            .map(|(chunk_x, chunk_y)| {
+                let start_x = chunk_x * CHUNK_SIZE;
+                let start_y = chunk_y * CHUNK_SIZE;
+                let end_x = (start_x + CHUNK_SIZE).min(width);
+                let end_y = (start_y + CHUNK_SIZE).min(height);
+                
+                TerrainChunk {
+                    tiles: Vec::new(),
+                    vertex_buffer: None,
+                    index_buffer: None,
+                    index_ranges: HashMap::new(),
+                    bounds_min: Vec2::new(start_x as f32, start_y as f32) * TILE_SIZE,
+                    bounds_max: Vec2::new(end_x as f32, end_y as f32) * TILE_SIZE,
+                    dirty: true,
+                }
+            })
+            .collect();
+    }
+}
+
+/// Flat color for a terrain type when it has no texture
+fn terrain_color(terrain: TerrainTile) -> [f32; 4] {
+    match terrain {
+        TerrainTile::Ground => [0.45, 0.55, 0.3, 1.0],  // Grass green
+        TerrainTile::Water => [0.15, 0.35, 0.7, 1.0],   // Blue
+        TerrainTile::Mountain => [0.5, 0.45, 0.4, 1.0], // Gray brown
+        TerrainTile::Forest => [0.15, 0.4, 0.15, 1.0],  // Dark green
+    }
+}
+
+/// Copy the tiles belonging to a chunk out of the map, row by row
+fn chunk_tiles(map: &GameMap, chunk_x: u32, chunk_y: u32) -> Vec<TerrainTile> {
+    let start_x = chunk_x * CHUNK_SIZE;
+    let start_y = chunk_y * CHUNK_SIZE;
+    let end_x = (start_x + CHUNK_SIZE).min(map.width);
+    let end_y = (start_y + CHUNK_SIZE).min(map.height);
+    
+    let mut tiles = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
+    for y in start_y..end_y {
+        let row_start = (y * map.width + start_x) as usize;
+        let row_end = (y * map.width + end_x) as usize;
+        match map.terrain_tiles.get(row_start..row_end) {
+            Some(row) => tiles.extend_from_slice(row),
+            // Map without terrain data renders as plain ground
+            None => tiles.extend(std::iter::repeat(TerrainTile::Ground).take((end_x - start_x) as usize)),
+        }
+    }
+    tiles
+}
+
+/// Rebuild a chunk's vertex and index buffers from its cached tiles
+fn build_chunk_mesh(
+    device: &Device,
+    chunk: &mut TerrainChunk,
+    map_width: u32,
+    chunk_x: u32,
+    chunk_y: u32,
+    textured: &HashSet<TerrainTile>,
+) {
+    let start_x = chunk_x * CHUNK_SIZE;
+    let start_y = chunk_y * CHUNK_SIZE;
+    let row_length = (start_x + CHUNK_SIZE).min(map_width) - start_x;
+    
+    let mut vertices = Vec::with_capacity(chunk.tiles.len() * 4);
+    let mut indices: Vec<u16> = Vec::with_capacity(chunk.tiles.len() * 6);
+    chunk.index_ranges.clear();
+    
+    for terrain in TERRAIN_TYPES {
+        let color = if textured.contains(&terrain) {
+            [1.0, 1.0, 1.0, 1.0]
+        } else {
+            terrain_color(terrain)
+        };
+        let range_start = indices.len() as u32;
+        
+        for (i, tile) in chunk.tiles.iter().enumerate() {
+            if *tile != terrain {
+                continue;
+            }
+            
+            let x = (start_x + i as u32 % row_length) as f32 * TILE_SIZE;
+            let y = (start_y + i as u32 / row_length) as f32 * TILE_SIZE;
+            let base = vertices.len() as u16;
+            
+            vertices.push(TerrainVertex { position: [x, y], tex_coords: [0.0, 1.0], color });
+            vertices.push(TerrainVertex { position: [x + TILE_SIZE, y], tex_coords: [1.0, 1.0], color });
+            vertices.push(TerrainVertex { position: [x + TILE_SIZE, y + TILE_SIZE], tex_coords: [1.0, 0.0], color });
+            vertices.push(TerrainVertex { position: [x, y + TILE_SIZE], tex_coords: [0.0, 0.0], color });
+            
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        
+        chunk.index_ranges.insert(terrain, range_start..indices.len() as u32);
+    }
+    
+    if indices.is_empty() {
+        chunk.vertex_buffer = None;
+        chunk.index_buffer = None;
+        return;
+    }
+    
+    chunk.vertex_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Terrain Chunk Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    }));
+    chunk.index_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Terrain Chunk Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    }));
+}
+
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Terrain Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
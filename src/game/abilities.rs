@@ -0,0 +1,388 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use rhai::Dynamic;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+use crate::ecs::components::{
+    ActiveBuffs, Buff, BuffStat, CastOrder, CastTarget, Caster, Collider, Movement, NetworkId, Owner, Selectable,
+    Summoned, Transform, Unit, UnitType,
+};
+use crate::ecs::events::DamageEvent;
+use crate::ecs::network_id::network_order;
+use crate::ecs::resources::{CombatTextEvents, CombatTextKind, GameMap, GameTime, PlayerInfo, TechState};
+use crate::ecs::spatial::SpatialGrid;
+use crate::ecs::systems::{stop_movement, walk_to};
+use crate::game::data;
+use crate::game::map;
+use crate::game::pathfinding;
+use crate::game::scripting::{self, ScriptSlot};
+use crate::game::units::{self, UnitSpawnParams};
+
+/// Energy a unit with abilities can store
+pub const MAX_ENERGY: f32 = 200.0;
+
+/// Energy a unit with abilities starts with
+pub const STARTING_ENERGY: f32 = 50.0;
+
+/// Energy restored per second
+pub const ENERGY_REGENERATION: f32 = 0.8;
+
+/// Distance from the target point summoned units appear at
+const SUMMON_SPREAD: f32 = 12.0;
+
+/// Definition of an ability, loaded from abilities.ron
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbilityData {
+    pub id: u8, // Sent in UseAbility commands
+    pub name: String,
+    pub description: String,
+    pub unit_types: Vec<UnitType>, // Unit types that can cast it
+    pub energy_cost: f32,
+    pub cooldown: f32, // Seconds
+    pub range: f32,    // Casting range for Point and Entity abilities
+    pub target: AbilityTarget,
+    pub effect: AbilityEffect,
+}
+
+/// What an ability is aimed at when cast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AbilityTarget {
+    /// A spot on the ground
+    Point,
+    /// A unit or building
+    Entity,
+    /// The caster itself; every selected caster uses it at once
+    SelfCast,
+    /// No target; takes effect around the caster
+    Untargeted,
+}
+
+/// What an ability does once cast
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum AbilityEffect {
+    /// Damage enemies within `radius` of the target, or only the target entity when the radius is 0
+    Damage { amount: f32, radius: f32 },
+    /// Restore health to allied units within `radius` of the target, or only the target entity when the radius is 0
+    Heal { amount: f32, radius: f32 },
+    /// Multiply a stat of the target unit, or the caster for self-cast abilities, for a while
+    Buff { stat: BuffStat, multiplier: f32, duration: f32 },
+    /// Call in units next to the target that leave after `duration` seconds
+    Summon { unit_type: UnitType, count: u32, duration: f32 },
+    /// Call `ability_<id>(caster, player, x, y, target)` in the ability script, for modded abilities;
+    /// `target` is the target's id, or the caster's for abilities without an entity target
+    Script,
+}
+
+impl AbilityEffect {
+    /// Whether the effect is meant for the caster's side rather than its enemies
+    pub fn is_friendly(self) -> bool {
+        !matches!(self, AbilityEffect::Damage { .. })
+    }
+}
+
+/// Why a unit can't cast an ability right now, if it can't
+pub fn cast_blocker(caster: &Caster, ability: &AbilityData) -> Option<&'static str> {
+    if caster.cooldowns.contains_key(&ability.id) {
+        Some("Ability is on cooldown")
+    } else if caster.energy < ability.energy_cost {
+        Some("Not enough energy")
+    } else {
+        None
+    }
+}
+
+/// Whether an Entity-targeted ability can be aimed at `target`: allied units for friendly effects,
+/// enemies the player can see for harmful ones
+pub fn is_valid_target(world: &World, player_id: u8, ability: &AbilityData, target: Entity) -> bool {
+    let (owner, position) = match (world.get::<Owner>(target), world.get::<Transform>(target)) {
+        (Some(owner), Some(transform)) => (owner.0, transform.position),
+        _ => return false,
+    };
+    let allied = world
+        .get_resource::<PlayerInfo>()
+        .map_or(owner == player_id, |info| info.are_allies(owner, player_id));
+    
+    if ability.effect.is_friendly() {
+        allied && world.get::<Unit>(target).map_or(false, |unit| unit.health > 0.0)
+    } else {
+        !allied && world
+            .get_resource::<GameMap>()
+            .map_or(false, |game_map| map::is_position_visible(game_map, player_id, position, pathfinding::PATH_GRID_SIZE))
+    }
+}
+
+/// Damage multiplier from a unit's buffs, if it has any
+pub fn damage_multiplier(buffs: Option<&ActiveBuffs>) -> f32 {
+    buffs.map_or(1.0, |buffs| buffs.multiplier(BuffStat::AttackDamage))
+}
+
+/// System that charges energy, counts down cooldowns and casts ordered abilities once the caster is in range
+pub fn ability_system(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    game_map: Res<GameMap>,
+    tech_state: Res<TechState>,
+    new_units: Query<(Entity, &Unit), (Added<Unit>, Without<Caster>)>,
+    mut casters: Query<(
+        Entity,
+        &Transform,
+        &Owner,
+        &mut Caster,
+        Option<&CastOrder>,
+        Option<&mut Movement>,
+        Option<&Collider>,
+    )>,
+    targets: Query<&Transform>,
+) {
+    // Units with abilities get their energy pool when they appear
+    for (entity, unit) in new_units.iter() {
+        if !data::game_data().unit_abilities(unit.unit_type).is_empty() {
+            commands.entity(entity).insert(Caster {
+                energy: STARTING_ENERGY,
+                max_energy: MAX_ENERGY,
+                cooldowns: BTreeMap::new(),
+            });
+        }
+    }
+    
+    for (entity, transform, owner, mut caster, cast_order, mut movement, collider) in casters.iter_mut() {
+        caster.energy = (caster.energy + ENERGY_REGENERATION * time.delta_time).min(caster.max_energy);
+        for remaining in caster.cooldowns.values_mut() {
+            *remaining -= time.delta_time;
+        }
+        caster.cooldowns.retain(|_, remaining| *remaining > 0.0);
+        
+        let order = match cast_order {
+            Some(order) => *order,
+            None => continue,
+        };
+        let ability = match data::game_data().ability(order.ability_id) {
+            Some(ability) => ability,
+            None => {
+                commands.entity(entity).remove::<CastOrder>();
+                continue;
+            }
+        };
+        
+        let target_position = match order.target {
+            CastTarget::Point(position) => position,
+            CastTarget::Entity(target) => match targets.get(target) {
+                Ok(target_transform) => target_transform.position,
+                Err(_) => {
+                    // Target died or was removed before the cast
+                    commands.entity(entity).remove::<CastOrder>();
+                    continue;
+                }
+            },
+            CastTarget::Caster => transform.position,
+        };
+        
+        // Walk into range first
+        if (target_position - transform.position).length() > ability.range {
+            match (movement.as_deref_mut(), game_map.grid_for(owner.0)) {
+                (Some(movement), Some(grid)) => {
+                    let radius = collider.map_or(0.0, |collider| collider.radius);
+                    walk_to(movement, transform.position, target_position, grid, radius);
+                }
+                _ => {
+                    commands.entity(entity).remove::<CastOrder>();
+                }
+            }
+            continue;
+        }
+        
+        commands.entity(entity).remove::<CastOrder>();
+        
+        // Energy or the cooldown may have gone to another cast since the order was given
+        if cast_blocker(&caster, ability).is_some() {
+            continue;
+        }
+        caster.energy -= ability.energy_cost;
+        if ability.cooldown > 0.0 {
+            caster.cooldowns.insert(ability.id, ability.cooldown);
+        }
+        if order.target != CastTarget::Caster {
+            if let Some(movement) = movement.as_deref_mut() {
+                stop_movement(movement);
+            }
+        }
+        
+        cast(&mut commands, entity, owner.0, ability, order.target, target_position, &tech_state);
+    }
+}
+
+/// Carry out an ability's effect at `position`
+fn cast(
+    commands: &mut Commands,
+    caster: Entity,
+    player_id: u8,
+    ability: &AbilityData,
+    target: CastTarget,
+    position: Vec2,
+    tech_state: &TechState,
+) {
+    match ability.effect {
+        AbilityEffect::Summon { unit_type, count, duration } => {
+            // Spread the units in a ring around the target
+            for index in 0..count {
+                let angle = index as f32 / count as f32 * std::f32::consts::TAU;
+                let params = UnitSpawnParams {
+                    unit_type,
+                    owner: player_id,
+                    position: position + Vec2::new(angle.cos(), angle.sin()) * SUMMON_SPREAD,
+                };
+                if let Some(unit) = units::spawn_unit(commands, params, tech_state) {
+                    commands.entity(unit).insert((Selectable, Summoned { remaining: duration }));
+                }
+            }
+        }
+        AbilityEffect::Script => {
+            let function = format!("ability_{}", ability.id);
+            let target = match target {
+                CastTarget::Entity(target) => target,
+                _ => caster,
+            };
+            commands.add(move |world: &mut World| {
+                // Scripts know units by their network IDs; -1 for one that hasn't got its ID yet
+                let id = |entity: Entity| world.get::<NetworkId>(entity).map_or(-1, |id| id.0 as i64);
+                let args = vec![
+                    Dynamic::from(id(caster)),
+                    Dynamic::from(player_id as i64),
+                    Dynamic::from(position.x as f64),
+                    Dynamic::from(position.y as f64),
+                    Dynamic::from(id(target)),
+                ];
+                scripting::call_script(world, ScriptSlot::Abilities, &function, args);
+            });
+        }
+        effect => {
+            let target = match target {
+                CastTarget::Entity(target) => target,
+                _ => caster,
+            };
+            commands.add(move |world: &mut World| apply_effect(world, caster, player_id, effect, target, position));
+        }
+    }
+}
+
+/// Apply a damage, heal or buff effect to whatever it reaches
+fn apply_effect(world: &mut World, caster: Entity, player_id: u8, effect: AbilityEffect, target: Entity, position: Vec2) {
+    match effect {
+        AbilityEffect::Damage { amount, radius } => {
+            for entity in affected_entities(world, player_id, target, position, radius, false) {
+                damage_entity(world, caster, player_id, entity, amount);
+            }
+        }
+        AbilityEffect::Heal { amount, radius } => {
+            for entity in affected_entities(world, player_id, target, position, radius, true) {
+                let healed = match world.get_mut::<Unit>(entity) {
+                    Some(mut unit) if unit.health > 0.0 => {
+                        let healed = amount.min(unit.max_health - unit.health);
+                        unit.health += healed;
+                        healed
+                    }
+                    _ => continue,
+                };
+                if let Some(position) = world.get::<Transform>(entity).map(|transform| transform.position) {
+                    world.get_resource_or_insert_with(CombatTextEvents::default).push(
+                        CombatTextKind::Heal,
+                        entity,
+                        position,
+                        healed,
+                    );
+                }
+            }
+        }
+        AbilityEffect::Buff { stat, multiplier, duration } => {
+            if world.get::<Unit>(target).map_or(true, |unit| unit.health <= 0.0) {
+                return;
+            }
+            
+            // Casting again refreshes a buff rather than stacking it
+            let buff = Buff { stat, multiplier, remaining: duration };
+            match world.get_mut::<ActiveBuffs>(target) {
+                Some(mut active) => {
+                    active.buffs.retain(|existing| existing.stat != stat);
+                    active.buffs.push(buff);
+                }
+                None => {
+                    world.entity_mut(target).insert(ActiveBuffs { buffs: vec![buff] });
+                }
+            }
+        }
+        AbilityEffect::Summon { .. } | AbilityEffect::Script => {
+            // Summoned units are spawned and scripts called when the ability is cast
+        }
+    }
+}
+
+/// Units and buildings an effect reaches: allies (or enemies) of the caster within `radius` of the
+/// position, or just the target entity without a radius
+fn affected_entities(world: &World, player_id: u8, target: Entity, position: Vec2, radius: f32, allies: bool) -> Vec<Entity> {
+    let candidates = if radius > 0.0 {
+        world
+            .get_resource::<SpatialGrid>()
+            .map(|spatial_grid| spatial_grid.query_radius(position, radius).into_iter().map(|entry| entry.entity).collect())
+            .unwrap_or_default()
+    } else {
+        vec![target]
+    };
+    
+    let player_info = world.get_resource::<PlayerInfo>();
+    let mut entities: Vec<Entity> = candidates
+        .into_iter()
+        .filter(|&entity| {
+            world.get::<Owner>(entity).map_or(false, |owner| {
+                let allied = player_info.map_or(owner.0 == player_id, |info| info.are_allies(owner.0, player_id));
+                allied == allies
+            })
+        })
+        .collect();
+    
+    // Grid order isn't guaranteed; sort so every machine applies the effect in the same order
+    entities.sort_by_key(|&entity| network_order(world, entity));
+    entities
+}
+
+/// Deal ability damage to a unit or building; it goes through the damage system like weapon hits,
+/// so the caster is credited with the kill
+fn damage_entity(world: &mut World, caster: Entity, player_id: u8, target: Entity, amount: f32) {
+    if world.get::<Owner>(target).is_none() {
+        return;
+    }
+    
+    world.send_event(DamageEvent {
+        attacker: caster,
+        attacker_owner: Some(player_id),
+        target,
+        amount,
+        damage_type: None,
+    });
+}
+
+/// System that counts down ability buffs and removes summoned units whose time is up
+pub fn ability_timer_system(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    mut buffed: Query<(Entity, &mut ActiveBuffs)>,
+    mut summoned: Query<(Entity, &mut Summoned)>,
+) {
+    for (entity, mut active) in buffed.iter_mut() {
+        for buff in active.buffs.iter_mut() {
+            buff.remaining -= time.delta_time;
+        }
+        active.buffs.retain(|buff| buff.remaining > 0.0);
+        
+        if active.buffs.is_empty() {
+            commands.entity(entity).remove::<ActiveBuffs>();
+        }
+    }
+    
+    for (entity, mut summon) in summoned.iter_mut() {
+        summon.remaining -= time.delta_time;
+        if summon.remaining <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -1,23 +1,163 @@
 use bevy_ecs::prelude::*;
+use bevy_tasks::{ComputeTaskPool, TaskPool};
 use glam::Vec2;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use tracing::debug;
 
-use crate::ecs::components::{UnitType, BuildingType, ResourceType, Transform, Owner, Unit, Building};
-use crate::ecs::resources::{GameMap, PlayerResources};
+use crate::ecs::components::{UnitType, BuildingType, ResourceType, Transform, Owner, Unit, Building, NetworkId};
+use crate::ecs::network_id::network_ids;
+use crate::ecs::resources::{GameMap, PlayerInfo, PlayerResources, NEUTRAL_PLAYER};
+use crate::ecs::systems::combat::{get_unit_armor_type, DamageTable};
 use crate::engine::input::Command;
+use crate::game::buildings::{self, BuildingData};
+use crate::game::data;
+use crate::game::map::is_position_visible;
+use crate::game::pathfinding::PATH_GRID_SIZE;
+use crate::game::threat::{combat_strength, ThreatMap, ThreatMaps};
+
+/// Extra share of every drop-off a Hard computer player can be given, as offered in the game setup
+pub const RESOURCE_BONUS_OPTIONS: [f32; 4] = [0.0, 0.25, 0.5, 1.0];
+
+/// Fewest units a squad gathered at home sets out to attack with
+const MIN_ATTACK_SQUAD_SIZE: usize = 3;
+
+/// Distance around a squad's middle within which known enemies count against it
+const SQUAD_ENGAGE_RADIUS: f32 = 160.0;
+
+/// How much stronger the enemies around a squad may be before it falls back home
+const RETREAT_RATIO: f32 = 1.3;
+
+/// Distance from a waypoint at which a squad counts as having reached it
+const WAYPOINT_RADIUS: f32 = 48.0;
+
+/// Distance from home at which a retreating squad rejoins the one gathering there
+const HOME_RADIUS: f32 = 96.0;
+
+/// Distance beyond a slower enemy's reach a kiting unit keeps clear of
+const KITE_MARGIN: f32 = 16.0;
+
+/// How far a kiting unit steps back from the enemy closing in
+const KITE_DISTANCE: f32 = 48.0;
+
+/// Distance from home, in tiles, the placement planner looks for building sites within
+const BASE_RADIUS: i32 = 24;
+
+/// Tiles kept free around every building, so units can still walk between them
+const BUILDING_GAP: f32 = 1.0;
+
+/// Mineral nodes this close to home make up the base's mineral line
+const MINERAL_LINE_RADIUS: f32 = 16.0;
+
+/// Distance buildings keep from the lanes workers walk between the headquarters and the minerals
+const MINING_LANE_CLEARANCE: f32 = 2.0;
+
+/// How far past the mineral line production buildings go, where attackers reach them last
+const BEHIND_MINERALS: f32 = 6.0;
+
+/// How far out toward the most threatened approach defense towers go
+const TOWER_DISTANCE: f32 = 12.0;
+
+/// Distance to the nearest other supply depot beyond which spreading them out stops mattering
+const SUPPLY_SPREAD: f32 = 10.0;
+
+/// Seconds between scout missions
+const SCOUT_INTERVAL: f32 = 30.0;
+
+/// Seconds an enemy unit still counts toward the army estimate after it was last seen
+const UNIT_MEMORY: f32 = 120.0;
+
+/// Scouted enemy combat units needed before the estimate is trusted enough to counter
+const MIN_SCOUTED_UNITS: u32 = 3;
+
+/// Chance a military unit is picked to counter the scouted army rather than by personality
+const COUNTER_BIAS: f64 = 0.6;
+
+/// Military units the AI chooses between when countering
+const COUNTER_CANDIDATES: [UnitType; 3] = [UnitType::Soldier, UnitType::Scout, UnitType::Tank];
 
 /// AI difficulty level
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AiDifficulty {
     Easy,
     Medium,
     Hard,
 }
 
-/// AI personality type that affects strategy
+impl Default for AiDifficulty {
+    fn default() -> Self {
+        AiDifficulty::Medium
+    }
+}
+
+impl AiDifficulty {
+    /// Every difficulty, in the order the game setup lists them
+    pub const ALL: [AiDifficulty; 3] = [AiDifficulty::Easy, AiDifficulty::Medium, AiDifficulty::Hard];
+    
+    /// String key of the difficulty's name, as shown in the settings and game setup
+    pub fn name_key(self) -> &'static str {
+        match self {
+            AiDifficulty::Easy => "settings.difficulty_easy",
+            AiDifficulty::Medium => "settings.difficulty_normal",
+            AiDifficulty::Hard => "settings.difficulty_hard",
+        }
+    }
+    
+    /// How quickly a computer player of this difficulty acts, how much it may do and what it knows
+    pub fn profile(self) -> DifficultyProfile {
+        match self {
+            AiDifficulty::Easy => DifficultyProfile {
+                reaction_time: 2.0,
+                actions_per_minute: Some(20.0),
+                max_simultaneous_orders: 1,
+                first_attack_time: 480.0,
+                sees_through_fog: false,
+                target_priority: TargetPriority::Nearest,
+            },
+            AiDifficulty::Medium => DifficultyProfile {
+                reaction_time: 1.0,
+                actions_per_minute: Some(60.0),
+                max_simultaneous_orders: 3,
+                first_attack_time: 240.0,
+                sees_through_fog: false,
+                target_priority: TargetPriority::Nearest,
+            },
+            AiDifficulty::Hard => DifficultyProfile {
+                reaction_time: 0.5,
+                actions_per_minute: None,
+                max_simultaneous_orders: 8,
+                first_attack_time: 0.0,
+                sees_through_fog: true,
+                target_priority: TargetPriority::HighValue,
+            },
+        }
+    }
+}
+
+/// What sets the difficulties apart; every difficulty follows the same strategy for its personality
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultyProfile {
+    pub reaction_time: f32,              // Seconds between decisions
+    pub actions_per_minute: Option<f32>, // Orders issued a minute on average; None is uncapped
+    pub max_simultaneous_orders: u32,    // Orders issued at once, however long the AI has held back
+    pub first_attack_time: f32,          // Seconds into the game before the first attack
+    pub sees_through_fog: bool,          // Targets enemies none of its units can see
+    pub target_priority: TargetPriority,
+}
+
+/// How the AI picks what to attack
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetPriority {
+    /// Whatever enemy is closest to home
+    Nearest,
+    /// Workers, production and damaged targets first, closer ones breaking ties
+    HighValue,
+}
+
+/// AI personality type that affects strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AiPersonality {
     Rusher,    // Aggressive early game
     Boomer,    // Economy focused
@@ -25,18 +165,251 @@ pub enum AiPersonality {
     Balanced,  // Mix of strategies
 }
 
+/// AI slot chosen in the game setup menu
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AiSlotConfig {
+    pub player_id: u8,
+    pub difficulty: AiDifficulty,
+    pub personality: AiPersonality,
+    #[serde(default)]
+    pub team: Option<u8>, // None plays for itself
+    #[serde(default)]
+    pub resource_bonus: f32, // Extra share of every drop-off, such as 0.25 for a quarter more
+}
+
+/// A computer player's brain. Each update it is handed what its player can see of the game and
+/// answers with the commands to issue, exactly as a human player's input would be. Implement it to
+/// write a custom bot and register it for a slot with `AiPlayers::register_bot`.
+pub trait AiPlayer: Send {
+    /// Decide what to do; called every frame, `delta_time` seconds after the last call
+    fn update(&mut self, snapshot: &WorldSnapshot, delta_time: f32) -> Vec<Command>;
+    
+    /// Whether the snapshot should include enemies outside the player's sight; only for
+    /// deliberately cheating opponents
+    fn sees_through_fog(&self) -> bool {
+        false
+    }
+}
+
+/// Builds a custom bot for a slot from the slot's settings and the game seed
+pub type BotFactory = Box<dyn Fn(&AiSlotConfig, u64) -> Box<dyn AiPlayer>>;
+
+/// A unit as a bot sees it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitInfo {
+    pub entity: Entity,
+    pub network_id: NetworkId, // For SelectUnits and RepairBuilding commands
+    pub owner: u8,
+    pub unit_type: UnitType,
+    pub position: Vec2,
+    pub health: f32,
+    pub max_health: f32,
+}
+
+/// A building as a bot sees it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuildingInfo {
+    pub entity: Entity,
+    pub network_id: NetworkId,
+    pub owner: u8,
+    pub building_type: BuildingType,
+    pub position: Vec2,
+    pub health: f32,
+    pub max_health: f32,
+    pub finished: bool, // False while still under construction
+}
+
+/// What one player can see of the game at a moment, handed to its `AiPlayer`. Other players'
+/// units and buildings outside the player's and its allies' sight are left out, as they are from
+/// a human player's screen; the map's layout and everyone's starting positions are common knowledge.
+pub struct WorldSnapshot<'w> {
+    pub player_id: u8,
+    pub elapsed_time: f32,                            // Seconds since the game started
+    pub allies: Vec<u8>,                              // Players on the same team, not including this one
+    pub resources: HashMap<ResourceType, f32>,        // The player's stockpile
+    pub own_units: Vec<UnitInfo>,
+    pub own_buildings: Vec<BuildingInfo>,
+    pub visible_units: Vec<UnitInfo>,                 // Everyone else's in sight: allies, enemies and creeps
+    pub visible_buildings: Vec<BuildingInfo>,
+    pub map_size: (u32, u32),                         // In tiles
+    pub starting_positions: Vec<Vec2>,                // In player order
+    pub resource_nodes: Vec<(Vec2, ResourceType)>,    // Where the map placed them; some may be mined out
+    world: &'w World,                                 // For the built-in controller, which filters what it sees itself
+}
+
+impl<'w> WorldSnapshot<'w> {
+    /// What `player_id` can see of the world; with `sees_through_fog` every unit and building is in sight
+    pub fn capture(world: &'w World, player_id: u8, elapsed_time: f32, sees_through_fog: bool) -> Self {
+        let map = world.get_resource::<GameMap>();
+        let player_info = world.get_resource::<PlayerInfo>();
+        let is_ally = |owner: u8| owner != player_id && player_info.map_or(false, |info| info.are_allies(player_id, owner));
+        
+        // Allies share what they see with us, as they do on the player's own screen
+        let visible = |position: Vec2| {
+            sees_through_fog || map.map_or(true, |map| is_position_visible(map, player_id, position, PATH_GRID_SIZE))
+        };
+        
+        let mut snapshot = Self {
+            player_id,
+            elapsed_time,
+            allies: player_info.map_or_else(Vec::new, |info| {
+                let mut allies: Vec<u8> = info.player_names.keys().copied().filter(|&other| is_ally(other)).collect();
+                allies.sort_unstable();
+                allies
+            }),
+            resources: HashMap::new(),
+            own_units: Vec::new(),
+            own_buildings: Vec::new(),
+            visible_units: Vec::new(),
+            visible_buildings: Vec::new(),
+            map_size: map.map_or((0, 0), |map| (map.width, map.height)),
+            starting_positions: map.map_or_else(Vec::new, |map| map.starting_positions.clone()),
+            resource_nodes: map.map_or_else(Vec::new, |map| {
+                map.resource_positions.iter().map(|&(position, resource_type, _)| (position, resource_type)).collect()
+            }),
+            world,
+        };
+        if let Some(player_resources) = world.get_resource::<PlayerResources>() {
+            for resource_type in [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy] {
+                let amount = player_resources.resources.get(&(player_id, resource_type)).copied().unwrap_or(0.0);
+                snapshot.resources.insert(resource_type, amount);
+            }
+        }
+        
+        for entity in world.iter_entities() {
+            let (transform, owner, network_id) = match (entity.get::<Transform>(), entity.get::<Owner>(), entity.get::<NetworkId>()) {
+                (Some(transform), Some(owner), Some(network_id)) => (transform, owner.0, *network_id),
+                _ => continue,
+            };
+            let is_own = owner == player_id;
+            if !is_own && !is_ally(owner) && !visible(transform.position) {
+                continue;
+            }
+            
+            if let Some(unit) = entity.get::<Unit>().filter(|unit| unit.health > 0.0) {
+                let info = UnitInfo {
+                    entity: entity.id(),
+                    network_id,
+                    owner,
+                    unit_type: unit.unit_type,
+                    position: transform.position,
+                    health: unit.health,
+                    max_health: unit.max_health,
+                };
+                if is_own { snapshot.own_units.push(info) } else { snapshot.visible_units.push(info) }
+            } else if let Some(building) = entity.get::<Building>().filter(|building| building.health > 0.0) {
+                let info = BuildingInfo {
+                    entity: entity.id(),
+                    network_id,
+                    owner,
+                    building_type: building.building_type,
+                    position: transform.position,
+                    health: building.health,
+                    max_health: building.max_health,
+                    finished: building.construction_progress.is_none(),
+                };
+                if is_own { snapshot.own_buildings.push(info) } else { snapshot.visible_buildings.push(info) }
+            }
+        }
+        
+        snapshot
+    }
+    
+    /// Whether a player is an enemy: anyone not us and not on our team, creeps included
+    pub fn is_enemy(&self, owner: u8) -> bool {
+        owner != self.player_id && !self.allies.contains(&owner)
+    }
+    
+    /// Enemy units in sight
+    pub fn enemy_units(&self) -> impl Iterator<Item = &UnitInfo> + '_ {
+        self.visible_units.iter().filter(move |unit| self.is_enemy(unit.owner))
+    }
+    
+    /// Enemy buildings in sight
+    pub fn enemy_buildings(&self) -> impl Iterator<Item = &BuildingInfo> + '_ {
+        self.visible_buildings.iter().filter(move |building| self.is_enemy(building.owner))
+    }
+}
+
+/// Owns one brain per AI slot and drives them every tick
+pub struct AiPlayers {
+    players: Vec<(u8, Box<dyn AiPlayer>)>, // (Player ID, brain)
+    bots: HashMap<u8, BotFactory>,         // Custom bots by player slot, used instead of the built-in controller
+}
+
+impl AiPlayers {
+    pub fn new() -> Self {
+        Self {
+            players: Vec::new(),
+            bots: HashMap::new(),
+        }
+    }
+    
+    /// Have a custom bot play the slot from the next game on, instead of the built-in controller
+    pub fn register_bot(&mut self, player_id: u8, factory: BotFactory) {
+        self.bots.insert(player_id, factory);
+    }
+    
+    /// Go back to the built-in controller for a slot
+    pub fn unregister_bot(&mut self, player_id: u8) {
+        self.bots.remove(&player_id);
+    }
+    
+    /// Create a brain for each configured AI slot, replacing any existing ones
+    pub fn setup(&mut self, slots: &[AiSlotConfig], seed: u64) {
+        self.players = slots
+            .iter()
+            .map(|slot| {
+                let player: Box<dyn AiPlayer> = match self.bots.get(&slot.player_id) {
+                    Some(factory) => factory(slot, seed),
+                    None => Box::new(AiController::new(slot.player_id, slot.difficulty, slot.personality, seed)),
+                };
+                (slot.player_id, player)
+            })
+            .collect();
+    }
+    
+    /// Remove all AI brains
+    pub fn clear(&mut self) {
+        self.players.clear();
+    }
+    
+    /// Player IDs controlled by the AI
+    pub fn player_ids(&self) -> Vec<u8> {
+        self.players.iter().map(|(player_id, _)| *player_id).collect()
+    }
+    
+    /// Update every brain with what its player can see and collect the commands they want to issue, per player.
+    /// Brains only read the world and keep their own random numbers, so they think on worker threads at once;
+    /// their commands still come back in slot order
+    pub fn update(&mut self, world: &World, elapsed_time: f32, delta_time: f32) -> Vec<(u8, Vec<Command>)> {
+        let results = ComputeTaskPool::init(TaskPool::default).scope(|scope| {
+            for (player_id, player) in self.players.iter_mut() {
+                scope.spawn(async move {
+                    let snapshot = WorldSnapshot::capture(world, *player_id, elapsed_time, player.sees_through_fog());
+                    (*player_id, player.update(&snapshot, delta_time))
+                });
+            }
+        });
+        results.into_iter().filter(|(_, commands)| !commands.is_empty()).collect()
+    }
+}
+
 /// Main AI controller for a computer player
 pub struct AiController {
     player_id: u8,
-    difficulty: AiDifficulty,
     personality: AiPersonality,
+    profile: DifficultyProfile, // From the slot's difficulty
     rng: StdRng,
+    order_budget: f32, // Orders that may be issued now, refilled at the profile's actions per minute
     
     // Strategy state
     build_order: VecDeque<AiBuildTask>,
     attack_squads: Vec<AiSquad>,
     defense_squads: Vec<AiSquad>,
     economy_state: AiEconomyState,
+    intel: EnemyIntel,
+    scout: Option<Entity>, // Unit sent on scout missions, kept out of the squads
     
     // Timers
     decision_timer: f32,
@@ -44,6 +417,17 @@ pub struct AiController {
     attack_timer: f32,
 }
 
+impl AiPlayer for AiController {
+    fn update(&mut self, snapshot: &WorldSnapshot, delta_time: f32) -> Vec<Command> {
+        // The built-in controller reads the world itself and leaves out what its difficulty may not know
+        self.think(snapshot.world, snapshot.elapsed_time, delta_time)
+    }
+    
+    fn sees_through_fog(&self) -> bool {
+        self.profile.sees_through_fog
+    }
+}
+
 #[derive(Debug, Clone)]
 enum AiBuildTask {
     BuildUnit(UnitType),
@@ -57,6 +441,20 @@ struct AiSquad {
     role: SquadRole,
     target: Option<Vec2>,
     state: SquadState,
+    waypoints: Vec<Vec2>, // Route still ahead, ending at the target or at home
+    ordered: bool,        // Whether the squad has been sent to the first waypoint
+    kiting: Vec<Entity>,  // Units stepping back from slower enemies, sent back in once clear
+}
+
+/// What a squad's commander needs to know of one of its units
+#[derive(Debug, Clone, Copy)]
+struct SquadMember {
+    entity: Entity,
+    position: Vec2,
+    dps: f32,
+    range: f32,
+    health: f32,
+    speed: f32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,6 +473,33 @@ enum SquadState {
     Retreating,
 }
 
+/// An enemy building as it was last seen
+#[derive(Debug, Clone, Copy)]
+struct SeenBuilding {
+    building_type: BuildingType,
+    position: Vec2,
+    seen_at: f32, // Game time in seconds
+}
+
+/// What a computer player remembers of its enemies from what it has seen
+#[derive(Debug, Default)]
+struct EnemyIntel {
+    buildings: HashMap<Entity, SeenBuilding>, // Kept until the spot is seen without them
+    units: HashMap<Entity, (UnitType, f32)>,  // Type and when last seen, forgotten after UNIT_MEMORY
+    starts_seen: HashMap<usize, f32>,         // Starting position index -> when it was last in sight
+}
+
+impl EnemyIntel {
+    /// Enemy combat units remembered, by type
+    fn army_estimate(&self) -> HashMap<UnitType, u32> {
+        let mut army = HashMap::new();
+        for &(unit_type, _) in self.units.values().filter(|(unit_type, _)| *unit_type != UnitType::Worker) {
+            *army.entry(unit_type).or_insert(0) += 1;
+        }
+        army
+    }
+}
+
 #[derive(Debug)]
 struct AiEconomyState {
     desired_workers: u32,
@@ -124,9 +549,10 @@ impl AiController {
         
         Self {
             player_id,
-            difficulty,
             personality,
+            profile: difficulty.profile(),
             rng,
+            order_budget: difficulty.profile().max_simultaneous_orders as f32,
             build_order: build_order.into(),
             attack_squads: Vec::new(),
             defense_squads: Vec::new(),
@@ -137,6 +563,8 @@ impl AiController {
                 current_bases: 1,
                 resource_targets: HashMap::new(),
             },
+            intel: EnemyIntel::default(),
+            scout: None,
             decision_timer: 0.0,
             scout_timer: 0.0,
             attack_timer: 0.0,
@@ -144,7 +572,7 @@ impl AiController {
     }
     
     // Main update function called each game tick
-    pub fn update(
+    fn think(
         &mut self,
         world: &World,
         elapsed_time: f32,
@@ -157,19 +585,27 @@ impl AiController {
         self.scout_timer += delta_time;
         self.attack_timer += delta_time;
         
-        // Make decisions at fixed intervals (based on difficulty)
-        let decision_interval = match self.difficulty {
-            AiDifficulty::Easy => 2.0,    // Slower decisions
-            AiDifficulty::Medium => 1.0,
-            AiDifficulty::Hard => 0.5,    // Faster decisions
+        let max_orders = self.profile.max_simultaneous_orders as f32;
+        self.order_budget = match self.profile.actions_per_minute {
+            Some(actions_per_minute) => (self.order_budget + actions_per_minute / 60.0 * delta_time).min(max_orders),
+            None => max_orders,
         };
         
-        if self.decision_timer >= decision_interval {
+        // An AI that has used up its orders waits, rather than deciding things it could not act on
+        if self.order_budget < 1.0 {
+            return commands;
+        }
+        
+        // Make decisions at fixed intervals (based on difficulty)
+        if self.decision_timer >= self.profile.reaction_time {
             self.decision_timer = 0.0;
             
             // Update economy state
             self.update_economy_state(world);
             
+            // Remember what we see of the enemy
+            self.update_intel(world, elapsed_time);
+            
             // Execute build order if possible
             if let Some(commands_to_issue) = self.process_build_order(world) {
                 commands.extend(commands_to_issue);
@@ -178,36 +614,40 @@ impl AiController {
             // Update squad assignments
             self.update_squads(world);
             
-            // Issue squad commands
-            commands.extend(self.command_squads(world));
+            // Issue squad commands with the orders the build order left over
+            let budget = (self.order_budget as usize).saturating_sub(commands.len());
+            commands.extend(self.command_squads(world, budget));
             
             // Decide on next strategic moves
             self.update_strategy(world, elapsed_time);
         }
         
-        // Check if it's time to scout
-        if self.scout_timer >= 30.0 {
+        // Check if it's time to scout, refreshing what we know
+        if self.scout_timer >= SCOUT_INTERVAL {
             self.scout_timer = 0.0;
             
-            // Find a good spot to scout
-            if let Some(scout_pos) = self.choose_scout_target(world) {
+            // Send the scout to the spot we know least about
+            if let (Some(scout_pos), Some(scout)) = (self.choose_scout_target(world), self.find_scout(world)) {
+                commands.push(Command::SelectUnits(network_ids(world, &[scout])));
                 commands.push(Command::Move(scout_pos));
             }
         }
         
-        // Check if it's time to attack
-        if self.attack_timer >= 60.0 && self.personality == AiPersonality::Rusher {
+        // Check if it's time to attack; easier computer players leave the opening minutes in peace
+        let attacks_allowed = elapsed_time >= self.profile.first_attack_time;
+        if self.attack_timer >= 60.0 && self.personality == AiPersonality::Rusher && attacks_allowed {
             self.attack_timer = 0.0;
             
-            // Launch attack if we have enough units
-            if self.attack_squads.len() > 0 {
-                // Find a target to attack
-                if let Some(target_pos) = self.choose_attack_target(world) {
-                    commands.push(Command::Attack(target_pos));
-                }
+            // Find a target and send the squad gathered at home, if it is big enough
+            if let Some(target_pos) = self.choose_attack_target(world) {
+                self.launch_attack(world, target_pos);
             }
         }
         
+        // Build orders come first, so they are the last to be held back by the order cap
+        commands.truncate(self.order_budget as usize);
+        self.order_budget -= commands.len() as f32;
+        
         commands
     }
     
@@ -231,7 +671,7 @@ impl AiController {
         let mut building_query = world.query::<(&Building, &Owner)>();
         for (building, owner) in building_query.iter(world) {
             if owner.0 == self.player_id {
-                if building.building_type == BuildingType::Headquarters {
+                if building.building_type.is_headquarters() {
                     self.economy_state.current_bases += 1;
                 }
             }
@@ -255,7 +695,7 @@ impl AiController {
     fn process_build_order(&mut self, world: &World) -> Option<Vec<Command>> {
         if self.build_order.is_empty() {
             // Generate a new task if build order is empty
-            self.generate_next_task();
+            self.generate_next_task(world);
         }
         
         // Peek at the next task
@@ -280,17 +720,19 @@ impl AiController {
                 AiBuildTask::BuildBuilding(building_type, position) => {
                     // Check if we can afford this building
                     if self.can_afford_building(*building_type, world) {
-                        // Find a position to build if none specified
-                        let build_pos = position.unwrap_or_else(|| self.find_building_position(*building_type, world));
+                        // Find a position to build if none specified; with no room, try again later
+                        let build_pos = position.or_else(|| self.find_building_position(*building_type, world))?;
+                        let builder = self.find_builder(build_pos, world)?;
+                        let commands = vec![
+                            Command::SelectUnits(network_ids(world, &[builder])),
+                            Command::BuildBuilding { building_type: *building_type, position: build_pos },
+                        ];
                         
                         // Remove the task from the queue
                         self.build_order.pop_front();
                         
-                        // Return command to build the building
-                        return Some(vec![Command::Build(crate::engine::input::BuildingCommand {
-                            building_type: *building_type as u8,
-                            position: build_pos,
-                        })]);
+                        // Return commands sending a worker to build the building
+                        return Some(commands);
                     }
                 }
                 
@@ -302,12 +744,8 @@ impl AiController {
                             // Remove the task from the queue
                             self.build_order.pop_front();
                             
-                            // Return command to research (would need a proper command for this)
-                            return Some(vec![Command::UseAbility(crate::engine::input::AbilityCommand {
-                                ability_id: *tech_type as u8,
-                                target_position: None,
-                                target_entity_id: None,
-                            })]);
+                            // Return command to research the tech
+                            return Some(vec![Command::Research(*tech_type as u8)]);
                         }
                     }
                 }
@@ -318,7 +756,7 @@ impl AiController {
     }
     
     // Generate the next strategic task
-    fn generate_next_task(&mut self) {
+    fn generate_next_task(&mut self, world: &World) {
         // Different logic based on personality and current state
         if self.economy_state.current_workers < self.economy_state.desired_workers {
             // Need more workers
@@ -327,7 +765,14 @@ impl AiController {
             // Need more bases
             self.build_order.push_back(AiBuildTask::BuildBuilding(BuildingType::Headquarters, None));
         } else {
-            // Build military
+            // Build military, often to counter what we have scouted
+            if let Some(counter) = self.counter_unit(world) {
+                if self.rng.gen_bool(COUNTER_BIAS) {
+                    self.build_order.push_back(AiBuildTask::BuildUnit(counter));
+                    return;
+                }
+            }
+            
             match self.personality {
                 AiPersonality::Rusher => {
                     // Rushers favor basic military units
@@ -416,24 +861,309 @@ impl AiController {
         None
     }
     
-    // Find a suitable position for a new building
-    fn find_building_position(&self, building_type: BuildingType, world: &World) -> Vec2 {
-        // In a real game, this would use pathfinding to find valid build locations
-        // Simplified placeholder
-        Vec2::new(100.0, 100.0)
+    // Find a site for a new building near the base that suits what the building is for
+    fn find_building_position(&self, building_type: BuildingType, world: &World) -> Option<Vec2> {
+        let map = world.get_resource::<GameMap>()?;
+        let home = self.home(world);
+        let mut existing = Vec::new();
+        let mut supply_depots = Vec::new();
+        for entity in world.iter_entities() {
+            let (building, transform) = match (entity.get::<Building>(), entity.get::<Transform>()) {
+                (Some(building), Some(transform)) => (building, transform),
+                _ => continue,
+            };
+            existing.push((transform.position, BuildingData::get(building.building_type).size));
+            if building.building_type == BuildingType::SupplyDepot &&
+                entity.get::<Owner>().map_or(false, |owner| owner.0 == self.player_id) {
+                supply_depots.push(transform.position);
+            }
+        }
+        
+        // Resource collectors have to sit on gas, so take the free gas node closest to our starting position
+        if building_type == BuildingType::ResourceCollector {
+            let mut gas: Vec<Vec2> = map.resource_positions
+                .iter()
+                .filter(|(_, resource_type, _)| *resource_type == ResourceType::Gas)
+                .map(|(position, _, _)| *position)
+                .collect();
+            gas.sort_by(|a, b| a.distance(home).partial_cmp(&b.distance(home)).unwrap_or(std::cmp::Ordering::Equal));
+            return gas
+                .into_iter()
+                .find(|&position| buildings::is_valid_placement(building_type, position, self.player_id, map, &existing));
+        }
+        
+        // Keep a lane free around everything already standing
+        let padded: Vec<(Vec2, Vec2)> = existing
+            .iter()
+            .map(|&(position, size)| (position, size + Vec2::splat(BUILDING_GAP * 2.0)))
+            .collect();
+        let minerals: Vec<Vec2> = map.resource_positions
+            .iter()
+            .filter(|(position, resource_type, _)| *resource_type == ResourceType::Mineral && position.distance(home) <= MINERAL_LINE_RADIUS)
+            .map(|(position, _, _)| *position)
+            .collect();
+        let mineral_center = (!minerals.is_empty()).then(|| minerals.iter().sum::<Vec2>() / minerals.len() as f32);
+        let threat_direction = self.threat_direction(world, home);
+        
+        // Where the building would best go, before finding room near there
+        let anchor = match building_type {
+            // Production behind the mineral line, with the minerals between it and attackers
+            BuildingType::Barracks | BuildingType::Factory | BuildingType::ResearchCenter => match mineral_center {
+                Some(center) => center + (center - home).try_normalize().unwrap_or(-threat_direction) * BEHIND_MINERALS,
+                None => home - threat_direction * BEHIND_MINERALS,
+            },
+            BuildingType::DefenseTower => home + threat_direction * TOWER_DISTANCE,
+            _ => home,
+        };
+        let half_size = BuildingData::get(building_type).size * 0.5;
+        let clearance = MINING_LANE_CLEARANCE + half_size.max_element();
+        
+        let mut best: Option<(f32, Vec2)> = None;
+        for dy in -BASE_RADIUS..=BASE_RADIUS {
+            for dx in -BASE_RADIUS..=BASE_RADIUS {
+                let position = home.round() + Vec2::new(dx as f32, dy as f32);
+                if position.distance(home) > BASE_RADIUS as f32 {
+                    continue;
+                }
+                
+                // Workers walking between the headquarters and the minerals must not have to go round
+                if minerals.iter().any(|&mineral| distance_to_segment(position, home, mineral) < clearance) {
+                    continue;
+                }
+                
+                let mut score = position.distance(anchor);
+                if building_type == BuildingType::SupplyDepot {
+                    let nearest_depot = supply_depots
+                        .iter()
+                        .map(|depot| depot.distance(position))
+                        .fold(SUPPLY_SPREAD, f32::min);
+                    score -= nearest_depot * 2.0;
+                }
+                if best.map_or(false, |(best_score, _)| score >= best_score) {
+                    continue;
+                }
+                if buildings::is_valid_placement(building_type, position, self.player_id, map, &padded) {
+                    best = Some((score, position));
+                }
+            }
+        }
+        best.map(|(_, position)| position)
+    }
+    
+    // Direction from home that danger comes from: the enemies we know of, weighted by how hard
+    // they hit, else the nearest enemy start
+    fn threat_direction(&self, world: &World, home: Vec2) -> Vec2 {
+        let known = world
+            .get_resource::<ThreatMaps>()
+            .and_then(|maps| maps.get(self.player_id))
+            .map_or(Vec2::ZERO, |threat_map| {
+                threat_map.enemies
+                    .iter()
+                    .filter_map(|enemy| Some((enemy.position - home).try_normalize()? * enemy.dps.max(1.0)))
+                    .sum::<Vec2>()
+            });
+        if let Some(direction) = known.try_normalize() {
+            return direction;
+        }
+        
+        let player_info = world.get_resource::<PlayerInfo>();
+        world
+            .get_resource::<GameMap>()
+            .and_then(|map| {
+                map.starting_positions
+                    .iter()
+                    .enumerate()
+                    .filter(|&(player_id, _)| player_id != self.player_id as usize)
+                    .filter(|&(player_id, _)| !player_info.map_or(false, |info| info.are_allies(self.player_id, player_id as u8)))
+                    .map(|(_, &position)| position)
+                    .min_by(|a, b| a.distance(home).partial_cmp(&b.distance(home)).unwrap_or(std::cmp::Ordering::Equal))
+            })
+            .and_then(|start| (start - home).try_normalize())
+            .unwrap_or(Vec2::X)
+    }
+    
+    // Our worker closest to a building site, to send to build there
+    fn find_builder(&self, site: Vec2, world: &World) -> Option<Entity> {
+        world
+            .iter_entities()
+            .filter(|entity| entity.get::<Owner>().map_or(false, |owner| owner.0 == self.player_id))
+            .filter(|entity| entity.get::<Unit>().map_or(false, |unit| unit.unit_type == UnitType::Worker && unit.health > 0.0))
+            .filter_map(|entity| Some((entity.id(), entity.get::<Transform>()?.position.distance(site))))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(entity, _)| entity)
+    }
+    
+    // Where our base is: our starting position
+    fn home(&self, world: &World) -> Vec2 {
+        world
+            .get_resource::<GameMap>()
+            .and_then(|map| map.starting_positions.get(self.player_id as usize).copied())
+            .unwrap_or(Vec2::ZERO)
     }
     
     // Update squad assignments for military units
     fn update_squads(&mut self, world: &World) {
-        // In a real game, this would organize units into tactical squads
-        // Simplified version for this example
+        let army: Vec<Entity> = world
+            .iter_entities()
+            .filter(|entity| entity.get::<Owner>().map_or(false, |owner| owner.0 == self.player_id))
+            .filter(|entity| {
+                entity.get::<Unit>().map_or(false, |unit| {
+                    unit.health > 0.0 && unit.unit_type != UnitType::Worker &&
+                        data::game_data().unit(unit.unit_type).weapon.is_some()
+                })
+            })
+            .map(|entity| entity.id())
+            .filter(|&entity| Some(entity) != self.scout)
+            .collect();
+        
+        // Forget the fallen, and squads with nobody left
+        for squad in &mut self.attack_squads {
+            squad.units.retain(|entity| army.contains(entity));
+            squad.kiting.retain(|entity| army.contains(entity));
+        }
+        self.attack_squads.retain(|squad| !squad.units.is_empty() || squad.state == SquadState::Forming);
+        
+        // Squads that made it back home join the one gathering there
+        let home = self.home(world);
+        let mut returned = Vec::new();
+        self.attack_squads.retain(|squad| {
+            let is_home = squad.state == SquadState::Retreating &&
+                squad_center(world, &squad.units).map_or(true, |center| center.distance(home) <= HOME_RADIUS);
+            if is_home {
+                returned.extend(squad.units.iter().copied());
+            }
+            !is_home
+        });
+        
+        // New units and returned ones gather at home until there are enough to attack with
+        let assigned: HashSet<Entity> = self.attack_squads.iter().flat_map(|squad| squad.units.iter().copied()).collect();
+        let joining: Vec<Entity> = returned
+            .into_iter()
+            .chain(army.into_iter().filter(|entity| !assigned.contains(entity)))
+            .collect();
+        let forming = match self.attack_squads.iter().position(|squad| squad.state == SquadState::Forming) {
+            Some(index) => index,
+            None => {
+                self.attack_squads.push(AiSquad {
+                    units: Vec::new(),
+                    role: SquadRole::Attack,
+                    target: None,
+                    state: SquadState::Forming,
+                    waypoints: Vec::new(),
+                    ordered: false,
+                    kiting: Vec::new(),
+                });
+                self.attack_squads.len() - 1
+            }
+        };
+        for entity in joining {
+            if !self.attack_squads[forming].units.contains(&entity) {
+                self.attack_squads[forming].units.push(entity);
+            }
+        }
     }
     
-    // Command squads to move, attack, etc.
-    fn command_squads(&self, world: &World) -> Vec<Command> {
-        // Issue commands to each squad based on their role and state
-        // Simplified version for this example
-        Vec::new()
+    // Send the squad gathered at home to attack, routed around the defenses we know of
+    fn launch_attack(&mut self, world: &World, target: Vec2) {
+        let player_id = self.player_id;
+        let squad = self.attack_squads
+            .iter_mut()
+            .find(|squad| squad.state == SquadState::Forming && squad.units.len() >= MIN_ATTACK_SQUAD_SIZE);
+        if let Some(squad) = squad {
+            let center = match squad_center(world, &squad.units) {
+                Some(center) => center,
+                None => return,
+            };
+            squad.waypoints = safe_route(world, player_id, center, target);
+            debug!(target: "ai", "Player {} attacks {:?} with {} units by {} waypoints", player_id, target, squad.units.len(), squad.waypoints.len());
+            squad.target = Some(target);
+            squad.state = SquadState::Moving;
+            squad.ordered = false;
+        }
+    }
+    
+    // Command squads to move, attack, etc., with at most `budget` commands
+    fn command_squads(&mut self, world: &World, budget: usize) -> Vec<Command> {
+        let home = self.home(world);
+        let threat_map = world.get_resource::<ThreatMaps>().and_then(|maps| maps.get(self.player_id));
+        let mut commands = Vec::new();
+        
+        for squad in &mut self.attack_squads {
+            if squad.state == SquadState::Forming {
+                continue;
+            }
+            let members = squad_members(world, &squad.units);
+            if members.is_empty() {
+                continue;
+            }
+            let center = members.iter().map(|member| member.position).sum::<Vec2>() / members.len() as f32;
+            
+            // Fall back home when the enemies we know of around the squad outgun it
+            if let Some(threat_map) = threat_map.filter(|_| squad.state != SquadState::Retreating) {
+                let (dps, health) = members
+                    .iter()
+                    .fold((0.0, 0.0), |(dps, health), member| (dps + member.dps, health + member.health));
+                let enemy_strength = threat_map.enemy_strength(center, SQUAD_ENGAGE_RADIUS);
+                if enemy_strength > combat_strength(dps, health) * RETREAT_RATIO {
+                    squad.state = SquadState::Retreating;
+                    squad.waypoints = threat_map.safe_route(center, home);
+                    squad.ordered = false;
+                    squad.kiting.clear();
+                }
+            }
+            
+            if commands.len() + 2 > budget {
+                break;
+            }
+            match squad.state {
+                SquadState::Moving | SquadState::Retreating => {
+                    if let Some(order) = advance_route(squad, center) {
+                        commands.push(Command::SelectUnits(network_ids(world, &squad.units)));
+                        commands.push(order);
+                    }
+                }
+                SquadState::Attacking => {
+                    let target = squad.target.unwrap_or(center);
+                    let kiting = match threat_map {
+                        Some(threat_map) => kite(threat_map, &members),
+                        None => Vec::new(),
+                    };
+                    
+                    // Units that are clear of what they were running from go back in
+                    let clear: Vec<Entity> = squad.kiting
+                        .iter()
+                        .copied()
+                        .filter(|entity| !kiting.iter().any(|(kiter, _)| kiter == entity))
+                        .collect();
+                    if !clear.is_empty() && commands.len() + 2 <= budget {
+                        commands.push(Command::SelectUnits(network_ids(world, &clear)));
+                        commands.push(Command::Attack(target));
+                    }
+                    squad.kiting.clear();
+                    for (entity, retreat_to) in kiting {
+                        if commands.len() + 2 > budget {
+                            break;
+                        }
+                        commands.push(Command::SelectUnits(network_ids(world, &[entity])));
+                        commands.push(Command::Move(retreat_to));
+                        squad.kiting.push(entity);
+                    }
+                    
+                    // Nothing left where we attacked: head home to regroup for the next attack
+                    let cleared = center.distance(target) <= WAYPOINT_RADIUS &&
+                        threat_map.map_or(true, |threat_map| threat_map.enemies_near(center, SQUAD_ENGAGE_RADIUS).next().is_none());
+                    if cleared {
+                        squad.state = SquadState::Retreating;
+                        squad.waypoints = safe_route(world, self.player_id, center, home);
+                        squad.ordered = false;
+                        squad.kiting.clear();
+                    }
+                }
+                SquadState::Forming => {}
+            }
+        }
+        
+        commands
     }
     
     // Update the overall strategy
@@ -451,17 +1181,275 @@ impl AiController {
         }
     }
     
-    // Choose a location to scout
+    // Whether a player is an enemy of ours; creeps are left to themselves
+    fn is_enemy(&self, player_info: Option<&PlayerInfo>, owner: u8) -> bool {
+        owner != self.player_id && owner != NEUTRAL_PLAYER &&
+            !player_info.map_or(false, |info| info.are_allies(self.player_id, owner))
+    }
+    
+    // Remember the enemy units and buildings in sight, and forget what has gone
+    fn update_intel(&mut self, world: &World, elapsed_time: f32) {
+        let map = match world.get_resource::<GameMap>() {
+            Some(map) => map,
+            None => return,
+        };
+        let player_info = world.get_resource::<PlayerInfo>();
+        let (player_id, sees_through_fog) = (self.player_id, self.profile.sees_through_fog);
+        let visible = |position: Vec2| sees_through_fog || is_position_visible(map, player_id, position, PATH_GRID_SIZE);
+        
+        for entity in world.iter_entities() {
+            let transform = match (entity.get::<Transform>(), entity.get::<Owner>()) {
+                (Some(transform), Some(owner)) if self.is_enemy(player_info, owner.0) => transform,
+                _ => continue,
+            };
+            if !visible(transform.position) {
+                continue;
+            }
+            if let Some(unit) = entity.get::<Unit>().filter(|unit| unit.health > 0.0) {
+                self.intel.units.insert(entity.id(), (unit.unit_type, elapsed_time));
+            } else if let Some(building) = entity.get::<Building>().filter(|building| building.health > 0.0) {
+                let seen = SeenBuilding { building_type: building.building_type, position: transform.position, seen_at: elapsed_time };
+                self.intel.buildings.insert(entity.id(), seen);
+            }
+        }
+        
+        // A building missing from a spot in sight was destroyed; units not seen for a while may well be dead
+        self.intel.buildings.retain(|&entity, seen| {
+            !visible(seen.position) || world.get::<Building>(entity).map_or(false, |building| building.health > 0.0)
+        });
+        self.intel.units.retain(|&entity, (_, seen_at)| {
+            elapsed_time - *seen_at <= UNIT_MEMORY && world.get::<Unit>(entity).is_some()
+        });
+        for (index, &start) in map.starting_positions.iter().enumerate() {
+            if visible(start) {
+                self.intel.starts_seen.insert(index, elapsed_time);
+            }
+        }
+    }
+    
+    // Military unit that trades best, for its cost, against the enemy army we have scouted
+    fn counter_unit(&self, world: &World) -> Option<UnitType> {
+        let army = self.intel.army_estimate();
+        if army.values().sum::<u32>() < MIN_SCOUTED_UNITS {
+            return None;
+        }
+        let damage_table = world.get_resource::<DamageTable>()?;
+        
+        // Share of a defender's health an attacker takes off per second
+        let kill_rate = |attacker: UnitType, defender: UnitType| {
+            data::game_data().unit(attacker).weapon.as_ref().map_or(0.0, |weapon| {
+                let multiplier = damage_table.multipliers
+                    .get(&(weapon.damage_type, get_unit_armor_type(defender)))
+                    .copied()
+                    .unwrap_or(1.0);
+                weapon.damage / weapon.cooldown.max(0.1) * multiplier / data::game_data().unit(defender).health.max(1.0)
+            })
+        };
+        let score = |ours: UnitType| {
+            let cost: f32 = data::game_data().unit(ours).costs.values().sum();
+            let exchange: f32 = army
+                .iter()
+                .map(|(&theirs, &count)| count as f32 * kill_rate(ours, theirs) / kill_rate(theirs, ours).max(0.01))
+                .sum();
+            exchange / cost.max(1.0)
+        };
+        COUNTER_CANDIDATES
+            .into_iter()
+            .max_by(|&a, &b| score(a).partial_cmp(&score(b)).unwrap_or(std::cmp::Ordering::Equal))
+    }
+    
+    // Unit to scout with: the one already scouting, else a Scout, else a worker; taken out of its squad
+    fn find_scout(&mut self, world: &World) -> Option<Entity> {
+        let alive = |entity: Entity| world.get::<Unit>(entity).map_or(false, |unit| unit.health > 0.0);
+        if let Some(scout) = self.scout.filter(|&scout| alive(scout)) {
+            return Some(scout);
+        }
+        
+        let own_units: Vec<(Entity, UnitType)> = world
+            .iter_entities()
+            .filter(|entity| entity.get::<Owner>().map_or(false, |owner| owner.0 == self.player_id))
+            .filter_map(|entity| entity.get::<Unit>().filter(|unit| unit.health > 0.0).map(|unit| (entity.id(), unit.unit_type)))
+            .collect();
+        let scout = own_units
+            .iter()
+            .find(|(_, unit_type)| *unit_type == UnitType::Scout)
+            .or_else(|| own_units.iter().find(|(_, unit_type)| *unit_type == UnitType::Worker))
+            .map(|&(entity, _)| entity)?;
+        
+        for squad in &mut self.attack_squads {
+            squad.units.retain(|&entity| entity != scout);
+        }
+        self.scout = Some(scout);
+        Some(scout)
+    }
+    
+    // Choose a location to scout: the enemy start or remembered building we have gone longest without seeing
     fn choose_scout_target(&self, world: &World) -> Option<Vec2> {
-        // In a real game, this would look for unexplored areas or enemy bases
-        // Simplified placeholder
-        Some(Vec2::new(self.rng.gen_range(100.0..900.0), self.rng.gen_range(100.0..700.0)))
+        let map = world.get_resource::<GameMap>()?;
+        let player_info = world.get_resource::<PlayerInfo>();
+        
+        let starts = map.starting_positions
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| index < NEUTRAL_PLAYER as usize && self.is_enemy(player_info, index as u8))
+            .map(|(index, &position)| (position, self.intel.starts_seen.get(&index).copied().unwrap_or(f32::NEG_INFINITY)));
+        let buildings = self.intel.buildings.values().map(|seen| (seen.position, seen.seen_at));
+        starts
+            .chain(buildings)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(position, _)| position)
     }
     
-    // Choose a target to attack
+    // Choose a target to attack among the enemies we know of, else an enemy start
     fn choose_attack_target(&self, world: &World) -> Option<Vec2> {
-        // In a real game, this would find enemy buildings or units to attack
-        // Simplified placeholder
-        Some(Vec2::new(self.rng.gen_range(100.0..900.0), self.rng.gen_range(100.0..700.0)))
+        let map = world.get_resource::<GameMap>()?;
+        let player_info = world.get_resource::<PlayerInfo>();
+        let is_enemy = |owner: u8| self.is_enemy(player_info, owner);
+        let home = map.starting_positions.get(self.player_id as usize).copied().unwrap_or(Vec2::ZERO);
+        
+        // Easier computer players only go for what their units can see
+        let mut targets = Vec::new();
+        for entity in world.iter_entities() {
+            let transform = match (entity.get::<Transform>(), entity.get::<Owner>()) {
+                (Some(transform), Some(owner)) if is_enemy(owner.0) => transform,
+                _ => continue,
+            };
+            if let Some(unit) = entity.get::<Unit>().filter(|unit| unit.health > 0.0) {
+                let value = match unit.unit_type {
+                    UnitType::Worker => 3.0,
+                    UnitType::Healer | UnitType::Transport => 2.0,
+                    _ => 1.0,
+                };
+                targets.push((transform.position, value, unit.health / unit.max_health.max(1.0)));
+            } else if let Some(building) = entity.get::<Building>().filter(|building| building.health > 0.0) {
+                let value = building_value(building.building_type);
+                targets.push((transform.position, value, building.health / building.max_health.max(1.0)));
+            }
+        }
+        targets.retain(|&(position, _, _)| {
+            self.profile.sees_through_fog || is_position_visible(map, self.player_id, position, PATH_GRID_SIZE)
+        });
+        
+        // Buildings out of sight are still worth going for where we last saw them
+        for seen in self.intel.buildings.values() {
+            if !targets.iter().any(|&(position, _, _)| position == seen.position) {
+                targets.push((seen.position, building_value(seen.building_type), 1.0));
+            }
+        }
+        
+        let score = |&(position, value, health): &(Vec2, f32, f32)| match self.profile.target_priority {
+            TargetPriority::Nearest => -position.distance(home),
+            // A worth-while target twice as far off still beats a worthless one, and damage counts for as much as value
+            TargetPriority::HighValue => value * (2.0 - health) - position.distance(home) / 100.0,
+        };
+        let best = targets
+            .iter()
+            .max_by(|a, b| score(a).partial_cmp(&score(b)).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|&(position, _, _)| position);
+        
+        // Every player knows where the others started, seen or not
+        best.or_else(|| {
+            let enemy_starts = map.starting_positions
+                .iter()
+                .enumerate()
+                .filter(|&(player_id, _)| player_id < NEUTRAL_PLAYER as usize && is_enemy(player_id as u8))
+                .map(|(_, &position)| position);
+            enemy_starts.min_by(|a, b| a.distance(home).partial_cmp(&b.distance(home)).unwrap_or(std::cmp::Ordering::Equal))
+        })
+    }
+}
+
+/// How much destroying an enemy building sets them back, for picking attack targets
+fn building_value(building_type: BuildingType) -> f32 {
+    match building_type {
+        BuildingType::Headquarters | BuildingType::Fortress => 5.0,
+        BuildingType::Barracks | BuildingType::Factory | BuildingType::ResearchCenter => 4.0,
+        BuildingType::ResourceCollector | BuildingType::SupplyDepot => 3.0,
+        _ => 1.0,
     }
-}
\ No newline at end of file
+}
+
+/// Living units of a squad with what its commander weighs them by
+fn squad_members(world: &World, units: &[Entity]) -> Vec<SquadMember> {
+    units
+        .iter()
+        .filter_map(|&entity| {
+            let unit = world.get::<Unit>(entity).filter(|unit| unit.health > 0.0)?;
+            let transform = world.get::<Transform>(entity)?;
+            let weapon = data::game_data().unit(unit.unit_type).weapon.as_ref();
+            Some(SquadMember {
+                entity,
+                position: transform.position,
+                dps: weapon.map_or(0.0, |weapon| weapon.damage / weapon.cooldown.max(0.1)),
+                range: weapon.map_or(0.0, |weapon| weapon.range),
+                health: unit.health,
+                speed: unit.movement_speed,
+            })
+        })
+        .collect()
+}
+
+/// Middle of a squad's living units
+fn squad_center(world: &World, units: &[Entity]) -> Option<Vec2> {
+    let positions: Vec<Vec2> = units
+        .iter()
+        .filter_map(|&entity| world.get::<Transform>(entity).map(|transform| transform.position))
+        .collect();
+    (!positions.is_empty()).then(|| positions.iter().sum::<Vec2>() / positions.len() as f32)
+}
+
+/// Route around the dangers a player knows of, or straight there before it knows of any
+fn safe_route(world: &World, player_id: u8, from: Vec2, to: Vec2) -> Vec<Vec2> {
+    world
+        .get_resource::<ThreatMaps>()
+        .and_then(|maps| maps.get(player_id))
+        .map_or_else(|| vec![to], |threat_map| threat_map.safe_route(from, to))
+}
+
+/// Next order along a squad's route: nothing while it is still on its way to the waypoint it was
+/// sent to. The last leg of an attack is an attack-move, so the squad fights what it finds there.
+fn advance_route(squad: &mut AiSquad, center: Vec2) -> Option<Command> {
+    if squad.waypoints.len() > 1 && squad.waypoints.first().map_or(false, |waypoint| center.distance(*waypoint) <= WAYPOINT_RADIUS) {
+        squad.waypoints.remove(0);
+        squad.ordered = false;
+    }
+    if squad.ordered {
+        return None;
+    }
+    
+    let next = *squad.waypoints.first()?;
+    squad.ordered = true;
+    if squad.waypoints.len() == 1 && squad.state == SquadState::Moving {
+        squad.state = SquadState::Attacking;
+        return Some(Command::Attack(next));
+    }
+    Some(Command::Move(next))
+}
+
+/// Units faster and longer-ranged than an enemy closing in on them, with where to step back to
+/// so they keep shooting while it can't reach them
+fn kite(threat_map: &ThreatMap, members: &[SquadMember]) -> Vec<(Entity, Vec2)> {
+    members
+        .iter()
+        .filter_map(|member| {
+            let chaser = threat_map
+                .enemies_near(member.position, member.range)
+                .filter(|enemy| enemy.speed > 0.0 && enemy.speed < member.speed && enemy.range < member.range)
+                .filter(|enemy| enemy.position.distance(member.position) < enemy.range + KITE_MARGIN)
+                .min_by(|a, b| {
+                    a.position.distance(member.position)
+                        .partial_cmp(&b.position.distance(member.position))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })?;
+            let away = (member.position - chaser.position).try_normalize().unwrap_or(Vec2::X);
+            Some((member.entity, member.position + away * KITE_DISTANCE))
+        })
+        .collect()
+}
+
+/// Shortest distance from a point to the line segment between `a` and `b`
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let segment = b - a;
+    let t = ((point - a).dot(segment) / segment.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    point.distance(a + segment * t)
+}
@@ -0,0 +1,135 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::ecs::components::{
+    Animation, AnimationState, AttackTarget, Corpse, HealTarget, Movement, Owner, Transform, Unit, UnitType,
+};
+use crate::ecs::events::{Casualty, DeathEvent};
+use crate::ecs::resources::GameTime;
+use crate::game::data;
+
+/// Seconds a corpse stays on the last frame of its death clip before it disappears
+pub const CORPSE_LINGER: f32 = 3.0;
+
+/// One row of a sprite sheet played as a clip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimationClip {
+    pub row: u32,
+    pub frame_times: Vec<f32>, // Seconds each frame stays on screen, one per frame from the left of the row
+    pub looping: bool,         // Clips that don't loop hold their last frame
+}
+
+impl AnimationClip {
+    pub fn duration(&self) -> f32 {
+        self.frame_times.iter().sum()
+    }
+    
+    /// Column of the frame shown `elapsed` seconds into the clip
+    pub fn frame_at(&self, elapsed: f32) -> u32 {
+        let duration = self.duration();
+        let mut time = if self.looping && duration > 0.0 { elapsed % duration } else { elapsed };
+        for (frame, frame_time) in self.frame_times.iter().enumerate() {
+            if time < *frame_time {
+                return frame as u32;
+            }
+            time -= frame_time;
+        }
+        self.frame_times.len().saturating_sub(1) as u32
+    }
+}
+
+/// Layout of a unit type's sprite sheet and the clip it plays in each state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimationData {
+    pub unit_type: UnitType,
+    pub columns: u32, // Frames per row of the sheet
+    pub rows: u32,
+    pub clips: HashMap<AnimationState, AnimationClip>,
+}
+
+impl AnimationData {
+    /// Clip played in a state; states the sheet has no row for play the idle clip
+    pub fn clip(&self, state: AnimationState) -> &AnimationClip {
+        // Validation guarantees every sheet has an idle clip
+        self.clips.get(&state).unwrap_or_else(|| &self.clips[&AnimationState::Idle])
+    }
+    
+    /// Atlas region of the frame an animation is on, as offset and size in 0..1 texture coordinates
+    pub fn frame_region(&self, animation: &Animation) -> (Vec2, Vec2) {
+        let clip = self.clip(animation.state);
+        let size = Vec2::new(1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        let offset = Vec2::new(clip.frame_at(animation.elapsed) as f32, clip.row as f32) * size;
+        (offset, size)
+    }
+}
+
+/// State a unit's movement and orders call for; walking wins over attacking so units chasing a target walk
+fn unit_state(movement: Option<&Movement>, attacking: bool) -> AnimationState {
+    if movement.map_or(false, |movement| movement.velocity != Vec2::ZERO) {
+        AnimationState::Walk
+    } else if attacking {
+        AnimationState::Attack
+    } else {
+        AnimationState::Idle
+    }
+}
+
+/// System that picks each unit's clip from what it is doing, advances every clip by a tick and
+/// removes corpses once their death clip has played and lingered
+pub fn animation_system(
+    mut commands: Commands,
+    new_units: Query<Entity, (With<Unit>, Without<Animation>)>,
+    mut units: Query<
+        (&mut Animation, Option<&Movement>, Option<&AttackTarget>, Option<&HealTarget>),
+        (With<Unit>, Without<Corpse>),
+    >,
+    mut corpses: Query<(Entity, &Corpse, &mut Animation), Without<Unit>>,
+    time: Res<GameTime>,
+) {
+    for entity in new_units.iter() {
+        commands.entity(entity).insert(Animation::default());
+    }
+    
+    for (mut animation, movement, attack_target, heal_target) in units.iter_mut() {
+        let state = unit_state(movement, attack_target.is_some() || heal_target.is_some());
+        if animation.state == state {
+            animation.elapsed += time.delta_time;
+        } else {
+            // A new clip starts from its first frame
+            *animation = Animation { state, elapsed: 0.0 };
+        }
+    }
+    
+    for (entity, corpse, mut animation) in corpses.iter_mut() {
+        animation.elapsed += time.delta_time;
+        let clip = data::game_data().animation(corpse.unit_type).clip(AnimationState::Death);
+        if animation.elapsed >= clip.duration() + CORPSE_LINGER {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// System that leaves a corpse playing the death clip where a unit on the map died.
+/// Runs before death_system despawns the unit; units dying inside buildings or transports leave none.
+pub fn corpse_system(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    transforms: Query<&Transform, With<Unit>>,
+) {
+    for death in death_events.iter() {
+        let unit_type = match death.casualty {
+            Casualty::Unit(unit_type) => unit_type,
+            Casualty::Building(_) => continue,
+        };
+        if let Ok(transform) = transforms.get(death.entity) {
+            commands.spawn((
+                Corpse { unit_type },
+                Owner(death.owner),
+                transform.clone(),
+                Animation { state: AnimationState::Death, elapsed: 0.0 },
+            ));
+        }
+    }
+}
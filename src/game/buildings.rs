@@ -1,9 +1,18 @@
 use std::collections::HashMap;
 use glam::Vec2;
+use serde::{Serialize, Deserialize};
 
 use crate::ecs::components::{BuildingType, ResourceType, UnitType};
+use crate::ecs::resources::{GameMap, PlayerInfo, TechState, TechType, TerrainTile};
+use crate::ecs::systems::combat::WeaponData;
+use crate::game::data;
+use crate::game::pathfinding::{self, PATH_GRID_SIZE};
+
+/// Most segments one wall drag lays down
+pub const MAX_WALL_SEGMENTS: usize = 24;
 
 /// Building data structure containing properties for each building type
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildingData {
     pub building_type: BuildingType,
     pub name: String,
@@ -15,168 +24,36 @@ pub struct BuildingData {
     pub texture_name: String,
     pub can_produce: Vec<UnitType>,
     pub provides_supply: u32,
-    pub tech_requirements: Vec<crate::ecs::resources::TechType>,
+    pub tech_requirements: Vec<TechType>,
     pub sight_range: f32,
-    pub attack_damage: Option<f32>,
-    pub attack_range: Option<f32>,
-    pub attack_speed: Option<f32>,
+    pub weapon: Option<WeaponData>,
+    #[serde(default)]
+    pub upgrades_to: Option<BuildingType>, // Improved variant this building can be upgraded into
+    #[serde(default)]
+    pub garrison_capacity: u32, // Units that can shelter inside; 0 for buildings that take none
 }
 
 impl BuildingData {
-    /// Get building data for a specific building type
-    pub fn get(building_type: BuildingType) -> Self {
-        match building_type {
-            BuildingType::Headquarters => Self::headquarters(),
-            BuildingType::Barracks => Self::barracks(),
-            BuildingType::Factory => Self::factory(),
-            BuildingType::ResourceCollector => Self::resource_collector(),
-            BuildingType::ResearchCenter => Self::research_center(),
-            BuildingType::DefenseTower => Self::defense_tower(),
-        }
-    }
-    
-    /// Helper to create cost map
-    fn create_costs(mineral: f32, gas: f32) -> HashMap<ResourceType, f32> {
-        let mut costs = HashMap::new();
-        if mineral > 0.0 {
-            costs.insert(ResourceType::Mineral, mineral);
-        }
-        if gas > 0.0 {
-            costs.insert(ResourceType::Gas, gas);
-        }
-        costs
+    /// Get building data for a specific building type, as loaded from the data files
+    pub fn get(building_type: BuildingType) -> &'static Self {
+        data::game_data().building(building_type)
     }
     
-    // SPLIT 1
-    /// Headquarters building data
-    pub fn headquarters() -> Self {
-        Self {
-            building_type: BuildingType::Headquarters,
-            name: "Command Center".to_string(),
-            description: "Main base building that produces workers and provides supply.".to_string(),
-            health: 1500.0,
-            size: Vec2::new(4.0, 4.0),
-            build_time: 120.0,
-            costs: Self::create_costs(400.0, 0.0),
-            texture_name: "building_hq",
-            can_produce: vec![UnitType::Worker],
-            provides_supply: 10,
-            tech_requirements: vec![],
-            sight_range: 100.0,
-            attack_damage: None,
-            attack_range: None,
-            attack_speed: None,
-        }
-    }
-    
-    /// Barracks building data
-    pub fn barracks() -> Self {
-        Self {
-            building_type: BuildingType::Barracks,
-            name: "Barracks".to_string(),
-            description: "Training facility for infantry units.".to_string(),
-            health: 1000.0,
-            size: Vec2::new(3.0, 3.0),
-            build_time: 60.0,
-            costs: Self::create_costs(150.0, 0.0),
-            texture_name: "building_barracks",
-            can_produce: vec![UnitType::Soldier, UnitType::Scout],
-            provides_supply: 0,
-            tech_requirements: vec![],
-            sight_range: 80.0,
-            attack_damage: None,
-            attack_range: None,
-            attack_speed: None,
-        }
-    }
-    
-    // SPLIT 2
-    /// Factory building data
-    pub fn factory() -> Self {
-        Self {
-            building_type: BuildingType::Factory,
-            name: "Factory".to_string(),
-            description: "Production facility for advanced combat units.".to_string(),
-            health: 1200.0,
-            size: Vec2::new(3.0, 3.0),
-            build_time: 90.0,
-            costs: Self::create_costs(200.0, 100.0),
-            texture_name: "building_factory",
-            can_produce: vec![UnitType::Tank],
-            provides_supply: 0,
-            tech_requirements: vec![crate::ecs::resources::TechType::AdvancedUnits],
-            sight_range: 80.0,
-            attack_damage: None,
-            attack_range: None,
-            attack_speed: None,
-        }
-    }
-    
-    /// Resource collector building data
-    pub fn resource_collector() -> Self {
-        Self {
-            building_type: BuildingType::ResourceCollector,
-            name: "Resource Collector".to_string(),
-            description: "Increases resource gathering efficiency nearby.".to_string(),
-            health: 800.0,
-            size: Vec2::new(2.0, 2.0),
-            build_time: 45.0,
-            costs: Self::create_costs(100.0, 50.0),
-            texture_name: "building_resource",
-            can_produce: vec![],
-            provides_supply: 0,
-            tech_requirements: vec![],
-            sight_range: 60.0,
-            attack_damage: None,
-            attack_range: None,
-            attack_speed: None,
-        }
-    }
-    
-    /// Research center building data
-    pub fn research_center() -> Self {
-        Self {
-            building_type: BuildingType::ResearchCenter,
-            name: "Research Center".to_string(),
-            description: "Allows research of new technologies.".to_string(),
-            health: 850.0,
-            size: Vec2::new(3.0, 3.0),
-            build_time: 60.0,
-            costs: Self::create_costs(150.0, 100.0),
-            texture_name: "building_research",
-            can_produce: vec![UnitType::Healer],
-            provides_supply: 0,
-            tech_requirements: vec![],
-            sight_range: 80.0,
-            attack_damage: None,
-            attack_range: None,
-            attack_speed: None,
-        }
-    }
-    
-    // SPLIT 3  
-    /// Defense tower building data
-    pub fn defense_tower() -> Self {
-        Self {
-            building_type: BuildingType::DefenseTower,
-            name: "Defense Tower".to_string(),
-            description: "Static defense structure that attacks nearby enemies.".to_string(),
-            health: 500.0,
-            size: Vec2::new(2.0, 2.0),
-            build_time: 30.0,
-            costs: Self::create_costs(75.0, 25.0),
-            texture_name: "building_defense",
-            can_produce: vec![],
-            provides_supply: 0,
-            tech_requirements: vec![],
-            sight_range: 150.0,
-            attack_damage: Some(15.0),
-            attack_range: Some(100.0),
-            attack_speed: Some(1.0),
-        }
+    /// Whether a building type is only reached by upgrading another, rather than built from scratch
+    pub fn is_upgrade(building_type: BuildingType) -> bool {
+        BuildingType::ALL.iter().any(|&other| Self::get(other).upgrades_to == Some(building_type))
     }
 }
 
+/// First of a building type's tech requirements the player hasn't researched yet
+pub fn missing_tech(building_type: BuildingType, tech_state: &TechState, player_id: u8) -> Option<TechType> {
+    BuildingData::get(building_type)
+        .tech_requirements
+        .iter()
+        .copied()
+        .find(|&tech_type| !tech_state.researched.get(&(player_id, tech_type)).copied().unwrap_or(false))
+}
+
 /// Check if a building location is valid
 pub fn is_valid_build_location(
     building_type: BuildingType,
@@ -200,35 +77,18 @@ pub fn is_valid_build_location(
         return false;
     }
     
-    // Check terrain at building corners
-    let corners = [
-        Vec2::new(min_x, min_y),
-        Vec2::new(max_x, min_y),
-        Vec2::new(min_x, max_y),
-        Vec2::new(max_x, max_y),
-        Vec2::new(position.x, position.y), // Center
-    ];
-    
-    for corner in &corners {
-        let x = corner.x as usize;
-        let y = corner.y as usize;
-        let idx = y * game_map.width as usize + x;
-        
-        // Check if terrain is buildable
-        if idx < game_map.terrain_tiles.len() {
-            match game_map.terrain_tiles[idx] {
-                crate::ecs::resources::TerrainTile::Water | 
-                crate::ecs::resources::TerrainTile::Mountain => {
-                    return false;
-                }
-                _ => {}
-            }
-        } else {
-            return false; // Out of bounds
+    // Every tile under the footprint has to be buildable terrain with no building on it
+    for index in footprint_tiles(building_type, position, game_map) {
+        if game_map.is_tile_occupied(index) {
+            return false;
+        }
+        match game_map.terrain_tiles.get(index as usize) {
+            Some(TerrainTile::Water) | Some(TerrainTile::Mountain) | None => return false,
+            _ => {}
         }
     }
     
-    // Check collision with existing buildings
+    // Buildings placed this tick aren't on the map's occupied tiles yet
     for (other_pos, other_size) in existing_buildings {
         let other_half_size = other_size * 0.5;
         
@@ -241,24 +101,128 @@ pub fn is_valid_build_location(
         }
     }
     
-    // Check for resources nearby if it's a resource collector
-    if building_type == BuildingType::ResourceCollector {
-        let mut resource_nearby = false;
-        let collection_radius = 100.0;
-        
-        for (res_pos, _, _) in &game_map.resource_positions {
-            if (position - *res_pos).length() < collection_radius {
-                resource_nearby = true;
-                break;
+    // Resource collectors double as gas extractors and have to sit on a gas node
+    if building_type == BuildingType::ResourceCollector && gas_node_under(position, game_map).is_none() {
+        return false;
+    }
+    
+    true
+}
+
+/// Map tiles under a building's rectangular footprint, clipped to the map; tiles are one world unit across
+pub fn footprint_tiles(building_type: BuildingType, position: Vec2, game_map: &GameMap) -> Vec<u32> {
+    let half_size = BuildingData::get(building_type).size * 0.5;
+    let min = (position - half_size).floor();
+    let max = (position + half_size).ceil();
+    
+    let mut tiles = Vec::new();
+    for y in min.y as i32..max.y as i32 {
+        for x in min.x as i32..max.x as i32 {
+            if let Some(index) = game_map.tile_index(x, y) {
+                tiles.push(index);
             }
         }
-        
-        if !resource_nearby {
-            return false;
+    }
+    tiles
+}
+
+/// Mark the tiles under the given buildings (type and position) as occupied, replacing any previous footprints
+pub fn occupy_footprints(game_map: &mut GameMap, buildings: &[(BuildingType, Vec2)]) {
+    game_map.occupied_tiles.clear();
+    for &(building_type, position) in buildings {
+        let tiles = footprint_tiles(building_type, position, game_map);
+        game_map.occupied_tiles.extend(tiles);
+    }
+}
+
+/// Mark the tiles under the given gates (owner and position) as passable by the owner and their allies
+pub fn open_gates(game_map: &mut GameMap, gates: &[(u8, Vec2)], player_info: Option<&PlayerInfo>) {
+    game_map.gate_tiles.clear();
+    for &(owner, position) in gates {
+        let players = player_info.map_or_else(|| vec![owner], |info| info.allies_of(owner));
+        for index in footprint_tiles(BuildingType::Gate, position, game_map) {
+            game_map.gate_tiles.insert(index, players.clone());
         }
     }
+}
+
+/// Whether a building type is a wall piece. Wall pieces sit one per pathfinding cell so a row of them
+/// seals off a continuous line of cells.
+pub fn is_wall_piece(building_type: BuildingType) -> bool {
+    matches!(building_type, BuildingType::Wall | BuildingType::Gate)
+}
+
+/// Center of the pathfinding cell a wall piece placed at a position snaps to
+pub fn snap_wall_piece(position: Vec2) -> Vec2 {
+    pathfinding::grid_to_world(pathfinding::world_to_grid(position, PATH_GRID_SIZE), PATH_GRID_SIZE)
+}
+
+/// Positions of the wall segments joining two points, one per cell from start to end. The chain steps
+/// along one axis at a time, since units can slip between two segments that only touch at a corner.
+pub fn wall_segments(start: Vec2, end: Vec2) -> Vec<Vec2> {
+    let from = pathfinding::world_to_grid(start, PATH_GRID_SIZE);
+    let to = pathfinding::world_to_grid(end, PATH_GRID_SIZE);
+    let (dx, dy) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
     
-    true
+    let mut cells = vec![from];
+    let (mut moved_x, mut moved_y) = (0, 0);
+    while (moved_x < dx || moved_y < dy) && cells.len() < MAX_WALL_SEGMENTS {
+        // Step along whichever axis keeps the chain closest to the straight line
+        if moved_y == dy || (moved_x < dx && (2 * moved_x + 1) * dy <= (2 * moved_y + 1) * dx) {
+            moved_x += 1;
+        } else {
+            moved_y += 1;
+        }
+        cells.push((from.0 + moved_x * step_x, from.1 + moved_y * step_y));
+    }
+    
+    cells.into_iter().map(|cell| pathfinding::grid_to_world(cell, PATH_GRID_SIZE)).collect()
+}
+
+/// Whether a point lies under a building's footprint
+pub fn footprint_contains(building_type: BuildingType, building_position: Vec2, point: Vec2) -> bool {
+    let half_size = BuildingData::get(building_type).size * 0.5;
+    let offset = (point - building_position).abs();
+    offset.x <= half_size.x && offset.y <= half_size.y
+}
+
+/// Gas node a Resource Collector placed here would extract from
+pub fn gas_node_under(position: Vec2, game_map: &crate::ecs::resources::GameMap) -> Option<Vec2> {
+    game_map.resource_positions
+        .iter()
+        .filter(|(_, resource_type, _)| *resource_type == ResourceType::Gas)
+        .map(|(resource_position, _, _)| *resource_position)
+        .find(|resource_position| footprint_contains(BuildingType::ResourceCollector, position, *resource_position))
+}
+
+/// Whether the player's workers may harvest a node; gas needs one of the player's finished
+/// Resource Collectors on top of it. `extractors` holds the owner and position of each finished collector.
+pub fn can_harvest(resource_type: ResourceType, node_position: Vec2, player_id: u8, extractors: &[(u8, Vec2)]) -> bool {
+    resource_type != ResourceType::Gas || extractors.iter().any(|&(owner, position)| {
+        owner == player_id && footprint_contains(BuildingType::ResourceCollector, position, node_position)
+    })
+}
+
+/// Check if a player may place a building here: terrain, other buildings and fog of war
+pub fn is_valid_placement(
+    building_type: BuildingType,
+    position: Vec2,
+    player_id: u8,
+    game_map: &crate::ecs::resources::GameMap,
+    existing_buildings: &[(Vec2, Vec2)],
+) -> bool {
+    if !is_valid_build_location(building_type, position, game_map, existing_buildings) {
+        return false;
+    }
+    
+    // Players can only build where they currently have vision
+    crate::game::map::is_position_visible(
+        game_map,
+        player_id,
+        position,
+        crate::game::pathfinding::PATH_GRID_SIZE,
+    )
 }
 
 /// Find a valid build location near the target position
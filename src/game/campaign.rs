@@ -0,0 +1,160 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+use crate::ecs::resources::{TechState, TechType};
+use crate::game::scenario::SCENARIO_DIR;
+
+/// File listing the campaign's missions in the order they are played
+pub const CAMPAIGN_FILE: &str = "assets/scenarios/campaign.ron";
+
+/// File the player's campaign progress is kept in between sessions
+pub const PROGRESS_FILE: &str = "config/campaign.toml";
+
+/// One mission of the campaign: the briefing shown before it and the scenario played
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissionData {
+    pub id: String,       // Recorded in the progress file, so missions can be reordered or renamed
+    pub title: String,
+    pub briefing: String,
+    pub scenario: String, // Scenario file in SCENARIO_DIR
+    #[serde(default)]
+    pub unlocks: Vec<TechType>, // Researched from the start of every mission once this one is won
+}
+
+/// The ordered list of missions; each is playable once the one before it has been won
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub missions: Vec<MissionData>,
+}
+
+impl Campaign {
+    /// Read a campaign file, rejecting missions with clashing ids
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path.as_ref())?;
+        let campaign: Campaign = ron::from_str(&text)?;
+        
+        let mut ids = HashSet::new();
+        for mission in &campaign.missions {
+            if !ids.insert(mission.id.as_str()) {
+                return Err(anyhow::anyhow!("{}: mission {} is defined more than once", path.as_ref().display(), mission.id));
+            }
+        }
+        
+        Ok(campaign)
+    }
+    
+    /// The campaign file, or no missions if it is missing or broken
+    pub fn load_or_default() -> Self {
+        match Self::load(CAMPAIGN_FILE) {
+            Ok(campaign) => campaign,
+            Err(e) => {
+                error!("Failed to load campaign: {}", e);
+                Self::default()
+            }
+        }
+    }
+    
+    /// Path of the scenario file a mission plays
+    pub fn scenario_path(&self, index: usize) -> Option<PathBuf> {
+        self.missions.get(index).map(|mission| PathBuf::from(SCENARIO_DIR).join(&mission.scenario))
+    }
+}
+
+/// Missions the player has won and what winning them unlocked
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub completed: Vec<String>,         // Mission ids
+    pub unlocked_techs: Vec<TechType>,
+}
+
+impl CampaignProgress {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+    
+    /// Write the progress to `path`, creating its directory if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    
+    /// The saved progress, or a fresh campaign if there is none yet
+    pub fn load_or_default() -> Self {
+        if !Path::new(PROGRESS_FILE).exists() {
+            return Self::default();
+        }
+        
+        match Self::load(PROGRESS_FILE) {
+            Ok(progress) => progress,
+            Err(e) => {
+                warn!("Failed to load campaign progress, starting over: {}", e);
+                Self::default()
+            }
+        }
+    }
+    
+    pub fn is_completed(&self, mission: &MissionData) -> bool {
+        self.completed.contains(&mission.id)
+    }
+    
+    /// Whether a mission can be played: the first always can, the others once the one before is won
+    pub fn is_available(&self, campaign: &Campaign, index: usize) -> bool {
+        match index {
+            0 => !campaign.missions.is_empty(),
+            _ => campaign.missions.get(index - 1).map_or(false, |previous| self.is_completed(previous)),
+        }
+    }
+    
+    /// Record a won mission and what it unlocks; returns whether it had not been won before
+    pub fn complete(&mut self, mission: &MissionData) -> bool {
+        for &tech in &mission.unlocks {
+            if !self.unlocked_techs.contains(&tech) {
+                self.unlocked_techs.push(tech);
+            }
+        }
+        if self.is_completed(mission) {
+            return false;
+        }
+        self.completed.push(mission.id.clone());
+        true
+    }
+    
+    /// Tech state a mission starts from: everything unlocked so far researched for the player
+    pub fn starting_tech_state(&self, player_id: u8) -> TechState {
+        let mut tech_state = TechState::default();
+        for &tech in &self.unlocked_techs {
+            tech_state.researched.insert((player_id, tech), true);
+        }
+        tech_state
+    }
+}
+
+/// Campaign mission being played, so winning it can be recorded
+#[derive(Resource, Debug, Clone)]
+pub struct CampaignMission {
+    pub index: usize,
+    pub mission: MissionData,
+}
+
+/// Record a won mission in the saved progress
+pub fn record_victory(mission: &MissionData) {
+    let mut progress = CampaignProgress::load_or_default();
+    if progress.complete(mission) {
+        info!("Campaign: completed {}", mission.title);
+    }
+    if let Err(e) = progress.save(PROGRESS_FILE) {
+        error!("Failed to save campaign progress: {}", e);
+    }
+}
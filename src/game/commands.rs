@@ -0,0 +1,1533 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+
+use std::collections::{BTreeSet, VecDeque};
+use tracing::{info, warn};
+
+use crate::ecs::components::{
+    AttackMove, AttackTarget, BoardTarget, BuildTarget, Building, BuildingType, Cargo, CastOrder, CastTarget, Caster, Collider,
+    ConstructionSite, FlowFieldMove, Follow, FormationMove, Garrison, GarrisonTarget, Gate, HarvestTarget, HealTarget, HoldPosition,
+    IdleWorker, Movement, Owner, Patrol, RepairTarget, ResearchQueue, Resource, ResourceType, Selectable, Selected, Stance,
+    Transform, Unit, UnitType, Upgrading,
+};
+use crate::ecs::resources::{
+    AudioEvents, ControlGroups, GameMap, PathfindingGrid, PlayerAlerts, PlayerFormations, PlayerInfo, PlayerResources,
+    SelectionKind, TechState, TechType,
+};
+use crate::ecs::events::DamageEvent;
+use crate::ecs::network_id::{entities_of, network_order};
+use crate::ecs::spatial::SpatialGrid;
+use crate::engine::audio::GameSoundType;
+use crate::engine::input::{AbilityCommand, Command};
+use crate::game::abilities::{self, AbilityData, AbilityTarget};
+use crate::game::buildings::{self, BuildingData};
+use crate::game::data;
+use crate::game::formation::{formation_destinations, Formation, FormationMember, MIN_FORMATION_SPEED_FACTOR};
+use crate::game::garrison;
+use crate::game::map;
+use crate::game::path_queue::{queue_group_path, queue_path};
+use crate::game::pathfinding;
+use crate::game::stats::GameStats;
+use crate::game::tech::{is_tech_available, TechData};
+use crate::game::transport;
+use crate::game::units::{can_train_unit, player_supply, unit_costs, unit_supply_cost};
+
+/// Extra distance around a click that still picks an entity
+const PICK_RADIUS: f32 = 4.0;
+
+/// Distance from a resource node's center at which a gather click still picks it
+const GATHER_PICK_RADIUS: f32 = 16.0;
+
+/// Most units a building can have queued at once
+pub const MAX_PRODUCTION_QUEUE: usize = 5;
+
+/// Apply a batch of player commands to the world
+pub fn process_commands(world: &mut World, player_id: u8, commands: &[Command]) {
+    for command in commands {
+        match command {
+            Command::Move(target_pos) => issue_move_order(world, player_id, *target_pos, MoveOrder::Move),
+            Command::SmartOrder(target_pos) => smart_order(world, player_id, *target_pos),
+            Command::Attack(target_pos) => issue_move_order(world, player_id, *target_pos, MoveOrder::AttackMove),
+            Command::Patrol(origin, target_pos) => {
+                issue_move_order(world, player_id, *target_pos, MoveOrder::Patrol(*origin));
+            }
+            Command::BuildBuilding { building_type, position } => {
+                place_building(world, player_id, *building_type, *position);
+            }
+            Command::BuildWall { start, end } => place_wall(world, player_id, *start, *end),
+            Command::Select(position) => select_at(world, player_id, *position),
+            Command::MultiSelect(start, end) => select_in_rect(world, player_id, *start, *end),
+            Command::SelectSameType { position, view_min, view_max } => {
+                select_same_type(world, player_id, *position, *view_min, *view_max);
+            }
+            Command::GroupAssign(group) => assign_control_group(world, player_id, *group),
+            Command::GroupSelect(group) => select_control_group(world, player_id, *group),
+            Command::SelectIdleWorker => {
+                if let Some(worker) = next_idle_worker(world, player_id) {
+                    replace_selection(world, player_id, vec![worker]);
+                }
+            }
+            Command::SelectUnits(ids) => {
+                let entities = entities_of(world, ids).into_iter().filter(|&entity| is_selectable_by(world, entity, player_id)).collect();
+                replace_selection(world, player_id, entities);
+            }
+            Command::SetFormation(formation) => set_formation(world, player_id, *formation),
+            Command::Load => load_selection(world, player_id),
+            Command::Unload => unload_selection(world, player_id),
+            Command::UnloadSlot(slot) => unload_slot(world, player_id, *slot),
+            Command::Ungarrison => ungarrison_selection(world, player_id),
+            Command::UngarrisonSlot(slot) => ungarrison_slot(world, player_id, *slot),
+            Command::Gather(position) => gather_at(world, player_id, *position),
+            Command::Stop => stop_units(world, player_id, false),
+            Command::HoldPosition => stop_units(world, player_id, true),
+            Command::SetStance(stance) => set_stance(world, player_id, *stance),
+            Command::Train(unit_command) => match UnitType::from_u8(unit_command.unit_type) {
+                Some(unit_type) => train_unit(world, player_id, unit_type),
+                None => warn!("Unknown unit type {}", unit_command.unit_type),
+            },
+            Command::CancelTrain(slot) => cancel_training(world, player_id, *slot),
+            Command::Research(tech) => match TechType::from_u8(*tech) {
+                Some(tech_type) => research_tech(world, player_id, tech_type),
+                None => warn!("Unknown tech type {}", tech),
+            },
+            Command::UpgradeBuilding => upgrade_building(world, player_id),
+            Command::UseAbility(ability_command) => use_ability(world, player_id, ability_command),
+            Command::RepairBuilding(building) => {
+                if let Some(building) = entities_of(world, &[*building]).pop() {
+                    repair_building(world, player_id, building);
+                }
+            }
+            Command::Surrender => surrender(world, player_id),
+            _ => {
+                // Other commands are handled by their own subsystems
+            }
+        }
+    }
+}
+
+/// Kind of movement order given to selected units
+#[derive(Debug, Clone, Copy)]
+enum MoveOrder {
+    Move,
+    AttackMove,
+    Patrol(Vec2), // Patrol origin; the order target is the far end
+}
+
+/// Give every selected unit owned by the player a pathfinding route to its slot in the player's formation
+fn issue_move_order(world: &mut World, player_id: u8, target_pos: Vec2, order: MoveOrder) {
+    let members = selected_members(world, player_id);
+    move_members(world, player_id, members, target_pos, order);
+}
+
+/// The player's selected units that can move, sorted by network ID
+fn selected_members(world: &mut World, player_id: u8) -> Vec<FormationMember> {
+    let mut members = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Transform, &Owner, Option<&Collider>), (With<Selected>, With<Movement>)>();
+    for (entity, transform, owner, collider) in query.iter(world) {
+        if owner.0 == player_id {
+            members.push(FormationMember {
+                entity,
+                position: transform.position,
+                radius: collider.map(|c| c.radius).unwrap_or(0.0),
+            });
+        }
+    }
+    
+    // Query order isn't guaranteed; sort so every machine hands out the same slots
+    members.sort_by_key(|member| network_order(world, member.entity));
+    members
+}
+
+/// Route a group of units to their slots in the player's formation around a target position
+fn move_members(world: &mut World, player_id: u8, members: Vec<FormationMember>, target_pos: Vec2, order: MoveOrder) {
+    if members.is_empty() || world.get_resource::<GameMap>().and_then(|map| map.grid_for(player_id)).is_none() {
+        return;
+    }
+    
+    let formation = world
+        .get_resource::<PlayerFormations>()
+        .and_then(|formations| formations.formations.get(&player_id).copied())
+        .unwrap_or_default();
+    let destinations = formation_destinations(formation, &members, target_pos);
+    
+    // Send every unit to its slot, noting roughly how long each will take to get there; the routes
+    // themselves, or for a large group a shared flow field, are worked out by the path queue over the next few ticks
+    let group_target = match order {
+        MoveOrder::Patrol(origin) => origin,
+        _ => target_pos,
+    };
+    let mut routes = Vec::new();
+    for (entity, slot) in destinations {
+        let member = members.iter().find(|member| member.entity == entity).copied().unwrap();
+        let offset = slot - target_pos;
+        
+        // Patrols start by walking to the origin
+        let destination = match order {
+            MoveOrder::Patrol(origin) => origin + offset,
+            _ => slot,
+        };
+        
+        let speed = world.get::<Unit>(entity).map(|unit| unit.movement_speed).unwrap_or(100.0);
+        if world.get::<Movement>(entity).is_some() {
+            queue_group_path(world, entity, player_id, member.position, destination, member.radius, members.len(), group_target);
+            let travel_time = if speed > 0.0 { member.position.distance(destination) / speed } else { 0.0 };
+            routes.push((entity, offset, destination, speed, travel_time));
+        }
+    }
+    
+    // Units acknowledge the order
+    let sound = match order {
+        MoveOrder::AttackMove => GameSoundType::UnitAttack,
+        _ => GameSoundType::UnitMove,
+    };
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, sound, target_pos);
+    
+    // Faster units slow down so the whole group arrives with the slowest one
+    let group_travel_time = routes.iter().map(|route| route.4).fold(0.0, f32::max);
+    
+    for (entity, offset, destination, speed, travel_time) in routes {
+        let mut entity = world.entity_mut(entity);
+        
+        // A new order replaces whatever the unit was doing (including construction)
+        entity.remove::<BuildTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<HealTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.remove::<FlowFieldMove>();
+        
+        if members.len() > 1 && group_travel_time > 0.0 {
+            let group_speed = speed * travel_time / group_travel_time;
+            entity.insert(FormationMove {
+                destination,
+                speed: group_speed.max(speed * MIN_FORMATION_SPEED_FACTOR),
+            });
+        }
+        
+        match order {
+            MoveOrder::Move => {}
+            MoveOrder::AttackMove => {
+                entity.insert(AttackMove { destination });
+            }
+            MoveOrder::Patrol(origin) => {
+                entity.insert(Patrol {
+                    start: origin + offset,
+                    end: target_pos + offset,
+                    heading_to_end: false,
+                });
+            }
+        }
+    }
+}
+
+/// What a right-click at a position orders the player's selection to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderContext {
+    Move,
+    /// Workers harvest the node; other units move there
+    Gather(Entity),
+    /// Workers repair the damaged building; other units move there
+    Repair(Entity),
+    /// Attack this enemy unit or building
+    Attack(Entity),
+    /// Stay close to this friendly unit
+    Follow(Entity),
+    /// Climb aboard this transport of the player's
+    Board(Entity),
+    /// Shelter inside this building of the player's
+    Garrison(Entity),
+}
+
+/// Work out what a right-click at a position means for the player's selected units
+pub fn order_context(world: &mut World, player_id: u8, position: Vec2) -> OrderContext {
+    let selected = selected_movers(world, player_id);
+    if selected.is_empty() {
+        return OrderContext::Move;
+    }
+    let has_worker = selected
+        .iter()
+        .any(|&entity| world.get::<Unit>(entity).map_or(false, |unit| unit.unit_type == UnitType::Worker));
+    
+    let picked = entity_at(world, player_id, position);
+    if let Some(target) = picked {
+        let owner = world.get::<Owner>(target).map_or(player_id, |owner| owner.0);
+        if !is_allied(world, player_id, owner) {
+            return OrderContext::Attack(target);
+        }
+    }
+    
+    if let Some((node, _, _)) = resource_node_at(world, position) {
+        return if has_worker { OrderContext::Gather(node) } else { OrderContext::Move };
+    }
+    
+    let target = match picked {
+        Some(target) => target,
+        None => return OrderContext::Move,
+    };
+    if let Some(building) = world.get::<Building>(target) {
+        let owned = world.get::<Owner>(target).map_or(false, |owner| owner.0 == player_id);
+        let damaged = building.construction_progress.is_none() && building.health < building.max_health;
+        if owned && damaged && has_worker {
+            return OrderContext::Repair(target);
+        }
+        
+        // Infantry shelters in the player's own buildings that take occupants
+        let can_garrison = selected
+            .iter()
+            .any(|&entity| world.get::<Unit>(entity).map_or(false, |unit| garrison::can_garrison(unit.unit_type)));
+        return if owned && can_garrison && world.get::<Garrison>(target).is_some() {
+            OrderContext::Garrison(target)
+        } else {
+            OrderContext::Move
+        };
+    }
+    
+    // Units that can ride climb into the player's own transports
+    let owned = world.get::<Owner>(target).map_or(false, |owner| owner.0 == player_id);
+    let can_board = selected.iter().any(|&entity| {
+        entity != target && world.get::<Unit>(entity).map_or(false, |unit| transport::can_board(unit.unit_type))
+    });
+    if owned && can_board && world.get::<Cargo>(target).is_some() {
+        return OrderContext::Board(target);
+    }
+    
+    // A unit can't follow itself
+    if world.get::<Unit>(target).is_some() && selected != [target] {
+        return OrderContext::Follow(target);
+    }
+    OrderContext::Move
+}
+
+/// Unit or building under a position that the player can see; enemies hidden by the fog of war can't be picked
+pub fn entity_at(world: &World, player_id: u8, position: Vec2) -> Option<Entity> {
+    let spatial_grid = world.get_resource::<SpatialGrid>()?;
+    let picked = spatial_grid.nearest(position, PICK_RADIUS + spatial_grid.max_radius(), |candidate| {
+        (candidate.position - position).length() <= candidate.radius + PICK_RADIUS &&
+        world.get::<Owner>(candidate.entity).map_or(false, |owner| {
+            is_allied(world, player_id, owner.0) || is_visible_to(world, player_id, candidate.position)
+        })
+    });
+    picked.map(|entry| entry.entity)
+}
+
+/// Carry out a right-click order according to what was clicked
+fn smart_order(world: &mut World, player_id: u8, position: Vec2) {
+    match order_context(world, player_id, position) {
+        OrderContext::Move => issue_move_order(world, player_id, position, MoveOrder::Move),
+        OrderContext::Gather(_) => {
+            gather_at(world, player_id, position);
+            move_others(world, player_id, position, |unit| unit.unit_type != UnitType::Worker);
+        }
+        OrderContext::Repair(building) => {
+            repair_building(world, player_id, building);
+            move_others(world, player_id, position, |unit| unit.unit_type != UnitType::Worker);
+        }
+        OrderContext::Attack(target) => {
+            attack_entity(world, player_id, target);
+            move_others(world, player_id, position, |unit| matches!(unit.unit_type, UnitType::Healer | UnitType::Transport));
+        }
+        OrderContext::Follow(target) => follow_unit(world, player_id, target),
+        OrderContext::Board(transport) => {
+            board_transport(world, player_id, transport);
+            move_others(world, player_id, position, |unit| !transport::can_board(unit.unit_type));
+        }
+        OrderContext::Garrison(building) => {
+            garrison_building(world, player_id, building);
+            move_others(world, player_id, position, |unit| !garrison::can_garrison(unit.unit_type));
+        }
+    }
+}
+
+/// Move the player's selected units that `filter` accepts, e.g. those a context order doesn't apply to
+fn move_others(world: &mut World, player_id: u8, position: Vec2, filter: impl Fn(&Unit) -> bool) {
+    let members = selected_members(world, player_id)
+        .into_iter()
+        .filter(|member| world.get::<Unit>(member.entity).map_or(false, |unit| filter(unit)))
+        .collect();
+    move_members(world, player_id, members, position, MoveOrder::Move);
+}
+
+/// Send the player's selected workers to repair one of the player's buildings
+fn repair_building(world: &mut World, player_id: u8, building: Entity) {
+    let owned = world.get::<Owner>(building).map_or(false, |owner| owner.0 == player_id);
+    let position = match (world.get::<Building>(building), world.get::<Transform>(building)) {
+        (Some(_), Some(transform)) if owned => transform.position,
+        _ => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if world.get::<Unit>(entity).map_or(true, |unit| unit.unit_type != UnitType::Worker) {
+            continue;
+        }
+        
+        // The repair system walks the worker there
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<Follow>();
+        entity.insert(RepairTarget { target_entity: building });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitMove, position);
+}
+
+/// Order the player's selected units that can fight to attack one enemy, chasing it whatever their stance
+fn attack_entity(world: &mut World, player_id: u8, target: Entity) {
+    let position = match world.get::<Transform>(target) {
+        Some(transform) => transform.position,
+        None => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if world.get::<Unit>(entity).map_or(true, |unit| matches!(unit.unit_type, UnitType::Healer | UnitType::Transport)) {
+            continue;
+        }
+        
+        // Attack-moving to the target lets defensive units chase it, and they carry on there once it falls
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<HealTarget>();
+        entity.remove::<Patrol>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.insert(AttackTarget { target_entity: target });
+        entity.insert(AttackMove { destination: position });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitAttack, position);
+}
+
+/// Order the player's selected units to keep close to a friendly unit
+fn follow_unit(world: &mut World, player_id: u8, target: Entity) {
+    let position = match world.get::<Transform>(target) {
+        Some(transform) => transform.position,
+        None => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if entity == target {
+            continue;
+        }
+        
+        // The follow system walks the unit after its target
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<HealTarget>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.insert(Follow { target_entity: target });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitMove, position);
+}
+
+/// Order the player's selected units that can ride to walk to one of the player's transports and climb aboard
+fn board_transport(world: &mut World, player_id: u8, transport: Entity) {
+    let position = match world.get::<Transform>(transport) {
+        Some(transform) => transform.position,
+        None => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if entity == transport || world.get::<Unit>(entity).map_or(true, |unit| !transport::can_board(unit.unit_type)) {
+            continue;
+        }
+        
+        // The boarding system walks the unit to the transport
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<HealTarget>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.remove::<GarrisonTarget>();
+        entity.insert(BoardTarget { target_entity: transport });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitMove, position);
+}
+
+/// The player's selected transports, sorted by network ID
+fn selected_transports(world: &mut World, player_id: u8) -> Vec<Entity> {
+    let mut query = world.query_filtered::<(Entity, &Owner), (With<Selected>, With<Cargo>)>();
+    let mut transports: Vec<Entity> = query
+        .iter(world)
+        .filter(|(_, owner)| owner.0 == player_id)
+        .map(|(entity, _)| entity)
+        .collect();
+    transports.sort_by_key(|&entity| network_order(world, entity));
+    transports
+}
+
+/// Send each of the player's selected units that can ride to the nearest selected transport that still has room
+fn load_selection(world: &mut World, player_id: u8) {
+    let mut transports: Vec<(Entity, Vec2, u32)> = selected_transports(world, player_id)
+        .into_iter()
+        .filter_map(|entity| {
+            let position = world.get::<Transform>(entity)?.position;
+            let cargo = world.get::<Cargo>(entity)?;
+            Some((entity, position, cargo.capacity.saturating_sub(cargo.passengers.len() as u32)))
+        })
+        .collect();
+    
+    let mut riders: Vec<(Entity, Vec2)> = selected_movers(world, player_id)
+        .into_iter()
+        .filter(|&entity| world.get::<Unit>(entity).map_or(false, |unit| transport::can_board(unit.unit_type)))
+        .filter_map(|entity| world.get::<Transform>(entity).map(|transform| (entity, transform.position)))
+        .collect();
+    riders.sort_by_key(|&(entity, _)| network_order(world, entity));
+    
+    for (rider, position) in riders {
+        let nearest = transports
+            .iter_mut()
+            .filter(|(_, _, room)| *room > 0)
+            .min_by(|a, b| {
+                let distance_a = (a.1 - position).length_squared();
+                let distance_b = (b.1 - position).length_squared();
+                distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal)
+            });
+        let (transport, _, room) = match nearest {
+            Some(transport) => transport,
+            None => {
+                world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+                    player_id,
+                    "Not enough room in the selected transports".to_string(),
+                ));
+                return;
+            }
+        };
+        *room -= 1;
+        
+        let mut entity = world.entity_mut(rider);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<HealTarget>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.remove::<GarrisonTarget>();
+        entity.insert(BoardTarget { target_entity: *transport });
+    }
+}
+
+/// Set down everyone aboard the player's selected transports
+fn unload_selection(world: &mut World, player_id: u8) {
+    for transport in selected_transports(world, player_id) {
+        transport::unload(world, transport, None);
+    }
+}
+
+/// Set down the passenger in one cargo slot of the player's selected transport
+fn unload_slot(world: &mut World, player_id: u8, slot: usize) {
+    let transport = selected_transports(world, player_id)
+        .into_iter()
+        .find(|&entity| world.get::<Cargo>(entity).map_or(false, |cargo| slot < cargo.passengers.len()));
+    if let Some(transport) = transport {
+        transport::unload(world, transport, Some(slot));
+    }
+}
+
+/// Order the player's selected infantry to walk into one of the player's buildings
+fn garrison_building(world: &mut World, player_id: u8, building: Entity) {
+    let owned = world.get::<Owner>(building).map_or(false, |owner| owner.0 == player_id);
+    let position = match (world.get::<Garrison>(building), world.get::<Transform>(building)) {
+        (Some(_), Some(transform)) if owned => transform.position,
+        _ => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if world.get::<Unit>(entity).map_or(true, |unit| !garrison::can_garrison(unit.unit_type)) {
+            continue;
+        }
+        
+        // The garrison system walks the unit to the building
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<HealTarget>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.remove::<BoardTarget>();
+        entity.insert(GarrisonTarget { target_entity: building });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitMove, position);
+}
+
+/// The player's selected buildings that take occupants, sorted by network ID
+fn selected_garrisons(world: &mut World, player_id: u8) -> Vec<Entity> {
+    let mut query = world.query_filtered::<(Entity, &Owner), (With<Selected>, With<Garrison>)>();
+    let mut buildings: Vec<Entity> = query
+        .iter(world)
+        .filter(|(_, owner)| owner.0 == player_id)
+        .map(|(entity, _)| entity)
+        .collect();
+    buildings.sort_by_key(|&entity| network_order(world, entity));
+    buildings
+}
+
+/// Send everyone inside the player's selected buildings back out
+fn ungarrison_selection(world: &mut World, player_id: u8) {
+    for building in selected_garrisons(world, player_id) {
+        garrison::eject(world, building, None);
+    }
+}
+
+/// Send the occupant in one garrison slot of the player's selected building back out
+fn ungarrison_slot(world: &mut World, player_id: u8, slot: usize) {
+    let building = selected_garrisons(world, player_id)
+        .into_iter()
+        .find(|&entity| world.get::<Garrison>(entity).map_or(false, |garrison| slot < garrison.occupants.len()));
+    if let Some(building) = building {
+        garrison::eject(world, building, Some(slot));
+    }
+}
+
+/// Whether another player is the player themself or an ally
+fn is_allied(world: &World, player_id: u8, other: u8) -> bool {
+    world.get_resource::<PlayerInfo>().map_or(player_id == other, |info| info.are_allies(player_id, other))
+}
+
+/// Whether a position is outside the fog of war for the player
+fn is_visible_to(world: &World, player_id: u8, position: Vec2) -> bool {
+    world
+        .get_resource::<GameMap>()
+        .map_or(false, |game_map| map::is_position_visible(game_map, player_id, position, pathfinding::PATH_GRID_SIZE))
+}
+
+/// Send the player's selected workers to harvest the resource node at a clicked world position
+fn gather_at(world: &mut World, player_id: u8, position: Vec2) {
+    let node = match resource_node_at(world, position) {
+        Some((node, resource_type, node_position)) => {
+            if !buildings::can_harvest(resource_type, node_position, player_id, &finished_extractors(world)) {
+                world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+                    player_id,
+                    "Build a Resource Collector on this gas to harvest it".to_string(),
+                ));
+                return;
+            }
+            node
+        }
+        None => return,
+    };
+    
+    for entity in selected_movers(world, player_id) {
+        if world.get::<Unit>(entity).map_or(true, |unit| unit.unit_type != UnitType::Worker) {
+            continue;
+        }
+        
+        // The resource collection system walks the worker there, dropping off any load of another kind first
+        let mut entity = world.entity_mut(entity);
+        entity.remove::<BuildTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<FormationMove>();
+        entity.remove::<HoldPosition>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        entity.insert(HarvestTarget { target_entity: node, returning: false });
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitMove, position);
+}
+
+/// Order the player's selected units to use an ability: every ready caster uses a self-cast ability,
+/// otherwise the ready caster nearest the target does
+fn use_ability(world: &mut World, player_id: u8, command: &AbilityCommand) {
+    let ability = match data::game_data().ability(command.ability_id) {
+        Some(ability) => ability,
+        None => {
+            warn!("Unknown ability {}", command.ability_id);
+            return;
+        }
+    };
+    
+    // Selected units that know the ability, and what keeps each from casting it
+    let mut casters = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Unit, &Transform, &Owner, &Caster), With<Selected>>();
+    for (entity, unit, transform, owner, caster) in query.iter(world) {
+        if owner.0 == player_id && unit.health > 0.0 && ability.unit_types.contains(&unit.unit_type) {
+            casters.push((entity, transform.position, abilities::cast_blocker(caster, ability)));
+        }
+    }
+    if casters.is_empty() {
+        return;
+    }
+    
+    // Query order isn't guaranteed; sort so every machine picks the same caster
+    casters.sort_by_key(|&(entity, _, _)| network_order(world, entity));
+    
+    let target = match ability.target {
+        AbilityTarget::Point => match command.target_position {
+            Some(position) => CastTarget::Point(position),
+            None => return,
+        },
+        AbilityTarget::Entity => match ability_target_entity(world, player_id, ability, command) {
+            Some(target) => CastTarget::Entity(target),
+            None => {
+                world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+                    player_id,
+                    format!("{} can't target that", ability.name),
+                ));
+                return;
+            }
+        },
+        AbilityTarget::SelfCast | AbilityTarget::Untargeted => CastTarget::Caster,
+    };
+    
+    let ready: Vec<(Entity, Vec2)> = casters
+        .iter()
+        .filter(|(_, _, blocker)| blocker.is_none())
+        .map(|(entity, position, _)| (*entity, *position))
+        .collect();
+    if ready.is_empty() {
+        let reason = casters[0].2.unwrap_or_default();
+        world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((player_id, reason.to_string()));
+        return;
+    }
+    
+    let chosen: Vec<Entity> = match target {
+        CastTarget::Caster if ability.target == AbilityTarget::SelfCast => ready.iter().map(|(entity, _)| *entity).collect(),
+        CastTarget::Caster => vec![ready[0].0],
+        CastTarget::Point(position) => vec![nearest_caster(&ready, position)],
+        CastTarget::Entity(target) => {
+            let position = world.get::<Transform>(target).map_or(Vec2::ZERO, |transform| transform.position);
+            vec![nearest_caster(&ready, position)]
+        }
+    };
+    
+    for entity in chosen {
+        let mut entity = world.entity_mut(entity);
+        
+        // Targeted casts may need a walk, which replaces the caster's other orders
+        if target != CastTarget::Caster {
+            entity.remove::<AttackTarget>();
+            entity.remove::<AttackMove>();
+            entity.remove::<Patrol>();
+            entity.remove::<HealTarget>();
+            entity.remove::<BuildTarget>();
+            entity.remove::<HarvestTarget>();
+            entity.remove::<FormationMove>();
+            entity.remove::<RepairTarget>();
+            entity.remove::<Follow>();
+        }
+        entity.insert(CastOrder { ability_id: ability.id, target });
+    }
+}
+
+/// Ready caster closest to a target; `ready` is sorted so ties go to the lowest network ID
+fn nearest_caster(ready: &[(Entity, Vec2)], position: Vec2) -> Entity {
+    ready
+        .iter()
+        .min_by(|a, b| {
+            let distance_a = (a.1 - position).length_squared();
+            let distance_b = (b.1 - position).length_squared();
+            distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(entity, _)| *entity)
+        .unwrap()
+}
+
+/// Unit or building an Entity-targeted ability was aimed at, by ID or under the clicked position
+fn ability_target_entity(world: &World, player_id: u8, ability: &AbilityData, command: &AbilityCommand) -> Option<Entity> {
+    let spatial_grid = world.get_resource::<SpatialGrid>()?;
+    let target = match (command.target_entity_id, command.target_position) {
+        (Some(network_id), _) => entities_of(world, &[network_id]).pop()?,
+        (None, Some(position)) => spatial_grid
+            .nearest(position, PICK_RADIUS + spatial_grid.max_radius(), |candidate| {
+                (candidate.position - position).length() <= candidate.radius + PICK_RADIUS &&
+                abilities::is_valid_target(world, player_id, ability, candidate.entity)
+            })?
+            .entity,
+        _ => return None,
+    };
+    
+    abilities::is_valid_target(world, player_id, ability, target).then_some(target)
+}
+
+/// Resource node with resources left closest to a clicked world position, with its type and position
+fn resource_node_at(world: &mut World, position: Vec2) -> Option<(Entity, ResourceType, Vec2)> {
+    let mut nodes = world.query::<(Entity, &Resource, &Transform)>();
+    nodes
+        .iter(world)
+        .filter(|(_, resource, transform)| resource.amount > 0.0 && (transform.position - position).length() <= GATHER_PICK_RADIUS)
+        .min_by(|a, b| {
+            let distance_a = (a.2.position - position).length_squared();
+            let distance_b = (b.2.position - position).length_squared();
+            distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0))
+        })
+        .map(|(node, resource, transform)| (node, resource.resource_type, transform.position))
+}
+
+/// Owner and position of every finished Resource Collector, the extractors gas is harvested through
+fn finished_extractors(world: &mut World) -> Vec<(u8, Vec2)> {
+    let mut query = world.query::<(&Building, &Transform, &Owner)>();
+    query.iter(world)
+        .filter(|(building, _, _)| {
+            building.building_type == BuildingType::ResourceCollector &&
+            building.construction_progress.is_none() &&
+            building.health > 0.0
+        })
+        .map(|(_, transform, owner)| (owner.0, transform.position))
+        .collect()
+}
+
+/// The player's selected units that can move
+fn selected_movers(world: &mut World, player_id: u8) -> Vec<Entity> {
+    let mut units = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Owner), (With<Selected>, With<Unit>)>();
+    for (entity, owner) in query.iter(world) {
+        if owner.0 == player_id {
+            units.push(entity);
+        }
+    }
+    units
+}
+
+/// The player gives up. Allies still in the game take their units and buildings in turn and share
+/// their stockpile, which leaves them without a headquarters and so eliminated; without allies
+/// everything they own is destroyed
+fn surrender(world: &mut World, player_id: u8) {
+    let mut owned = world.query::<(Entity, &Owner)>();
+    let mut owners: Vec<(Entity, u8)> = owned.iter(world).map(|(entity, owner)| (entity, owner.0)).collect();
+    owners.sort_by_key(|&(entity, _)| network_order(world, entity));
+    
+    let allies = world.get_resource::<PlayerInfo>().map(|info| info.allies_of(player_id)).unwrap_or_default();
+    let heirs: Vec<u8> = allies
+        .into_iter()
+        .filter(|&ally| ally != player_id && owners.iter().any(|(_, owner)| *owner == ally))
+        .collect();
+    let entities: Vec<Entity> = owners.iter().filter(|(_, owner)| *owner == player_id).map(|(entity, _)| *entity).collect();
+    
+    // Their selection and control groups don't pass on
+    replace_selection(world, player_id, Vec::new());
+    if let Some(mut control_groups) = world.get_resource_mut::<ControlGroups>() {
+        control_groups.groups.retain(|(owner, _), _| *owner != player_id);
+    }
+    
+    if heirs.is_empty() {
+        // Destroyed through the damage system, so deaths are counted as usual
+        for entity in entities {
+            let health = world.get::<Unit>(entity).map(|unit| unit.health)
+                .or_else(|| world.get::<Building>(entity).map(|building| building.health));
+            if let Some(health) = health.filter(|health| *health > 0.0) {
+                world.send_event(DamageEvent {
+                    attacker: entity,
+                    attacker_owner: None,
+                    target: entity,
+                    amount: health,
+                    damage_type: None,
+                });
+            }
+        }
+    } else {
+        for (index, entity) in entities.into_iter().enumerate() {
+            world.entity_mut(entity).insert(Owner(heirs[index % heirs.len()]));
+        }
+        
+        if let Some(mut player_resources) = world.get_resource_mut::<PlayerResources>() {
+            let stockpile: Vec<(ResourceType, f32)> = player_resources.resources
+                .iter()
+                .filter(|((owner, _), _)| *owner == player_id)
+                .map(|(&(_, resource_type), &amount)| (resource_type, amount))
+                .collect();
+            for (resource_type, amount) in stockpile {
+                player_resources.resources.insert((player_id, resource_type), 0.0);
+                for &heir in &heirs {
+                    *player_resources.resources.entry((heir, resource_type)).or_insert(0.0) += amount / heirs.len() as f32;
+                }
+            }
+        }
+    }
+    
+    // Tell everyone, the surrendering player included
+    let name = world
+        .get_resource::<PlayerInfo>()
+        .and_then(|info| info.player_names.get(&player_id).cloned())
+        .unwrap_or_else(|| format!("Player {}", player_id + 1));
+    let message = if heirs.is_empty() {
+        format!("{} surrendered", name)
+    } else {
+        format!("{} surrendered; their forces join their allies", name)
+    };
+    let players: BTreeSet<u8> = owners.iter().map(|(_, owner)| *owner).chain(std::iter::once(player_id)).collect();
+    let mut player_alerts = world.get_resource_or_insert_with(PlayerAlerts::default);
+    for other in players {
+        player_alerts.alerts.push((other, message.clone()));
+    }
+}
+
+/// Drop every order of the player's selected units; with `hold` they also stay put until ordered to move
+fn stop_units(world: &mut World, player_id: u8, hold: bool) {
+    for entity in selected_movers(world, player_id) {
+        let mut entity = world.entity_mut(entity);
+        
+        if let Some(mut movement) = entity.get_mut::<Movement>() {
+            movement.path.clear();
+            movement.path_index = 0;
+            movement.target = None;
+            movement.velocity = Vec2::ZERO;
+        }
+        
+        entity.remove::<AttackTarget>();
+        entity.remove::<AttackMove>();
+        entity.remove::<Patrol>();
+        entity.remove::<HealTarget>();
+        entity.remove::<BuildTarget>();
+        entity.remove::<HarvestTarget>();
+        entity.remove::<FormationMove>();
+        entity.remove::<CastOrder>();
+        entity.remove::<RepairTarget>();
+        entity.remove::<Follow>();
+        
+        if hold {
+            entity.insert(HoldPosition);
+        } else {
+            entity.remove::<HoldPosition>();
+        }
+    }
+}
+
+/// Set how the player's selected units react to enemies
+fn set_stance(world: &mut World, player_id: u8, stance: Stance) {
+    for entity in selected_movers(world, player_id) {
+        world.entity_mut(entity).insert(stance);
+    }
+}
+
+/// Switch the formation the player's group orders use
+fn set_formation(world: &mut World, player_id: u8, formation: Formation) {
+    world.get_resource_or_insert_with(PlayerFormations::default).formations.insert(player_id, formation);
+    world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+        player_id,
+        format!("Formation: {}", formation.name()),
+    ));
+}
+
+/// Store the player's current selection in a control group
+fn assign_control_group(world: &mut World, player_id: u8, group: u8) {
+    let mut members = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Owner), With<Selected>>();
+    for (entity, owner) in query.iter(world) {
+        if owner.0 == player_id {
+            members.push(entity);
+        }
+    }
+    
+    let mut control_groups = world.get_resource_or_insert_with(ControlGroups::default);
+    if members.is_empty() {
+        control_groups.groups.remove(&(player_id, group));
+    } else {
+        control_groups.groups.insert((player_id, group), members);
+    }
+}
+
+/// Replace the player's selection with the living members of a control group
+fn select_control_group(world: &mut World, player_id: u8, group: u8) {
+    let members = match world.get_resource::<ControlGroups>().and_then(|groups| groups.groups.get(&(player_id, group))) {
+        Some(members) => members.clone(),
+        None => return,
+    };
+    
+    // Forget units that have died since the group was assigned
+    let alive: Vec<Entity> = members.into_iter().filter(|entity| world.get_entity(*entity).is_some()).collect();
+    if alive.is_empty() {
+        if let Some(mut control_groups) = world.get_resource_mut::<ControlGroups>() {
+            control_groups.groups.remove(&(player_id, group));
+        }
+        return;
+    }
+    
+    if let Some(mut control_groups) = world.get_resource_mut::<ControlGroups>() {
+        control_groups.groups.insert((player_id, group), alive.clone());
+    }
+    
+    // Members aboard a transport or inside a building stay in the group but can't be selected until they come out
+    let on_map = alive.into_iter().filter(|entity| world.get::<Transform>(*entity).is_some()).collect();
+    replace_selection(world, player_id, on_map);
+}
+
+/// Select the player's entity under a clicked world position
+fn select_at(world: &mut World, player_id: u8, position: Vec2) {
+    let picked = world.get_resource::<SpatialGrid>().and_then(|spatial_grid| {
+        spatial_grid.nearest(position, PICK_RADIUS + spatial_grid.max_radius(), |candidate| {
+            (candidate.position - position).length() <= candidate.radius + PICK_RADIUS &&
+            is_selectable_by(world, candidate.entity, player_id)
+        })
+    });
+    
+    replace_selection(world, player_id, picked.map(|entry| entry.entity).into_iter().collect());
+}
+
+/// Select the player's units inside a dragged rectangle, or their buildings if it holds no units
+fn select_in_rect(world: &mut World, player_id: u8, start: Vec2, end: Vec2) {
+    let selectable: Vec<Entity> = match world.get_resource::<SpatialGrid>() {
+        Some(spatial_grid) => spatial_grid
+            .query_rect(start.min(end), start.max(end))
+            .into_iter()
+            .map(|entry| entry.entity)
+            .filter(|entity| is_selectable_by(world, *entity, player_id))
+            .collect(),
+        None => Vec::new(),
+    };
+    
+    let (units, buildings): (Vec<Entity>, Vec<Entity>) = selectable
+        .into_iter()
+        .partition(|entity| world.get::<Unit>(*entity).is_some());
+    let picked = if units.is_empty() { buildings } else { units };
+    
+    replace_selection(world, player_id, picked);
+}
+
+/// Select every entity of the clicked entity's type that lies in the visible area
+fn select_same_type(world: &mut World, player_id: u8, position: Vec2, view_min: Vec2, view_max: Vec2) {
+    let clicked = world.get_resource::<SpatialGrid>().and_then(|spatial_grid| {
+        spatial_grid.nearest(position, PICK_RADIUS + spatial_grid.max_radius(), |candidate| {
+            (candidate.position - position).length() <= candidate.radius + PICK_RADIUS &&
+            is_selectable_by(world, candidate.entity, player_id)
+        })
+    });
+    let kind = match clicked.and_then(|entry| selection_kind(world, entry.entity)) {
+        Some(kind) => kind,
+        None => return,
+    };
+    
+    let picked: Vec<Entity> = match world.get_resource::<SpatialGrid>() {
+        Some(spatial_grid) => spatial_grid
+            .query_rect(view_min.min(view_max), view_min.max(view_max))
+            .into_iter()
+            .map(|entry| entry.entity)
+            .filter(|entity| is_selectable_by(world, *entity, player_id) && selection_kind(world, *entity) == Some(kind))
+            .collect(),
+        None => Vec::new(),
+    };
+    
+    replace_selection(world, player_id, picked);
+}
+
+/// Subgroup an entity falls into when selected
+pub fn selection_kind(world: &World, entity: Entity) -> Option<SelectionKind> {
+    if let Some(unit) = world.get::<Unit>(entity) {
+        return Some(SelectionKind::Unit(unit.unit_type));
+    }
+    world.get::<Building>(entity).map(|building| SelectionKind::Building(building.building_type))
+}
+
+/// Whether the player may select an entity
+fn is_selectable_by(world: &World, entity: Entity, player_id: u8) -> bool {
+    world.get::<Selectable>(entity).is_some() &&
+        world.get::<Owner>(entity).map(|owner| owner.0 == player_id).unwrap_or(false)
+}
+
+/// Make `entities` the player's selection, deselecting everything else they own
+fn replace_selection(world: &mut World, player_id: u8, entities: Vec<Entity>) {
+    let mut previously_selected = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Owner), With<Selected>>();
+    for (entity, owner) in query.iter(world) {
+        if owner.0 == player_id {
+            previously_selected.push(entity);
+        }
+    }
+    for entity in previously_selected {
+        world.entity_mut(entity).remove::<Selected>();
+    }
+    
+    for entity in &entities {
+        world.entity_mut(*entity).insert(Selected);
+    }
+    
+    if let Some(position) = entities.first().and_then(|entity| world.get::<Transform>(*entity)).map(|t| t.position) {
+        world.get_resource_or_insert_with(AudioEvents::default).play_for(player_id, GameSoundType::UnitSelect, position);
+    }
+}
+
+/// Average position of a control group's living members, used to center the camera on it
+pub fn control_group_center(world: &World, player_id: u8, group: u8) -> Option<Vec2> {
+    let members = world.get_resource::<ControlGroups>()?.groups.get(&(player_id, group))?;
+    let positions: Vec<Vec2> = members
+        .iter()
+        .filter_map(|entity| world.get::<Transform>(*entity).map(|transform| transform.position))
+        .collect();
+    
+    if positions.is_empty() {
+        return None;
+    }
+    Some(positions.iter().copied().sum::<Vec2>() / positions.len() as f32)
+}
+
+/// The player's idle workers, sorted by entity
+pub fn idle_workers(world: &mut World, player_id: u8) -> Vec<Entity> {
+    let mut workers: Vec<Entity> = world
+        .query_filtered::<(Entity, &Owner), With<IdleWorker>>()
+        .iter(world)
+        .filter(|(_, owner)| owner.0 == player_id)
+        .map(|(entity, _)| entity)
+        .collect();
+    workers.sort();
+    workers
+}
+
+/// Idle worker the idle worker button selects next: the one after the selected idle worker, wrapping around
+pub fn next_idle_worker(world: &mut World, player_id: u8) -> Option<Entity> {
+    let workers = idle_workers(world, player_id);
+    let current = workers.iter().rev().find(|&&worker| world.get::<Selected>(worker).is_some()).copied();
+    match current {
+        Some(current) => workers.iter().find(|&&worker| worker > current).or(workers.first()).copied(),
+        None => workers.first().copied(),
+    }
+}
+
+/// Control groups of the player that contain at least one selected entity
+pub fn selected_control_groups(world: &World, player_id: u8) -> Vec<u8> {
+    let control_groups = match world.get_resource::<ControlGroups>() {
+        Some(control_groups) => control_groups,
+        None => return Vec::new(),
+    };
+    
+    let mut groups: Vec<u8> = control_groups.groups
+        .iter()
+        .filter(|((owner, _), members)| {
+            *owner == player_id && members.iter().any(|entity| world.get::<Selected>(*entity).is_some())
+        })
+        .map(|((_, group), _)| *group)
+        .collect();
+    groups.sort_unstable();
+    groups
+}
+
+/// Point a unit's movement at a destination using a pathfinding route
+pub fn set_path(movement: &mut Movement, start: Vec2, destination: Vec2, grid: &PathfindingGrid, unit_radius: f32) {
+    match pathfinding::request_path(start, destination, grid, unit_radius) {
+        Some(path) => {
+            movement.path = path;
+            movement.path_index = 0;
+            movement.target = Some(destination);
+        }
+        None => {
+            // Target unreachable - stop instead of walking through obstacles
+            movement.path.clear();
+            movement.path_index = 0;
+            movement.target = None;
+        }
+    }
+}
+
+/// Positions and footprints of every building in the world
+pub fn existing_building_footprints(world: &mut World) -> Vec<(Vec2, Vec2)> {
+    let mut query = world.query::<(&Building, &Transform)>();
+    query.iter(world)
+        .map(|(building, transform)| (transform.position, BuildingData::get(building.building_type).size))
+        .collect()
+}
+
+/// Lay down a construction site and send the player's selected workers to build it
+fn place_building(world: &mut World, player_id: u8, building_type: BuildingType, position: Vec2) {
+    if BuildingData::is_upgrade(building_type) {
+        info!("{:?} can only be reached by upgrading", building_type);
+        return;
+    }
+    
+    // Wall pieces line up on the pathfinding cells so they join whatever wall they're placed against
+    let position = if buildings::is_wall_piece(building_type) {
+        buildings::snap_wall_piece(position)
+    } else {
+        position
+    };
+    
+    let existing = existing_building_footprints(world);
+    
+    let valid = match world.get_resource::<GameMap>() {
+        Some(map) => buildings::is_valid_placement(building_type, position, player_id, map, &existing),
+        None => false,
+    };
+    if !valid {
+        info!("Cannot place {:?} at {:?}", building_type, position);
+        let on_gas = world.get_resource::<GameMap>().map_or(false, |map| buildings::gas_node_under(position, map).is_some());
+        if building_type == BuildingType::ResourceCollector && !on_gas {
+            world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+                player_id,
+                "Resource Collectors must be built on a gas node".to_string(),
+            ));
+        }
+        return;
+    }
+    
+    // Pay for the building up front
+    if !pay_for_buildings(world, player_id, building_type, 1) {
+        return;
+    }
+    
+    spawn_construction_site(world, player_id, building_type, position);
+    assign_builders(world, player_id, building_type, position);
+}
+
+/// Lay down a row of wall segments from `start` to `end`, skipping cells that can't be built on, and send
+/// the player's selected workers to the first; construction_system walks them along the rest of the row
+fn place_wall(world: &mut World, player_id: u8, start: Vec2, end: Vec2) {
+    let existing = existing_building_footprints(world);
+    let segments: Vec<Vec2> = match world.get_resource::<GameMap>() {
+        Some(map) => buildings::wall_segments(start, end)
+            .into_iter()
+            .filter(|&position| buildings::is_valid_placement(BuildingType::Wall, position, player_id, map, &existing))
+            .collect(),
+        None => return,
+    };
+    if segments.is_empty() {
+        info!("Cannot place a wall from {:?} to {:?}", start, end);
+        return;
+    }
+    
+    // The whole row is paid for up front
+    if !pay_for_buildings(world, player_id, BuildingType::Wall, segments.len()) {
+        return;
+    }
+    
+    for &position in &segments {
+        spawn_construction_site(world, player_id, BuildingType::Wall, position);
+    }
+    assign_builders(world, player_id, BuildingType::Wall, segments[0]);
+}
+
+/// Take the cost of `count` buildings of a type from the player; false, taking nothing, if they can't afford them
+fn pay_for_buildings(world: &mut World, player_id: u8, building_type: BuildingType, count: usize) -> bool {
+    let building_data = BuildingData::get(building_type);
+    if let Some(mut player_resources) = world.get_resource_mut::<PlayerResources>() {
+        let affordable = building_data.costs.iter().all(|(res_type, cost)| {
+            player_resources.resources.get(&(player_id, *res_type)).copied().unwrap_or(0.0) >= cost * count as f32
+        });
+        if !affordable {
+            info!("Not enough resources to build {:?}", building_type);
+            return false;
+        }
+        
+        for (res_type, cost) in &building_data.costs {
+            if let Some(amount) = player_resources.resources.get_mut(&(player_id, *res_type)) {
+                *amount -= cost * count as f32;
+            }
+        }
+    }
+    record_spending(world, player_id, building_data.costs.values().sum::<f32>() * count as f32);
+    true
+}
+
+/// Spawn a construction site; it starts with a sliver of health and grows as workers build it
+fn spawn_construction_site(world: &mut World, player_id: u8, building_type: BuildingType, position: Vec2) {
+    let building_data = BuildingData::get(building_type);
+    let mut site = world.spawn((
+        Building {
+            building_type,
+            health: building_data.health * 0.1,
+            max_health: building_data.health,
+            production_queue: VecDeque::new(),
+            production_progress: None,
+            construction_progress: Some(0.0),
+            rally_point: None,
+        },
+        Transform {
+            position,
+            rotation: 0.0,
+            scale: building_data.size,
+        },
+        Owner(player_id),
+        Collider {
+            radius: building_data.size.max_element() * 0.5,
+            collision_layer: 2, // Building layer
+            collision_mask: 1 | 2,
+        },
+        ConstructionSite {
+            building_type,
+            progress: 0.0,
+        },
+        Selectable,
+    ));
+    if building_type == BuildingType::Gate {
+        site.insert(Gate);
+    }
+}
+
+/// Send the player's selected workers to build the site at a position
+fn assign_builders(world: &mut World, player_id: u8, building_type: BuildingType, position: Vec2) {
+    let has_grid = world.get_resource::<GameMap>().map_or(false, |map| map.grid_for(player_id).is_some());
+    let mut workers = Vec::new();
+    let mut query = world.query_filtered::<(Entity, &Unit, &Owner), With<Selected>>();
+    for (entity, unit, owner) in query.iter(world) {
+        if owner.0 == player_id && unit.unit_type == UnitType::Worker {
+            workers.push(entity);
+        }
+    }
+    
+    for worker in workers {
+        world.entity_mut(worker).insert(BuildTarget {
+            position,
+            building_type,
+        });
+        world.entity_mut(worker).remove::<HoldPosition>();
+        world.entity_mut(worker).remove::<RepairTarget>();
+        
+        let entity = world.entity(worker);
+        let start = match entity.get::<Transform>() {
+            Some(transform) => transform.position,
+            None => continue,
+        };
+        let unit_radius = entity.get::<Collider>().map(|c| c.radius).unwrap_or(0.0);
+        if has_grid && entity.contains::<Movement>() {
+            queue_path(world, worker, player_id, start, position, unit_radius);
+        }
+    }
+}
+
+/// Pay for a unit and queue it at the player's least busy building that can produce it.
+/// Selected buildings are preferred; with none selected (e.g. computer players) any owned building is used.
+fn train_unit(world: &mut World, player_id: u8, unit_type: UnitType) {
+    let mut selected_producers = Vec::new();
+    let mut other_producers = Vec::new();
+    let mut query = world.query::<(Entity, &Building, &Owner, Option<&Selected>)>();
+    for (entity, building, owner, selected) in query.iter(world) {
+        let can_produce = BuildingData::get(building.building_type).can_produce.contains(&unit_type);
+        if owner.0 != player_id || !can_produce || building.construction_progress.is_some() ||
+           building.production_queue.len() >= MAX_PRODUCTION_QUEUE {
+            continue;
+        }
+        
+        let producers = if selected.is_some() { &mut selected_producers } else { &mut other_producers };
+        producers.push((entity, building.production_queue.len()));
+    }
+    
+    let producers = if selected_producers.is_empty() { other_producers } else { selected_producers };
+    let producer = match producers.into_iter().min_by_key(|&(entity, queue_length)| (queue_length, entity)) {
+        Some((entity, _)) => entity,
+        None => {
+            info!("No building available to train {:?}", unit_type);
+            return;
+        }
+    };
+    
+    // Queued units hold their supply, so a full supply cap blocks training
+    let (used_supply, max_supply) = player_supply(world, player_id);
+    if used_supply + unit_supply_cost(unit_type) > max_supply {
+        info!("Not enough supply to train {:?} ({}/{})", unit_type, used_supply, max_supply);
+        world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+            player_id,
+            format!("Not enough supply ({}/{}) - build a Supply Depot", used_supply, max_supply),
+        ));
+        return;
+    }
+    
+    // Validate and pay up front; the cost is refunded if the unit is cancelled
+    let affordable = match (world.get_resource::<PlayerResources>(), world.get_resource::<TechState>()) {
+        (Some(player_resources), Some(tech_state)) => can_train_unit(unit_type, player_resources, player_id, tech_state),
+        _ => false,
+    };
+    if !affordable {
+        info!("Cannot train {:?}", unit_type);
+        return;
+    }
+    
+    if let Some(mut player_resources) = world.get_resource_mut::<PlayerResources>() {
+        for (res_type, cost) in unit_costs(unit_type) {
+            if let Some(amount) = player_resources.resources.get_mut(&(player_id, res_type)) {
+                *amount -= cost;
+            }
+        }
+    }
+    record_spending(world, player_id, unit_costs(unit_type).values().sum());
+    
+    if let Some(mut building) = world.get_mut::<Building>(producer) {
+        building.production_queue.push_back(unit_type);
+    }
+}
+
+/// Pay for and start upgrading the player's selected building into its improved variant.
+/// With several selected, the first (by entity) that isn't already upgrading is upgraded.
+fn upgrade_building(world: &mut World, player_id: u8) {
+    let mut query = world.query_filtered::<(Entity, &Building, &Owner, Option<&Upgrading>), With<Selected>>();
+    let upgrade = query
+        .iter(world)
+        .filter(|(_, building, owner, upgrading)| {
+            owner.0 == player_id && upgrading.is_none() && building.construction_progress.is_none()
+        })
+        .filter_map(|(entity, building, _, _)| {
+            BuildingData::get(building.building_type).upgrades_to.map(|target| (entity, target))
+        })
+        .min_by_key(|&(entity, _)| entity);
+    let (building, target) = match upgrade {
+        Some(upgrade) => upgrade,
+        None => return,
+    };
+    let target_data = BuildingData::get(target);
+    
+    let missing_tech = world
+        .get_resource::<TechState>()
+        .and_then(|tech_state| buildings::missing_tech(target, tech_state, player_id));
+    if let Some(tech_type) = missing_tech {
+        world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+            player_id,
+            format!("Research {} to upgrade to {}", TechData::get(tech_type).name, target_data.name),
+        ));
+        return;
+    }
+    
+    if let Some(mut player_resources) = world.get_resource_mut::<PlayerResources>() {
+        let affordable = target_data.costs.iter().all(|(res_type, cost)| {
+            player_resources.resources.get(&(player_id, *res_type)).copied().unwrap_or(0.0) >= *cost
+        });
+        if !affordable {
+            info!("Not enough resources to upgrade to {:?}", target);
+            return;
+        }
+        
+        for (res_type, cost) in &target_data.costs {
+            if let Some(amount) = player_resources.resources.get_mut(&(player_id, *res_type)) {
+                *amount -= cost;
+            }
+        }
+    }
+    record_spending(world, player_id, target_data.costs.values().sum());
+    
+    world.entity_mut(building).insert(Upgrading { target, progress: 0.0 });
+}
+
+/// Remove a unit from the selected building's production queue and refund its cost
+fn cancel_training(world: &mut World, player_id: u8, slot: usize) {
+    let mut query = world.query_filtered::<(Entity, &Building, &Owner), With<Selected>>();
+    let building_entity = query
+        .iter(world)
+        .filter(|(_, building, owner)| owner.0 == player_id && slot < building.production_queue.len())
+        .map(|(entity, _, _)| entity)
+        .min();
+    let building_entity = match building_entity {
+        Some(entity) => entity,
+        None => return,
+    };
+    
+    let unit_type = {
+        let mut building = match world.get_mut::<Building>(building_entity) {
+            Some(building) => building,
+            None => return,
+        };
+        let unit_type = building.production_queue.remove(slot);
+        
+        // Cancelling the unit in production throws away its progress
+        if slot == 0 {
+            building.production_progress = None;
+        }
+        unit_type
+    };
+    
+    if let (Some(unit_type), Some(mut player_resources)) = (unit_type, world.get_resource_mut::<PlayerResources>()) {
+        for (res_type, cost) in unit_costs(unit_type) {
+            *player_resources.resources.entry((player_id, res_type)).or_insert(0.0) += cost;
+        }
+    }
+    if let Some(unit_type) = unit_type {
+        record_spending(world, player_id, -unit_costs(unit_type).values().sum::<f32>());
+    }
+}
+
+/// Count resources paid (or, when negative, refunded) towards the player's statistics
+fn record_spending(world: &mut World, player_id: u8, amount: f32) {
+    if let Some(mut stats) = world.get_resource_mut::<GameStats>() {
+        stats.resources_spent(player_id, amount);
+    }
+}
+
+/// Pay for a technology and queue it at the player's least busy research building
+fn research_tech(world: &mut World, player_id: u8, tech_type: TechType) {
+    // Researched, already queued, or missing prerequisites
+    let available = world
+        .get_resource::<TechState>()
+        .map(|tech_state| is_tech_available(tech_type, tech_state, player_id))
+        .unwrap_or(false);
+    if !available {
+        info!("{:?} is not available to research", tech_type);
+        return;
+    }
+    
+    let mut query = world.query::<(Entity, &Building, &Owner, Option<&ResearchQueue>, Option<&Selected>)>();
+    let researcher = query
+        .iter(world)
+        .filter(|(_, building, owner, _, _)| {
+            owner.0 == player_id &&
+            building.building_type == BuildingType::ResearchCenter &&
+            building.construction_progress.is_none()
+        })
+        .map(|(entity, _, _, research_queue, selected)| {
+            let queue_length = research_queue.map(|research_queue| research_queue.queue.len()).unwrap_or(0);
+            (entity, queue_length, selected.is_none())
+        })
+        .filter(|&(_, queue_length, _)| queue_length < MAX_PRODUCTION_QUEUE)
+        .min_by_key(|&(entity, queue_length, unselected)| (unselected, queue_length, entity))
+        .map(|(entity, _, _)| entity);
+    let researcher = match researcher {
+        Some(entity) => entity,
+        None => {
+            info!("No research building available for {:?}", tech_type);
+            return;
+        }
+    };
+    
+    let tech_data = TechData::get(tech_type);
+    if let Some(mut player_resources) = world.get_resource_mut::<PlayerResources>() {
+        let affordable = tech_data.costs.iter().all(|(res_type, cost)| {
+            player_resources.resources.get(&(player_id, *res_type)).copied().unwrap_or(0.0) >= *cost
+        });
+        if !affordable {
+            info!("Not enough resources to research {:?}", tech_type);
+            return;
+        }
+        
+        for (res_type, cost) in &tech_data.costs {
+            if let Some(amount) = player_resources.resources.get_mut(&(player_id, *res_type)) {
+                *amount -= cost;
+            }
+        }
+    }
+    record_spending(world, player_id, tech_data.costs.values().sum());
+    
+    // Queued techs count as in progress so they can't be ordered twice
+    if let Some(mut tech_state) = world.get_resource_mut::<TechState>() {
+        tech_state.in_progress.insert((player_id, tech_type), 0.0);
+    }
+    
+    let mut entity = world.entity_mut(researcher);
+    match entity.get_mut::<ResearchQueue>() {
+        Some(mut research_queue) => research_queue.queue.push_back(tech_type),
+        None => {
+            entity.insert(ResearchQueue { queue: VecDeque::from([tech_type]) });
+        }
+    }
+}
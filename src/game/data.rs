@@ -0,0 +1,441 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+use crate::ecs::components::{AnimationState, BuildingType, ResourceType, UnitType};
+use crate::ecs::resources::TechType;
+use crate::ecs::systems::combat::WeaponData;
+use crate::game::abilities::{AbilityData, AbilityEffect, AbilityTarget};
+use crate::game::animation::AnimationData;
+use crate::game::buildings::BuildingData;
+use crate::game::tech::TechData;
+
+/// Directory the definition files are read from at startup
+pub const DEFAULT_DATA_DIR: &str = "assets/data";
+
+const UNITS_FILE: &str = "units.ron";
+const BUILDINGS_FILE: &str = "buildings.ron";
+const TECHS_FILE: &str = "techs.ron";
+const ABILITIES_FILE: &str = "abilities.ron";
+const ANIMATIONS_FILE: &str = "animations.ron";
+
+/// Definitions loaded at startup, shared by every system
+static GAME_DATA: OnceLock<GameData> = OnceLock::new();
+
+/// Stats, costs and weapon of a unit type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitData {
+    pub unit_type: UnitType,
+    pub name: String,
+    pub health: f32,
+    pub attack_damage: f32,
+    pub attack_range: f32,  // Heal range for healers
+    pub attack_speed: f32,  // Heal speed for healers
+    pub movement_speed: f32,
+    pub sight_range: f32,
+    pub collider_radius: f32,
+    pub train_time: f32,    // Seconds
+    pub supply: u32,
+    pub costs: HashMap<ResourceType, f32>,
+    pub weapon: Option<WeaponData>,
+    #[serde(default)]
+    pub cargo_capacity: u32, // Units a transport can carry; 0 for everything else
+}
+
+/// Problem found while loading the definition files
+#[derive(Debug, Error)]
+pub enum DataError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: ron::error::SpannedError,
+    },
+    #[error("{file}: {name} is defined more than once")]
+    Duplicate { file: &'static str, name: String },
+    #[error("{file}: no definition for {name}")]
+    Missing { file: &'static str, name: String },
+    #[error("{file}: {name}: {reason}")]
+    Invalid {
+        file: &'static str,
+        name: String,
+        reason: String,
+    },
+    #[error("game data is already loaded")]
+    AlreadyLoaded,
+}
+
+/// Registries of unit, building, tech, ability and animation definitions
+pub struct GameData {
+    units: HashMap<UnitType, UnitData>,
+    buildings: HashMap<BuildingType, BuildingData>,
+    techs: HashMap<TechType, TechData>,
+    abilities: HashMap<u8, AbilityData>,
+    animations: HashMap<UnitType, AnimationData>,
+}
+
+impl GameData {
+    /// Load and validate every definition file in `dir`
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, DataError> {
+        let dir = dir.as_ref();
+        
+        let data = Self {
+            units: registry(UNITS_FILE, read_definitions(dir, UNITS_FILE)?, |unit: &UnitData| unit.unit_type)?,
+            buildings: registry(BUILDINGS_FILE, read_definitions(dir, BUILDINGS_FILE)?, |building: &BuildingData| {
+                building.building_type
+            })?,
+            techs: registry(TECHS_FILE, read_definitions(dir, TECHS_FILE)?, |tech: &TechData| tech.tech_type)?,
+            abilities: registry(ABILITIES_FILE, read_definitions(dir, ABILITIES_FILE)?, |ability: &AbilityData| ability.id)?,
+            animations: registry(ANIMATIONS_FILE, read_definitions(dir, ANIMATIONS_FILE)?, |animation: &AnimationData| {
+                animation.unit_type
+            })?,
+        };
+        data.validate()?;
+        
+        Ok(data)
+    }
+    
+    pub fn unit(&self, unit_type: UnitType) -> &UnitData {
+        // Validation guarantees every type has an entry
+        &self.units[&unit_type]
+    }
+    
+    pub fn building(&self, building_type: BuildingType) -> &BuildingData {
+        &self.buildings[&building_type]
+    }
+    
+    pub fn tech(&self, tech_type: TechType) -> &TechData {
+        &self.techs[&tech_type]
+    }
+    
+    /// Ability with the given ID; `None` for IDs that aren't defined, which can arrive in commands
+    pub fn ability(&self, ability_id: u8) -> Option<&AbilityData> {
+        self.abilities.get(&ability_id)
+    }
+    
+    /// Sprite sheet layout and clips of a unit type
+    pub fn animation(&self, unit_type: UnitType) -> &AnimationData {
+        &self.animations[&unit_type]
+    }
+    
+    /// Abilities a unit type can cast, by ID
+    pub fn unit_abilities(&self, unit_type: UnitType) -> Vec<&AbilityData> {
+        let mut abilities: Vec<&AbilityData> = self.abilities
+            .values()
+            .filter(|ability| ability.unit_types.contains(&unit_type))
+            .collect();
+        abilities.sort_by_key(|ability| ability.id);
+        abilities
+    }
+    
+    fn validate(&self) -> Result<(), DataError> {
+        for unit_type in UnitType::ALL {
+            let unit = self.units.get(&unit_type).ok_or_else(|| missing(UNITS_FILE, unit_type))?;
+            let invalid_unit = |reason: &str| invalid(UNITS_FILE, unit_type, reason);
+            
+            if unit.health <= 0.0 {
+                return Err(invalid_unit("health must be positive"));
+            }
+            if unit.train_time <= 0.0 {
+                return Err(invalid_unit("train_time must be positive"));
+            }
+            if unit.collider_radius <= 0.0 {
+                return Err(invalid_unit("collider_radius must be positive"));
+            }
+            if unit.movement_speed < 0.0 || unit.sight_range < 0.0 || unit.attack_range < 0.0 {
+                return Err(invalid_unit("speeds and ranges can't be negative"));
+            }
+            if unit.costs.values().any(|cost| *cost < 0.0) {
+                return Err(invalid_unit("costs can't be negative"));
+            }
+            if let Some(weapon) = &unit.weapon {
+                validate_weapon(weapon).map_err(invalid_unit)?;
+            }
+            
+            let animation = self.animations.get(&unit_type).ok_or_else(|| missing(ANIMATIONS_FILE, unit_type))?;
+            validate_animation(animation).map_err(|reason| invalid(ANIMATIONS_FILE, unit_type, reason))?;
+        }
+        
+        for building_type in BuildingType::ALL {
+            let building = self.buildings.get(&building_type).ok_or_else(|| missing(BUILDINGS_FILE, building_type))?;
+            let invalid = |reason: &str| invalid(BUILDINGS_FILE, building_type, reason);
+            
+            if building.health <= 0.0 {
+                return Err(invalid("health must be positive"));
+            }
+            if building.build_time <= 0.0 {
+                return Err(invalid("build_time must be positive"));
+            }
+            if building.size.x <= 0.0 || building.size.y <= 0.0 {
+                return Err(invalid("size must be positive"));
+            }
+            if building.costs.values().any(|cost| *cost < 0.0) {
+                return Err(invalid("costs can't be negative"));
+            }
+            if let Some(weapon) = &building.weapon {
+                validate_weapon(weapon).map_err(invalid)?;
+            }
+            if let Some(upgrade) = building.upgrades_to {
+                let target = self.buildings.get(&upgrade).ok_or_else(|| missing(BUILDINGS_FILE, upgrade))?;
+                if upgrade == building_type || target.size != building.size {
+                    return Err(invalid("upgrades_to must name another building with the same size"));
+                }
+            }
+        }
+        
+        for tech_type in TechType::ALL {
+            let tech = self.techs.get(&tech_type).ok_or_else(|| missing(TECHS_FILE, tech_type))?;
+            
+            if tech.research_time <= 0.0 {
+                return Err(invalid(TECHS_FILE, tech_type, "research_time must be positive"));
+            }
+            if tech.costs.values().any(|cost| *cost < 0.0) {
+                return Err(invalid(TECHS_FILE, tech_type, "costs can't be negative"));
+            }
+            if self.requires_itself(tech_type, tech_type, &mut HashSet::new()) {
+                return Err(invalid(TECHS_FILE, tech_type, "prerequisites form a cycle"));
+            }
+        }
+        
+        for ability in self.abilities.values() {
+            let invalid = |reason: &str| invalid(ABILITIES_FILE, &ability.name, reason);
+            
+            if ability.unit_types.is_empty() {
+                return Err(invalid("no unit type can cast it"));
+            }
+            if ability.energy_cost < 0.0 || ability.cooldown < 0.0 {
+                return Err(invalid("energy_cost and cooldown can't be negative"));
+            }
+            if matches!(ability.target, AbilityTarget::Point | AbilityTarget::Entity) && ability.range <= 0.0 {
+                return Err(invalid("targeted abilities need a positive range"));
+            }
+            match ability.effect {
+                AbilityEffect::Damage { amount, radius } | AbilityEffect::Heal { amount, radius } => {
+                    if amount <= 0.0 || radius < 0.0 {
+                        return Err(invalid("amount must be positive and radius can't be negative"));
+                    }
+                    if radius == 0.0 && ability.target != AbilityTarget::Entity {
+                        return Err(invalid("effects without a radius need an Entity target"));
+                    }
+                }
+                AbilityEffect::Buff { multiplier, duration, .. } => {
+                    if multiplier <= 0.0 || duration <= 0.0 {
+                        return Err(invalid("buff multiplier and duration must be positive"));
+                    }
+                    if !matches!(ability.target, AbilityTarget::Entity | AbilityTarget::SelfCast) {
+                        return Err(invalid("buffs need an Entity or SelfCast target"));
+                    }
+                }
+                AbilityEffect::Summon { count, duration, .. } => {
+                    if count == 0 || duration <= 0.0 {
+                        return Err(invalid("summons need a count and a positive duration"));
+                    }
+                }
+                AbilityEffect::Script => {}
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Whether `target` appears among the (transitive) prerequisites of `tech_type`
+    fn requires_itself(&self, target: TechType, tech_type: TechType, visited: &mut HashSet<TechType>) -> bool {
+        self.techs[&tech_type].prerequisites.iter().any(|&prereq| {
+            prereq == target || (visited.insert(prereq) && self.requires_itself(target, prereq, visited))
+        })
+    }
+}
+
+/// Load the definition files and make them available through `game_data`. Every entry point
+/// calls this once before anything else; a second call is an error rather than a silent no-op.
+pub fn init(dir: impl AsRef<Path>) -> Result<&'static GameData, DataError> {
+    if GAME_DATA.get().is_some() {
+        return Err(DataError::AlreadyLoaded);
+    }
+    GAME_DATA.set(GameData::load(dir)?).map_err(|_| DataError::AlreadyLoaded)?;
+    Ok(game_data())
+}
+
+/// Loaded definitions. Panics if `init` hasn't run, which is a bug in the entry point.
+#[cfg(not(test))]
+pub fn game_data() -> &'static GameData {
+    GAME_DATA.get().expect("game data used before data::init")
+}
+
+/// Tests share the shipped definitions, loaded on first use wherever the test runs from
+#[cfg(test)]
+pub fn game_data() -> &'static GameData {
+    GAME_DATA.get_or_init(|| {
+        GameData::load(Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_DATA_DIR))
+            .unwrap_or_else(|e| panic!("Failed to load game data: {}", e))
+    })
+}
+
+fn read_definitions<T: DeserializeOwned>(dir: &Path, file: &str) -> Result<Vec<T>, DataError> {
+    let path = dir.join(file);
+    let contents = fs::read_to_string(&path).map_err(|source| DataError::Io { path: path.clone(), source })?;
+    ron::from_str(&contents).map_err(|source| DataError::Parse { path, source })
+}
+
+/// Index definitions by type, rejecting duplicates
+fn registry<K: Copy + Eq + Hash + std::fmt::Debug, T>(
+    file: &'static str,
+    definitions: Vec<T>,
+    key: impl Fn(&T) -> K,
+) -> Result<HashMap<K, T>, DataError> {
+    let mut registry = HashMap::new();
+    for definition in definitions {
+        let id = key(&definition);
+        if registry.insert(id, definition).is_some() {
+            return Err(DataError::Duplicate { file, name: format!("{:?}", id) });
+        }
+    }
+    Ok(registry)
+}
+
+fn validate_weapon(weapon: &WeaponData) -> Result<(), &'static str> {
+    if weapon.range <= 0.0 {
+        return Err("weapon range must be positive");
+    }
+    if weapon.cooldown <= 0.0 {
+        return Err("weapon cooldown must be positive");
+    }
+    if !(0.0..=1.0).contains(&weapon.accuracy) {
+        return Err("weapon accuracy must be between 0 and 1");
+    }
+    Ok(())
+}
+
+fn validate_animation(animation: &AnimationData) -> Result<(), &'static str> {
+    if animation.columns == 0 || animation.rows == 0 {
+        return Err("columns and rows must be positive");
+    }
+    if !animation.clips.contains_key(&AnimationState::Idle) {
+        return Err("every sheet needs an Idle clip");
+    }
+    for clip in animation.clips.values() {
+        if clip.row >= animation.rows {
+            return Err("clip row is outside the sheet");
+        }
+        if clip.frame_times.is_empty() || clip.frame_times.len() as u32 > animation.columns {
+            return Err("clips need between one frame and a row's worth of frames");
+        }
+        if clip.frame_times.iter().any(|time| *time <= 0.0) {
+            return Err("frame times must be positive");
+        }
+    }
+    Ok(())
+}
+
+fn missing(file: &'static str, id: impl std::fmt::Debug) -> DataError {
+    DataError::Missing { file, name: format!("{:?}", id) }
+}
+
+fn invalid(file: &'static str, id: impl std::fmt::Debug, reason: &str) -> DataError {
+    DataError::Invalid { file, name: format!("{:?}", id), reason: reason.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const FILES: [&str; 5] = [UNITS_FILE, BUILDINGS_FILE, TECHS_FILE, ABILITIES_FILE, ANIMATIONS_FILE];
+    
+    fn shipped_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_DATA_DIR)
+    }
+    
+    /// Load a copy of the shipped definitions with one file changed by `edit`
+    fn load_edited(test: &str, file: &str, edit: impl Fn(String) -> String) -> Result<GameData, DataError> {
+        let dir = std::env::temp_dir().join(format!("fff-data-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in FILES {
+            let contents = fs::read_to_string(shipped_dir().join(name)).unwrap();
+            let contents = if name == file { edit(contents) } else { contents };
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let result = GameData::load(&dir);
+        fs::remove_dir_all(&dir).ok();
+        result
+    }
+    
+    /// Replace the first occurrence of `from`, which the shipped file must contain
+    fn replace(from: &'static str, to: &'static str) -> impl Fn(String) -> String {
+        move |contents| {
+            assert!(contents.contains(from), "shipped data no longer contains {:?}", from);
+            contents.replacen(from, to, 1)
+        }
+    }
+    
+    #[test]
+    fn shipped_definitions_are_valid() {
+        let data = GameData::load(shipped_dir()).unwrap();
+        for unit_type in UnitType::ALL {
+            assert!(data.unit(unit_type).health > 0.0);
+        }
+    }
+    
+    #[test]
+    fn second_init_is_an_error() {
+        game_data();
+        assert!(matches!(init(shipped_dir()), Err(DataError::AlreadyLoaded)));
+    }
+    
+    #[test]
+    fn missing_file_is_an_io_error() {
+        let dir = std::env::temp_dir().join(format!("fff-data-empty-{}", std::process::id()));
+        assert!(matches!(GameData::load(&dir), Err(DataError::Io { .. })));
+    }
+    
+    #[test]
+    fn malformed_file_is_a_parse_error() {
+        let result = load_edited("parse", UNITS_FILE, replace("health: 30.0,", "health: thirty,"));
+        assert!(matches!(result, Err(DataError::Parse { .. })));
+    }
+    
+    #[test]
+    fn duplicate_definition_is_rejected() {
+        let result = load_edited("duplicate", UNITS_FILE, replace("unit_type: Soldier,", "unit_type: Worker,"));
+        assert!(matches!(result, Err(DataError::Duplicate { file: UNITS_FILE, .. })));
+    }
+    
+    #[test]
+    fn missing_definition_is_rejected() {
+        let result = load_edited("missing", UNITS_FILE, |contents| {
+            // Drop the Worker's whole entry, up to the Soldier's
+            let start = contents.find("    (\n        unit_type: Worker,").unwrap();
+            let end = contents.find("    (\n        unit_type: Soldier,").unwrap();
+            format!("{}{}", &contents[..start], &contents[end..])
+        });
+        assert!(matches!(result, Err(DataError::Missing { file: UNITS_FILE, .. })));
+    }
+    
+    #[test]
+    fn invalid_values_are_rejected() {
+        let result = load_edited("health", UNITS_FILE, replace("health: 30.0,", "health: 0.0,"));
+        assert!(matches!(result, Err(DataError::Invalid { file: UNITS_FILE, .. })));
+        
+        let result = load_edited("accuracy", UNITS_FILE, replace("accuracy: 0.9,", "accuracy: 1.5,"));
+        assert!(matches!(result, Err(DataError::Invalid { file: UNITS_FILE, .. })));
+    }
+    
+    #[test]
+    fn prerequisite_cycle_is_rejected() {
+        let result = load_edited("cycle", TECHS_FILE, |contents| {
+            // ImprovedWeapons already leads to AdvancedUnits; make it require it back
+            let weapons = contents.find("tech_type: ImprovedWeapons,").unwrap();
+            let (before, after) = contents.split_at(weapons);
+            format!("{}{}", before, after.replacen("prerequisites: [],", "prerequisites: [AdvancedUnits],", 1))
+        });
+        assert!(matches!(result, Err(DataError::Invalid { file: TECHS_FILE, .. })));
+    }
+}
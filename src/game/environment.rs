@@ -0,0 +1,89 @@
+use bevy_ecs::prelude::*;
+use tracing::info;
+
+use crate::ecs::resources::TICK_RATE;
+
+/// Length of a full day and night in minutes of game time
+pub const DAY_LENGTH_MINUTES: f32 = 8.0;
+
+/// Share of their daytime sight range units and buildings keep in the dead of night
+pub const NIGHT_SIGHT_FACTOR: f32 = 0.6;
+
+/// Points of the day, as fractions of it, where dusk starts, night falls and dawn breaks; games start at sunrise
+const DUSK_START: f32 = 0.5;
+const NIGHT_START: f32 = 0.6;
+const DAWN_START: f32 = 0.9;
+
+/// Color laid over the world at full night; its alpha fades in with the dark
+pub const NIGHT_TINT: [f32; 4] = [0.05, 0.08, 0.25, 0.45];
+
+/// Day and night cycle of the current game. Only derived from the game tick, so every peer
+/// in a lockstep game agrees on how far anyone can see.
+#[derive(Resource, Debug, Clone)]
+pub struct DayNightCycle {
+    pub enabled: bool,
+    pub day_length: f32, // Minutes
+}
+
+impl Default for DayNightCycle {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            day_length: DAY_LENGTH_MINUTES,
+        }
+    }
+}
+
+impl DayNightCycle {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, ..Self::default() }
+    }
+    
+    /// How far through the current day a tick falls, from 0 at sunrise to 1 at the next
+    pub fn time_of_day(&self, tick: u64) -> f32 {
+        let day_ticks = (self.day_length * 60.0 * TICK_RATE as f32).max(1.0) as u64;
+        (tick % day_ticks) as f32 / day_ticks as f32
+    }
+    
+    /// 1 in full daylight, 0 at full night, ramping in between at dusk and dawn
+    pub fn daylight(&self, tick: u64) -> f32 {
+        if !self.enabled {
+            return 1.0;
+        }
+        
+        let time = self.time_of_day(tick);
+        if time < DUSK_START {
+            1.0
+        } else if time < NIGHT_START {
+            1.0 - (time - DUSK_START) / (NIGHT_START - DUSK_START)
+        } else if time < DAWN_START {
+            0.0
+        } else {
+            (time - DAWN_START) / (1.0 - DAWN_START)
+        }
+    }
+    
+    /// Factor sight ranges are scaled by at a tick
+    pub fn sight_multiplier(&self, tick: u64) -> f32 {
+        NIGHT_SIGHT_FACTOR + (1.0 - NIGHT_SIGHT_FACTOR) * self.daylight(tick)
+    }
+    
+    /// Tint the renderer lays over the world at a tick; fully transparent by day
+    pub fn tint(&self, tick: u64) -> [f32; 4] {
+        let darkness = 1.0 - self.daylight(tick);
+        [NIGHT_TINT[0], NIGHT_TINT[1], NIGHT_TINT[2], NIGHT_TINT[3] * darkness]
+    }
+}
+
+/// Factor sight ranges are scaled by at a tick, for systems in worlds that may have no cycle
+pub fn sight_multiplier(cycle: Option<&DayNightCycle>, tick: u64) -> f32 {
+    cycle.map_or(1.0, |cycle| cycle.sight_multiplier(tick))
+}
+
+/// Start the day and night cycle of a new or loaded game
+pub fn start_day_night_cycle(world: &mut World, enabled: bool) {
+    world.insert_resource(DayNightCycle::new(enabled));
+    if enabled {
+        info!("Day and night cycle: night cuts sight to {:.0}%", NIGHT_SIGHT_FACTOR * 100.0);
+    }
+}
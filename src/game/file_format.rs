@@ -0,0 +1,84 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Length of the raw header (magic + format version) in front of every versioned file
+const HEADER_LEN: usize = 8;
+
+/// Layout of a versioned binary file: a raw header followed by a bincode body
+#[derive(Debug, Clone, Copy)]
+pub struct FileFormat {
+    pub kind: &'static str, // Shown in errors, e.g. "save" or "replay"
+    pub magic: [u8; 4],
+    pub version: u32,        // Version written by this build
+    pub oldest_version: u32, // Oldest version that can still be migrated to `version`
+}
+
+/// Problem found while reading a versioned file
+#[derive(Debug, Error)]
+pub enum FileFormatError {
+    #[error("{name} is not a valid {kind} file")]
+    NotRecognized { name: String, kind: &'static str },
+    #[error("{name} is {kind} format version {found}, which is newer than this build supports ({oldest} to {current})")]
+    TooNew { name: String, kind: &'static str, found: u32, oldest: u32, current: u32 },
+    #[error("{name} is {kind} format version {found}, which can no longer be loaded (supported versions are {oldest} to {current})")]
+    TooOld { name: String, kind: &'static str, found: u32, oldest: u32, current: u32 },
+    #[error("{name} is {kind} format version {version} but its contents could not be read: {source}")]
+    Corrupt { name: String, kind: &'static str, version: u32, source: bincode::Error },
+}
+
+impl FileFormat {
+    /// Serialize a body behind this format's header
+    pub fn encode<T: Serialize>(&self, body: &T) -> bincode::Result<Vec<u8>> {
+        Ok(self.wrap(&bincode::serialize(body)?))
+    }
+    
+    /// Put this format's header in front of an already serialized body, e.g. a compressed one
+    pub fn wrap(&self, body: &[u8]) -> Vec<u8> {
+        // Header (magic + version) is written raw so it can be checked before deserializing
+        let mut data = Vec::with_capacity(HEADER_LEN + body.len());
+        data.extend_from_slice(&self.magic);
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(body);
+        data
+    }
+
+    /// Validate the header of a file; returns its format version and the serialized body
+    pub fn read_header<'a>(&self, name: &str, data: &'a [u8]) -> Result<(u32, &'a [u8]), FileFormatError> {
+        if data.len() < HEADER_LEN || data[0..4] != self.magic {
+            return Err(FileFormatError::NotRecognized { name: name.to_string(), kind: self.kind });
+        }
+
+        let found = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        if found > self.version {
+            return Err(FileFormatError::TooNew {
+                name: name.to_string(),
+                kind: self.kind,
+                found,
+                oldest: self.oldest_version,
+                current: self.version,
+            });
+        }
+        if found < self.oldest_version {
+            return Err(FileFormatError::TooOld {
+                name: name.to_string(),
+                kind: self.kind,
+                found,
+                oldest: self.oldest_version,
+                current: self.version,
+            });
+        }
+
+        Ok((found, &data[HEADER_LEN..]))
+    }
+
+    /// Deserialize a body laid out as the given format version
+    pub fn decode<T: DeserializeOwned>(&self, name: &str, version: u32, body: &[u8]) -> Result<T, FileFormatError> {
+        bincode::deserialize(body).map_err(|source| FileFormatError::Corrupt {
+            name: name.to_string(),
+            kind: self.kind,
+            version,
+            source,
+        })
+    }
+}
@@ -0,0 +1,223 @@
+use bevy_ecs::prelude::Entity;
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+
+/// Space left between neighbouring units, on top of their collider radii
+const FORMATION_GAP: f32 = 4.0;
+
+/// Slowest a unit may be held back to keep pace with its group, as a fraction of its own speed
+pub const MIN_FORMATION_SPEED_FACTOR: f32 = 0.25;
+
+/// Shape a group of units takes when ordered to move together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Formation {
+    Box,
+    Line,
+    Wedge,
+}
+
+impl Default for Formation {
+    fn default() -> Self {
+        Formation::Box
+    }
+}
+
+impl Formation {
+    /// Formation selected after this one by the toggle hotkey
+    pub fn next(self) -> Self {
+        match self {
+            Formation::Box => Formation::Line,
+            Formation::Line => Formation::Wedge,
+            Formation::Wedge => Formation::Box,
+        }
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Formation::Box => "Box",
+            Formation::Line => "Line",
+            Formation::Wedge => "Wedge",
+        }
+    }
+    
+    /// Slot offsets for `count` units, centered on the origin; +y is the direction of travel
+    fn slot_offsets(self, count: usize, spacing: f32) -> Vec<Vec2> {
+        let mut offsets: Vec<Vec2> = match self {
+            Formation::Box => {
+                let columns = (count as f32).sqrt().ceil() as usize;
+                (0..count)
+                    .map(|i| {
+                        let (row, column) = (i / columns, i % columns);
+                        // The last row may be short; center it behind the others
+                        let row_length = columns.min(count - row * columns);
+                        let x = column as f32 - (row_length - 1) as f32 / 2.0;
+                        Vec2::new(x * spacing, -(row as f32) * spacing)
+                    })
+                    .collect()
+            }
+            Formation::Line => (0..count)
+                .map(|i| Vec2::new((i as f32 - (count - 1) as f32 / 2.0) * spacing, 0.0))
+                .collect(),
+            Formation::Wedge => (0..count)
+                .map(|i| {
+                    // Tip first, then alternating left and right further back
+                    let rank = ((i + 1) / 2) as f32;
+                    let side = if i % 2 == 1 { -1.0 } else { 1.0 };
+                    Vec2::new(side * rank * spacing, -rank * spacing)
+                })
+                .collect(),
+        };
+        
+        // Center the whole shape on the ordered point
+        let center = offsets.iter().copied().sum::<Vec2>() / count as f32;
+        for offset in &mut offsets {
+            *offset -= center;
+        }
+        offsets
+    }
+}
+
+/// A unit taking part in a group order
+#[derive(Debug, Clone, Copy)]
+pub struct FormationMember {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// Spread `members` into `formation` around `target`, facing the way the group is travelling
+pub fn formation_destinations(formation: Formation, members: &[FormationMember], target: Vec2) -> Vec<(Entity, Vec2)> {
+    if members.len() <= 1 {
+        return members.iter().map(|member| (member.entity, target)).collect();
+    }
+    
+    let spacing = members.iter().map(|member| member.radius).fold(0.0, f32::max) * 2.0 + FORMATION_GAP;
+    let centroid = members.iter().map(|member| member.position).sum::<Vec2>() / members.len() as f32;
+    let forward = (target - centroid).try_normalize().unwrap_or(Vec2::Y);
+    let right = Vec2::new(forward.y, -forward.x);
+    
+    let slots: Vec<Vec2> = formation
+        .slot_offsets(members.len(), spacing)
+        .into_iter()
+        .map(|offset| target + right * offset.x + forward * offset.y)
+        .collect();
+    
+    // Fill slots front to back with the closest unit still unassigned
+    let mut unassigned: Vec<FormationMember> = members.to_vec();
+    let mut destinations = Vec::with_capacity(members.len());
+    for slot in slots {
+        let (index, _) = unassigned
+            .iter()
+            .enumerate()
+            .map(|(index, member)| (index, (member.position - slot).length_squared()))
+            .fold((0, f32::INFINITY), |best, candidate| if candidate.1 < best.1 { candidate } else { best });
+        destinations.push((unassigned.remove(index).entity, slot));
+    }
+    destinations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const RADIUS: f32 = 5.0;
+    const SPACING: f32 = RADIUS * 2.0 + FORMATION_GAP;
+    
+    /// Members all standing at `center`, so the group travels exactly towards its target
+    fn members(count: usize, center: Vec2) -> Vec<FormationMember> {
+        (0..count)
+            .map(|i| FormationMember { entity: Entity::from_raw(i as u32), position: center, radius: RADIUS })
+            .collect()
+    }
+    
+    fn min_distance(points: &[Vec2]) -> f32 {
+        let mut min = f32::INFINITY;
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                min = min.min(a.distance(*b));
+            }
+        }
+        min
+    }
+    
+    #[test]
+    fn every_member_gets_one_slot() {
+        for formation in [Formation::Box, Formation::Line, Formation::Wedge] {
+            for count in [2, 5, 9, 17] {
+                let destinations = formation_destinations(formation, &members(count, Vec2::ZERO), Vec2::new(200.0, 0.0));
+                assert_eq!(destinations.len(), count);
+                
+                let mut entities: Vec<Entity> = destinations.iter().map(|(entity, _)| *entity).collect();
+                entities.sort();
+                entities.dedup();
+                assert_eq!(entities.len(), count, "{:?} gave a unit two slots", formation);
+            }
+        }
+    }
+    
+    #[test]
+    fn a_lone_unit_goes_straight_to_the_target() {
+        let target = Vec2::new(50.0, 80.0);
+        assert_eq!(formation_destinations(Formation::Box, &members(1, Vec2::ZERO), target), vec![(Entity::from_raw(0), target)]);
+        assert!(formation_destinations(Formation::Box, &[], target).is_empty());
+    }
+    
+    #[test]
+    fn slots_are_centered_and_spaced_by_the_largest_radius() {
+        for formation in [Formation::Box, Formation::Line, Formation::Wedge] {
+            let offsets = formation.slot_offsets(7, SPACING);
+            assert_eq!(offsets.len(), 7);
+            assert!(offsets.iter().copied().sum::<Vec2>().length() < 1e-3);
+            assert!(min_distance(&offsets) >= SPACING - 1e-3, "{:?} packs units too tightly", formation);
+        }
+        
+        // A bigger unit in the group spreads everyone out to fit it
+        let mut group = members(4, Vec2::ZERO);
+        group[0].radius = RADIUS * 3.0;
+        let slots: Vec<Vec2> = formation_destinations(Formation::Line, &group, Vec2::new(200.0, 0.0))
+            .into_iter()
+            .map(|(_, slot)| slot)
+            .collect();
+        assert!((min_distance(&slots) - (RADIUS * 6.0 + FORMATION_GAP)).abs() < 1e-3);
+    }
+    
+    #[test]
+    fn box_centers_its_short_last_row() {
+        let offsets = Formation::Box.slot_offsets(5, SPACING);
+        let back = offsets.iter().map(|offset| offset.y).fold(f32::INFINITY, f32::min);
+        let back_row: Vec<f32> = offsets.iter().filter(|offset| (offset.y - back).abs() < 1e-3).map(|offset| offset.x).collect();
+        assert_eq!(back_row.len(), 2);
+        assert!((back_row[0] + back_row[1]).abs() < 1e-3);
+    }
+    
+    #[test]
+    fn formations_face_the_direction_of_travel() {
+        // Travelling along +x, so the line runs along y and the wedge's tip leads along x
+        let target = Vec2::new(200.0, 0.0);
+        let line = formation_destinations(Formation::Line, &members(5, Vec2::ZERO), target);
+        assert!(line.iter().all(|(_, slot)| (slot.x - target.x).abs() < 1e-3));
+        
+        let wedge = formation_destinations(Formation::Wedge, &members(5, Vec2::ZERO), target);
+        let tips = wedge.iter().filter(|(_, slot)| wedge.iter().all(|(_, other)| slot.x >= other.x - 1e-3)).count();
+        assert_eq!(tips, 1);
+        
+        // Travelling along -y turns the line to run along x
+        let target = Vec2::new(0.0, -200.0);
+        let line = formation_destinations(Formation::Line, &members(5, Vec2::ZERO), target);
+        assert!(line.iter().all(|(_, slot)| (slot.y - target.y).abs() < 1e-3));
+    }
+    
+    #[test]
+    fn units_take_the_nearest_slots() {
+        // Two units side by side, ordered straight ahead: neither should cross the other's path
+        let group = [
+            FormationMember { entity: Entity::from_raw(0), position: Vec2::new(-20.0, 0.0), radius: RADIUS },
+            FormationMember { entity: Entity::from_raw(1), position: Vec2::new(20.0, 0.0), radius: RADIUS },
+        ];
+        let destinations = formation_destinations(Formation::Line, &group, Vec2::new(0.0, 200.0));
+        for (entity, slot) in destinations {
+            let start = group.iter().find(|member| member.entity == entity).unwrap().position;
+            assert_eq!(slot.x.signum(), start.x.signum());
+        }
+    }
+}
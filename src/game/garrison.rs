@@ -0,0 +1,205 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+
+use crate::ecs::components::{
+    AttackMove, AttackTarget, Building, BuildingType, BuildTarget, CastOrder, Collider, Follow, FormationMove, Garrison,
+    GarrisonTarget, Garrisoned, HarvestTarget, HealTarget, HoldPosition, IdleWorker, Movement, Owner, Patrol,
+    RepairTarget, Selected, Transform, Unit, UnitType,
+};
+use crate::ecs::events::{Casualty, DeathEvent};
+use crate::ecs::resources::{AudioEvents, GameMap, PlayerAlerts};
+use crate::ecs::systems::combat::{get_building_weapon, get_weapon_data, WeaponData};
+use crate::ecs::systems::stop_movement;
+use crate::engine::audio::GameSoundType;
+use crate::game::commands::set_path;
+use crate::game::data;
+use crate::game::transport::unload_spots;
+
+/// Gap between a unit's collider and the building's at which the unit goes inside
+const ENTRY_DISTANCE: f32 = 6.0;
+
+/// Share of their maximum health occupants lose when their building is destroyed around them
+pub const COLLAPSE_DAMAGE: f32 = 0.4;
+
+/// How many units can shelter in a building type; 0 for buildings that take none
+pub fn garrison_capacity(building_type: BuildingType) -> u32 {
+    data::game_data().building(building_type).garrison_capacity
+}
+
+/// Whether a unit type fits inside a building; only infantry does
+pub fn can_garrison(unit_type: UnitType) -> bool {
+    match unit_type {
+        UnitType::Worker | UnitType::Scout | UnitType::Soldier | UnitType::Healer => true,
+        UnitType::Tank | UnitType::Transport => false,
+    }
+}
+
+/// Weapon a building fires with: its own, firing faster by the damage per second of its armed occupants.
+/// An unarmed building shoots with its first armed occupant's weapon.
+pub fn garrison_weapon(building_type: BuildingType, occupants: &[UnitType]) -> Option<WeaponData> {
+    let occupant_weapons: Vec<WeaponData> = occupants.iter().filter_map(|&unit_type| get_weapon_data(unit_type)).collect();
+    let mut weapon = match get_building_weapon(building_type) {
+        Some(weapon) => weapon,
+        None => WeaponData { damage: 0.0, ..occupant_weapons.first()?.clone() },
+    };
+    
+    for occupant in &occupant_weapons {
+        weapon.damage += occupant.damage / occupant.cooldown.max(f32::EPSILON) * weapon.cooldown;
+        weapon.range = weapon.range.max(occupant.range);
+    }
+    Some(weapon)
+}
+
+/// System that gives finished buildings that take occupants their garrison and walks entering units to
+/// their building, taking them off the map once they reach it
+pub fn garrison_system(
+    mut commands: Commands,
+    new_buildings: Query<(Entity, &Building), Without<Garrison>>,
+    mut entering: Query<(Entity, &Transform, &Owner, &GarrisonTarget, Option<&Collider>, &mut Movement)>,
+    mut buildings: Query<(&Building, &Transform, &Owner, Option<&Collider>, &mut Garrison)>,
+    occupants: Query<(Entity, &Garrisoned)>,
+    game_map: Res<GameMap>,
+) {
+    // Checked every tick rather than on Added<Building>, so finished construction and upgrades pick it up
+    for (entity, building) in new_buildings.iter() {
+        let capacity = garrison_capacity(building.building_type);
+        if capacity > 0 && building.construction_progress.is_none() {
+            commands.entity(entity).insert(Garrison { capacity, occupants: Vec::new() });
+        }
+    }
+    
+    // Occupants are lost with a building that disappears without being destroyed
+    for (entity, garrisoned) in occupants.iter() {
+        if buildings.get(garrisoned.building).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+    
+    for (entity, transform, owner, garrison_target, collider, mut movement) in entering.iter_mut() {
+        let (building_position, reach) = match buildings.get_mut(garrison_target.target_entity) {
+            Ok((building, building_transform, building_owner, building_collider, garrison))
+                if building.health > 0.0 && building_owner.0 == owner.0 &&
+                    (garrison.occupants.len() as u32) < garrison.capacity =>
+            {
+                let reach = collider.map_or(0.0, |c| c.radius) + building_collider.map_or(0.0, |c| c.radius);
+                (building_transform.position, reach + ENTRY_DISTANCE)
+            }
+            _ => {
+                // Gone, lost or full
+                commands.entity(entity).remove::<GarrisonTarget>();
+                stop_movement(&mut movement);
+                continue;
+            }
+        };
+        
+        if (building_position - transform.position).length() <= reach {
+            if let Ok((_, _, _, _, mut garrison)) = buildings.get_mut(garrison_target.target_entity) {
+                garrison.occupants.push(entity);
+            }
+            stop_movement(&mut movement);
+            commands.entity(entity).remove::<(
+                Transform,
+                GarrisonTarget,
+                Selected,
+                IdleWorker,
+                AttackTarget,
+                AttackMove,
+                Patrol,
+                HealTarget,
+                BuildTarget,
+                HarvestTarget,
+                FormationMove,
+                HoldPosition,
+                CastOrder,
+                RepairTarget,
+                Follow,
+            )>();
+            commands.entity(entity).insert(Garrisoned { building: garrison_target.target_entity });
+            continue;
+        }
+        
+        // Buildings don't move, so one path there is enough
+        if movement.target.is_none() || movement.path_index >= movement.path.len() {
+            if let Some(grid) = game_map.grid_for(owner.0) {
+                let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+                set_path(&mut movement, transform.position, building_position, grid, unit_radius);
+            }
+        }
+    }
+}
+
+/// System that throws the survivors out of destroyed buildings; damage_system has already hurt them
+pub fn garrison_collapse_system(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    garrisons: Query<&Garrison>,
+    occupants: Query<&Unit, With<Garrisoned>>,
+    game_map: Res<GameMap>,
+) {
+    for death in death_events.iter() {
+        if !matches!(death.casualty, Casualty::Building(_)) {
+            continue;
+        }
+        let survivors: Vec<Entity> = match garrisons.get(death.entity) {
+            Ok(garrison) => garrison
+                .occupants
+                .iter()
+                .copied()
+                .filter(|&occupant| occupants.get(occupant).map_or(false, |unit| unit.health > 0.0))
+                .collect(),
+            Err(_) => continue,
+        };
+        
+        // Anyone without room to land is lost with the building once it is gone
+        let spots = unload_spots(Some(&game_map), death.owner, death.position, survivors.len());
+        for (survivor, spot) in survivors.into_iter().zip(spots) {
+            commands.entity(survivor).remove::<Garrisoned>();
+            commands.entity(survivor).insert(Transform {
+                position: spot,
+                rotation: 0.0,
+                scale: Vec2::new(1.0, 1.0),
+            });
+        }
+    }
+}
+
+/// Put the occupants of a building back on free cells around it: every occupant, or only the one in `slot`.
+/// Occupants that don't fit stay inside.
+pub fn eject(world: &mut World, building: Entity, slot: Option<usize>) {
+    let (position, owner) = match (world.get::<Transform>(building), world.get::<Owner>(building)) {
+        (Some(transform), Some(owner)) => (transform.position, owner.0),
+        _ => return,
+    };
+    let occupants: Vec<Entity> = match world.get::<Garrison>(building) {
+        Some(garrison) => match slot {
+            Some(slot) => garrison.occupants.get(slot).copied().into_iter().collect(),
+            None => garrison.occupants.clone(),
+        },
+        None => return,
+    };
+    if occupants.is_empty() {
+        return;
+    }
+    
+    let spots = unload_spots(world.get_resource::<GameMap>(), owner, position, occupants.len());
+    if spots.len() < occupants.len() {
+        world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+            owner,
+            "There is no room to leave the building".to_string(),
+        ));
+    }
+    
+    for (occupant, spot) in occupants.iter().zip(spots) {
+        let mut entity = world.entity_mut(*occupant);
+        entity.remove::<Garrisoned>();
+        entity.insert(Transform {
+            position: spot,
+            rotation: 0.0,
+            scale: Vec2::new(1.0, 1.0),
+        });
+        if let Some(mut garrison) = world.get_mut::<Garrison>(building) {
+            garrison.occupants.retain(|entity| entity != occupant);
+        }
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(owner, GameSoundType::UnitMove, position);
+}
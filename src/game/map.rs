@@ -1,20 +1,146 @@
+use bevy_ecs::world::World;
 use glam::Vec2;
 use noise::{NoiseFn, Perlin, Seedable};
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
 use std::collections::HashSet;
+use tracing::info;
 
-use crate::ecs::resources::{GameMap, TerrainTile, PathfindingGrid};
-use crate::ecs::components::ResourceType;
+use crate::ecs::resources::{GameMap, NeutralSite, TerrainTile, PathfindingGrid};
+use crate::ecs::components::{BuildingType, Resource, ResourceType, Transform, UnitType};
+use crate::game::map_validation;
 use crate::game::pathfinding;
 
+/// Chance a resource away from every starting position gets a creep camp guarding it
+const CAMP_CHANCE: f64 = 0.15;
+
+/// Camps are kept at least this far from starting positions so nobody is attacked at home
+const CAMP_MIN_START_DISTANCE: f32 = 40.0;
+
+/// Creeps per camp
+const CAMP_SIZE: std::ops::RangeInclusive<u32> = 2..=3;
+
+/// Share of a generated map's tiles validation may carve into ground before the map is regenerated instead
+const MAX_CARVED_SHARE: f32 = 0.02;
+
+/// Seeds tried after the first before a map is kept however much carving it needs
+const MAX_REGENERATIONS: u32 = 5;
+
+/// Tiles around each start cleared to buildable ground
+const START_ZONE_RADIUS: f32 = 10.0;
+
+/// Tiles around each watchtower site cleared to ground, so the tower stands where it was planned
+const TOWER_ZONE_RADIUS: f32 = 2.0;
+
+/// Distance of symmetric starts from the map edge they are placed along, as a fraction of the map width
+const START_EDGE_MARGIN: f32 = 0.15;
+
+/// Standard resources every start gets: a mineral arc behind the base and a gas geyser beside it
+const START_MINERALS: u32 = 6;
+const START_MINERAL_AMOUNT: f32 = 1500.0;
+const START_GAS_AMOUNT: f32 = 1000.0;
+const START_RESOURCE_DISTANCE: f32 = 7.0;
+const START_MINERAL_SPACING: f32 = 0.3; // Radians between neighbouring mineral patches
+
+/// Land masses across and down an Islands map
+const ISLAND_GRID: f64 = 3.0;
+
+/// Rivers run where the river noise layer is this close to zero, except at fords
+const RIVER_WIDTH: f64 = 0.04;
+const FORD_THRESHOLD: f64 = 0.5;
+
+/// Overall shape of a generated map, picked in the game setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapPreset {
+    Continental, // Land broken up by lakes, hills and woods
+    Islands,     // Land masses across open water
+    Rivers,      // Mostly dry land cut by rivers with fords to cross at
+    OpenPlains,  // Wide open ground with little in the way
+}
+
+impl Default for MapPreset {
+    fn default() -> Self {
+        MapPreset::Continental
+    }
+}
+
+impl MapPreset {
+    /// Presets in the order the game setup dropdown lists them
+    pub const ALL: [MapPreset; 4] = [
+        MapPreset::Continental,
+        MapPreset::Islands,
+        MapPreset::Rivers,
+        MapPreset::OpenPlains,
+    ];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            MapPreset::Continental => "Continental",
+            MapPreset::Islands => "Islands",
+            MapPreset::Rivers => "Rivers",
+            MapPreset::OpenPlains => "Open Plains",
+        }
+    }
+}
+
+/// How one half of a generated map mirrors the other, so no start gets better ground
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapSymmetry {
+    None,
+    Mirrored,   // Left half reflected onto the right
+    Rotational, // Top half turned half a circle onto the bottom
+}
+
+impl Default for MapSymmetry {
+    fn default() -> Self {
+        MapSymmetry::None
+    }
+}
+
+impl MapSymmetry {
+    /// Symmetries in the order the game setup dropdown lists them
+    pub const ALL: [MapSymmetry; 3] = [MapSymmetry::None, MapSymmetry::Mirrored, MapSymmetry::Rotational];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            MapSymmetry::None => "None",
+            MapSymmetry::Mirrored => "Mirrored",
+            MapSymmetry::Rotational => "Rotational",
+        }
+    }
+    
+    /// Whether a tile is generated directly rather than copied from its counterpart
+    pub fn is_primary(self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        match self {
+            MapSymmetry::None => true,
+            MapSymmetry::Mirrored => x * 2 < width,
+            // The middle row of an odd-height map is its own counterpart, so only its left half is primary
+            MapSymmetry::Rotational => y * 2 + 1 < height || (y * 2 + 1 == height && x * 2 < width),
+        }
+    }
+    
+    /// Counterpart of a tile position on the other side of the map; itself without symmetry
+    pub fn mirror(self, position: Vec2, width: u32, height: u32) -> Vec2 {
+        let far_corner = Vec2::new(width as f32 - 1.0, height as f32 - 1.0);
+        match self {
+            MapSymmetry::None => position,
+            MapSymmetry::Mirrored => Vec2::new(far_corner.x - position.x, position.y),
+            MapSymmetry::Rotational => far_corner - position,
+        }
+    }
+}
+
 /// Map generation parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapGenerationParams {
     pub width: u32,
     pub height: u32,
     pub seed: u64,
     pub player_count: u8,
-    pub water_threshold: f64,
+    pub preset: MapPreset,
+    pub symmetry: MapSymmetry,
+    pub water_threshold: f64, // Noise thresholds; presets pick their own
     pub mountain_threshold: f64,
     pub forest_threshold: f64,
     pub resource_density: f32,
@@ -27,6 +153,8 @@ impl Default for MapGenerationParams {
             height: 256,
             seed: 12345,
             player_count: 2,
+            preset: MapPreset::default(),
+            symmetry: MapSymmetry::default(),
             water_threshold: 0.3,
             mountain_threshold: 0.7,
             forest_threshold: 0.6,
@@ -35,48 +163,121 @@ impl Default for MapGenerationParams {
     }
 }
 
-/// Generate a new random map
+impl MapGenerationParams {
+    /// Default parameters with the thresholds and resource density a preset is tuned for
+    pub fn preset(preset: MapPreset) -> Self {
+        let (water_threshold, mountain_threshold, forest_threshold, resource_density) = match preset {
+            MapPreset::Continental => (0.3, 0.7, 0.6, 0.01),
+            MapPreset::Islands => (0.2, 1.1, 0.8, 0.012),
+            MapPreset::Rivers => (-0.5, 0.75, 0.55, 0.01),
+            MapPreset::OpenPlains => (-0.7, 0.9, 0.6, 0.008),
+        };
+        Self {
+            preset,
+            water_threshold,
+            mountain_threshold,
+            forest_threshold,
+            resource_density,
+            ..Self::default()
+        }
+    }
+}
+
+/// Generate a new random map, checked to be playable. Maps needing more than a little terrain
+/// carved to get there are thrown away and generated again from the next seed.
 pub fn generate_map(params: &MapGenerationParams) -> GameMap {
+    let max_carved = ((params.width * params.height) as f32 * MAX_CARVED_SHARE) as usize;
+    let mut params = params.clone();
+    
+    for attempt in 0..=MAX_REGENERATIONS {
+        let mut map = build_map(&params);
+        let fixes = map_validation::validate_and_fix(&mut map, params.symmetry);
+        let carved: usize = fixes.iter().map(|fix| fix.tiles_changed()).sum();
+        
+        if carved > max_carved && attempt < MAX_REGENERATIONS {
+            info!("Map from seed {} needs {} tiles carved to be playable, regenerating", params.seed, carved);
+            params.seed = params.seed.wrapping_add(1);
+            continue;
+        }
+        for fix in &fixes {
+            info!("Map validation {}", fix);
+        }
+        
+        // Generate pathfinding grid
+        map.pathfinding_grid = Some(pathfinding::generate_pathfinding_grid(&map, pathfinding::PATH_GRID_SIZE));
+        return map;
+    }
+    unreachable!("the last attempt always returns its map")
+}
+
+/// Generate terrain, starts, resources and neutral sites from the parameters as they are
+fn build_map(params: &MapGenerationParams) -> GameMap {
     let mut rng = StdRng::seed_from_u64(params.seed);
+    let (width, height, symmetry) = (params.width, params.height, params.symmetry);
     
-    // Create terrain using Perlin noise
+    // Create terrain using Perlin noise, shaped by the preset
     let perlin = Perlin::new().set_seed(params.seed as u32);
-    let mut terrain_tiles = Vec::with_capacity((params.width * params.height) as usize);
+    let mut terrain_tiles = Vec::with_capacity((width * height) as usize);
     
-    for y in 0..params.height {
-        for x in 0..params.width {
-            let nx = x as f64 / params.width as f64;
-            let ny = y as f64 / params.height as f64;
-            
-            // Generate base noise value
-            let noise_val = perlin.get([nx * 4.0, ny * 4.0, 0.0]);
-            
-            // Determine terrain type based on noise
-            let terrain = if noise_val < params.water_threshold {
-                TerrainTile::Water
-            } else if noise_val > params.mountain_threshold {
-                TerrainTile::Mountain
-            } else if noise_val > params.forest_threshold {
-                TerrainTile::Forest
-            } else {
-                TerrainTile::Ground
-            };
-            
-            terrain_tiles.push(terrain);
+    for y in 0..height {
+        for x in 0..width {
+            let nx = x as f64 / width as f64;
+            let ny = y as f64 / height as f64;
+            terrain_tiles.push(terrain_at(params, &perlin, nx, ny));
         }
     }
     
-    // Generate resource positions
-    let mut resource_positions = Vec::new();
-    let num_resources = (params.width * params.height) as f32 * params.resource_density;
+    // Symmetric maps copy each tile of the primary half onto its counterpart
+    if symmetry != MapSymmetry::None {
+        for y in 0..height {
+            for x in 0..width {
+                if !symmetry.is_primary(x, y, width, height) {
+                    let source = symmetry.mirror(Vec2::new(x as f32, y as f32), width, height);
+                    terrain_tiles[(y * width + x) as usize] = terrain_tiles[(source.y as u32 * width + source.x as u32) as usize];
+                }
+            }
+        }
+    }
+    
+    // Generate player starting positions; symmetric ones come in mirrored pairs
+    let starting_positions = if symmetry == MapSymmetry::None {
+        generate_starting_positions(width, height, params.player_count, &terrain_tiles, &mut rng)
+    } else {
+        symmetric_starting_positions(width, height, params.player_count, symmetry)
+    };
+    
+    // Every start gets open ground to build on and the same resources close by
+    for &start in &starting_positions {
+        clear_zone(&mut terrain_tiles, width, height, start, START_ZONE_RADIUS);
+    }
+    for site in watchtower_sites(&starting_positions) {
+        clear_zone(&mut terrain_tiles, width, height, site, TOWER_ZONE_RADIUS);
+    }
+    let mut resource_positions: Vec<(Vec2, ResourceType, f32)> = starting_positions
+        .iter()
+        .flat_map(|&start| start_resources(start, width, height))
+        .collect();
+    
+    // Scatter the rest of the resources outside the start zones, mirrored like the terrain
+    let mut num_resources = (width * height) as f32 * params.resource_density;
+    if symmetry != MapSymmetry::None {
+        num_resources /= 2.0;
+    }
     
     for _ in 0..num_resources as usize {
         // Try to find a valid position for resources (not in water or mountains)
         let mut attempts = 0;
         while attempts < 10 {
-            let x = rng.gen_range(0..params.width);
-            let y = rng.gen_range(0..params.height);
-            let idx = (y * params.width + x) as usize;
+            attempts += 1;
+            let x = rng.gen_range(0..width);
+            let y = rng.gen_range(0..height);
+            let idx = (y * width + x) as usize;
+            let pos = Vec2::new(x as f32, y as f32);
+            
+            let in_start_zone = starting_positions.iter().any(|start| start.distance(pos) <= START_ZONE_RADIUS);
+            if in_start_zone || !symmetry.is_primary(x, y, width, height) {
+                continue;
+            }
             
             if let TerrainTile::Ground | TerrainTile::Forest = terrain_tiles[idx] {
                 // Determine resource type
@@ -93,39 +294,207 @@ pub fn generate_map(params: &MapGenerationParams) -> GameMap {
                     ResourceType::Energy => rng.gen_range(0.0..100.0),
                 };
                 
-                let pos = Vec2::new(x as f32, y as f32);
                 resource_positions.push((pos, resource_type, amount));
+                let mirrored = symmetry.mirror(pos, width, height);
+                if mirrored != pos {
+                    resource_positions.push((mirrored, resource_type, amount));
+                }
                 break;
             }
-            
-            attempts += 1;
         }
     }
     
-    // Generate player starting positions
-    let starting_positions = generate_starting_positions(
-        params.width,
-        params.height,
-        params.player_count,
+    // Creep camps and watchtowers between the players
+    let neutral_sites = generate_neutral_sites(
+        width,
+        height,
         &terrain_tiles,
+        &resource_positions,
+        &starting_positions,
+        symmetry,
         &mut rng,
     );
     
     // Create the game map
-    let mut map = GameMap {
-        width: params.width,
-        height: params.height,
+    GameMap {
+        width,
+        height,
         terrain_tiles,
         resource_positions,
         starting_positions,
         pathfinding_grid: None,
         fog_of_war: Default::default(),
+        occupied_tiles: Default::default(),
+        gate_tiles: Default::default(),
+        gate_grids: Default::default(),
+        neutral_sites,
+    }
+}
+
+/// Terrain at a point given as fractions of the map's size, shaped by the preset
+fn terrain_at(params: &MapGenerationParams, perlin: &Perlin, nx: f64, ny: f64) -> TerrainTile {
+    // Generate base noise value
+    let noise_val = perlin.get([nx * 4.0, ny * 4.0, 0.0]);
+    
+    let elevation = match params.preset {
+        // Raise a grid of land masses out of the sea and roughen their coasts with the noise
+        MapPreset::Islands => {
+            let island = (nx * std::f64::consts::PI * ISLAND_GRID).sin().abs()
+                * (ny * std::f64::consts::PI * ISLAND_GRID).sin().abs();
+            island * 1.5 - 0.5 + noise_val * 0.4
+        }
+        _ => noise_val,
+    };
+    
+    if params.preset == MapPreset::Rivers {
+        // Rivers follow where a second noise layer crosses zero; a third, finer one leaves fords
+        let river = perlin.get([nx * 2.5, ny * 2.5, 10.0]).abs() < RIVER_WIDTH;
+        let ford = perlin.get([nx * 12.0, ny * 12.0, 20.0]) > FORD_THRESHOLD;
+        if river && !ford {
+            return TerrainTile::Water;
+        }
+    }
+    
+    // Determine terrain type based on elevation
+    if elevation < params.water_threshold {
+        TerrainTile::Water
+    } else if elevation > params.mountain_threshold {
+        TerrainTile::Mountain
+    } else if elevation > params.forest_threshold {
+        TerrainTile::Forest
+    } else {
+        TerrainTile::Ground
+    }
+}
+
+/// Starting positions for a symmetric map: pairs down one side, each with its counterpart across the map.
+/// An odd player out starts in the middle, which is its own counterpart.
+fn symmetric_starting_positions(width: u32, height: u32, player_count: u8, symmetry: MapSymmetry) -> Vec<Vec2> {
+    let pairs = player_count as u32 / 2;
+    let mut positions = Vec::with_capacity(player_count as usize);
+    
+    for index in 0..pairs {
+        let x = (width as f32 * START_EDGE_MARGIN).floor();
+        let y = (height as f32 * (index + 1) as f32 / (pairs + 1) as f32).floor();
+        let position = Vec2::new(x, y);
+        positions.push(position);
+        positions.push(symmetry.mirror(position, width, height));
+    }
+    if player_count % 2 == 1 {
+        positions.push(Vec2::new((width / 2) as f32, (height / 2) as f32));
+    }
+    
+    positions
+}
+
+/// Turn every tile within `radius` of `center` into ground
+fn clear_zone(terrain_tiles: &mut [TerrainTile], width: u32, height: u32, center: Vec2, radius: f32) {
+    let min_x = (center.x - radius).floor().max(0.0) as u32;
+    let min_y = (center.y - radius).floor().max(0.0) as u32;
+    let max_x = ((center.x + radius).ceil().max(0.0) as u32).min(width.saturating_sub(1));
+    let max_y = ((center.y + radius).ceil().max(0.0) as u32).min(height.saturating_sub(1));
+    
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            if Vec2::new(x as f32, y as f32).distance(center) <= radius {
+                terrain_tiles[(y * width + x) as usize] = TerrainTile::Ground;
+            }
+        }
+    }
+}
+
+/// The standard resources of a start: minerals in an arc on the side facing away from the middle
+/// of the map, so the base stands between them and its neighbours, and a gas geyser off to one side
+fn start_resources(start: Vec2, width: u32, height: u32) -> Vec<(Vec2, ResourceType, f32)> {
+    let far_corner = Vec2::new(width as f32 - 1.0, height as f32 - 1.0);
+    let away = (start - far_corner * 0.5).try_normalize().unwrap_or(Vec2::X);
+    let facing = away.y.atan2(away.x);
+    let at_angle = |angle: f32| {
+        (start + Vec2::from_angle(angle) * START_RESOURCE_DISTANCE).round().clamp(Vec2::ZERO, far_corner)
+    };
+    
+    let mut resources: Vec<(Vec2, ResourceType, f32)> = (0..START_MINERALS)
+        .map(|index| {
+            let offset = (index as f32 - (START_MINERALS as f32 - 1.0) * 0.5) * START_MINERAL_SPACING;
+            (at_angle(facing + offset), ResourceType::Mineral, START_MINERAL_AMOUNT)
+        })
+        .collect();
+    resources.push((at_angle(facing + std::f32::consts::FRAC_PI_2), ResourceType::Gas, START_GAS_AMOUNT));
+    resources
+}
+
+/// Spawn a resource node entity at each of the map's resource positions
+pub fn spawn_resource_nodes(world: &mut World) {
+    let positions = match world.get_resource::<GameMap>() {
+        Some(map) => map.resource_positions.clone(),
+        None => return,
     };
     
-    // Generate pathfinding grid
-    map.pathfinding_grid = Some(pathfinding::generate_pathfinding_grid(&map, 8.0));
+    for (position, resource_type, amount) in positions {
+        world.spawn((
+            Resource { resource_type, amount },
+            Transform { position, ..Transform::default() },
+        ));
+    }
+}
+
+/// Place neutral sites on a map: camps of creeps guarding some of the resources away from the
+/// starting positions, and a capturable watchtower halfway between each pair of neighbouring players
+pub fn generate_neutral_sites(
+    width: u32,
+    height: u32,
+    terrain_tiles: &[TerrainTile],
+    resource_positions: &[(Vec2, ResourceType, f32)],
+    starting_positions: &[Vec2],
+    symmetry: MapSymmetry,
+    rng: &mut StdRng,
+) -> Vec<NeutralSite> {
+    let mut sites = Vec::new();
+    
+    // Camps are rolled on the primary half only and copied across, so both sides face the same creeps
+    for &(position, _, _) in resource_positions {
+        if !symmetry.is_primary(position.x as u32, position.y as u32, width, height) {
+            continue;
+        }
+        let far_from_starts = starting_positions
+            .iter()
+            .all(|start| start.distance(position) >= CAMP_MIN_START_DISTANCE);
+        if far_from_starts && rng.gen_bool(CAMP_CHANCE) {
+            let count = rng.gen_range(CAMP_SIZE);
+            sites.push(NeutralSite::Camp { position, unit_type: UnitType::Soldier, count });
+            let mirrored = symmetry.mirror(position, width, height);
+            if mirrored != position {
+                sites.push(NeutralSite::Camp { position: mirrored, unit_type: UnitType::Soldier, count });
+            }
+        }
+    }
+    
+    for site in watchtower_sites(starting_positions) {
+        let (x, y) = find_valid_starting_position(site.x as u32, site.y as u32, width, height, terrain_tiles, rng);
+        sites.push(NeutralSite::Structure {
+            position: Vec2::new(x as f32, y as f32),
+            building_type: BuildingType::Watchtower,
+        });
+    }
     
-    map
+    sites
+}
+
+/// Points halfway between neighbouring starts where watchtowers go, before snapping to open ground.
+/// Two players share a single neighbour pair, so they get one tower in the middle.
+fn watchtower_sites(starting_positions: &[Vec2]) -> Vec<Vec2> {
+    let pairs = match starting_positions.len() {
+        0 | 1 => 0,
+        2 => 1,
+        count => count,
+    };
+    (0..pairs)
+        .map(|index| {
+            let first = starting_positions[index];
+            let second = starting_positions[(index + 1) % starting_positions.len()];
+            (first + second) * 0.5
+        })
+        .collect()
 }
 
 /// Generate fair starting positions for players
@@ -355,6 +724,47 @@ pub fn update_fog_of_war(map: &mut GameMap, player_id: u8, visible_tiles: HashSe
     map.fog_of_war.insert(player_id, visible_tiles);
 }
 
+/// Index of the fog of war tile containing a world position
+pub fn fog_tile_index(map: &GameMap, position: Vec2, grid_size: f32) -> u32 {
+    let x = (position.x / grid_size).floor().max(0.0) as u32;
+    let y = (position.y / grid_size).floor().max(0.0) as u32;
+    y * map.width + x
+}
+
+/// Check if a world position is currently visible to a player
+pub fn is_position_visible(map: &GameMap, player_id: u8, position: Vec2, grid_size: f32) -> bool {
+    match map.fog_of_war.get(&player_id) {
+        Some(visible_tiles) => visible_tiles.contains(&fog_tile_index(map, position, grid_size)),
+        // Fog hasn't been computed yet for this player
+        None => true,
+    }
+}
+
+/// Sight range needed to see from one point to another (both in fog tiles) once the terrain
+/// between them is counted; None when a mountain is in the way
+fn sight_distance(map: &GameMap, from: Vec2, to: Vec2, grid_size: f32) -> Option<f32> {
+    let distance = (to - from).length();
+    let steps = distance.ceil() as i32;
+    let (from_tile, to_tile) = (from.floor(), to.floor());
+    
+    // Sample the line once per tile, skipping the viewer's own tile and the tile being looked at
+    let mut extra = 0.0;
+    for step in 1..steps {
+        let point = from + (to - from) * (step as f32 / steps as f32);
+        if point.floor() == from_tile || point.floor() == to_tile {
+            continue;
+        }
+        
+        if let Some(terrain) = pathfinding::terrain_at(point * grid_size, map) {
+            if terrain.blocks_sight() {
+                return None;
+            }
+            extra += terrain.sight_cost() - 1.0;
+        }
+    }
+    Some(distance + extra)
+}
+
 /// Calculate visible tiles based on unit positions and sight ranges
 pub fn calculate_visible_tiles(
     map: &GameMap,
@@ -364,8 +774,8 @@ pub fn calculate_visible_tiles(
     let mut visible_tiles = HashSet::new();
     
     for (position, sight_range) in unit_positions {
-        let center_x = position.x / grid_size;
-        let center_y = position.y / grid_size;
+        let center = *position / grid_size;
+        let (center_x, center_y) = (center.x, center.y);
         let radius = sight_range / grid_size;
         
         // Mark all tiles within sight range as visible
@@ -379,8 +789,13 @@ pub fn calculate_visible_tiles(
                 let dx = x as f32 - center_x;
                 let dy = y as f32 - center_y;
                 let distance = (dx * dx + dy * dy).sqrt();
+                if distance > radius {
+                    continue;
+                }
                 
-                if distance <= radius {
+                // Mountains hide what lies behind them and forests shorten the view through them
+                let tile_center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if sight_distance(map, center, tile_center, grid_size).map_or(false, |sight| sight <= radius) {
                     let tile_idx = (y as u32 * map.width + x as u32);
                     visible_tiles.insert(tile_idx);
                 }
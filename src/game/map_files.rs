@@ -0,0 +1,328 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use glam::Vec2;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use tracing::info;
+
+use crate::ecs::components::ResourceType;
+use crate::ecs::resources::{GameMap, TerrainTile};
+use crate::game::file_format::FileFormat;
+use crate::game::map::{self, MapGenerationParams, MapPreset, MapSymmetry};
+use crate::game::map_validation;
+use crate::game::pathfinding;
+
+/// Current map format version; bump when the layout of `MapFile` changes
+pub const MAP_VERSION: u32 = 2;
+
+/// Oldest map format version that can still be migrated and loaded
+pub const OLDEST_MAP_VERSION: u32 = 1;
+
+/// Header of every map file
+const MAP_FORMAT: FileFormat = FileFormat {
+    kind: "map",
+    magic: *b"RMAP",
+    version: MAP_VERSION,
+    oldest_version: OLDEST_MAP_VERSION,
+};
+
+/// Directory scanned for custom maps
+const MAP_DIRECTORY: &str = "maps";
+
+/// File extension of map files
+const MAP_EXTENSION: &str = "rmap";
+
+/// Edge length in pixels of the square thumbnail stored with each map
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+/// Procedural map sizes offered in the game setup, before any custom maps
+const PROCEDURAL_SIZES: [(&str, u32); 3] = [("Small", 128), ("Medium", 256), ("Large", 512)];
+
+/// Map details shown in the game setup, stored in front of the map data so it can be listed cheaply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapMetadata {
+    pub name: String,
+    pub players: u8, // Number of starting positions
+    pub width: u32,
+    pub height: u32,
+    pub thumbnail: Vec<u8>, // THUMBNAIL_SIZE x THUMBNAIL_SIZE RGBA
+    pub day_night: bool,    // Games on this map have a day and night cycle whatever the game setup says
+}
+
+/// Map details as written by map version 1, before maps could turn on the day and night cycle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MapMetadataV1 {
+    name: String,
+    players: u8,
+    width: u32,
+    height: u32,
+    thumbnail: Vec<u8>,
+}
+
+impl From<MapMetadataV1> for MapMetadata {
+    fn from(metadata: MapMetadataV1) -> Self {
+        Self {
+            name: metadata.name,
+            players: metadata.players,
+            width: metadata.width,
+            height: metadata.height,
+            thumbnail: metadata.thumbnail,
+            day_night: false,
+        }
+    }
+}
+
+/// Serialized map (the pathfinding grid is rebuilt on load); older versions differ only in their metadata
+#[derive(Debug, Serialize, Deserialize)]
+struct MapFile<M = MapMetadata> {
+    metadata: M,
+    terrain_tiles: Vec<TerrainTile>,
+    resource_positions: Vec<(Vec2, ResourceType, f32)>,
+    starting_positions: Vec<Vec2>,
+}
+
+impl<M: Into<MapMetadata>> MapFile<M> {
+    /// Convert a map to the current metadata layout
+    fn migrate(self) -> MapFile {
+        MapFile {
+            metadata: self.metadata.into(),
+            terrain_tiles: self.terrain_tiles,
+            resource_positions: self.resource_positions,
+            starting_positions: self.starting_positions,
+        }
+    }
+}
+
+/// Map a game is played on, as chosen in the game setup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MapChoice {
+    /// Generated from the game seed
+    Procedural { name: String, size: u32 },
+    /// Map file in the maps directory
+    Custom { name: String, file_name: String },
+}
+
+impl Default for MapChoice {
+    fn default() -> Self {
+        procedural_choice(PROCEDURAL_SIZES[1])
+    }
+}
+
+impl MapChoice {
+    /// Name shown in the map dropdown
+    pub fn name(&self) -> &str {
+        match self {
+            MapChoice::Procedural { name, .. } | MapChoice::Custom { name, .. } => name,
+        }
+    }
+}
+
+/// Maps for the game setup dropdown: the procedural sizes followed by the custom maps found on disk
+pub fn setup_map_choices() -> Vec<MapChoice> {
+    let procedural = PROCEDURAL_SIZES.iter().copied().map(procedural_choice);
+    let custom = discover_maps().into_iter().filter_map(|path| {
+        let metadata = read_map_metadata(&path).ok()?;
+        let file_name = path.file_name()?.to_string_lossy().into_owned();
+        Some(MapChoice::Custom { name: metadata.name, file_name })
+    });
+    procedural.chain(custom).collect()
+}
+
+fn procedural_choice((size_name, size): (&str, u32)) -> MapChoice {
+    MapChoice::Procedural {
+        name: format!("Random ({})", size_name),
+        size,
+    }
+}
+
+/// Build the map for a new game; the preset and symmetry only shape random maps
+pub fn create_map(
+    choice: &MapChoice,
+    preset: MapPreset,
+    symmetry: MapSymmetry,
+    seed: u64,
+    player_count: u8,
+) -> Result<GameMap> {
+    let map = match choice {
+        MapChoice::Procedural { size, .. } => map::generate_map(&MapGenerationParams {
+            width: *size,
+            height: *size,
+            seed,
+            player_count,
+            symmetry,
+            ..MapGenerationParams::preset(preset)
+        }),
+        MapChoice::Custom { file_name, .. } => {
+            // Map files only hold the terrain, so neutral sites are placed from the game seed
+            let mut map = load_map(PathBuf::from(MAP_DIRECTORY).join(file_name))?;
+            let mut rng = StdRng::seed_from_u64(seed);
+            map.neutral_sites = map::generate_neutral_sites(
+                map.width,
+                map.height,
+                &map.terrain_tiles,
+                &map.resource_positions,
+                &map.starting_positions[..(player_count as usize).min(map.starting_positions.len())],
+                MapSymmetry::None,
+                &mut rng,
+            );
+
+            // Hand-made maps can't be regenerated, so whatever is unplayable gets carved open
+            let fixes = map_validation::validate_and_fix(&mut map, MapSymmetry::None);
+            for fix in &fixes {
+                info!("Map validation {} on {}", fix, choice.name());
+            }
+            if !fixes.is_empty() {
+                pathfinding::rebuild_pathfinding_grid(&mut map);
+            }
+            map
+        }
+    };
+
+    if map.starting_positions.len() < player_count as usize {
+        return Err(anyhow::anyhow!(
+            "{} has room for {} players, not {}",
+            choice.name(), map.starting_positions.len(), player_count
+        ));
+    }
+    Ok(map)
+}
+
+/// Map files in the maps directory, sorted by file name
+pub fn discover_maps() -> Vec<PathBuf> {
+    let entries = match fs::read_dir(MAP_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |extension| extension == MAP_EXTENSION))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Path a map of the given name is saved to in the maps directory
+pub fn map_path(name: &str) -> PathBuf {
+    let file_stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    PathBuf::from(MAP_DIRECTORY).join(format!("{}.{}", file_stem, MAP_EXTENSION))
+}
+
+/// Write a map to a file, e.g. to keep a generated map for later games; `day_night` gives every
+/// game on it a day and night cycle
+pub fn save_map(map: &GameMap, name: &str, day_night: bool, path: impl AsRef<Path>) -> Result<MapMetadata> {
+    let metadata = MapMetadata {
+        name: name.to_string(),
+        players: map.starting_positions.len() as u8,
+        width: map.width,
+        height: map.height,
+        thumbnail: thumbnail(map),
+        day_night,
+    };
+
+    let map_file = MapFile {
+        metadata: metadata.clone(),
+        terrain_tiles: map.terrain_tiles.clone(),
+        resource_positions: map.resource_positions.clone(),
+        starting_positions: map.starting_positions.clone(),
+    };
+
+    let data = MAP_FORMAT.encode(&map_file)?;
+
+    if let Some(directory) = path.as_ref().parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut file = File::create(path)?;
+    file.write_all(&data)?;
+
+    Ok(metadata)
+}
+
+/// Load a map file into a playable map
+pub fn load_map(path: impl AsRef<Path>) -> Result<GameMap> {
+    let data = read_map_file(path.as_ref())?;
+    let name = path.as_ref().display().to_string();
+    let (version, body) = MAP_FORMAT.read_header(&name, &data)?;
+    let map_file = match version {
+        1 => MAP_FORMAT.decode::<MapFile<MapMetadataV1>>(&name, version, body)?.migrate(),
+        _ => MAP_FORMAT.decode::<MapFile>(&name, version, body)?,
+    };
+
+    let expected_tiles = (map_file.metadata.width * map_file.metadata.height) as usize;
+    if map_file.terrain_tiles.len() != expected_tiles {
+        return Err(anyhow::anyhow!(
+            "{} has {} terrain tiles, expected {}",
+            path.as_ref().display(), map_file.terrain_tiles.len(), expected_tiles
+        ));
+    }
+
+    let mut map = GameMap {
+        width: map_file.metadata.width,
+        height: map_file.metadata.height,
+        terrain_tiles: map_file.terrain_tiles,
+        resource_positions: map_file.resource_positions,
+        starting_positions: map_file.starting_positions,
+        pathfinding_grid: None,
+        fog_of_war: Default::default(),
+        occupied_tiles: Default::default(),
+        gate_tiles: Default::default(),
+        gate_grids: Default::default(),
+        neutral_sites: Vec::new(),
+    };
+    map.pathfinding_grid = Some(pathfinding::generate_pathfinding_grid(&map, pathfinding::PATH_GRID_SIZE));
+
+    Ok(map)
+}
+
+/// Read only the metadata of a map file
+pub fn read_map_metadata(path: impl AsRef<Path>) -> Result<MapMetadata> {
+    let data = read_map_file(path.as_ref())?;
+    let name = path.as_ref().display().to_string();
+    let (version, body) = MAP_FORMAT.read_header(&name, &data)?;
+
+    // Metadata is the first field of MapFile in every version, so bincode can decode it on its own
+    let metadata = match version {
+        1 => MAP_FORMAT.decode::<MapMetadataV1>(&name, version, body)?.into(),
+        _ => MAP_FORMAT.decode::<MapMetadata>(&name, version, body)?,
+    };
+    Ok(metadata)
+}
+
+/// Whether games on a map have a day and night cycle regardless of the game setup
+pub fn map_has_day_night(choice: &MapChoice) -> bool {
+    match choice {
+        MapChoice::Procedural { .. } => false,
+        MapChoice::Custom { file_name, .. } => read_map_metadata(PathBuf::from(MAP_DIRECTORY).join(file_name))
+            .map_or(false, |metadata| metadata.day_night),
+    }
+}
+
+/// Read the raw contents of a map file
+fn read_map_file(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Shrink the minimap image of a map to a square thumbnail
+fn thumbnail(map: &GameMap) -> Vec<u8> {
+    let minimap = map::generate_minimap(map);
+    let mut thumbnail = Vec::with_capacity((THUMBNAIL_SIZE * THUMBNAIL_SIZE * 4) as usize);
+
+    for y in 0..THUMBNAIL_SIZE {
+        for x in 0..THUMBNAIL_SIZE {
+            let source_x = (x * map.width / THUMBNAIL_SIZE) as usize;
+            let source_y = (y * map.height / THUMBNAIL_SIZE) as usize;
+            let index = (source_y * map.width as usize + source_x) * 4;
+            thumbnail.extend_from_slice(&minimap[index..index + 4]);
+        }
+    }
+    thumbnail
+}
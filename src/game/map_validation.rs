@@ -0,0 +1,215 @@
+use glam::Vec2;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+
+use crate::ecs::resources::{GameMap, TerrainTile};
+use crate::game::map::MapSymmetry;
+
+/// Tiles around a start on water or mountains turned into ground
+const START_CLEAR_RADIUS: i32 = 3;
+
+/// Path cost of digging through a blocked tile instead of walking around it; high enough that
+/// carved paths only cut through terrain where no long way round exists
+const CARVE_COST: u32 = 20;
+
+/// A change validation made to a map so every player can play it
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapFix {
+    ClearedStart { index: usize, position: Vec2, tiles: usize },   // A start stood in water or mountains
+    ConnectedStart { index: usize, position: Vec2, tiles: usize }, // A start was cut off from the first one
+    ConnectedResource { position: Vec2, tiles: usize },            // A resource could not be walked to
+}
+
+impl MapFix {
+    /// Terrain tiles the fix turned into ground
+    pub fn tiles_changed(&self) -> usize {
+        match self {
+            MapFix::ClearedStart { tiles, .. }
+            | MapFix::ConnectedStart { tiles, .. }
+            | MapFix::ConnectedResource { tiles, .. } => *tiles,
+        }
+    }
+}
+
+impl fmt::Display for MapFix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapFix::ClearedStart { index, position, tiles } => {
+                write!(f, "cleared {} tiles around start {} at {}", tiles, index + 1, position)
+            }
+            MapFix::ConnectedStart { index, position, tiles } => {
+                write!(f, "carved a {} tile path to start {} at {}", tiles, index + 1, position)
+            }
+            MapFix::ConnectedResource { position, tiles } => {
+                write!(f, "carved a {} tile path to the resource at {}", tiles, position)
+            }
+        }
+    }
+}
+
+/// Check that no start stands in water or mountains, that every start can reach every other by
+/// ground and that every resource can be walked to, fixing the terrain where they don't.
+/// Carved tiles are mirrored like the rest of a symmetric map. Returns what was changed;
+/// the caller rebuilds the pathfinding grid if anything was.
+pub fn validate_and_fix(map: &mut GameMap, symmetry: MapSymmetry) -> Vec<MapFix> {
+    let mut fixes = Vec::new();
+    
+    for (index, &position) in map.starting_positions.clone().iter().enumerate() {
+        if is_passable(map, position) {
+            continue;
+        }
+        let (x, y) = (position.x as i32, position.y as i32);
+        let mut tiles = 0;
+        for dy in -START_CLEAR_RADIUS..=START_CLEAR_RADIUS {
+            for dx in -START_CLEAR_RADIUS..=START_CLEAR_RADIUS {
+                if dx * dx + dy * dy <= START_CLEAR_RADIUS * START_CLEAR_RADIUS {
+                    tiles += clear_tile(map, symmetry, x + dx, y + dy);
+                }
+            }
+        }
+        fixes.push(MapFix::ClearedStart { index, position, tiles });
+    }
+    
+    // Everyone must reach the first start, which makes every start reachable from every other
+    let first = match map.starting_positions.first() {
+        Some(&first) => first,
+        None => return fixes,
+    };
+    let mut reachable = flood_fill(map, first);
+    
+    for (index, &position) in map.starting_positions.clone().iter().enumerate().skip(1) {
+        if reachable[tile_index(map, position)] {
+            continue;
+        }
+        let tiles = carve_path(map, symmetry, position, &reachable);
+        fixes.push(MapFix::ConnectedStart { index, position, tiles });
+        reachable = flood_fill(map, first);
+    }
+    
+    // Workers gather standing next to a resource, so one reachable neighbour is enough
+    for (position, _, _) in map.resource_positions.clone() {
+        let (x, y) = (position.x as i32, position.y as i32);
+        if !in_bounds(map, x, y) {
+            continue;
+        }
+        let gatherable = neighbours(map, x, y)
+            .chain(std::iter::once((x, y)))
+            .any(|(nx, ny)| reachable[(ny as u32 * map.width + nx as u32) as usize]);
+        if gatherable {
+            continue;
+        }
+        let tiles = carve_path(map, symmetry, position, &reachable);
+        fixes.push(MapFix::ConnectedResource { position, tiles });
+        reachable = flood_fill(map, first);
+    }
+    
+    fixes
+}
+
+fn in_bounds(map: &GameMap, x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && x < map.width as i32 && y < map.height as i32
+}
+
+fn tile_index(map: &GameMap, position: Vec2) -> usize {
+    let x = (position.x.max(0.0) as u32).min(map.width.saturating_sub(1));
+    let y = (position.y.max(0.0) as u32).min(map.height.saturating_sub(1));
+    (y * map.width + x) as usize
+}
+
+fn is_passable(map: &GameMap, position: Vec2) -> bool {
+    map.terrain_tiles
+        .get(tile_index(map, position))
+        .map_or(false, |tile| tile.movement_cost().is_some())
+}
+
+/// The four tiles sharing an edge with a tile that lie on the map
+fn neighbours(map: &GameMap, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .into_iter()
+        .map(move |(dx, dy)| (x + dx, y + dy))
+        .filter(move |&(nx, ny)| in_bounds(map, nx, ny))
+}
+
+/// Turn a tile, and its counterpart on a symmetric map, into ground; returns how many tiles changed
+fn clear_tile(map: &mut GameMap, symmetry: MapSymmetry, x: i32, y: i32) -> usize {
+    if !in_bounds(map, x, y) {
+        return 0;
+    }
+    let position = Vec2::new(x as f32, y as f32);
+    let mirrored = symmetry.mirror(position, map.width, map.height);
+    let mut changed = 0;
+    for tile in [position, mirrored] {
+        let index = tile_index(map, tile);
+        if map.terrain_tiles[index].movement_cost().is_none() {
+            map.terrain_tiles[index] = TerrainTile::Ground;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Tiles ground units can walk to from `from`
+fn flood_fill(map: &GameMap, from: Vec2) -> Vec<bool> {
+    let mut reached = vec![false; map.terrain_tiles.len()];
+    if !is_passable(map, from) {
+        return reached;
+    }
+    
+    let mut queue = VecDeque::from([(from.x as i32, from.y as i32)]);
+    reached[tile_index(map, from)] = true;
+    while let Some((x, y)) = queue.pop_front() {
+        for (nx, ny) in neighbours(map, x, y) {
+            let index = (ny as u32 * map.width + nx as u32) as usize;
+            if !reached[index] && map.terrain_tiles[index].movement_cost().is_some() {
+                reached[index] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    reached
+}
+
+/// Dig the cheapest way from `from` into the reachable area, walking over open tiles where it can
+/// and turning the blocked tiles it must cross into ground. Returns how many tiles changed.
+fn carve_path(map: &mut GameMap, symmetry: MapSymmetry, from: Vec2, reachable: &[bool]) -> usize {
+    let start = tile_index(map, from);
+    let mut cost = vec![u32::MAX; map.terrain_tiles.len()];
+    let mut previous = vec![usize::MAX; map.terrain_tiles.len()];
+    let mut open = BinaryHeap::from([Reverse((0, start))]);
+    cost[start] = 0;
+    
+    let mut end = None;
+    while let Some(Reverse((tile_cost, index))) = open.pop() {
+        if reachable[index] {
+            end = Some(index);
+            break;
+        }
+        if tile_cost > cost[index] {
+            continue;
+        }
+        let (x, y) = ((index as u32 % map.width) as i32, (index as u32 / map.width) as i32);
+        for (nx, ny) in neighbours(map, x, y) {
+            let next = (ny as u32 * map.width + nx as u32) as usize;
+            let step = if map.terrain_tiles[next].movement_cost().is_some() { 1 } else { CARVE_COST };
+            if tile_cost + step < cost[next] {
+                cost[next] = tile_cost + step;
+                previous[next] = index;
+                open.push(Reverse((tile_cost + step, next)));
+            }
+        }
+    }
+    
+    // Nothing reachable at all, as when the first start is boxed in; leave the map as it is
+    let mut index = match end {
+        Some(end) => end,
+        None => return 0,
+    };
+    let mut changed = 0;
+    while index != usize::MAX {
+        let (x, y) = ((index as u32 % map.width) as i32, (index as u32 / map.width) as i32);
+        changed += clear_tile(map, symmetry, x, y);
+        index = previous[index];
+    }
+    changed
+}
@@ -1,8 +1,45 @@
+pub mod abilities;
+pub mod ai;
+pub mod animation;
+pub mod buildings;
+pub mod campaign;
+pub mod commands;
+pub mod data;
+pub mod environment;
+pub mod file_format;
+pub mod formation;
+pub mod garrison;
+pub mod map;
+pub mod map_files;
+pub mod map_validation;
+pub mod neutral;
+pub mod path_queue;
+pub mod pathfinding;
+pub mod savegame;
+pub mod scenario;
+pub mod scripting;
+pub mod settings;
+pub mod simulation;
+pub mod stats;
+pub mod tech;
+pub mod threat;
+pub mod transport;
+pub mod units;
+pub mod validation;
+pub mod veterancy;
+pub mod victory;
+
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use glam::Vec2;
 
 use crate::ecs::components::{UnitType, BuildingType, ResourceType};
+use crate::engine::audio::AudioSettings;
+use crate::engine::camera::CameraSettings;
+use crate::engine::renderer::{HealthBarMode, VideoSettings};
+use crate::networking::TransportKind;
+use crate::ui::i18n::DEFAULT_LANGUAGE;
+use crate::ui::theme::DEFAULT_THEME;
 
 /// Game state enum to track which phase the game is in
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -21,6 +58,8 @@ pub struct GameState {
     pub current_tick: u64,
     pub is_multiplayer: bool,
     pub winner: Option<u8>,
+    #[serde(default)]
+    pub winning_team: Option<u8>,
     pub player_count: u8,
     pub seed: u64,
     pub game_speed: f32,
@@ -40,6 +79,24 @@ pub struct GameSettings {
     pub auto_save_enabled: bool,
     pub auto_save_interval: f32,
     pub show_fps: bool,
+    pub health_bars: HealthBarMode,
+    pub combat_text: bool, // Damage, heal and miss numbers floating over units
+    pub ai_slots: Vec<ai::AiSlotConfig>,
+    pub player_team: Option<u8>, // Local player's team in skirmish games; None plays alone
+    pub shared_vision: bool,     // Allies see everything their teammates see
+    pub day_night_cycle: bool,   // Nights cut sight ranges; maps can turn the cycle on as well
+    pub map: map_files::MapChoice,
+    pub map_preset: map::MapPreset,     // Terrain shape of random maps
+    pub map_symmetry: map::MapSymmetry, // How random maps mirror one half onto the other
+    pub victory_condition: victory::VictoryConditionType,
+    pub audio: AudioSettings,
+    pub camera: CameraSettings,
+    pub video: VideoSettings,
+    pub player_name: String, // Shown to other players in multiplayer lobbies
+    pub ui_theme: String,    // Name of a theme in the UI themes file
+    pub language: String,    // Code of the string table text is shown from
+    pub network_transport: TransportKind, // What multiplayer sessions run over
+    pub upnp: bool,                       // Hosts ask the router to forward their port
 }
 
 impl Default for GameSettings {
@@ -50,6 +107,24 @@ impl Default for GameSettings {
             auto_save_enabled: false,
             auto_save_interval: 300.0, // 5 minutes
             show_fps: false,
+            health_bars: HealthBarMode::default(),
+            combat_text: true,
+            ai_slots: Vec::new(),
+            player_team: None,
+            shared_vision: false,
+            day_night_cycle: false,
+            map: map_files::MapChoice::default(),
+            map_preset: map::MapPreset::default(),
+            map_symmetry: map::MapSymmetry::default(),
+            victory_condition: victory::VictoryConditionType::default(),
+            audio: AudioSettings::default(),
+            camera: CameraSettings::default(),
+            video: VideoSettings::default(),
+            player_name: "Player".to_string(),
+            ui_theme: DEFAULT_THEME.to_string(),
+            language: DEFAULT_LANGUAGE.to_string(),
+            network_transport: TransportKind::default(),
+            upnp: false,
         }
     }
 }
@@ -82,6 +157,7 @@ impl GameState {
             current_tick: 0,
             is_multiplayer: false,
             winner: None,
+            winning_team: None,
             player_count: 2,
             seed: 12345, // Default seed, should be randomized for real games
             game_speed: 1.0,
@@ -98,6 +174,7 @@ pub struct GameState {
     pub current_tick: u64,
     pub is_multiplayer: bool,
     pub winner: Option<u8>,
+    pub winning_team: Option<u8>,
     pub player_count: u8,
     pub seed: u64,
     pub game_speed: f32,
@@ -116,6 +193,22 @@ pub struct GameSettings {
     pub auto_save_enabled: bool,
     pub auto_save_interval: f32,
     pub show_fps: bool,
+    pub health_bars: HealthBarMode,
+    pub combat_text: bool, // Damage, heal and miss numbers floating over units
+    pub ai_slots: Vec<ai::AiSlotConfig>,
+    pub player_team: Option<u8>, // Local player's team in skirmish games; None plays alone
+    pub shared_vision: bool,     // Allies see everything their teammates see
+    pub day_night_cycle: bool,   // Nights cut sight ranges; maps can turn the cycle on as well
+    pub map: map_files::MapChoice,
+    pub map_preset: map::MapPreset,     // Terrain shape of random maps
+    pub map_symmetry: map::MapSymmetry, // How random maps mirror one half onto the other
+    pub victory_condition: victory::VictoryConditionType,
+    pub audio: AudioSettings,
+    pub camera: CameraSettings,
+    pub video: VideoSettings,
+    pub player_name: String, // Shown to other players in multiplayer lobbies
+    pub ui_theme: String,    // Name of a theme in the UI themes file
+    pub language: String,    // Code of the string table text is shown from
 }
 
 impl Default for GameSettings {
@@ -126,6 +219,22 @@ impl Default for GameSettings {
             auto_save_enabled: false,
             auto_save_interval: 300.0, // 5 minutes
             show_fps: false,
+            health_bars: HealthBarMode::default(),
+            combat_text: true,
+            ai_slots: Vec::new(),
+            player_team: None,
+            shared_vision: false,
+            day_night_cycle: false,
+            map: map_files::MapChoice::default(),
+            map_preset: map::MapPreset::default(),
+            map_symmetry: map::MapSymmetry::default(),
+            victory_condition: victory::VictoryConditionType::default(),
+            audio: AudioSettings::default(),
+            camera: CameraSettings::default(),
+            video: VideoSettings::default(),
+            player_name: "Player".to_string(),
+            ui_theme: DEFAULT_THEME.to_string(),
+            language: DEFAULT_LANGUAGE.to_string(),
         }
     }
 }
@@ -158,6 +267,7 @@ impl GameState {
             current_tick: 0,
             is_multiplayer: false,
             winner: None,
+            winning_team: None,
             player_count: 2,
             seed: 12345, // Default seed, should be randomized for real games
             game_speed: 1.0,
@@ -170,9 +280,6 @@ impl GameState {
     
     pub fn update(&mut self) {
         self.current_tick += 1;
-        
-        // Check for game over conditions
-        self.check_victory_conditions();
     }
     
     pub fn start_game(&mut self, multiplayer: bool, player_count: u8, seed: u64) {
@@ -182,6 +289,7 @@ impl GameState {
         self.seed = seed;
         self.current_tick = 0;
         self.winner = None;
+        self.winning_team = None;
         
         // Reset player resources and supply
         for player_id in 0..player_count {
@@ -252,35 +360,18 @@ impl GameState {
         }
     }
     
-    fn check_victory_conditions(&mut self, world: &World) {
-        let mut active_players = 0;
-        let mut last_active_player = 0;
-    
-        // Count players with active headquarters
-        let mut hq_query = world.query::<(&Building, &Owner)>();
-        let mut player_hqs: HashMap<u8, usize> = HashMap::new();
-    
-        for (building, owner) in hq_query.iter() {
-            if building.building_type == BuildingType::Headquarters && building.health > 0.0 {
-                *player_hqs.entry(owner.0).or_insert(0) += 1;
+    /// End the game with the result the victory condition reached
+    pub fn end_game(&mut self, outcome: victory::VictoryOutcome) {
+        match outcome {
+            victory::VictoryOutcome::Winner { team, player_id } => {
+                self.winning_team = Some(team);
+                self.winner = Some(player_id);
             }
-        }
-    
-        for (player_id, hq_count) in player_hqs.iter() {
-            if *hq_count > 0 {
-                active_players += 1;
-                last_active_player = *player_id;
+            victory::VictoryOutcome::Draw => {
+                self.winning_team = None;
+                self.winner = None;
             }
         }
-    
-        // If only one player remains, they win
-        if active_players == 1 {
-            self.winner = Some(last_active_player);
-            self.phase = GamePhase::GameOver;
-        }
-        // If no players remain, game is a draw
-        else if active_players == 0 {
-            self.phase = GamePhase::GameOver;
-        }
+        self.phase = GamePhase::GameOver;
     }
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::CommandQueue;
+use glam::Vec2;
+use std::collections::BTreeSet;
+
+use crate::ecs::components::{
+    AttackTarget, Building, BuildingType, Capturable, Collider, Creep, Movement, Owner, Selectable, Transform, Unit,
+};
+use crate::ecs::resources::{GameMap, GameTime, NeutralSite, PlayerAlerts, TechState, NEUTRAL_PLAYER};
+use crate::ecs::spatial::SpatialGrid;
+use crate::game::commands::set_path;
+use crate::game::data;
+use crate::game::units::{self, UnitSpawnParams};
+
+/// Creeps chasing something further than this from their post give up and walk back
+pub const CREEP_LEASH: f32 = 60.0;
+
+/// Distance from a camp's center its creeps stand at
+const CAMP_SPREAD: f32 = 4.0;
+
+/// Units this close to a capturable structure count towards taking or holding it
+pub const CAPTURE_RADIUS: f32 = 48.0;
+
+/// Seconds a player's units must be alone around a structure to take it
+pub const CAPTURE_SECONDS: f32 = 15.0;
+
+/// Whether buildings of a type change hands by capture rather than being built
+pub fn is_capturable(building_type: BuildingType) -> bool {
+    building_type == BuildingType::Watchtower
+}
+
+/// Spawn the creep camps and structures the map placed, all owned by the neutral player
+pub fn spawn_neutral_sites(world: &mut World) {
+    let sites = match world.get_resource::<GameMap>() {
+        Some(map) => map.neutral_sites.clone(),
+        None => return,
+    };
+    
+    let mut queue = CommandQueue::default();
+    {
+        let tech_state = world.resource::<TechState>();
+        let mut commands = Commands::new(&mut queue, world);
+        for site in sites {
+            match site {
+                NeutralSite::Camp { position, unit_type, count } => {
+                    for index in 0..count {
+                        let angle = index as f32 * std::f32::consts::TAU / count as f32;
+                        let post = position + Vec2::from_angle(angle) * CAMP_SPREAD;
+                        let params = UnitSpawnParams { unit_type, owner: NEUTRAL_PLAYER, position: post };
+                        if let Some(entity) = units::spawn_unit(&mut commands, params, tech_state) {
+                            commands.entity(entity).insert(Creep { post });
+                        }
+                    }
+                }
+                NeutralSite::Structure { position, building_type } => {
+                    let building_data = data::game_data().building(building_type);
+                    commands.spawn((
+                        Building {
+                            building_type,
+                            health: building_data.health,
+                            max_health: building_data.health,
+                            production_queue: std::collections::VecDeque::new(),
+                            production_progress: None,
+                            construction_progress: None,
+                            rally_point: None,
+                        },
+                        Transform {
+                            position,
+                            rotation: 0.0,
+                            scale: building_data.size,
+                        },
+                        Owner(NEUTRAL_PLAYER),
+                        Collider {
+                            radius: building_data.size.max_element() * 0.5,
+                            collision_layer: 2, // Building layer
+                            collision_mask: 1 | 2,
+                        },
+                        Capturable::default(),
+                        Selectable,
+                    ));
+                }
+            }
+        }
+    }
+    queue.apply(world);
+}
+
+/// System that calls creeps back to their post once a chase has taken them too far from it.
+/// Runs after unit_behavior_system so the retreat wins over the chase it just planned.
+pub fn creep_leash_system(
+    mut commands: Commands,
+    mut creeps: Query<(Entity, &Creep, &Transform, &Owner, Option<&Collider>, &mut Movement), With<AttackTarget>>,
+    game_map: Res<GameMap>,
+) {
+    for (entity, creep, transform, owner, collider, mut movement) in creeps.iter_mut() {
+        if transform.position.distance(creep.post) <= CREEP_LEASH {
+            continue;
+        }
+        
+        commands.entity(entity).remove::<AttackTarget>();
+        if let Some(grid) = game_map.grid_for(owner.0) {
+            let unit_radius = collider.map_or(0.0, |collider| collider.radius);
+            set_path(&mut movement, transform.position, creep.post, grid, unit_radius);
+        }
+    }
+}
+
+/// System that hands capturable structures to the player whose units have had them to themselves
+/// for CAPTURE_SECONDS. Progress holds while the structure is contested and drains while nobody is there.
+pub fn capture_system(
+    mut structures: Query<(&Building, &Transform, &mut Owner, &mut Capturable)>,
+    units: Query<(&Unit, &Owner), Without<Building>>,
+    spatial_grid: Res<SpatialGrid>,
+    time: Res<GameTime>,
+    mut player_alerts: ResMut<PlayerAlerts>,
+) {
+    for (building, transform, mut owner, mut capturable) in structures.iter_mut() {
+        let present: BTreeSet<u8> = spatial_grid
+            .query_radius(transform.position, CAPTURE_RADIUS)
+            .into_iter()
+            .filter_map(|nearby| units.get(nearby.entity).ok())
+            .filter(|(unit, _)| unit.health > 0.0)
+            .map(|(_, unit_owner)| unit_owner.0)
+            .collect();
+        
+        // Creeps standing by block a capture just like another player's units
+        let sole_player = if present.len() == 1 { present.iter().next().copied() } else { None };
+        match sole_player.filter(|&player| player != NEUTRAL_PLAYER && player != owner.0) {
+            Some(player) if capturable.claimant == Some(player) => capturable.progress += time.delta_time,
+            Some(player) => {
+                capturable.claimant = Some(player);
+                capturable.progress = time.delta_time;
+            }
+            None if present.len() > 1 => {}
+            None => {
+                capturable.progress = (capturable.progress - time.delta_time).max(0.0);
+                if capturable.progress <= 0.0 {
+                    capturable.claimant = None;
+                }
+            }
+        }
+        
+        let new_owner = match capturable.claimant.filter(|_| capturable.progress >= CAPTURE_SECONDS) {
+            Some(player) => player,
+            None => continue,
+        };
+        let name = &data::game_data().building(building.building_type).name;
+        if owner.0 != NEUTRAL_PLAYER {
+            player_alerts.alerts.push((owner.0, format!("Our {} has been captured", name)));
+        }
+        player_alerts.alerts.push((new_owner, format!("{} captured", name)));
+        owner.0 = new_owner;
+        *capturable = Capturable::default();
+    }
+}
@@ -0,0 +1,336 @@
+use bevy_ecs::prelude::*;
+use bevy_tasks::{AsyncComputeTaskPool, Task, TaskPool};
+use glam::Vec2;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::ecs::components::{AwaitingPath, Building, FlowFieldMove, Movement};
+use crate::ecs::resources::{FlowField, GameMap, GameTime, PathfindingGrid};
+use crate::game::pathfinding::{self, grid_to_world, world_to_grid, PATH_GRID_SIZE};
+
+/// Searches handed to worker threads per tick; requests past this wait for the following ticks
+pub const PATHS_PER_TICK: usize = 32;
+
+/// Groups at least this large ordered to one place share a flow field instead of a route each
+pub const FLOW_FIELD_GROUP_SIZE: usize = 24;
+
+/// Units steering by a flow field head straight for their own destination once this close
+pub const FLOW_FIELD_ARRIVAL_DISTANCE: f32 = 48.0;
+
+/// Ticks between handing a request to a worker and the unit taking the route. Every machine in a
+/// lockstep game applies it on the same tick however fast its workers are, waiting for them if late
+pub const PATH_RESULT_DELAY: u64 = 2;
+
+/// Side of the squares of grid cells that share routes: units starting in one and heading for
+/// another take the same way, joining it where it passes closest to them, and groups heading for
+/// one share its flow field
+const CACHE_REGION_CELLS: i32 = 4;
+
+/// Routes and flow fields kept before the cache starts over
+const MAX_CACHED_PATHS: usize = 1024;
+
+/// A unit waiting for its route
+#[derive(Debug, Clone, Copy)]
+struct PathRequest {
+    id: u64,
+    entity: Entity,
+    player_id: u8, // Whose grid to search; players walk through their own gates
+    start: Vec2,
+    goal: Vec2,
+    radius: f32,
+    group_target: Option<Vec2>, // Where a large group is heading, to steer by a flow field toward
+}
+
+/// Requests answered by the same search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SearchKey {
+    /// Same player, same regions at both ends and units of a size
+    Route { player_id: u8, start_region: (i32, i32), goal_region: (i32, i32), radius_cells: i32 },
+    /// Same player and the same region the group is heading for
+    Field { player_id: u8, goal_region: (i32, i32) },
+}
+
+impl SearchKey {
+    fn of(request: &PathRequest) -> Self {
+        match request.group_target {
+            Some(target) => SearchKey::Field { player_id: request.player_id, goal_region: region(target) },
+            None => SearchKey::Route {
+                player_id: request.player_id,
+                start_region: region(request.start),
+                goal_region: region(request.goal),
+                radius_cells: (request.radius / PATH_GRID_SIZE).ceil() as i32,
+            },
+        }
+    }
+}
+
+fn region(position: Vec2) -> (i32, i32) {
+    let (x, y) = world_to_grid(position, PATH_GRID_SIZE);
+    (x.div_euclid(CACHE_REGION_CELLS), y.div_euclid(CACHE_REGION_CELLS))
+}
+
+/// How a search tells units to go
+#[derive(Debug, Clone)]
+enum Guidance {
+    Route(Option<Vec<Vec2>>), // None when the goal can't be reached
+    Field(Arc<FlowField>),
+}
+
+/// A search being worked on by a worker thread, with every request it answers
+struct SearchJob {
+    apply_tick: u64,
+    key: SearchKey,
+    task: Task<Guidance>,
+    requests: Vec<PathRequest>,
+}
+
+/// Pathfinding spread over worker threads and ticks, so ordering a large selection across the map
+/// doesn't stall the tick that applies the order
+#[derive(Resource, Default)]
+pub struct PathQueue {
+    next_id: u64,
+    waiting: VecDeque<PathRequest>,
+    jobs: VecDeque<SearchJob>, // Oldest first, so due in order
+    cache: HashMap<SearchKey, Guidance>, // Routes found and flow fields built
+}
+
+/// Send a unit toward a destination and queue the search for its route. Until the route arrives a
+/// few ticks later, the unit heads straight for the destination
+pub fn queue_path(world: &mut World, entity: Entity, player_id: u8, start: Vec2, destination: Vec2, unit_radius: f32) {
+    push_request(world, entity, player_id, start, destination, unit_radius, None);
+}
+
+/// Like `queue_path` for one unit of a group order. Large groups steer by one flow field toward
+/// where the group is heading instead of searching a route for every unit
+pub fn queue_group_path(
+    world: &mut World,
+    entity: Entity,
+    player_id: u8,
+    start: Vec2,
+    destination: Vec2,
+    unit_radius: f32,
+    group_size: usize,
+    group_target: Vec2,
+) {
+    let group_target = (group_size >= FLOW_FIELD_GROUP_SIZE).then_some(group_target);
+    push_request(world, entity, player_id, start, destination, unit_radius, group_target);
+}
+
+fn push_request(
+    world: &mut World,
+    entity: Entity,
+    player_id: u8,
+    start: Vec2,
+    destination: Vec2,
+    unit_radius: f32,
+    group_target: Option<Vec2>,
+) {
+    let mut queue = world.get_resource_or_insert_with(PathQueue::default);
+    let id = queue.next_id;
+    queue.next_id += 1;
+    queue.waiting.push_back(PathRequest { id, entity, player_id, start, goal: destination, radius: unit_radius, group_target });
+    
+    let mut entity = world.entity_mut(entity);
+    if let Some(mut movement) = entity.get_mut::<Movement>() {
+        movement.path = vec![destination];
+        movement.path_index = 0;
+        movement.target = Some(destination);
+    }
+    entity.insert(AwaitingPath(id));
+}
+
+/// Give units the routes and flow fields due this tick, then hand this tick's share of the waiting
+/// requests to worker threads
+pub fn path_queue_system(
+    mut commands: Commands,
+    queue: Option<ResMut<PathQueue>>,
+    mut units: Query<(&mut Movement, &AwaitingPath)>,
+    added_buildings: Query<(), Added<Building>>,
+    mut removed_buildings: RemovedComponents<Building>,
+    game_map: Res<GameMap>,
+    time: Res<GameTime>,
+) {
+    let mut queue = match queue {
+        Some(queue) => queue,
+        None => return,
+    };
+    
+    // Buildings going up or coming down change the way, so cached routes and fields can't be trusted
+    let removed_any = removed_buildings.iter().count() > 0;
+    if !added_buildings.is_empty() || removed_any {
+        queue.cache.clear();
+    }
+    
+    // Searches due this tick, in the order they were asked for
+    while queue.jobs.front().map_or(false, |job| job.apply_tick <= time.current_tick) {
+        let job = queue.jobs.pop_front().unwrap();
+        // Deliberately waits for a search still running rather than applying it a tick later: a slow
+        // machine may stall the tick, but never takes the route on a different tick from the others.
+        // PATHS_PER_TICK keeps the work that can be left over small
+        let guidance = pollster::block_on(job.task);
+        if !matches!(guidance, Guidance::Route(None)) {
+            if queue.cache.len() >= MAX_CACHED_PATHS {
+                queue.cache.clear();
+            }
+            queue.cache.insert(job.key, guidance.clone());
+        }
+        for request in &job.requests {
+            apply_guidance(&mut commands, &mut units, &game_map, request, &guidance);
+        }
+    }
+    
+    // Requests a cached search or one already being worked on answers cost nothing from the budget
+    let mut grids: HashMap<u8, Option<Arc<PathfindingGrid>>> = HashMap::new();
+    let mut started = 0;
+    while started < PATHS_PER_TICK {
+        let request = match queue.waiting.pop_front() {
+            Some(request) => request,
+            None => break,
+        };
+        let key = SearchKey::of(&request);
+        
+        if let Some(guidance) = queue.cache.get(&key) {
+            apply_guidance(&mut commands, &mut units, &game_map, &request, guidance);
+            continue;
+        }
+        if let Some(job) = queue.jobs.iter_mut().find(|job| job.key == key) {
+            job.requests.push(request);
+            continue;
+        }
+        
+        // Workers get their own copy of the grid, shared by every search this tick
+        let grid = grids
+            .entry(request.player_id)
+            .or_insert_with(|| game_map.grid_for(request.player_id).cloned().map(Arc::new))
+            .clone();
+        let grid = match grid {
+            Some(grid) => grid,
+            None => {
+                apply_guidance(&mut commands, &mut units, &game_map, &request, &Guidance::Route(None));
+                continue;
+            }
+        };
+        
+        // Fields lead to the middle of the region the group is heading for, so every group going there can share it
+        let task = AsyncComputeTaskPool::init(TaskPool::default).spawn(async move {
+            match key {
+                SearchKey::Field { goal_region: (x, y), .. } => {
+                    let center = (x * CACHE_REGION_CELLS + CACHE_REGION_CELLS / 2, y * CACHE_REGION_CELLS + CACHE_REGION_CELLS / 2);
+                    let target = grid_to_world(center, PATH_GRID_SIZE);
+                    Guidance::Field(Arc::new(pathfinding::create_flow_field(target, &grid, PATH_GRID_SIZE)))
+                }
+                SearchKey::Route { .. } => {
+                    Guidance::Route(pathfinding::request_path(request.start, request.goal, &grid, request.radius))
+                }
+            }
+        });
+        queue.jobs.push_back(SearchJob {
+            apply_tick: time.current_tick + PATH_RESULT_DELAY,
+            key,
+            task,
+            requests: vec![request],
+        });
+        started += 1;
+    }
+}
+
+/// Put a unit on its route, ending at its own goal, or on its group's flow field, or stop it if its
+/// goal can't be reached. Units given a newer order since asking are left alone
+fn apply_guidance(
+    commands: &mut Commands,
+    units: &mut Query<(&mut Movement, &AwaitingPath)>,
+    game_map: &GameMap,
+    request: &PathRequest,
+    guidance: &Guidance,
+) {
+    let mut movement = match units.get_mut(request.entity) {
+        Ok((movement, awaiting)) if awaiting.0 == request.id => movement,
+        _ => return,
+    };
+    commands.entity(request.entity).remove::<AwaitingPath>();
+    if movement.target != Some(request.goal) {
+        return;
+    }
+    
+    match guidance {
+        // The unit keeps heading for its destination, now steered around what lies between
+        Guidance::Field(field) => {
+            commands.entity(request.entity).insert(FlowFieldMove { destination: request.goal, field: field.clone() });
+        }
+        Guidance::Route(Some(route)) => {
+            commands.entity(request.entity).remove::<FlowFieldMove>();
+            let goal_walkable = game_map
+                .grid_for(request.player_id)
+                .map_or(false, |grid| pathfinding::is_position_walkable(request.goal, grid, PATH_GRID_SIZE));
+            movement.path = fit_route(route, request.start, request.goal, goal_walkable);
+            movement.path_index = 0;
+        }
+        Guidance::Route(None) => {
+            // Target unreachable - stop instead of walking through obstacles
+            movement.path.clear();
+            movement.path_index = 0;
+            movement.target = None;
+        }
+    }
+}
+
+/// A route found for another unit in the same regions, trimmed to run from the waypoint nearest this
+/// unit's start to the one nearest its goal, so it doesn't walk to where the other unit set out from
+/// first. The last waypoint becomes the goal itself when that can be stood on
+fn fit_route(route: &[Vec2], start: Vec2, goal: Vec2, goal_walkable: bool) -> Vec<Vec2> {
+    if route.is_empty() {
+        return Vec::new();
+    }
+    let first = nearest_waypoint(route, start);
+    let last = first + nearest_waypoint(&route[first..], goal);
+    let mut path = route[first..=last].to_vec();
+    if let (true, Some(end)) = (goal_walkable, path.last_mut()) {
+        *end = goal;
+    }
+    path
+}
+
+/// Index of the waypoint closest to a position; the earliest of equally close ones
+fn nearest_waypoint(route: &[Vec2], position: Vec2) -> usize {
+    route
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
+        .map_or(0, |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn route() -> Vec<Vec2> {
+        (0..10).map(|i| Vec2::new(i as f32 * PATH_GRID_SIZE, 0.0)).collect()
+    }
+    
+    #[test]
+    fn shared_route_is_joined_where_it_passes_closest() {
+        let start = Vec2::new(3.2 * PATH_GRID_SIZE, PATH_GRID_SIZE);
+        let goal = Vec2::new(9.4 * PATH_GRID_SIZE, 0.0);
+        let path = fit_route(&route(), start, goal, true);
+        
+        assert_eq!(path.first(), Some(&Vec2::new(3.0 * PATH_GRID_SIZE, 0.0)));
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(path.len(), 7);
+    }
+    
+    #[test]
+    fn shared_route_stops_short_of_the_other_units_goal() {
+        let goal = Vec2::new(6.8 * PATH_GRID_SIZE, 0.5 * PATH_GRID_SIZE);
+        let path = fit_route(&route(), Vec2::ZERO, goal, false);
+        
+        assert_eq!(path.first(), Some(&Vec2::ZERO));
+        assert_eq!(path.last(), Some(&Vec2::new(7.0 * PATH_GRID_SIZE, 0.0)));
+    }
+    
+    #[test]
+    fn own_route_is_kept_whole() {
+        let route = route();
+        let path = fit_route(&route, route[0], *route.last().unwrap(), true);
+        assert_eq!(path, route);
+    }
+}
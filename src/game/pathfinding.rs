@@ -3,7 +3,10 @@ use pathfinding::prelude::astar;
 use std::collections::{HashMap, HashSet};
 
 
-use crate::ecs::resources::{GameMap, PathfindingGrid, PathNode};
+use crate::ecs::resources::{FlowField, GameMap, PathfindingGrid, PathNode};
+
+/// Size of a pathfinding grid cell in world units
+pub const PATH_GRID_SIZE: f32 = 8.0;
 
 /// Convert world position to grid coordinates
 pub fn world_to_grid(pos: Vec2, grid_size: f32) -> (i32, i32) {
@@ -98,6 +101,57 @@ pub fn find_path(
     }
 }
 
+/// Request a smoothed waypoint path for a unit, ending exactly at the goal when possible
+pub fn request_path(
+    start: Vec2,
+    goal: Vec2,
+    grid: &PathfindingGrid,
+    unit_radius: f32,
+) -> Option<Vec<Vec2>> {
+    let raw_path = find_path(start, goal, grid, PATH_GRID_SIZE, unit_radius)?;
+    let mut path = smooth_path(&raw_path, grid, PATH_GRID_SIZE, unit_radius);
+    
+    // The first waypoint is the cell the unit is already standing in
+    if path.len() > 1 {
+        path.remove(0);
+    }
+    
+    // Replace the last cell center with the exact goal if the unit can stand there
+    if is_position_walkable(goal, grid, PATH_GRID_SIZE) {
+        if let Some(last) = path.last_mut() {
+            *last = goal;
+        }
+    }
+    
+    Some(path)
+}
+
+/// Check if a world position lies on a walkable grid cell
+pub fn is_position_walkable(pos: Vec2, grid: &PathfindingGrid, grid_size: f32) -> bool {
+    is_walkable(world_to_grid(pos, grid_size), grid, 0.0)
+}
+
+/// Rebuild the map's pathfinding grid from terrain and block the cells covering occupied tiles,
+/// plus a grid per player allowed through a gate with those gates left open
+pub fn rebuild_pathfinding_grid(map: &mut GameMap) {
+    let terrain_grid = generate_pathfinding_grid(map, PATH_GRID_SIZE);
+    
+    let mut gate_users: Vec<u8> = map.gate_tiles.values().flatten().copied().collect();
+    gate_users.sort();
+    gate_users.dedup();
+    let mut gate_grids = HashMap::new();
+    for player_id in gate_users {
+        let mut grid = terrain_grid.clone();
+        block_occupied_tiles(&mut grid, map, PATH_GRID_SIZE, Some(player_id));
+        gate_grids.insert(player_id, grid);
+    }
+    
+    let mut grid = terrain_grid;
+    block_occupied_tiles(&mut grid, map, PATH_GRID_SIZE, None);
+    map.pathfinding_grid = Some(grid);
+    map.gate_grids = gate_grids;
+}
+
 /// Check if grid position is in bounds
 fn is_in_bounds(pos: (i32, i32), grid: &PathfindingGrid) -> bool {
     pos.0 >= 0 && pos.0 < grid.width as i32 && pos.1 >= 0 && pos.1 < grid.height as i32
@@ -160,26 +214,11 @@ pub fn generate_pathfinding_grid(map: &GameMap, grid_size: f32) -> PathfindingGr
                 cost: 1.0,
             };
             
-            // Check terrain type at this position
+            // Water and mountains can't be crossed, forests are slow going
             if let Some(terrain_idx) = get_terrain_at(world_pos, map) {
-                let terrain = &map.terrain_tiles[terrain_idx];
-                match terrain {
-                    crate::ecs::resources::TerrainTile::Ground => {
-                        // Basic terrain, default cost
-                        node.cost = 1.0;
-                    }
-                    crate::ecs::resources::TerrainTile::Water => {
-                        // Water is not walkable for land units
-                        node.walkable = false;
-                    }
-                    crate::ecs::resources::TerrainTile::Mountain => {
-                        // Mountains are not walkable
-                        node.walkable = false;
-                    }
-                    crate::ecs::resources::TerrainTile::Forest => {
-                        // Forests slow movement
-                        node.cost = 2.0;
-                    }
+                match map.terrain_tiles[terrain_idx].movement_cost() {
+                    Some(cost) => node.cost = cost,
+                    None => node.walkable = false,
                 }
             }
             
@@ -195,6 +234,11 @@ pub fn generate_pathfinding_grid(map: &GameMap, grid_size: f32) -> PathfindingGr
 }
 
 /// Helper function to get terrain index at a world position
+/// Terrain tile at a world position, if it lies on the map
+pub fn terrain_at(pos: Vec2, map: &GameMap) -> Option<crate::ecs::resources::TerrainTile> {
+    get_terrain_at(pos, map).map(|idx| map.terrain_tiles[idx])
+}
+
 fn get_terrain_at(pos: Vec2, map: &GameMap) -> Option<usize> {
     // In a real implementation, this would check the actual map data
     // This is a placeholder
@@ -213,37 +257,22 @@ fn get_terrain_at(pos: Vec2, map: &GameMap) -> Option<usize> {
     }
 }
 
-/// Update the pathfinding grid with dynamic obstacles
-pub fn update_grid_with_obstacles(
-    grid: &mut PathfindingGrid,
-    obstacles: &[(Vec2, f32)], // Position and radius pairs
-    grid_size: f32,
-) {
-    // Reset grid to initial state (would need to store the original grid)
-    // For each obstacle, mark grid cells as unwalkable
-    for (position, radius) in obstacles {
-        let grid_pos = world_to_grid(*position, grid_size);
-        let grid_radius = (radius / grid_size).ceil() as i32;
+/// Mark every grid cell that contains an occupied map tile as unwalkable, except tiles under gates `player_id` may use
+pub fn block_occupied_tiles(grid: &mut PathfindingGrid, map: &GameMap, grid_size: f32, player_id: Option<u8>) {
+    for &index in &map.occupied_tiles {
+        let passable = player_id.map_or(false, |player_id| {
+            map.gate_tiles.get(&index).map_or(false, |players| players.contains(&player_id))
+        });
+        if passable {
+            continue;
+        }
         
-        // Mark cells within radius as unwalkable
-        for y in -grid_radius..=grid_radius {
-            for x in -grid_radius..=grid_radius {
-                let pos = (grid_pos.0 + x, grid_pos.1 + y);
-                
-                // Skip if out of bounds
-                if !is_in_bounds(pos, grid) {
-                    continue;
-                }
-                
-                // Check if cell center is within obstacle radius
-                let cell_center = grid_to_world(pos, grid_size);
-                let distance = (*position - cell_center).length();
-                
-                if distance <= *radius {
-                    let idx = grid_pos_to_index(pos, grid.width);
-                    grid.nodes[idx].walkable = false;
-                }
-            }
+        // Tiles are one world unit across, so a tile's center is its world position
+        let tile_center = Vec2::new((index % map.width) as f32 + 0.5, (index / map.width) as f32 + 0.5);
+        let pos = world_to_grid(tile_center, grid_size);
+        if is_in_bounds(pos, grid) {
+            let idx = grid_pos_to_index(pos, grid.width);
+            grid.nodes[idx].walkable = false;
         }
     }
 }
@@ -285,8 +314,11 @@ pub fn smooth_path(path: &[Vec2], grid: &PathfindingGrid, grid_size: f32, unit_r
 }
 
 /// Check if there's a clear line of sight between two points
-fn has_line_of_sight(start: Vec2, end: Vec2, grid: &PathfindingGrid, grid_size: f32, unit_radius: f32) -> bool {
+pub fn has_line_of_sight(start: Vec2, end: Vec2, grid: &PathfindingGrid, grid_size: f32, unit_radius: f32) -> bool {
     let distance = (end - start).length();
+    if distance < f32::EPSILON {
+        return is_walkable(world_to_grid(start, grid_size), grid, unit_radius);
+    }
     let direction = (end - start).normalize();
     
     // Check points along the line
@@ -310,7 +342,7 @@ pub fn create_flow_field(
     target: Vec2,
     grid: &PathfindingGrid,
     grid_size: f32,
-) -> HashMap<(i32, i32), Vec2> {
+) -> FlowField {
     let target_grid = world_to_grid(target, grid_size);
     let mut flow_field = HashMap::new();
     
@@ -336,8 +368,8 @@ pub fn create_flow_field(
                 continue;
             }
             
-            // Diagonal movement costs more
-            let move_cost = if next.0 != x && next.1 != y { 1.4 } else { 1.0 };
+            // Diagonal movement costs more; costs are in tenths of a cell so the difference survives rounding
+            let move_cost = if next.0 != x && next.1 != y { 14.0 } else { 10.0 };
             
             // Add terrain cost
             let idx = grid_pos_to_index(next, grid.width);
@@ -382,5 +414,5 @@ pub fn create_flow_field(
         }
     }
     
-    flow_field
+    FlowField { directions: flow_field }
 }
\ No newline at end of file
@@ -0,0 +1,1155 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+use tracing::info;
+
+use crate::ecs::components::{
+    Building, BuildingType, Capturable, CarriedResources, Collider, Corpse, Creep, Experience, Garrisoned, Gate, Movement,
+    NetworkId, Owner, Passenger, Resource, ResourceType, Selectable, Transform, Unit, Upgrading,
+};
+use crate::ecs::network_id::{assign_network_ids, NetworkIds};
+use crate::ecs::resources::{
+    ControlGroups, GameMap, PlayerInfo, PlayerResources, RevealedAreas, SelectionState, TechState, TerrainTile,
+    NEUTRAL_PLAYER,
+};
+use crate::engine::audio::AudioSettings;
+use crate::engine::camera::CameraSettings;
+use crate::engine::renderer::{HealthBarMode, VideoSettings};
+use crate::game::ai::{AiDifficulty, AiPersonality, AiSlotConfig};
+use crate::game::buildings;
+use crate::game::campaign::CampaignMission;
+use crate::game::file_format::{FileFormat, FileFormatError};
+use crate::game::map::{MapPreset, MapSymmetry};
+use crate::game::map_files::MapChoice;
+use crate::game::neutral;
+use crate::game::path_queue::PathQueue;
+use crate::game::pathfinding;
+use crate::game::scenario::ScenarioState;
+use crate::game::scripting::Scripts;
+use crate::game::threat::ThreatMaps;
+use crate::game::victory::VictoryConditionType;
+use crate::game::{GamePhase, GameSettings, GameState};
+use crate::networking::framing;
+use crate::networking::TransportKind;
+
+/// Current save format version; bump when the layout of `SaveGame` or anything it contains (including
+/// `SavedGameState`) changes, and teach `decode_save` to migrate the previous layout
+pub const SAVE_VERSION: u32 = 6;
+
+/// Oldest save format version that can still be migrated and loaded
+pub const OLDEST_SAVE_VERSION: u32 = 1;
+
+/// Header of every save file
+const SAVE_FORMAT: FileFormat = FileFormat {
+    kind: "save",
+    magic: *b"RRTS",
+    version: SAVE_VERSION,
+    oldest_version: OLDEST_SAVE_VERSION,
+};
+
+/// Directory save slots are written to
+const SAVE_DIRECTORY: &str = "saves";
+
+/// Number of manual save slots offered in the menus
+pub const SAVE_SLOT_COUNT: u8 = 5;
+
+/// Slot written by autosaves and by the crash handler, after the player's own slots
+pub const AUTOSAVE_SLOT: u8 = SAVE_SLOT_COUNT;
+
+/// Slot recorded in the metadata of world snapshots, which are sent over the network instead of saved
+const SNAPSHOT_SLOT: u8 = u8::MAX;
+
+/// Save slot metadata, stored in front of the world data so it can be listed cheaply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    pub version: u32,
+    pub slot: u8,
+    pub timestamp: u64, // Seconds since UNIX epoch
+    pub map_seed: u64,
+    pub tick: u64,
+    pub player_count: u8,
+}
+
+/// Serialized map state (the pathfinding grid is rebuilt on load)
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedMap {
+    width: u32,
+    height: u32,
+    terrain_tiles: Vec<TerrainTile>,
+    resource_positions: Vec<(Vec2, ResourceType, f32)>,
+    starting_positions: Vec<Vec2>,
+    fog_of_war: HashMap<u8, HashSet<u32>>,
+}
+
+/// Serialized unit entity
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedUnit {
+    unit: Unit,
+    transform: Transform,
+    owner: Owner,
+    movement: Option<Movement>,
+    collider: Option<Collider>,
+    carried: Option<CarriedResources>,
+    experience: Option<Experience>,
+    network_id: Option<NetworkId>, // None in saves from before network IDs; a new one is handed out
+}
+
+/// Serialized unit entity as written by save versions 3 and 4, before network IDs
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedUnitV4 {
+    unit: Unit,
+    transform: Transform,
+    owner: Owner,
+    movement: Option<Movement>,
+    collider: Option<Collider>,
+    carried: Option<CarriedResources>,
+    experience: Option<Experience>,
+}
+
+/// Serialized unit entity as written by save version 1, before workers carried resources
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedUnitV1 {
+    unit: Unit,
+    transform: Transform,
+    owner: Owner,
+    movement: Option<Movement>,
+    collider: Option<Collider>,
+}
+
+/// Serialized unit entity as written by save version 2, before veterancy
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedUnitV2 {
+    unit: Unit,
+    transform: Transform,
+    owner: Owner,
+    movement: Option<Movement>,
+    collider: Option<Collider>,
+    carried: Option<CarriedResources>,
+}
+
+impl From<SavedUnitV1> for SavedUnitV2 {
+    fn from(saved: SavedUnitV1) -> Self {
+        Self {
+            unit: saved.unit,
+            transform: saved.transform,
+            owner: saved.owner,
+            movement: saved.movement,
+            collider: saved.collider,
+            carried: None,
+        }
+    }
+}
+
+impl From<SavedUnitV2> for SavedUnitV4 {
+    fn from(saved: SavedUnitV2) -> Self {
+        Self {
+            unit: saved.unit,
+            transform: saved.transform,
+            owner: saved.owner,
+            movement: saved.movement,
+            collider: saved.collider,
+            carried: saved.carried,
+            experience: None,
+        }
+    }
+}
+
+impl From<SavedUnitV4> for SavedUnit {
+    fn from(saved: SavedUnitV4) -> Self {
+        Self {
+            unit: saved.unit,
+            transform: saved.transform,
+            owner: saved.owner,
+            movement: saved.movement,
+            collider: saved.collider,
+            carried: saved.carried,
+            experience: saved.experience,
+            network_id: None,
+        }
+    }
+}
+
+/// Serialized building entity
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedBuilding {
+    building: Building,
+    transform: Transform,
+    owner: Owner,
+    collider: Option<Collider>,
+    upgrading: Option<Upgrading>,
+    network_id: Option<NetworkId>,
+}
+
+/// Serialized building entity as written by save version 4, before network IDs
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedBuildingV4 {
+    building: Building,
+    transform: Transform,
+    owner: Owner,
+    collider: Option<Collider>,
+    upgrading: Option<Upgrading>,
+}
+
+/// Serialized building entity as written by save version 3 and earlier, before building upgrades
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedBuildingV3 {
+    building: Building,
+    transform: Transform,
+    owner: Owner,
+    collider: Option<Collider>,
+}
+
+impl From<SavedBuildingV3> for SavedBuildingV4 {
+    fn from(saved: SavedBuildingV3) -> Self {
+        Self {
+            building: saved.building,
+            transform: saved.transform,
+            owner: saved.owner,
+            collider: saved.collider,
+            upgrading: None,
+        }
+    }
+}
+
+impl From<SavedBuildingV4> for SavedBuilding {
+    fn from(saved: SavedBuildingV4) -> Self {
+        Self {
+            building: saved.building,
+            transform: saved.transform,
+            owner: saved.owner,
+            collider: saved.collider,
+            upgrading: saved.upgrading,
+            network_id: None,
+        }
+    }
+}
+
+/// Serialized resource node entity
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedResourceNode {
+    resource: Resource,
+    transform: Transform,
+    network_id: Option<NetworkId>,
+}
+
+/// Serialized resource node entity as written by save version 4 and earlier, before network IDs
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedResourceNodeV4 {
+    resource: Resource,
+    transform: Transform,
+}
+
+impl From<SavedResourceNodeV4> for SavedResourceNode {
+    fn from(saved: SavedResourceNodeV4) -> Self {
+        Self {
+            resource: saved.resource,
+            transform: saved.transform,
+            network_id: None,
+        }
+    }
+}
+
+/// The match a save holds: the game state with only the settings that shape the game. The player's own
+/// settings, such as audio, theme and language, are not saved and stay as the running game has them
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedGameState {
+    phase: GamePhase,
+    current_tick: u64,
+    is_multiplayer: bool,
+    winner: Option<u8>,
+    winning_team: Option<u8>,
+    player_count: u8,
+    seed: u64,
+    game_speed: f32,
+    player_resources: HashMap<(u8, ResourceType), f32>,
+    player_supply: HashMap<u8, (u32, u32)>,
+    player_scores: HashMap<u8, u32>,
+    fog_of_war_enabled: bool,
+    ai_slots: Vec<AiSlotConfig>,
+    player_team: Option<u8>,
+    shared_vision: bool,
+    day_night_cycle: bool,
+    map: MapChoice,
+    map_preset: MapPreset,
+    map_symmetry: MapSymmetry,
+    victory_condition: VictoryConditionType,
+}
+
+impl SavedGameState {
+    fn capture(game_state: &GameState) -> Self {
+        let settings = &game_state.settings;
+        Self {
+            phase: game_state.phase,
+            current_tick: game_state.current_tick,
+            is_multiplayer: game_state.is_multiplayer,
+            winner: game_state.winner,
+            winning_team: game_state.winning_team,
+            player_count: game_state.player_count,
+            seed: game_state.seed,
+            game_speed: game_state.game_speed,
+            player_resources: game_state.player_resources.clone(),
+            player_supply: game_state.player_supply.clone(),
+            player_scores: game_state.player_scores.clone(),
+            fog_of_war_enabled: settings.fog_of_war_enabled,
+            ai_slots: settings.ai_slots.clone(),
+            player_team: settings.player_team,
+            shared_vision: settings.shared_vision,
+            day_night_cycle: settings.day_night_cycle,
+            map: settings.map.clone(),
+            map_preset: settings.map_preset,
+            map_symmetry: settings.map_symmetry,
+            victory_condition: settings.victory_condition,
+        }
+    }
+
+    /// Put the saved match in place of the running one, leaving the player's own settings alone
+    fn restore(self, game_state: &mut GameState) {
+        game_state.phase = self.phase;
+        game_state.current_tick = self.current_tick;
+        game_state.is_multiplayer = self.is_multiplayer;
+        game_state.winner = self.winner;
+        game_state.winning_team = self.winning_team;
+        game_state.player_count = self.player_count;
+        game_state.seed = self.seed;
+        game_state.game_speed = self.game_speed;
+        game_state.player_resources = self.player_resources;
+        game_state.player_supply = self.player_supply;
+        game_state.player_scores = self.player_scores;
+
+        let settings = &mut game_state.settings;
+        settings.fog_of_war_enabled = self.fog_of_war_enabled;
+        settings.ai_slots = self.ai_slots;
+        settings.player_team = self.player_team;
+        settings.shared_vision = self.shared_vision;
+        settings.day_night_cycle = self.day_night_cycle;
+        settings.map = self.map;
+        settings.map_preset = self.map_preset;
+        settings.map_symmetry = self.map_symmetry;
+        settings.victory_condition = self.victory_condition;
+    }
+}
+
+/// The whole `GameState` as written by save version 5 and earlier, with the settings laid out as `S`
+#[derive(Serialize, Deserialize)]
+struct GameStateV5<S = GameSettingsV5> {
+    phase: GamePhase,
+    current_tick: u64,
+    is_multiplayer: bool,
+    winner: Option<u8>,
+    winning_team: Option<u8>,
+    player_count: u8,
+    seed: u64,
+    game_speed: f32,
+    player_resources: HashMap<(u8, ResourceType), f32>,
+    player_supply: HashMap<u8, (u32, u32)>,
+    player_scores: HashMap<u8, u32>,
+    settings: S,
+}
+
+/// Game settings as the last builds writing save versions 4 and 5 laid them out
+#[derive(Serialize, Deserialize)]
+struct GameSettingsV5 {
+    fog_of_war_enabled: bool,
+    game_speed: f32,
+    auto_save_enabled: bool,
+    auto_save_interval: f32,
+    show_fps: bool,
+    health_bars: HealthBarMode,
+    combat_text: bool,
+    ai_slots: Vec<AiSlotConfig>,
+    player_team: Option<u8>,
+    shared_vision: bool,
+    day_night_cycle: bool,
+    map: MapChoice,
+    map_preset: MapPreset,
+    map_symmetry: MapSymmetry,
+    victory_condition: VictoryConditionType,
+    audio: AudioSettings,
+    camera: CameraSettings,
+    video: VideoSettings,
+    player_name: String,
+    ui_theme: String,
+    language: String,
+    network_transport: TransportKind,
+    upnp: bool,
+}
+
+/// Game settings as the last builds writing save version 3 laid them out, before combat text, day and
+/// night, map presets, themes, languages and network transports
+#[derive(Serialize, Deserialize)]
+struct GameSettingsV3 {
+    fog_of_war_enabled: bool,
+    game_speed: f32,
+    auto_save_enabled: bool,
+    auto_save_interval: f32,
+    show_fps: bool,
+    health_bars: HealthBarMode,
+    ai_slots: Vec<AiSlotConfigV3>,
+    player_team: Option<u8>,
+    shared_vision: bool,
+    map: MapChoice,
+    victory_condition: VictoryConditionType,
+    audio: AudioSettings,
+    camera: CameraSettings,
+    video: VideoSettings,
+    player_name: String,
+}
+
+/// Game settings as the last builds writing save versions 1 and 2 laid them out, before health bar modes
+#[derive(Serialize, Deserialize)]
+struct GameSettingsV2 {
+    fog_of_war_enabled: bool,
+    game_speed: f32,
+    auto_save_enabled: bool,
+    auto_save_interval: f32,
+    show_fps: bool,
+    ai_slots: Vec<AiSlotConfigV3>,
+    player_team: Option<u8>,
+    shared_vision: bool,
+    map: MapChoice,
+    victory_condition: VictoryConditionType,
+    audio: AudioSettings,
+    camera: CameraSettings,
+    video: VideoSettings,
+    player_name: String,
+}
+
+/// AI slot as written by save version 3 and earlier, before resource bonuses
+#[derive(Serialize, Deserialize)]
+struct AiSlotConfigV3 {
+    player_id: u8,
+    difficulty: AiDifficulty,
+    personality: AiPersonality,
+    team: Option<u8>,
+}
+
+impl From<AiSlotConfigV3> for AiSlotConfig {
+    fn from(saved: AiSlotConfigV3) -> Self {
+        Self {
+            player_id: saved.player_id,
+            difficulty: saved.difficulty,
+            personality: saved.personality,
+            team: saved.team,
+            resource_bonus: 0.0,
+        }
+    }
+}
+
+impl From<GameSettingsV2> for GameSettingsV3 {
+    fn from(saved: GameSettingsV2) -> Self {
+        Self {
+            fog_of_war_enabled: saved.fog_of_war_enabled,
+            game_speed: saved.game_speed,
+            auto_save_enabled: saved.auto_save_enabled,
+            auto_save_interval: saved.auto_save_interval,
+            show_fps: saved.show_fps,
+            health_bars: HealthBarMode::default(),
+            ai_slots: saved.ai_slots,
+            player_team: saved.player_team,
+            shared_vision: saved.shared_vision,
+            map: saved.map,
+            victory_condition: saved.victory_condition,
+            audio: saved.audio,
+            camera: saved.camera,
+            video: saved.video,
+            player_name: saved.player_name,
+        }
+    }
+}
+
+impl From<GameSettingsV3> for GameSettingsV5 {
+    fn from(saved: GameSettingsV3) -> Self {
+        let defaults = GameSettings::default();
+        Self {
+            fog_of_war_enabled: saved.fog_of_war_enabled,
+            game_speed: saved.game_speed,
+            auto_save_enabled: saved.auto_save_enabled,
+            auto_save_interval: saved.auto_save_interval,
+            show_fps: saved.show_fps,
+            health_bars: saved.health_bars,
+            combat_text: defaults.combat_text,
+            ai_slots: saved.ai_slots.into_iter().map(AiSlotConfig::from).collect(),
+            player_team: saved.player_team,
+            shared_vision: saved.shared_vision,
+            day_night_cycle: defaults.day_night_cycle,
+            map: saved.map,
+            map_preset: defaults.map_preset,
+            map_symmetry: defaults.map_symmetry,
+            victory_condition: saved.victory_condition,
+            audio: saved.audio,
+            camera: saved.camera,
+            video: saved.video,
+            player_name: saved.player_name,
+            ui_theme: defaults.ui_theme,
+            language: defaults.language,
+            network_transport: defaults.network_transport,
+            upnp: defaults.upnp,
+        }
+    }
+}
+
+impl From<GameSettingsV2> for GameSettingsV5 {
+    fn from(saved: GameSettingsV2) -> Self {
+        GameSettingsV3::from(saved).into()
+    }
+}
+
+impl<S: Into<GameSettingsV5>> From<GameStateV5<S>> for SavedGameState {
+    fn from(saved: GameStateV5<S>) -> Self {
+        let settings: GameSettingsV5 = saved.settings.into();
+        Self {
+            phase: saved.phase,
+            current_tick: saved.current_tick,
+            is_multiplayer: saved.is_multiplayer,
+            winner: saved.winner,
+            winning_team: saved.winning_team,
+            player_count: saved.player_count,
+            seed: saved.seed,
+            game_speed: saved.game_speed,
+            player_resources: saved.player_resources,
+            player_supply: saved.player_supply,
+            player_scores: saved.player_scores,
+            fog_of_war_enabled: settings.fog_of_war_enabled,
+            ai_slots: settings.ai_slots,
+            player_team: settings.player_team,
+            shared_vision: settings.shared_vision,
+            day_night_cycle: settings.day_night_cycle,
+            map: settings.map,
+            map_preset: settings.map_preset,
+            map_symmetry: settings.map_symmetry,
+            victory_condition: settings.victory_condition,
+        }
+    }
+}
+
+/// Complete snapshot of a game in progress; older save versions differ in how entities and the game state
+/// are stored
+#[derive(Serialize, Deserialize)]
+struct SaveGame<U = SavedUnit, B = SavedBuilding, R = SavedResourceNode, G = SavedGameState> {
+    metadata: SaveMetadata,
+    game_state: G,
+    map: Option<SavedMap>,
+    tech_state: Option<TechState>,
+    player_resources: HashMap<(u8, ResourceType), f32>,
+    income_rate: HashMap<(u8, ResourceType), f32>,
+    units: Vec<U>,
+    buildings: Vec<B>,
+    resource_nodes: Vec<R>,
+}
+
+impl<U, B, R, G> SaveGame<U, B, R, G> {
+    /// Convert a save to the next format version's unit, building and resource node layouts
+    fn migrate<V: From<U>, C: From<B>, N: From<R>>(self, version: u32) -> SaveGame<V, C, N, G> {
+        SaveGame {
+            metadata: SaveMetadata { version, ..self.metadata },
+            game_state: self.game_state,
+            map: self.map,
+            tech_state: self.tech_state,
+            player_resources: self.player_resources,
+            income_rate: self.income_rate,
+            units: self.units.into_iter().map(V::from).collect(),
+            buildings: self.buildings.into_iter().map(C::from).collect(),
+            resource_nodes: self.resource_nodes.into_iter().map(N::from).collect(),
+        }
+    }
+
+    /// Convert a save to the next format version's game state layout
+    fn migrate_game_state<H: From<G>>(self, version: u32) -> SaveGame<U, B, R, H> {
+        SaveGame {
+            metadata: SaveMetadata { version, ..self.metadata },
+            game_state: H::from(self.game_state),
+            map: self.map,
+            tech_state: self.tech_state,
+            player_resources: self.player_resources,
+            income_rate: self.income_rate,
+            units: self.units,
+            buildings: self.buildings,
+            resource_nodes: self.resource_nodes,
+        }
+    }
+}
+
+/// Path of the file backing a save slot
+pub fn save_slot_path(slot: u8) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("slot_{}.sav", slot))
+}
+
+/// Serialize the world and game state into the given slot
+pub fn save_game(world: &mut World, game_state: &GameState, slot: u8) -> Result<SaveMetadata> {
+    let save = capture(world, game_state, slot);
+    write_save_file(slot, &SAVE_FORMAT.encode(&save)?)?;
+    Ok(save.metadata)
+}
+
+/// Serialize the world and game state as the contents of a save file, to write out later
+pub fn encode_save(world: &mut World, game_state: &GameState, slot: u8) -> Result<Vec<u8>> {
+    Ok(SAVE_FORMAT.encode(&capture(world, game_state, slot))?)
+}
+
+/// Write the contents of a save file to a slot
+pub fn write_save_file(slot: u8, data: &[u8]) -> Result<()> {
+    fs::create_dir_all(SAVE_DIRECTORY)?;
+    let mut file = File::create(save_slot_path(slot))?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+/// Restore a saved game into the running world, replacing all game entities
+pub fn load_game(world: &mut World, game_state: &mut GameState, slot: u8) -> Result<SaveMetadata> {
+    let data = read_save_file(slot)?;
+    let (version, body) = SAVE_FORMAT.read_header(&slot_name(slot), &data)?;
+    let save = decode_save(slot, version, body)?;
+
+    Ok(restore(world, game_state, save))
+}
+
+/// Serialize the world and game state in the save format and compress them, for the host of a network
+/// game to send to players whose world went out of step
+pub fn capture_snapshot(world: &mut World, game_state: &GameState) -> Result<Vec<u8>> {
+    let save = capture(world, game_state, SNAPSHOT_SLOT);
+    Ok(framing::compress(&SAVE_FORMAT.encode(&save)?))
+}
+
+/// Replace the running game with a snapshot from `capture_snapshot`. Entities keep the network IDs they had
+/// on the host, so commands already sent still find them
+pub fn restore_snapshot(world: &mut World, game_state: &mut GameState, snapshot: &[u8]) -> Result<SaveMetadata> {
+    let name = "World snapshot";
+    let data = framing::decompress(snapshot)?;
+    let (version, body) = SAVE_FORMAT.read_header(name, &data)?;
+    if version != SAVE_VERSION {
+        return Err(anyhow::anyhow!("{} is from save version {}, not {}", name, version, SAVE_VERSION));
+    }
+    let save: SaveGame = SAVE_FORMAT.decode(name, version, body)?;
+
+    // Projectiles and effects in flight go too; no machine keeps them
+    world.clear_entities();
+    if let Some(mut selection) = world.get_resource_mut::<SelectionState>() {
+        *selection = SelectionState::default();
+    }
+
+    Ok(restore(world, game_state, save))
+}
+
+/// Everything a save holds about the running game
+fn capture(world: &mut World, game_state: &GameState, slot: u8) -> SaveGame {
+    let metadata = SaveMetadata {
+        version: SAVE_VERSION,
+        slot,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        map_seed: game_state.seed,
+        tick: game_state.current_tick,
+        player_count: game_state.player_count,
+    };
+
+    SaveGame {
+        metadata,
+        game_state: SavedGameState::capture(game_state),
+        map: world.get_resource::<GameMap>().map(|map| SavedMap {
+            width: map.width,
+            height: map.height,
+            terrain_tiles: map.terrain_tiles.clone(),
+            resource_positions: map.resource_positions.clone(),
+            starting_positions: map.starting_positions.clone(),
+            fog_of_war: map.fog_of_war.clone(),
+        }),
+        tech_state: world.get_resource::<TechState>().cloned(),
+        player_resources: world.get_resource::<PlayerResources>()
+            .map(|r| r.resources.clone())
+            .unwrap_or_default(),
+        income_rate: world.get_resource::<PlayerResources>()
+            .map(|r| r.income_rate.clone())
+            .unwrap_or_default(),
+        units: collect_units(world),
+        buildings: collect_buildings(world),
+        resource_nodes: collect_resource_nodes(world),
+    }
+}
+
+/// Put a save's world and game state in place of the running game's
+fn restore(world: &mut World, game_state: &mut GameState, save: SaveGame) -> SaveMetadata {
+    clear_game_entities(world);
+
+    // Control groups refer to entities that no longer exist
+    if let Some(mut control_groups) = world.get_resource_mut::<ControlGroups>() {
+        control_groups.groups.clear();
+    }
+
+    // Restore map and rebuild derived data
+    if let Some(saved_map) = save.map {
+        let mut map = GameMap {
+            width: saved_map.width,
+            height: saved_map.height,
+            terrain_tiles: saved_map.terrain_tiles,
+            resource_positions: saved_map.resource_positions,
+            starting_positions: saved_map.starting_positions,
+            pathfinding_grid: None,
+            fog_of_war: saved_map.fog_of_war,
+            occupied_tiles: Default::default(),
+            gate_tiles: Default::default(),
+            gate_grids: Default::default(),
+            neutral_sites: Vec::new(), // Already spawned; the creeps and structures are saved as entities
+        };
+
+        let footprints: Vec<(BuildingType, Vec2)> = save.buildings
+            .iter()
+            .map(|b| (b.building.building_type, b.transform.position))
+            .collect();
+        buildings::occupy_footprints(&mut map, &footprints);
+        let gates: Vec<(u8, Vec2)> = save.buildings
+            .iter()
+            .filter(|b| b.building.building_type == BuildingType::Gate)
+            .map(|b| (b.owner.0, b.transform.position))
+            .collect();
+        buildings::open_gates(&mut map, &gates, world.get_resource::<PlayerInfo>());
+        pathfinding::rebuild_pathfinding_grid(&mut map);
+
+        world.insert_resource(map);
+    }
+
+    if let Some(tech_state) = save.tech_state {
+        world.insert_resource(tech_state);
+    }
+
+    world.insert_resource(PlayerResources {
+        resources: save.player_resources,
+        income_rate: save.income_rate,
+        deposited: HashMap::new(),
+    });
+
+    // Respawn entities
+    for saved in save.units {
+        // Creeps guard wherever they stood when the game was saved
+        let creep = (saved.owner.0 == NEUTRAL_PLAYER).then(|| Creep { post: saved.transform.position });
+        let mut entity = world.spawn((saved.unit, saved.transform, saved.owner, Selectable));
+        if let Some(network_id) = saved.network_id {
+            entity.insert(network_id);
+        }
+        if let Some(movement) = saved.movement {
+            entity.insert(movement);
+        }
+        if let Some(collider) = saved.collider {
+            entity.insert(collider);
+        }
+        if let Some(carried) = saved.carried {
+            entity.insert(carried);
+        }
+        if let Some(experience) = saved.experience {
+            entity.insert(experience);
+        }
+        if let Some(creep) = creep {
+            entity.insert(creep);
+        }
+    }
+
+    for saved in save.buildings {
+        let is_gate = saved.building.building_type == BuildingType::Gate;
+        let capturable = neutral::is_capturable(saved.building.building_type);
+        let mut entity = world.spawn((saved.building, saved.transform, saved.owner, Selectable));
+        if let Some(network_id) = saved.network_id {
+            entity.insert(network_id);
+        }
+        if is_gate {
+            entity.insert(Gate);
+        }
+        if capturable {
+            // Capture progress starts over, like the victory condition's
+            entity.insert(Capturable::default());
+        }
+        if let Some(collider) = saved.collider {
+            entity.insert(collider);
+        }
+        if let Some(upgrading) = saved.upgrading {
+            entity.insert(upgrading);
+        }
+    }
+
+    for saved in save.resource_nodes {
+        let mut entity = world.spawn((saved.resource, saved.transform));
+        if let Some(network_id) = saved.network_id {
+            entity.insert(network_id);
+        }
+    }
+
+    // Entities saved without an ID get theirs after those that kept one. IDs of entities that had died
+    // before the save may be handed out again; nothing saved refers to them
+    let mut query = world.query::<(Entity, &NetworkId)>();
+    let restored: Vec<(Entity, NetworkId)> = query.iter(world).map(|(entity, network_id)| (entity, *network_id)).collect();
+    let mut network_ids = NetworkIds::default();
+    for (entity, network_id) in restored {
+        network_ids.insert(entity, network_id);
+    }
+    world.insert_resource(network_ids);
+    assign_network_ids(world);
+
+    save.game_state.restore(game_state);
+
+    save.metadata
+}
+
+/// Read only the metadata of a save slot
+pub fn read_save_metadata(slot: u8) -> Result<SaveMetadata> {
+    let data = read_save_file(slot)?;
+    let (version, body) = SAVE_FORMAT.read_header(&slot_name(slot), &data)?;
+
+    // Metadata is the first field of SaveGame in every version, so bincode can decode it on its own
+    let metadata: SaveMetadata = SAVE_FORMAT.decode(&slot_name(slot), version, body)?;
+    Ok(metadata)
+}
+
+/// List metadata for all occupied save slots, the autosave last
+pub fn list_save_slots() -> Vec<SaveMetadata> {
+    (0..=AUTOSAVE_SLOT)
+        .filter_map(|slot| read_save_metadata(slot).ok())
+        .collect()
+}
+
+/// Delete a save slot
+pub fn delete_save(slot: u8) -> Result<()> {
+    let path = save_slot_path(slot);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Deserialize a save body of any supported version, migrating it to the current layout
+fn decode_save(slot: u8, version: u32, body: &[u8]) -> Result<SaveGame, FileFormatError> {
+    let name = slot_name(slot);
+    let save = match version {
+        1 => SAVE_FORMAT
+            .decode::<SaveGame<SavedUnitV1, SavedBuildingV3, SavedResourceNodeV4, GameStateV5<GameSettingsV2>>>(&name, version, body)?
+            .migrate::<SavedUnitV2, SavedBuildingV3, SavedResourceNodeV4>(2)
+            .migrate::<SavedUnitV4, SavedBuildingV3, SavedResourceNodeV4>(3)
+            .migrate::<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4>(4)
+            .migrate::<SavedUnit, SavedBuilding, SavedResourceNode>(5)
+            .migrate_game_state::<SavedGameState>(6),
+        2 => SAVE_FORMAT
+            .decode::<SaveGame<SavedUnitV2, SavedBuildingV3, SavedResourceNodeV4, GameStateV5<GameSettingsV2>>>(&name, version, body)?
+            .migrate::<SavedUnitV4, SavedBuildingV3, SavedResourceNodeV4>(3)
+            .migrate::<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4>(4)
+            .migrate::<SavedUnit, SavedBuilding, SavedResourceNode>(5)
+            .migrate_game_state::<SavedGameState>(6),
+        3 => SAVE_FORMAT
+            .decode::<SaveGame<SavedUnitV4, SavedBuildingV3, SavedResourceNodeV4, GameStateV5<GameSettingsV3>>>(&name, version, body)?
+            .migrate::<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4>(4)
+            .migrate::<SavedUnit, SavedBuilding, SavedResourceNode>(5)
+            .migrate_game_state::<SavedGameState>(6),
+        4 => SAVE_FORMAT
+            .decode::<SaveGame<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4, GameStateV5>>(&name, version, body)?
+            .migrate::<SavedUnit, SavedBuilding, SavedResourceNode>(5)
+            .migrate_game_state::<SavedGameState>(6),
+        5 => SAVE_FORMAT
+            .decode::<SaveGame<SavedUnit, SavedBuilding, SavedResourceNode, GameStateV5>>(&name, version, body)?
+            .migrate_game_state::<SavedGameState>(6),
+        _ => SAVE_FORMAT.decode::<SaveGame>(&name, version, body)?,
+    };
+
+    if version != SAVE_VERSION {
+        info!("Migrated save slot {} from version {} to {}", slot, version, SAVE_VERSION);
+    }
+    Ok(save)
+}
+
+/// Name of a save slot in error messages
+fn slot_name(slot: u8) -> String {
+    format!("Save slot {}", slot)
+}
+
+/// Read the raw contents of a save file
+fn read_save_file(slot: u8) -> Result<Vec<u8>> {
+    let path = save_slot_path(slot);
+    if !path.exists() {
+        return Err(anyhow::anyhow!("Save slot {} is empty", slot));
+    }
+
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Snapshot all unit entities; units aboard a transport or inside a building are saved standing where it is
+fn collect_units(world: &mut World) -> Vec<SavedUnit> {
+    let mut query = world.query::<(
+        &Unit,
+        Option<&Transform>,
+        Option<&Passenger>,
+        Option<&Garrisoned>,
+        &Owner,
+        Option<&Movement>,
+        Option<&Collider>,
+        Option<&CarriedResources>,
+        Option<&Experience>,
+        Option<&NetworkId>,
+    )>();
+    query.iter(world)
+        .filter_map(|(unit, transform, passenger, garrisoned, owner, movement, collider, carried, experience, network_id)| {
+            let transform = transform
+                .or_else(|| world.get::<Transform>(passenger?.transport))
+                .or_else(|| world.get::<Transform>(garrisoned?.building))?;
+            Some(SavedUnit {
+                unit: unit.clone(),
+                transform: transform.clone(),
+                owner: *owner,
+                movement: movement.cloned(),
+                collider: collider.cloned(),
+                carried: carried.copied(),
+                experience: experience.copied(),
+                network_id: network_id.copied(),
+            })
+        })
+        .collect()
+}
+
+/// Snapshot all building entities
+fn collect_buildings(world: &mut World) -> Vec<SavedBuilding> {
+    let mut query = world.query::<(&Building, &Transform, &Owner, Option<&Collider>, Option<&Upgrading>, Option<&NetworkId>)>();
+    query.iter(world)
+        .map(|(building, transform, owner, collider, upgrading, network_id)| SavedBuilding {
+            building: building.clone(),
+            transform: transform.clone(),
+            owner: *owner,
+            collider: collider.cloned(),
+            upgrading: upgrading.copied(),
+            network_id: network_id.copied(),
+        })
+        .collect()
+}
+
+/// Snapshot all resource node entities
+fn collect_resource_nodes(world: &mut World) -> Vec<SavedResourceNode> {
+    let mut query = world.query::<(&Resource, &Transform, Option<&NetworkId>)>();
+    query.iter(world)
+        .map(|(resource, transform, network_id)| SavedResourceNode {
+            resource: resource.clone(),
+            transform: transform.clone(),
+            network_id: network_id.copied(),
+        })
+        .collect()
+}
+
+/// Despawn every unit, building and resource node before restoring a save or starting a game.
+/// Scenario triggers, reveals and the campaign mission go with them; saves don't keep them
+/// and new games start without, as does the scenario's script. Computer players forget the enemies
+/// they had seen, and network IDs start over
+pub fn clear_game_entities(world: &mut World) {
+    world.remove_resource::<ScenarioState>();
+    world.remove_resource::<CampaignMission>();
+    world.remove_resource::<RevealedAreas>();
+    world.insert_resource(ThreatMaps::default());
+    world.insert_resource(NetworkIds::default());
+    world.insert_resource(PathQueue::default());
+    if let Some(mut scripts) = world.get_resource_mut::<Scripts>() {
+        scripts.scenario = None;
+    }
+
+    let mut to_despawn = Vec::new();
+
+    let mut units = world.query_filtered::<Entity, With<Unit>>();
+    to_despawn.extend(units.iter(world));
+
+    let mut buildings = world.query_filtered::<Entity, With<Building>>();
+    to_despawn.extend(buildings.iter(world));
+
+    let mut resources = world.query_filtered::<Entity, With<Resource>>();
+    to_despawn.extend(resources.iter(world));
+
+    let mut corpses = world.query_filtered::<Entity, With<Corpse>>();
+    to_despawn.extend(corpses.iter(world));
+
+    for entity in to_despawn {
+        world.despawn(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save as an older build wrote it, behind that build's header
+    fn encode_as_version<T: Serialize>(version: u32, save: &T) -> Vec<u8> {
+        FileFormat { version, ..SAVE_FORMAT }.encode(save).unwrap()
+    }
+
+    fn load(data: &[u8]) -> SaveGame {
+        let (version, body) = SAVE_FORMAT.read_header("test save", data).unwrap();
+        decode_save(0, version, body).unwrap()
+    }
+
+    /// A save without entities, holding the whole game state as versions 5 and earlier did
+    fn legacy_save<U, B, R, S>(version: u32, settings: S) -> SaveGame<U, B, R, GameStateV5<S>> {
+        SaveGame {
+            metadata: SaveMetadata { version, slot: 0, timestamp: 0, map_seed: 77, tick: 300, player_count: 2 },
+            game_state: GameStateV5 {
+                phase: GamePhase::Playing,
+                current_tick: 300,
+                is_multiplayer: false,
+                winner: None,
+                winning_team: None,
+                player_count: 2,
+                seed: 77,
+                game_speed: 1.5,
+                player_resources: HashMap::from([((0, ResourceType::Mineral), 250.0)]),
+                player_supply: HashMap::new(),
+                player_scores: HashMap::new(),
+                settings,
+            },
+            map: None,
+            tech_state: None,
+            player_resources: HashMap::new(),
+            income_rate: HashMap::new(),
+            units: Vec::new(),
+            buildings: Vec::new(),
+            resource_nodes: Vec::new(),
+        }
+    }
+
+    fn version_4_settings() -> GameSettingsV5 {
+        GameSettingsV5 {
+            fog_of_war_enabled: true,
+            game_speed: 1.0,
+            auto_save_enabled: false,
+            auto_save_interval: 300.0,
+            show_fps: false,
+            health_bars: HealthBarMode::default(),
+            combat_text: false,
+            ai_slots: vec![AiSlotConfig {
+                player_id: 1,
+                difficulty: AiDifficulty::Hard,
+                personality: AiPersonality::Balanced,
+                team: None,
+                resource_bonus: 0.25,
+            }],
+            player_team: None,
+            shared_vision: false,
+            day_night_cycle: true,
+            map: MapChoice::Procedural { name: "Large".to_string(), size: 192 },
+            map_preset: MapPreset::Islands,
+            map_symmetry: MapSymmetry::Rotational,
+            victory_condition: VictoryConditionType::ResourceControl { points: 3, minutes: 5.0 },
+            audio: AudioSettings::default(),
+            camera: CameraSettings::default(),
+            video: VideoSettings::default(),
+            player_name: "Saved Name".to_string(),
+            ui_theme: "saved theme".to_string(),
+            language: "saved language".to_string(),
+            network_transport: TransportKind::default(),
+            upnp: true,
+        }
+    }
+
+    #[test]
+    fn loads_version_1_save() {
+        let settings = GameSettingsV2 {
+            fog_of_war_enabled: false,
+            game_speed: 1.0,
+            auto_save_enabled: false,
+            auto_save_interval: 300.0,
+            show_fps: true,
+            ai_slots: vec![AiSlotConfigV3 {
+                player_id: 1,
+                difficulty: AiDifficulty::Hard,
+                personality: AiPersonality::Balanced,
+                team: Some(1),
+            }],
+            player_team: Some(0),
+            shared_vision: true,
+            map: MapChoice::default(),
+            victory_condition: VictoryConditionType::TimeLimit { minutes: 20.0 },
+            audio: AudioSettings::default(),
+            camera: CameraSettings::default(),
+            video: VideoSettings::default(),
+            player_name: "Saved Name".to_string(),
+        };
+        let save: SaveGame<SavedUnitV1, SavedBuildingV3, SavedResourceNodeV4, _> = legacy_save(1, settings);
+        let loaded = load(&encode_as_version(1, &save));
+
+        assert_eq!(loaded.metadata.version, SAVE_VERSION);
+        let game_state = loaded.game_state;
+        assert_eq!(game_state.phase, GamePhase::Playing);
+        assert_eq!(game_state.seed, 77);
+        assert_eq!(game_state.current_tick, 300);
+        assert_eq!(game_state.player_resources.get(&(0, ResourceType::Mineral)), Some(&250.0));
+        assert!(!game_state.fog_of_war_enabled);
+        assert!(game_state.shared_vision);
+        assert_eq!(game_state.player_team, Some(0));
+        assert_eq!(game_state.ai_slots.len(), 1);
+        assert_eq!(game_state.ai_slots[0].difficulty, AiDifficulty::Hard);
+        assert_eq!(game_state.ai_slots[0].team, Some(1));
+        assert_eq!(game_state.ai_slots[0].resource_bonus, 0.0);
+        assert_eq!(game_state.victory_condition, VictoryConditionType::TimeLimit { minutes: 20.0 });
+
+        // Settings added after version 1 start at their defaults
+        assert!(!game_state.day_night_cycle);
+        assert_eq!(game_state.map_preset, MapPreset::default());
+        assert_eq!(game_state.map_symmetry, MapSymmetry::default());
+    }
+
+    #[test]
+    fn loads_version_4_save() {
+        let save: SaveGame<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4, _> = legacy_save(4, version_4_settings());
+        let loaded = load(&encode_as_version(4, &save));
+
+        assert_eq!(loaded.metadata.version, SAVE_VERSION);
+        let game_state = loaded.game_state;
+        assert_eq!(game_state.seed, 77);
+        assert_eq!(game_state.game_speed, 1.5);
+        assert!(game_state.day_night_cycle);
+        assert_eq!(game_state.map, MapChoice::Procedural { name: "Large".to_string(), size: 192 });
+        assert_eq!(game_state.map_preset, MapPreset::Islands);
+        assert_eq!(game_state.map_symmetry, MapSymmetry::Rotational);
+        assert_eq!(game_state.ai_slots[0].resource_bonus, 0.25);
+        assert_eq!(game_state.victory_condition, VictoryConditionType::ResourceControl { points: 3, minutes: 5.0 });
+    }
+
+    #[test]
+    fn loading_keeps_the_players_own_settings() {
+        let save: SaveGame<SavedUnitV4, SavedBuildingV4, SavedResourceNodeV4, _> = legacy_save(4, version_4_settings());
+        let loaded = load(&encode_as_version(4, &save));
+
+        let mut game_state = GameState::new();
+        game_state.settings.player_name = "Current Name".to_string();
+        game_state.settings.language = "de".to_string();
+        game_state.settings.combat_text = true;
+        game_state.settings.upnp = false;
+        loaded.game_state.restore(&mut game_state);
+
+        assert_eq!(game_state.seed, 77);
+        assert!(game_state.settings.day_night_cycle);
+        assert_eq!(game_state.settings.map_preset, MapPreset::Islands);
+        assert_eq!(game_state.settings.player_name, "Current Name");
+        assert_eq!(game_state.settings.language, "de");
+        assert!(game_state.settings.combat_text);
+        assert!(!game_state.settings.upnp);
+    }
+
+    #[test]
+    fn current_save_round_trips() {
+        let mut game_state = GameState::new();
+        game_state.seed = 99;
+        game_state.settings.map_symmetry = MapSymmetry::Mirrored;
+        let save = SaveGame {
+            metadata: SaveMetadata { version: SAVE_VERSION, slot: 0, timestamp: 0, map_seed: 99, tick: 0, player_count: 1 },
+            game_state: SavedGameState::capture(&game_state),
+            map: None,
+            tech_state: None,
+            player_resources: HashMap::new(),
+            income_rate: HashMap::new(),
+            units: Vec::new(),
+            buildings: Vec::new(),
+            resource_nodes: Vec::new(),
+        };
+        let loaded = load(&SAVE_FORMAT.encode(&save).unwrap());
+
+        assert_eq!(loaded.game_state.seed, 99);
+        assert_eq!(loaded.game_state.map_symmetry, MapSymmetry::Mirrored);
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::CommandQueue;
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{error, info};
+
+use crate::ecs::components::{
+    Building, BuildingType, Collider, Gate, Owner, ResourceType, Selectable, Transform, Unit, UnitType,
+};
+use crate::ecs::resources::{
+    GameMap, GameTime, PlayerAlerts, PlayerInfo, PlayerResources, RevealedAreas, SelectionState, TechState,
+    NEUTRAL_PLAYER, TICK_RATE,
+};
+use crate::game::ai::AiSlotConfig;
+use crate::game::{buildings, data, pathfinding};
+use crate::game::map::{MapPreset, MapSymmetry};
+use crate::game::map_files::MapChoice;
+use crate::game::scripting::{self, ScriptRole, ScriptRuntime, ScriptSlot, Scripts};
+use crate::game::stats::GameStats;
+use crate::game::units::{self, UnitSpawnParams};
+use crate::game::victory::{VictoryOutcome, VictoryState};
+
+/// Directory the scenario files are read from
+pub const SCENARIO_DIR: &str = "assets/scenarios";
+
+/// Scenario the main menu's tutorial button starts
+pub const TUTORIAL_SCENARIO: &str = "tutorial.ron";
+
+/// Distance from the spawn point units spawned together stand at
+const SPAWN_SPREAD: f32 = 4.0;
+
+/// Something that has to hold for a trigger to fire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TriggerCondition {
+    /// Seconds since the trigger was armed: the start of the game, or when its `after` trigger fired
+    TimeElapsed(f32),
+    /// The player has at least this many living units, of one type or of any
+    UnitCount { player: u8, unit_type: Option<UnitType>, at_least: u32 },
+    /// The player has at least this many finished buildings, of one type or of any
+    BuildingCount { player: u8, building_type: Option<BuildingType>, at_least: u32 },
+    /// One of the player's units is inside the circle
+    RegionEntered { player: u8, center: Vec2, radius: f32 },
+    /// The player's workers have brought in at least this much over the game
+    ResourcesGathered { player: u8, at_least: f32 },
+    /// The local player has at least this many units selected
+    UnitsSelected(u32),
+    /// The inner condition does not hold, as for a headquarters that no longer stands
+    Not(Box<TriggerCondition>),
+    /// The named function of the scenario's script returns true
+    Script(String),
+}
+
+/// What a trigger does when it fires
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TriggerAction {
+    SpawnUnits { unit_type: UnitType, owner: u8, position: Vec2, count: u32 },
+    ShowMessage { player: u8, text: String },
+    /// Lift the fog over the circle for the player for the rest of the game
+    RevealArea { player: u8, center: Vec2, radius: f32 },
+    Win { player: u8 },
+    Lose { player: u8 },
+    /// Call the named function of the scenario's script
+    RunScript(String),
+}
+
+/// Conditions and the actions taken the first time they all hold. Triggers with an `after`
+/// wait for that trigger to fire first, so steps of a tutorial happen in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerData {
+    pub name: String,
+    #[serde(default)]
+    pub after: Option<String>,             // Name of the trigger that arms this one
+    pub conditions: Vec<TriggerCondition>, // All must hold; with none the trigger fires as soon as it is armed
+    pub actions: Vec<TriggerAction>,
+}
+
+/// A unit standing on the map when the scenario starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedUnit {
+    pub unit_type: UnitType,
+    pub owner: u8,
+    pub position: Vec2,
+}
+
+/// A finished building standing on the map when the scenario starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedBuilding {
+    pub building_type: BuildingType,
+    pub owner: u8,
+    pub position: Vec2,
+}
+
+/// What a mission starts with, placed instead of a headquarters and workers at every start
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenarioWorld {
+    pub units: Vec<PlacedUnit>,
+    pub buildings: Vec<PlacedBuilding>,
+    pub resources: Vec<(u8, ResourceType, f32)>, // (Player ID, Resource Type, Amount); unlisted players keep the usual amounts
+}
+
+/// A scenario file: the game it sets up and the triggers scripted on top of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioData {
+    pub name: String,
+    pub description: String,
+    pub map: MapChoice,
+    #[serde(default)]
+    pub map_preset: MapPreset,
+    #[serde(default)]
+    pub map_symmetry: MapSymmetry,
+    #[serde(default)]
+    pub ai_slots: Vec<AiSlotConfig>,
+    #[serde(default)]
+    pub world: Option<ScenarioWorld>, // Starting world; without one every player gets the usual starting base
+    #[serde(default)]
+    pub script: Option<String>,       // Script in the scripts directory its triggers can call into
+    pub triggers: Vec<TriggerData>,
+}
+
+impl ScenarioData {
+    /// Read a scenario file, rejecting triggers with clashing names or waiting on triggers that don't exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path.as_ref())?;
+        let scenario: ScenarioData = ron::from_str(&text)?;
+        
+        let mut names = HashSet::new();
+        for trigger in &scenario.triggers {
+            if !names.insert(trigger.name.as_str()) {
+                return Err(anyhow::anyhow!("{}: trigger {} is defined more than once", path.as_ref().display(), trigger.name));
+            }
+        }
+        for trigger in &scenario.triggers {
+            if let Some(after) = trigger.after.as_deref().filter(|after| !names.contains(after)) {
+                return Err(anyhow::anyhow!("{}: trigger {} waits for unknown trigger {}", path.as_ref().display(), trigger.name, after));
+            }
+        }
+        
+        Ok(scenario)
+    }
+}
+
+/// Triggers of the running scenario and how far along each is
+#[derive(Resource, Debug, Clone)]
+pub struct ScenarioState {
+    pub name: String,
+    triggers: Vec<TriggerData>,
+    armed_at: Vec<Option<u64>>, // Tick each trigger was armed at; None while it waits for its `after` trigger
+    fired: Vec<bool>,
+}
+
+impl ScenarioState {
+    pub fn new(scenario: &ScenarioData) -> Self {
+        Self {
+            name: scenario.name.clone(),
+            triggers: scenario.triggers.clone(),
+            armed_at: scenario.triggers.iter().map(|trigger| trigger.after.is_none().then_some(0)).collect(),
+            fired: vec![false; scenario.triggers.len()],
+        }
+    }
+}
+
+/// Arm a scenario's triggers in a world that has just been set up for it
+pub fn start_scenario(world: &mut World, scenario: &ScenarioData) {
+    world.insert_resource(ScenarioState::new(scenario));
+    world.insert_resource(RevealedAreas::default());
+    
+    // A broken script leaves the triggers calling into it doing nothing, rather than the scenario unplayable
+    let script = scenario.script.as_deref().and_then(|file_name| match ScriptRuntime::load(file_name, ScriptRole::World) {
+        Ok(runtime) => Some(runtime),
+        Err(e) => {
+            error!("Failed to load scenario script: {}", e);
+            None
+        }
+    });
+    world.get_resource_or_insert_with(Scripts::default).scenario = script;
+    info!("Scenario: {} ({} triggers)", scenario.name, scenario.triggers.len());
+}
+
+/// Place a scenario's starting units, buildings and resources in a world whose map is set up
+pub fn spawn_scenario_world(world: &mut World, scenario_world: &ScenarioWorld) {
+    let mut queue = CommandQueue::default();
+    {
+        let tech_state = world.resource::<TechState>();
+        let mut commands = Commands::new(&mut queue, world);
+        for placed in &scenario_world.buildings {
+            let building_data = data::game_data().building(placed.building_type);
+            let mut building = commands.spawn((
+                Building {
+                    building_type: placed.building_type,
+                    health: building_data.health,
+                    max_health: building_data.health,
+                    production_queue: std::collections::VecDeque::new(),
+                    production_progress: None,
+                    construction_progress: None,
+                    rally_point: None,
+                },
+                Transform {
+                    position: placed.position,
+                    rotation: 0.0,
+                    scale: building_data.size,
+                },
+                Owner(placed.owner),
+                Collider {
+                    radius: building_data.size.max_element() * 0.5,
+                    collision_layer: 2, // Building layer
+                    collision_mask: 1 | 2,
+                },
+                Selectable,
+            ));
+            if placed.building_type == BuildingType::Gate {
+                building.insert(Gate);
+            }
+        }
+        for placed in &scenario_world.units {
+            let params = UnitSpawnParams { unit_type: placed.unit_type, owner: placed.owner, position: placed.position };
+            units::spawn_unit(&mut commands, params, tech_state);
+        }
+    }
+    queue.apply(world);
+    
+    // Units path around the placed buildings like around ones built during the game
+    if let Some(mut map) = world.get_resource_mut::<GameMap>() {
+        let footprints: Vec<(BuildingType, Vec2)> = scenario_world.buildings
+            .iter()
+            .map(|placed| (placed.building_type, placed.position))
+            .collect();
+        buildings::occupy_footprints(&mut map, &footprints);
+        pathfinding::rebuild_pathfinding_grid(&mut map);
+    }
+    
+    let mut player_resources = world.get_resource_or_insert_with(PlayerResources::default);
+    for &(player_id, resource_type, amount) in &scenario_world.resources {
+        player_resources.resources.insert((player_id, resource_type), amount);
+    }
+}
+
+/// Where a player's part of a starting world is, for pointing the camera at: their first building,
+/// else their first unit
+pub fn player_world_position(scenario_world: &ScenarioWorld, player_id: u8) -> Option<Vec2> {
+    scenario_world.buildings
+        .iter()
+        .find(|placed| placed.owner == player_id)
+        .map(|placed| placed.position)
+        .or_else(|| scenario_world.units.iter().find(|placed| placed.owner == player_id).map(|placed| placed.position))
+}
+
+/// System that fires every armed trigger whose conditions hold, arms the triggers waiting on it
+/// and carries out its actions
+pub fn scenario_system(world: &mut World) {
+    if !world.contains_resource::<ScenarioState>() {
+        return;
+    }
+    let tick = world.resource::<GameTime>().current_tick;
+    
+    let actions = world.resource_scope(|world, mut scenario: Mut<ScenarioState>| {
+        let mut actions = Vec::new();
+        for index in 0..scenario.triggers.len() {
+            let armed_at = match scenario.armed_at[index] {
+                Some(armed_at) if !scenario.fired[index] => armed_at,
+                _ => continue,
+            };
+            let trigger = &scenario.triggers[index];
+            if !trigger.conditions.iter().all(|condition| condition_holds(world, condition, tick - armed_at)) {
+                continue;
+            }
+            
+            let name = trigger.name.clone();
+            actions.extend(trigger.actions.iter().cloned());
+            scenario.fired[index] = true;
+            for waiting in 0..scenario.triggers.len() {
+                if scenario.triggers[waiting].after.as_deref() == Some(name.as_str()) {
+                    scenario.armed_at[waiting] = Some(tick);
+                }
+            }
+        }
+        actions
+    });
+    
+    for action in actions {
+        run_action(world, action);
+    }
+}
+
+fn condition_holds(world: &mut World, condition: &TriggerCondition, armed_ticks: u64) -> bool {
+    match *condition {
+        TriggerCondition::Not(ref inner) => !condition_holds(world, inner, armed_ticks),
+        TriggerCondition::TimeElapsed(seconds) => armed_ticks as f32 / TICK_RATE as f32 >= seconds,
+        TriggerCondition::UnitCount { player, unit_type, at_least } => {
+            let mut units = world.query::<(&Unit, &Owner)>();
+            let count = units
+                .iter(world)
+                .filter(|(unit, owner)| owner.0 == player && unit.health > 0.0)
+                .filter(|(unit, _)| unit_type.map_or(true, |unit_type| unit.unit_type == unit_type))
+                .count();
+            count as u32 >= at_least
+        }
+        TriggerCondition::BuildingCount { player, building_type, at_least } => {
+            let mut buildings = world.query::<(&Building, &Owner)>();
+            let count = buildings
+                .iter(world)
+                .filter(|(building, owner)| owner.0 == player && building.construction_progress.is_none())
+                .filter(|(building, _)| building_type.map_or(true, |building_type| building.building_type == building_type))
+                .count();
+            count as u32 >= at_least
+        }
+        TriggerCondition::RegionEntered { player, center, radius } => {
+            let mut units = world.query_filtered::<(&Transform, &Owner), With<Unit>>();
+            units
+                .iter(world)
+                .any(|(transform, owner)| owner.0 == player && transform.position.distance(center) <= radius)
+        }
+        TriggerCondition::ResourcesGathered { player, at_least } => world
+            .get_resource::<GameStats>()
+            .and_then(|stats| stats.player(player))
+            .map_or(false, |stats| stats.resources_gathered >= at_least),
+        TriggerCondition::UnitsSelected(at_least) => world
+            .get_resource::<SelectionState>()
+            .map_or(false, |selection| selection.selected_entities.len() as u32 >= at_least),
+        TriggerCondition::Script(ref function) => scripting::call_script(world, ScriptSlot::Scenario, function, Vec::new())
+            .and_then(|result| result.as_bool().ok())
+            .unwrap_or(false),
+    }
+}
+
+/// Carry out a trigger's action, or one a script asked for
+pub fn run_action(world: &mut World, action: TriggerAction) {
+    match action {
+        TriggerAction::SpawnUnits { unit_type, owner, position, count } => {
+            let mut queue = CommandQueue::default();
+            {
+                let tech_state = world.resource::<TechState>();
+                let mut commands = Commands::new(&mut queue, world);
+                for index in 0..count {
+                    let angle = index as f32 * std::f32::consts::TAU / count as f32;
+                    let offset = if count > 1 { Vec2::from_angle(angle) * SPAWN_SPREAD } else { Vec2::ZERO };
+                    let params = UnitSpawnParams { unit_type, owner, position: position + offset };
+                    units::spawn_unit(&mut commands, params, tech_state);
+                }
+            }
+            queue.apply(world);
+        }
+        TriggerAction::ShowMessage { player, text } => {
+            world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((player, text));
+        }
+        TriggerAction::RevealArea { player, center, radius } => {
+            world.get_resource_or_insert_with(RevealedAreas::default).areas.push((player, center, radius));
+        }
+        TriggerAction::Win { player } => {
+            let team = team_of(world, player);
+            decide(world, VictoryOutcome::Winner { team, player_id: player });
+        }
+        TriggerAction::Lose { player } => {
+            // The lowest numbered player on another team wins. A player alone in the scenario loses
+            // to the neutral side, so they still see a defeat
+            let team = team_of(world, player);
+            let winner = world.get_resource::<PlayerInfo>().and_then(|info| {
+                info.player_names.keys().copied().filter(|&other| info.team_of(other) != team).min()
+            });
+            let outcome = match winner {
+                Some(winner) => VictoryOutcome::Winner { team: team_of(world, winner), player_id: winner },
+                None => VictoryOutcome::Winner { team: NEUTRAL_PLAYER, player_id: NEUTRAL_PLAYER },
+            };
+            decide(world, outcome);
+        }
+        TriggerAction::RunScript(function) => {
+            scripting::call_script(world, ScriptSlot::Scenario, &function, Vec::new());
+        }
+    }
+}
+
+fn team_of(world: &World, player_id: u8) -> u8 {
+    world.get_resource::<PlayerInfo>().map_or(player_id, |info| info.team_of(player_id))
+}
+
+fn decide(world: &mut World, outcome: VictoryOutcome) {
+    if let Some(mut victory) = world.get_resource_mut::<VictoryState>() {
+        victory.decide(outcome);
+    }
+}
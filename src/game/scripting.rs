@@ -0,0 +1,511 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+use crate::ecs::components::{Building, NetworkId, Owner, ResourceType, Transform, Unit, UnitType};
+use crate::ecs::resources::{GameTime, PlayerResources, TICK_RATE};
+use crate::engine::input::Command;
+use crate::game::ai::{AiPlayer, BuildingInfo, UnitInfo, WorldSnapshot};
+use crate::game::commands::process_commands;
+use crate::game::scenario::{self, TriggerAction};
+
+/// Directory scripts are loaded from; nothing outside it can be run
+pub const SCRIPT_DIR: &str = "assets/scripts";
+
+/// Script holding the functions of abilities with a Script effect, loaded with every game if present
+pub const ABILITY_SCRIPT: &str = "abilities.rhai";
+
+/// Most operations one call into a script may take before it is stopped, so a runaway loop can't hang the game
+const MAX_OPERATIONS: u64 = 200_000;
+
+/// Deepest a script's function calls may nest
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Largest string, array or object map a script may build
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+/// Most `every` timers one script may have running, so a script can't bury each tick in calls
+const MAX_TIMERS: usize = 64;
+
+/// Seconds between calls to a bot script's update function
+const BOT_THINK_INTERVAL: f32 = 0.5;
+
+/// What a script may touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptRole {
+    /// Scenario and ability scripts: see everything, spawn units, end the game and order any player's units
+    World,
+    /// Bot scripts: see what the player can see and order only that player's units
+    Player(u8),
+}
+
+/// Something a script asked for, carried out once the call into it has returned
+#[derive(Debug, Clone)]
+pub enum ScriptAction {
+    Trigger(TriggerAction),
+    Orders { player: u8, commands: Vec<Command> },
+}
+
+/// A function a script asked to have called every `interval` seconds
+#[derive(Debug, Clone)]
+struct ScriptTimer {
+    function: String,
+    interval: f32,
+    next: f32, // Game time of the next call
+}
+
+/// State shared between a runtime and the functions it registers with the script engine
+#[derive(Debug, Default)]
+struct ScriptContext {
+    time: f32,
+    units: Vec<UnitInfo>,
+    buildings: Vec<BuildingInfo>,
+    resources: HashMap<(u8, ResourceType), f32>,
+    actions: Vec<ScriptAction>,
+    timers: Vec<ScriptTimer>,
+}
+
+/// A loaded script: its compiled code, its global variables and the sandboxed engine running it
+pub struct ScriptRuntime {
+    pub name: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    context: Arc<Mutex<ScriptContext>>,
+}
+
+impl ScriptRuntime {
+    /// Compile a script from SCRIPT_DIR and run its top level, where it sets up its globals and timers
+    pub fn load(file_name: &str, role: ScriptRole) -> Result<Self> {
+        let path = script_path(file_name)?;
+        let text = fs::read_to_string(&path)?;
+        let runtime = Self::compile(file_name, &text, role).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        
+        info!("Loaded script {}", file_name);
+        Ok(runtime)
+    }
+    
+    /// Compile a script's text in the sandbox and run its top level
+    fn compile(name: &str, text: &str, role: ScriptRole) -> Result<Self> {
+        let context = Arc::new(Mutex::new(ScriptContext::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_COLLECTION_SIZE);
+        engine.set_max_array_size(MAX_COLLECTION_SIZE);
+        engine.set_max_map_size(MAX_COLLECTION_SIZE);
+        engine.disable_symbol("eval");
+        register_api(&mut engine, &context, role);
+        
+        let ast = engine.compile(text)?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+        
+        Ok(Self { name: name.to_string(), engine, ast, scope, context })
+    }
+    
+    /// Whether the script defines a function, with any number of parameters
+    pub fn has_function(&self, function: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == function)
+    }
+    
+    /// Call one of the script's functions; what it asks for is kept for `take_actions`
+    pub fn call(&mut self, function: &str, args: Vec<Dynamic>) -> Result<Dynamic> {
+        self.engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, function, args)
+            .map_err(|e| anyhow::anyhow!("{}: {} failed: {}", self.name, function, e))
+    }
+    
+    /// Everything the script has asked for since this was last called
+    pub fn take_actions(&mut self) -> Vec<ScriptAction> {
+        std::mem::take(&mut self.context.lock().unwrap().actions)
+    }
+    
+    /// Functions whose timers have run out by `time`, rescheduling them
+    fn due_timers(&mut self, time: f32) -> Vec<String> {
+        let mut context = self.context.lock().unwrap();
+        let mut due = Vec::new();
+        for timer in context.timers.iter_mut().filter(|timer| timer.next <= time) {
+            due.push(timer.function.clone());
+            timer.next = time + timer.interval;
+        }
+        due
+    }
+    
+    /// Let the script see the whole world as it is now
+    fn refresh_from_world(&mut self, world: &World, time: f32) {
+        let mut units = Vec::new();
+        let mut buildings = Vec::new();
+        for entity in world.iter_entities() {
+            let (transform, owner, network_id) = match (entity.get::<Transform>(), entity.get::<Owner>(), entity.get::<NetworkId>()) {
+                (Some(transform), Some(owner), Some(network_id)) => (transform, owner.0, *network_id),
+                _ => continue,
+            };
+            if let Some(unit) = entity.get::<Unit>().filter(|unit| unit.health > 0.0) {
+                units.push(UnitInfo {
+                    entity: entity.id(),
+                    network_id,
+                    owner,
+                    unit_type: unit.unit_type,
+                    position: transform.position,
+                    health: unit.health,
+                    max_health: unit.max_health,
+                });
+            } else if let Some(building) = entity.get::<Building>().filter(|building| building.health > 0.0) {
+                buildings.push(BuildingInfo {
+                    entity: entity.id(),
+                    network_id,
+                    owner,
+                    building_type: building.building_type,
+                    position: transform.position,
+                    health: building.health,
+                    max_health: building.max_health,
+                    finished: building.construction_progress.is_none(),
+                });
+            }
+        }
+        
+        let mut context = self.context.lock().unwrap();
+        context.time = time;
+        context.units = units;
+        context.buildings = buildings;
+        context.resources = world
+            .get_resource::<PlayerResources>()
+            .map_or_else(HashMap::new, |player_resources| player_resources.resources.clone());
+    }
+    
+    /// Let a bot script see what its player can see
+    fn refresh_from_snapshot(&mut self, snapshot: &WorldSnapshot) {
+        let mut context = self.context.lock().unwrap();
+        context.time = snapshot.elapsed_time;
+        context.units = snapshot.own_units.iter().chain(&snapshot.visible_units).copied().collect();
+        context.buildings = snapshot.own_buildings.iter().chain(&snapshot.visible_buildings).copied().collect();
+        context.resources = snapshot.resources
+            .iter()
+            .map(|(&resource_type, &amount)| ((snapshot.player_id, resource_type), amount))
+            .collect();
+    }
+}
+
+/// Path of a script in SCRIPT_DIR, refusing names that would reach outside it
+fn script_path(file_name: &str) -> Result<PathBuf> {
+    let inside = Path::new(file_name).components().all(|component| matches!(component, Component::Normal(_)));
+    if !inside || file_name.is_empty() {
+        return Err(anyhow::anyhow!("{}: scripts are only loaded from {}", file_name, SCRIPT_DIR));
+    }
+    Ok(PathBuf::from(SCRIPT_DIR).join(file_name))
+}
+
+/// Register the functions scripts call into the game with. Orders go to the units named by the
+/// ids `units()` hands out; what a script asks for happens once the call into it returns.
+fn register_api(engine: &mut Engine, context: &Arc<Mutex<ScriptContext>>, role: ScriptRole) {
+    // Reading the game
+    let ctx = context.clone();
+    engine.register_fn("game_time", move || ctx.lock().unwrap().time as f64);
+    let ctx = context.clone();
+    engine.register_fn("units", move |player: i64| -> Array {
+        let context = ctx.lock().unwrap();
+        context.units.iter().filter(|unit| unit.owner as i64 == player).map(|unit| Dynamic::from_map(unit_map(unit))).collect()
+    });
+    let ctx = context.clone();
+    engine.register_fn("buildings", move |player: i64| -> Array {
+        let context = ctx.lock().unwrap();
+        context.buildings
+            .iter()
+            .filter(|building| building.owner as i64 == player)
+            .map(|building| Dynamic::from_map(building_map(building)))
+            .collect()
+    });
+    let ctx = context.clone();
+    engine.register_fn("resources", move |player: i64, resource: &str| -> Result<f64, Box<EvalAltResult>> {
+        let resource_type = parse_resource(resource)?;
+        Ok(ctx.lock().unwrap().resources.get(&(player as u8, resource_type)).copied().unwrap_or(0.0) as f64)
+    });
+    let ctx = context.clone();
+    engine.register_fn("every", move |seconds: f64, function: &str| -> Result<(), Box<EvalAltResult>> {
+        let seconds = (seconds as f32).max(1.0 / TICK_RATE as f32);
+        let mut context = ctx.lock().unwrap();
+        if context.timers.len() >= MAX_TIMERS {
+            return Err(format!("a script can have at most {} timers", MAX_TIMERS).into());
+        }
+        let next = context.time + seconds;
+        context.timers.push(ScriptTimer { function: function.to_string(), interval: seconds, next });
+        Ok(())
+    });
+    
+    // Ordering units about
+    let order = move |ctx: &Arc<Mutex<ScriptContext>>, player: i64, units: Array, command: Command| -> Result<(), Box<EvalAltResult>> {
+        if let ScriptRole::Player(own) = role {
+            if player != own as i64 {
+                return Err(format!("bots can only order player {}'s units", own).into());
+            }
+        }
+        let ids = units
+            .into_iter()
+            .filter_map(|id| id.as_int().ok())
+            .map(|id| NetworkId(id as u32))
+            .collect();
+        let commands = vec![Command::SelectUnits(ids), command];
+        ctx.lock().unwrap().actions.push(ScriptAction::Orders { player: player as u8, commands });
+        Ok(())
+    };
+    let ctx = context.clone();
+    engine.register_fn("order_move", move |player: i64, units: Array, x: f64, y: f64| {
+        order(&ctx, player, units, Command::Move(Vec2::new(x as f32, y as f32)))
+    });
+    let ctx = context.clone();
+    engine.register_fn("order_attack", move |player: i64, units: Array, x: f64, y: f64| {
+        order(&ctx, player, units, Command::Attack(Vec2::new(x as f32, y as f32)))
+    });
+    let ctx = context.clone();
+    engine.register_fn("order_stop", move |player: i64, units: Array| order(&ctx, player, units, Command::Stop));
+    
+    if role != ScriptRole::World {
+        return;
+    }
+    
+    // Changing the world, for scenarios and abilities only
+    let trigger = |ctx: &Arc<Mutex<ScriptContext>>, action: TriggerAction| {
+        ctx.lock().unwrap().actions.push(ScriptAction::Trigger(action));
+    };
+    let ctx = context.clone();
+    engine.register_fn("spawn_units", move |unit_type: &str, owner: i64, x: f64, y: f64, count: i64| -> Result<(), Box<EvalAltResult>> {
+        let unit_type = parse_unit_type(unit_type)?;
+        let position = Vec2::new(x as f32, y as f32);
+        trigger(&ctx, TriggerAction::SpawnUnits { unit_type, owner: owner as u8, position, count: count.max(0) as u32 });
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("show_message", move |player: i64, text: &str| {
+        trigger(&ctx, TriggerAction::ShowMessage { player: player as u8, text: text.to_string() });
+    });
+    let ctx = context.clone();
+    engine.register_fn("reveal_area", move |player: i64, x: f64, y: f64, radius: f64| {
+        let center = Vec2::new(x as f32, y as f32);
+        trigger(&ctx, TriggerAction::RevealArea { player: player as u8, center, radius: radius as f32 });
+    });
+    let ctx = context.clone();
+    engine.register_fn("win", move |player: i64| trigger(&ctx, TriggerAction::Win { player: player as u8 }));
+    let ctx = context.clone();
+    engine.register_fn("lose", move |player: i64| trigger(&ctx, TriggerAction::Lose { player: player as u8 }));
+}
+
+fn unit_map(unit: &UnitInfo) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (unit.network_id.0 as i64).into());
+    map.insert("owner".into(), (unit.owner as i64).into());
+    map.insert("unit_type".into(), format!("{:?}", unit.unit_type).into());
+    map.insert("x".into(), (unit.position.x as f64).into());
+    map.insert("y".into(), (unit.position.y as f64).into());
+    map.insert("health".into(), (unit.health as f64).into());
+    map.insert("max_health".into(), (unit.max_health as f64).into());
+    map
+}
+
+fn building_map(building: &BuildingInfo) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (building.network_id.0 as i64).into());
+    map.insert("owner".into(), (building.owner as i64).into());
+    map.insert("building_type".into(), format!("{:?}", building.building_type).into());
+    map.insert("x".into(), (building.position.x as f64).into());
+    map.insert("y".into(), (building.position.y as f64).into());
+    map.insert("health".into(), (building.health as f64).into());
+    map.insert("max_health".into(), (building.max_health as f64).into());
+    map.insert("finished".into(), building.finished.into());
+    map
+}
+
+fn parse_unit_type(name: &str) -> Result<UnitType, Box<EvalAltResult>> {
+    UnitType::ALL
+        .into_iter()
+        .find(|unit_type| format!("{:?}", unit_type) == name)
+        .ok_or_else(|| format!("unknown unit type {}", name).into())
+}
+
+fn parse_resource(name: &str) -> Result<ResourceType, Box<EvalAltResult>> {
+    [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy]
+        .into_iter()
+        .find(|resource_type| format!("{:?}", resource_type) == name)
+        .ok_or_else(|| format!("unknown resource {}", name).into())
+}
+
+/// Which of a game's scripts to call into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptSlot {
+    Scenario,
+    Abilities,
+}
+
+/// Scripts loaded for the game being played
+#[derive(Resource, Default)]
+pub struct Scripts {
+    pub scenario: Option<ScriptRuntime>,  // From the scenario file's `script`
+    pub abilities: Option<ScriptRuntime>, // ABILITY_SCRIPT, when there is one
+}
+
+impl Scripts {
+    fn runtime_mut(&mut self, slot: ScriptSlot) -> Option<&mut ScriptRuntime> {
+        match slot {
+            ScriptSlot::Scenario => self.scenario.as_mut(),
+            ScriptSlot::Abilities => self.abilities.as_mut(),
+        }
+    }
+}
+
+/// Ability script of a fresh game, if the game ships one
+pub fn load_ability_script() -> Option<ScriptRuntime> {
+    if !Path::new(SCRIPT_DIR).join(ABILITY_SCRIPT).exists() {
+        return None;
+    }
+    match ScriptRuntime::load(ABILITY_SCRIPT, ScriptRole::World) {
+        Ok(runtime) => Some(runtime),
+        Err(e) => {
+            error!("Failed to load ability script: {}", e);
+            None
+        }
+    }
+}
+
+/// Call a function of one of the game's scripts with the world as it is now and carry out what it
+/// asks for. Returns what the function returned, or None without such a script or on failure.
+pub fn call_script(world: &mut World, slot: ScriptSlot, function: &str, args: Vec<Dynamic>) -> Option<Dynamic> {
+    if !world.contains_resource::<Scripts>() {
+        return None;
+    }
+    let time = world.get_resource::<GameTime>().map_or(0.0, |time| time.current_tick as f32 / TICK_RATE as f32);
+    
+    let (result, actions) = world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+        let runtime = scripts.runtime_mut(slot)?;
+        runtime.refresh_from_world(world, time);
+        let result = runtime.call(function, args).map_err(|e| error!("Script error: {}", e)).ok();
+        Some((result, runtime.take_actions()))
+    })?;
+    apply_actions(world, actions);
+    result
+}
+
+fn apply_actions(world: &mut World, actions: Vec<ScriptAction>) {
+    for action in actions {
+        match action {
+            ScriptAction::Trigger(action) => scenario::run_action(world, action),
+            ScriptAction::Orders { player, commands } => process_commands(world, player, &commands),
+        }
+    }
+}
+
+/// System that calls the script functions whose timers have run out
+pub fn script_system(world: &mut World) {
+    if !world.contains_resource::<Scripts>() {
+        return;
+    }
+    let time = world.resource::<GameTime>().current_tick as f32 / TICK_RATE as f32;
+    
+    for slot in [ScriptSlot::Scenario, ScriptSlot::Abilities] {
+        let due = world
+            .resource_mut::<Scripts>()
+            .runtime_mut(slot)
+            .map_or_else(Vec::new, |runtime| runtime.due_timers(time));
+        for function in due {
+            call_script(world, slot, &function, Vec::new());
+        }
+    }
+}
+
+/// Computer player run by a script, for trying out AI ideas without rebuilding the game. The
+/// script's `update()` is called every BOT_THINK_INTERVAL seconds with what the player can see,
+/// and orders the player's units with the order_* functions.
+pub struct ScriptBot {
+    runtime: ScriptRuntime,
+    timer: f32,
+}
+
+impl ScriptBot {
+    pub fn load(file_name: &str, player_id: u8) -> Result<Self> {
+        let runtime = ScriptRuntime::load(file_name, ScriptRole::Player(player_id))?;
+        if !runtime.has_function("update") {
+            return Err(anyhow::anyhow!("{}: bot scripts need an update() function", file_name));
+        }
+        Ok(Self { runtime, timer: 0.0 })
+    }
+}
+
+impl AiPlayer for ScriptBot {
+    fn update(&mut self, snapshot: &WorldSnapshot, delta_time: f32) -> Vec<Command> {
+        self.timer += delta_time;
+        if self.timer < BOT_THINK_INTERVAL {
+            return Vec::new();
+        }
+        self.timer = 0.0;
+        
+        self.runtime.refresh_from_snapshot(snapshot);
+        if let Err(e) = self.runtime.call("update", Vec::new()) {
+            error!("Script error: {}", e);
+        }
+        
+        // Bots are loaded without the world functions, so orders for their own units are all they can ask for
+        self.runtime
+            .take_actions()
+            .into_iter()
+            .flat_map(|action| match action {
+                ScriptAction::Orders { commands, .. } => commands,
+                ScriptAction::Trigger(_) => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn run(text: &str) -> Result<ScriptRuntime> {
+        ScriptRuntime::compile("test.rhai", text, ScriptRole::World)
+    }
+    
+    fn error_of(text: &str) -> String {
+        match run(text) {
+            Ok(_) => panic!("script should have been stopped: {}", text),
+            Err(e) => e.to_string(),
+        }
+    }
+    
+    #[test]
+    fn runaway_loops_are_stopped() {
+        assert!(error_of("loop {}").contains("Too many operations"));
+    }
+    
+    #[test]
+    fn deep_recursion_is_stopped() {
+        assert!(error_of("fn dive(n) { dive(n + 1) } dive(0);").contains("Stack overflow"));
+    }
+    
+    #[test]
+    fn oversized_collections_are_refused() {
+        assert!(error_of("let a = []; for i in 0..20000 { a.push(i); }").contains("too large"));
+        assert!(error_of("let s = \"\"; loop { s += \"xxxxxxxxxx\"; }").contains("too large"));
+    }
+    
+    #[test]
+    fn eval_is_disabled() {
+        assert!(error_of("eval(\"1 + 1\")").contains("eval"));
+    }
+    
+    #[test]
+    fn timers_are_capped_per_script() {
+        let mut runtime = run(&format!("for i in 0..{} {{ every(1.0, \"tick\"); }}", MAX_TIMERS)).unwrap();
+        assert_eq!(runtime.due_timers(1.0).len(), MAX_TIMERS);
+        assert!(error_of(&format!("for i in 0..{} {{ every(1.0, \"tick\"); }}", MAX_TIMERS + 1)).contains("timers"));
+    }
+    
+    #[test]
+    fn scripts_within_the_limits_run() {
+        let mut runtime = run("fn double(n) { n * 2 }").unwrap();
+        assert_eq!(runtime.call("double", vec![Dynamic::from(21_i64)]).unwrap().as_int().unwrap(), 42);
+    }
+}
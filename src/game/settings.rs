@@ -0,0 +1,159 @@
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::engine::audio::AudioSettings;
+use crate::engine::camera::CameraSettings;
+use crate::engine::renderer::{HealthBarMode, VideoSettings};
+use crate::game::GameSettings;
+
+/// File the player's settings are kept in between sessions
+pub const SETTINGS_FILE: &str = "config/settings.toml";
+
+/// Longest player name the settings menu accepts
+pub const MAX_PLAYER_NAME_LENGTH: usize = 16;
+
+/// Range of the game speed slider in the settings menu
+pub const MIN_GAME_SPEED: f32 = 0.5;
+pub const MAX_GAME_SPEED: f32 = 3.0;
+
+/// Speeds the faster and slower game speed hotkeys step through
+const GAME_SPEED_STEPS: [f32; 6] = [0.5, 0.75, 1.0, 1.5, 2.0, 3.0];
+
+/// Gameplay options from the settings menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub player_name: String,
+    pub fog_of_war_enabled: bool,
+    pub game_speed: f32,
+    pub show_fps: bool,
+    pub health_bars: HealthBarMode,
+    pub combat_text: bool,
+    pub auto_save_enabled: bool,
+    pub auto_save_interval: f32,
+    pub ui_theme: String,
+    pub language: String,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        let defaults = GameSettings::default();
+        Self {
+            player_name: defaults.player_name,
+            fog_of_war_enabled: defaults.fog_of_war_enabled,
+            game_speed: defaults.game_speed,
+            show_fps: defaults.show_fps,
+            health_bars: defaults.health_bars,
+            combat_text: defaults.combat_text,
+            auto_save_enabled: defaults.auto_save_enabled,
+            auto_save_interval: defaults.auto_save_interval,
+            ui_theme: defaults.ui_theme,
+            language: defaults.language,
+        }
+    }
+}
+
+/// Everything written to the settings file; sections missing from the file keep their defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsFile {
+    pub audio: AudioSettings,
+    pub video: VideoSettings,
+    pub gameplay: GameplaySettings,
+    pub camera: CameraSettings,
+}
+
+impl SettingsFile {
+    /// The persistent part of the current game settings
+    pub fn from_settings(settings: &GameSettings) -> Self {
+        Self {
+            audio: settings.audio.clone(),
+            video: settings.video.clone(),
+            gameplay: GameplaySettings {
+                player_name: settings.player_name.clone(),
+                fog_of_war_enabled: settings.fog_of_war_enabled,
+                game_speed: settings.game_speed,
+                show_fps: settings.show_fps,
+                health_bars: settings.health_bars,
+                combat_text: settings.combat_text,
+                auto_save_enabled: settings.auto_save_enabled,
+                auto_save_interval: settings.auto_save_interval,
+                ui_theme: settings.ui_theme.clone(),
+                language: settings.language.clone(),
+            },
+            camera: settings.camera.clone(),
+        }
+    }
+    
+    /// Copy these options into the game settings, leaving per-match options such as AI slots alone
+    pub fn apply_to(&self, settings: &mut GameSettings) {
+        settings.audio = self.audio.clone();
+        settings.video = self.video.clone();
+        settings.player_name = self.gameplay.player_name.clone();
+        settings.fog_of_war_enabled = self.gameplay.fog_of_war_enabled;
+        settings.game_speed = self.gameplay.game_speed;
+        settings.show_fps = self.gameplay.show_fps;
+        settings.health_bars = self.gameplay.health_bars;
+        settings.combat_text = self.gameplay.combat_text;
+        settings.auto_save_enabled = self.gameplay.auto_save_enabled;
+        settings.auto_save_interval = self.gameplay.auto_save_interval;
+        settings.ui_theme = self.gameplay.ui_theme.clone();
+        settings.language = self.gameplay.language.clone();
+        settings.camera = self.camera.clone();
+    }
+    
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+    
+    /// Write the settings to `path`, creating its directory if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Overwrite `settings` with the saved settings file, if there is one
+pub fn load_settings(settings: &mut GameSettings) {
+    if !Path::new(SETTINGS_FILE).exists() {
+        return;
+    }
+    
+    match SettingsFile::load(SETTINGS_FILE) {
+        Ok(file) => file.apply_to(settings),
+        Err(e) => warn!("Failed to load settings, using defaults: {}", e),
+    }
+}
+
+/// Game speed `steps` hotkey presses faster (or slower, when negative) than `current`
+pub fn step_game_speed(current: f32, steps: i32) -> f32 {
+    let mut speed = current;
+    for _ in 0..steps.abs() {
+        speed = if steps > 0 {
+            GAME_SPEED_STEPS.iter().copied().find(|&step| step > speed + f32::EPSILON).unwrap_or(MAX_GAME_SPEED)
+        } else {
+            GAME_SPEED_STEPS.iter().rev().copied().find(|&step| step < speed - f32::EPSILON).unwrap_or(MIN_GAME_SPEED)
+        };
+    }
+    speed
+}
+
+/// Whether a name can be shown to other players: not blank, not too long, no control characters
+pub fn is_valid_player_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_PLAYER_NAME_LENGTH
+        && !name.chars().any(char::is_control)
+}
+
+/// Write the persistent part of `settings` to the settings file
+pub fn save_settings(settings: &GameSettings) -> Result<()> {
+    SettingsFile::from_settings(settings).save(SETTINGS_FILE)
+}
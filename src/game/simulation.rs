@@ -0,0 +1,639 @@
+use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::{ExecutorKind, LogLevel, ScheduleBuildSettings};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::ecs::events::{DamageEvent, DeathEvent};
+use crate::ecs::network_id::assign_network_ids;
+use crate::ecs::resources::{GameTime, PlayerAlerts, TICK_DELTA};
+use crate::ecs::systems::*;
+use crate::ecs::systems::combat::{building_lost_alert_system, building_targeting_system, combat_system, damage_system, death_system};
+use crate::engine::input::Command;
+use crate::engine::profiler::timed;
+use crate::game::abilities::{ability_system, ability_timer_system};
+use crate::game::animation::{animation_system, corpse_system};
+use crate::game::commands::process_commands;
+use crate::game::garrison::{garrison_collapse_system, garrison_system};
+use crate::game::neutral::{capture_system, creep_leash_system};
+use crate::game::path_queue::path_queue_system;
+use crate::game::scenario::scenario_system;
+use crate::game::scripting::script_system;
+use crate::game::stats::{kill_stats_system, stats_sampling_system};
+use crate::game::threat::threat_map_system;
+use crate::game::transport::boarding_system;
+use crate::game::validation::{validate_command, Violation};
+use crate::game::veterancy::{kill_experience_system, veterancy_system};
+use crate::game::victory::victory_system;
+use crate::networking::checksum::world_checksum;
+
+/// RNG stream for combat rolls (accuracy, impact scatter)
+pub const RNG_STREAM_COMBAT: u64 = 1;
+/// RNG stream for unit behaviour decisions
+pub const RNG_STREAM_BEHAVIOR: u64 = 2;
+/// RNG stream for spawn placement
+pub const RNG_STREAM_SPAWN: u64 = 3;
+
+/// Random number generator derived from the game seed, the tick and a stream id.
+/// Every machine in a lockstep game gets the same rolls for the same tick.
+pub fn tick_rng(seed: u64, tick: u64, stream: u64) -> StdRng {
+    let mut state = seed ^ tick.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03);
+    
+    // SplitMix64 finalizer so neighbouring ticks get unrelated seeds
+    state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    state ^= state >> 31;
+    
+    StdRng::seed_from_u64(state)
+}
+
+/// Stages of a simulation tick, run one after another in declaration order
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    Events,      // Rotating the event queues
+    Movement,    // Obstacles, queued paths, movement, the spatial grid and collisions
+    Orders,      // Unit behaviour, creep leashes, healing, abilities, following, boarding and garrisoning
+    Combat,      // Targeting, damage, deaths, promotions and repairs
+    Economy,     // Construction, production, harvesting and research
+    Bookkeeping, // Captures, scenario triggers, fog of war, statistics, the victory check and animations
+}
+
+/// Build the schedule of systems that make up one simulation tick
+pub fn build_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    
+    // Systems that touch different data run alongside each other on worker threads. Any two that
+    // touch the same data must be ordered, or machines in a lockstep game could apply their changes
+    // in different sequences; debug builds refuse to build a schedule that leaves such a pair unordered
+    schedule.set_executor_kind(ExecutorKind::MultiThreaded);
+    schedule.set_build_settings(ScheduleBuildSettings {
+        ambiguity_detection: if cfg!(debug_assertions) { LogLevel::Error } else { LogLevel::Ignore },
+        ..Default::default()
+    });
+    
+    // The stages run one after another
+    schedule.configure_sets(
+        (
+            SimulationSet::Events,
+            SimulationSet::Movement,
+            SimulationSet::Orders,
+            SimulationSet::Combat,
+            SimulationSet::Economy,
+            SimulationSet::Bookkeeping,
+        )
+            .chain(),
+    );
+    
+    // Events last two ticks, so each one is seen by every reader whether it was raised before or after it ran.
+    // Each queue is its own resource, so they rotate in parallel
+    schedule.add_systems(
+        (
+            Events::<DamageEvent>::update_system,
+            Events::<DeathEvent>::update_system,
+        )
+            .in_set(SimulationSet::Events),
+    );
+    // Within the other stages each system builds on what the one before changed, so they run in order.
+    // Heavy systems spread their own work over worker threads instead
+    schedule.add_systems(
+        (
+            timed(pathfinding_obstacle_system),
+            timed(path_queue_system),
+            timed(update_movement_system),
+            timed(spatial_grid_system),
+            timed(collision_detection_system),
+        )
+            .chain()
+            .in_set(SimulationSet::Movement),
+    );
+    schedule.add_systems(
+        (
+            timed(unit_behavior_system),
+            timed(creep_leash_system),
+            timed(healing_system),
+            timed(ability_system),
+            timed(follow_system),
+            timed(boarding_system),
+            timed(garrison_system),
+        )
+            .chain()
+            .in_set(SimulationSet::Orders),
+    );
+    schedule.add_systems(
+        (
+            timed(building_targeting_system),
+            timed(combat_system),
+            timed(damage_system),
+            timed(kill_stats_system),
+            timed(kill_experience_system),
+            timed(building_lost_alert_system),
+            timed(garrison_collapse_system),
+            timed(corpse_system),
+            timed(death_system),
+            timed(veterancy_system),
+            timed(ability_timer_system),
+            timed(repair_system),
+        )
+            .chain()
+            .in_set(SimulationSet::Combat),
+    );
+    schedule.add_systems(
+        (
+            timed(construction_system),
+            timed(building_upgrade_system),
+            timed(building_production_system),
+            timed(resource_collection_system),
+            timed(economy_system),
+            timed(tech_research_system),
+            timed(idle_worker_system),
+        )
+            .chain()
+            .in_set(SimulationSet::Economy),
+    );
+    // Vision, statistics and animations read the settled world and write nothing the others read,
+    // so they run side by side once scenarios and scripts are done; the threat maps need the new vision
+    schedule.add_systems(
+        (
+            (timed(capture_system), timed(scenario_system), timed(script_system)).chain(),
+            (
+                (timed(fog_of_war_system), timed(threat_map_system)).chain(),
+                timed(stats_sampling_system),
+                timed(animation_system),
+            ),
+            timed(victory_system),
+        )
+            .chain()
+            .in_set(SimulationSet::Bookkeeping),
+    );
+    
+    schedule
+}
+
+/// Apply one tick's commands in player order so every machine applies them identically
+pub fn apply_tick_commands(world: &mut World, commands: &HashMap<u8, Vec<Command>>) {
+    let mut player_ids: Vec<u8> = commands.keys().copied().collect();
+    player_ids.sort_unstable();
+    for player_id in player_ids {
+        // Checked one at a time, as each may change what the next can do
+        for command in &commands[&player_id] {
+            match validate_command(world, player_id, command) {
+                Ok(()) => process_commands(world, player_id, std::slice::from_ref(command)),
+                // Commands are applied a few ticks after they were given, so an order the player could
+                // afford then may no longer be; that isn't cheating
+                Err(Violation::Unaffordable) => {
+                    world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((player_id, "Not enough resources".to_string()));
+                }
+                // Only a tampered client sends anything else
+                Err(violation) => {
+                    warn!(target: "net", "Rejected {:?} from player {}: {}", command, player_id, violation);
+                }
+            }
+        }
+    }
+}
+
+/// Advance the simulation by exactly one fixed timestep
+pub fn step(world: &mut World, schedule: &mut Schedule) {
+    // Systems always see the fixed timestep, whatever the frame rate
+    world.resource_mut::<GameTime>().delta_time = TICK_DELTA;
+    
+    schedule.run(world);
+    assign_network_ids(world);
+    
+    let mut game_time = world.resource_mut::<GameTime>();
+    game_time.current_tick += 1;
+    game_time.elapsed_time = game_time.current_tick as f32 * TICK_DELTA;
+}
+
+/// The game world with the systems that advance it and nothing else: no window, renderer, audio
+/// or network. Dedicated hosts, stress runs and tests drive the game through this
+pub struct Simulation {
+    pub world: World,
+    schedule: Schedule,
+}
+
+impl Simulation {
+    pub fn new(world: World) -> Self {
+        Self { world, schedule: build_schedule() }
+    }
+    
+    /// Apply every player's commands for this tick, then advance one tick
+    pub fn tick(&mut self, commands: &HashMap<u8, Vec<Command>>) {
+        apply_tick_commands(&mut self.world, commands);
+        step(&mut self.world, &mut self.schedule);
+    }
+    
+    /// Advance the given number of ticks with nobody giving orders
+    pub fn run(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick(&HashMap::new());
+        }
+    }
+    
+    pub fn current_tick(&self) -> u64 {
+        self.world.resource::<GameTime>().current_tick
+    }
+    
+    /// Checksum of the game state, the same on every machine that applied the same commands
+    pub fn checksum(&mut self) -> u64 {
+        world_checksum(&mut self.world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_tasks::{ComputeTaskPool, TaskPoolBuilder};
+    use glam::Vec2;
+    
+    use std::collections::VecDeque;
+    
+    use crate::ecs::components::{Building, BuildingType, Collider, Movement, Owner, ResourceType, Selectable, Selected, Transform, Unit, UnitType};
+    use crate::ecs::events::{init_events, DamageEvent};
+    use crate::ecs::network_id::network_ids;
+    use crate::ecs::resources::{
+        AttackAlerts, AudioEvents, CombatTextEvents, ControlGroups, GameMap, PlayerAlerts, PlayerInfo, PlayerResources, TechState,
+        TICK_RATE,
+    };
+    use crate::ecs::spatial::SpatialGrid;
+    use crate::ecs::systems::CARRY_CAPACITY;
+    use crate::ecs::systems::combat::DamageTable;
+    use crate::engine::input::UnitCommand;
+    use crate::engine::{init_game_world, spawn_starting_bases};
+    use crate::game::ai::{AiDifficulty, AiPersonality, AiPlayers, AiSlotConfig};
+    use crate::game::map::{generate_map, spawn_resource_nodes, MapGenerationParams};
+    use crate::game::stats::GameStats;
+    use crate::game::victory::{VictoryConditionType, VictoryOutcome, VictoryState};
+    use crate::game::{data, GameState};
+    use crate::networking::replay::{GameReplay, PlayerTickCommands, ReplayRecorder};
+    
+    const SEED: u64 = 1234;
+    
+    fn spawn_soldier(world: &mut World, owner: u8, position: Vec2) -> Entity {
+        world.spawn((
+            Unit {
+                unit_type: UnitType::Soldier,
+                health: 100.0,
+                max_health: 100.0,
+                attack_damage: 10.0,
+                attack_range: 30.0,
+                attack_speed: 1.0,
+                movement_speed: 60.0,
+                sight_range: 80.0,
+                buildable: false,
+            },
+            Transform {
+                position,
+                rotation: 0.0,
+                scale: Vec2::ONE,
+            },
+            Owner(owner),
+            Movement {
+                path: Vec::new(),
+                path_index: 0,
+                target: None,
+                velocity: Vec2::ZERO,
+            },
+            Collider {
+                radius: 5.0,
+                collision_layer: 1,
+                collision_mask: 1 | 2,
+            },
+            Selectable,
+        )).id()
+    }
+    
+    fn spawn_building(world: &mut World, building_type: BuildingType, owner: u8, position: Vec2) -> Entity {
+        let building_data = data::game_data().building(building_type).clone();
+        world.spawn((
+            Building {
+                building_type,
+                health: building_data.health,
+                max_health: building_data.health,
+                production_queue: VecDeque::new(),
+                production_progress: None,
+                construction_progress: None,
+                rally_point: None,
+            },
+            Transform {
+                position,
+                rotation: 0.0,
+                scale: building_data.size,
+            },
+            Owner(owner),
+            Collider {
+                radius: building_data.size.max_element() * 0.5,
+                collision_layer: 2,
+                collision_mask: 1 | 2,
+            },
+            Selectable,
+        )).id()
+    }
+    
+    /// Worker threads the shared task pool gets, so systems, movement, vision and AI brains really
+    /// run side by side in tests, whatever machine they run on
+    const WORKER_THREADS: usize = 4;
+    
+    /// The pool is made once per process by whoever needs it first, so every test that runs the
+    /// schedule sets it up the same way before it does
+    fn init_task_pool() {
+        ComputeTaskPool::init(|| TaskPoolBuilder::new().num_threads(WORKER_THREADS).build());
+    }
+    
+    fn new_world() -> World {
+        init_task_pool();
+        let mut world = World::new();
+        world.insert_resource(GameTime { seed: SEED, ..GameTime::default() });
+        world.insert_resource(generate_map(&MapGenerationParams {
+            width: 128,
+            height: 128,
+            seed: SEED,
+            ..MapGenerationParams::default()
+        }));
+        world.insert_resource(PlayerResources::default());
+        world.insert_resource(TechState::default());
+        world.insert_resource(DamageTable::default());
+        world.insert_resource(ControlGroups::default());
+        world.insert_resource(PlayerInfo::default());
+        world.insert_resource(SpatialGrid::default());
+        world.insert_resource(AudioEvents::default());
+        world.insert_resource(CombatTextEvents::default());
+        world.insert_resource(AttackAlerts::default());
+        world.insert_resource(GameStats::default());
+        world.insert_resource(PlayerAlerts::default());
+        init_events(&mut world);
+        
+        for i in 0..6 {
+            let offset = Vec2::new(i as f32 * 12.0, 0.0);
+            spawn_soldier(&mut world, 0, Vec2::new(20.0, 40.0) + offset);
+            spawn_soldier(&mut world, 1, Vec2::new(20.0, 100.0) + offset);
+        }
+        world
+    }
+    
+    fn command_log() -> Vec<(u64, u8, Vec<Command>)> {
+        vec![
+            (0, 0, vec![Command::MultiSelect(Vec2::new(0.0, 30.0), Vec2::new(100.0, 50.0))]),
+            (0, 1, vec![Command::MultiSelect(Vec2::new(0.0, 90.0), Vec2::new(100.0, 110.0))]),
+            (1, 0, vec![Command::Attack(Vec2::new(50.0, 100.0))]),
+            (1, 1, vec![Command::Attack(Vec2::new(50.0, 40.0))]),
+            (60, 0, vec![Command::Move(Vec2::new(30.0, 70.0))]),
+        ]
+    }
+    
+    /// Replay the command log with the given executor and record the world checksum after every tick
+    fn run_simulation(log: &[(u64, u8, Vec<Command>)], ticks: u64, executor: ExecutorKind) -> Vec<u64> {
+        let mut simulation = Simulation::new(new_world());
+        simulation.schedule.set_executor_kind(executor);
+        
+        let mut checksums = Vec::new();
+        for tick in 0..ticks {
+            let mut tick_commands: HashMap<u8, Vec<Command>> = HashMap::new();
+            for (_, player_id, commands) in log.iter().filter(|(command_tick, _, _)| *command_tick == tick) {
+                tick_commands.entry(*player_id).or_default().extend(commands.iter().cloned());
+            }
+            simulation.tick(&tick_commands);
+            checksums.push(simulation.checksum());
+        }
+        checksums
+    }
+    
+    /// Building the schedule already fails in test builds if two unordered systems touch the same data;
+    /// this checks that systems on worker threads, and the work they spread over them, still land on
+    /// the same state as everything run one after another on this thread
+    #[test]
+    fn same_command_log_gives_same_checksums() {
+        let serial = run_simulation(&command_log(), 200, ExecutorKind::SingleThreaded);
+        let first = run_simulation(&command_log(), 200, ExecutorKind::MultiThreaded);
+        let second = run_simulation(&command_log(), 200, ExecutorKind::MultiThreaded);
+        
+        for (tick, ((a, b), c)) in serial.iter().zip(first.iter()).zip(second.iter()).enumerate() {
+            assert_eq!(a, b, "multi-threaded simulation diverged from the single-threaded one at tick {}", tick);
+            assert_eq!(b, c, "multi-threaded simulations diverged at tick {}", tick);
+        }
+    }
+    
+    /// Play both players with AI brains thinking on worker threads, recording the world checksum after every tick
+    fn run_ai_game(ticks: u64, executor: ExecutorKind) -> Vec<u64> {
+        let mut simulation = new_game();
+        simulation.schedule.set_executor_kind(executor);
+        
+        let slots: Vec<AiSlotConfig> = [(0, AiPersonality::Rusher), (1, AiPersonality::Boomer)]
+            .into_iter()
+            .map(|(player_id, personality)| AiSlotConfig {
+                player_id,
+                difficulty: AiDifficulty::Hard,
+                personality,
+                team: None,
+                resource_bonus: 0.0,
+            })
+            .collect();
+        let mut ai_players = AiPlayers::new();
+        ai_players.setup(&slots, SEED);
+        simulation.world.resource_mut::<PlayerInfo>().ai_players = ai_players.player_ids();
+        
+        let mut checksums = Vec::new();
+        for _ in 0..ticks {
+            let elapsed_time = simulation.world.resource::<GameTime>().elapsed_time;
+            let commands: HashMap<u8, Vec<Command>> = ai_players.update(&simulation.world, elapsed_time, TICK_DELTA).into_iter().collect();
+            simulation.tick(&commands);
+            checksums.push(simulation.checksum());
+        }
+        checksums
+    }
+    
+    #[test]
+    fn ai_games_on_worker_threads_stay_in_step() {
+        let ticks = TICK_RATE as u64 * 60;
+        assert_eq!(run_ai_game(ticks, ExecutorKind::SingleThreaded), run_ai_game(ticks, ExecutorKind::MultiThreaded));
+    }
+    
+    /// The command log recorded as a replay, saved to a file and loaded back
+    fn saved_replay() -> GameReplay {
+        let mut recorder = ReplayRecorder::new(&GameState::new());
+        recorder.start_recording();
+        for (tick, player_id, commands) in command_log() {
+            recorder.record_tick_commands(tick, vec![PlayerTickCommands { player_id, commands }]);
+        }
+        
+        let path = std::env::temp_dir().join(format!("simulation_test_{}.replay", std::process::id()));
+        let path = path.to_str().unwrap();
+        recorder.save_replay(path).unwrap();
+        let replay = ReplayRecorder::load_replay(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        replay
+    }
+    
+    #[test]
+    fn replay_reproduces_checksums() {
+        let log: Vec<(u64, u8, Vec<Command>)> = saved_replay()
+            .commands
+            .into_iter()
+            .flat_map(|recorded| {
+                let tick = recorded.tick;
+                recorded.player_commands.into_iter().map(move |player| (tick, player.player_id, player.commands))
+            })
+            .collect();
+        assert_eq!(
+            run_simulation(&command_log(), 200, ExecutorKind::MultiThreaded),
+            run_simulation(&log, 200, ExecutorKind::MultiThreaded),
+        );
+    }
+    
+    /// Two players with a headquarters and workers at each starting position, the resources around
+    /// them and the last-headquarters-standing victory condition
+    fn new_game() -> Simulation {
+        init_task_pool();
+        let mut world = init_game_world();
+        world.insert_resource(GameTime { seed: SEED, ..GameTime::default() });
+        world.insert_resource(generate_map(&MapGenerationParams {
+            width: 128,
+            height: 128,
+            seed: SEED,
+            ..MapGenerationParams::default()
+        }));
+        world.insert_resource(PlayerResources::default());
+        world.insert_resource(TechState::default());
+        world.insert_resource(VictoryState::new(VictoryConditionType::Annihilation));
+        spawn_resource_nodes(&mut world);
+        
+        let bases: Vec<(u8, Vec2)> = world
+            .resource::<GameMap>()
+            .starting_positions
+            .iter()
+            .enumerate()
+            .map(|(player_id, &position)| (player_id as u8, position))
+            .collect();
+        spawn_starting_bases(&mut world, &bases);
+        
+        let mut simulation = Simulation::new(world);
+        simulation.run(1);
+        simulation
+    }
+    
+    fn orders(player_id: u8, commands: Vec<Command>) -> HashMap<u8, Vec<Command>> {
+        HashMap::from([(player_id, commands)])
+    }
+    
+    fn units_of(world: &mut World, player_id: u8, unit_type: UnitType) -> Vec<Entity> {
+        let mut units: Vec<Entity> = world
+            .query::<(Entity, &Unit, &Owner)>()
+            .iter(world)
+            .filter(|(_, unit, owner)| owner.0 == player_id && unit.unit_type == unit_type)
+            .map(|(entity, ..)| entity)
+            .collect();
+        units.sort();
+        units
+    }
+    
+    fn headquarters_of(world: &mut World, player_id: u8) -> (Entity, Vec2) {
+        world
+            .query::<(Entity, &Building, &Owner, &Transform)>()
+            .iter(world)
+            .find(|(_, building, owner, _)| owner.0 == player_id && building.building_type == BuildingType::Headquarters)
+            .map(|(entity, _, _, transform)| (entity, transform.position))
+            .expect("every player starts with a headquarters")
+    }
+    
+    #[test]
+    fn workers_gather_and_deposit() {
+        let mut simulation = new_game();
+        let (_, base) = headquarters_of(&mut simulation.world, 0);
+        let node = simulation
+            .world
+            .resource::<GameMap>()
+            .resource_positions
+            .iter()
+            .filter(|(_, resource_type, _)| *resource_type == ResourceType::Mineral)
+            .map(|&(position, ..)| position)
+            .min_by(|a, b| a.distance(base).total_cmp(&b.distance(base)))
+            .unwrap();
+        
+        let workers = units_of(&mut simulation.world, 0, UnitType::Worker);
+        let workers = network_ids(&simulation.world, &workers);
+        simulation.tick(&orders(0, vec![Command::SelectUnits(workers), Command::Gather(node)]));
+        simulation.run(TICK_RATE as u64 * 60);
+        
+        let deposited = simulation.world.resource::<PlayerResources>().deposited.get(&(0, ResourceType::Mineral)).copied();
+        assert!(deposited.unwrap_or(0.0) >= CARRY_CAPACITY, "deposited {:?}", deposited);
+    }
+    
+    #[test]
+    fn headquarters_trains_a_worker() {
+        let mut simulation = new_game();
+        let before = units_of(&mut simulation.world, 0, UnitType::Worker).len();
+        
+        simulation.tick(&orders(0, vec![Command::Train(UnitCommand { unit_type: UnitType::Worker as u8 })]));
+        let train_time = data::game_data().unit(UnitType::Worker).train_time;
+        simulation.run(((train_time + 1.0) * TICK_RATE as f32) as u64);
+        
+        assert_eq!(units_of(&mut simulation.world, 0, UnitType::Worker).len(), before + 1);
+    }
+    
+    #[test]
+    fn tower_kills_an_intruder() {
+        let mut simulation = new_game();
+        let (_, base) = headquarters_of(&mut simulation.world, 0);
+        let tower = spawn_building(&mut simulation.world, BuildingType::DefenseTower, 0, base + Vec2::new(40.0, 0.0));
+        let intruder = spawn_soldier(&mut simulation.world, 1, base + Vec2::new(100.0, 0.0));
+        
+        simulation.run(TICK_RATE as u64 * 30);
+        
+        assert!(simulation.world.get_entity(tower).is_some());
+        assert!(simulation.world.get_entity(intruder).is_none());
+    }
+    
+    #[test]
+    fn destroying_the_last_enemy_headquarters_ends_the_game() {
+        let mut simulation = new_game();
+        let (attacker, _) = headquarters_of(&mut simulation.world, 0);
+        let (target, _) = headquarters_of(&mut simulation.world, 1);
+        assert_eq!(simulation.world.resource::<VictoryState>().outcome(), None);
+        
+        simulation.world.send_event(DamageEvent {
+            attacker,
+            attacker_owner: Some(0),
+            target,
+            amount: f32::MAX,
+            damage_type: None,
+        });
+        simulation.run(2);
+        
+        assert!(simulation.world.get_entity(target).is_none());
+        assert_eq!(
+            simulation.world.resource::<VictoryState>().outcome(),
+            Some(VictoryOutcome::Winner { team: 0, player_id: 0 }),
+        );
+    }
+    
+    #[test]
+    fn unaffordable_commands_alert_the_player() {
+        let mut world = new_world();
+        apply_tick_commands(&mut world, &orders(0, vec![Command::Train(UnitCommand { unit_type: UnitType::Soldier as u8 })]));
+        assert_eq!(world.resource::<PlayerAlerts>().alerts, vec![(0, "Not enough resources".to_string())]);
+    }
+    
+    #[test]
+    fn tampered_commands_are_dropped_without_an_alert() {
+        let mut world = new_world();
+        assign_network_ids(&mut world);
+        let enemy = units_of(&mut world, 1, UnitType::Soldier);
+        let enemy = network_ids(&world, &enemy);
+        apply_tick_commands(&mut world, &orders(0, vec![Command::SelectUnits(enemy), Command::Move(Vec2::new(-50.0, 0.0))]));
+        
+        assert!(world.resource::<PlayerAlerts>().alerts.is_empty());
+        assert!(world.query::<&Selected>().iter(&world).next().is_none());
+    }
+    
+    #[test]
+    fn tick_rng_depends_on_seed_tick_and_stream() {
+        use rand::Rng;
+        
+        let roll = |seed, tick, stream| tick_rng(seed, tick, stream).gen::<u64>();
+        assert_eq!(roll(SEED, 10, RNG_STREAM_COMBAT), roll(SEED, 10, RNG_STREAM_COMBAT));
+        assert_ne!(roll(SEED, 10, RNG_STREAM_COMBAT), roll(SEED, 11, RNG_STREAM_COMBAT));
+        assert_ne!(roll(SEED, 10, RNG_STREAM_COMBAT), roll(SEED, 10, RNG_STREAM_BEHAVIOR));
+        assert_ne!(roll(SEED, 10, RNG_STREAM_COMBAT), roll(SEED + 1, 10, RNG_STREAM_COMBAT));
+    }
+}
@@ -0,0 +1,186 @@
+use bevy_ecs::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::ecs::components::{BuildingType, Owner, Unit, UnitType};
+use crate::ecs::events::{Casualty, DeathEvent};
+use crate::ecs::resources::{GameTime, PlayerResources, NEUTRAL_PLAYER};
+use crate::game::data;
+
+/// Seconds of game time between samples for the post-game graphs
+pub const STATS_SAMPLE_INTERVAL: f32 = 10.0;
+
+/// Score for each veterancy promotion a player's units earn
+const PROMOTION_SCORE: f32 = 50.0;
+
+/// Running totals and graph samples for one player
+#[derive(Debug, Clone, Default)]
+pub struct PlayerStats {
+    pub units_produced: u32,
+    pub units_lost: u32,
+    pub units_killed: u32,
+    pub buildings_constructed: u32,
+    pub buildings_lost: u32,
+    pub buildings_razed: u32,
+    pub units_promoted: u32,
+    pub resources_gathered: f32,
+    pub resources_spent: f32,
+    pub value_destroyed: f32,       // Resource cost of the enemy units and buildings destroyed
+    pub resource_curve: Vec<f32>,   // Resources in the bank at each sample
+    pub army_value_curve: Vec<f32>, // Resource cost of living units at each sample
+}
+
+impl PlayerStats {
+    /// Score shown during play and used to break Time Limit games
+    pub fn score(&self) -> u32 {
+        (self.resources_gathered + self.resources_spent + self.value_destroyed * 2.0 + self.units_promoted as f32 * PROMOTION_SCORE) as u32
+    }
+}
+
+/// Statistics for every player in the current game
+#[derive(Resource, Debug, Clone, Default)]
+pub struct GameStats {
+    players: BTreeMap<u8, PlayerStats>,
+    sample_times: Vec<f32>, // Game time of each graph sample, in seconds
+}
+
+impl GameStats {
+    pub fn player(&self, player_id: u8) -> Option<&PlayerStats> {
+        self.players.get(&player_id)
+    }
+    
+    /// Every player with recorded stats, by player ID
+    pub fn players(&self) -> impl Iterator<Item = (u8, &PlayerStats)> {
+        self.players.iter().map(|(&player_id, stats)| (player_id, stats))
+    }
+    
+    pub fn sample_times(&self) -> &[f32] {
+        &self.sample_times
+    }
+    
+    pub fn scores(&self) -> BTreeMap<u8, u32> {
+        self.players().map(|(player_id, stats)| (player_id, stats.score())).collect()
+    }
+    
+    fn entry(&mut self, player_id: u8) -> &mut PlayerStats {
+        let samples = self.sample_times.len();
+        self.players.entry(player_id).or_insert_with(|| PlayerStats {
+            // Players first seen mid-game had nothing at the earlier samples
+            resource_curve: vec![0.0; samples],
+            army_value_curve: vec![0.0; samples],
+            ..PlayerStats::default()
+        })
+    }
+    
+    pub fn unit_produced(&mut self, player_id: u8) {
+        self.entry(player_id).units_produced += 1;
+    }
+    
+    /// A unit died; `killer` is the owner of whatever landed the last hit.
+    /// The neutral player isn't tracked, but killing its creeps counts for whoever did it.
+    pub fn unit_destroyed(&mut self, owner: u8, unit_type: UnitType, killer: Option<u8>) {
+        if owner != NEUTRAL_PLAYER {
+            self.entry(owner).units_lost += 1;
+        }
+        if let Some(killer) = killer.filter(|&killer| killer != owner && killer != NEUTRAL_PLAYER) {
+            let stats = self.entry(killer);
+            stats.units_killed += 1;
+            stats.value_destroyed += unit_value(unit_type);
+        }
+    }
+    
+    pub fn unit_promoted(&mut self, player_id: u8) {
+        self.entry(player_id).units_promoted += 1;
+    }
+    
+    pub fn building_constructed(&mut self, player_id: u8) {
+        self.entry(player_id).buildings_constructed += 1;
+    }
+    
+    /// A building was destroyed; `killer` is the owner of whatever landed the last hit
+    pub fn building_destroyed(&mut self, owner: u8, building_type: BuildingType, killer: Option<u8>) {
+        if owner != NEUTRAL_PLAYER {
+            self.entry(owner).buildings_lost += 1;
+        }
+        if let Some(killer) = killer.filter(|&killer| killer != owner && killer != NEUTRAL_PLAYER) {
+            let stats = self.entry(killer);
+            stats.buildings_razed += 1;
+            stats.value_destroyed += building_value(building_type);
+        }
+    }
+    
+    pub fn resources_gathered(&mut self, player_id: u8, amount: f32) {
+        self.entry(player_id).resources_gathered += amount;
+    }
+    
+    /// Resources paid for something; refunds are recorded as negative spending
+    pub fn resources_spent(&mut self, player_id: u8, amount: f32) {
+        self.entry(player_id).resources_spent += amount;
+    }
+    
+    /// Add a graph sample; `values` holds each player's (banked resources, army value)
+    pub fn record_sample(&mut self, time: f32, values: &BTreeMap<u8, (f32, f32)>) {
+        for &player_id in values.keys() {
+            self.entry(player_id);
+        }
+        
+        self.sample_times.push(time);
+        for (player_id, stats) in self.players.iter_mut() {
+            let (banked, army_value) = values.get(player_id).copied().unwrap_or((0.0, 0.0));
+            stats.resource_curve.push(banked);
+            stats.army_value_curve.push(army_value);
+        }
+    }
+}
+
+/// Resource cost of a unit type
+pub fn unit_value(unit_type: UnitType) -> f32 {
+    data::game_data().unit(unit_type).costs.values().sum()
+}
+
+/// Resource cost of a building type
+pub fn building_value(building_type: BuildingType) -> f32 {
+    data::game_data().building(building_type).costs.values().sum()
+}
+
+/// Resource cost of a destroyed unit or building
+pub fn casualty_value(casualty: Casualty) -> f32 {
+    match casualty {
+        Casualty::Unit(unit_type) => unit_value(unit_type),
+        Casualty::Building(building_type) => building_value(building_type),
+    }
+}
+
+/// System that counts losses and kills from death events
+pub fn kill_stats_system(mut death_events: EventReader<DeathEvent>, mut stats: ResMut<GameStats>) {
+    for death in death_events.iter() {
+        match death.casualty {
+            Casualty::Unit(unit_type) => stats.unit_destroyed(death.owner, unit_type, death.killer_owner),
+            Casualty::Building(building_type) => stats.building_destroyed(death.owner, building_type, death.killer_owner),
+        }
+    }
+}
+
+/// System that samples each player's bank and army value for the post-game graphs
+pub fn stats_sampling_system(
+    time: Res<GameTime>,
+    units: Query<(&Unit, &Owner)>,
+    player_resources: Res<PlayerResources>,
+    mut stats: ResMut<GameStats>,
+) {
+    let next_sample = stats.sample_times.len() as f32 * STATS_SAMPLE_INTERVAL;
+    if time.elapsed_time < next_sample {
+        return;
+    }
+    
+    let mut values: BTreeMap<u8, (f32, f32)> = BTreeMap::new();
+    for (&(player_id, _), &amount) in player_resources.resources.iter() {
+        values.entry(player_id).or_insert((0.0, 0.0)).0 += amount;
+    }
+    for (unit, owner) in units.iter() {
+        if unit.health > 0.0 && unit.unit_type != UnitType::Worker && owner.0 != NEUTRAL_PLAYER {
+            values.entry(owner.0).or_insert((0.0, 0.0)).1 += unit_value(unit.unit_type);
+        }
+    }
+    
+    stats.record_sample(next_sample, &values);
+}
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
 
 use crate::ecs::resources::TechType;
 use crate::ecs::components::ResourceType;
+use crate::game::data;
 
 /// Technology data structure containing properties for each technology
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TechData {
     pub tech_type: TechType,
     pub name: String,
@@ -16,7 +19,7 @@ pub struct TechData {
 }
 
 /// Effect of researching a technology
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TechEffect {
     UnitDamageMultiplier(f32),          // +X% to unit damage
     UnitHealthMultiplier(f32),          // +X% to unit health
@@ -34,157 +37,21 @@ pub enum TechEffect {
 }
 
 impl TechData {
-    /// Get technology data for a specific tech type
-    pub fn get(tech_type: TechType) -> Self {
-        match tech_type {
-            TechType::ImprovedHarvesting => Self::improved_harvesting(),
-            TechType::ImprovedWeapons => Self::improved_weapons(),
-            TechType::ImprovedArmor => Self::improved_armor(),
-            TechType::AdvancedUnits => Self::advanced_units(),
-            TechType::AdvancedBuildings => Self::advanced_buildings(),
-            TechType::ImprovedHealing => Self::improved_healing(),
-            TechType::ImprovedSpeed => Self::improved_speed(),
-        }
-    }
-    
-    /// Helper to create cost map
-    fn create_costs(mineral: f32, gas: f32) -> HashMap<ResourceType, f32> {
-        let mut costs = HashMap::new();
-        if mineral > 0.0 {
-            costs.insert(ResourceType::Mineral, mineral);
-        }
-        if gas > 0.0 {
-            costs.insert(ResourceType::Gas, gas);
-        }
-        costs
-    }
-    
-    /// Improved harvesting technology data
-    pub fn improved_harvesting() -> Self {
-        Self {
-            tech_type: TechType::ImprovedHarvesting,
-            name: "Improved Harvesting".to_string(),
-            description: "Increases resource gathering speed by 20%.".to_string(),
-            research_time: 60.0,
-            costs: Self::create_costs(100.0, 100.0),
-            icon_name: "tech_harvesting",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::ResourceGatheringMultiplier(1.2),
-            ],
-        }
-    }
-    
-    /// Improved weapons technology data
-    pub fn improved_weapons() -> Self {
-        Self {
-            tech_type: TechType::ImprovedWeapons,
-            name: "Improved Weapons".to_string(),
-            description: "Increases unit attack damage by 25%.".to_string(),
-            research_time: 80.0,
-            costs: Self::create_costs(150.0, 150.0),
-            icon_name: "tech_weapons",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::UnitDamageMultiplier(1.25),
-            ],
-        }
-    }
-    
-    /// Improved armor technology data
-    pub fn improved_armor() -> Self {
-        Self {
-            tech_type: TechType::ImprovedArmor,
-            name: "Improved Armor".to_string(),
-            description: "Increases unit and building health by 20%.".to_string(),
-            research_time: 70.0,
-            costs: Self::create_costs(125.0, 125.0),
-            icon_name: "tech_armor",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::UnitHealthMultiplier(1.2),
-                TechEffect::BuildingHealthMultiplier(1.2),
-            ],
-        }
-    }
-    
-    /// Advanced units technology data
-    pub fn advanced_units() -> Self {
-        Self {
-            tech_type: TechType::AdvancedUnits,
-            name: "Advanced Units".to_string(),
-            description: "Unlocks advanced combat units.".to_string(),
-            research_time: 120.0,
-            costs: Self::create_costs(200.0, 200.0),
-            icon_name: "tech_advanced_units",
-            prerequisites: vec![TechType::ImprovedWeapons],
-            effects: vec![
-                TechEffect::UnlockUnit(crate::ecs::components::UnitType::Tank),
-            ],
-        }
-    }
-    
-    /// Advanced buildings technology data
-    pub fn advanced_buildings() -> Self {
-        Self {
-            tech_type: TechType::AdvancedBuildings,
-            name: "Advanced Buildings".to_string(),
-            description: "Unlocks advanced structures.".to_string(),
-            research_time: 100.0,
-            costs: Self::create_costs(150.0, 200.0),
-            icon_name: "tech_advanced_buildings",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::UnlockBuilding(crate::ecs::components::BuildingType::DefenseTower),
-                TechEffect::BuildingHealthMultiplier(1.15),
-            ],
-        }
-    }
-    
-    /// Improved healing technology data
-    pub fn improved_healing() -> Self {
-        Self {
-            tech_type: TechType::ImprovedHealing,
-            name: "Improved Healing".to_string(),
-            description: "Increases healing effectiveness by 30%.".to_string(),
-            research_time: 60.0,
-            costs: Self::create_costs(100.0, 150.0),
-            icon_name: "tech_healing",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::UnitHealthMultiplier(1.1),
-                // In a real implementation, there would be a specific healing multiplier
-            ],
-        }
-    }
-    
-    /// Improved speed technology data
-    pub fn improved_speed() -> Self {
-        Self {
-            tech_type: TechType::ImprovedSpeed,
-            name: "Improved Speed".to_string(),
-            description: "Increases unit movement speed by 15%.".to_string(),
-            research_time: 70.0,
-            costs: Self::create_costs(125.0, 100.0),
-            icon_name: "tech_speed",
-            prerequisites: vec![],
-            effects: vec![
-                TechEffect::UnitSpeedMultiplier(1.15),
-                TechEffect::UnitAttackSpeedMultiplier(1.1),
-            ],
-        }
+    /// Get technology data for a specific tech type, as loaded from the data files
+    pub fn get(tech_type: TechType) -> &'static Self {
+        data::game_data().tech(tech_type)
     }
 }
 
 /// Get all technologies in a dependency tree order
-pub fn get_tech_tree() -> Vec<TechData> {
+pub fn get_tech_tree() -> Vec<&'static TechData> {
     let mut techs = Vec::new();
     let mut added = HashMap::new();
     
     // Helper function to add a tech and its prerequisites
     fn add_tech_with_prerequisites(
         tech_type: TechType,
-        techs: &mut Vec<TechData>,
+        techs: &mut Vec<&'static TechData>,
         added: &mut HashMap<TechType, bool>,
     ) {
         // Skip if already added
@@ -205,13 +72,9 @@ pub fn get_tech_tree() -> Vec<TechData> {
     }
     
     // Add all techs in the correct order
-    add_tech_with_prerequisites(TechType::ImprovedHarvesting, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::ImprovedWeapons, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::ImprovedArmor, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::AdvancedUnits, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::AdvancedBuildings, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::ImprovedHealing, &mut techs, &mut added);
-    add_tech_with_prerequisites(TechType::ImprovedSpeed, &mut techs, &mut added);
+    for tech_type in TechType::ALL {
+        add_tech_with_prerequisites(tech_type, &mut techs, &mut added);
+    }
     
     techs
 }
@@ -299,6 +162,41 @@ pub enum TechEffectType {
     ResearchTime,
 }
 
+/// Where a technology stands for a player, as shown in the tech tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TechStatus {
+    Locked,           // Prerequisites missing
+    Available,        // Can be researched now
+    InProgress(f32),  // Queued or being researched; progress 0.0 to 1.0
+    Researched,
+}
+
+/// Current status of a technology for a player
+pub fn tech_status(
+    tech_type: TechType,
+    tech_state: &crate::ecs::resources::TechState,
+    player_id: u8,
+) -> TechStatus {
+    if *tech_state.researched.get(&(player_id, tech_type)).unwrap_or(&false) {
+        TechStatus::Researched
+    } else if let Some(&progress) = tech_state.in_progress.get(&(player_id, tech_type)) {
+        TechStatus::InProgress(progress)
+    } else if is_tech_available(tech_type, tech_state, player_id) {
+        TechStatus::Available
+    } else {
+        TechStatus::Locked
+    }
+}
+
+/// Number of prerequisite steps below a technology (0 for techs with no prerequisites)
+pub fn tech_depth(tech_type: TechType) -> usize {
+    TechData::get(tech_type).prerequisites
+        .iter()
+        .map(|prereq| tech_depth(*prereq) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
 /// Check if a technology is available to research
 pub fn is_tech_available(
     tech_type: TechType,
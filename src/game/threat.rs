@@ -0,0 +1,281 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::ecs::components::{Building, NetworkId, Owner, Transform, Unit};
+use crate::ecs::resources::{GameMap, GameTime, PlayerInfo, TICK_RATE};
+use crate::ecs::systems::combat::WeaponData;
+use crate::game::data;
+use crate::game::map::is_position_visible;
+use crate::game::pathfinding::PATH_GRID_SIZE;
+
+/// Side of a threat map cell in world units
+pub const THREAT_CELL_SIZE: f32 = 32.0;
+
+/// Distance beyond its weapon's reach an enemy still threatens, for the ground it closes in a moment
+const THREAT_MARGIN: f32 = 32.0;
+
+/// Ticks between rebuilding the threat maps
+const THREAT_UPDATE_TICKS: u64 = TICK_RATE as u64 / 2;
+
+/// Route cost of a cell a known defensive building covers, on top of the one every cell costs
+const TOWER_COVER_COST: u32 = 50;
+
+/// Route cost per point of damage per second enemies bring to bear on a cell
+const THREAT_COST: f32 = 0.5;
+
+/// Cells a route search looks at before giving up and heading straight for the goal
+const MAX_ROUTE_CELLS: usize = 8192;
+
+/// An enemy an AI player knows of: one in sight, or a building seen before and not seen gone since
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KnownEnemy {
+    pub entity: Entity,
+    pub network_id: Option<NetworkId>, // Orders the map the same way on every machine
+    pub position: Vec2,
+    pub dps: f32,
+    pub range: f32,
+    pub health: f32,
+    pub speed: f32,       // 0 for buildings
+    pub is_defense: bool, // A finished building with a weapon
+}
+
+/// Influence map of the danger one AI player knows of, by cell
+#[derive(Debug, Clone, Default)]
+pub struct ThreatMap {
+    pub enemies: Vec<KnownEnemy>,
+    threat: HashMap<(i32, i32), f32>,  // Cell -> damage per second enemies can bring to bear there
+    tower_cover: HashSet<(i32, i32)>, // Cells in reach of a defensive building
+    cells: (i32, i32),                // Cells across and down the map
+}
+
+impl ThreatMap {
+    /// Spread the known enemies' damage over the cells they can reach
+    pub fn new(enemies: Vec<KnownEnemy>, map: &GameMap) -> Self {
+        let cells = (
+            (map.width as f32 * PATH_GRID_SIZE / THREAT_CELL_SIZE).ceil() as i32,
+            (map.height as f32 * PATH_GRID_SIZE / THREAT_CELL_SIZE).ceil() as i32,
+        );
+        let mut threat_map = Self { enemies: Vec::new(), threat: HashMap::new(), tower_cover: HashSet::new(), cells };
+        
+        for enemy in enemies.iter().filter(|enemy| enemy.dps > 0.0) {
+            let reach = enemy.range + THREAT_MARGIN;
+            let (min_x, min_y) = Self::cell(enemy.position - Vec2::splat(reach));
+            let (max_x, max_y) = Self::cell(enemy.position + Vec2::splat(reach));
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    if Self::cell_center((x, y)).distance(enemy.position) > reach {
+                        continue;
+                    }
+                    *threat_map.threat.entry((x, y)).or_insert(0.0) += enemy.dps;
+                    if enemy.is_defense {
+                        threat_map.tower_cover.insert((x, y));
+                    }
+                }
+            }
+        }
+        
+        threat_map.enemies = enemies;
+        threat_map
+    }
+    
+    fn cell(position: Vec2) -> (i32, i32) {
+        ((position.x / THREAT_CELL_SIZE).floor() as i32, (position.y / THREAT_CELL_SIZE).floor() as i32)
+    }
+    
+    fn cell_center(cell: (i32, i32)) -> Vec2 {
+        (Vec2::new(cell.0 as f32, cell.1 as f32) + 0.5) * THREAT_CELL_SIZE
+    }
+    
+    /// Damage per second known enemies can bring to bear at a position
+    pub fn threat_at(&self, position: Vec2) -> f32 {
+        self.threat.get(&Self::cell(position)).copied().unwrap_or(0.0)
+    }
+    
+    /// Whether a known defensive building can shoot at a position
+    pub fn in_tower_cover(&self, position: Vec2) -> bool {
+        self.tower_cover.contains(&Self::cell(position))
+    }
+    
+    /// Known enemies within `radius` of a position
+    pub fn enemies_near(&self, position: Vec2, radius: f32) -> impl Iterator<Item = &KnownEnemy> + '_ {
+        self.enemies.iter().filter(move |enemy| enemy.position.distance(position) <= radius)
+    }
+    
+    /// Combined strength of the known enemies within `radius` of a position
+    pub fn enemy_strength(&self, position: Vec2, radius: f32) -> f32 {
+        let (dps, health) = self
+            .enemies_near(position, radius)
+            .fold((0.0, 0.0), |(dps, health), enemy| (dps + enemy.dps, health + enemy.health));
+        combat_strength(dps, health)
+    }
+    
+    /// Waypoints from one position to another over the least dangerous ground, going around the
+    /// cover of known defensive buildings where there is a way round. Ends at `to`; just `to`
+    /// when no better route turns up.
+    pub fn safe_route(&self, from: Vec2, to: Vec2) -> Vec<Vec2> {
+        let (start, goal) = (Self::cell(from), Self::cell(to));
+        let in_bounds = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < self.cells.0 && y < self.cells.1;
+        if start == goal || !in_bounds(start) || !in_bounds(goal) {
+            return vec![to];
+        }
+        
+        let step_cost = |cell: (i32, i32)| {
+            let cover = if cell != goal && self.tower_cover.contains(&cell) { TOWER_COVER_COST } else { 0 };
+            1 + cover + (self.threat.get(&cell).copied().unwrap_or(0.0) * THREAT_COST) as u32
+        };
+        let estimate = |(x, y): (i32, i32)| ((x - goal.0).abs() + (y - goal.1).abs()) as u32;
+        
+        let mut cost = HashMap::from([(start, 0u32)]);
+        let mut previous: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+        let mut open = BinaryHeap::from([Reverse((estimate(start), start))]);
+        let mut searched = 0;
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if cell == goal {
+                break;
+            }
+            searched += 1;
+            if searched > MAX_ROUTE_CELLS {
+                return vec![to];
+            }
+            
+            let cell_cost = cost[&cell];
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let next = (cell.0 + dx, cell.1 + dy);
+                if !in_bounds(next) {
+                    continue;
+                }
+                let next_cost = cell_cost + step_cost(next);
+                if cost.get(&next).map_or(true, |&known| next_cost < known) {
+                    cost.insert(next, next_cost);
+                    previous.insert(next, cell);
+                    open.push(Reverse((next_cost + estimate(next), next)));
+                }
+            }
+        }
+        if !previous.contains_key(&goal) {
+            return vec![to];
+        }
+        
+        // Walk back from the goal, keeping only the cells where the route turns
+        let mut cells = vec![goal];
+        while let Some(&cell) = previous.get(cells.last().unwrap()) {
+            cells.push(cell);
+        }
+        cells.reverse();
+        let mut waypoints: Vec<Vec2> = cells
+            .windows(3)
+            .filter(|window| {
+                let before = (window[1].0 - window[0].0, window[1].1 - window[0].1);
+                let after = (window[2].0 - window[1].0, window[2].1 - window[1].1);
+                before != after
+            })
+            .map(|window| Self::cell_center(window[1]))
+            .collect();
+        waypoints.push(to);
+        waypoints
+    }
+}
+
+/// Strength of a force for weighing two sides against each other: the damage it deals times the
+/// damage it can take, square-rooted so doubling a force doubles its strength
+pub fn combat_strength(dps: f32, health: f32) -> f32 {
+    (dps * health).sqrt()
+}
+
+/// Damage per second and reach of a weapon, 0 for things without one
+fn weapon_stats(weapon: Option<&WeaponData>) -> (f32, f32) {
+    weapon.map_or((0.0, 0.0), |weapon| (weapon.damage / weapon.cooldown.max(0.1), weapon.range))
+}
+
+/// Threat map of every computer player, built only from what that player has seen
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ThreatMaps {
+    maps: HashMap<u8, ThreatMap>,
+}
+
+impl ThreatMaps {
+    pub fn get(&self, player_id: u8) -> Option<&ThreatMap> {
+        self.maps.get(&player_id)
+    }
+}
+
+/// System that rebuilds each computer player's threat map from the enemies in its sight, keeping
+/// buildings it has seen but lost sight of where it last saw them. Runs after fog of war.
+pub fn threat_map_system(
+    units: Query<(Entity, &Unit, &Transform, &Owner, Option<&NetworkId>)>,
+    buildings: Query<(Entity, &Building, &Transform, &Owner, Option<&NetworkId>)>,
+    game_map: Res<GameMap>,
+    player_info: Res<PlayerInfo>,
+    time: Res<GameTime>,
+    threat_maps: Option<ResMut<ThreatMaps>>,
+) {
+    let mut threat_maps = match threat_maps {
+        Some(threat_maps) => threat_maps,
+        None => return,
+    };
+    if time.current_tick % THREAT_UPDATE_TICKS.max(1) != 0 {
+        return;
+    }
+    
+    let mut ai_players: Vec<u8> = player_info.ai_players.iter().copied().collect();
+    ai_players.sort_unstable();
+    threat_maps.maps.retain(|player_id, _| ai_players.contains(player_id));
+    
+    for player_id in ai_players {
+        // Creeps count as enemies too
+        let is_enemy = |owner: u8| !player_info.are_allies(player_id, owner);
+        let visible = |position: Vec2| is_position_visible(&game_map, player_id, position, PATH_GRID_SIZE);
+        
+        let mut enemies = Vec::new();
+        for (entity, unit, transform, owner, network_id) in units.iter() {
+            if !is_enemy(owner.0) || unit.health <= 0.0 || !visible(transform.position) {
+                continue;
+            }
+            let (dps, range) = weapon_stats(data::game_data().unit(unit.unit_type).weapon.as_ref());
+            enemies.push(KnownEnemy {
+                entity,
+                network_id: network_id.copied(),
+                position: transform.position,
+                dps,
+                range,
+                health: unit.health,
+                speed: unit.movement_speed,
+                is_defense: false,
+            });
+        }
+        for (entity, building, transform, owner, network_id) in buildings.iter() {
+            if !is_enemy(owner.0) || building.health <= 0.0 || !visible(transform.position) {
+                continue;
+            }
+            // Unfinished buildings don't shoot yet
+            let (dps, range) = match building.construction_progress {
+                Some(_) => (0.0, 0.0),
+                None => weapon_stats(data::game_data().building(building.building_type).weapon.as_ref()),
+            };
+            enemies.push(KnownEnemy {
+                entity,
+                network_id: network_id.copied(),
+                position: transform.position,
+                dps,
+                range,
+                health: building.health,
+                speed: 0.0,
+                is_defense: dps > 0.0,
+            });
+        }
+        
+        // Buildings don't walk off, so one out of sight is still where it was until seen gone
+        let previous = threat_maps.maps.remove(&player_id).unwrap_or_default();
+        let remembered: Vec<KnownEnemy> = previous.enemies
+            .into_iter()
+            .filter(|enemy| enemy.speed == 0.0 && !visible(enemy.position))
+            .filter(|enemy| !enemies.iter().any(|known| known.entity == enemy.entity))
+            .collect();
+        enemies.extend(remembered);
+        enemies.sort_by_key(|enemy| enemy.network_id);
+        
+        threat_maps.maps.insert(player_id, ThreatMap::new(enemies, &game_map));
+    }
+}
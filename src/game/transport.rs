@@ -0,0 +1,175 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+
+use crate::ecs::components::{
+    AttackMove, AttackTarget, BoardTarget, BuildTarget, Cargo, CastOrder, Collider, Follow, FormationMove, HarvestTarget,
+    HealTarget, HoldPosition, IdleWorker, Movement, Owner, Passenger, Patrol, RepairTarget, Selected, Transform, Unit,
+    UnitType,
+};
+use crate::ecs::resources::{AudioEvents, GameMap, PlayerAlerts};
+use crate::ecs::systems::stop_movement;
+use crate::engine::audio::GameSoundType;
+use crate::game::commands::set_path;
+use crate::game::data;
+use crate::game::pathfinding::{self, PATH_GRID_SIZE};
+
+/// Gap between a unit's collider and its transport's at which the unit climbs aboard
+const BOARDING_DISTANCE: f32 = 6.0;
+
+/// Rings of pathfinding cells around a transport searched for somewhere to set a passenger down
+const UNLOAD_SEARCH_RINGS: i32 = 3;
+
+/// How many units a unit type carries; 0 for everything but transports
+pub fn cargo_capacity(unit_type: UnitType) -> u32 {
+    data::game_data().unit(unit_type).cargo_capacity
+}
+
+/// Whether a unit type can ride in a transport; transports can't carry each other
+pub fn can_board(unit_type: UnitType) -> bool {
+    cargo_capacity(unit_type) == 0
+}
+
+/// System that gives new transports their cargo hold and walks boarding units to their transport,
+/// taking them off the map once they reach it
+pub fn boarding_system(
+    mut commands: Commands,
+    new_units: Query<(Entity, &Unit), (Added<Unit>, Without<Cargo>)>,
+    mut boarders: Query<(Entity, &Transform, &Owner, &BoardTarget, Option<&Collider>, &mut Movement), Without<Cargo>>,
+    mut transports: Query<(&Unit, &Transform, &Owner, Option<&Collider>, &mut Cargo)>,
+    passengers: Query<(Entity, &Passenger)>,
+    game_map: Res<GameMap>,
+) {
+    for (entity, unit) in new_units.iter() {
+        let capacity = cargo_capacity(unit.unit_type);
+        if capacity > 0 {
+            commands.entity(entity).insert(Cargo { capacity, passengers: Vec::new() });
+        }
+    }
+    
+    // Passengers go down with a transport that disappears without dying, e.g. a summon running out
+    for (entity, passenger) in passengers.iter() {
+        if transports.get(passenger.transport).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+    
+    for (entity, transform, owner, board, collider, mut movement) in boarders.iter_mut() {
+        let (transport_position, reach) = match transports.get_mut(board.target_entity) {
+            Ok((unit, transport_transform, transport_owner, transport_collider, cargo))
+                if unit.health > 0.0 && transport_owner.0 == owner.0 &&
+                    (cargo.passengers.len() as u32) < cargo.capacity =>
+            {
+                let reach = collider.map_or(0.0, |c| c.radius) + transport_collider.map_or(0.0, |c| c.radius);
+                (transport_transform.position, reach + BOARDING_DISTANCE)
+            }
+            _ => {
+                // Gone, lost or full
+                commands.entity(entity).remove::<BoardTarget>();
+                stop_movement(&mut movement);
+                continue;
+            }
+        };
+        
+        if (transport_position - transform.position).length() <= reach {
+            if let Ok((_, _, _, _, mut cargo)) = transports.get_mut(board.target_entity) {
+                cargo.passengers.push(entity);
+            }
+            stop_movement(&mut movement);
+            commands.entity(entity).remove::<(
+                Transform,
+                BoardTarget,
+                Selected,
+                IdleWorker,
+                AttackTarget,
+                AttackMove,
+                Patrol,
+                HealTarget,
+                BuildTarget,
+                HarvestTarget,
+                FormationMove,
+                HoldPosition,
+                CastOrder,
+                RepairTarget,
+                Follow,
+            )>();
+            commands.entity(entity).insert(Passenger { transport: board.target_entity });
+            continue;
+        }
+        
+        // Re-path only once the transport has moved away from where we were heading
+        let heading_near = movement.target.map_or(false, |target| (target - transport_position).length() <= reach);
+        if !heading_near || movement.path_index >= movement.path.len() {
+            if let Some(grid) = game_map.grid_for(owner.0) {
+                let unit_radius = collider.map(|c| c.radius).unwrap_or(0.0);
+                set_path(&mut movement, transform.position, transport_position, grid, unit_radius);
+            }
+        }
+    }
+}
+
+/// Set a transport's passengers down on free cells around it: every passenger, or only the one in `slot`.
+/// Passengers that don't fit stay aboard.
+pub fn unload(world: &mut World, transport: Entity, slot: Option<usize>) {
+    let (position, owner) = match (world.get::<Transform>(transport), world.get::<Owner>(transport)) {
+        (Some(transform), Some(owner)) => (transform.position, owner.0),
+        _ => return,
+    };
+    let passengers: Vec<Entity> = match world.get::<Cargo>(transport) {
+        Some(cargo) => match slot {
+            Some(slot) => cargo.passengers.get(slot).copied().into_iter().collect(),
+            None => cargo.passengers.clone(),
+        },
+        None => return,
+    };
+    if passengers.is_empty() {
+        return;
+    }
+    
+    let spots = unload_spots(world.get_resource::<GameMap>(), owner, position, passengers.len());
+    if spots.len() < passengers.len() {
+        world.get_resource_or_insert_with(PlayerAlerts::default).alerts.push((
+            owner,
+            "There is no room to unload here".to_string(),
+        ));
+    }
+    
+    for (passenger, spot) in passengers.iter().zip(spots) {
+        let mut entity = world.entity_mut(*passenger);
+        entity.remove::<Passenger>();
+        entity.insert(Transform {
+            position: spot,
+            rotation: 0.0,
+            scale: Vec2::new(1.0, 1.0),
+        });
+        if let Some(mut cargo) = world.get_mut::<Cargo>(transport) {
+            cargo.passengers.retain(|entity| entity != passenger);
+        }
+    }
+    world.get_resource_or_insert_with(AudioEvents::default).play_for(owner, GameSoundType::UnitMove, position);
+}
+
+/// Centers of up to `count` cells around a position the player's units can walk on, nearest ring first
+pub fn unload_spots(game_map: Option<&GameMap>, player_id: u8, position: Vec2, count: usize) -> Vec<Vec2> {
+    let grid = game_map.and_then(|map| map.grid_for(player_id));
+    let center = pathfinding::world_to_grid(position, PATH_GRID_SIZE);
+    
+    let mut spots = Vec::new();
+    for ring in 1..=UNLOAD_SEARCH_RINGS {
+        for dy in -ring..=ring {
+            for dx in -ring..=ring {
+                if dx.abs() != ring && dy.abs() != ring {
+                    continue;
+                }
+                
+                let spot = pathfinding::grid_to_world((center.0 + dx, center.1 + dy), PATH_GRID_SIZE);
+                if grid.map_or(true, |grid| pathfinding::is_position_walkable(spot, grid, PATH_GRID_SIZE)) {
+                    spots.push(spot);
+                    if spots.len() == count {
+                        return spots;
+                    }
+                }
+            }
+        }
+    }
+    spots
+}
@@ -1,13 +1,16 @@
 use bevy_ecs::prelude::*;
 use glam::Vec2;
+use std::collections::HashMap;
 
 use crate::ecs::components::{
     Unit, UnitType, Owner, Transform, Collider, 
-    Movement, MinimapMarker, MinimapShape
+    Movement, MinimapMarker, MinimapShape, Building
 };
-use crate::ecs::resources::{TechState, TechEffectType, PlayerResources};
-use crate::game::tech::{TechData, apply_tech_effect};
+use crate::ecs::resources::{TechState, TechType, PlayerResources, NEUTRAL_PLAYER};
+use crate::game::tech::{TechData, TechEffectType, apply_tech_effect};
 use crate::game::buildings::BuildingData;
+use crate::game::data;
+use crate::ui::minimap::NEUTRAL_COLOR;
 use crate::ecs::components::{BuildingType, ResourceType};
 
 /// Unit spawn parameters
@@ -23,13 +26,7 @@ pub fn calculate_training_time(
     tech_state: &TechState, 
     player_id: u8
 ) -> f32 {
-    let base_times = match unit_type {
-        UnitType::Worker => 15.0,
-        UnitType::Soldier => 25.0,
-        UnitType::Scout => 20.0,
-        UnitType::Tank => 40.0,
-        UnitType::Healer => 30.0,
-    };
+    let base_times = data::game_data().unit(unit_type).train_time;
 
     // Apply tech effects to reduce training time
     apply_tech_effect(
@@ -40,6 +37,45 @@ pub fn calculate_training_time(
     )
 }
 
+/// Resources needed to train a unit
+pub fn unit_costs(unit_type: UnitType) -> HashMap<ResourceType, f32> {
+    data::game_data().unit(unit_type).costs.clone()
+}
+
+/// Highest supply limit a player can reach, however many supply buildings they own
+pub const MAX_SUPPLY: u32 = 200;
+
+/// Supply a unit takes up while alive or queued for training
+pub fn unit_supply_cost(unit_type: UnitType) -> u32 {
+    data::game_data().unit(unit_type).supply
+}
+
+/// Current (used, max) supply for a player.
+/// Used supply counts living units plus units queued in production; max supply comes from finished buildings.
+pub fn player_supply(world: &mut World, player_id: u8) -> (u32, u32) {
+    let mut used = 0;
+    let mut unit_query = world.query::<(&Unit, &Owner)>();
+    for (unit, owner) in unit_query.iter(world) {
+        if owner.0 == player_id && unit.health > 0.0 {
+            used += unit_supply_cost(unit.unit_type);
+        }
+    }
+    
+    let mut max = 0;
+    let mut building_query = world.query::<(&Building, &Owner)>();
+    for (building, owner) in building_query.iter(world) {
+        if owner.0 != player_id {
+            continue;
+        }
+        used += building.production_queue.iter().map(|&unit_type| unit_supply_cost(unit_type)).sum::<u32>();
+        if building.construction_progress.is_none() {
+            max += BuildingData::get(building.building_type).provides_supply;
+        }
+    }
+    
+    (used, max.min(MAX_SUPPLY))
+}
+
 /// Check if a unit can be trained
 pub fn can_train_unit(
     unit_type: UnitType,
@@ -47,37 +83,7 @@ pub fn can_train_unit(
     player_id: u8,
     tech_state: &TechState,
 ) -> bool {
-    let costs = match unit_type {
-        UnitType::Worker => {
-            let mut costs = HashMap::new();
-            costs.insert(ResourceType::Mineral, 50.0);
-            costs
-        },
-        UnitType::Soldier => {
-            let mut costs = HashMap::new();
-            costs.insert(ResourceType::Mineral, 75.0);
-            costs.insert(ResourceType::Energy, 10.0);
-            costs
-        },
-        UnitType::Scout => {
-            let mut costs = HashMap::new();
-            costs.insert(ResourceType::Mineral, 60.0);
-            costs.insert(ResourceType::Energy, 5.0);
-            costs
-        },
-        UnitType::Tank => {
-            let mut costs = HashMap::new();
-            costs.insert(ResourceType::Mineral, 150.0);
-            costs.insert(ResourceType::Gas, 50.0);
-            costs
-        },
-        UnitType::Healer => {
-            let mut costs = HashMap::new();
-            costs.insert(ResourceType::Mineral, 100.0);
-            costs.insert(ResourceType::Energy, 25.0);
-            costs
-        },
-    };
+    let costs = unit_costs(unit_type);
 
     // Check if player has enough resources
     for (&resource_type, &cost) in &costs {
@@ -92,21 +98,20 @@ pub fn can_train_unit(
     }
 
     // Check tech requirements
+    unit_tech_requirements(unit_type).iter().all(|&tech_type| {
+        tech_state.researched
+            .get(&(player_id, tech_type))
+            .copied()
+            .unwrap_or(false)
+    })
+}
+
+/// Technologies a player must have researched to train a unit type
+pub fn unit_tech_requirements(unit_type: UnitType) -> &'static [TechType] {
     match unit_type {
-        UnitType::Tank => {
-            let tech_researched = tech_state.researched
-                .get(&(player_id, TechType::AdvancedUnits))
-                .copied()
-                .unwrap_or(false);
-            
-            if !tech_researched {
-                return false;
-            }
-        },
-        _ => {}
+        UnitType::Tank => &[TechType::AdvancedUnits],
+        _ => &[],
     }
-
-    true
 }
 
 /// Check if a worker can build a specific building
@@ -123,10 +128,9 @@ pub fn can_build_building(
 
     // Get building costs
     let building_data = BuildingData::get(building_type);
-    let costs = building_data.costs;
 
     // Check resource availability
-    for (&resource_type, &cost) in &costs {
+    for (&resource_type, &cost) in &building_data.costs {
         let current = player_resources.resources
             .get(&(player_id, resource_type))
             .copied()
@@ -196,11 +200,7 @@ pub fn spawn_unit(
             velocity: Vec2::ZERO,
         },
         Collider {
-            radius: match params.unit_type {
-                UnitType::Tank => 8.0,
-                UnitType::Worker | UnitType::Healer => 4.0,
-                _ => 5.0,
-            },
+            radius: data::game_data().unit(params.unit_type).collider_radius,
             collision_layer: 1, // Unit layer
             collision_mask: 1 | 2, // Collide with units and buildings
         },
@@ -210,11 +210,13 @@ pub fn spawn_unit(
                 1 => [255, 0, 0, 255],   // Red
                 2 => [0, 255, 0, 255],   // Green
                 3 => [255, 255, 0, 255], // Yellow
+                NEUTRAL_PLAYER => NEUTRAL_COLOR,
                 _ => [255, 255, 255, 255], // White
             },
             shape: match params.unit_type {
                 UnitType::Worker => MinimapShape::Circle,
                 UnitType::Tank => MinimapShape::Square,
+                UnitType::Transport => MinimapShape::Diamond,
                 _ => MinimapShape::Triangle,
             },
         },
@@ -230,49 +232,15 @@ fn calculate_unit_stats(
     tech_state: &TechState, 
     player_id: u8
 ) -> (f32, f32, f32, f32, f32, f32) {
-    let (base_health, base_damage, base_range, base_attack_speed, base_movement, base_sight) = 
-        match unit_type {
-            UnitType::Worker => (
-                30.0,   // Health
-                3.0,    // Attack damage
-                10.0,   // Attack range
-                1.0,    // Attack speed  
-                80.0,   // Movement speed
-                100.0,  // Sight range
-            ),
-            UnitType::Soldier => (
-                60.0,   // Health
-                10.0,   // Attack damage
-                50.0,   // Attack range
-                0.8,    // Attack speed
-                60.0,   // Movement speed
-                120.0,  // Sight range  
-            ),
-            UnitType::Scout => (
-                40.0,   // Health
-                6.0,    // Attack damage
-                40.0,   // Attack range
-                0.5,    // Attack speed
-                120.0,  // Movement speed
-                150.0,  // Sight range
-            ),
-            UnitType::Tank => (
-                120.0,  // Health
-                30.0,   // Attack damage
-                70.0,   // Attack range
-                2.0,    // Attack speed
-                40.0,   // Movement speed
-                100.0,  // Sight range
-            ),
-            UnitType::Healer => (
-                40.0,   // Health
-                0.0,    // Attack damage
-                60.0,   // Heal range
-                1.0,    // Heal speed
-                50.0,   // Movement speed
-                120.0,  // Sight range
-            ),
-        };
+    let unit_data = data::game_data().unit(unit_type);
+    let (base_health, base_damage, base_range, base_attack_speed, base_movement, base_sight) = (
+        unit_data.health,
+        unit_data.attack_damage,
+        unit_data.attack_range,  // Heal range for healers
+        unit_data.attack_speed,  // Heal speed for healers
+        unit_data.movement_speed,
+        unit_data.sight_range,
+    );
 
     // Apply tech multipliers
     let health = apply_tech_effect(
@@ -0,0 +1,230 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ecs::components::{Owner, ResourceType, Transform, UnitType};
+use crate::ecs::network_id::entities_of;
+use crate::ecs::resources::{GameMap, PlayerInfo, PlayerResources, TechType};
+use crate::engine::input::{AbilityCommand, Command};
+use crate::game::buildings::BuildingData;
+use crate::game::commands::entity_at;
+use crate::game::map;
+use crate::game::pathfinding::PATH_GRID_SIZE;
+use crate::game::tech::TechData;
+use crate::game::units::unit_costs;
+
+/// Why a player's command was thrown out instead of being applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Names a unit or building the player doesn't own
+    NotOwned(Entity),
+    /// Aims at an enemy hidden from the player by the fog of war
+    Hidden(Entity),
+    /// A position off the map, or not a number at all
+    OffMap(Vec2),
+    /// Costs more than the player has
+    Unaffordable,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::NotOwned(entity) => write!(f, "{:?} belongs to someone else", entity),
+            Violation::Hidden(entity) => write!(f, "{:?} is hidden by the fog of war", entity),
+            Violation::OffMap(position) => write!(f, "{} is off the map", position),
+            Violation::Unaffordable => write!(f, "not enough resources"),
+        }
+    }
+}
+
+/// Check a command against the world before applying it for a player. Every machine checks the
+/// same commands against the same world, so they all throw out the same ones
+pub fn validate_command(world: &World, player_id: u8, command: &Command) -> Result<(), Violation> {
+    match command {
+        Command::SelectUnits(ids) => entities_of(world, ids).into_iter().try_for_each(|entity| check_owned(world, player_id, entity)),
+        Command::RepairBuilding(building) => entities_of(world, &[*building]).into_iter().try_for_each(|entity| check_owned(world, player_id, entity)),
+        Command::SmartOrder(position) => {
+            check_on_map(world, *position)?;
+            check_order_target(world, player_id, *position)
+        }
+        // Attack-moving aims at a position; units only pick out the enemies they see on the way
+        Command::Move(position) |
+        Command::Attack(position) |
+        Command::Gather(position) |
+        Command::SetRallyPoint(position) => check_on_map(world, *position),
+        Command::Patrol(from, to) | Command::BuildWall { start: from, end: to } => {
+            check_on_map(world, *from)?;
+            check_on_map(world, *to)
+        }
+        Command::BuildBuilding { building_type, position } => {
+            check_on_map(world, *position)?;
+            check_affordable(world, player_id, &BuildingData::get(*building_type).costs)
+        }
+        // Unknown types are turned down when the command is applied
+        Command::Train(unit_command) => match UnitType::from_u8(unit_command.unit_type) {
+            Some(unit_type) => check_affordable(world, player_id, &unit_costs(unit_type)),
+            None => Ok(()),
+        },
+        Command::Research(tech) => match TechType::from_u8(*tech) {
+            Some(tech_type) => check_affordable(world, player_id, &TechData::get(tech_type).costs),
+            None => Ok(()),
+        },
+        Command::UseAbility(ability) => check_ability_target(world, player_id, ability),
+        _ => Ok(()),
+    }
+}
+
+/// IDs of entities that no longer exist are let through before this; the command skips them when applied
+fn check_owned(world: &World, player_id: u8, entity: Entity) -> Result<(), Violation> {
+    match world.get::<Owner>(entity) {
+        Some(owner) if owner.0 == player_id => Ok(()),
+        _ => Err(Violation::NotOwned(entity)),
+    }
+}
+
+fn check_on_map(world: &World, position: Vec2) -> Result<(), Violation> {
+    let on_map = position.is_finite() && world.get_resource::<GameMap>().map_or(true, |game_map| {
+        position.x >= 0.0 &&
+        position.y >= 0.0 &&
+        position.x <= game_map.width as f32 * PATH_GRID_SIZE &&
+        position.y <= game_map.height as f32 * PATH_GRID_SIZE
+    });
+    if on_map { Ok(()) } else { Err(Violation::OffMap(position)) }
+}
+
+fn check_affordable(world: &World, player_id: u8, costs: &HashMap<ResourceType, f32>) -> Result<(), Violation> {
+    let player_resources = match world.get_resource::<PlayerResources>() {
+        Some(player_resources) => player_resources,
+        None => return Ok(()),
+    };
+    let affordable = costs.iter().all(|(res_type, cost)| {
+        player_resources.resources.get(&(player_id, *res_type)).copied().unwrap_or(0.0) >= *cost
+    });
+    if affordable { Ok(()) } else { Err(Violation::Unaffordable) }
+}
+
+/// An ability aimed at a particular enemy needs that enemy in sight; a position is enough otherwise
+fn check_ability_target(world: &World, player_id: u8, ability: &AbilityCommand) -> Result<(), Violation> {
+    if let Some(position) = ability.target_position {
+        check_on_map(world, position)?;
+    }
+    
+    match ability.target_entity_id.and_then(|network_id| entities_of(world, &[network_id]).pop()) {
+        Some(target) => check_target_visible(world, player_id, target),
+        None => Ok(()),
+    }
+}
+
+/// A right-click is carried out on the unit or building under it, which an enemy has to be in sight to be
+fn check_order_target(world: &World, player_id: u8, position: Vec2) -> Result<(), Violation> {
+    match entity_at(world, player_id, position) {
+        Some(target) => check_target_visible(world, player_id, target),
+        None => Ok(()),
+    }
+}
+
+/// Allies can always be aimed at; an enemy only while the fog of war doesn't hide it
+fn check_target_visible(world: &World, player_id: u8, target: Entity) -> Result<(), Violation> {
+    let (owner, position) = match (world.get::<Owner>(target), world.get::<Transform>(target)) {
+        (Some(owner), Some(transform)) => (owner.0, transform.position),
+        _ => return Ok(()),
+    };
+    let allied = world.get_resource::<PlayerInfo>().map_or(owner == player_id, |info| info.are_allies(player_id, owner));
+    let visible = world
+        .get_resource::<GameMap>()
+        .map_or(true, |game_map| map::is_position_visible(game_map, player_id, position, PATH_GRID_SIZE));
+    if allied || visible { Ok(()) } else { Err(Violation::Hidden(target)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    
+    use crate::ecs::network_id::{assign_network_ids, network_ids};
+    use crate::ecs::spatial::SpatialGrid;
+    use crate::engine::input::UnitCommand;
+    
+    const ENEMY_POSITION: Vec2 = Vec2::new(200.0, 200.0);
+    
+    /// Player 0 facing one enemy of player 1's, which player 0 sees only when `enemy_visible`
+    fn world_with_enemy(enemy_visible: bool) -> (World, Entity) {
+        let mut world = World::new();
+        let mut game_map = GameMap::default();
+        let mut visible_tiles = HashSet::new();
+        if enemy_visible {
+            visible_tiles.insert(map::fog_tile_index(&game_map, ENEMY_POSITION, PATH_GRID_SIZE));
+        }
+        game_map.fog_of_war.insert(0, visible_tiles);
+        world.insert_resource(game_map);
+        world.insert_resource(PlayerInfo::default());
+        
+        let enemy = world.spawn((
+            Owner(1),
+            Transform {
+                position: ENEMY_POSITION,
+                rotation: 0.0,
+                scale: Vec2::ONE,
+            },
+        )).id();
+        let mut spatial_grid = SpatialGrid::default();
+        spatial_grid.insert(enemy, ENEMY_POSITION, 5.0);
+        world.insert_resource(spatial_grid);
+        (world, enemy)
+    }
+    
+    fn ability_at(world: &mut World, target: Entity) -> Command {
+        assign_network_ids(world);
+        Command::UseAbility(AbilityCommand {
+            ability_id: 0,
+            target_position: None,
+            target_entity_id: network_ids(world, &[target]).pop(),
+        })
+    }
+    
+    #[test]
+    fn ability_on_a_hidden_enemy_is_rejected() {
+        let (mut world, enemy) = world_with_enemy(false);
+        let command = ability_at(&mut world, enemy);
+        assert_eq!(validate_command(&world, 0, &command), Err(Violation::Hidden(enemy)));
+    }
+    
+    #[test]
+    fn ability_on_a_visible_enemy_is_accepted() {
+        let (mut world, enemy) = world_with_enemy(true);
+        let command = ability_at(&mut world, enemy);
+        assert_eq!(validate_command(&world, 0, &command), Ok(()));
+    }
+    
+    #[test]
+    fn smart_order_on_a_hidden_enemy_never_targets_it() {
+        let (world, enemy) = world_with_enemy(false);
+        assert_eq!(entity_at(&world, 0, ENEMY_POSITION), None);
+        assert_eq!(validate_command(&world, 0, &Command::SmartOrder(ENEMY_POSITION)), Ok(()));
+        assert_eq!(check_target_visible(&world, 0, enemy), Err(Violation::Hidden(enemy)));
+    }
+    
+    #[test]
+    fn smart_order_on_a_visible_enemy_is_accepted() {
+        let (world, enemy) = world_with_enemy(true);
+        assert_eq!(entity_at(&world, 0, ENEMY_POSITION), Some(enemy));
+        assert_eq!(validate_command(&world, 0, &Command::SmartOrder(ENEMY_POSITION)), Ok(()));
+    }
+    
+    #[test]
+    fn orders_off_the_map_are_rejected() {
+        let (world, _) = world_with_enemy(true);
+        let position = Vec2::new(-10.0, 50.0);
+        assert_eq!(validate_command(&world, 0, &Command::Attack(position)), Err(Violation::OffMap(position)));
+        assert_eq!(validate_command(&world, 0, &Command::SmartOrder(position)), Err(Violation::OffMap(position)));
+    }
+    
+    #[test]
+    fn training_without_resources_is_unaffordable() {
+        let (mut world, _) = world_with_enemy(true);
+        world.insert_resource(PlayerResources::default());
+        let command = Command::Train(UnitCommand { unit_type: UnitType::Soldier as u8 });
+        assert_eq!(validate_command(&world, 0, &command), Err(Violation::Unaffordable));
+    }
+}
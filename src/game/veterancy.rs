@@ -0,0 +1,101 @@
+use bevy_ecs::prelude::*;
+
+use crate::ecs::components::{Experience, Owner, Unit};
+use crate::ecs::events::DeathEvent;
+use crate::ecs::resources::GameTime;
+use crate::game::stats::{casualty_value, GameStats};
+
+/// Experience earned per resource the destroyed unit or building cost
+pub const EXPERIENCE_PER_RESOURCE: f32 = 1.0;
+
+/// A veterancy rank and the bonuses a unit gets once promoted to it
+#[derive(Debug, Clone, Copy)]
+pub struct VeterancyRank {
+    pub name: &'static str,
+    pub experience: f32,   // Experience needed for the promotion
+    pub damage_bonus: f32, // Fraction added to weapon damage
+    pub health_bonus: f32, // Fraction added to maximum health
+    pub regeneration: f32, // Health restored per second
+}
+
+/// Ranks in promotion order; every unit starts at the first
+pub const RANKS: [VeterancyRank; 4] = [
+    VeterancyRank { name: "Recruit", experience: 0.0, damage_bonus: 0.0, health_bonus: 0.0, regeneration: 0.0 },
+    VeterancyRank { name: "Veteran", experience: 100.0, damage_bonus: 0.1, health_bonus: 0.1, regeneration: 0.5 },
+    VeterancyRank { name: "Elite", experience: 300.0, damage_bonus: 0.2, health_bonus: 0.2, regeneration: 1.0 },
+    VeterancyRank { name: "Heroic", experience: 600.0, damage_bonus: 0.35, health_bonus: 0.3, regeneration: 2.0 },
+];
+
+pub fn rank(rank: u8) -> &'static VeterancyRank {
+    &RANKS[(rank as usize).min(RANKS.len() - 1)]
+}
+
+/// Highest rank the given experience is enough for
+pub fn rank_for(experience: f32) -> u8 {
+    RANKS.iter().rposition(|rank| experience >= rank.experience).unwrap_or(0) as u8
+}
+
+/// Weapon damage multiplier for a unit with the given experience, if any
+pub fn damage_multiplier(experience: Option<&Experience>) -> f32 {
+    1.0 + experience.map_or(0.0, |experience| rank(experience.rank).damage_bonus)
+}
+
+/// Queue experience for the entity that landed a killing blow worth `value` resources
+pub fn award_kill(commands: &mut Commands, killer: Entity, value: f32) {
+    let points = value * EXPERIENCE_PER_RESOURCE;
+    commands.add(move |world: &mut World| award_experience(world, killer, points));
+}
+
+/// Give a unit experience; buildings and entities that have since died get nothing
+pub fn award_experience(world: &mut World, entity: Entity, points: f32) {
+    if world.get::<Unit>(entity).map_or(true, |unit| unit.health <= 0.0) {
+        return;
+    }
+    
+    match world.get_mut::<Experience>(entity) {
+        Some(mut experience) => experience.points += points,
+        None => {
+            world.entity_mut(entity).insert(Experience { points, rank: 0 });
+        }
+    }
+}
+
+/// System that credits killers with experience for the units and buildings they destroyed
+pub fn kill_experience_system(mut commands: Commands, mut death_events: EventReader<DeathEvent>) {
+    for death in death_events.iter() {
+        award_kill(&mut commands, death.killer, casualty_value(death.casualty));
+    }
+}
+
+/// System that promotes units with enough experience and regenerates veterans' health
+pub fn veterancy_system(
+    mut units: Query<(&mut Unit, &mut Experience, &Owner)>,
+    time: Res<GameTime>,
+    mut stats: ResMut<GameStats>,
+) {
+    for (mut unit, mut experience, owner) in units.iter_mut() {
+        if unit.health <= 0.0 {
+            continue;
+        }
+        
+        let new_rank = rank_for(experience.points);
+        if new_rank > experience.rank {
+            // Scale maximum health from the unpromoted value and heal by the difference
+            let old_bonus = rank(experience.rank).health_bonus;
+            let base_health = unit.max_health / (1.0 + old_bonus);
+            let max_health = base_health * (1.0 + rank(new_rank).health_bonus);
+            unit.health += max_health - unit.max_health;
+            unit.max_health = max_health;
+            
+            for _ in experience.rank..new_rank {
+                stats.unit_promoted(owner.0);
+            }
+            experience.rank = new_rank;
+        }
+        
+        let regeneration = rank(experience.rank).regeneration;
+        if regeneration > 0.0 && unit.health < unit.max_health {
+            unit.health = (unit.health + regeneration * time.delta_time).min(unit.max_health);
+        }
+    }
+}
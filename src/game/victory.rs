@@ -0,0 +1,310 @@
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ecs::components::{Building, Owner};
+use crate::ecs::resources::{GameMap, GameTime, PlayerInfo, NEUTRAL_PLAYER};
+use crate::ecs::spatial::SpatialGrid;
+use crate::game::stats::GameStats;
+
+/// Length of a Time Limit game
+pub const TIME_LIMIT_MINUTES: f32 = 30.0;
+
+/// Resource points a team must hold at once to win a Resource Control game
+pub const CONTROL_POINTS_REQUIRED: usize = 5;
+
+/// How long the points must be held without a break
+pub const CONTROL_HOLD_MINUTES: f32 = 3.0;
+
+/// A team holds a resource point while it has units or buildings this close and nobody else does
+pub const CONTROL_RADIUS: f32 = 96.0;
+
+/// Victory condition picked in the game setup
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum VictoryConditionType {
+    /// Last team with a headquarters standing wins
+    Annihilation,
+    /// Highest scoring team wins when the clock runs out
+    TimeLimit { minutes: f32 },
+    /// First team to hold `points` resource points for `minutes` wins
+    ResourceControl { points: usize, minutes: f32 },
+    /// Only a scenario's triggers end the game
+    Scripted,
+}
+
+impl Default for VictoryConditionType {
+    fn default() -> Self {
+        VictoryConditionType::Annihilation
+    }
+}
+
+impl VictoryConditionType {
+    /// Conditions in the order the game setup dropdown lists them
+    pub const SETUP_OPTIONS: [VictoryConditionType; 3] = [
+        VictoryConditionType::Annihilation,
+        VictoryConditionType::TimeLimit { minutes: TIME_LIMIT_MINUTES },
+        VictoryConditionType::ResourceControl { points: CONTROL_POINTS_REQUIRED, minutes: CONTROL_HOLD_MINUTES },
+    ];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            VictoryConditionType::Annihilation => "Annihilation",
+            VictoryConditionType::TimeLimit { .. } => "Time Limit",
+            VictoryConditionType::ResourceControl { .. } => "Resource Control",
+            VictoryConditionType::Scripted => "Scripted",
+        }
+    }
+    
+    /// Fresh evaluator for this condition
+    pub fn create(self) -> Box<dyn VictoryCondition> {
+        match self {
+            VictoryConditionType::Annihilation => Box::new(Annihilation),
+            VictoryConditionType::TimeLimit { minutes } => Box::new(TimeLimit::new(minutes)),
+            VictoryConditionType::ResourceControl { points, minutes } => Box::new(ResourceControl::new(points, minutes)),
+            VictoryConditionType::Scripted => Box::new(Scripted),
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VictoryOutcome {
+    /// `player_id` is the lowest numbered surviving player of the winning team
+    Winner { team: u8, player_id: u8 },
+    Draw,
+}
+
+/// Decides when a game is over and who won; evaluated once per simulation tick
+pub trait VictoryCondition: Send + Sync {
+    fn condition_type(&self) -> VictoryConditionType;
+    
+    /// Look at the world after this tick's systems ran; `Some` ends the game
+    fn evaluate(&mut self, world: &mut World) -> Option<VictoryOutcome>;
+}
+
+/// The active victory condition and, once decided, the result
+#[derive(Resource)]
+pub struct VictoryState {
+    condition: Box<dyn VictoryCondition>,
+    outcome: Option<VictoryOutcome>,
+}
+
+impl VictoryState {
+    pub fn new(condition_type: VictoryConditionType) -> Self {
+        Self {
+            condition: condition_type.create(),
+            outcome: None,
+        }
+    }
+    
+    pub fn condition_type(&self) -> VictoryConditionType {
+        self.condition.condition_type()
+    }
+    
+    pub fn outcome(&self) -> Option<VictoryOutcome> {
+        self.outcome
+    }
+    
+    /// End the game with this outcome unless it is already decided
+    pub fn decide(&mut self, outcome: VictoryOutcome) {
+        self.outcome.get_or_insert(outcome);
+    }
+}
+
+/// Last team with a headquarters standing wins; the other conditions end this way too
+pub struct Annihilation;
+
+impl VictoryCondition for Annihilation {
+    fn condition_type(&self) -> VictoryConditionType {
+        VictoryConditionType::Annihilation
+    }
+    
+    fn evaluate(&mut self, world: &mut World) -> Option<VictoryOutcome> {
+        last_team_standing(world)
+    }
+}
+
+/// Never ends the game itself; scenario triggers decide the outcome instead
+pub struct Scripted;
+
+impl VictoryCondition for Scripted {
+    fn condition_type(&self) -> VictoryConditionType {
+        VictoryConditionType::Scripted
+    }
+    
+    fn evaluate(&mut self, _world: &mut World) -> Option<VictoryOutcome> {
+        None
+    }
+}
+
+/// Highest score wins when the clock runs out
+pub struct TimeLimit {
+    seconds: f32,
+}
+
+impl TimeLimit {
+    pub fn new(minutes: f32) -> Self {
+        Self {
+            seconds: minutes * 60.0,
+        }
+    }
+}
+
+impl VictoryCondition for TimeLimit {
+    fn condition_type(&self) -> VictoryConditionType {
+        VictoryConditionType::TimeLimit { minutes: self.seconds / 60.0 }
+    }
+    
+    fn evaluate(&mut self, world: &mut World) -> Option<VictoryOutcome> {
+        if let Some(outcome) = last_team_standing(world) {
+            return Some(outcome);
+        }
+        if world.resource::<GameTime>().elapsed_time < self.seconds {
+            return None;
+        }
+        
+        // Team scores, keeping the lowest numbered player of each team to name the winner
+        let scores = world.get_resource::<GameStats>().map(|stats| stats.scores()).unwrap_or_default();
+        let mut team_scores: BTreeMap<u8, (u32, u8)> = BTreeMap::new();
+        for (&player_id, &score) in &scores {
+            let entry = team_scores.entry(team_of(world, player_id)).or_insert((0, player_id));
+            entry.0 += score;
+            entry.1 = entry.1.min(player_id);
+        }
+        
+        let best = team_scores.values().map(|(score, _)| *score).max()?;
+        let mut leaders = team_scores.iter().filter(|(_, (score, _))| *score == best);
+        match (leaders.next(), leaders.next()) {
+            (Some((&team, &(_, player_id))), None) => Some(VictoryOutcome::Winner { team, player_id }),
+            _ => Some(VictoryOutcome::Draw),
+        }
+    }
+}
+
+/// First team to hold enough resource points for long enough wins
+pub struct ResourceControl {
+    points_required: usize,
+    hold_seconds: f32,
+    held_since: BTreeMap<u8, f32>, // Team -> elapsed time it started holding enough points
+}
+
+impl ResourceControl {
+    pub fn new(points_required: usize, minutes: f32) -> Self {
+        Self {
+            points_required,
+            hold_seconds: minutes * 60.0,
+            held_since: BTreeMap::new(),
+        }
+    }
+    
+    /// Number of resource points each team currently holds
+    fn points_held(world: &World) -> BTreeMap<u8, usize> {
+        let mut held = BTreeMap::new();
+        let (map, spatial_grid) = match (world.get_resource::<GameMap>(), world.get_resource::<SpatialGrid>()) {
+            (Some(map), Some(spatial_grid)) => (map, spatial_grid),
+            _ => return held,
+        };
+        
+        for (position, _, _) in &map.resource_positions {
+            if let Some(team) = controlling_team(world, spatial_grid, *position) {
+                *held.entry(team).or_insert(0) += 1;
+            }
+        }
+        held
+    }
+}
+
+impl VictoryCondition for ResourceControl {
+    fn condition_type(&self) -> VictoryConditionType {
+        VictoryConditionType::ResourceControl {
+            points: self.points_required,
+            minutes: self.hold_seconds / 60.0,
+        }
+    }
+    
+    fn evaluate(&mut self, world: &mut World) -> Option<VictoryOutcome> {
+        if let Some(outcome) = last_team_standing(world) {
+            return Some(outcome);
+        }
+        
+        let now = world.resource::<GameTime>().elapsed_time;
+        let held = Self::points_held(world);
+        
+        // Dropping below the required count restarts a team's clock
+        let required = self.points_required;
+        self.held_since.retain(|team, _| held.get(team).map_or(false, |&count| count >= required));
+        for (&team, &count) in &held {
+            if count >= required {
+                self.held_since.entry(team).or_insert(now);
+            }
+        }
+        
+        let (&team, _) = self.held_since.iter().find(|(_, &since)| now - since >= self.hold_seconds)?;
+        let player_id = living_players(world)
+            .into_iter()
+            .filter(|&player_id| team_of(world, player_id) == team)
+            .min()
+            .unwrap_or(team);
+        Some(VictoryOutcome::Winner { team, player_id })
+    }
+}
+
+/// System that evaluates the active victory condition and records the result once the game is decided
+pub fn victory_system(world: &mut World) {
+    if !world.contains_resource::<VictoryState>() {
+        return;
+    }
+    
+    world.resource_scope(|world, mut victory: Mut<VictoryState>| {
+        if victory.outcome.is_none() {
+            victory.outcome = victory.condition.evaluate(world);
+        }
+    });
+}
+
+/// Team a player is on, per the player info
+fn team_of(world: &World, player_id: u8) -> u8 {
+    world.get_resource::<PlayerInfo>().map_or(player_id, |info| info.team_of(player_id))
+}
+
+/// Players who still have at least one living headquarters
+fn living_players(world: &mut World) -> BTreeSet<u8> {
+    let mut query = world.query::<(&Building, &Owner)>();
+    query
+        .iter(world)
+        .filter(|(building, _)| building.building_type.is_headquarters() && building.health > 0.0)
+        .map(|(_, owner)| owner.0)
+        .collect()
+}
+
+/// The winner once only one team has a headquarters left, or a draw when none does
+fn last_team_standing(world: &mut World) -> Option<VictoryOutcome> {
+    let mut teams: BTreeMap<u8, u8> = BTreeMap::new(); // Team -> lowest surviving player
+    for player_id in living_players(world) {
+        teams.entry(team_of(world, player_id)).or_insert(player_id);
+    }
+    
+    match teams.len() {
+        0 => Some(VictoryOutcome::Draw),
+        1 => teams.into_iter().next().map(|(team, player_id)| VictoryOutcome::Winner { team, player_id }),
+        _ => None,
+    }
+}
+
+/// Team holding a resource point at `position`, if exactly one team has anything near it.
+/// Creeps guarding a point keep anyone from holding it but never hold it themselves.
+fn controlling_team(world: &World, spatial_grid: &SpatialGrid, position: Vec2) -> Option<u8> {
+    let mut teams = BTreeSet::new();
+    for nearby in spatial_grid.query_radius(position, CONTROL_RADIUS) {
+        if let Some(owner) = world.get::<Owner>(nearby.entity) {
+            teams.insert(team_of(world, owner.0));
+        }
+    }
+    
+    if teams.len() == 1 {
+        teams.into_iter().next().filter(|&team| team != NEUTRAL_PLAYER)
+    } else {
+        None
+    }
+}
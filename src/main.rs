@@ -5,7 +5,8 @@ mod networking;
 mod ui;
 
 use anyhow::Result;
-use log::{info, error, warn};
+use rand::Rng;
+use tracing::{info, error, warn};
 use winit::event_loop::EventLoop;
 use glam::Vec2;
 use std::collections::HashMap;
@@ -13,12 +14,10 @@ use std::collections::HashSet;
 
 use crate::ecs::components::*;
 use crate::ecs::resources::*;
+use crate::ecs::systems::combat::DamageTable;
 use crate::game::{GamePhase, GameState};
 // TODO: Implement autobattler menu factory
 
-const TICK_RATE: f64 = 20.0; // 20 ticks per second
-const MS_PER_TICK: f64 = 1000.0 / TICK_RATE;
-
 /// Sophisticated army composition strategy
 struct ArmyCompositionStrategy {
     soldier_ratio: f32,
@@ -50,7 +49,7 @@ impl ArmyCompositionStrategy {
             }
         }
     }
-
+    
     fn suggest_next_unit(&mut self, current_units: &[UnitType]) -> UnitType {
         let counts = current_units.iter().fold(
             [0, 0, 0, 0], 
@@ -64,7 +63,7 @@ impl ArmyCompositionStrategy {
                 acc
             }
         );
-
+        
         let total_units = current_units.len() as f32;
         let ratios = [
             counts[0] as f32 / total_units,
@@ -72,14 +71,14 @@ impl ArmyCompositionStrategy {
             counts[2] as f32 / total_units,
             counts[3] as f32 / total_units,
         ];
-
+        
         let target_ratios = [
             self.soldier_ratio,
             self.scout_ratio, 
             self.tank_ratio, 
             self.healer_ratio
         ];
-
+        
         // Find unit type furthest from its target ratio
         target_ratios.iter()
             .zip(ratios.iter())
@@ -113,6 +112,8 @@ fn initialize_autobattler(
             .unwrap_or_default()
             .as_secs(),
         player_count: 2,
+        preset: game::map::MapPreset::Continental,
+        symmetry: game::map::MapSymmetry::None,
         water_threshold: 0.2,
         mountain_threshold: 0.8,
         forest_threshold: 0.5,
@@ -120,8 +121,14 @@ fn initialize_autobattler(
     };
     
     let game_map = game::map::generate_map(&map_params);
+    world.insert_resource(game_map);
+    game::map::spawn_resource_nodes(world);
     world.insert_resource(DamageTable::default());
-
+    
+    // Starting armies are placed from the game seed so every machine builds the same world
+    let mut spawn_rng = game::simulation::tick_rng(map_params.seed, 0, game::simulation::RNG_STREAM_SPAWN);
+    world.resource_mut::<GameTime>().seed = map_params.seed;
+    
     // Player starting positions
     let start_positions = [
         Vec2::new(50.0, 50.0),   // Player 0
@@ -153,16 +160,16 @@ fn initialize_autobattler(
                 collision_mask: 1 | 2, // Collide with units and buildings
             },
         ));
-
+        
         // Initial army generation using strategy
         let mut player_units = Vec::new();
         for _ in 0..10 {
             let unit_type = army_strategy.suggest_next_unit(&player_units);
             player_units.push(unit_type);
-
+            
             let offset = Vec2::new(
-                rand::thread_rng().gen_range(-20.0..20.0),
-                rand::thread_rng().gen_range(-20.0..20.0)
+                spawn_rng.gen_range(-20.0..20.0),
+                spawn_rng.gen_range(-20.0..20.0)
             );
             
             game::units::spawn_unit(
@@ -176,7 +183,16 @@ fn initialize_autobattler(
             );
         }
     }
-
+    
+    // Player 1 is a computer opponent
+    game_state.settings.ai_slots = vec![game::ai::AiSlotConfig {
+        player_id: 1,
+        difficulty: game::ai::AiDifficulty::Medium,
+        personality: game::ai::AiPersonality::Balanced,
+        team: None,
+        resource_bonus: 0.0,
+    }];
+    
     // Configure game state
     game_state.start_game(
         false,  // Single player
@@ -186,27 +202,96 @@ fn initialize_autobattler(
 }
 
 fn main() -> Result<()> {
-    // Initialize logging
-    env_logger::init();
+    // Log to stderr and logs/, filtered per subsystem by RUST_LOG, e.g. `RUST_LOG=info,net=debug`
+    engine::logging::init();
     info!("Starting Rusty Autobattler");
-
+    
+    // A crash leaves a log and the last autosave behind instead of just a message on stderr
+    engine::crash::install_panic_hook();
+    
+    // `--dedicated [--port <port>]` hosts a lobby without a window, e.g. on a server;
+    // `--tcp` plays multiplayer over TCP for networks that drop UDP;
+    // `--upnp` asks the router to forward the port when hosting, so players outside the LAN can join
+    let args: Vec<String> = std::env::args().collect();
+    let transport = if args.iter().any(|arg| arg == "--tcp") {
+        networking::TransportKind::Tcp
+    } else {
+        networking::TransportKind::Udp
+    };
+    let upnp = args.iter().any(|arg| arg == "--upnp");
+    if args.iter().any(|arg| arg == "--dedicated") {
+        let port = match args.iter().position(|arg| arg == "--port").and_then(|index| args.get(index + 1)) {
+            Some(port) => port.parse()?,
+            None => networking::lockstep::DEFAULT_PORT,
+        };
+        let mut host = engine::headless::HeadlessHost::new(port, transport, upnp)?;
+        return host.run();
+    }
+    
+    // `--stress [--units <n>] [--enemies <n>] [--map-size <cells>] [--ticks <n>] [--seed <n>]` runs a battle
+    // with no window and reports how long ticks and the slowest systems took, to catch performance regressions
+    if args.iter().any(|arg| arg == "--stress") {
+        let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|index| args.get(index + 1));
+        let mut params = engine::stress::StressParams::default();
+        if let Some(units) = value("--units") {
+            params.units = units.parse()?;
+        }
+        if let Some(enemies) = value("--enemies") {
+            params.enemies = enemies.parse()?;
+        }
+        if let Some(map_size) = value("--map-size") {
+            params.map_size = map_size.parse()?;
+        }
+        if let Some(ticks) = value("--ticks") {
+            params.ticks = ticks.parse()?;
+        }
+        if let Some(seed) = value("--seed") {
+            params.seed = seed.parse()?;
+        }
+        engine::stress::run(&params)?.print();
+        return Ok(());
+    }
+    
     // Create game window
     let (mut engine, event_loop) = engine::Engine::new("Rusty Autobattler", 1024, 768).await?;
     
     // Load game assets
     engine.load_assets()?;
-
+    
+    engine.game_state.settings.network_transport = transport;
+    engine.game_state.settings.upnp = upnp;
+    
     // Create army composition strategy
     let mut army_strategy = ArmyCompositionStrategy::new(0.5); // Medium complexity
-
-    // Initialize autobattler game state
-    initialize_autobattler(&mut engine.world, &mut engine.game_state, &mut army_strategy);
-
+    
+    // `--debug` makes the developer console available in singleplayer
+    if args.iter().any(|arg| arg == "--debug") {
+        engine.enable_debug_console();
+    }
+    
+    // `--scenario <file>` plays a scenario, such as assets/scenarios/tutorial.ron, instead of the autobattler;
+    // `--mission <number>` plays a campaign mission the player has reached
+    let mission = args.iter()
+        .position(|arg| arg == "--mission")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|number| number.parse::<usize>().ok())
+        .filter(|&number| number > 0);
+    if let Some(path) = args.iter().position(|arg| arg == "--scenario").and_then(|index| args.get(index + 1)) {
+        engine.start_scenario(path)?;
+    } else if let Some(number) = mission {
+        engine.start_campaign_mission(number - 1)?;
+    } else {
+        // Initialize autobattler game state
+        initialize_autobattler(&mut engine.world, &mut engine.game_state, &mut army_strategy);
+        engine.start_ai_players();
+        engine.start_victory_condition();
+    }
+    
     // Optional: Add simple networking for potential multiplayer
     if let Err(e) = engine.enable_networking(true, None) {
         warn!("Failed to enable networking: {}", e);
     }
-
+    
     // Run the game
     engine.run(event_loop)
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// How long bytes are counted for before the figures shown are updated
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Network traffic over the last second, for the performance overlay
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BandwidthStats {
+    pub sent: u32,     // Bytes per second, to every peer together
+    pub received: u32, // Bytes per second
+    pub commands: u32, // Bytes per second of our own command batches, before they were sent to each peer
+    pub unbatched_commands: u32, // What those commands would have taken serialized one by one
+}
+
+impl BandwidthStats {
+    /// Share of command bytes batching saved, 0 to 1
+    pub fn batching_savings(&self) -> f32 {
+        if self.unbatched_commands == 0 {
+            return 0.0;
+        }
+        1.0 - self.commands as f32 / self.unbatched_commands as f32
+    }
+}
+
+/// Counts bytes as they go out and come in. Sending only borrows the network, so the counts are cells
+#[derive(Debug)]
+pub struct BandwidthMeter {
+    counting: Cell<BandwidthStats>, // Since the current interval began
+    latest: BandwidthStats,         // Over the last full interval, scaled to a second
+    interval_start: Instant,
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self {
+            counting: Cell::new(BandwidthStats::default()),
+            latest: BandwidthStats::default(),
+            interval_start: Instant::now(),
+        }
+    }
+}
+
+impl BandwidthMeter {
+    pub fn record_sent(&self, bytes: usize) {
+        let mut stats = self.counting.get();
+        stats.sent = stats.sent.saturating_add(bytes as u32);
+        self.counting.set(stats);
+    }
+    
+    pub fn record_received(&self, bytes: usize) {
+        let mut stats = self.counting.get();
+        stats.received = stats.received.saturating_add(bytes as u32);
+        self.counting.set(stats);
+    }
+    
+    /// A batch of our commands, with the size the commands would have had serialized one by one
+    pub fn record_commands(&self, batched: usize, unbatched: usize) {
+        let mut stats = self.counting.get();
+        stats.commands = stats.commands.saturating_add(batched as u32);
+        stats.unbatched_commands = stats.unbatched_commands.saturating_add(unbatched as u32);
+        self.counting.set(stats);
+    }
+    
+    /// Start a new interval once the current one is over
+    pub fn update(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.interval_start);
+        if elapsed < MEASURE_INTERVAL {
+            return;
+        }
+        
+        let counted = self.counting.take();
+        let per_second = |bytes: u32| (bytes as f64 / elapsed.as_secs_f64()).round() as u32;
+        self.latest = BandwidthStats {
+            sent: per_second(counted.sent),
+            received: per_second(counted.received),
+            commands: per_second(counted.commands),
+            unbatched_commands: per_second(counted.unbatched_commands),
+        };
+        self.interval_start = now;
+    }
+    
+    pub fn stats(&self) -> BandwidthStats {
+        self.latest
+    }
+}
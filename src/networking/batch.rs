@@ -0,0 +1,256 @@
+use anyhow::Result;
+use bincode::Options;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::ecs::components::NetworkId;
+use crate::engine::input::Command;
+use crate::networking::framing::MAX_MESSAGE_SIZE;
+
+// Kinds of entry in a batch
+const ENTRY_COMMAND: u8 = 0;      // A command on its own
+const ENTRY_SELECTION: u8 = 1;    // Units selected, with no order following
+const ENTRY_ORDER: u8 = 2;        // Units selected and the order given to them
+const ENTRY_REPEAT_ORDER: u8 = 3; // Units selected and given the same order as the entry before
+
+/// Bincode with variable-length integers, so enum tags and small numbers take a byte instead of four or eight
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_MESSAGE_SIZE as u64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Unit IDs as their count, then each one's difference from the one before, zigzagged so a step back
+/// costs as little as a step forward. IDs handed out together are close, so most take a byte
+fn write_ids(out: &mut Vec<u8>, ids: &[NetworkId]) {
+    write_varint(out, ids.len() as u64);
+    let mut previous = 0i64;
+    for id in ids {
+        let delta = id.0 as i64 - previous;
+        write_varint(out, ((delta << 1) ^ (delta >> 63)) as u64);
+        previous = id.0 as i64;
+    }
+}
+
+/// One player's commands for a tick, packed for sending. A selection followed by an order, as computer
+/// players and scripts give them, becomes one entry; squads sent to the same place share the order
+pub fn encode_batch(commands: &[Command]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut previous_order: Option<&Command> = None;
+    let mut index = 0;
+    while index < commands.len() {
+        match (&commands[index], commands.get(index + 1)) {
+            (Command::SelectUnits(ids), Some(order)) if !matches!(order, Command::SelectUnits(_)) => {
+                if previous_order.map_or(false, |previous| same_order(previous, order)) {
+                    out.push(ENTRY_REPEAT_ORDER);
+                    write_ids(&mut out, ids);
+                } else {
+                    out.push(ENTRY_ORDER);
+                    write_ids(&mut out, ids);
+                    out.extend(bincode_options().serialize(order)?);
+                }
+                previous_order = Some(order);
+                index += 2;
+            }
+            (Command::SelectUnits(ids), _) => {
+                out.push(ENTRY_SELECTION);
+                write_ids(&mut out, ids);
+                index += 1;
+            }
+            (command, _) => {
+                out.push(ENTRY_COMMAND);
+                out.extend(bincode_options().serialize(command)?);
+                index += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Whether two orders encode the same; `Command` has no `PartialEq`
+fn same_order(a: &Command, b: &Command) -> bool {
+    match (bincode_options().serialize(a), bincode_options().serialize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Reads a batch front to back
+struct BatchReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BatchReader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let (&byte, rest) = self.data.split_first().ok_or_else(|| anyhow::anyhow!("Command batch cut short"))?;
+        self.data = rest;
+        Ok(byte)
+    }
+    
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow::anyhow!("Varint in command batch is too long"))
+    }
+    
+    fn ids(&mut self) -> Result<Vec<NetworkId>> {
+        // Every ID takes at least a byte, so a count past what is left can't be right
+        let count = self.varint()? as usize;
+        if count > self.data.len() {
+            return Err(anyhow::anyhow!("Command batch claims {} unit IDs in {} bytes", count, self.data.len()));
+        }
+        
+        let mut ids = Vec::with_capacity(count);
+        let mut previous = 0i64;
+        for _ in 0..count {
+            let zigzag = self.varint()?;
+            let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+            previous = previous.checked_add(delta).ok_or_else(|| anyhow::anyhow!("Unit ID out of range in command batch"))?;
+            ids.push(NetworkId(u32::try_from(previous).map_err(|_| anyhow::anyhow!("Unit ID out of range in command batch"))?));
+        }
+        Ok(ids)
+    }
+    
+    fn command(&mut self) -> Result<Command> {
+        Ok(bincode_options().deserialize_from(&mut self.data)?)
+    }
+}
+
+/// Undo `encode_batch`
+pub fn decode_batch(data: &[u8]) -> Result<Vec<Command>> {
+    if data.len() > MAX_MESSAGE_SIZE {
+        return Err(anyhow::anyhow!("Command batch of {} bytes is over the {} byte limit", data.len(), MAX_MESSAGE_SIZE));
+    }
+    let mut reader = BatchReader { data };
+    let mut commands = Vec::new();
+    let mut previous_order: Option<Command> = None;
+    while !reader.data.is_empty() {
+        match reader.byte()? {
+            ENTRY_COMMAND => commands.push(reader.command()?),
+            ENTRY_SELECTION => commands.push(Command::SelectUnits(reader.ids()?)),
+            ENTRY_ORDER => {
+                commands.push(Command::SelectUnits(reader.ids()?));
+                let order = reader.command()?;
+                commands.push(order.clone());
+                previous_order = Some(order);
+            }
+            ENTRY_REPEAT_ORDER => {
+                let ids = reader.ids()?;
+                let order = previous_order.clone().ok_or_else(|| anyhow::anyhow!("Command batch repeats an order before giving one"))?;
+                commands.push(Command::SelectUnits(ids));
+                commands.push(order);
+            }
+            entry => return Err(anyhow::anyhow!("Unknown command batch entry {}", entry)),
+        }
+    }
+    Ok(commands)
+}
+
+/// For `#[serde(with = "batch")]`: a list of commands sent as an encoded batch
+pub fn serialize<S: Serializer>(commands: &[Command], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let batch = encode_batch(commands).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_bytes(&batch)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<Command>, D::Error> {
+    let batch = Vec::<u8>::deserialize(deserializer)?;
+    decode_batch(&batch).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+    
+    use crate::engine::input::UnitCommand;
+    
+    fn ids(ids: &[u32]) -> Vec<NetworkId> {
+        ids.iter().copied().map(NetworkId).collect()
+    }
+    
+    /// `Command` has no `PartialEq`, so batches are compared by how each command serializes
+    fn assert_round_trips(commands: &[Command]) -> Vec<u8> {
+        let batch = encode_batch(commands).unwrap();
+        let decoded = decode_batch(&batch).unwrap();
+        assert_eq!(decoded.len(), commands.len());
+        for (decoded, command) in decoded.iter().zip(commands) {
+            assert!(same_order(decoded, command), "{:?} came back as {:?}", command, decoded);
+        }
+        batch
+    }
+    
+    #[test]
+    fn commands_on_their_own_round_trip() {
+        let batch = assert_round_trips(&[Command::Stop, Command::Train(UnitCommand { unit_type: 2 }), Command::Move(Vec2::new(12.5, 40.0))]);
+        assert_eq!(batch[0], ENTRY_COMMAND);
+    }
+    
+    #[test]
+    fn selections_round_trip() {
+        let batch = assert_round_trips(&[Command::SelectUnits(ids(&[7, 3, 900, 901])), Command::SelectUnits(ids(&[u32::MAX, 0]))]);
+        assert_eq!(batch[0], ENTRY_SELECTION);
+    }
+    
+    #[test]
+    fn orders_round_trip() {
+        let batch = assert_round_trips(&[Command::SelectUnits(ids(&[10, 11, 12])), Command::Attack(Vec2::new(300.0, 120.0))]);
+        assert_eq!(batch[0], ENTRY_ORDER);
+    }
+    
+    #[test]
+    fn repeated_orders_round_trip_once_encoded() {
+        let target = Vec2::new(64.0, 64.0);
+        let commands = [
+            Command::SelectUnits(ids(&[1, 2])),
+            Command::Attack(target),
+            Command::SelectUnits(ids(&[5, 6])),
+            Command::Attack(target),
+        ];
+        let batch = assert_round_trips(&commands);
+        
+        let single = encode_batch(&commands[..2]).unwrap();
+        assert_eq!(batch[single.len()], ENTRY_REPEAT_ORDER);
+    }
+    
+    #[test]
+    fn truncated_batch_is_rejected() {
+        let batch = encode_batch(&[Command::SelectUnits(ids(&[10, 200, 3000])), Command::Move(Vec2::new(1.0, 2.0))]).unwrap();
+        for length in 1..batch.len() {
+            assert!(decode_batch(&batch[..length]).is_err(), "batch cut to {} bytes was accepted", length);
+        }
+    }
+    
+    #[test]
+    fn oversized_batch_is_rejected() {
+        let batch = vec![ENTRY_COMMAND; MAX_MESSAGE_SIZE + 1];
+        assert!(decode_batch(&batch).is_err());
+    }
+    
+    #[test]
+    fn id_count_past_the_data_is_rejected() {
+        let mut batch = vec![ENTRY_SELECTION];
+        write_varint(&mut batch, 1_000_000);
+        assert!(decode_batch(&batch).is_err());
+    }
+    
+    #[test]
+    fn overflowing_id_deltas_are_rejected() {
+        let mut batch = vec![ENTRY_SELECTION];
+        write_varint(&mut batch, 2);
+        write_varint(&mut batch, u32::MAX as u64 * 2); // Zigzagged, the highest ID there is
+        write_varint(&mut batch, u64::MAX - 1);        // Then a step of i64::MAX past it
+        assert!(decode_batch(&batch).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How long a reliable packet waits for its ack before it is sent again
+pub const RESEND_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Sends of one reliable packet before the peer is given up on
+pub const MAX_SEND_ATTEMPTS: u32 = 25;
+
+/// How far past the next delivery a packet may arrive and still be held; later ones are dropped
+/// unacknowledged, to be resent once the gap before them has filled
+pub const RECEIVE_WINDOW: u32 = 1024;
+
+/// How a message travels between peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Resent until acknowledged and delivered in the order sent, for the lobby, chat and commands
+    ReliableOrdered,
+    /// Sent once and possibly lost, for pings and anything else that is stale by the time it'd be resent
+    Unreliable,
+}
+
+/// What goes over the wire for each datagram of a channelled UDP transport
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Packet {
+    Unreliable(Vec<u8>),
+    Reliable { sequence: u32, payload: Vec<u8> },
+    Ack { sequence: u32 },
+}
+
+/// A reliable packet still waiting for its ack
+#[derive(Debug, Clone)]
+struct Unacked {
+    payload: Vec<u8>,
+    last_sent: Instant,
+    attempts: u32,
+}
+
+/// Reliable-ordered state kept for one peer: what we sent that it hasn't acknowledged, and what
+/// it sent that arrived ahead of a packet still missing
+#[derive(Debug, Default)]
+pub struct ReliableChannel {
+    next_sequence: u32,
+    unacked: BTreeMap<u32, Unacked>,
+    next_delivery: u32,            // Sequence of the next payload to hand on
+    early: BTreeMap<u32, Vec<u8>>, // Arrived out of order, by sequence
+}
+
+impl ReliableChannel {
+    /// Number the payload and keep it until the peer acknowledges it
+    pub fn send(&mut self, payload: &[u8], now: Instant) -> Packet {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.unacked.insert(sequence, Unacked { payload: payload.to_vec(), last_sent: now, attempts: 1 });
+        Packet::Reliable { sequence, payload: payload.to_vec() }
+    }
+    
+    /// The peer got a packet; stop resending it
+    pub fn acknowledge(&mut self, sequence: u32) {
+        self.unacked.remove(&sequence);
+    }
+    
+    /// Take in a reliable packet from the peer and return every payload now deliverable in order.
+    /// Duplicates of packets already delivered give nothing, though they still need acking.
+    /// None for packets beyond RECEIVE_WINDOW, which must not be acked so the peer resends them.
+    pub fn receive(&mut self, sequence: u32, payload: Vec<u8>) -> Option<Vec<Vec<u8>>> {
+        // Sequences behind the next delivery were delivered already; the distance wraps with them
+        let distance = sequence.wrapping_sub(self.next_delivery);
+        if distance >= u32::MAX / 2 {
+            return Some(Vec::new());
+        }
+        if distance >= RECEIVE_WINDOW {
+            return None;
+        }
+        self.early.insert(sequence, payload);
+        
+        let mut delivered = Vec::new();
+        while let Some(payload) = self.early.remove(&self.next_delivery) {
+            delivered.push(payload);
+            self.next_delivery = self.next_delivery.wrapping_add(1);
+        }
+        Some(delivered)
+    }
+    
+    /// Packets whose ack is overdue, to send again. Err once one has gone unacknowledged through
+    /// MAX_SEND_ATTEMPTS sends, when the peer is presumed gone.
+    pub fn due_resends(&mut self, now: Instant) -> Result<Vec<Packet>, u32> {
+        let mut resends = Vec::new();
+        for (&sequence, unacked) in self.unacked.iter_mut() {
+            if now.duration_since(unacked.last_sent) < RESEND_INTERVAL {
+                continue;
+            }
+            if unacked.attempts >= MAX_SEND_ATTEMPTS {
+                return Err(sequence);
+            }
+            unacked.last_sent = now;
+            unacked.attempts += 1;
+            resends.push(Packet::Reliable { sequence, payload: unacked.payload.clone() });
+        }
+        Ok(resends)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn out_of_order_packets_are_delivered_in_order() {
+        let mut channel = ReliableChannel::default();
+        assert_eq!(channel.receive(2, vec![2]), Some(Vec::new()));
+        assert_eq!(channel.receive(1, vec![1]), Some(Vec::new()));
+        assert_eq!(channel.receive(0, vec![0]), Some(vec![vec![0], vec![1], vec![2]]));
+        assert_eq!(channel.receive(3, vec![3]), Some(vec![vec![3]]));
+    }
+    
+    #[test]
+    fn duplicates_are_delivered_once() {
+        let mut channel = ReliableChannel::default();
+        assert_eq!(channel.receive(0, vec![0]), Some(vec![vec![0]]));
+        assert_eq!(channel.receive(0, vec![0]), Some(Vec::new()));
+        
+        // A repeat of a packet still waiting on an earlier one is held only once
+        assert_eq!(channel.receive(2, vec![2]), Some(Vec::new()));
+        assert_eq!(channel.receive(2, vec![2]), Some(Vec::new()));
+        assert_eq!(channel.receive(1, vec![1]), Some(vec![vec![1], vec![2]]));
+    }
+    
+    #[test]
+    fn packets_beyond_the_window_are_dropped() {
+        let mut channel = ReliableChannel::default();
+        assert_eq!(channel.receive(RECEIVE_WINDOW, vec![0]), None);
+        assert_eq!(channel.receive(RECEIVE_WINDOW - 1, vec![1]), Some(Vec::new()));
+        assert_eq!(channel.early.len(), 1);
+    }
+    
+    #[test]
+    fn delivery_continues_across_sequence_wraparound() {
+        let mut channel = ReliableChannel { next_delivery: u32::MAX, ..Default::default() };
+        assert_eq!(channel.receive(0, vec![1]), Some(Vec::new()));
+        assert_eq!(channel.receive(u32::MAX, vec![0]), Some(vec![vec![0], vec![1]]));
+        assert_eq!(channel.receive(u32::MAX, vec![0]), Some(Vec::new()));
+    }
+    
+    #[test]
+    fn unacked_packets_are_resent_until_acknowledged() {
+        let mut channel = ReliableChannel::default();
+        let start = Instant::now();
+        channel.send(&[7], start);
+        
+        assert!(channel.due_resends(start).unwrap().is_empty());
+        let resends = channel.due_resends(start + RESEND_INTERVAL).unwrap();
+        assert!(matches!(resends.as_slice(), [Packet::Reliable { sequence: 0, payload }] if payload == &[7]));
+        
+        channel.acknowledge(0);
+        assert!(channel.due_resends(start + RESEND_INTERVAL * 2).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn peer_is_given_up_on_after_max_send_attempts() {
+        let mut channel = ReliableChannel::default();
+        let mut now = Instant::now();
+        channel.send(&[7], now);
+        for _ in 1..MAX_SEND_ATTEMPTS {
+            now += RESEND_INTERVAL;
+            assert_eq!(channel.due_resends(now).unwrap().len(), 1);
+        }
+        now += RESEND_INTERVAL;
+        assert_eq!(channel.due_resends(now).unwrap_err(), 0);
+    }
+}
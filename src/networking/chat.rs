@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Chat lines kept in the log
+pub const CHAT_HISTORY: usize = 50;
+
+/// Longest chat line that can be sent
+pub const MAX_CHAT_LENGTH: usize = 120;
+
+/// Who a chat line is for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatChannel {
+    All,
+    /// Only players on the sender's team
+    Allies,
+    /// Notices such as players joining or leaving, not written by a player
+    System,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatMessage {
+    pub player_id: u8,
+    pub channel: ChatChannel,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn system(text: String) -> Self {
+        Self {
+            player_id: 0,
+            channel: ChatChannel::System,
+            text,
+        }
+    }
+}
+
+/// Recent chat, plus the lines the HUD hasn't shown yet
+#[derive(Default)]
+pub struct ChatLog {
+    history: VecDeque<ChatMessage>,
+    unread: Vec<ChatMessage>,
+}
+
+impl ChatLog {
+    pub fn push(&mut self, message: ChatMessage) {
+        self.history.push_back(message.clone());
+        if self.history.len() > CHAT_HISTORY {
+            self.history.pop_front();
+        }
+        
+        self.unread.push(message);
+        if self.unread.len() > CHAT_HISTORY {
+            self.unread.remove(0);
+        }
+    }
+    
+    /// Recent chat, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.history.iter()
+    }
+    
+    /// Lines received since the last call, oldest first
+    pub fn take_unread(&mut self) -> Vec<ChatMessage> {
+        std::mem::take(&mut self.unread)
+    }
+}
@@ -0,0 +1,80 @@
+use bevy_ecs::prelude::*;
+
+use crate::ecs::components::{Building, NetworkId, Owner, Transform, Unit};
+use crate::ecs::resources::PlayerResources;
+
+/// 64-bit FNV-1a hasher; stable across platforms and compiler versions, unlike `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+    
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+    
+    fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+}
+
+/// Checksum of the simulation state that must match on every machine in a lockstep game
+pub fn world_checksum(world: &mut World) -> u64 {
+    let mut hasher = Fnv1a::new();
+    
+    // Units, in network ID order so iteration order doesn't matter; their entities may differ between machines
+    let mut unit_query = world.query::<(&NetworkId, &Unit, &Transform, &Owner)>();
+    let mut units: Vec<_> = unit_query.iter(world)
+        .map(|(network_id, unit, transform, owner)| (network_id.0, unit.health, transform.position, owner.0))
+        .collect();
+    units.sort_by_key(|(network_id, _, _, _)| *network_id);
+    
+    for (network_id, health, position, owner) in units {
+        hasher.write_u64(network_id as u64);
+        hasher.write_f32(health);
+        hasher.write_f32(position.x);
+        hasher.write_f32(position.y);
+        hasher.write(&[owner]);
+    }
+    
+    // Buildings
+    let mut building_query = world.query::<(&NetworkId, &Building, &Owner)>();
+    let mut buildings: Vec<_> = building_query.iter(world)
+        .map(|(network_id, building, owner)| {
+            (network_id.0, building.health, building.construction_progress, owner.0)
+        })
+        .collect();
+    buildings.sort_by_key(|(network_id, _, _, _)| *network_id);
+    
+    for (network_id, health, construction_progress, owner) in buildings {
+        hasher.write_u64(network_id as u64);
+        hasher.write_f32(health);
+        hasher.write_f32(construction_progress.unwrap_or(1.0));
+        hasher.write(&[owner]);
+    }
+    
+    // Player stockpiles
+    if let Some(player_resources) = world.get_resource::<PlayerResources>() {
+        let mut stockpiles: Vec<_> = player_resources.resources
+            .iter()
+            .map(|(&(player_id, resource_type), &amount)| (player_id, resource_type as u8, amount))
+            .collect();
+        stockpiles.sort_by_key(|(player_id, resource_type, _)| (*player_id, *resource_type));
+        
+        for (player_id, resource_type, amount) in stockpiles {
+            hasher.write(&[player_id, resource_type]);
+            hasher.write_f32(amount);
+        }
+    }
+    
+    hasher.0
+}
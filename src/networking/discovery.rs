@@ -0,0 +1,141 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Port hosts broadcast their lobbies to and browsers listen on
+pub const DISCOVERY_PORT: u16 = 12346;
+
+/// How often a host tells the local network about its lobby
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Games not heard from for this long are taken off the list
+const GAME_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Marks our announcements apart from anything else broadcast to the port
+const ANNOUNCEMENT_MAGIC: [u8; 4] = *b"RRLA";
+
+/// What a host broadcasts about its lobby
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameAnnouncement {
+    pub name: String,
+    pub port: u16, // Where the lobby takes players; the host's IP is where the announcement came from
+    pub players: u8,
+    pub max_players: u8,
+    pub in_game: bool,
+    pub version: String, // Players can only join hosts on the same build
+}
+
+/// A lobby heard about on the local network
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredGame {
+    pub address: SocketAddr, // To join at
+    pub announcement: GameAnnouncement,
+}
+
+impl DiscoveredGame {
+    /// Whether we can join it: not started, not full and running our build
+    pub fn is_joinable(&self) -> bool {
+        let announcement = &self.announcement;
+        !announcement.in_game
+            && announcement.players < announcement.max_players
+            && announcement.version == env!("CARGO_PKG_VERSION")
+    }
+}
+
+/// Broadcasts a host's lobby to the local network so players there see it without typing an IP
+pub struct LanAnnouncer {
+    socket: UdpSocket,
+    last_announced: Option<Instant>,
+}
+
+impl LanAnnouncer {
+    pub fn new() -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, last_announced: None })
+    }
+
+    /// Broadcast the lobby if it's time to; call every frame
+    pub fn update(&mut self, announcement: &GameAnnouncement) -> Result<()> {
+        let now = Instant::now();
+        if self.last_announced.map_or(false, |last| now.duration_since(last) < ANNOUNCE_INTERVAL) {
+            return Ok(());
+        }
+        self.last_announced = Some(now);
+
+        let mut data = ANNOUNCEMENT_MAGIC.to_vec();
+        data.extend_from_slice(&bincode::serialize(announcement)?);
+        self.socket.send_to(&data, (Ipv4Addr::BROADCAST, DISCOVERY_PORT))?;
+        Ok(())
+    }
+}
+
+/// Listens for hosts announcing lobbies on the local network, for the multiplayer menu's game list
+pub struct LanBrowser {
+    socket: UdpSocket,
+    games: HashMap<SocketAddr, (DiscoveredGame, Instant)>, // By join address, with when last heard from
+}
+
+impl LanBrowser {
+    /// Start listening; fails when another program on this machine already listens on DISCOVERY_PORT
+    pub fn new() -> Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, games: HashMap::new() })
+    }
+
+    /// Take in announcements and forget hosts that went quiet; call every frame. Returns whether the
+    /// list changed
+    pub fn update(&mut self) -> bool {
+        let now = Instant::now();
+        let mut changed = false;
+        let mut buf = [0u8; 1024];
+        loop {
+            let (len, src_addr) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!(target: "net", "Error receiving LAN announcement: {}", e);
+                    break;
+                }
+            };
+            if len < ANNOUNCEMENT_MAGIC.len() || buf[..ANNOUNCEMENT_MAGIC.len()] != ANNOUNCEMENT_MAGIC {
+                continue;
+            }
+            let announcement = match bincode::deserialize::<GameAnnouncement>(&buf[ANNOUNCEMENT_MAGIC.len()..len]) {
+                Ok(announcement) => announcement,
+                Err(_) => continue,
+            };
+
+            let address = SocketAddr::new(src_addr.ip(), announcement.port);
+            let game = DiscoveredGame { address, announcement };
+            changed |= self.games.get(&address).map_or(true, |(known, _)| *known != game);
+            self.games.insert(address, (game, now));
+        }
+
+        let count = self.games.len();
+        self.games.retain(|_, (_, last_heard)| now.duration_since(*last_heard) < GAME_TIMEOUT);
+        changed || self.games.len() != count
+    }
+
+    /// Forget every game, so the list fills again from the next announcements
+    pub fn refresh(&mut self) {
+        self.games.clear();
+    }
+
+    /// Games on the local network, joinable ones first, then by name
+    pub fn games(&self) -> Vec<DiscoveredGame> {
+        let mut games: Vec<DiscoveredGame> = self.games.values().map(|(game, _)| game.clone()).collect();
+        games.sort_by(|a, b| {
+            b.is_joinable()
+                .cmp(&a.is_joinable())
+                .then_with(|| a.announcement.name.cmp(&b.announcement.name))
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        games
+    }
+}
@@ -0,0 +1,405 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Most message bytes one datagram carries, keeping it under common path MTUs so it isn't split by IP
+pub const MAX_FRAGMENT_SIZE: usize = 1200;
+
+/// Largest message peers send each other, compressed or not; anything announcing more is refused
+pub const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Messages shorter than this are sent as they are; compressing them saves too little to bother
+pub const COMPRESSION_THRESHOLD: usize = 256;
+
+/// How long the fragments of an unfinished message are kept waiting for the rest
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Unfinished messages kept per peer; fragments starting more are dropped until some complete or expire
+const MAX_PENDING_MESSAGES: usize = 64;
+
+/// Bytes of unfinished messages kept per peer, so a peer can't make us buffer without end
+const MAX_PENDING_BYTES: usize = 2 * MAX_MESSAGE_SIZE;
+
+// Flag in front of every packed message
+const UNCOMPRESSED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// Compress a block of data, such as a replay body or a world snapshot, with lz4
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    lz4_flex::compress_prepend_size(data)
+}
+
+/// Undo `compress`, refusing data that claims to expand past MAX_MESSAGE_SIZE
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() >= 4 && u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize > MAX_MESSAGE_SIZE {
+        return Err(anyhow::anyhow!("Compressed data is too large"));
+    }
+    lz4_flex::decompress_size_prepended(data).map_err(|e| anyhow::anyhow!("Corrupt compressed data: {}", e))
+}
+
+/// Prepare a message for the wire: large ones are compressed when that makes them smaller
+pub fn pack(message: &[u8]) -> Vec<u8> {
+    if message.len() >= COMPRESSION_THRESHOLD {
+        let compressed = compress(message);
+        if compressed.len() < message.len() {
+            let mut packed = Vec::with_capacity(compressed.len() + 1);
+            packed.push(COMPRESSED);
+            packed.extend_from_slice(&compressed);
+            return packed;
+        }
+    }
+    
+    let mut packed = Vec::with_capacity(message.len() + 1);
+    packed.push(UNCOMPRESSED);
+    packed.extend_from_slice(message);
+    packed
+}
+
+/// Recover a message made by `pack`
+pub fn unpack(packed: &[u8]) -> Result<Vec<u8>> {
+    match packed.split_first() {
+        Some((&UNCOMPRESSED, message)) => Ok(message.to_vec()),
+        Some((&COMPRESSED, compressed)) => decompress(compressed),
+        _ => Err(anyhow::anyhow!("Unknown message packing")),
+    }
+}
+
+/// Append a message to a stream buffer behind its length
+pub fn write_length_prefixed(out: &mut Vec<u8>, message: &[u8]) {
+    out.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    out.extend_from_slice(message);
+}
+
+/// Take the first complete message off the front of a stream buffer, if it has all arrived.
+/// Err when the length can't be right, after which the stream is beyond saving.
+pub fn read_length_prefixed(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    if buf.len() < 4 {
+        return Ok(None);
+    }
+    let len = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(anyhow::anyhow!("Message of {} bytes is too large", len));
+    }
+    if buf.len() < 4 + len {
+        return Ok(None);
+    }
+    let message = buf[4..4 + len].to_vec();
+    buf.drain(..4 + len);
+    Ok(Some(message))
+}
+
+/// One datagram's worth of a packed message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fragment {
+    pub message_id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub data: Vec<u8>,
+}
+
+/// Cuts packed messages into fragments small enough for a datagram
+#[derive(Debug, Default)]
+pub struct Fragmenter {
+    next_message_id: u32,
+}
+
+impl Fragmenter {
+    pub fn split(&mut self, packed: &[u8]) -> Result<Vec<Fragment>> {
+        if packed.len() > MAX_MESSAGE_SIZE {
+            return Err(anyhow::anyhow!("Message of {} bytes is too large to send", packed.len()));
+        }
+        
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+        
+        // Even an empty message takes one fragment, so it still arrives
+        let chunks: Vec<&[u8]> = if packed.is_empty() { vec![packed] } else { packed.chunks(MAX_FRAGMENT_SIZE).collect() };
+        let count = chunks.len() as u16;
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| Fragment { message_id, index: index as u16, count, data: chunk.to_vec() })
+            .collect())
+    }
+}
+
+/// Fragments of a message that hasn't fully arrived
+#[derive(Debug)]
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    bytes: usize, // Held in the fragments received so far
+    first_seen: Instant,
+}
+
+/// What one peer's unfinished messages hold
+#[derive(Debug, Default)]
+struct Pending {
+    messages: usize,
+    bytes: usize,
+}
+
+/// Puts fragments from every peer back together into packed messages
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    partial: HashMap<(SocketAddr, u32), Partial>,
+    pending: HashMap<SocketAddr, Pending>,
+}
+
+impl Reassembler {
+    /// Take in a fragment; returns the packed message once its last missing fragment is in
+    pub fn add(&mut self, addr: SocketAddr, fragment: Fragment, now: Instant) -> Option<Vec<u8>> {
+        let count = fragment.count as usize;
+        if count == 0 || fragment.index as usize >= count || count * MAX_FRAGMENT_SIZE > MAX_MESSAGE_SIZE + MAX_FRAGMENT_SIZE {
+            return None;
+        }
+        if fragment.data.len() > MAX_FRAGMENT_SIZE {
+            return None;
+        }
+        if count == 1 {
+            return Some(fragment.data);
+        }
+        
+        let key = (addr, fragment.message_id);
+        let pending = self.pending.entry(addr).or_default();
+        if !self.partial.contains_key(&key) {
+            if pending.messages >= MAX_PENDING_MESSAGES {
+                return None;
+            }
+            pending.messages += 1;
+            self.partial.insert(key, Partial {
+                fragments: vec![None; count],
+                missing: count,
+                bytes: 0,
+                first_seen: now,
+            });
+        }
+        let partial = self.partial.get_mut(&key)?;
+        if partial.fragments.len() != count {
+            return None;
+        }
+        
+        let slot = &mut partial.fragments[fragment.index as usize];
+        if slot.is_none() {
+            if pending.bytes + fragment.data.len() > MAX_PENDING_BYTES {
+                return None;
+            }
+            pending.bytes += fragment.data.len();
+            partial.bytes += fragment.data.len();
+            *slot = Some(fragment.data);
+            partial.missing -= 1;
+        }
+        if partial.missing > 0 {
+            return None;
+        }
+        
+        let partial = self.remove(key)?;
+        Some(partial.fragments.into_iter().flatten().flatten().collect())
+    }
+    
+    /// Drop messages that stopped arriving part way, such as unreliable ones with a lost fragment
+    pub fn expire(&mut self, now: Instant) {
+        let expired: Vec<(SocketAddr, u32)> = self.partial
+            .iter()
+            .filter(|(_, partial)| now.duration_since(partial.first_seen) >= REASSEMBLY_TIMEOUT)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            self.remove(key);
+        }
+    }
+    
+    /// Forget an unfinished message, and what it held against its peer's limits
+    fn remove(&mut self, key: (SocketAddr, u32)) -> Option<Partial> {
+        let partial = self.partial.remove(&key)?;
+        if let Some(pending) = self.pending.get_mut(&key.0) {
+            pending.messages -= 1;
+            pending.bytes -= partial.bytes;
+            if pending.messages == 0 {
+                self.pending.remove(&key.0);
+            }
+        }
+        Some(partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+    
+    fn fragment(message_id: u32, index: u16, count: u16, data: &[u8]) -> Fragment {
+        Fragment { message_id, index, count, data: data.to_vec() }
+    }
+    
+    #[test]
+    fn pack_round_trips_small_and_compressible_messages() {
+        let small = b"hello".to_vec();
+        let packed = pack(&small);
+        assert_eq!(packed[0], UNCOMPRESSED);
+        assert_eq!(unpack(&packed).unwrap(), small);
+        
+        let large = vec![7u8; COMPRESSION_THRESHOLD * 4];
+        let packed = pack(&large);
+        assert_eq!(packed[0], COMPRESSED);
+        assert!(packed.len() < large.len());
+        assert_eq!(unpack(&packed).unwrap(), large);
+    }
+    
+    #[test]
+    fn unpack_rejects_unknown_packing_and_oversized_claims() {
+        assert!(unpack(&[]).is_err());
+        assert!(unpack(&[9, 1, 2, 3]).is_err());
+        
+        let mut bomb = vec![COMPRESSED];
+        bomb.extend_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes());
+        bomb.extend_from_slice(&[0; 16]);
+        assert!(unpack(&bomb).is_err());
+    }
+    
+    #[test]
+    fn length_prefixed_messages_come_off_a_stream_whole() {
+        let mut stream = Vec::new();
+        write_length_prefixed(&mut stream, b"first");
+        write_length_prefixed(&mut stream, b"second");
+        
+        // Only part of the second message has arrived
+        let partial_len = stream.len() - 2;
+        let mut buf = stream[..partial_len].to_vec();
+        assert_eq!(read_length_prefixed(&mut buf).unwrap(), Some(b"first".to_vec()));
+        assert_eq!(read_length_prefixed(&mut buf).unwrap(), None);
+        
+        buf.extend_from_slice(&stream[partial_len..]);
+        assert_eq!(read_length_prefixed(&mut buf).unwrap(), Some(b"second".to_vec()));
+        assert!(buf.is_empty());
+        
+        // A length under four bytes isn't even a prefix yet
+        let mut short = vec![1, 0];
+        assert_eq!(read_length_prefixed(&mut short).unwrap(), None);
+    }
+    
+    #[test]
+    fn length_prefix_past_the_message_limit_is_an_error() {
+        let mut buf = (MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes().to_vec();
+        assert!(read_length_prefixed(&mut buf).is_err());
+    }
+    
+    #[test]
+    fn fragments_reassemble_in_any_order() {
+        let message: Vec<u8> = (0..MAX_FRAGMENT_SIZE * 3 + 10).map(|i| i as u8).collect();
+        let mut fragments = Fragmenter::default().split(&message).unwrap();
+        assert_eq!(fragments.len(), 4);
+        fragments.reverse();
+        
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        let last = fragments.pop().unwrap();
+        for fragment in fragments {
+            assert_eq!(reassembler.add(peer(1), fragment, now), None);
+        }
+        assert_eq!(reassembler.add(peer(1), last, now), Some(message));
+        assert!(reassembler.partial.is_empty());
+        assert!(reassembler.pending.is_empty());
+    }
+    
+    #[test]
+    fn empty_messages_still_take_a_fragment() {
+        let fragments = Fragmenter::default().split(&[]).unwrap();
+        assert_eq!(fragments.len(), 1);
+        let mut reassembler = Reassembler::default();
+        assert_eq!(reassembler.add(peer(1), fragments[0].clone(), Instant::now()), Some(Vec::new()));
+    }
+    
+    #[test]
+    fn duplicate_fragments_are_counted_once() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, 2, b"ab"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, 2, b"ab"), now), None);
+        assert_eq!(reassembler.pending[&peer(1)].bytes, 2);
+        assert_eq!(reassembler.add(peer(1), fragment(0, 1, 2, b"cd"), now), Some(b"abcd".to_vec()));
+    }
+    
+    #[test]
+    fn malformed_fragments_are_dropped() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, 0, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(0, 2, 2, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, 1, &[0; MAX_FRAGMENT_SIZE + 1]), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, u16::MAX, b"a"), now), None);
+        
+        // A fragment disagreeing with the count the message started with
+        assert_eq!(reassembler.add(peer(1), fragment(1, 0, 2, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(1, 1, 3, b"b"), now), None);
+        assert!(reassembler.partial.values().all(|partial| partial.missing == 1));
+    }
+    
+    #[test]
+    fn each_peer_is_limited_in_unfinished_messages() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        for message_id in 0..MAX_PENDING_MESSAGES as u32 {
+            assert_eq!(reassembler.add(peer(1), fragment(message_id, 0, 2, b"a"), now), None);
+        }
+        
+        // One more can't start, while another peer is unaffected
+        let extra = MAX_PENDING_MESSAGES as u32;
+        assert_eq!(reassembler.add(peer(1), fragment(extra, 0, 2, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(extra, 1, 2, b"b"), now), None);
+        assert_eq!(reassembler.add(peer(2), fragment(extra, 0, 2, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(2), fragment(extra, 1, 2, b"b"), now), Some(b"ab".to_vec()));
+        
+        // Finishing one makes room again
+        assert_eq!(reassembler.add(peer(1), fragment(0, 1, 2, b"b"), now), Some(b"ab".to_vec()));
+        assert_eq!(reassembler.add(peer(1), fragment(extra, 0, 2, b"a"), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(extra, 1, 2, b"b"), now), Some(b"ab".to_vec()));
+    }
+    
+    #[test]
+    fn each_peer_is_limited_in_unfinished_bytes() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        let count = (MAX_MESSAGE_SIZE / MAX_FRAGMENT_SIZE + 1) as u16;
+        let chunk = vec![0u8; MAX_FRAGMENT_SIZE];
+        
+        // Two of the largest messages, each a fragment short, nearly fill the allowance
+        for message_id in 0..2 {
+            for index in 0..count - 1 {
+                assert_eq!(reassembler.add(peer(1), fragment(message_id, index, count, &chunk), now), None);
+            }
+        }
+        assert!(reassembler.pending[&peer(1)].bytes + MAX_FRAGMENT_SIZE > MAX_PENDING_BYTES);
+        
+        assert_eq!(reassembler.add(peer(1), fragment(2, 0, 2, &chunk), now), None);
+        assert_eq!(reassembler.add(peer(1), fragment(2, 1, 2, &chunk), now), None);
+        assert_eq!(reassembler.add(peer(2), fragment(2, 0, 2, &chunk), now), None);
+        assert!(reassembler.add(peer(2), fragment(2, 1, 2, &chunk), now).is_some());
+    }
+    
+    #[test]
+    fn expire_drops_stale_messages_and_frees_their_allowance() {
+        let mut reassembler = Reassembler::default();
+        let start = Instant::now();
+        assert_eq!(reassembler.add(peer(1), fragment(0, 0, 2, b"a"), start), None);
+        assert_eq!(reassembler.add(peer(1), fragment(1, 0, 2, b"c"), start + REASSEMBLY_TIMEOUT / 2), None);
+        
+        reassembler.expire(start + REASSEMBLY_TIMEOUT);
+        assert_eq!(reassembler.partial.len(), 1);
+        assert_eq!(reassembler.pending[&peer(1)].messages, 1);
+        assert_eq!(reassembler.pending[&peer(1)].bytes, 1);
+        
+        // The expired message's remaining fragment starts it over rather than completing it
+        assert_eq!(reassembler.add(peer(1), fragment(0, 1, 2, b"b"), start + REASSEMBLY_TIMEOUT), None);
+        assert_eq!(reassembler.add(peer(1), fragment(1, 1, 2, b"d"), start + REASSEMBLY_TIMEOUT), Some(b"cd".to_vec()));
+        
+        reassembler.expire(start + REASSEMBLY_TIMEOUT * 2);
+        assert!(reassembler.partial.is_empty());
+        assert!(reassembler.pending.is_empty());
+    }
+}
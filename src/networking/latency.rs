@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Pings remembered for working out packet loss
+const LOSS_WINDOW: usize = 20;
+
+/// A ping not answered within this long is counted as lost
+const PONG_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Round trip times up to these (in ms) earn 4, 3 and 2 bars; anything slower gets 1
+const BAR_THRESHOLDS_MS: [u32; 3] = [80, 150, 300];
+
+/// How good a player's connection is, as shown by the ping bars
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConnectionQuality {
+    pub rtt_ms: u32,
+    pub jitter_ms: u32,
+    pub loss: f32, // Share of recent pings that went unanswered, 0 to 1
+}
+
+impl ConnectionQuality {
+    /// Ping bars from 1 to 4; every tenth of pings lost takes a bar off
+    pub fn bars(&self) -> u8 {
+        let by_rtt = 4 - BAR_THRESHOLDS_MS.iter().filter(|&&threshold| self.rtt_ms > threshold).count() as u8;
+        let by_loss = (self.loss * 10.0).floor() as u8;
+        by_rtt.saturating_sub(by_loss).max(1)
+    }
+}
+
+/// Round trip time, jitter and packet loss on the link to one peer, from the pings we send it
+#[derive(Debug, Default)]
+pub struct LinkStats {
+    srtt_ms: Option<f32>, // Smoothed round trip time; None until the first pong
+    rttvar_ms: f32,       // Smoothed deviation from it, i.e. jitter
+    pings: VecDeque<(u64, bool)>, // Timestamps of recent pings, with whether each was answered
+}
+
+impl LinkStats {
+    pub fn record_ping_sent(&mut self, timestamp: u64) {
+        if self.pings.len() == LOSS_WINDOW {
+            self.pings.pop_front();
+        }
+        self.pings.push_back((timestamp, false));
+    }
+    
+    /// Take in the answer to a ping sent at `timestamp`, received at `now` (both ms since the epoch).
+    /// Duplicates and answers to pings we don't remember are ignored
+    pub fn record_pong(&mut self, timestamp: u64, now: u64) {
+        let answered = match self.pings.iter_mut().find(|(sent, _)| *sent == timestamp) {
+            Some((_, answered)) if !*answered => answered,
+            _ => return,
+        };
+        *answered = true;
+        
+        // Smoothed the same way TCP estimates its retransmission timeout
+        let sample = now.saturating_sub(timestamp) as f32;
+        match self.srtt_ms {
+            Some(srtt) => {
+                self.rttvar_ms = 0.75 * self.rttvar_ms + 0.25 * (srtt - sample).abs();
+                self.srtt_ms = Some(0.875 * srtt + 0.125 * sample);
+            }
+            None => {
+                self.rttvar_ms = sample / 2.0;
+                self.srtt_ms = Some(sample);
+            }
+        }
+    }
+    
+    /// Share of pings old enough to have been answered that weren't
+    pub fn loss(&self, now: u64) -> f32 {
+        let timeout = PONG_TIMEOUT.as_millis() as u64;
+        let due: Vec<bool> = self.pings
+            .iter()
+            .filter(|(sent, _)| now.saturating_sub(*sent) >= timeout)
+            .map(|(_, answered)| *answered)
+            .collect();
+        if due.is_empty() {
+            return 0.0;
+        }
+        due.iter().filter(|answered| !**answered).count() as f32 / due.len() as f32
+    }
+    
+    /// Latest figures; None until a ping has been answered
+    pub fn quality(&self, now: u64) -> Option<ConnectionQuality> {
+        let srtt = self.srtt_ms?;
+        Some(ConnectionQuality {
+            rtt_ms: srtt.round() as u32,
+            jitter_ms: self.rttvar_ms.round() as u32,
+            loss: self.loss(now),
+        })
+    }
+    
+    /// Forget everything measured, e.g. when the peer comes back at a new address
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
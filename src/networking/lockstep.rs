@@ -1,19 +1,84 @@
 use anyhow::Result;
 use bincode::{serialize, deserialize};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::VecDeque;
-use std::net::{SocketAddr, UdpSocket};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
+use crate::ecs::resources::TICK_RATE;
 use crate::engine::input::Command;
+use crate::game::map::MapGenerationParams;
+use crate::game::victory::VictoryConditionType;
+use crate::networking::bandwidth::{BandwidthMeter, BandwidthStats};
+use crate::networking::batch;
+use crate::networking::channel::Channel;
+use crate::networking::chat::{ChatChannel, ChatLog, ChatMessage, MAX_CHAT_LENGTH};
+use crate::networking::discovery::{GameAnnouncement, LanAnnouncer};
+use crate::networking::latency::{ConnectionQuality, LinkStats};
+use crate::networking::pause::{PauseControl, PauseState};
+use crate::networking::upnp::PortMapping;
+use crate::networking::{NetworkTransport, TransportKind};
 
-// Maximum number of ticks we can get ahead of the slowest player
-const MAX_TICK_LEAD: u64 = 5;
+/// Fewest ticks between issuing a command and executing it; hides network latency
+pub const INPUT_DELAY_TICKS: u64 = 3;
+
+/// Most ticks the host stretches the input delay to on a slow connection; past this the game stalls instead
+pub const MAX_INPUT_DELAY_TICKS: u64 = 12;
+
+/// How often (in ticks) players exchange world checksums
+pub const CHECKSUM_INTERVAL: u64 = 20;
+
+// Checksums older than this many ticks are dropped
+const CHECKSUM_HISTORY: u64 = 200;
+
+/// Port a host listens on for players joining its lobby
+pub const DEFAULT_PORT: u16 = 12345;
+
+/// Most players a lobby takes
+pub const MAX_LOBBY_PLAYERS: usize = 4;
+
+/// Silence after which a peer's connection is taken as lost
+pub const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often peers ping each other, measuring latency and keeping a quiet but healthy connection from
+/// being taken as lost
+const KEEPALIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the host shares everyone's connection quality and the input delay it calls for
+const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a client that lost the host asks to be let back in
+const REJOIN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a client keeps trying to rejoin before taking the host as gone for good
+const REJOIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most ticks a rejoining player replays per frame while catching up, so the window stays responsive
+pub const MAX_CATCH_UP_TICKS_PER_FRAME: u64 = 200;
+
+/// Player colors to pick from in the lobby
+pub const PLAYER_COLORS: [[u8; 4]; 6] = [
+    [0, 0, 255, 255],   // Blue
+    [255, 0, 0, 255],   // Red
+    [0, 255, 0, 255],   // Green
+    [255, 255, 0, 255], // Yellow
+    [255, 128, 0, 255], // Orange
+    [160, 0, 255, 255], // Purple
+];
+
+/// Every player's commands for one tick
+pub type TickCommands = HashMap<u8, Vec<Command>>;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NetworkCommand {
     pub tick: u64,
     pub player_id: u8,
+    #[serde(with = "batch")]
     pub commands: Vec<Command>,
 }
 
@@ -22,85 +87,300 @@ pub enum NetworkMessage {
     Commands(NetworkCommand),
     Ping(u64),
     Pong(u64),
-    Hello { player_id: u8, name: String },
-    Start { seed: u64, start_tick: u64 },
+    Hello { player_id: u8, name: String, rejoin_token: u64 }, // The host's answer carries the token for rejoining
+    Start(GameStartInfo),
+    Lobby { slots: Vec<LobbySlot> },
+    SlotUpdate(LobbySlot),
+    Chat(ChatMessage),
+    Leave { player_id: u8 },
     Sync { current_tick: u64 },
+    Roster { player_ids: Vec<u8> },
+    Checksum { tick: u64, player_id: u8, checksum: u64, epoch: u32 }, // Epoch: resyncs the sender had been through
+    /// Host to clients: where every other player is, so they can carry on if the host goes
+    Peers { peers: Vec<(u8, SocketAddr)> },
+    /// A client that lost its connection asks to be let back into the game
+    Rejoin { player_id: u8, rejoin_token: u64 },
+    /// Host to a rejoining client: the game so far, for it to replay up to `current_tick`
+    Resume {
+        start: GameStartInfo,
+        current_tick: u64,
+        participants: Vec<u8>,
+        history: Vec<(u64, TickCommands)>, // Every tick executed so far
+        queued: Vec<(u64, TickCommands)>,  // Input already in for ticks still to come
+        resync: Option<ResyncSnapshot>,    // The latest resync, which the replay starts from instead of the game start
+    },
+    /// Host to clients after a desync: its world, for everyone to carry on from
+    Resync(ResyncSnapshot),
+    /// Host to clients: how every player's link to the host is doing, and the input delay to schedule with
+    Latency { input_delay: u64, players: Vec<(u8, ConnectionQuality)> },
+    /// Client to host: pause the game for everyone, or resume it
+    PauseRequest { paused: bool },
+    /// Host to clients: the game was paused or resumed
+    Pause(PauseState),
+}
+
+impl NetworkMessage {
+    /// Pings and latency reports are stale by the time they'd be resent, and rejoin requests are repeated until
+    /// answered and must get through before the reliable channel is back in step; everything else must arrive,
+    /// and in order
+    pub fn channel(&self) -> Channel {
+        match self {
+            NetworkMessage::Ping(_)
+            | NetworkMessage::Pong(_)
+            | NetworkMessage::Rejoin { .. }
+            | NetworkMessage::Latency { .. } => Channel::Unreliable,
+            _ => Channel::ReliableOrdered,
+        }
+    }
+}
+
+/// A player's seat in the lobby
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LobbySlot {
+    pub player_id: u8,
+    pub name: String,
+    pub color: u8, // Index into PLAYER_COLORS
+    pub team: u8,
+    pub ready: bool,
+}
+
+/// Everything peers need to build the same world when the host starts the game
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameStartInfo {
+    pub seed: u64,
+    pub start_tick: u64,
+    pub map: MapGenerationParams,
+    pub slots: Vec<LobbySlot>,
+    pub shared_vision: bool,   // Teammates see everything each other sees
+    pub day_night_cycle: bool, // Nights cut everyone's sight ranges
+    pub victory: VictoryConditionType,
+}
+
+/// The host's world at the start of a tick, which every player restores after a desync
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResyncSnapshot {
+    pub tick: u64,
+    pub epoch: u32,     // How many resyncs this game has had, this one included
+    pub data: Vec<u8>, // From `savegame::capture_snapshot`
+}
+
+/// Players disagreed about the world state at a tick
+#[derive(Debug, Clone)]
+pub struct DesyncReport {
+    pub tick: u64,
+    pub checksums: HashMap<u8, u64>, // Player ID -> Checksum
+}
+
+impl fmt::Display for DesyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut checksums: Vec<_> = self.checksums.iter().collect();
+        checksums.sort();
+        
+        write!(f, "Desync detected at tick {}:", self.tick)?;
+        for (player_id, checksum) in checksums {
+            write!(f, " player {} = {:016x}", player_id, checksum)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct LockstepNetwork {
     active: bool,
-    socket: Option<UdpSocket>,
+    transport: Box<dyn NetworkTransport>,
+    transport_kind: TransportKind,
+    port: u16, // Hosts only: where the lobby takes players
+    announcer: Option<LanAnnouncer>, // Hosts only: tells the local network about the lobby
+    port_mapping: Option<PortMapping>, // Hosts only: forwarded on the router to reach players outside the LAN
     players: HashMap<u8, PlayerInfo>,
     local_player_id: u8,
+    host_id: u8, // Player 0 until a host leaves mid-game and another player takes over
     current_tick: u64,
-    command_queue: HashMap<u64, HashMap<u8, Vec<Command>>>,
+    command_queue: HashMap<u64, TickCommands>,
+    history: Vec<(u64, TickCommands)>, // Every tick executed this game, for players who rejoin
     message_queue: VecDeque<NetworkMessage>,
     is_host: bool,
     pending_commands: Vec<Command>,
-    last_sent_commands_tick: u64,
+    last_sent_commands_tick: Option<u64>,
+    last_scheduled_tick: u64, // Latest tick our own input went out for
+    input_delay: u64, // Ticks ahead our input is scheduled; set by the host from everyone's latency
+    participants: HashSet<u8>, // Players whose input every tick waits for
+    start_tick: u64,
+    checksums: HashMap<u64, HashMap<u8, u64>>, // Tick -> (Player ID -> Checksum)
+    desync: Option<DesyncReport>,
+    epoch: u32, // Resyncs so far; checksums taken before the latest one are ignored
+    last_resync: Option<ResyncSnapshot>, // For players who rejoin
+    pending_resync: Option<ResyncSnapshot>, // Not yet restored by the engine
+    lobby: Vec<LobbySlot>, // Sorted by player ID
+    chat: ChatLog,
+    game_start: Option<GameStartInfo>, // Start announced but not yet picked up by the engine
+    game_info: Option<GameStartInfo>,  // The game under way, for players who rejoin
+    in_game: bool, // The lobby has closed
+    rejoin_token: u64, // Clients only: lets the host know us when we rejoin
+    rejoining_since: Option<Instant>, // Clients only: when we lost the host and started asking to rejoin
+    last_rejoin_request: Option<Instant>,
+    catch_up_tick: u64, // Ticks before this are replayed from the host's history after rejoining
+    last_keepalive: Option<Instant>,
+    last_latency_report: Option<Instant>,
+    pause: PauseControl,
+    bandwidth: BandwidthMeter,
 }
 
 struct PlayerInfo {
     address: SocketAddr,
     name: String,
     last_tick_received: u64,
-    ping_ms: u32,
+    link: LinkStats, // Our pings to them; clients only ping the host
+    reported_quality: Option<ConnectionQuality>, // Clients only: their link to the host, as the host measures it
+    last_heard: Instant, // For noticing a dropped connection
+    connected: bool, // While false the host fills in empty input for them, until they rejoin
+    rejoin_token: u64, // Host only: proves a rejoining player is who they say
+}
+
+impl PlayerInfo {
+    fn new(address: SocketAddr, name: String, last_tick_received: u64) -> Self {
+        Self {
+            address,
+            name,
+            last_tick_received,
+            link: LinkStats::default(),
+            reported_quality: None,
+            last_heard: Instant::now(),
+            connected: true,
+            rejoin_token: 0,
+        }
+    }
+}
+
+/// Hard-to-guess number for a player to rejoin with
+fn new_rejoin_token() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish()
+}
+
+/// Milliseconds since the epoch, which pings carry so their answers tell the round trip time
+fn timestamp_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
 }
 
 impl LockstepNetwork {
-    pub fn new() -> Self {
+    pub fn new(transport: TransportKind) -> Self {
         Self {
             active: false,
-            socket: None,
+            transport: transport.create(),
+            transport_kind: transport,
+            port: 0,
+            announcer: None,
+            port_mapping: None,
             players: HashMap::new(),
             local_player_id: 0,
+            host_id: 0,
             current_tick: 0,
             command_queue: HashMap::new(),
+            history: Vec::new(),
             message_queue: VecDeque::new(),
             is_host: false,
             pending_commands: Vec::new(),
-            last_sent_commands_tick: 0,
+            last_sent_commands_tick: None,
+            last_scheduled_tick: 0,
+            input_delay: INPUT_DELAY_TICKS,
+            participants: HashSet::new(),
+            start_tick: 0,
+            checksums: HashMap::new(),
+            desync: None,
+            epoch: 0,
+            last_resync: None,
+            pending_resync: None,
+            lobby: Vec::new(),
+            chat: ChatLog::default(),
+            game_start: None,
+            game_info: None,
+            in_game: false,
+            rejoin_token: 0,
+            rejoining_since: None,
+            last_rejoin_request: None,
+            catch_up_tick: 0,
+            last_keepalive: None,
+            last_latency_report: None,
+            pause: PauseControl::default(),
+            bandwidth: BandwidthMeter::default(),
         }
     }
     
     pub fn host_game(&mut self, port: u16, player_name: String) -> Result<()> {
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", port))?;
-        socket.set_nonblocking(true)?;
+        self.bind_host(port, player_name.clone())?;
+        self.participants.insert(0);
         
-        self.socket = Some(socket);
+        // The host has nobody to wait for, so it is always ready
+        self.lobby.push(LobbySlot {
+            player_id: 0,
+            name: player_name,
+            color: 0,
+            team: 0,
+            ready: true,
+        });
+        
+        Ok(())
+    }
+    
+    /// Host a lobby as a dedicated server: it relays and simulates the game but doesn't play in it,
+    /// so it takes no lobby slot and nobody waits for its input
+    pub fn host_dedicated(&mut self, port: u16, server_name: String) -> Result<()> {
+        self.bind_host(port, server_name)
+    }
+    
+    fn bind_host(&mut self, port: u16, name: String) -> Result<()> {
+        self.transport.listen(port)?;
+        self.port = port;
         self.is_host = true;
         self.active = true;
-        self.local_player_id = 0; // Host is always player 0
+        self.local_player_id = 0; // Host is player 0
+        self.host_id = 0;
         
         // Add ourselves as a player
-        self.players.insert(
-            0,
-            PlayerInfo {
-                address: "127.0.0.1:0".parse().unwrap(),
-                name: player_name,
-                last_tick_received: 0,
-                ping_ms: 0,
-            },
-        );
+        self.players.insert(0, PlayerInfo::new("127.0.0.1:0".parse().unwrap(), name, 0));
+        
+        // Players on the local network find the lobby without typing our address
+        match LanAnnouncer::new() {
+            Ok(announcer) => self.announcer = Some(announcer),
+            Err(e) => warn!(target: "net", "Failed to announce the lobby on the local network: {}", e),
+        }
         
         Ok(())
     }
     
-    pub fn join_game(&mut self, host_address: &str, player_name: String) -> Result<()> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_nonblocking(true)?;
+    /// Host only: forward our port on the router with UPnP so players outside the local network can
+    /// join. Returns the address they should join at.
+    pub fn open_port_mapping(&mut self) -> Result<SocketAddr> {
+        if !self.is_host {
+            return Err(anyhow::anyhow!("Only the host forwards its port"));
+        }
         
+        let mapping = PortMapping::open(self.port, self.transport_kind)?;
+        let address = SocketAddr::new(mapping.external_ip, self.port);
+        self.port_mapping = Some(mapping);
+        Ok(address)
+    }
+    
+    pub fn join_game(&mut self, host_address: &str, player_name: String) -> Result<()> {
         // Connect to host
-        let host_addr = host_address.parse()?;
-        
-        self.socket = Some(socket);
+        let host_addr: SocketAddr = host_address.parse()?;
+        self.transport.connect(host_addr)?;
         self.is_host = false;
         self.active = true;
         
+        // Remember the host so messages can reach it before it answers
+        self.host_id = 0; // The host we join is player 0
+        self.players.insert(0, PlayerInfo::new(host_addr, String::new(), 0));
+        
         // Send hello message to host
         self.send_to_host(NetworkMessage::Hello {
             player_id: 255, // Will be assigned by host
             name: player_name,
+            rejoin_token: 0,
         })?;
         
         Ok(())
@@ -110,120 +390,839 @@ impl LockstepNetwork {
         self.active
     }
     
-    pub fn process_messages(&mut self) -> Result<()> {
+    pub fn is_host(&self) -> bool {
+        self.is_host
+    }
+    
+    /// Whether the host has started the game and closed the lobby
+    pub fn in_game(&self) -> bool {
+        self.in_game
+    }
+    
+    /// Players in the lobby, ordered by player ID
+    pub fn lobby_slots(&self) -> &[LobbySlot] {
+        &self.lobby
+    }
+    
+    /// Recent chat, oldest first
+    pub fn chat_log(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.chat.iter()
+    }
+    
+    /// Chat received or posted since the last call, for the in-game message log
+    pub fn take_new_chat(&mut self) -> Vec<ChatMessage> {
+        self.chat.take_unread()
+    }
+    
+    /// Whether every player in the lobby is ready to start
+    pub fn all_ready(&self) -> bool {
+        self.lobby.len() > 1 && self.lobby.iter().all(|slot| slot.ready)
+    }
+    
+    pub fn set_ready(&mut self, ready: bool) -> Result<()> {
+        self.update_local_slot(|slot| slot.ready = ready)
+    }
+    
+    pub fn set_team(&mut self, team: u8) -> Result<()> {
+        self.update_local_slot(|slot| slot.team = team)
+    }
+    
+    /// Pick a color from `PLAYER_COLORS`; colors already taken are refused
+    pub fn set_color(&mut self, color: u8) -> Result<()> {
+        let local_player_id = self.local_player_id;
+        if color as usize >= PLAYER_COLORS.len()
+            || self.lobby.iter().any(|slot| slot.player_id != local_player_id && slot.color == color)
+        {
+            return Err(anyhow::anyhow!("Color {} is not available", color));
+        }
+        
+        self.update_local_slot(|slot| slot.color = color)
+    }
+    
+    /// Send a chat line to everyone, or only to our team
+    pub fn send_chat(&mut self, text: String, channel: ChatChannel) -> Result<()> {
+        if channel == ChatChannel::System {
+            return Err(anyhow::anyhow!("Players can't send system messages"));
+        }
+        
+        let message = ChatMessage {
+            player_id: self.local_player_id,
+            channel,
+            text: text.chars().take(MAX_CHAT_LENGTH).collect(),
+        };
+        self.chat.push(message.clone());
+        self.broadcast(NetworkMessage::Chat(message))
+    }
+    
+    /// Show a notice in our own chat log only, e.g. a desync warning
+    pub fn post_system_message(&mut self, text: String) {
+        self.chat.push(ChatMessage::system(text));
+    }
+    
+    /// Tell the other players we are going; a leaving host ends the session for everyone
+    pub fn leave(&mut self) -> Result<()> {
         if !self.active {
             return Ok(());
         }
         
-        let socket = match &self.socket {
-            Some(s) => s,
-            None => return Ok(()),
+        self.broadcast(NetworkMessage::Leave { player_id: self.local_player_id })?;
+        self.transport.close();
+        self.announcer = None;
+        self.port_mapping = None;
+        self.rejoining_since = None;
+        self.active = false;
+        Ok(())
+    }
+    
+    /// Host only: start the game for everyone once all players are ready
+    pub fn start_game(
+        &mut self,
+        seed: u64,
+        mut map: MapGenerationParams,
+        shared_vision: bool,
+        day_night_cycle: bool,
+        victory: VictoryConditionType,
+    ) -> Result<()> {
+        if !self.is_host {
+            return Err(anyhow::anyhow!("Only the host can start the game"));
+        }
+        if !self.all_ready() {
+            return Err(anyhow::anyhow!("Not every player is ready"));
+        }
+        
+        map.seed = seed;
+        map.player_count = self.lobby.len() as u8;
+        let start = GameStartInfo {
+            seed,
+            start_tick: self.current_tick,
+            map,
+            slots: self.lobby.clone(),
+            shared_vision,
+            day_night_cycle,
+            victory,
         };
         
-        // Buffer for incoming data
-        let mut buf = [0u8; 1024];
+        self.broadcast(NetworkMessage::Start(start.clone()))?;
+        self.begin_game(start);
+        
+        Ok(())
+    }
+    
+    /// Take the game start announced by the host, once, so the engine can build the world
+    pub fn take_game_start(&mut self) -> Option<GameStartInfo> {
+        self.game_start.take()
+    }
+    
+    fn begin_game(&mut self, start: GameStartInfo) {
+        self.epoch = 0;
+        self.last_resync = None;
+        self.pending_resync = None;
+        self.pause.reset();
+        self.current_tick = start.start_tick;
+        self.start_tick = start.start_tick;
+        self.last_scheduled_tick = start.start_tick + INPUT_DELAY_TICKS - 1;
+        self.participants = start.slots.iter().map(|slot| slot.player_id).collect();
+        self.game_info = Some(start.clone());
+        self.game_start = Some(start);
+        self.in_game = true;
+    }
+    
+    /// Change our own lobby slot and tell the others; the host has the final say
+    fn update_local_slot(&mut self, update: impl FnOnce(&mut LobbySlot)) -> Result<()> {
+        let local_player_id = self.local_player_id;
+        let slot = match self.lobby.iter_mut().find(|slot| slot.player_id == local_player_id) {
+            Some(slot) => slot,
+            None => return Err(anyhow::anyhow!("Not in a lobby")),
+        };
+        update(slot);
+        
+        if self.is_host {
+            self.broadcast_lobby()
+        } else {
+            let slot = slot.clone();
+            self.send_to_host(NetworkMessage::SlotUpdate(slot))
+        }
+    }
+    
+    fn broadcast_lobby(&self) -> Result<()> {
+        self.broadcast(NetworkMessage::Lobby { slots: self.lobby.clone() })
+    }
+    
+    /// Host only: tell everyone whose input the lockstep waits for, and where every player is
+    fn broadcast_roster(&self) -> Result<()> {
+        let mut player_ids: Vec<u8> = self.participants.iter().copied().collect();
+        player_ids.sort_unstable();
+        self.broadcast(NetworkMessage::Roster { player_ids })?;
+        
+        let mut peers: Vec<(u8, SocketAddr)> = self.players
+            .iter()
+            .filter(|(&player_id, _)| player_id != self.local_player_id)
+            .map(|(&player_id, player)| (player_id, player.address))
+            .collect();
+        peers.sort_unstable();
+        self.broadcast(NetworkMessage::Peers { peers })
+    }
+    
+    /// Host only: take a player out of the lobby and the lockstep for good; returns their name
+    fn remove_player(&mut self, player_id: u8) -> Result<String> {
+        let name = self.players.remove(&player_id).map(|player| player.name).unwrap_or_default();
+        self.lobby.retain(|slot| slot.player_id != player_id);
+        self.participants.remove(&player_id);
+        
+        self.broadcast_roster()?;
+        self.broadcast_lobby()?;
+        Ok(name)
+    }
+    
+    fn slot_name(&self, player_id: u8) -> String {
+        self.lobby.iter().find(|slot| slot.player_id == player_id).map(|slot| slot.name.clone()).unwrap_or_default()
+    }
+    
+    /// Host only: post a notice to everyone's chat
+    fn announce(&mut self, text: String) -> Result<()> {
+        let message = ChatMessage::system(text);
+        self.chat.push(message.clone());
+        self.broadcast(NetworkMessage::Chat(message))
+    }
+    
+    fn team_of(&self, player_id: u8) -> Option<u8> {
+        self.lobby.iter().find(|slot| slot.player_id == player_id).map(|slot| slot.team)
+    }
+    
+    /// Player ID of a known peer address
+    fn player_at(&self, addr: SocketAddr) -> Option<u8> {
+        self.players
+            .iter()
+            .find(|(&player_id, player)| player_id != self.local_player_id && player.address == addr)
+            .map(|(&player_id, _)| player_id)
+    }
+    
+    /// Host only: keep the local network's game lists up to date with our lobby
+    fn announce_lobby(&mut self) {
+        let announcer = match &mut self.announcer {
+            Some(announcer) => announcer,
+            None => return,
+        };
+        
+        let announcement = GameAnnouncement {
+            name: self.players.get(&self.local_player_id).map(|host| host.name.clone()).unwrap_or_default(),
+            port: self.port,
+            players: self.lobby.len() as u8,
+            max_players: MAX_LOBBY_PLAYERS as u8,
+            in_game: self.in_game,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        if let Err(e) = announcer.update(&announcement) {
+            warn!(target: "net", "Failed to announce the lobby on the local network: {}", e);
+        }
+    }
+    
+    pub fn process_messages(&mut self) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        
+        // Resend what peers haven't acknowledged before taking in what they sent
+        self.transport.update()?;
+        self.announce_lobby();
         
         // Process all pending messages
         loop {
-            match socket.recv_from(&mut buf) {
-                Ok((bytes_received, src_addr)) => {
+            match self.transport.recv_from() {
+                Ok(Some((data, src_addr))) => {
+                    self.bandwidth.record_received(data.len());
+                    
                     // Deserialize the message
-                    match deserialize::<NetworkMessage>(&buf[0..bytes_received]) {
+                    match deserialize::<NetworkMessage>(&data) {
                         Ok(message) => self.handle_message(message, src_addr)?,
-                        Err(e) => eprintln!("Failed to deserialize network message: {}", e),
+                        Err(e) => warn!(target: "net", "Failed to deserialize network message: {}", e),
                     }
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                Ok(None) => {
                     // No more messages to process
                     break;
                 }
                 Err(e) => {
-                    eprintln!("Error receiving network message: {}", e);
+                    error!(target: "net", "Error receiving network message: {}", e);
                     break;
                 }
             }
         }
         
+        self.check_connections()
+    }
+    
+    /// Notice dropped connections and keep quiet ones alive. The host fills in empty input for players
+    /// it lost until they rejoin; a client that lost the host asks to rejoin, and takes the host as gone
+    /// for good if it can't
+    fn check_connections(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if self.last_keepalive.map_or(true, |last| now.duration_since(last) >= KEEPALIVE_INTERVAL) {
+            self.last_keepalive = Some(now);
+            if self.rejoining_since.is_none() {
+                self.ping_peers()?;
+            }
+        }
+        if self.is_host && self.last_latency_report.map_or(true, |last| now.duration_since(last) >= LATENCY_REPORT_INTERVAL) {
+            self.last_latency_report = Some(now);
+            self.report_latency()?;
+        }
+        self.update_pause(now)?;
+        self.bandwidth.update(now);
+        
+        let lost_peers = self.transport.take_lost_peers();
+        let mut lost: Vec<u8> = lost_peers.into_iter().filter_map(|addr| self.player_at(addr)).collect();
+        
+        if self.is_host {
+            lost.extend(
+                self.players
+                    .iter()
+                    .filter(|(&player_id, player)| {
+                        player_id != self.local_player_id
+                            && player.connected
+                            && now.duration_since(player.last_heard) >= DISCONNECT_TIMEOUT
+                    })
+                    .map(|(&player_id, _)| player_id),
+            );
+            lost.sort_unstable();
+            lost.dedup();
+            for player_id in lost {
+                self.drop_player(player_id)?;
+            }
+            self.fill_in_for_dropped_players()
+        } else if self.rejoining_since.is_some() {
+            self.request_rejoin(now)
+        } else {
+            let host_silent = self.players
+                .get(&self.host_id)
+                .map_or(false, |host| now.duration_since(host.last_heard) >= DISCONNECT_TIMEOUT);
+            if host_silent || lost.contains(&self.host_id) {
+                self.lose_host(now)?;
+            }
+            Ok(())
+        }
+    }
+    
+    /// Ping everyone we talk to directly: the host pings every player, clients only the host
+    fn ping_peers(&mut self) -> Result<()> {
+        let timestamp = timestamp_ms();
+        let (local_player_id, host_id, is_host) = (self.local_player_id, self.host_id, self.is_host);
+        for (&player_id, player) in self.players.iter_mut() {
+            let pinged = if is_host { player_id != local_player_id && player.connected } else { player_id == host_id };
+            if pinged {
+                player.link.record_ping_sent(timestamp);
+            }
+        }
+        self.broadcast(NetworkMessage::Ping(timestamp))
+    }
+    
+    /// Host only: fit the input delay to the latency we measure, and tell everyone how each link is doing
+    fn report_latency(&mut self) -> Result<()> {
+        let now = timestamp_ms();
+        let mut players: Vec<(u8, ConnectionQuality)> = self.players
+            .iter()
+            .filter(|(&player_id, player)| player_id != self.local_player_id && player.connected)
+            .filter_map(|(&player_id, player)| Some((player_id, player.link.quality(now)?)))
+            .collect();
+        players.sort_by_key(|(player_id, _)| *player_id);
+        
+        // Input takes about half a round trip to reach the host, more when it jitters or a lost packet is
+        // resent. Between clients it goes through the host, so the slowest path crosses the two slowest links
+        let mut one_way_ms: Vec<f32> = players
+            .iter()
+            .map(|(_, quality)| {
+                let rtt = quality.rtt_ms as f32;
+                rtt / 2.0 + 2.0 * quality.jitter_ms as f32 + quality.loss * rtt
+            })
+            .collect();
+        one_way_ms.sort_by(|a, b| b.total_cmp(a));
+        let path_ms: f32 = one_way_ms.iter().take(2).sum();
+        
+        // One tick more covers input waiting for the next tick to go out
+        let tick_ms = 1000.0 / TICK_RATE as f32;
+        let wanted = ((path_ms / tick_ms).ceil() as u64 + 1).clamp(INPUT_DELAY_TICKS, MAX_INPUT_DELAY_TICKS);
+        
+        // Raise the delay at once so nobody stalls, but lower it a tick at a time so one quick ping
+        // doesn't set it flapping
+        self.input_delay = if wanted >= self.input_delay { wanted } else { self.input_delay - 1 };
+        
+        self.broadcast(NetworkMessage::Latency { input_delay: self.input_delay, players })
+    }
+    
+    /// Host only: a player's connection dropped. Before the game they just leave; during it their
+    /// slot is kept for them to rejoin
+    fn drop_player(&mut self, player_id: u8) -> Result<()> {
+        if !self.in_game {
+            let name = self.remove_player(player_id)?;
+            return self.announce(format!("{} lost connection", name));
+        }
+        
+        let player = match self.players.get_mut(&player_id) {
+            Some(player) if player.connected => player,
+            _ => return Ok(()),
+        };
+        player.connected = false;
+        let (name, address) = (player.name.clone(), player.address);
+        self.transport.reset_peer(address);
+        self.announce(format!("{} lost connection; their units stand idle until they rejoin", name))
+    }
+    
+    /// Host only: send empty input for players we lost, so nobody waits on them. Stops short of the
+    /// tick a rejoining player's own input would start at
+    fn fill_in_for_dropped_players(&mut self) -> Result<()> {
+        if !self.in_game {
+            return Ok(());
+        }
+        
+        let last_tick = self.current_tick + self.input_delay - 1;
+        let dropped: Vec<(u8, u64)> = self.players
+            .iter()
+            .filter(|(player_id, player)| !player.connected && self.participants.contains(player_id))
+            .map(|(&player_id, player)| (player_id, player.last_tick_received))
+            .collect();
+        for (player_id, last_received) in dropped {
+            for tick in (last_received + 1).max(self.current_tick)..=last_tick {
+                let command = NetworkCommand { tick, player_id, commands: Vec::new() };
+                self.command_queue.entry(tick).or_insert_with(HashMap::new).insert(player_id, Vec::new());
+                self.broadcast(NetworkMessage::Commands(command))?;
+            }
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.last_tick_received = last_received.max(last_tick);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Client only: the host stopped answering. In a game we ask to rejoin; in the lobby there is nothing to rejoin
+    fn lose_host(&mut self, now: Instant) -> Result<()> {
+        if !self.in_game {
+            self.post_system_message("Lost connection to the host".to_string());
+            self.transport.close();
+            self.active = false;
+            return Ok(());
+        }
+        
+        self.post_system_message("Lost connection to the host; trying to rejoin".to_string());
+        self.rejoining_since = Some(now);
+        self.last_rejoin_request = None;
+        self.request_rejoin(now)
+    }
+    
+    /// Client only: ask the host to let us back in, every REJOIN_INTERVAL until REJOIN_TIMEOUT
+    fn request_rejoin(&mut self, now: Instant) -> Result<()> {
+        let since = match self.rejoining_since {
+            Some(since) => since,
+            None => return Ok(()),
+        };
+        if now.duration_since(since) >= REJOIN_TIMEOUT {
+            self.rejoining_since = None;
+            return self.migrate_host();
+        }
+        if self.last_rejoin_request.map_or(false, |last| now.duration_since(last) < REJOIN_INTERVAL) {
+            return Ok(());
+        }
+        self.last_rejoin_request = Some(now);
+        
+        let host_addr = match self.players.get(&self.host_id) {
+            Some(host) => host.address,
+            None => return Ok(()),
+        };
+        
+        // Start the channel to the host afresh, dropping whatever it sent before it gave up on us;
+        // over TCP this opens a new stream
+        self.transport.reset_peer(host_addr);
+        if let Err(e) = self.transport.connect(host_addr) {
+            error!(target: "net", "Failed to reach the host at {}: {}", host_addr, e);
+            return Ok(());
+        }
+        
+        let request = NetworkMessage::Rejoin { player_id: self.local_player_id, rejoin_token: self.rejoin_token };
+        if let Err(e) = self.send_to_host(request) {
+            error!(target: "net", "Failed to ask the host to rejoin: {}", e);
+        }
         Ok(())
     }
     
+    /// Client only: the host left or can't be reached, so the remaining player with the lowest ID takes
+    /// over. Only UDP games can carry on: over TCP nobody else has a listening socket to reach. Players
+    /// who drop after a migration can't rejoin, as only the first host knows their rejoin tokens
+    fn migrate_host(&mut self) -> Result<()> {
+        let old_host = self.host_id;
+        let name = self.players.remove(&old_host).map(|host| host.name).unwrap_or_default();
+        if !self.in_game || self.transport_kind != TransportKind::Udp {
+            self.post_system_message(format!("{} (host) left the game", name));
+            return Ok(());
+        }
+        
+        self.lobby.retain(|slot| slot.player_id != old_host);
+        self.participants.remove(&old_host);
+        let new_host = self.players.keys().copied().chain(std::iter::once(self.local_player_id)).min().unwrap_or(self.local_player_id);
+        self.host_id = new_host;
+        self.post_system_message(format!("{} (host) left the game; {} is now the host", name, self.slot_name(new_host)));
+        
+        // Input the old host may not have passed on to everyone goes around again; peers keep the first copy
+        let mut queued: Vec<NetworkCommand> = self.command_queue
+            .iter()
+            .flat_map(|(&tick, commands)| {
+                commands.iter().map(move |(&player_id, commands)| NetworkCommand { tick, player_id, commands: commands.clone() })
+            })
+            .collect();
+        queued.sort_by_key(|command| (command.tick, command.player_id));
+        
+        let now = Instant::now();
+        if new_host == self.local_player_id {
+            self.is_host = true;
+            
+            // Everyone gets the full timeout to find us
+            for player in self.players.values_mut() {
+                player.last_heard = now;
+            }
+            let name = self.slot_name(self.local_player_id);
+            self.players.insert(self.local_player_id, PlayerInfo::new("127.0.0.1:0".parse().unwrap(), name, self.current_tick));
+            
+            self.broadcast_roster()?;
+            self.broadcast_lobby()?;
+        } else if let Some(host) = self.players.get_mut(&new_host) {
+            host.last_heard = now;
+        }
+        for command in queued {
+            self.broadcast(NetworkMessage::Commands(command))?;
+        }
+        Ok(())
+    }
+    
+    /// Schedule local commands for execution `input_delay` ticks from now and send them to
+    /// the other players; input is sent every tick, even when empty, so peers know it is complete
     pub fn send_commands(&mut self, commands: &[Command]) -> Result<()> {
-        if !self.active || commands.is_empty() {
+        if !self.active {
+            return Ok(());
+        }
+        
+        // Ticks replayed after rejoining were played long ago; our input picks up where the game is now
+        if self.rejoining_since.is_some() || self.current_tick < self.catch_up_tick {
+            self.pending_commands.clear();
             return Ok(());
         }
         
         // Add commands to pending list
         self.pending_commands.extend_from_slice(commands);
         
-        // Only send commands periodically (e.g., every tick)
-        if self.current_tick == self.last_sent_commands_tick {
+        // Input for this tick already went out; the rest waits for the next tick
+        if self.last_sent_commands_tick == Some(self.current_tick) {
+            return Ok(());
+        }
+        
+        // Commands will be executed in the future. After the delay shrank, that tick may already have our
+        // input; the commands wait until the schedule moves past it
+        let tick = self.current_tick + self.input_delay;
+        if tick <= self.last_scheduled_tick {
             return Ok(());
         }
         
-        // Create network command
+        // After the delay grew, the ticks skipped over get empty input so peers aren't left waiting on them
+        for skipped_tick in (self.last_scheduled_tick + 1).max(self.current_tick)..tick {
+            self.schedule_local_commands(skipped_tick, Vec::new())?;
+        }
+        let commands = std::mem::take(&mut self.pending_commands);
+        self.schedule_local_commands(tick, commands)?;
+        self.last_sent_commands_tick = Some(self.current_tick);
+        
+        Ok(())
+    }
+    
+    /// Queue our input for a tick and send it to the other players
+    fn schedule_local_commands(&mut self, tick: u64, commands: Vec<Command>) -> Result<()> {
+        if !commands.is_empty() {
+            self.bandwidth.record_commands(batch::encode_batch(&commands)?.len(), serialize(&commands)?.len());
+        }
+        
         let net_command = NetworkCommand {
-            tick: self.current_tick + MAX_TICK_LEAD, // Commands will be executed in the future
+            tick,
             player_id: self.local_player_id,
-            commands: std::mem::take(&mut self.pending_commands),
+            commands,
         };
         
-        // Send command to all players (or just host if client)
+        // Our own commands go through the same schedule as everyone else's
+        self.command_queue
+            .entry(net_command.tick)
+            .or_insert_with(HashMap::new)
+            .insert(self.local_player_id, net_command.commands.clone());
+        self.last_scheduled_tick = tick;
+        
+        self.broadcast(NetworkMessage::Commands(net_command))
+    }
+    
+    /// Commands of every player for the current tick, advancing to the next tick.
+    /// Returns `None` while some player's input is still missing; the simulation must stall.
+    pub fn receive_commands(&mut self) -> Option<HashMap<u8, Vec<Command>>> {
+        if !self.active {
+            return Some(HashMap::new());
+        }
+        
+        if !self.is_tick_ready(self.current_tick) {
+            return None;
+        }
+        
+        let commands = self.command_queue.remove(&self.current_tick).unwrap_or_default();
+        self.history.push((self.current_tick, commands.clone()));
+        self.current_tick += 1;
+        Some(commands)
+    }
+    
+    /// Ticks still to replay after rejoining; the engine runs these as fast as it can
+    pub fn ticks_behind(&self) -> u64 {
+        self.catch_up_tick.saturating_sub(self.current_tick)
+    }
+    
+    /// Whether we lost the host and are asking to be let back in
+    pub fn is_rejoining(&self) -> bool {
+        self.rejoining_since.is_some()
+    }
+    
+    /// Ticks ahead our input is currently scheduled
+    pub fn input_delay(&self) -> u64 {
+        self.input_delay
+    }
+    
+    /// How a player's link to the host is doing; None for the host itself, and until a ping was answered
+    pub fn connection_quality(&self, player_id: u8) -> Option<ConnectionQuality> {
+        if player_id == self.host_id {
+            return None;
+        }
         if self.is_host {
-            for (&player_id, player_info) in self.players.iter() {
-                if player_id != self.local_player_id {
-                    self.send_to(
-                        NetworkMessage::Commands(net_command.clone()),
-                        player_info.address,
-                    )?;
-                }
-            }
+            return self.players.get(&player_id)?.link.quality(timestamp_ms());
+        }
+        if player_id == self.local_player_id {
+            return self.players.get(&self.host_id)?.link.quality(timestamp_ms());
+        }
+        self.players.get(&player_id)?.reported_quality
+    }
+    
+    /// Our own connection for the HUD: a client's link to the host, or for the host the worst link
+    /// among its players, since the slowest player sets everyone's pace
+    pub fn local_connection_quality(&self) -> Option<ConnectionQuality> {
+        if !self.is_host {
+            return self.connection_quality(self.local_player_id);
+        }
+        self.players
+            .iter()
+            .filter(|(&player_id, player)| player_id != self.local_player_id && player.connected)
+            .filter_map(|(&player_id, _)| self.connection_quality(player_id))
+            .min_by_key(|quality| (quality.bars(), std::cmp::Reverse(quality.rtt_ms)))
+    }
+    
+    /// Traffic over the last second, for the performance overlay
+    pub fn bandwidth(&self) -> BandwidthStats {
+        self.bandwidth.stats()
+    }
+    
+    /// Whether the game is paused for everyone, and by whom
+    pub fn pause_state(&self) -> PauseState {
+        self.pause.state(Instant::now())
+    }
+    
+    /// Whether the engine must hold its ticks, during the resume countdown too
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+    
+    /// Pauses a player has left this game
+    pub fn pauses_left(&self, player_id: u8) -> u8 {
+        self.pause.pauses_left(player_id)
+    }
+    
+    /// Whether we may resume the pause now; others only can once it has gone on for a while
+    pub fn can_resume(&self) -> bool {
+        self.pause.can_resume(self.local_player_id, Instant::now())
+    }
+    
+    /// Ask to pause the game for everyone; the host decides, against our pause budget
+    pub fn request_pause(&mut self) -> Result<()> {
+        self.request_pause_change(true)
+    }
+    
+    /// Ask to start the countdown to resume
+    pub fn request_resume(&mut self) -> Result<()> {
+        self.request_pause_change(false)
+    }
+    
+    fn request_pause_change(&mut self, paused: bool) -> Result<()> {
+        if !self.active || !self.in_game {
+            return Ok(());
+        }
+        if self.is_host {
+            self.decide_pause(self.local_player_id, paused)
         } else {
-            // Send only to host
-            self.send_to_host(NetworkMessage::Commands(net_command))?;
+            self.send_to_host(NetworkMessage::PauseRequest { paused })
+        }
+    }
+    
+    /// Host only: grant or refuse a player's pause or resume, and tell everyone if it was granted
+    fn decide_pause(&mut self, player_id: u8, paused: bool) -> Result<()> {
+        let now = Instant::now();
+        let state = if paused { self.pause.pause(player_id, now) } else { self.pause.resume(player_id, now) };
+        match state {
+            Some(state) => self.broadcast(NetworkMessage::Pause(state)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Run the resume countdown out; the host also ends pauses that have gone on too long
+    fn update_pause(&mut self, now: Instant) -> Result<()> {
+        if self.is_host {
+            if let Some(state) = self.pause.expire(now) {
+                self.broadcast(NetworkMessage::Pause(state))?;
+            }
+        }
+        self.pause.update(now);
+        Ok(())
+    }
+    
+    /// Whether input from every participant has arrived for a tick
+    pub fn is_tick_ready(&self, tick: u64) -> bool {
+        // Nobody could schedule commands this soon after the start
+        if tick < self.start_tick + INPUT_DELAY_TICKS {
+            return true;
         }
         
-        self.last_sent_commands_tick = self.current_tick;
+        match self.command_queue.get(&tick) {
+            Some(received) => self.participants.iter().all(|player_id| received.contains_key(player_id)),
+            None => false,
+        }
+    }
+    
+    /// Players whose input for the current tick hasn't arrived yet
+    pub fn waiting_on(&self) -> Vec<u8> {
+        let received = self.command_queue.get(&self.current_tick);
+        let mut waiting: Vec<u8> = self.participants
+            .iter()
+            .copied()
+            .filter(|player_id| !received.map(|r| r.contains_key(player_id)).unwrap_or(false))
+            .collect();
+        waiting.sort_unstable();
+        waiting
+    }
+    
+    pub fn local_player_id(&self) -> u8 {
+        self.local_player_id
+    }
+    
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+    
+    /// Record our world checksum for a tick and share it with the other players
+    pub fn submit_checksum(&mut self, tick: u64, checksum: u64) -> Result<()> {
+        // The others compared replayed ticks long ago
+        if !self.active || self.ticks_behind() > 0 {
+            return Ok(());
+        }
         
+        self.record_checksum(tick, self.local_player_id, checksum);
+        self.broadcast(NetworkMessage::Checksum {
+            tick,
+            player_id: self.local_player_id,
+            checksum,
+            epoch: self.epoch,
+        })
+    }
+    
+    /// First desync found since the latest resync, if any
+    pub fn desync(&self) -> Option<&DesyncReport> {
+        self.desync.as_ref()
+    }
+    
+    /// Host only: after a desync, replace everyone's world with ours. `snapshot` is our world at the start
+    /// of the current tick, from `savegame::capture_snapshot`; it goes to everyone as a large message, which
+    /// the transport fragments, and comes back from `take_resync` for us to restore as well, so every
+    /// machine rebuilds its world the same way
+    pub fn send_resync(&mut self, snapshot: Vec<u8>) -> Result<()> {
+        if !self.is_host {
+            return Err(anyhow::anyhow!("Only the host can resync the game"));
+        }
+        
+        let snapshot = ResyncSnapshot { tick: self.current_tick, epoch: self.epoch + 1, data: snapshot };
+        self.broadcast(NetworkMessage::Resync(snapshot.clone()))?;
+        self.apply_resync(snapshot);
         Ok(())
     }
     
-    pub fn receive_commands(&mut self) -> HashMap<u8, Vec<Command>> {
-        // Get commands for current tick
-        match self.command_queue.remove(&self.current_tick) {
-            Some(commands) => {
-                // Advance tick
-                self.current_tick += 1;
-                commands
-            }
-            None => {
-                // If no commands for this tick, still advance unless we're too far ahead
-                let min_tick = self.players.values()
-                    .map(|p| p.last_tick_received)
-                    .min()
-                    .unwrap_or(0);
-                
-                if self.current_tick - min_tick < MAX_TICK_LEAD {
-                    self.current_tick += 1;
-                }
-                
-                HashMap::new()
+    /// A resync for the engine to restore before running another tick
+    pub fn take_resync(&mut self) -> Option<ResyncSnapshot> {
+        self.pending_resync.take()
+    }
+    
+    /// Start over from a snapshot's tick with fresh checksums
+    fn apply_resync(&mut self, snapshot: ResyncSnapshot) {
+        self.jump_to_tick(snapshot.tick);
+        self.epoch = snapshot.epoch;
+        self.checksums.clear();
+        self.desync = None;
+        self.last_resync = Some(snapshot.clone());
+        self.pending_resync = Some(snapshot);
+    }
+    
+    /// Move the schedule to a tick: ticks we ran past it go back in the queue to run again, and ticks we
+    /// hadn't reached yet are taken as run, their input going into the history
+    fn jump_to_tick(&mut self, tick: u64) {
+        let rerun = self.history.partition_point(|(history_tick, _)| *history_tick < tick);
+        let rerun: Vec<(u64, TickCommands)> = self.history.drain(rerun..).collect();
+        self.command_queue.extend(rerun);
+        
+        let mut skipped: Vec<u64> = self.command_queue.keys().copied().filter(|&queued_tick| queued_tick < tick).collect();
+        skipped.sort_unstable();
+        for skipped_tick in skipped {
+            if let Some(commands) = self.command_queue.remove(&skipped_tick) {
+                self.history.push((skipped_tick, commands));
             }
         }
+        self.current_tick = tick;
+    }
+    
+    fn record_checksum(&mut self, tick: u64, player_id: u8, checksum: u64) {
+        let tick_checksums = self.checksums.entry(tick).or_insert_with(HashMap::new);
+        tick_checksums.insert(player_id, checksum);
+        
+        let mismatch = tick_checksums.values().any(|other| *other != checksum);
+        if mismatch && self.desync.is_none() {
+            self.desync = Some(DesyncReport {
+                tick,
+                checksums: tick_checksums.clone(),
+            });
+        }
+        
+        // Forget checksums nobody will compare against anymore
+        let oldest = tick.saturating_sub(CHECKSUM_HISTORY);
+        self.checksums.retain(|&checksum_tick, _| checksum_tick >= oldest);
     }
     
     fn handle_message(&mut self, message: NetworkMessage, src_addr: SocketAddr) -> Result<()> {
+        // Hearing from a player shows their connection is alive; one we gave up on must rejoin first
+        if let Some(player) = self.player_at(src_addr).and_then(|player_id| self.players.get_mut(&player_id)) {
+            if !player.connected && !matches!(message, NetworkMessage::Rejoin { .. } | NetworkMessage::Leave { .. }) {
+                return Ok(());
+            }
+            player.last_heard = Instant::now();
+        }
+        
         match message {
             NetworkMessage::Commands(cmd) => {
-                // Store commands in queue for appropriate tick
+                // The host already has input for these ticks, or filled it in while the player was gone
+                if self.is_host && self.players.get(&cmd.player_id).map_or(false, |player| cmd.tick <= player.last_tick_received) {
+                    return Ok(());
+                }
+                
+                // Store commands in queue for appropriate tick; after a host migration copies arrive
+                // again, and the first one stands
                 let player_cmds = self.command_queue
                     .entry(cmd.tick)
                     .or_insert_with(HashMap::new);
                 
-                player_cmds.insert(cmd.player_id, cmd.commands);
+                player_cmds.entry(cmd.player_id).or_insert_with(|| cmd.commands.clone());
                 
                 // Update last tick received for this player
                 if let Some(player) = self.players.get_mut(&cmd.player_id) {
-                    player.last_tick_received = cmd.tick;
+                    player.last_tick_received = player.last_tick_received.max(cmd.tick);
                 }
                 
                 // If host, relay commands to other players
@@ -238,50 +1237,145 @@ impl LockstepNetwork {
                     }
                 }
             }
-            NetworkMessage::Hello { player_id, name } => {
+            NetworkMessage::Hello { player_id, name, rejoin_token } => {
                 if self.is_host {
+                    // A repeated hello means our answer got lost; answer again
+                    if let Some(known_id) = self.player_at(src_addr) {
+                        return self.send_to(
+                            NetworkMessage::Hello {
+                                player_id: known_id,
+                                name: self.players[&self.local_player_id].name.clone(),
+                                rejoin_token: self.players[&known_id].rejoin_token,
+                            },
+                            src_addr,
+                        );
+                    }
+                    
+                    // Nobody joins a full lobby or a game already under way
+                    if self.lobby.len() >= MAX_LOBBY_PLAYERS || self.in_game {
+                        info!(target: "net", "Refused {} ({}): lobby closed", name, src_addr);
+                        return Ok(());
+                    }
+                    
                     // Assign a player ID and add to our list
                     let new_player_id = self.players.keys().max().unwrap_or(&0) + 1;
                     
-                    self.players.insert(
-                        new_player_id,
-                        PlayerInfo {
-                            address: src_addr,
-                            name,
-                            last_tick_received: self.current_tick,
-                            ping_ms: 0,
-                        },
-                    );
+                    let mut player = PlayerInfo::new(src_addr, name.clone(), self.current_tick);
+                    player.rejoin_token = new_rejoin_token();
+                    let rejoin_token = player.rejoin_token;
+                    self.players.insert(new_player_id, player);
+                    
+                    // Seat the newcomer with the first free color on their own team
+                    let color = (0..PLAYER_COLORS.len() as u8)
+                        .find(|color| self.lobby.iter().all(|slot| slot.color != *color))
+                        .unwrap_or(0);
+                    self.lobby.push(LobbySlot {
+                        player_id: new_player_id,
+                        name,
+                        color,
+                        team: new_player_id,
+                        ready: false,
+                    });
                     
                     // Send join confirmation with assigned ID
                     self.send_to(
                         NetworkMessage::Hello {
                             player_id: new_player_id,
-                            name: "Host".to_string(),
+                            name: self.players[&self.local_player_id].name.clone(),
+                            rejoin_token,
                         },
                         src_addr,
                     )?;
+                    
+                    // Everyone now waits for the new player's input
+                    self.participants.insert(new_player_id);
+                    self.broadcast_roster()?;
+                    self.broadcast_lobby()?;
+                    self.announce(format!("{} joined the game", self.players[&new_player_id].name))?;
                 } else if player_id != 255 {
                     // We've been assigned a player ID by the host
                     self.local_player_id = player_id;
+                    self.rejoin_token = rejoin_token;
+                    self.participants.insert(self.host_id);
+                    self.participants.insert(player_id);
                     
                     // Add host to our players list
-                    self.players.insert(
-                        0, // Host is always player 0
-                        PlayerInfo {
-                            address: src_addr,
-                            name,
-                            last_tick_received: self.current_tick,
-                            ping_ms: 0,
-                        },
-                    );
+                    self.players.insert(self.host_id, PlayerInfo::new(src_addr, name, self.current_tick));
                 }
             }
-            NetworkMessage::Start { seed, start_tick } => {
+            NetworkMessage::Start(start) => {
                 // Game starting command (host to clients)
                 if !self.is_host {
-                    self.current_tick = start_tick;
-                    // Initialize game with seed
+                    self.begin_game(start);
+                }
+            }
+            NetworkMessage::Lobby { slots } => {
+                // Host shares the lobby whenever it changes
+                if !self.is_host {
+                    self.lobby = slots;
+                }
+            }
+            NetworkMessage::SlotUpdate(mut slot) => {
+                // Players may only change their own slot, and not to a color someone else has
+                if self.is_host {
+                    let player_id = match self.player_at(src_addr) {
+                        Some(player_id) => player_id,
+                        None => return Ok(()),
+                    };
+                    slot.player_id = player_id;
+                    
+                    let color_taken = self.lobby.iter().any(|other| other.player_id != player_id && other.color == slot.color);
+                    if let Some(existing) = self.lobby.iter_mut().find(|existing| existing.player_id == player_id) {
+                        if color_taken || slot.color as usize >= PLAYER_COLORS.len() {
+                            slot.color = existing.color;
+                        }
+                        *existing = slot;
+                    }
+                    
+                    // Echo back even a refused change so the sender's view stays correct
+                    self.broadcast_lobby()?;
+                }
+            }
+            NetworkMessage::Chat(mut message) => {
+                // If host, relay chat to its audience under the sender's real ID
+                if self.is_host {
+                    let player_id = match self.player_at(src_addr) {
+                        Some(player_id) => player_id,
+                        None => return Ok(()),
+                    };
+                    if message.channel == ChatChannel::System {
+                        return Ok(());
+                    }
+                    message.player_id = player_id;
+                    message.text = message.text.chars().take(MAX_CHAT_LENGTH).collect();
+                    
+                    let team = self.team_of(player_id);
+                    for (&other_id, player_info) in self.players.iter() {
+                        let audience = message.channel == ChatChannel::All || self.team_of(other_id) == team;
+                        if other_id != player_id && other_id != self.local_player_id && audience {
+                            self.send_to(NetworkMessage::Chat(message.clone()), player_info.address)?;
+                        }
+                    }
+                    
+                    // Team chat from the other side isn't for us either
+                    if message.channel == ChatChannel::Allies && self.team_of(self.local_player_id) != team {
+                        return Ok(());
+                    }
+                }
+                
+                self.chat.push(message);
+            }
+            NetworkMessage::Leave { player_id } => {
+                if self.is_host {
+                    // Stop waiting on the player and tell everyone else
+                    let player_id = match self.player_at(src_addr) {
+                        Some(player_id) => player_id,
+                        None => return Ok(()),
+                    };
+                    let name = self.remove_player(player_id)?;
+                    self.announce(format!("{} left the game", name))?;
+                } else if player_id == self.host_id && self.player_at(src_addr) == Some(self.host_id) {
+                    self.migrate_host()?;
                 }
             }
             NetworkMessage::Ping(timestamp) => {
@@ -289,21 +1383,184 @@ impl LockstepNetwork {
                 self.send_to(NetworkMessage::Pong(timestamp), src_addr)?;
             }
             NetworkMessage::Pong(timestamp) => {
-                // Calculate ping time
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
+                // The answer tells the round trip time, and which pings went missing
+                let now = timestamp_ms();
+                if let Some(player) = self.player_at(src_addr).and_then(|player_id| self.players.get_mut(&player_id)) {
+                    player.link.record_pong(timestamp, now);
+                }
+            }
+            NetworkMessage::Latency { input_delay, players } => {
+                // The host measures everyone's link and picks the delay
+                if self.is_host || self.player_at(src_addr) != Some(self.host_id) {
+                    return Ok(());
+                }
+                self.input_delay = input_delay.clamp(INPUT_DELAY_TICKS, MAX_INPUT_DELAY_TICKS);
+                for player in self.players.values_mut() {
+                    player.reported_quality = None;
+                }
+                for (player_id, quality) in players {
+                    if let Some(player) = self.players.get_mut(&player_id) {
+                        player.reported_quality = Some(quality);
+                    }
+                }
+            }
+            NetworkMessage::Roster { player_ids } => {
+                // Host tells clients who takes part in the lockstep
+                if !self.is_host {
+                    self.participants = player_ids.into_iter().collect();
+                }
+            }
+            NetworkMessage::Checksum { tick, player_id, checksum, epoch } => {
+                // Worlds from before the latest resync are expected to disagree
+                if epoch != self.epoch {
+                    return Ok(());
+                }
+                self.record_checksum(tick, player_id, checksum);
                 
-                let ping_ms = (now - timestamp) as u32;
+                // If host, relay checksums to other players
+                if self.is_host {
+                    for (&other_id, player_info) in self.players.iter() {
+                        if other_id != player_id && other_id != self.local_player_id {
+                            self.send_to(
+                                NetworkMessage::Checksum { tick, player_id, checksum, epoch },
+                                player_info.address,
+                            )?;
+                        }
+                    }
+                }
+            }
+            NetworkMessage::Peers { peers } => {
+                // Remember where the other players are, in case we have to carry on without the host
+                if !self.is_host {
+                    let (local_player_id, host_id) = (self.local_player_id, self.host_id);
+                    self.players.retain(|player_id, _| *player_id == host_id || peers.iter().any(|(peer_id, _)| peer_id == player_id));
+                    for (player_id, address) in peers {
+                        if player_id == local_player_id || player_id == host_id {
+                            continue;
+                        }
+                        let name = self.slot_name(player_id);
+                        let current_tick = self.current_tick;
+                        self.players
+                            .entry(player_id)
+                            .and_modify(|player| player.address = address)
+                            .or_insert_with(|| PlayerInfo::new(address, name, current_tick));
+                    }
+                }
+            }
+            NetworkMessage::Rejoin { player_id, rejoin_token } => {
+                // Only a player we lost can take their slot back, and only with the token we gave them
+                if !self.is_host || !self.in_game || player_id == self.local_player_id {
+                    return Ok(());
+                }
+                match self.players.get(&player_id) {
+                    Some(player) if !player.connected && player.rejoin_token == rejoin_token => {}
+                    _ => return Ok(()),
+                }
                 
-                // Update player's ping time
-                for player in self.players.values_mut() {
-                    if player.address == src_addr {
-                        player.ping_ms = ping_ms;
-                        break;
+                // Empty input covers the ticks until theirs picks up again
+                self.fill_in_for_dropped_players()?;
+                let name = match self.players.get_mut(&player_id) {
+                    Some(player) => {
+                        player.address = src_addr;
+                        player.connected = true;
+                        player.last_heard = Instant::now();
+                        player.link.reset();
+                        player.name.clone()
                     }
+                    None => return Ok(()),
+                };
+                self.transport.reset_peer(src_addr);
+                
+                let start = match &self.game_info {
+                    Some(start) => start.clone(),
+                    None => return Ok(()),
+                };
+                let mut participants: Vec<u8> = self.participants.iter().copied().collect();
+                participants.sort_unstable();
+                let mut queued: Vec<(u64, TickCommands)> = self.command_queue.iter().map(|(&tick, commands)| (tick, commands.clone())).collect();
+                queued.sort_by_key(|(tick, _)| *tick);
+                
+                // Ticks before the latest resync would rebuild the world that went out of step
+                let resync = self.last_resync.clone();
+                let resync_tick = resync.as_ref().map_or(0, |resync| resync.tick);
+                let history = self.history.iter().filter(|(tick, _)| *tick >= resync_tick).cloned().collect();
+                self.send_to(
+                    NetworkMessage::Resume {
+                        start,
+                        current_tick: self.current_tick,
+                        participants,
+                        history,
+                        queued,
+                        resync,
+                    },
+                    src_addr,
+                )?;
+                
+                // A paused game stays paused for them too
+                if self.pause.is_paused() {
+                    self.send_to(NetworkMessage::Pause(self.pause.state(Instant::now())), src_addr)?;
+                }
+                
+                // They may be back at a new address
+                self.broadcast_roster()?;
+                self.announce(format!("{} rejoined the game", name))?;
+            }
+            NetworkMessage::Resume { start, current_tick, participants, history, queued, resync } => {
+                // The host let us back in: rebuild the game from its start and replay every tick we missed
+                if self.is_host || self.rejoining_since.is_none() {
+                    return Ok(());
+                }
+                
+                self.rejoining_since = None;
+                self.command_queue = history.into_iter().chain(queued).collect();
+                self.history.clear();
+                self.pending_commands.clear();
+                self.last_sent_commands_tick = None;
+                self.checksums.clear();
+                self.desync = None;
+                self.begin_game(start);
+                self.participants = participants.into_iter().collect();
+                self.catch_up_tick = current_tick;
+                
+                // The replay starts from the host's world at the latest resync, if the game had one
+                if let Some(resync) = resync {
+                    self.apply_resync(resync);
+                }
+                
+                // Our input picks up after the last tick the host already has some for, filled in or ours
+                let local_player_id = self.local_player_id;
+                if let Some(last_scheduled) = self.command_queue
+                    .iter()
+                    .filter(|(_, commands)| commands.contains_key(&local_player_id))
+                    .map(|(&tick, _)| tick)
+                    .max()
+                {
+                    self.last_scheduled_tick = self.last_scheduled_tick.max(last_scheduled);
                 }
+                self.post_system_message("Rejoined the game; catching up".to_string());
+            }
+            NetworkMessage::Resync(snapshot) => {
+                // Only the host's world counts
+                if self.is_host || !self.in_game || self.player_at(src_addr) != Some(self.host_id) {
+                    return Ok(());
+                }
+                self.apply_resync(snapshot);
+            }
+            NetworkMessage::PauseRequest { paused } => {
+                // The host keeps everyone's pause budget
+                if !self.is_host || !self.in_game {
+                    return Ok(());
+                }
+                if let Some(player_id) = self.player_at(src_addr) {
+                    self.decide_pause(player_id, paused)?;
+                }
+            }
+            NetworkMessage::Pause(state) => {
+                // Only the host pauses and resumes the game
+                if self.is_host || self.player_at(src_addr) != Some(self.host_id) {
+                    return Ok(());
+                }
+                self.pause.apply(state, Instant::now());
             }
             NetworkMessage::Sync { current_tick } => {
                 // Handle sync message (used for catching up)
@@ -318,16 +1575,34 @@ impl LockstepNetwork {
     }
     
     fn send_to(&self, message: NetworkMessage, addr: SocketAddr) -> Result<()> {
-        if let Some(socket) = &self.socket {
-            let data = serialize(&message)?;
-            socket.send_to(&data, addr)?;
+        // Players we gave up on hear nothing until they rejoin, so they notice and their channel starts afresh
+        if self.players.values().any(|player| !player.connected && player.address == addr) {
+            return Ok(());
+        }
+        
+        let channel = message.channel();
+        let data = serialize(&message)?;
+        self.bandwidth.record_sent(data.len());
+        self.transport.send_to(&data, addr, channel)
+    }
+    
+    /// Send to every other player (or just the host if client, which relays)
+    fn broadcast(&self, message: NetworkMessage) -> Result<()> {
+        if self.is_host {
+            for (&player_id, player_info) in self.players.iter() {
+                if player_id != self.local_player_id {
+                    self.send_to(message.clone(), player_info.address)?;
+                }
+            }
+        } else {
+            self.send_to_host(message)?;
         }
         
         Ok(())
     }
     
     fn send_to_host(&self, message: NetworkMessage) -> Result<()> {
-        if let Some(host) = self.players.get(&0) {
+        if let Some(host) = self.players.get(&self.host_id) {
             self.send_to(message, host.address)?;
         }
         
@@ -1,21 +1,60 @@
 pub mod commands;
 pub mod replay;
 pub mod lockstep;
+pub mod checksum;
+pub mod chat;
+pub mod channel;
+pub mod framing;
+pub mod tcp;
+pub mod discovery;
+pub mod upnp;
+pub mod latency;
+pub mod pause;
+pub mod batch;
+pub mod bandwidth;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::time::Instant;
+use tracing::warn;
+
+use crate::networking::channel::{Channel, Packet, ReliableChannel};
+use crate::networking::framing::{Fragment, Fragmenter, Reassembler, MAX_FRAGMENT_SIZE};
+use crate::networking::tcp::TcpTransport;
 
 /// Trait for network transport implementations
 pub trait NetworkTransport {
     /// Initialize the transport
     fn init(&mut self) -> Result<()>;
     
+    /// Accept players on a port, as a host
+    fn listen(&mut self, port: u16) -> Result<()>;
+    
+    /// Get ready to talk to a host, as a client
+    fn connect(&mut self, addr: SocketAddr) -> Result<()>;
+    
     /// Send data to a specific address
-    fn send_to(&self, data: &[u8], addr: SocketAddr) -> Result<()>;
+    fn send_to(&self, data: &[u8], addr: SocketAddr, channel: Channel) -> Result<()>;
     
     /// Receive data from any address
     fn recv_from(&self) -> Result<Option<(Vec<u8>, SocketAddr)>>;
     
+    /// Resend or finish sending whatever is overdue; called every frame
+    fn update(&self) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Peers given up on since the last call, e.g. after too many unacknowledged resends
+    fn take_lost_peers(&self) -> Vec<SocketAddr> {
+        Vec::new()
+    }
+    
+    /// Forget everything kept about a peer, so messages to and from it start afresh, e.g. when it rejoins
+    fn reset_peer(&self, _addr: SocketAddr) {}
+    
     /// Close the transport
     fn close(&mut self);
     
@@ -23,10 +62,72 @@ pub trait NetworkTransport {
     fn is_connected(&self) -> bool;
 }
 
-/// UDP transport implementation
+/// Which transport a session runs over, chosen when it is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransportKind {
+    /// Datagrams with our own acks and resends for the reliable channel
+    Udp,
+    /// One stream per peer, for networks where UDP doesn't get through
+    Tcp,
+}
+
+impl Default for TransportKind {
+    fn default() -> Self {
+        TransportKind::Udp
+    }
+}
+
+impl TransportKind {
+    pub fn create(self) -> Box<dyn NetworkTransport> {
+        match self {
+            TransportKind::Udp => Box::new(UdpTransport::new()),
+            TransportKind::Tcp => Box::new(TcpTransport::new()),
+        }
+    }
+}
+
+/// Largest datagram a UDP transport sends or takes in: one fragment plus packet and fragment headers
+const MAX_DATAGRAM_SIZE: usize = MAX_FRAGMENT_SIZE + 128;
+
+/// Reliable channels, fragments of messages still arriving and messages received but not yet
+/// handed out, per UDP transport
+#[derive(Default)]
+struct UdpState {
+    peers: HashMap<SocketAddr, ReliableChannel>,
+    fragmenter: Fragmenter,
+    reassembler: Reassembler,
+    inbox: VecDeque<(Vec<u8>, SocketAddr)>,
+    lost_peers: Vec<SocketAddr>,
+}
+
+impl UdpState {
+    /// Take in one fragment's bytes, queueing the message it completes
+    fn receive_fragment(&mut self, data: &[u8], addr: SocketAddr) {
+        let fragment = match bincode::deserialize::<Fragment>(data) {
+            Ok(fragment) => fragment,
+            Err(e) => {
+                warn!(target: "net", "Dropped malformed fragment from {}: {}", addr, e);
+                return;
+            }
+        };
+        let packed = match self.reassembler.add(addr, fragment, Instant::now()) {
+            Some(packed) => packed,
+            None => return,
+        };
+        match framing::unpack(&packed) {
+            Ok(message) => self.inbox.push_back((message, addr)),
+            Err(e) => warn!(target: "net", "Dropped message from {}: {}", addr, e),
+        }
+    }
+}
+
+/// UDP transport implementation. Messages are compressed when large and cut into fragments that
+/// each fit a datagram. Reliable fragments are numbered, acknowledged by the receiver and resent
+/// until they are, and held back on arrival until everything sent before them is in.
 pub struct UdpTransport {
     socket: Option<std::net::UdpSocket>,
     is_connected: bool,
+    state: RefCell<UdpState>,
 }
 
 impl UdpTransport {
@@ -34,6 +135,7 @@ impl UdpTransport {
         Self {
             socket: None,
             is_connected: false,
+            state: RefCell::new(UdpState::default()),
         }
     }
     
@@ -44,6 +146,15 @@ impl UdpTransport {
         self.is_connected = true;
         Ok(())
     }
+    
+    fn send_packet(&self, packet: &Packet, addr: SocketAddr) -> Result<()> {
+        if let Some(socket) = &self.socket {
+            socket.send_to(&bincode::serialize(packet)?, addr)?;
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Socket not initialized"))
+        }
+    }
 }
 
 impl NetworkTransport for UdpTransport {
@@ -55,36 +166,123 @@ impl NetworkTransport for UdpTransport {
         Ok(())
     }
     
-    fn send_to(&self, data: &[u8], addr: SocketAddr) -> Result<()> {
-        if let Some(socket) = &self.socket {
-            socket.send_to(data, addr)?;
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Socket not initialized"))
+    fn listen(&mut self, port: u16) -> Result<()> {
+        self.bind(&format!("0.0.0.0:{}", port))
+    }
+    
+    fn connect(&mut self, _addr: SocketAddr) -> Result<()> {
+        // Datagrams need no connection; any port will do for the host's answers
+        self.init()
+    }
+    
+    fn send_to(&self, data: &[u8], addr: SocketAddr, channel: Channel) -> Result<()> {
+        let now = Instant::now();
+        let mut packets = Vec::new();
+        {
+            let mut state = self.state.borrow_mut();
+            for fragment in state.fragmenter.split(&framing::pack(data))? {
+                let fragment = bincode::serialize(&fragment)?;
+                packets.push(match channel {
+                    Channel::ReliableOrdered => state.peers.entry(addr).or_default().send(&fragment, now),
+                    Channel::Unreliable => Packet::Unreliable(fragment),
+                });
+            }
         }
+        
+        for packet in packets {
+            self.send_packet(&packet, addr)?;
+        }
+        Ok(())
     }
     
     fn recv_from(&self) -> Result<Option<(Vec<u8>, SocketAddr)>> {
-        if let Some(socket) = &self.socket {
-            let mut buf = [0u8; 1024 * 16]; // 16KB buffer
-            match socket.recv_from(&mut buf) {
-                Ok((len, addr)) => {
-                    Ok(Some((buf[0..len].to_vec(), addr)))
-                }
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return Err(anyhow::anyhow!("Socket not initialized")),
+        };
+        
+        loop {
+            if let Some(message) = self.state.borrow_mut().inbox.pop_front() {
+                return Ok(Some(message));
+            }
+            
+            let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+            let (len, addr) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
                 Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     // No data available
-                    Ok(None)
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            
+            let packet = match bincode::deserialize::<Packet>(&buf[0..len]) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    warn!(target: "net", "Dropped malformed packet from {}: {}", addr, e);
+                    continue;
+                }
+            };
+            match packet {
+                Packet::Unreliable(payload) => self.state.borrow_mut().receive_fragment(&payload, addr),
+                Packet::Reliable { sequence, payload } => {
+                    let delivered = match self.state.borrow_mut().peers.entry(addr).or_default().receive(sequence, payload) {
+                        Some(delivered) => delivered,
+                        None => continue, // Too far ahead to hold; left unacked to come again
+                    };
+                    // Ack even repeats; the ack for the first copy may be what got lost
+                    self.send_packet(&Packet::Ack { sequence }, addr)?;
+                    let mut state = self.state.borrow_mut();
+                    for payload in delivered {
+                        state.receive_fragment(&payload, addr);
+                    }
+                }
+                Packet::Ack { sequence } => {
+                    if let Some(peer) = self.state.borrow_mut().peers.get_mut(&addr) {
+                        peer.acknowledge(sequence);
+                    }
                 }
-                Err(e) => Err(e.into()),
             }
-        } else {
-            Err(anyhow::anyhow!("Socket not initialized"))
         }
     }
     
+    fn update(&self) -> Result<()> {
+        let now = Instant::now();
+        let mut resends = Vec::new();
+        let mut state = self.state.borrow_mut();
+        state.reassembler.expire(now);
+        let UdpState { peers, lost_peers, .. } = &mut *state;
+        peers.retain(|addr, peer| match peer.due_resends(now) {
+            Ok(packets) => {
+                resends.extend(packets.into_iter().map(|packet| (packet, *addr)));
+                true
+            }
+            Err(sequence) => {
+                // The peer is presumed gone; the lockstep decides whether it may rejoin
+                warn!(target: "net", "Gave up on {}: packet {} was never acknowledged", addr, sequence);
+                lost_peers.push(*addr);
+                false
+            }
+        });
+        
+        for (packet, addr) in resends {
+            self.send_packet(&packet, addr)?;
+        }
+        Ok(())
+    }
+    
+    fn take_lost_peers(&self) -> Vec<SocketAddr> {
+        std::mem::take(&mut self.state.borrow_mut().lost_peers)
+    }
+    
+    fn reset_peer(&self, addr: SocketAddr) {
+        self.state.borrow_mut().peers.remove(&addr);
+    }
+    
     fn close(&mut self) {
         self.socket = None;
         self.is_connected = false;
+        *self.state.borrow_mut() = UdpState::default();
     }
     
     fn is_connected(&self) -> bool {
@@ -113,23 +311,16 @@ impl NetworkSession {
     }
     
     pub fn host_game(&mut self, port: u16) -> Result<()> {
-        let host_transport = UdpTransport::new();
-        self.transport = Box::new(host_transport);
-        self.transport.init()?;
+        self.transport.listen(port)?;
         self.local_player_id = Some(0); // Host is always player 0
         
-        // In a real implementation, you'd start listening for client connections
-        
         Ok(())
     }
     
     pub fn join_game(&mut self, host_address: &str) -> Result<()> {
-        let client_transport = UdpTransport::new();
-        self.transport = Box::new(client_transport);
-        self.transport.init()?;
-        
         // Connect to host
         let addr: SocketAddr = host_address.parse()?;
+        self.transport.connect(addr)?;
         self.host_addr = Some(addr);
         
         // Send join request
@@ -140,7 +331,7 @@ impl NetworkSession {
         });
         
         let data = bincode::serialize(&join_msg)?;
-        self.transport.send_to(&data, addr)?;
+        self.transport.send_to(&data, addr, Channel::ReliableOrdered)?;
         
         Ok(())
     }
@@ -154,6 +345,7 @@ impl NetworkSession {
         }
         
         // Process incoming network messages
+        self.transport.update()?;
         loop {
             match self.transport.recv_from() {
                 Ok(Some((data, src_addr))) => {
@@ -197,10 +389,10 @@ impl NetworkSession {
         match target_player {
             Some(player_id) => {
                 if let Some(addr) = self.player_addrs.get(&player_id) {
-                    self.transport.send_to(&data, *addr)?;
+                    self.transport.send_to(&data, *addr, Channel::ReliableOrdered)?;
                 } else if let Some(host_addr) = self.host_addr {
                     // If we don't know the player's address, send to host for relay
-                    self.transport.send_to(&data, host_addr)?;
+                    self.transport.send_to(&data, host_addr, Channel::ReliableOrdered)?;
                 } else {
                     return Err(anyhow::anyhow!("Unknown player address and no host address"));
                 }
@@ -210,11 +402,11 @@ impl NetworkSession {
                 if self.local_player_id == Some(0) {
                     // Host broadcasts to all clients
                     for addr in self.player_addrs.values() {
-                        self.transport.send_to(&data, *addr)?;
+                        self.transport.send_to(&data, *addr, Channel::ReliableOrdered)?;
                     }
                 } else if let Some(host_addr) = self.host_addr {
                     // Client sends to host for relay
-                    self.transport.send_to(&data, host_addr)?;
+                    self.transport.send_to(&data, host_addr, Channel::ReliableOrdered)?;
                 } else {
                     return Err(anyhow::anyhow!("No host address"));
                 }
@@ -246,7 +438,7 @@ impl NetworkSession {
             
             if let Ok(data) = bincode::serialize(&leave_msg) {
                 for addr in self.player_addrs.values() {
-                    let _ = self.transport.send_to(&data, *addr);
+                    let _ = self.transport.send_to(&data, *addr, Channel::ReliableOrdered);
                 }
             }
         }
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Times each player may pause a multiplayer game
+pub const PAUSES_PER_PLAYER: u8 = 3;
+
+/// A pause lasting this long starts counting down to resume by itself
+pub const MAX_PAUSE_DURATION: Duration = Duration::from_secs(120);
+
+/// Only the player who paused may resume before this; after it anyone can
+pub const OTHERS_RESUME_AFTER: Duration = Duration::from_secs(15);
+
+/// Warning everyone gets before the game carries on
+pub const RESUME_COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// Whether a multiplayer game is held for everyone, as the host decided
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PauseState {
+    Running,
+    /// Paused by a player, who has `pauses_left` more pauses
+    Paused { player_id: u8, pauses_left: u8 },
+    /// A player resumed; the game carries on when the countdown runs out
+    Resuming { player_id: u8, remaining_ms: u64 },
+}
+
+/// Pause state and budgets of a multiplayer game. Every player keeps one, following the host's
+/// decisions, so a player who takes over as host carries on with the same budgets
+#[derive(Debug)]
+pub struct PauseControl {
+    state: PauseState,
+    paused_since: Option<Instant>,
+    resume_at: Option<Instant>, // When the countdown runs out
+    pauses_used: HashMap<u8, u8>, // Player ID -> pauses taken this game
+}
+
+impl Default for PauseControl {
+    fn default() -> Self {
+        Self {
+            state: PauseState::Running,
+            paused_since: None,
+            resume_at: None,
+            pauses_used: HashMap::new(),
+        }
+    }
+}
+
+impl PauseControl {
+    /// Current state, with the countdown as it stands now
+    pub fn state(&self, now: Instant) -> PauseState {
+        match (self.state, self.resume_at) {
+            (PauseState::Resuming { player_id, .. }, Some(resume_at)) => PauseState::Resuming {
+                player_id,
+                remaining_ms: resume_at.saturating_duration_since(now).as_millis() as u64,
+            },
+            (state, _) => state,
+        }
+    }
+    
+    /// Whether ticks are held, counting down included
+    pub fn is_paused(&self) -> bool {
+        self.state != PauseState::Running
+    }
+    
+    pub fn pauses_left(&self, player_id: u8) -> u8 {
+        PAUSES_PER_PLAYER.saturating_sub(self.pauses_used.get(&player_id).copied().unwrap_or(0))
+    }
+    
+    /// Whether a player may resume the pause now
+    pub fn can_resume(&self, player_id: u8, now: Instant) -> bool {
+        match self.state {
+            PauseState::Paused { player_id: paused_by, .. } => {
+                paused_by == player_id
+                    || self.paused_since.map_or(true, |since| now.duration_since(since) >= OTHERS_RESUME_AFTER)
+            }
+            _ => false,
+        }
+    }
+    
+    /// Host only: a player asks to pause. Returns the state to tell everyone, or None if refused.
+    /// Pausing again during the countdown is allowed, and costs another pause
+    pub fn pause(&mut self, player_id: u8, now: Instant) -> Option<PauseState> {
+        if matches!(self.state, PauseState::Paused { .. }) || self.pauses_left(player_id) == 0 {
+            return None;
+        }
+        
+        let state = PauseState::Paused { player_id, pauses_left: self.pauses_left(player_id) - 1 };
+        self.apply(state, now);
+        Some(state)
+    }
+    
+    /// Host only: a player asks to resume. Returns the countdown to tell everyone, or None if refused
+    pub fn resume(&mut self, player_id: u8, now: Instant) -> Option<PauseState> {
+        if !self.can_resume(player_id, now) {
+            return None;
+        }
+        
+        let state = PauseState::Resuming { player_id, remaining_ms: RESUME_COUNTDOWN.as_millis() as u64 };
+        self.apply(state, now);
+        Some(state)
+    }
+    
+    /// Host only: start the countdown once a pause has gone on too long, in the name of whoever paused
+    pub fn expire(&mut self, now: Instant) -> Option<PauseState> {
+        let player_id = match self.state {
+            PauseState::Paused { player_id, .. } => player_id,
+            _ => return None,
+        };
+        if self.paused_since.map_or(true, |since| now.duration_since(since) < MAX_PAUSE_DURATION) {
+            return None;
+        }
+        
+        let state = PauseState::Resuming { player_id, remaining_ms: RESUME_COUNTDOWN.as_millis() as u64 };
+        self.apply(state, now);
+        Some(state)
+    }
+    
+    /// Take on a state decided by the host; the countdown runs from when it arrived
+    pub fn apply(&mut self, state: PauseState, now: Instant) {
+        match state {
+            PauseState::Running => {
+                self.paused_since = None;
+                self.resume_at = None;
+            }
+            PauseState::Paused { player_id, pauses_left } => {
+                self.pauses_used.insert(player_id, PAUSES_PER_PLAYER.saturating_sub(pauses_left));
+                self.paused_since = Some(now);
+                self.resume_at = None;
+            }
+            PauseState::Resuming { remaining_ms, .. } => {
+                self.resume_at = Some(now + Duration::from_millis(remaining_ms));
+            }
+        }
+        self.state = state;
+    }
+    
+    /// Carry on once the countdown runs out
+    pub fn update(&mut self, now: Instant) {
+        if matches!(self.state, PauseState::Resuming { .. }) && self.resume_at.map_or(false, |resume_at| now >= resume_at) {
+            self.apply(PauseState::Running, now);
+        }
+    }
+    
+    /// Running again with every budget full, for a new game
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
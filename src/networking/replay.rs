@@ -5,7 +5,29 @@ use serde::{Serialize, Deserialize};
 use anyhow::Result;
 
 use crate::engine::input::Command;
+use crate::game::file_format::FileFormat;
 use crate::game::GameState;
+use crate::networking::framing;
+
+/// Current replay format version; bump when the layout of `GameReplay` or `Command` changes, and teach
+/// `load_replay` to migrate the previous layout. Version 2 compresses the body; version 3 names entities
+/// in commands by their network IDs.
+pub const REPLAY_VERSION: u32 = 3;
+
+/// First replay format version whose body is compressed
+const COMPRESSED_REPLAY_VERSION: u32 = 2;
+
+/// Oldest replay format version that can still be migrated and played back. Earlier commands name
+/// entities by `Entity`, which can't be told apart from network IDs on playback
+pub const OLDEST_REPLAY_VERSION: u32 = 3;
+
+/// Header of every replay file
+const REPLAY_FORMAT: FileFormat = FileFormat {
+    kind: "replay",
+    magic: *b"RRPL",
+    version: REPLAY_VERSION,
+    oldest_version: OLDEST_REPLAY_VERSION,
+};
 
 /// Replay metadata and recording information
 #[derive(Debug, Serialize, Deserialize)]
@@ -122,8 +144,8 @@ impl ReplayRecorder {
 
     /// Save replay to a file
     pub fn save_replay(&self, path: &str) -> Result<()> {
-        // Serialize replay data
-        let serialized = bincode::serialize(&self.replay)?;
+        // Serialize and compress replay data behind the versioned header; long games record a lot of commands
+        let serialized = REPLAY_FORMAT.wrap(&framing::compress(&bincode::serialize(&self.replay)?));
 
         // Write to file
         let mut file = File::create(path)?;
@@ -144,8 +166,13 @@ impl ReplayRecorder {
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
 
-        // Deserialize replay data
-        let replay: GameReplay = bincode::deserialize(&buffer)?;
+        // Check the header, then deserialize the body laid out for its version
+        let (version, body) = REPLAY_FORMAT.read_header(path, &buffer)?;
+        let replay: GameReplay = if version >= COMPRESSED_REPLAY_VERSION {
+            REPLAY_FORMAT.decode(path, version, &framing::decompress(body)?)?
+        } else {
+            REPLAY_FORMAT.decode(path, version, body)?
+        };
 
         Ok(replay)
     }
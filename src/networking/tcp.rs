@@ -0,0 +1,197 @@
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use tracing::warn;
+
+use crate::networking::channel::Channel;
+use crate::networking::framing;
+use crate::networking::NetworkTransport;
+
+/// One open stream to a peer, with what is half read from it and what is still to be written
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, incoming: Vec::new(), outgoing: Vec::new() })
+    }
+    
+    /// Write as much of the outgoing data as the socket takes right now
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Read whatever has arrived and split off the complete messages. Err once the peer has closed
+    /// the stream or sent something that can't be a message.
+    fn read_messages(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut buf = [0u8; 1024 * 16];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(anyhow::anyhow!("closed by peer")),
+                Ok(len) => self.incoming.extend_from_slice(&buf[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        
+        // Every message is prefixed with its length
+        let mut messages = Vec::new();
+        while let Some(packed) = framing::read_length_prefixed(&mut self.incoming)? {
+            messages.push(framing::unpack(&packed)?);
+        }
+        Ok(messages)
+    }
+}
+
+/// Everything the transport changes while sending and receiving
+#[derive(Default)]
+struct TcpState {
+    listener: Option<TcpListener>,
+    connections: HashMap<SocketAddr, Connection>,
+    inbox: VecDeque<(Vec<u8>, SocketAddr)>,
+    lost_peers: Vec<SocketAddr>,
+}
+
+/// TCP transport, for networks that drop or block UDP. The stream already delivers everything in
+/// order and needs no fragments, so both channels go over it alike as length-prefixed messages,
+/// compressed when large. Hosts accept connections; clients open one to the host.
+pub struct TcpTransport {
+    state: RefCell<TcpState>,
+    is_connected: bool,
+}
+
+impl TcpTransport {
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(TcpState::default()),
+            is_connected: false,
+        }
+    }
+}
+
+impl NetworkTransport for TcpTransport {
+    fn init(&mut self) -> Result<()> {
+        self.is_connected = true;
+        Ok(())
+    }
+    
+    fn listen(&mut self, port: u16) -> Result<()> {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
+        listener.set_nonblocking(true)?;
+        self.state.borrow_mut().listener = Some(listener);
+        self.is_connected = true;
+        Ok(())
+    }
+    
+    fn connect(&mut self, addr: SocketAddr) -> Result<()> {
+        let connection = Connection::new(TcpStream::connect(addr)?)?;
+        self.state.borrow_mut().connections.insert(addr, connection);
+        self.is_connected = true;
+        Ok(())
+    }
+    
+    fn send_to(&self, data: &[u8], addr: SocketAddr, _channel: Channel) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let connection = match state.connections.get_mut(&addr) {
+            Some(connection) => connection,
+            None => return Err(anyhow::anyhow!("No connection to {}", addr)),
+        };
+        
+        framing::write_length_prefixed(&mut connection.outgoing, &framing::pack(data));
+        if let Err(e) = connection.flush() {
+            state.connections.remove(&addr);
+            state.lost_peers.push(addr);
+            return Err(anyhow::anyhow!("Lost connection to {}: {}", addr, e));
+        }
+        Ok(())
+    }
+    
+    fn recv_from(&self) -> Result<Option<(Vec<u8>, SocketAddr)>> {
+        let mut state = self.state.borrow_mut();
+        if let Some(message) = state.inbox.pop_front() {
+            return Ok(Some(message));
+        }
+        
+        // Take in players connecting to us
+        let mut accepted = Vec::new();
+        if let Some(listener) = &state.listener {
+            loop {
+                match listener.accept() {
+                    Ok((stream, addr)) => accepted.push((addr, Connection::new(stream)?)),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        state.connections.extend(accepted);
+        
+        // Closed streams are reported as lost peers; the lockstep decides whether they may rejoin
+        let TcpState { connections, inbox, lost_peers, .. } = &mut *state;
+        connections.retain(|addr, connection| match connection.read_messages() {
+            Ok(messages) => {
+                inbox.extend(messages.into_iter().map(|message| (message, *addr)));
+                true
+            }
+            Err(e) => {
+                warn!(target: "net", "Connection to {} closed: {}", addr, e);
+                lost_peers.push(*addr);
+                false
+            }
+        });
+        
+        Ok(state.inbox.pop_front())
+    }
+    
+    fn update(&self) -> Result<()> {
+        // Finish writes the socket couldn't take at once
+        let mut state = self.state.borrow_mut();
+        let TcpState { connections, lost_peers, .. } = &mut *state;
+        connections.retain(|addr, connection| match connection.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(target: "net", "Connection to {} closed: {}", addr, e);
+                lost_peers.push(*addr);
+                false
+            }
+        });
+        Ok(())
+    }
+    
+    fn take_lost_peers(&self) -> Vec<SocketAddr> {
+        std::mem::take(&mut self.state.borrow_mut().lost_peers)
+    }
+    
+    fn reset_peer(&self, addr: SocketAddr) {
+        self.state.borrow_mut().connections.remove(&addr);
+    }
+    
+    fn close(&mut self) {
+        let mut state = self.state.borrow_mut();
+        for connection in state.connections.values_mut() {
+            let _ = connection.flush();
+        }
+        *state = TcpState::default();
+        self.is_connected = false;
+    }
+    
+    fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use igd_next::{PortMappingProtocol, SearchOptions};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::networking::TransportKind;
+
+/// How long to look for a router before giving up
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Seconds a mapping lasts if we never get to remove it, e.g. after a crash
+const LEASE_DURATION: u32 = 2 * 60 * 60;
+
+/// A port forwarded on the router with UPnP so players outside the local network can reach our
+/// lobby. The mapping is removed again when this is dropped.
+pub struct PortMapping {
+    gateway: igd_next::Gateway,
+    protocol: PortMappingProtocol,
+    port: u16,
+    pub external_ip: IpAddr,
+}
+
+impl PortMapping {
+    /// Ask the router to forward `port` to this machine, over the protocol the transport uses. Many
+    /// routers have UPnP turned off, so failing here is normal; players can still forward the port by hand.
+    pub fn open(port: u16, transport: TransportKind) -> Result<Self> {
+        let gateway = igd_next::search_gateway(SearchOptions {
+            timeout: Some(SEARCH_TIMEOUT),
+            ..Default::default()
+        })
+        .map_err(|e| anyhow::anyhow!("No UPnP router found: {}", e))?;
+
+        let protocol = match transport {
+            TransportKind::Udp => PortMappingProtocol::UDP,
+            TransportKind::Tcp => PortMappingProtocol::TCP,
+        };
+        let local_addr = SocketAddr::new(local_ip_towards(gateway.addr)?, port);
+        gateway
+            .add_port(protocol, port, local_addr, LEASE_DURATION, "Rusty Autobattler lobby")
+            .map_err(|e| anyhow::anyhow!("The router refused to forward port {}: {}", port, e))?;
+
+        let external_ip = match gateway.get_external_ip() {
+            Ok(ip) => ip,
+            Err(e) => {
+                let _ = gateway.remove_port(protocol, port);
+                return Err(anyhow::anyhow!("The router didn't tell us its address: {}", e));
+            }
+        };
+
+        info!(target: "net", "Forwarded port {} to {} through UPnP; external address {}", port, local_addr, external_ip);
+        Ok(Self { gateway, protocol, port, external_ip })
+    }
+}
+
+impl Drop for PortMapping {
+    fn drop(&mut self) {
+        if let Err(e) = self.gateway.remove_port(self.protocol, self.port) {
+            warn!(target: "net", "Failed to remove UPnP port mapping: {}", e);
+        }
+    }
+}
+
+/// Our address on the network the router is on; connecting a UDP socket sends nothing but picks the route
+fn local_ip_towards(gateway: SocketAddr) -> Result<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(gateway)?;
+    Ok(socket.local_addr()?.ip())
+}
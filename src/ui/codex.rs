@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::ecs::components::{BuildingType, ResourceType, UnitType};
+use crate::ecs::resources::TechType;
+use crate::ecs::systems::combat::{get_unit_armor_type, ArmorType, DamageTable, DamageType};
+use crate::game::data::{self, GameData};
+use crate::game::units;
+use crate::ui::i18n::tr;
+
+/// Page of the codex, each a screen of its own so switching pages is only a change of screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodexPage {
+    Units,
+    Buildings,
+    Techs,
+    Damage,
+}
+
+impl CodexPage {
+    /// Pages in the order their tabs are shown
+    pub const ALL: [CodexPage; 4] = [CodexPage::Units, CodexPage::Buildings, CodexPage::Techs, CodexPage::Damage];
+    
+    /// Name of the menu screen showing the page
+    pub fn screen(self) -> &'static str {
+        match self {
+            CodexPage::Units => "codex_units",
+            CodexPage::Buildings => "codex_buildings",
+            CodexPage::Techs => "codex_techs",
+            CodexPage::Damage => "codex_damage",
+        }
+    }
+    
+    /// String key of the page's tab
+    pub fn title_key(self) -> &'static str {
+        match self {
+            CodexPage::Units => "codex.units",
+            CodexPage::Buildings => "codex.buildings",
+            CodexPage::Techs => "codex.techs",
+            CodexPage::Damage => "codex.damage",
+        }
+    }
+}
+
+/// A page's contents as a table: translated column headings, then one row of cells per entry
+#[derive(Debug, Clone, Default)]
+pub struct CodexTable {
+    pub headings: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CodexTable {
+    fn new(heading_keys: &[&str]) -> Self {
+        Self {
+            headings: heading_keys.iter().map(|key| tr(key)).collect(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+/// Build a page from the loaded definitions, so the codex always matches what the game plays by
+pub fn page_table(page: CodexPage) -> CodexTable {
+    let game_data = data::game_data();
+    match page {
+        CodexPage::Units => unit_table(game_data),
+        CodexPage::Buildings => building_table(game_data),
+        CodexPage::Techs => tech_table(game_data),
+        CodexPage::Damage => damage_table(&DamageTable::default()),
+    }
+}
+
+fn unit_table(game_data: &GameData) -> CodexTable {
+    let mut table = CodexTable::new(&[
+        "codex.name", "codex.health", "codex.armor", "codex.weapon", "codex.range", "codex.speed",
+        "codex.sight", "codex.supply", "codex.cost", "codex.requires",
+    ]);
+    for unit_type in UnitType::ALL {
+        let unit = game_data.unit(unit_type);
+        let weapon = match &unit.weapon {
+            Some(weapon) => format!("{:.0} {:?} / {:.1}s", weapon.damage, weapon.damage_type, weapon.cooldown),
+            None => "-".to_string(),
+        };
+        
+        // Trained at any building that produces the unit, once its techs are researched
+        let trained_at = BuildingType::ALL
+            .into_iter()
+            .filter(|&building_type| game_data.building(building_type).can_produce.contains(&unit_type))
+            .map(|building_type| game_data.building(building_type).name.clone());
+        let techs = units::unit_tech_requirements(unit_type).iter().map(|&tech| game_data.tech(tech).name.clone());
+        
+        table.rows.push(vec![
+            unit.name.clone(),
+            format!("{:.0}", unit.health),
+            format!("{:?}", get_unit_armor_type(unit_type)),
+            weapon,
+            format!("{:.0}", unit.attack_range),
+            format!("{:.0}", unit.movement_speed),
+            format!("{:.0}", unit.sight_range),
+            unit.supply.to_string(),
+            cost_text(&unit.costs),
+            list_text(trained_at.chain(techs)),
+        ]);
+    }
+    table
+}
+
+fn building_table(game_data: &GameData) -> CodexTable {
+    let mut table = CodexTable::new(&[
+        "codex.name", "codex.health", "codex.weapon", "codex.sight", "codex.build_time", "codex.supply",
+        "codex.cost", "codex.produces", "codex.requires",
+    ]);
+    for building_type in BuildingType::ALL {
+        let building = game_data.building(building_type);
+        let weapon = match &building.weapon {
+            Some(weapon) => format!("{:.0} {:?} / {:.1}s", weapon.damage, weapon.damage_type, weapon.cooldown),
+            None => "-".to_string(),
+        };
+        
+        table.rows.push(vec![
+            building.name.clone(),
+            format!("{:.0}", building.health),
+            weapon,
+            format!("{:.0}", building.sight_range),
+            format!("{:.0}s", building.build_time),
+            building.provides_supply.to_string(),
+            cost_text(&building.costs),
+            list_text(building.can_produce.iter().map(|&unit_type| game_data.unit(unit_type).name.clone())),
+            list_text(building.tech_requirements.iter().map(|&tech| game_data.tech(tech).name.clone())),
+        ]);
+    }
+    table
+}
+
+fn tech_table(game_data: &GameData) -> CodexTable {
+    let mut table = CodexTable::new(&[
+        "codex.name", "codex.research_time", "codex.cost", "codex.requires", "codex.description",
+    ]);
+    for tech_type in TechType::ALL {
+        let tech = game_data.tech(tech_type);
+        table.rows.push(vec![
+            tech.name.clone(),
+            format!("{:.0}s", tech.research_time),
+            cost_text(&tech.costs),
+            list_text(tech.prerequisites.iter().map(|&prerequisite| game_data.tech(prerequisite).name.clone())),
+            tech.description.clone(),
+        ]);
+    }
+    table
+}
+
+/// Damage types down the side, armor types across the top, each cell the multiplier a hit gets
+fn damage_table(damage_table: &DamageTable) -> CodexTable {
+    let mut table = CodexTable::new(&["codex.damage_type"]);
+    table.headings.extend(ArmorType::ALL.iter().map(|armor_type| format!("{:?}", armor_type)));
+    for damage_type in DamageType::ALL {
+        let mut row = vec![format!("{:?}", damage_type)];
+        row.extend(ArmorType::ALL.iter().map(|&armor_type| {
+            let multiplier = damage_table.multipliers.get(&(damage_type, armor_type)).copied().unwrap_or(1.0);
+            format!("x{:.2}", multiplier)
+        }));
+        table.rows.push(row);
+    }
+    table
+}
+
+/// Costs in resource order, like "150 Mineral, 50 Gas"; free entries show a dash
+fn cost_text(costs: &HashMap<ResourceType, f32>) -> String {
+    list_text(
+        [ResourceType::Mineral, ResourceType::Gas, ResourceType::Energy]
+            .into_iter()
+            .filter_map(|resource_type| costs.get(&resource_type).map(|cost| format!("{:.0} {:?}", cost, resource_type))),
+    )
+}
+
+/// Entries joined by commas, or a dash for none
+fn list_text(entries: impl Iterator<Item = String>) -> String {
+    let entries: Vec<String> = entries.collect();
+    if entries.is_empty() {
+        "-".to_string()
+    } else {
+        entries.join(", ")
+    }
+}
@@ -0,0 +1,135 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::collections::VecDeque;
+
+use crate::ui::{UiPipeline, UiQuad};
+use crate::ui::font::{self, TEXT_HEIGHT};
+
+/// Lines of output the console keeps
+const OUTPUT_LINES: usize = 200;
+
+/// Entered commands remembered for recall with the arrow keys
+const HISTORY_LENGTH: usize = 50;
+
+/// Fraction of the screen height the console covers when dropped down
+const CONSOLE_HEIGHT_FRACTION: f32 = 0.4;
+
+/// Height of the console's input line
+pub const INPUT_HEIGHT: f32 = 30.0;
+
+/// Gap between the panel's edges and the output
+const PADDING: f32 = 8.0;
+
+/// Distance from one output line to the next
+const LINE_SPACING: f32 = TEXT_HEIGHT + 4.0;
+
+const PANEL_COLOR: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.75);
+const TEXT_COLOR: Vec4 = Vec4::new(0.85, 0.85, 0.85, 1.0);
+
+/// Drop-down developer console: a log of output lines above an input line
+pub struct Console {
+    position: Vec2,
+    size: Vec2,
+    open: bool,
+    output: VecDeque<String>,
+    history: Vec<String>,         // Entered commands, oldest first
+    history_index: Option<usize>, // Entry being recalled with the arrow keys
+    submitted: Vec<String>,       // Commands entered since the engine last took them
+}
+
+impl Console {
+    pub fn new(screen_size: Vec2) -> Self {
+        let mut console = Self {
+            position: Vec2::ZERO,
+            size: Vec2::ZERO,
+            open: false,
+            output: VecDeque::new(),
+            history: Vec::new(),
+            history_index: None,
+            submitted: Vec::new(),
+        };
+        console.resize(screen_size);
+        console
+    }
+    
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.history_index = None;
+    }
+    
+    /// Top-left corner and size of the input line, along the console's bottom edge
+    pub fn input_rect(&self) -> (Vec2, Vec2) {
+        (
+            Vec2::new(self.position.x, self.position.y + self.size.y - INPUT_HEIGHT),
+            Vec2::new(self.size.x, INPUT_HEIGHT),
+        )
+    }
+    
+    /// Add a line to the output log
+    pub fn print(&mut self, line: String) {
+        self.output.push_back(line);
+        if self.output.len() > OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+    
+    /// Echo and queue a command typed into the input line
+    pub fn submit(&mut self, command: String) {
+        self.print(format!("> {}", command));
+        if self.history.last() != Some(&command) {
+            self.history.push(command.clone());
+            if self.history.len() > HISTORY_LENGTH {
+                self.history.remove(0);
+            }
+        }
+        self.history_index = None;
+        self.submitted.push(command);
+    }
+    
+    /// Take the commands entered since the last call
+    pub fn take_submitted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.submitted)
+    }
+    
+    /// Step through earlier commands (`older`) or back towards the newest; None past the newest
+    pub fn recall(&mut self, older: bool) -> Option<&str> {
+        let index = match (self.history_index, older) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|&index| index < self.history.len()),
+        };
+        self.history_index = index;
+        index.map(|index| self.history[index].as_str())
+    }
+    
+    pub fn resize(&mut self, screen_size: Vec2) {
+        self.position = Vec2::ZERO;
+        self.size = Vec2::new(screen_size.x, screen_size.y * CONSOLE_HEIGHT_FRACTION);
+    }
+    
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.open {
+            return;
+        }
+        
+        // The input line is an element of its own, drawn along the bottom edge
+        let output_size = Vec2::new(self.size.x, self.size.y - INPUT_HEIGHT);
+        let mut quads = vec![UiQuad::rect(self.position, output_size, PANEL_COLOR)];
+        
+        // Newest output at the bottom, working up until the panel is full
+        let rows = ((output_size.y - PADDING * 2.0) / LINE_SPACING).floor().max(0.0) as usize;
+        let max_chars = font::chars_fitting(output_size.x - PADDING * 2.0, TEXT_HEIGHT);
+        let bottom = self.position.y + output_size.y - PADDING - TEXT_HEIGHT;
+        for (row, line) in self.output.iter().rev().take(rows).enumerate() {
+            let line: String = line.chars().take(max_chars).collect();
+            let position = Vec2::new(self.position.x + PADDING, bottom - row as f32 * LINE_SPACING);
+            font::push_text(&mut quads, &line, position, TEXT_HEIGHT, TEXT_COLOR);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+}
@@ -0,0 +1,248 @@
+use glam::{Vec2, Vec4};
+
+use crate::ui::UiAlignment;
+use crate::ui::pipeline::UiQuad;
+
+/// Glyph height of ordinary UI text, two logical pixels per font pixel
+pub const TEXT_HEIGHT: f32 = 16.0;
+
+/// Glyph height of text in dense panels such as the performance overlay, a pixel per font pixel
+pub const SMALL_TEXT_HEIGHT: f32 = 8.0;
+
+/// Font pixels across a glyph
+pub const GLYPH_WIDTH: u32 = 5;
+
+/// Font pixels down a glyph, the bottom one for descenders
+pub const GLYPH_HEIGHT: u32 = 8;
+
+/// Font pixels from one character to the next, leaving a column between them
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// First and last characters the font has; others are drawn as '?'
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+
+/// Glyph cells across each row of the atlas
+const ATLAS_COLUMNS: u32 = 16;
+
+/// Edge length in pixels of an atlas cell
+const CELL_SIZE: u32 = 8;
+
+/// Atlas cell after the last glyph, filled solid white for untextured quads to sample
+const WHITE_CELL: u32 = LAST_CHAR as u32 - FIRST_CHAR as u32 + 1;
+
+/// Atlas size in pixels: every glyph and the white cell, in rows of ATLAS_COLUMNS
+pub const ATLAS_WIDTH: u32 = ATLAS_COLUMNS * CELL_SIZE;
+pub const ATLAS_HEIGHT: u32 = (WHITE_CELL / ATLAS_COLUMNS + 1) * CELL_SIZE;
+
+/// 5x8 bitmap font for printable ASCII, one byte per column from the left, lowest bit at the top
+const GLYPHS: [[u8; GLYPH_WIDTH as usize]; WHITE_CELL as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x56, 0x20, 0x50], // '&'
+    [0x00, 0x08, 0x07, 0x03, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x80, 0x70, 0x30, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x00, 0x60, 0x60, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x72, 0x49, 0x49, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x49, 0x4D, 0x33], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x31], // '6'
+    [0x41, 0x21, 0x11, 0x09, 0x07], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x46, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x00, 0x14, 0x00, 0x00], // ':'
+    [0x00, 0x40, 0x34, 0x00, 0x00], // ';'
+    [0x00, 0x08, 0x14, 0x22, 0x41], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x59, 0x09, 0x06], // '?'
+    [0x3E, 0x41, 0x5D, 0x59, 0x4E], // '@'
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x41, 0x51, 0x73], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x26, 0x49, 0x49, 0x49, 0x32], // 'S'
+    [0x03, 0x01, 0x7F, 0x01, 0x03], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x03, 0x04, 0x78, 0x04, 0x03], // 'Y'
+    [0x61, 0x59, 0x49, 0x4D, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x41], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x41, 0x7F], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x03, 0x07, 0x08, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x78, 0x40], // 'a'
+    [0x7F, 0x28, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x28], // 'c'
+    [0x38, 0x44, 0x44, 0x28, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x00, 0x08, 0x7E, 0x09, 0x02], // 'f'
+    [0x18, 0xA4, 0xA4, 0x9C, 0x78], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x40, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x78, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0xFC, 0x18, 0x24, 0x24, 0x18], // 'p'
+    [0x18, 0x24, 0x24, 0x18, 0xFC], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x24], // 's'
+    [0x04, 0x04, 0x3F, 0x44, 0x24], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x4C, 0x90, 0x90, 0x90, 0x7C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x77, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x02, 0x01, 0x02, 0x04, 0x02], // '~'
+];
+
+/// RGBA pixels of the font atlas: white glyphs on transparent cells, then the solid white cell
+pub fn atlas_pixels() -> Vec<u8> {
+    let mut pixels = vec![0u8; (ATLAS_WIDTH * ATLAS_HEIGHT * 4) as usize];
+    let mut set_white = |x: u32, y: u32| {
+        let pixel = ((y * ATLAS_WIDTH + x) * 4) as usize;
+        pixels[pixel..pixel + 4].copy_from_slice(&[255, 255, 255, 255]);
+    };
+    
+    for (index, columns) in GLYPHS.iter().enumerate() {
+        let origin = cell_origin(index as u32);
+        for (column, bits) in columns.iter().enumerate() {
+            for row in (0..GLYPH_HEIGHT).filter(|row| (bits >> row) & 1 == 1) {
+                set_white(origin.0 + column as u32, origin.1 + row);
+            }
+        }
+    }
+    
+    let origin = cell_origin(WHITE_CELL);
+    for y in 0..CELL_SIZE {
+        for x in 0..CELL_SIZE {
+            set_white(origin.0 + x, origin.1 + y);
+        }
+    }
+    pixels
+}
+
+/// Top-left pixel of an atlas cell
+fn cell_origin(cell: u32) -> (u32, u32) {
+    ((cell % ATLAS_COLUMNS) * CELL_SIZE, (cell / ATLAS_COLUMNS) * CELL_SIZE)
+}
+
+/// Atlas region of a cell, in 0..1 texture coordinates: offset in xy, size in zw
+fn cell_uv(cell: u32, width: u32) -> [f32; 4] {
+    let origin = cell_origin(cell);
+    [
+        origin.0 as f32 / ATLAS_WIDTH as f32,
+        origin.1 as f32 / ATLAS_HEIGHT as f32,
+        width as f32 / ATLAS_WIDTH as f32,
+        GLYPH_HEIGHT as f32 / ATLAS_HEIGHT as f32,
+    ]
+}
+
+/// Middle of the white cell, a point rather than a region so every pixel of a quad samples white
+pub fn white_uv() -> [f32; 4] {
+    let origin = cell_origin(WHITE_CELL);
+    let center = CELL_SIZE as f32 * 0.5;
+    [(origin.0 as f32 + center) / ATLAS_WIDTH as f32, (origin.1 as f32 + center) / ATLAS_HEIGHT as f32, 0.0, 0.0]
+}
+
+/// Width in logical pixels a line of text takes at a glyph height
+pub fn text_width(text: &str, height: f32) -> f32 {
+    text.chars().count() as f32 * GLYPH_ADVANCE as f32 * height / GLYPH_HEIGHT as f32
+}
+
+/// Characters of text at a glyph height that fit in a width
+pub fn chars_fitting(width: f32, height: f32) -> usize {
+    (width / (GLYPH_ADVANCE as f32 * height / GLYPH_HEIGHT as f32)).floor().max(0.0) as usize
+}
+
+/// Add a quad per character of a line of text, its top-left corner at a position. Spaces take room
+/// but no quad
+pub fn push_text(quads: &mut Vec<UiQuad>, text: &str, position: Vec2, height: f32, color: Vec4) {
+    let scale = height / GLYPH_HEIGHT as f32;
+    let size = Vec2::new(GLYPH_ADVANCE as f32, GLYPH_HEIGHT as f32) * scale;
+    for (index, c) in text.chars().enumerate() {
+        if c == ' ' {
+            continue;
+        }
+        let c = if (FIRST_CHAR..=LAST_CHAR).contains(&c) { c } else { '?' };
+        let cell = c as u32 - FIRST_CHAR as u32;
+        let offset = Vec2::new(index as f32 * size.x, 0.0);
+        quads.push(UiQuad::textured(position + offset, size, cell_uv(cell, GLYPH_ADVANCE), color));
+    }
+}
+
+/// Top-left corner for a line of text placed in a rectangle by an alignment, `padding` in from the
+/// edges it is aligned to
+pub fn align_text(text: &str, height: f32, position: Vec2, size: Vec2, alignment: UiAlignment, padding: f32) -> Vec2 {
+    let free = size - Vec2::new(text_width(text, height), height);
+    let x = match alignment {
+        UiAlignment::TopLeft | UiAlignment::Left | UiAlignment::BottomLeft => padding,
+        UiAlignment::TopRight | UiAlignment::Right | UiAlignment::BottomRight => free.x - padding,
+        _ => free.x * 0.5,
+    };
+    let y = match alignment {
+        UiAlignment::TopLeft | UiAlignment::Top | UiAlignment::TopRight => padding,
+        UiAlignment::BottomLeft | UiAlignment::Bottom | UiAlignment::BottomRight => free.y - padding,
+        _ => free.y * 0.5,
+    };
+    position + Vec2::new(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn every_cell_fits_in_the_atlas() {
+        let origin = cell_origin(WHITE_CELL);
+        assert!(origin.0 + CELL_SIZE <= ATLAS_WIDTH && origin.1 + CELL_SIZE <= ATLAS_HEIGHT);
+        assert_eq!(atlas_pixels().len(), (ATLAS_WIDTH * ATLAS_HEIGHT * 4) as usize);
+    }
+    
+    #[test]
+    fn text_takes_a_quad_per_visible_character() {
+        let mut quads = Vec::new();
+        push_text(&mut quads, "Hi there", Vec2::ZERO, 16.0, Vec4::ONE);
+        assert_eq!(quads.len(), 7);
+        assert_eq!(text_width("Hi there", 16.0), 8.0 * 12.0);
+        assert_eq!(chars_fitting(text_width("Hi there", 16.0), 16.0), 8);
+    }
+}
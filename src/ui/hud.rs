@@ -1,10 +1,20 @@
 use glam::{Vec2, Vec4};
 use wgpu::RenderPass;
-use std::collections::HashMap;
+use winit::event::VirtualKeyCode;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::debug;
 
-use crate::ecs::components::{UnitType, BuildingType, ResourceType};
+use crate::ecs::components::{UnitType, BuildingType, ResourceType, Stance};
+use crate::ecs::resources::TechType;
 use crate::game::GameState;
-use crate::ui::{UiPipeline, UiElement, UiElementType};
+use crate::game::tech::TechStatus;
+use crate::engine::input::key_name;
+use crate::networking::latency::ConnectionQuality;
+use crate::ui::{UiPipeline, UiElement, UiElementType, UiAlignment, UiColorScheme};
+use crate::ui::i18n::{tr, tr_args};
+use crate::ui::layout::{Layout, Stack, StackDirection};
+use crate::ui::menus::PingBars;
 
 /// Resource display for the HUD
 struct ResourceDisplay {
@@ -12,6 +22,7 @@ struct ResourceDisplay {
     size: Vec2,
     visible: bool,
     resources: HashMap<ResourceType, f32>,
+    supply: (u32, u32), // (used, max)
 }
 
 /// Unit info panel for the HUD
@@ -20,6 +31,10 @@ struct UnitInfoPanel {
     size: Vec2,
     visible: bool,
     selected_units: Vec<UnitInfo>,
+    active_unit_type: Option<UnitType>, // Subgroup whose commands the action panel shows
+    control_groups: Vec<u8>, // Control groups containing the selection, shown as badges
+    cargo_capacity: u32,     // Cargo slots shown for the selected transport; 0 when none is active
+    cargo: Vec<UnitInfo>,    // Units aboard the selected transport, one per filled slot
 }
 
 /// Building info panel for the HUD
@@ -46,23 +61,78 @@ struct CommandCard {
     commands: Vec<CommandButton>,
 }
 
+/// Button showing the number of idle workers; clicking it selects the next one
+struct IdleWorkerButton {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    count: usize,
+}
+
+/// Button to give up a multiplayer game; the first click asks for confirmation
+struct SurrenderButton {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    armed: Option<Instant>, // When the first click came; a second click within the window surrenders
+}
+
+/// Tech tree panel opened from a research building
+struct TechTreePanel {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    nodes: Vec<TechNode>,
+}
+
+/// One technology in the tech tree panel
+pub struct TechNode {
+    pub tech_type: TechType,
+    pub name: String,
+    pub status: TechStatus,
+    pub position: Vec2, // Relative to panel
+}
+
 /// Simple information about a selected unit
 #[derive(Clone)]
-struct UnitInfo {
-    unit_type: UnitType,
-    health: f32,
-    max_health: f32,
-    entity_id: u32,
+pub struct UnitInfo {
+    pub unit_type: UnitType,
+    pub health: f32,
+    pub max_health: f32,
+    pub entity_id: u32,
+    pub rank: u8, // Veterancy rank, drawn as that many chevrons
+    pub energy: Option<f32>, // For units with abilities
+}
+
+/// An ability the active subgroup can use, shown on the command card
+pub struct AbilityInfo {
+    pub ability_id: u8,
+    pub name: String,
+    pub energy_cost: f32,
+    pub cooldown: f32, // Seconds until the soonest caster can use it again
+    pub ready: bool,   // Some selected caster has the energy and no cooldown
 }
 
 /// Simple information about a selected building
-struct BuildingInfo {
-    building_type: BuildingType,
-    health: f32,
-    max_health: f32,
-    entity_id: u32,
-    production_progress: Option<f32>,
-    construction_progress: Option<f32>,
+pub struct BuildingInfo {
+    pub building_type: BuildingType,
+    pub health: f32,
+    pub max_health: f32,
+    pub entity_id: u32,
+    pub production_progress: Option<f32>,
+    pub construction_progress: Option<f32>,
+    pub production_queue: Vec<UnitType>,
+    pub upgrade_progress: Option<f32>, // While upgrading; production is paused until it finishes
+    pub upgrade: Option<UpgradeOption>,
+    pub garrison_capacity: u32,        // Garrison slots shown; 0 for buildings that take no occupants
+    pub garrison: Vec<UnitInfo>,       // Units inside, one per filled slot
+}
+
+/// The improved variant a selected building can be upgraded to
+pub struct UpgradeOption {
+    pub name: String,
+    pub cost: String,                 // e.g. "300 Mineral, 150 Gas"
+    pub missing_tech: Option<String>, // Technology still to research before the upgrade unlocks
 }
 
 /// Action button for unit/building commands
@@ -86,21 +156,28 @@ struct CommandButton {
 }
 
 /// Types of actions that can be performed
+#[derive(Debug, Clone, Copy)]
 enum ActionType {
     Move,
     Attack,
     Stop,
     Hold,
+    Stance(Stance),
     Patrol,
     Build(BuildingType),
     Train(UnitType),
     Research,
     Gather,
     Repair,
+    Load,
+    Unload,
+    Ungarrison,
+    Upgrade,
     Cancel,
 }
 
 /// Types of commands
+#[derive(Debug, Clone, Copy)]
 enum CommandType {
     UseAbility(u8),
     SetRallyPoint,
@@ -108,6 +185,99 @@ enum CommandType {
     Special,
 }
 
+/// Request raised by a HUD button for the engine to carry out
+#[derive(Debug, Clone, Copy)]
+pub enum HudRequest {
+    /// Enter building placement mode
+    PlaceBuilding(BuildingType),
+    /// Wait for an attack-move target
+    AttackMove,
+    /// Wait for patrol waypoints
+    Patrol,
+    /// Drop the selected units' orders
+    Stop,
+    /// Stop the selected units and keep them in place
+    HoldPosition,
+    /// Change how the selected units react to enemies
+    SetStance(Stance),
+    /// Queue a unit at the selected building
+    Train(UnitType),
+    /// Cancel a production queue slot of the selected building
+    CancelTrain(usize),
+    /// Queue a technology at a research building
+    Research(TechType),
+    /// Use an ability, waiting for a target first if it needs one
+    UseAbility(u8),
+    /// Select the next idle worker and center the camera on it
+    SelectIdleWorker,
+    /// Put the selected units aboard the selected transports
+    Load,
+    /// Set down everyone aboard the selected transports
+    Unload,
+    /// Set down the passenger in a cargo slot of the selected transport
+    UnloadSlot(usize),
+    /// Send everyone inside the selected building back out
+    Ungarrison,
+    /// Send the occupant in a garrison slot of the selected building back out
+    UngarrisonSlot(usize),
+    /// Upgrade the selected building into its improved variant
+    Upgrade,
+    /// Give up the game, after the player confirmed
+    Surrender,
+}
+
+/// How long a gameplay warning stays on screen
+const WARNING_DURATION: Duration = Duration::from_secs(3);
+
+/// How long a chat line stays in the message log
+const CHAT_LINE_DURATION: Duration = Duration::from_secs(10);
+/// Chat lines fade out over the end of their time on screen
+const CHAT_FADE_DURATION: Duration = Duration::from_secs(2);
+/// Most lines the message log holds
+const CHAT_LOG_LINES: usize = 8;
+
+/// Size of a node in the tech tree panel
+pub const TECH_NODE_SIZE: Vec2 = Vec2::new(128.0, 32.0);
+
+/// Size of a production queue slot in the building info panel
+const QUEUE_SLOT_SIZE: Vec2 = Vec2::new(32.0, 32.0);
+/// Offset of the first queue slot, relative to the building info panel
+const QUEUE_SLOT_OFFSET: Vec2 = Vec2::new(0.0, 60.0);
+
+/// Distance between neighbouring action button slots
+const BUTTON_SPACING: f32 = 36.0;
+
+/// Keys for the action panel's button slots, by row then column, laid out like the panel on the keyboard
+const HOTKEY_GRID: [[VirtualKeyCode; 5]; 3] = [
+    [VirtualKeyCode::Q, VirtualKeyCode::W, VirtualKeyCode::E, VirtualKeyCode::R, VirtualKeyCode::T],
+    [VirtualKeyCode::A, VirtualKeyCode::S, VirtualKeyCode::D, VirtualKeyCode::F, VirtualKeyCode::G],
+    [VirtualKeyCode::Z, VirtualKeyCode::X, VirtualKeyCode::C, VirtualKeyCode::V, VirtualKeyCode::B],
+];
+
+/// Size of a cargo slot in the unit info panel
+const CARGO_SLOT_SIZE: Vec2 = Vec2::new(24.0, 24.0);
+/// Offset of the first cargo slot, relative to the unit info panel
+const CARGO_SLOT_OFFSET: Vec2 = Vec2::new(0.0, 70.0);
+
+/// Offset of the first garrison slot, relative to the building info panel; slots are cargo slot sized
+const GARRISON_SLOT_OFFSET: Vec2 = Vec2::new(0.0, 30.0);
+
+/// Gap between HUD panels, and between them and the screen edges
+const PANEL_MARGIN: f32 = 10.0;
+
+/// Size of the connection quality bars beside the resource display
+const CONNECTION_INDICATOR_SIZE: Vec2 = Vec2::new(32.0, 20.0);
+
+/// How long the surrender button waits for the confirming click
+const SURRENDER_CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+
+/// A line in the message log overlay
+pub struct ChatLine {
+    pub text: String,
+    pub system: bool, // Notices are drawn in the accent color
+    posted: Instant,
+}
+
 /// Main HUD class
 pub struct Hud {
     resource_display: ResourceDisplay,
@@ -115,24 +285,44 @@ pub struct Hud {
     building_info_panel: BuildingInfoPanel,
     action_panel: ActionPanel,
     command_card: CommandCard,
+    tech_tree_panel: TechTreePanel,
+    idle_worker_button: IdleWorkerButton,
+    connection_indicator: PingBars, // Shown in multiplayer games once the connection has been measured
+    surrender_button: SurrenderButton, // Multiplayer games only
     screen_size: Vec2,
     visible: bool,
+    requests: Vec<HudRequest>,
+    alert: Option<String>, // Banner message such as network waits or errors
+    warning: Option<(String, Instant)>, // Short-lived gameplay warning and when it was raised
+    chat_log: VecDeque<ChatLine>,
+    chat_open: bool, // While typing, the whole log is shown without fading
+    placement_cost: Option<String>, // Total cost of the building or wall row being placed
+    color_scheme: UiColorScheme,    // Theme colors the panels and buttons are drawn in
+    bound_keys: HashSet<VirtualKeyCode>, // Keys bindings use, which grid slots give up
 }
 
 impl Hud {
     pub fn new() -> Self {
+        let mut connection_indicator = PingBars::new(Vec2::new(220.0, 10.0), CONNECTION_INDICATOR_SIZE, &UiColorScheme::default());
+        connection_indicator.set_visible(false);
+        
         Self {
             resource_display: ResourceDisplay {
                 position: Vec2::new(10.0, 10.0),
                 size: Vec2::new(200.0, 40.0),
                 visible: true,
                 resources: HashMap::new(),
+                supply: (0, 0),
             },
             unit_info_panel: UnitInfoPanel {
                 position: Vec2::new(10.0, 60.0),
                 size: Vec2::new(200.0, 100.0),
                 visible: false,
                 selected_units: Vec::new(),
+                active_unit_type: None,
+                control_groups: Vec::new(),
+                cargo_capacity: 0,
+                cargo: Vec::new(),
             },
             building_info_panel: BuildingInfoPanel {
                 position: Vec2::new(10.0, 60.0),
@@ -152,41 +342,200 @@ impl Hud {
                 visible: false,
                 commands: Vec::new(),
             },
+            tech_tree_panel: TechTreePanel {
+                position: Vec2::new(220.0, 200.0),
+                size: Vec2::new(420.0, 200.0),
+                visible: false,
+                nodes: Vec::new(),
+            },
+            idle_worker_button: IdleWorkerButton {
+                position: Vec2::new(10.0, 450.0),
+                size: Vec2::new(48.0, 48.0),
+                visible: false,
+                count: 0,
+            },
+            connection_indicator,
+            surrender_button: SurrenderButton {
+                position: Vec2::new(262.0, 10.0),
+                size: Vec2::new(80.0, 20.0),
+                visible: false,
+                armed: None,
+            },
             screen_size: Vec2::new(800.0, 600.0),
             visible: true,
+            requests: Vec::new(),
+            alert: None,
+            warning: None,
+            chat_log: VecDeque::new(),
+            chat_open: false,
+            placement_cost: None,
+            color_scheme: UiColorScheme::default(),
+            bound_keys: HashSet::new(),
         }
     }
     
-    pub fn update(&mut self, game_state: &GameState) {
+    pub fn update(&mut self, game_state: &GameState, local_player_id: u8) {
         // Update resource display
         for (&(player_id, resource_type), &amount) in &game_state.player_resources {
-            if player_id == 0 { // Local player
+            if player_id == local_player_id {
                 self.resource_display.resources.insert(resource_type, amount);
             }
         }
+        if let Some(&supply) = game_state.player_supply.get(&local_player_id) {
+            self.resource_display.supply = supply;
+        }
+        
+        // Drop the warning once it has been up long enough
+        if matches!(&self.warning, Some((_, raised)) if raised.elapsed() >= WARNING_DURATION) {
+            self.warning = None;
+        }
+        
+        // An unconfirmed surrender is called off
+        if self.surrender_button.armed.map_or(false, |armed| armed.elapsed() >= SURRENDER_CONFIRM_WINDOW) {
+            self.surrender_button.armed = None;
+        }
         
         // Update panels based on selection state
         // In a real implementation, this would use the ECS world to get info about selected entities
     }
     
-    pub fn set_selected_units(&mut self, units: Vec<UnitInfo>) {
-        self.unit_info_panel.selected_units = units;
+    /// Show the selected units; `active_unit_type` is the Tab subgroup whose commands are offered
+    pub fn set_selected_units(&mut self, units: Vec<UnitInfo>, active_unit_type: Option<UnitType>) {
         self.unit_info_panel.visible = !units.is_empty();
+        self.unit_info_panel.selected_units = units;
+        self.unit_info_panel.active_unit_type = active_unit_type;
         self.building_info_panel.visible = false;
         
         // Update action panel based on selection
         self.update_action_panel();
     }
     
+    /// Show a banner message at the top of the screen
+    pub fn show_alert(&mut self, message: String) {
+        self.alert = Some(message);
+    }
+    
+    pub fn clear_alert(&mut self) {
+        self.alert = None;
+    }
+    
+    /// Briefly show a gameplay warning such as being supply blocked
+    pub fn show_warning(&mut self, message: String) {
+        self.warning = Some((message, Instant::now()));
+    }
+    
+    /// Add a line to the message log overlay
+    pub fn add_chat_line(&mut self, text: String, system: bool) {
+        self.chat_log.push_back(ChatLine {
+            text,
+            system,
+            posted: Instant::now(),
+        });
+        if self.chat_log.len() > CHAT_LOG_LINES {
+            self.chat_log.pop_front();
+        }
+    }
+    
+    pub fn set_chat_open(&mut self, open: bool) {
+        self.chat_open = open;
+    }
+    
+    /// How opaque a message log line is drawn, fading out at the end of its time on screen
+    pub fn chat_line_opacity(&self, line: &ChatLine) -> f32 {
+        if self.chat_open {
+            return 1.0;
+        }
+        
+        let remaining = CHAT_LINE_DURATION.saturating_sub(line.posted.elapsed());
+        (remaining.as_secs_f32() / CHAT_FADE_DURATION.as_secs_f32()).min(1.0)
+    }
+    
+    /// Show what the building or wall row being placed will cost; None while not placing
+    pub fn set_placement_cost(&mut self, cost: Option<String>) {
+        self.placement_cost = cost;
+    }
+    
+    /// Set the control group badges shown in the selection panel
+    pub fn set_control_groups(&mut self, groups: Vec<u8>) {
+        self.unit_info_panel.control_groups = groups;
+    }
+    
+    /// Show the cargo slots of the selected transport; a capacity of 0 hides them
+    pub fn set_cargo(&mut self, capacity: u32, passengers: Vec<UnitInfo>) {
+        self.unit_info_panel.cargo_capacity = capacity;
+        self.unit_info_panel.cargo = passengers;
+    }
+    
+    /// Show the quality of our connection to the game; None hides the indicator, e.g. outside multiplayer games
+    pub fn set_connection_quality(&mut self, quality: Option<ConnectionQuality>) {
+        self.connection_indicator.set_quality(quality);
+        self.connection_indicator.set_visible(quality.is_some());
+    }
+    
+    /// Keys bound to actions without Ctrl; grid slots using one of them get no hotkey
+    pub fn set_bound_keys(&mut self, keys: HashSet<VirtualKeyCode>) {
+        self.bound_keys = keys;
+    }
+    
+    /// Offer the surrender button, e.g. once a multiplayer game starts
+    pub fn set_surrender_available(&mut self, available: bool) {
+        self.surrender_button.visible = available;
+        self.surrender_button.armed = None;
+    }
+    
+    /// Show the idle worker count; the button is hidden while every worker is busy
+    pub fn set_idle_workers(&mut self, count: usize) {
+        self.idle_worker_button.count = count;
+        self.idle_worker_button.visible = count > 0;
+    }
+    
+    /// Offer the active subgroup's abilities on the command card
+    pub fn set_abilities(&mut self, abilities: Vec<AbilityInfo>) {
+        self.command_card.commands = abilities
+            .into_iter()
+            .enumerate()
+            .map(|(i, ability)| CommandButton {
+                position: Vec2::new((i % 4) as f32 * 36.0, (i / 4) as f32 * 36.0), // Relative to panel
+                size: Vec2::new(32.0, 32.0),
+                visible: true,
+                command_type: CommandType::UseAbility(ability.ability_id),
+                enabled: ability.ready,
+                tooltip: if ability.cooldown > 0.0 {
+                    format!("{} ({:.0} energy, ready in {:.0}s)", ability.name, ability.energy_cost, ability.cooldown)
+                } else {
+                    format!("{} ({:.0} energy)", ability.name, ability.energy_cost)
+                },
+            })
+            .collect();
+        self.command_card.visible = !self.command_card.commands.is_empty();
+    }
+    
     pub fn set_selected_building(&mut self, building: Option<BuildingInfo>) {
-        self.building_info_panel.selected_building = building;
+        // Only take over from the unit panel when a building is actually selected
         self.building_info_panel.visible = building.is_some();
-        self.unit_info_panel.visible = false;
+        if building.is_some() {
+            self.unit_info_panel.visible = false;
+        }
+        self.building_info_panel.selected_building = building;
+        
+        // The tech tree belongs to the selected research building
+        let research_selected = self.building_info_panel.selected_building
+            .as_ref()
+            .map(|building| building.building_type == BuildingType::ResearchCenter)
+            .unwrap_or(false);
+        if !research_selected {
+            self.tech_tree_panel.visible = false;
+        }
         
         // Update action panel based on selection
         self.update_action_panel();
     }
-
+    
+    /// Set the technologies shown in the tech tree panel
+    pub fn set_tech_tree(&mut self, nodes: Vec<TechNode>) {
+        self.tech_tree_panel.nodes = nodes;
+    }
+    
     fn update_action_panel(&mut self) {
         // Clear current buttons
         self.action_panel.buttons.clear();
@@ -200,7 +549,7 @@ impl Hud {
                 visible: true,
                 action_type: ActionType::Move,
                 enabled: true,
-                tooltip: "Move".to_string(),
+                tooltip: tr("hud.move"),
             });
             
             self.action_panel.buttons.push(ActionButton {
@@ -209,7 +558,7 @@ impl Hud {
                 visible: true,
                 action_type: ActionType::Attack,
                 enabled: true,
-                tooltip: "Attack".to_string(),
+                tooltip: tr("hud.attack_move"),
             });
             
             self.action_panel.buttons.push(ActionButton {
@@ -218,12 +567,45 @@ impl Hud {
                 visible: true,
                 action_type: ActionType::Stop,
                 enabled: true,
-                tooltip: "Stop".to_string(),
+                tooltip: tr("hud.stop"),
+            });
+            
+            self.action_panel.buttons.push(ActionButton {
+                position: Vec2::new(108.0, 0.0), // Relative to panel
+                size: Vec2::new(32.0, 32.0),
+                visible: true,
+                action_type: ActionType::Patrol,
+                enabled: true,
+                tooltip: tr("hud.patrol"),
             });
             
-            // Check if any unit is a worker
-            let has_worker = self.unit_info_panel.selected_units.iter()
-                .any(|unit| unit.unit_type == UnitType::Worker);
+            self.action_panel.buttons.push(ActionButton {
+                position: Vec2::new(144.0, 0.0), // Relative to panel
+                size: Vec2::new(32.0, 32.0),
+                visible: true,
+                action_type: ActionType::Hold,
+                enabled: true,
+                tooltip: tr("hud.hold_position"),
+            });
+            
+            // Stance buttons
+            for (i, (stance, tooltip)) in [
+                (Stance::Aggressive, "hud.stance_aggressive"),
+                (Stance::Defensive, "hud.stance_defensive"),
+                (Stance::Passive, "hud.stance_passive"),
+            ].into_iter().enumerate() {
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(180.0 + i as f32 * 36.0, 0.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Stance(stance),
+                    enabled: true,
+                    tooltip: tr(tooltip),
+                });
+            }
+            
+            // Build commands belong to the worker subgroup
+            let has_worker = self.unit_info_panel.active_unit_type == Some(UnitType::Worker);
             
             if has_worker {
                 self.action_panel.buttons.push(ActionButton {
@@ -232,7 +614,16 @@ impl Hud {
                     visible: true,
                     action_type: ActionType::Build(BuildingType::Barracks),
                     enabled: true,
-                    tooltip: "Build Barracks".to_string(),
+                    tooltip: tr("hud.build_barracks"),
+                });
+                
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(72.0, 36.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Build(BuildingType::SupplyDepot),
+                    enabled: true,
+                    tooltip: tr("hud.build_supply_depot"),
                 });
                 
                 self.action_panel.buttons.push(ActionButton {
@@ -241,21 +632,66 @@ impl Hud {
                     visible: true,
                     action_type: ActionType::Gather,
                     enabled: true,
-                    tooltip: "Gather Resources".to_string(),
+                    tooltip: tr("hud.gather"),
+                });
+                
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(0.0, 72.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Build(BuildingType::Wall),
+                    enabled: true,
+                    tooltip: tr("hud.build_wall"),
+                });
+                
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(36.0, 72.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Build(BuildingType::Gate),
+                    enabled: true,
+                    tooltip: tr("hud.build_gate"),
+                });
+            }
+            
+            // Loading needs transports and units to put in them; unloading belongs to the transport subgroup
+            let units = &self.unit_info_panel.selected_units;
+            let has_transport = units.iter().any(|unit| unit.unit_type == UnitType::Transport);
+            let has_riders = units.iter().any(|unit| unit.unit_type != UnitType::Transport);
+            
+            if has_transport && has_riders {
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(108.0, 36.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Load,
+                    enabled: true,
+                    tooltip: tr("hud.load"),
+                });
+            }
+            
+            if self.unit_info_panel.active_unit_type == Some(UnitType::Transport) {
+                self.action_panel.buttons.push(ActionButton {
+                    position: Vec2::new(144.0, 36.0), // Relative to panel
+                    size: Vec2::new(32.0, 32.0),
+                    visible: true,
+                    action_type: ActionType::Unload,
+                    enabled: !self.unit_info_panel.cargo.is_empty(),
+                    tooltip: tr("hud.unload_all"),
                 });
             }
         } else if self.building_info_panel.visible {
             // Building actions
             if let Some(ref building) = self.building_info_panel.selected_building {
                 match building.building_type {
-                    BuildingType::Headquarters => {
+                    BuildingType::Headquarters | BuildingType::Fortress => {
                         self.action_panel.buttons.push(ActionButton {
                             position: Vec2::new(0.0, 0.0), // Relative to panel
                             size: Vec2::new(32.0, 32.0),
                             visible: true,
                             action_type: ActionType::Train(UnitType::Worker),
                             enabled: true,
-                            tooltip: "Train Worker".to_string(),
+                            tooltip: tr("hud.train_worker"),
                         });
                     }
                     BuildingType::Barracks => {
@@ -265,7 +701,7 @@ impl Hud {
                             visible: true,
                             action_type: ActionType::Train(UnitType::Soldier),
                             enabled: true,
-                            tooltip: "Train Soldier".to_string(),
+                            tooltip: tr("hud.train_soldier"),
                         });
                         
                         self.action_panel.buttons.push(ActionButton {
@@ -274,7 +710,7 @@ impl Hud {
                             visible: true,
                             action_type: ActionType::Train(UnitType::Scout),
                             enabled: true,
-                            tooltip: "Train Scout".to_string(),
+                            tooltip: tr("hud.train_scout"),
                         });
                     }
                     BuildingType::Factory => {
@@ -284,7 +720,16 @@ impl Hud {
                             visible: true,
                             action_type: ActionType::Train(UnitType::Tank),
                             enabled: true,
-                            tooltip: "Train Tank".to_string(),
+                            tooltip: tr("hud.train_tank"),
+                        });
+                        
+                        self.action_panel.buttons.push(ActionButton {
+                            position: Vec2::new(36.0, 0.0), // Relative to panel
+                            size: Vec2::new(32.0, 32.0),
+                            visible: true,
+                            action_type: ActionType::Train(UnitType::Transport),
+                            enabled: true,
+                            tooltip: tr("hud.train_transport"),
                         });
                     }
                     BuildingType::ResearchCenter => {
@@ -294,12 +739,57 @@ impl Hud {
                             visible: true,
                             action_type: ActionType::Research,
                             enabled: true,
-                            tooltip: "Research Technology".to_string(),
+                            tooltip: tr("hud.research"),
+                        });
+                        
+                        self.action_panel.buttons.push(ActionButton {
+                            position: Vec2::new(36.0, 0.0), // Relative to panel
+                            size: Vec2::new(32.0, 32.0),
+                            visible: true,
+                            action_type: ActionType::Train(UnitType::Healer),
+                            enabled: true,
+                            tooltip: tr("hud.train_healer"),
                         });
                     }
                     _ => {}
                 }
                 
+                // Nothing trains while the building upgrades
+                if building.upgrade_progress.is_some() {
+                    for button in &mut self.action_panel.buttons {
+                        if let ActionType::Train(_) = button.action_type {
+                            button.enabled = false;
+                        }
+                    }
+                }
+                
+                if let Some(ref upgrade) = building.upgrade {
+                    let args = [("building", upgrade.name.as_str()), ("cost", upgrade.cost.as_str())];
+                    let tooltip = match &upgrade.missing_tech {
+                        Some(tech) => tr_args("hud.upgrade_requires", &[args[0], args[1], ("tech", tech.as_str())]),
+                        None => tr_args("hud.upgrade", &args),
+                    };
+                    self.action_panel.buttons.push(ActionButton {
+                        position: Vec2::new(36.0, 72.0), // Relative to panel
+                        size: Vec2::new(32.0, 32.0),
+                        visible: true,
+                        action_type: ActionType::Upgrade,
+                        enabled: upgrade.missing_tech.is_none() && building.upgrade_progress.is_none(),
+                        tooltip,
+                    });
+                }
+                
+                if building.garrison_capacity > 0 {
+                    self.action_panel.buttons.push(ActionButton {
+                        position: Vec2::new(72.0, 72.0), // Relative to panel
+                        size: Vec2::new(32.0, 32.0),
+                        visible: true,
+                        action_type: ActionType::Ungarrison,
+                        enabled: !building.garrison.is_empty(),
+                        tooltip: tr("hud.eject_garrison"),
+                    });
+                }
+                
                 // For buildings under construction, add cancel button
                 if building.construction_progress.is_some() {
                     self.action_panel.buttons.push(ActionButton {
@@ -308,7 +798,7 @@ impl Hud {
                         visible: true,
                         action_type: ActionType::Cancel,
                         enabled: true,
-                        tooltip: "Cancel Construction".to_string(),
+                        tooltip: tr("hud.cancel_construction"),
                     });
                 }
             }
@@ -319,130 +809,351 @@ impl Hud {
     }
     
     pub fn handle_input(&mut self, position: Vec2) -> bool {
+        if self.idle_worker_button.visible {
+            let button = &self.idle_worker_button;
+            if position.x >= button.position.x &&
+               position.x <= button.position.x + button.size.x &&
+               position.y >= button.position.y &&
+               position.y <= button.position.y + button.size.y {
+                self.requests.push(HudRequest::SelectIdleWorker);
+                return true;
+            }
+        }
+        
+        // Surrendering takes a second click; the button goes away once it is confirmed
+        if self.surrender_button.visible {
+            let button = &mut self.surrender_button;
+            if position.x >= button.position.x &&
+               position.x <= button.position.x + button.size.x &&
+               position.y >= button.position.y &&
+               position.y <= button.position.y + button.size.y {
+                if button.armed.take().is_some() {
+                    button.visible = false;
+                    self.requests.push(HudRequest::Surrender);
+                } else {
+                    button.armed = Some(Instant::now());
+                    self.show_warning(tr("hud.surrender_confirm"));
+                }
+                return true;
+            }
+        }
+        
         // Check if any action button was clicked
         if self.action_panel.visible {
-            for button in &self.action_panel.buttons {
-                if button.visible && button.enabled {
-                    let absolute_pos = self.action_panel.position + button.position;
-                    if position.x >= absolute_pos.x && 
-                       position.x <= absolute_pos.x + button.size.x &&
-                       position.y >= absolute_pos.y && 
-                       position.y <= absolute_pos.y + button.size.y {
-                        // Button was clicked, handle the action
-                        return self.handle_action(&button.action_type);
-                    }
+            let panel_position = self.action_panel.position;
+            let clicked_action = self.action_panel.buttons.iter()
+                .filter(|button| button.visible && button.enabled)
+                .find(|button| {
+                    let absolute_pos = panel_position + button.position;
+                    position.x >= absolute_pos.x && 
+                    position.x <= absolute_pos.x + button.size.x &&
+                    position.y >= absolute_pos.y && 
+                    position.y <= absolute_pos.y + button.size.y
+                })
+                .map(|button| button.action_type);
+            
+            if let Some(action_type) = clicked_action {
+                // Button was clicked, handle the action
+                return self.handle_action(&action_type);
+            }
+        }
+        
+        // Clicking an available node in the tech tree starts researching it
+        if self.tech_tree_panel.visible {
+            let panel_position = self.tech_tree_panel.position;
+            let clicked_node = self.tech_tree_panel.nodes.iter().find(|node| {
+                let absolute_pos = panel_position + node.position;
+                position.x >= absolute_pos.x && 
+                position.x <= absolute_pos.x + TECH_NODE_SIZE.x &&
+                position.y >= absolute_pos.y && 
+                position.y <= absolute_pos.y + TECH_NODE_SIZE.y
+            });
+            
+            if let Some(node) = clicked_node {
+                if node.status == TechStatus::Available {
+                    self.requests.push(HudRequest::Research(node.tech_type));
                 }
+                return true;
             }
         }
         
+        // Clicking a production queue slot cancels that unit
+        if let Some(slot) = self.clicked_queue_slot(position) {
+            self.requests.push(HudRequest::CancelTrain(slot));
+            return true;
+        }
+        
+        // Clicking a filled cargo slot unloads that passenger
+        if let Some(slot) = self.clicked_cargo_slot(position) {
+            self.requests.push(HudRequest::UnloadSlot(slot));
+            return true;
+        }
+        
+        // Clicking a filled garrison slot sends that occupant out
+        if let Some(slot) = self.clicked_garrison_slot(position) {
+            self.requests.push(HudRequest::UngarrisonSlot(slot));
+            return true;
+        }
+        
         // Check if any command button was clicked
         if self.command_card.visible {
-            for button in &self.command_card.commands {
-                if button.visible && button.enabled {
-                    let absolute_pos = self.command_card.position + button.position;
-                    if position.x >= absolute_pos.x && 
-                       position.x <= absolute_pos.x + button.size.x &&
-                       position.y >= absolute_pos.y && 
-                       position.y <= absolute_pos.y + button.size.y {
-                        // Button was clicked, handle the command
-                        return self.handle_command(&button.command_type);
-                    }
-                }
+            let card_position = self.command_card.position;
+            let clicked_command = self.command_card.commands.iter()
+                .filter(|button| button.visible && button.enabled)
+                .find(|button| {
+                    let absolute_pos = card_position + button.position;
+                    position.x >= absolute_pos.x && 
+                    position.x <= absolute_pos.x + button.size.x &&
+                    position.y >= absolute_pos.y && 
+                    position.y <= absolute_pos.y + button.size.y
+                })
+                .map(|button| button.command_type);
+            
+            if let Some(command_type) = clicked_command {
+                // Button was clicked, handle the command
+                return self.handle_command(&command_type);
             }
         }
         
         false
     }
     
-    fn handle_action(&self, action_type: &ActionType) -> bool {
+    /// Press the visible action button whose slot in the hotkey grid uses a key; returns whether one did
+    pub fn handle_hotkey(&mut self, key: VirtualKeyCode) -> bool {
+        if !self.visible || !self.action_panel.visible {
+            return false;
+        }
+        
+        let pressed = self.action_panel.buttons.iter()
+            .filter(|button| button.visible)
+            .find(|button| self.hotkey(button.position) == Some(key))
+            .map(|button| (button.action_type, button.enabled));
+        
+        match pressed {
+            Some((action_type, true)) => self.handle_action(&action_type),
+            // A disabled button still owns its key, so the press doesn't trigger another binding instead
+            Some((_, false)) => true,
+            None => false,
+        }
+    }
+    
+    /// Key of the hotkey grid slot an action button sits in; None outside the grid, or when a binding
+    /// such as attack-move already uses the slot's key
+    fn hotkey(&self, position: Vec2) -> Option<VirtualKeyCode> {
+        grid_hotkey(position).filter(|key| !self.bound_keys.contains(key))
+    }
+    
+    /// Hotkey letters of the visible action buttons, with the panel position of the button each belongs to
+    pub fn hotkey_labels(&self) -> Vec<(Vec2, &'static str)> {
+        self.action_panel.buttons.iter()
+            .filter(|button| button.visible)
+            .filter_map(|button| Some((button.position, key_name(self.hotkey(button.position)?)?)))
+            .collect()
+    }
+    
+    /// Production queue slot of the selected building under a screen position
+    fn clicked_queue_slot(&self, position: Vec2) -> Option<usize> {
+        if !self.building_info_panel.visible {
+            return None;
+        }
+        let queue_length = self.building_info_panel.selected_building.as_ref()?.production_queue.len();
+        
+        (0..queue_length).find(|&slot| {
+            let slot_pos = self.queue_slot_position(slot);
+            position.x >= slot_pos.x && 
+            position.x <= slot_pos.x + QUEUE_SLOT_SIZE.x &&
+            position.y >= slot_pos.y && 
+            position.y <= slot_pos.y + QUEUE_SLOT_SIZE.y
+        })
+    }
+    
+    /// Screen position of a production queue slot
+    fn queue_slot_position(&self, slot: usize) -> Vec2 {
+        self.building_info_panel.position + QUEUE_SLOT_OFFSET + Vec2::new(slot as f32 * (QUEUE_SLOT_SIZE.x + 4.0), 0.0)
+    }
+    
+    /// Filled cargo slot of the selected transport under a screen position
+    fn clicked_cargo_slot(&self, position: Vec2) -> Option<usize> {
+        if !self.unit_info_panel.visible {
+            return None;
+        }
+        
+        (0..self.unit_info_panel.cargo.len()).find(|&slot| {
+            let slot_pos = self.cargo_slot_position(slot);
+            position.x >= slot_pos.x && 
+            position.x <= slot_pos.x + CARGO_SLOT_SIZE.x &&
+            position.y >= slot_pos.y && 
+            position.y <= slot_pos.y + CARGO_SLOT_SIZE.y
+        })
+    }
+    
+    /// Screen position of a cargo slot
+    fn cargo_slot_position(&self, slot: usize) -> Vec2 {
+        self.unit_info_panel.position + CARGO_SLOT_OFFSET + Vec2::new(slot as f32 * (CARGO_SLOT_SIZE.x + 4.0), 0.0)
+    }
+    
+    /// Filled garrison slot of the selected building under a screen position
+    fn clicked_garrison_slot(&self, position: Vec2) -> Option<usize> {
+        if !self.building_info_panel.visible {
+            return None;
+        }
+        let occupants = self.building_info_panel.selected_building.as_ref()?.garrison.len();
+        
+        (0..occupants).find(|&slot| {
+            let slot_pos = self.garrison_slot_position(slot);
+            position.x >= slot_pos.x && 
+            position.x <= slot_pos.x + CARGO_SLOT_SIZE.x &&
+            position.y >= slot_pos.y && 
+            position.y <= slot_pos.y + CARGO_SLOT_SIZE.y
+        })
+    }
+    
+    /// Screen position of a garrison slot
+    fn garrison_slot_position(&self, slot: usize) -> Vec2 {
+        self.building_info_panel.position + GARRISON_SLOT_OFFSET + Vec2::new(slot as f32 * (CARGO_SLOT_SIZE.x + 4.0), 0.0)
+    }
+    
+    fn handle_action(&mut self, action_type: &ActionType) -> bool {
         // In a real implementation, this would issue the corresponding command
         // to the game systems
         match action_type {
             ActionType::Move => {
                 // Set mode to move command
-                println!("Move command selected");
+                debug!("Move command selected");
             }
             ActionType::Attack => {
-                // Set mode to attack command
-                println!("Attack command selected");
+                // Wait for an attack-move target
+                self.requests.push(HudRequest::AttackMove);
             }
             ActionType::Stop => {
                 // Issue stop command to selected units
-                println!("Stop command issued");
+                self.requests.push(HudRequest::Stop);
             }
             ActionType::Hold => {
                 // Issue hold position command
-                println!("Hold position command issued");
+                self.requests.push(HudRequest::HoldPosition);
+            }
+            ActionType::Stance(stance) => {
+                // Change the selected units' stance
+                self.requests.push(HudRequest::SetStance(*stance));
             }
             ActionType::Patrol => {
-                // Set mode to patrol command
-                println!("Patrol command selected");
+                // Wait for patrol waypoints
+                self.requests.push(HudRequest::Patrol);
             }
             ActionType::Build(building_type) => {
-                // Set mode to build specified building
-                println!("Build {:?} command selected", building_type);
+                // Enter placement mode for the specified building
+                self.requests.push(HudRequest::PlaceBuilding(*building_type));
             }
             ActionType::Train(unit_type) => {
-                // Queue unit for training
-                println!("Train {:?} command issued", unit_type);
+                // Queue unit for training at the selected building
+                self.requests.push(HudRequest::Train(*unit_type));
             }
             ActionType::Research => {
-                // Open research menu
-                println!("Research menu opened");
+                // Toggle the tech tree
+                self.tech_tree_panel.visible = !self.tech_tree_panel.visible;
             }
             ActionType::Gather => {
                 // Set mode to gather resources
-                println!("Gather command selected");
+                debug!("Gather command selected");
             }
             ActionType::Repair => {
                 // Set mode to repair
-                println!("Repair command selected");
+                debug!("Repair command selected");
+            }
+            ActionType::Load => {
+                // Put the selected units aboard the selected transports
+                self.requests.push(HudRequest::Load);
+            }
+            ActionType::Unload => {
+                // Set down everyone aboard
+                self.requests.push(HudRequest::Unload);
+            }
+            ActionType::Ungarrison => {
+                // Send everyone inside back out
+                self.requests.push(HudRequest::Ungarrison);
+            }
+            ActionType::Upgrade => {
+                // Start upgrading the selected building
+                self.requests.push(HudRequest::Upgrade);
             }
             ActionType::Cancel => {
                 // Cancel current construction/training
-                println!("Cancel command issued");
+                debug!("Cancel command issued");
             }
         }
         
         true
     }
     
-    fn handle_command(&self, command_type: &CommandType) -> bool {
+    fn handle_command(&mut self, command_type: &CommandType) -> bool {
         // In a real implementation, this would issue the corresponding command
         match command_type {
             CommandType::UseAbility(ability_id) => {
-                // Use the specified ability
-                println!("Using ability {}", ability_id);
+                // Use the ability, or wait for its target
+                self.requests.push(HudRequest::UseAbility(*ability_id));
             }
             CommandType::SetRallyPoint => {
                 // Set mode to specify rally point
-                println!("Set rally point command selected");
+                debug!("Set rally point command selected");
             }
             CommandType::Upgrade => {
                 // Open upgrade menu
-                println!("Upgrade menu opened");
+                debug!("Upgrade menu opened");
             }
             CommandType::Special => {
                 // Trigger special ability
-                println!("Special ability triggered");
+                debug!("Special ability triggered");
             }
         }
         
         true
     }
     
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.screen_size = Vec2::new(width as f32, height as f32);
+    /// Take the requests raised by HUD buttons since the last call
+    pub fn take_requests(&mut self) -> Vec<HudRequest> {
+        std::mem::take(&mut self.requests)
+    }
+    
+    /// Look the button tooltips up again after the language changes
+    pub fn retranslate(&mut self) {
+        self.update_action_panel();
+    }
+    
+    /// Draw in a new theme's colors from the next frame on
+    pub fn set_color_scheme(&mut self, color_scheme: UiColorScheme) {
+        self.connection_indicator.apply_color_scheme(&color_scheme);
+        self.color_scheme = color_scheme;
+    }
+    
+    /// Lay the panels out for a screen size in logical pixels
+    pub fn resize(&mut self, screen_size: Vec2) {
+        self.screen_size = screen_size;
+        
+        // Position resource display at top left, with the connection indicator and surrender button beside it
+        let margin = Vec2::new(PANEL_MARGIN, PANEL_MARGIN);
+        let top_row = Stack::new(Layout::aligned(UiAlignment::TopLeft, margin, Vec2::ZERO), StackDirection::Row, PANEL_MARGIN);
+        let row = top_row.layouts(&[self.resource_display.size, CONNECTION_INDICATOR_SIZE, self.surrender_button.size]);
+        self.resource_display.position = row[0].place(screen_size).0;
+        let (position, size) = row[1].place(screen_size);
+        self.connection_indicator.set_bounds(position, size);
+        self.surrender_button.position = row[2].place(screen_size).0;
         
-        // Position resource display at top left
-        self.resource_display.position = Vec2::new(10.0, 10.0);
+        // Selection, action and command panels in a row along the bottom edge
+        let bottom_row = Stack::new(
+            Layout::aligned(UiAlignment::BottomLeft, Vec2::new(PANEL_MARGIN, -PANEL_MARGIN), Vec2::ZERO),
+            StackDirection::Row,
+            PANEL_MARGIN,
+        );
+        let row = bottom_row.layouts(&[self.unit_info_panel.size, self.action_panel.size, self.command_card.size]);
+        self.unit_info_panel.position = row[0].place(screen_size).0;
+        self.building_info_panel.position = row[0].place(screen_size).0;
+        self.action_panel.position = row[1].place(screen_size).0;
+        self.command_card.position = row[2].place(screen_size).0;
         
-        // Position panels at bottom of screen
-        let panel_y = height as f32 - 110.0;
-        self.unit_info_panel.position = Vec2::new(10.0, panel_y);
-        self.building_info_panel.position = Vec2::new(10.0, panel_y);
-        self.action_panel.position = Vec2::new(220.0, panel_y);
-        self.command_card.position = Vec2::new(530.0, panel_y);
+        // Tech tree sits above the action panel, and the idle worker button above the selection panel
+        self.tech_tree_panel.position = row[1].above(self.tech_tree_panel.size, PANEL_MARGIN).place(screen_size).0;
+        self.idle_worker_button.position = row[0].above(self.idle_worker_button.size, PANEL_MARGIN).place(screen_size).0;
     }
     
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
@@ -450,6 +1161,8 @@ impl Hud {
             return;
         }
         
+        // Every panel below is drawn in color_scheme's background, button and text colors
+        
         // Render resource display
         if self.resource_display.visible {
             self.render_resource_display(render_pass, ui_pipeline);
@@ -474,26 +1187,117 @@ impl Hud {
         if self.command_card.visible {
             self.render_command_card(render_pass, ui_pipeline);
         }
+        
+        // Render tech tree
+        if self.tech_tree_panel.visible {
+            self.render_tech_tree_panel(render_pass, ui_pipeline);
+        }
+        
+        // Render idle worker button
+        if self.idle_worker_button.visible {
+            self.render_idle_worker_button(render_pass, ui_pipeline);
+        }
+        
+        // Render connection quality
+        self.connection_indicator.render(render_pass, ui_pipeline);
+        
+        // Render surrender button
+        if self.surrender_button.visible {
+            self.render_surrender_button(render_pass, ui_pipeline);
+        }
+        
+        // Render alert banner
+        if self.alert.is_some() {
+            self.render_alert(render_pass, ui_pipeline);
+        }
+        
+        // Render gameplay warning
+        if self.warning.is_some() {
+            self.render_warning(render_pass, ui_pipeline);
+        }
+        
+        // Render the cost of what is being placed
+        if self.placement_cost.is_some() {
+            self.render_placement_cost(render_pass, ui_pipeline);
+        }
+        
+        // Render message log
+        if self.chat_log.iter().any(|line| self.chat_line_opacity(line) > 0.0) {
+            self.render_chat_log(render_pass, ui_pipeline);
+        }
     }
     
     fn render_resource_display<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
         // In a real implementation, this would render the resource counters
-        // using the UI pipeline, textures, and text
+        // and the used/max supply using the UI pipeline, textures, and text
     }
     
     fn render_unit_info_panel<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
         // In a real implementation, this would render the unit info panel
+        // with a numbered badge for each entry in control_groups, rank
+        // chevrons on each selected unit's portrait and an energy bar under
+        // the portraits of units with abilities, plus cargo_capacity slots at
+        // cargo_slot_position(slot) holding a portrait for each cargo entry
     }
     
     fn render_building_info_panel<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
         // In a real implementation, this would render the building info panel
+        // with an icon per production_queue entry at queue_slot_position(slot),
+        // the first slot overlaid with a progress bar filled to production_progress,
+        // or a single tr("hud.upgrading") bar filled to upgrade_progress in place of the queue,
+        // plus garrison_capacity slots at garrison_slot_position(slot) holding a portrait
+        // for each garrison entry
     }
     
     fn render_action_panel<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
-        // In a real implementation, this would render all action buttons
+        // In a real implementation, this would render all action buttons, then each of
+        // hotkey_labels() in the top-left corner of its button
     }
     
     fn render_command_card<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
-        // In a real implementation, this would render all command buttons
+        // In a real implementation, this would render all command buttons, greying out
+        // abilities that aren't ready and showing the tooltip of the hovered one
     }
-}
\ No newline at end of file
+    
+    fn render_idle_worker_button<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render a worker icon with the idle
+        // count in its corner and a tr("hud.idle_worker") tooltip on hover
+    }
+    
+    fn render_surrender_button<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render a white flag labelled tr("hud.surrender"),
+        // in the accent color and labelled tr("hud.surrender_confirm_short") while armed
+    }
+    
+    fn render_tech_tree_panel<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render each node with its name, greyed out
+        // when locked, highlighted when available, with a progress bar while in progress
+        // and a check mark once researched, plus lines to its prerequisites
+    }
+    
+    fn render_alert<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render the alert text centered
+        // near the top of the screen
+    }    
+    fn render_warning<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render the warning text
+        // just above the bottom panels
+    }
+    
+    fn render_placement_cost<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render the placement cost text
+        // just above the action panel
+    }
+    
+    fn render_chat_log<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        // In a real implementation, this would render each line, oldest at the top, at the
+        // left edge above the bottom panels, with alpha from chat_line_opacity
+    }
+}
+
+/// Key of the hotkey grid slot an action button sits in; None for buttons outside the grid
+fn grid_hotkey(position: Vec2) -> Option<VirtualKeyCode> {
+    let row = (position.y / BUTTON_SPACING).round() as usize;
+    let column = (position.x / BUTTON_SPACING).round() as usize;
+    HOTKEY_GRID.get(row)?.get(column).copied()
+}
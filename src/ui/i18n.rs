@@ -0,0 +1,117 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+/// Directory the string tables are read from, one `<language code>.ron` file per language
+pub const LANGUAGE_DIR: &str = "assets/lang";
+
+/// Language whose table every other one falls back to for keys it lacks
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Tables loaded on first use, and the language picked in the settings
+static LOCALIZATION: OnceLock<RwLock<Localization>> = OnceLock::new();
+
+/// One language's text for every key it translates; text may contain `{name}` placeholders
+#[derive(Debug, Clone, Deserialize)]
+pub struct StringTable {
+    pub name: String, // The language's name in itself, shown in the language dropdown
+    pub strings: HashMap<String, String>,
+}
+
+impl StringTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+}
+
+/// Every string table found, by language code
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    language: String,
+}
+
+impl Localization {
+    /// Read every table in `dir`; tables that fail to load are left out with a warning
+    pub fn load(dir: impl AsRef<Path>) -> Self {
+        let mut tables = HashMap::new();
+        match fs::read_dir(dir.as_ref()) {
+            Ok(entries) => {
+                for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                    if path.extension().map_or(true, |extension| extension != "ron") {
+                        continue;
+                    }
+                    let code = match path.file_stem().and_then(|stem| stem.to_str()) {
+                        Some(code) => code.to_string(),
+                        None => continue,
+                    };
+                    match StringTable::load(&path) {
+                        Ok(table) => {
+                            tables.insert(code, table);
+                        }
+                        Err(e) => warn!("Failed to load string table {}: {}", path.display(), e),
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to read {}, showing string keys: {}", dir.as_ref().display(), e),
+        }
+        
+        Self {
+            tables,
+            language: DEFAULT_LANGUAGE.to_string(),
+        }
+    }
+    
+    /// Text for a key in the current language, else in the default language
+    fn lookup(&self, key: &str) -> Option<&str> {
+        [self.language.as_str(), DEFAULT_LANGUAGE]
+            .iter()
+            .filter_map(|code| self.tables.get(*code))
+            .find_map(|table| table.strings.get(key))
+            .map(String::as_str)
+    }
+}
+
+fn localization() -> &'static RwLock<Localization> {
+    LOCALIZATION.get_or_init(|| RwLock::new(Localization::load(LANGUAGE_DIR)))
+}
+
+/// Text for a key in the current language. Keys the language lacks show in English, and keys
+/// no table has show as the key itself, so missing translations are easy to spot
+pub fn tr(key: &str) -> String {
+    localization().read().unwrap().lookup(key).unwrap_or(key).to_string()
+}
+
+/// `tr` with each `{name}` placeholder replaced by its value
+pub fn tr_args(key: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(tr(key), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+}
+
+/// Languages with a string table as (code, name), the default language first and the rest by code
+pub fn languages() -> Vec<(String, String)> {
+    let localization = localization().read().unwrap();
+    let mut languages: Vec<(String, String)> = localization
+        .tables
+        .iter()
+        .map(|(code, table)| (code.clone(), table.name.clone()))
+        .collect();
+    languages.sort_by_key(|(code, _)| (code.as_str() != DEFAULT_LANGUAGE, code.clone()));
+    languages
+}
+
+pub fn current_language() -> String {
+    localization().read().unwrap().language.clone()
+}
+
+/// Switch the language text is looked up in; codes without a table fall back to the default language
+pub fn set_language(code: &str) {
+    let mut localization = localization().write().unwrap();
+    if !localization.tables.contains_key(code) {
+        warn!("No string table for language {:?}, using {}", code, DEFAULT_LANGUAGE);
+    }
+    localization.language = code.to_string();
+}
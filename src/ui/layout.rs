@@ -0,0 +1,100 @@
+use glam::Vec2;
+
+use crate::ui::UiAlignment;
+
+/// Where an element sits in its parent (the screen, for top-level elements), so it follows window resizes
+/// instead of keeping pixel positions worked out for one resolution. All values are logical pixels;
+/// the UI manager scales them to the window's DPI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub anchor: Vec2, // Point of the parent the element is pinned to, as a fraction of its size; (1, 1) is bottom-right
+    pub pivot: Vec2,  // Point of the element placed on the anchor, as a fraction of the element's size
+    pub offset: Vec2, // From the anchor
+    pub size: Vec2,
+}
+
+impl Layout {
+    /// Pinned by the same corner, edge or center on the element and its parent, e.g. BottomRight to BottomRight
+    pub fn aligned(alignment: UiAlignment, offset: Vec2, size: Vec2) -> Self {
+        Self::at_fraction(alignment.fraction(), offset, size)
+    }
+    
+    /// Pinned at a fraction of the parent's size, e.g. (0.5, 0.25) for a quarter of the way down the middle.
+    /// The element's matching point goes there, so it never runs off the side it is anchored near
+    pub fn at_fraction(anchor: Vec2, offset: Vec2, size: Vec2) -> Self {
+        Self {
+            anchor,
+            pivot: anchor,
+            offset,
+            size,
+        }
+    }
+    
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+    
+    /// Layout for an element of `size` sitting `spacing` above this one, lined up with it at the same anchor
+    pub fn above(&self, size: Vec2, spacing: f32) -> Self {
+        let top = self.offset.y - self.size.y * self.pivot.y;
+        Self {
+            offset: Vec2::new(self.offset.x, top - spacing - size.y * (1.0 - self.pivot.y)),
+            size,
+            ..*self
+        }
+    }
+    
+    /// Top-left corner and size inside a parent rectangle
+    pub fn resolve(&self, parent_position: Vec2, parent_size: Vec2) -> (Vec2, Vec2) {
+        let position = parent_position + parent_size * self.anchor + self.offset - self.size * self.pivot;
+        (position, self.size)
+    }
+    
+    /// Top-left corner and size of a top-level element
+    pub fn place(&self, screen_size: Vec2) -> (Vec2, Vec2) {
+        self.resolve(Vec2::ZERO, screen_size)
+    }
+}
+
+/// Direction a stack lays its elements out in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackDirection {
+    Row,    // Left to right
+    Column, // Top to bottom
+}
+
+/// Auto-layout container that lines elements up one after another, such as a menu's button column.
+/// It grows rightwards or downwards from `start`, so the start's pivot belongs on its leading edge.
+#[derive(Debug, Clone, Copy)]
+pub struct Stack {
+    start: Layout, // Placement of the first element; each element keeps its own size
+    direction: StackDirection,
+    spacing: f32, // Gap between neighbours
+}
+
+impl Stack {
+    pub fn new(start: Layout, direction: StackDirection, spacing: f32) -> Self {
+        Self {
+            start,
+            direction,
+            spacing,
+        }
+    }
+    
+    /// Layouts for elements of the given sizes, in stacking order
+    pub fn layouts(&self, sizes: &[Vec2]) -> Vec<Layout> {
+        let mut offset = self.start.offset;
+        sizes
+            .iter()
+            .map(|&size| {
+                let layout = Layout { offset, size, ..self.start };
+                offset += match self.direction {
+                    StackDirection::Row => Vec2::new(size.x + self.spacing, 0.0),
+                    StackDirection::Column => Vec2::new(0.0, size.y + self.spacing),
+                };
+                layout
+            })
+            .collect()
+    }
+}
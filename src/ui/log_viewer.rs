@@ -0,0 +1,117 @@
+use glam::{Vec2, Vec4};
+use tracing::Level;
+use wgpu::RenderPass;
+
+use crate::engine::logging::{self, LogEntry};
+use crate::ui::{UiAlignment, UiPipeline, UiQuad};
+use crate::ui::font::{self, SMALL_TEXT_HEIGHT, TEXT_HEIGHT};
+use crate::ui::layout::Layout;
+
+/// Newest entries listed; older ones stay in the log file
+const LISTED_ENTRIES: usize = 20;
+
+/// Size of the viewer panel
+const VIEWER_SIZE: Vec2 = Vec2::new(520.0, 330.0);
+
+/// Gap between the viewer and the screen edges
+const MARGIN: f32 = 10.0;
+
+/// Gap between the panel's edges and what is drawn on it
+const PADDING: f32 = 8.0;
+
+/// Distance from one entry's line to the next
+const LINE_SPACING: f32 = SMALL_TEXT_HEIGHT + 4.0;
+
+const PANEL_COLOR: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.7);
+const TITLE_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.9, 1.0);
+const ERROR_COLOR: Vec4 = Vec4::new(1.0, 0.35, 0.35, 1.0);
+const WARNING_COLOR: Vec4 = Vec4::new(1.0, 0.85, 0.3, 1.0);
+const OTHER_COLOR: Vec4 = Vec4::new(0.8, 0.8, 0.8, 1.0);
+
+/// Panel listing the latest warnings and errors, optionally from one subsystem only
+pub struct LogViewer {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    target: Option<String>, // Subsystem shown; None shows every one
+}
+
+impl LogViewer {
+    pub fn new(screen_size: Vec2) -> Self {
+        let mut viewer = Self {
+            position: Vec2::ZERO,
+            size: VIEWER_SIZE,
+            visible: false,
+            target: None,
+        };
+        viewer.resize(screen_size);
+        viewer
+    }
+    
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+    
+    /// Only list entries logged under the given target, or every entry for None
+    pub fn set_target(&mut self, target: Option<String>) {
+        self.target = target;
+    }
+    
+    /// The newest entries shown, oldest first so the latest is at the bottom
+    pub fn entries(&self) -> Vec<LogEntry> {
+        let mut entries: Vec<LogEntry> = logging::recent_entries()
+            .into_iter()
+            .filter(|entry| self.target.as_ref().map_or(true, |target| &entry.target == target))
+            .collect();
+        let skipped = entries.len().saturating_sub(LISTED_ENTRIES);
+        entries.drain(..skipped);
+        entries
+    }
+    
+    /// Lines of text shown in the panel, e.g. "  12.3s WARN  net: Dropped message from ..."
+    pub fn lines(&self) -> Vec<String> {
+        self.entries().iter().map(entry_line).collect()
+    }
+    
+    pub fn resize(&mut self, screen_size: Vec2) {
+        // Middle of the left edge, below the resource bar and above the minimap
+        let layout = Layout::aligned(UiAlignment::Left, Vec2::new(MARGIN, 0.0), self.size);
+        self.position = layout.place(screen_size).0;
+    }
+    
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let mut quads = vec![UiQuad::rect(self.position, self.size, PANEL_COLOR)];
+        let title = match &self.target {
+            Some(target) => format!("Warnings and errors: {}", target),
+            None => "Warnings and errors".to_string(),
+        };
+        let title_position = self.position + Vec2::splat(PADDING);
+        font::push_text(&mut quads, &title, title_position, TEXT_HEIGHT, TITLE_COLOR);
+        
+        let max_chars = font::chars_fitting(self.size.x - PADDING * 2.0, SMALL_TEXT_HEIGHT);
+        let top = title_position.y + TEXT_HEIGHT + PADDING;
+        for (row, entry) in self.entries().iter().enumerate() {
+            let color = match entry.level {
+                Level::ERROR => ERROR_COLOR,
+                Level::WARN => WARNING_COLOR,
+                _ => OTHER_COLOR,
+            };
+            let line: String = entry_line(entry).chars().take(max_chars).collect();
+            let position = Vec2::new(title_position.x, top + row as f32 * LINE_SPACING);
+            font::push_text(&mut quads, &line, position, SMALL_TEXT_HEIGHT, color);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+}
+
+fn entry_line(entry: &LogEntry) -> String {
+    format!("{:>7.1}s {:<5} {}: {}", entry.seconds, entry.level, entry.target, entry.message)
+}
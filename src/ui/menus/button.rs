@@ -2,7 +2,11 @@ use glam::{Vec2, Vec4};
 use wgpu::RenderPass;
 use std::any::Any;
 
-use crate::ui::{UiElement, UiElementType, UiPipeline, UiAlignment};
+use crate::ui::{UiElement, UiElementType, UiPipeline, UiAlignment, UiColorScheme, UiQuad};
+use crate::ui::font::{self, TEXT_HEIGHT};
+
+/// Gap between the label and the edge it is aligned to
+const TEXT_PADDING: f32 = 8.0;
 
 pub struct Button {
     position: Vec2,
@@ -11,6 +15,7 @@ pub struct Button {
     visible: bool,
     enabled: bool,
     hovered: bool,
+    pressed: bool, // Left mouse button went down on the button and hasn't come up yet
     color_normal: Vec4,
     color_hovered: Vec4,
     color_pressed: Vec4,
@@ -29,6 +34,7 @@ impl Button {
             visible: true,
             enabled: true,
             hovered: false,
+            pressed: false,
             color_normal: Vec4::new(0.3, 0.3, 0.3, 1.0),
             color_hovered: Vec4::new(0.4, 0.4, 0.4, 1.0),
             color_pressed: Vec4::new(0.5, 0.5, 0.5, 1.0),
@@ -75,6 +81,23 @@ impl Button {
     
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
+        if !enabled {
+            self.hovered = false;
+            self.pressed = false;
+        }
+    }
+    
+    /// Background color for the button's current state; pressed only shows while the cursor is still on it
+    fn background_color(&self) -> Vec4 {
+        if !self.enabled {
+            self.color_disabled
+        } else if self.pressed && self.hovered {
+            self.color_pressed
+        } else if self.hovered {
+            self.color_hovered
+        } else {
+            self.color_normal
+        }
     }
 }
 
@@ -97,6 +120,10 @@ impl UiElement for Button {
     
     fn set_visible(&mut self, visible: bool) {
         self.visible = visible;
+        if !visible {
+            self.hovered = false;
+            self.pressed = false;
+        }
     }
     
     fn contains_point(&self, point: Vec2) -> bool {
@@ -112,20 +139,12 @@ impl UiElement for Button {
             return;
         }
         
-        // Set up pipeline
-        render_pass.set_pipeline(&ui_pipeline.pipeline);
-        render_pass.set_vertex_buffer(0, ui_pipeline.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(ui_pipeline.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        
-        // In a real implementation, we would:
-        // 1. Create vertices for the button based on position and size
-        // 2. Update vertex buffer or use instance data
-        // 3. Set the proper color based on state (normal, hovered, pressed, disabled)
-        // 4. Draw the button background
-        // 5. Draw the button text
-        
-        // For now, we'll just draw the button using the default quad
-        render_pass.draw_indexed(0..6, 0, 0..1);
+        // The background follows the hover and press state; disabled labels fade with it
+        let mut quads = vec![UiQuad::rect(self.position, self.size, self.background_color())];
+        let text_color = if self.enabled { self.text_color } else { self.text_color * Vec4::new(1.0, 1.0, 1.0, 0.5) };
+        let text_position = font::align_text(&self.text, TEXT_HEIGHT, self.position, self.size, self.alignment, TEXT_PADDING);
+        font::push_text(&mut quads, &self.text, text_position, TEXT_HEIGHT, text_color);
+        ui_pipeline.draw(render_pass, &quads);
     }
     
     fn handle_click(&mut self, _position: Vec2) -> bool {
@@ -140,6 +159,40 @@ impl UiElement for Button {
         }
     }
     
+    fn handle_hover(&mut self, position: Vec2) -> bool {
+        let hovered = self.contains_point(position);
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+    
+    fn handle_press(&mut self, _position: Vec2) -> bool {
+        if !self.visible || !self.enabled {
+            return false;
+        }
+        
+        self.pressed = true;
+        true
+    }
+    
+    fn handle_release(&mut self, _position: Vec2) -> bool {
+        std::mem::take(&mut self.pressed)
+    }
+    
+    fn set_bounds(&mut self, position: Vec2, size: Vec2) {
+        self.position = position;
+        self.size = size;
+    }
+    
+    /// Buttons given their own colors with with_colors are recolored too
+    fn apply_color_scheme(&mut self, color_scheme: &UiColorScheme) {
+        self.color_normal = color_scheme.button;
+        self.color_hovered = color_scheme.button_hover;
+        self.color_pressed = color_scheme.button_active;
+        self.color_disabled = color_scheme.button * Vec4::new(1.0, 1.0, 1.0, 0.5);
+        self.text_color = color_scheme.text;
+    }
+    
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,25 +1,179 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::path::PathBuf;
+use tracing::{debug, error, info};
 
+use crate::engine::audio::AudioSettings;
+use crate::engine::input::KeyAction;
+use crate::engine::renderer::{WindowMode, RESOLUTIONS};
 use crate::game::{GameState, GamePhase};
+use crate::game::settings::{self, MAX_GAME_SPEED, MIN_GAME_SPEED};
+use crate::game::ai::{AiSlotConfig, AiDifficulty, AiPersonality, RESOURCE_BONUS_OPTIONS};
+use crate::game::campaign::Campaign;
+use crate::game::map::{MapPreset, MapSymmetry};
+use crate::game::map_files;
+use crate::game::scenario::{SCENARIO_DIR, TUTORIAL_SCENARIO};
+use crate::game::victory::VictoryConditionType;
 use crate::ui::UiManager;
-use crate::ui::menus::MenuFactory;
+use crate::ui::codex::CodexPage;
+use crate::ui::i18n;
+use crate::ui::menus::{briefing_screen, MenuFactory, ServerList, TextInput};
+
+/// Save/load action requested from the pause menu, carried out by the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveRequest {
+    Save(u8),
+    Load(u8),
+}
+
+/// Key binding change requested from the controls menu, carried out by the engine's input handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeybindingRequest {
+    /// Bind the next key pressed to this action
+    Rebind(KeyAction),
+    ResetDefaults,
+}
+
+/// Multiplayer action chosen in the multiplayer or lobby menus, carried out by the engine's network session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LobbyRequest {
+    Host,
+    /// Join the lobby at this address; the port may be left out
+    Join(String),
+    /// Clear the list of games on the local network and listen for them again
+    RefreshGames,
+    Leave,
+    SetReady(bool),
+    SetTeam(u8),
+    SetColor(u8),
+    Chat(String),
+    StartGame,
+}
+
+/// Game speed for a settings slider position between 0 and 1
+fn game_speed_from_slider(value: f32) -> f32 {
+    MIN_GAME_SPEED + (MAX_GAME_SPEED - MIN_GAME_SPEED) * value
+}
+
+/// A change to the UI a menu control asked for. Controls run while the UI manager is handling input,
+/// so the change waits until the engine passes the manager back in
+type UiAction = Box<dyn FnOnce(&mut UiManager)>;
 
 /// Manages callbacks for menu interactions
 pub struct MenuCallbacks {
     game_state: Rc<RefCell<GameState>>,
-    ui_manager: Rc<RefCell<UiManager>>,
+    ui_actions: Rc<RefCell<Vec<UiAction>>>,
+    save_request: Rc<RefCell<Option<SaveRequest>>>,
+    audio_settings: Rc<RefCell<AudioSettings>>,
+    keybinding_request: Rc<RefCell<Option<KeybindingRequest>>>,
+    lobby_request: Rc<RefCell<Option<LobbyRequest>>>,
+    scenario_request: Rc<RefCell<Option<PathBuf>>>,
+    campaign_request: Rc<RefCell<Option<usize>>>,
 }
 
 impl MenuCallbacks {
-    /// Create a new MenuCallbacks instance
-    pub fn new(game_state: &mut GameState, ui_manager: &mut UiManager) -> Self {
+    /// Create a new MenuCallbacks instance acting on the engine's game state
+    pub fn new(game_state: Rc<RefCell<GameState>>) -> Self {
+        let audio_settings = game_state.borrow().settings.audio.clone();
         Self {
-            game_state: Rc::new(RefCell::new(game_state.clone())),
-            ui_manager: Rc::new(RefCell::new(ui_manager.clone())),
+            game_state,
+            ui_actions: Rc::new(RefCell::new(Vec::new())),
+            save_request: Rc::new(RefCell::new(None)),
+            audio_settings: Rc::new(RefCell::new(audio_settings)),
+            keybinding_request: Rc::new(RefCell::new(None)),
+            lobby_request: Rc::new(RefCell::new(None)),
+            scenario_request: Rc::new(RefCell::new(None)),
+            campaign_request: Rc::new(RefCell::new(None)),
         }
     }
-
+    
+    /// Make the UI changes menu controls asked for since the last call, such as switching screens
+    pub fn run_ui_actions(&self, ui_manager: &mut UiManager) {
+        let actions = std::mem::take(&mut *self.ui_actions.borrow_mut());
+        for action in actions {
+            action(ui_manager);
+        }
+    }
+    
+    /// Leave a change to the UI for the next `run_ui_actions`
+    fn defer(ui_actions: &Rc<RefCell<Vec<UiAction>>>, action: impl FnOnce(&mut UiManager) + 'static) {
+        ui_actions.borrow_mut().push(Box::new(action));
+    }
+    
+    /// Switch to another menu screen on the next `run_ui_actions`
+    fn show_screen(ui_actions: &Rc<RefCell<Vec<UiAction>>>, screen: impl Into<String>) {
+        let screen = screen.into();
+        Self::defer(ui_actions, move |ui_manager| ui_manager.set_active_screen(&screen));
+    }
+    
+    /// Take the pending save/load request, if a pause menu button was clicked
+    pub fn take_save_request(&self) -> Option<SaveRequest> {
+        self.save_request.borrow_mut().take()
+    }
+    
+    /// Take the pending key binding change, if a controls menu button was clicked
+    pub fn take_keybinding_request(&self) -> Option<KeybindingRequest> {
+        self.keybinding_request.borrow_mut().take()
+    }
+    
+    /// Take the pending multiplayer action, if a multiplayer or lobby menu control was used
+    pub fn take_lobby_request(&self) -> Option<LobbyRequest> {
+        self.lobby_request.borrow_mut().take()
+    }
+    
+    /// Take the scenario file to start, if a menu button picked one
+    pub fn take_scenario_request(&self) -> Option<PathBuf> {
+        self.scenario_request.borrow_mut().take()
+    }
+    
+    /// Take the campaign mission to start, if a briefing's start button was clicked
+    pub fn take_campaign_request(&self) -> Option<usize> {
+        self.campaign_request.borrow_mut().take()
+    }
+    
+    /// Volume and mute options as last set in the settings menu
+    pub fn audio_settings(&self) -> AudioSettings {
+        self.audio_settings.borrow().clone()
+    }
+    
+    /// Record a change to the audio options, keeping the game settings in step
+    fn update_audio_settings(
+        audio_settings: &Rc<RefCell<AudioSettings>>,
+        game_state: &Rc<RefCell<GameState>>,
+        update: impl FnOnce(&mut AudioSettings),
+    ) {
+        let mut settings = audio_settings.borrow_mut();
+        update(&mut settings);
+        game_state.borrow_mut().settings.audio = settings.clone();
+    }
+    
+    /// Take the display mode and resolution picked in the settings dropdowns
+    fn read_display_settings(ui_manager: &UiManager, game_state: &mut GameState) {
+        let selected = |id: &str| {
+            ui_manager.get_element(id)
+                .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                .map(|d| d.get_selected_index())
+        };
+        
+        if let Some(mode) = selected("settings_display_mode").and_then(|index| WindowMode::ALL.get(index)) {
+            game_state.settings.video.window_mode = *mode;
+        }
+        if let Some(resolution) = selected("settings_resolution").and_then(|index| RESOLUTIONS.get(index)) {
+            game_state.settings.video.resolution = *resolution;
+        }
+    }
+    
+    /// Take the player name typed in the settings menu; an empty field keeps the current name
+    fn read_player_name(ui_manager: &UiManager, game_state: &mut GameState) {
+        let name = ui_manager.get_element("settings_player_name")
+            .and_then(|e| e.as_any().downcast_ref::<TextInput>())
+            .map(|input| input.get_text().trim().to_string());
+        
+        if let Some(name) = name.filter(|name| settings::is_valid_player_name(name)) {
+            game_state.settings.player_name = name;
+        }
+    }
+    
     /// Attach callbacks to UI elements
     pub fn attach_callbacks(&self, ui_manager: &mut UiManager) {
         // Main Menu Callbacks
@@ -28,48 +182,87 @@ impl MenuCallbacks {
         // Multiplayer Menu Callbacks
         self.attach_multiplayer_menu_callbacks(ui_manager);
         
+        // Lobby Callbacks
+        self.attach_lobby_callbacks(ui_manager);
+        
         // Settings Menu Callbacks
         self.attach_settings_menu_callbacks(ui_manager);
         
+        // Controls Menu Callbacks
+        self.attach_controls_menu_callbacks(ui_manager);
+        
         // Game Setup Callbacks
         self.attach_game_setup_callbacks(ui_manager);
         
         // Pause Menu Callbacks
         self.attach_pause_menu_callbacks(ui_manager);
         
+        // Codex Callbacks
+        self.attach_codex_callbacks(ui_manager);
+        
+        // Campaign Callbacks
+        self.attach_campaign_callbacks(ui_manager);
+        
         // Game Over Menu Callbacks
         self.attach_game_over_menu_callbacks(ui_manager);
     }
-
+    
     /// Attach main menu button callbacks
     fn attach_main_menu_callbacks(&self, ui_manager: &mut UiManager) {
-        let game_state_clone = Rc::clone(&self.game_state);
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
-
         // Play button
         if let Some(play_button) = ui_manager.get_element_mut("main_menu_play_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
             play_button.set_on_click(move || {
-                ui_manager_clone.borrow_mut().set_active_screen("game_setup");
+                Self::show_screen(&ui_actions, "game_setup");
                 true
             });
         }
-
+        
+        // Campaign button
+        if let Some(campaign_button) = ui_manager.get_element_mut("main_menu_campaign_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            campaign_button.set_on_click(move || {
+                Self::show_screen(&ui_actions, "campaign");
+                true
+            });
+        }
+        
+        // Tutorial button
+        if let Some(tutorial_button) = ui_manager.get_element_mut("main_menu_tutorial_button") {
+            let scenario_request = Rc::clone(&self.scenario_request);
+            tutorial_button.set_on_click(move || {
+                *scenario_request.borrow_mut() = Some(PathBuf::from(SCENARIO_DIR).join(TUTORIAL_SCENARIO));
+                true
+            });
+        }
+        
         // Multiplayer button
         if let Some(multiplayer_button) = ui_manager.get_element_mut("main_menu_multiplayer_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
             multiplayer_button.set_on_click(move || {
-                ui_manager_clone.borrow_mut().set_active_screen("multiplayer");
+                Self::show_screen(&ui_actions, "multiplayer");
                 true
             });
         }
-
+        
         // Settings button
         if let Some(settings_button) = ui_manager.get_element_mut("main_menu_settings_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
             settings_button.set_on_click(move || {
-                ui_manager_clone.borrow_mut().set_active_screen("settings");
+                Self::show_screen(&ui_actions, "settings");
                 true
             });
         }
-
+        
+        // Codex button
+        if let Some(codex_button) = ui_manager.get_element_mut("main_menu_codex_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            codex_button.set_on_click(move || {
+                Self::show_screen(&ui_actions, CodexPage::Units.screen());
+                true
+            });
+        }
+        
         // Exit button
         if let Some(exit_button) = ui_manager.get_element_mut("main_menu_exit_button") {
             exit_button.set_on_click(|| {
@@ -79,116 +272,522 @@ impl MenuCallbacks {
     }
     /// Attach multiplayer menu button callbacks
     fn attach_multiplayer_menu_callbacks(&self, ui_manager: &mut UiManager) {
-        let game_state_clone = Rc::clone(&self.game_state);
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
-
         // Host game button
         if let Some(host_button) = ui_manager.get_element_mut("multiplayer_host_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
             host_button.set_on_click(move || {
-                // Implement host game logic
-                ui_manager_clone.borrow_mut().set_active_screen("game_setup");
+                *lobby_request.borrow_mut() = Some(LobbyRequest::Host);
+                Self::show_screen(&ui_actions, "lobby");
                 true
             });
         }
-
-        // Join game button
+        
+        // Join game button, connecting to the address typed above it
         if let Some(join_button) = ui_manager.get_element_mut("multiplayer_join_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
             join_button.set_on_click(move || {
-                // Implement join game logic
-                ui_manager_clone.borrow_mut().set_active_screen("game_setup");
+                let lobby_request = Rc::clone(&lobby_request);
+                Self::defer(&ui_actions, move |ui_manager| {
+                    let address = ui_manager
+                        .get_element("multiplayer_address")
+                        .and_then(|e| e.as_any().downcast_ref::<TextInput>())
+                        .map(|input| input.get_text().trim().to_string())
+                        .unwrap_or_default();
+                    if address.is_empty() {
+                        return;
+                    }
+                    
+                    *lobby_request.borrow_mut() = Some(LobbyRequest::Join(address));
+                    ui_manager.set_active_screen("lobby");
+                });
                 true
             });
         }
-
+        
+        // Clicking a game found on the local network joins it
+        if let Some(server_list) = ui_manager.get_element_mut("multiplayer_server_list")
+            .and_then(|e| e.as_any_mut().downcast_mut::<ServerList>())
+        {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
+            server_list.set_on_join(move |game| {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::Join(game.address.to_string()));
+                Self::show_screen(&ui_actions, "lobby");
+                true
+            });
+        }
+        
+        if let Some(refresh_button) = ui_manager.get_element_mut("multiplayer_refresh_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            refresh_button.set_on_click(move || {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::RefreshGames);
+                true
+            });
+        }
+        
+        // Enter in the address field joins as well
+        if let Some(address_input) = ui_manager.get_element_mut("multiplayer_address")
+            .and_then(|e| e.as_any_mut().downcast_mut::<TextInput>())
+        {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
+            address_input.set_on_submit(move |address| {
+                let address = address.trim();
+                if address.is_empty() {
+                    return false;
+                }
+                
+                *lobby_request.borrow_mut() = Some(LobbyRequest::Join(address.to_string()));
+                Self::show_screen(&ui_actions, "lobby");
+                true
+            });
+        }
+        
         // Back button
         if let Some(back_button) = ui_manager.get_element_mut("multiplayer_back_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
             back_button.set_on_click(move || {
-                ui_manager_clone.borrow_mut().set_active_screen("main_menu");
+                Self::show_screen(&ui_actions, "main_menu");
                 true
             });
         }
     }
-
+    
+    /// Attach lobby callbacks; the lobby screen is rebuilt as players come and go, so this runs again after each rebuild
+    pub fn attach_lobby_callbacks(&self, ui_manager: &mut UiManager) {
+        // Our color and team pickers
+        if let Some(color) = ui_manager.get_element_mut("lobby_color") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            color.set_on_change(move |index| {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::SetColor(index as u8));
+                true
+            });
+        }
+        
+        if let Some(team) = ui_manager.get_element_mut("lobby_team") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            team.set_on_change(move |index| {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::SetTeam(index as u8));
+                true
+            });
+        }
+        
+        // Ready checkbox
+        if let Some(ready) = ui_manager.get_element_mut("lobby_ready") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            ready.set_on_change(move |checked| {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::SetReady(checked));
+                true
+            });
+        }
+        
+        // Send button, posting the chat line typed beside it
+        if let Some(send_button) = ui_manager.get_element_mut("lobby_send_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
+            send_button.set_on_click(move || {
+                let lobby_request = Rc::clone(&lobby_request);
+                Self::defer(&ui_actions, move |ui_manager| {
+                    // The lobby rebuild that shows the new line also empties the input
+                    let text = ui_manager
+                        .get_element("lobby_chat_input")
+                        .and_then(|e| e.as_any().downcast_ref::<TextInput>())
+                        .map(|input| input.get_text().trim().to_string())
+                        .unwrap_or_default();
+                    if !text.is_empty() {
+                        *lobby_request.borrow_mut() = Some(LobbyRequest::Chat(text));
+                    }
+                });
+                true
+            });
+        }
+        
+        // Enter in the chat field sends the line too
+        if let Some(chat_input) = ui_manager.get_element_mut("lobby_chat_input")
+            .and_then(|e| e.as_any_mut().downcast_mut::<TextInput>())
+        {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            chat_input.set_on_submit(move |text| {
+                let text = text.trim();
+                if text.is_empty() {
+                    return false;
+                }
+                
+                *lobby_request.borrow_mut() = Some(LobbyRequest::Chat(text.to_string()));
+                true
+            });
+        }
+        
+        // Shared vision checkbox (host only), sent to everyone when the game starts
+        if let Some(shared_vision) = ui_manager.get_element_mut("lobby_shared_vision") {
+            let game_state = Rc::clone(&self.game_state);
+            shared_vision.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.shared_vision = checked;
+                true
+            });
+        }
+        
+        // Day and night checkbox (host only), sent to everyone when the game starts
+        if let Some(day_night) = ui_manager.get_element_mut("lobby_day_night") {
+            let game_state = Rc::clone(&self.game_state);
+            day_night.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.day_night_cycle = checked;
+                true
+            });
+        }
+        
+        // Start button (host only)
+        if let Some(start_button) = ui_manager.get_element_mut("lobby_start_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            start_button.set_on_click(move || {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::StartGame);
+                true
+            });
+        }
+        
+        // Leave button
+        if let Some(leave_button) = ui_manager.get_element_mut("lobby_leave_button") {
+            let lobby_request = Rc::clone(&self.lobby_request);
+            let ui_actions = Rc::clone(&self.ui_actions);
+            leave_button.set_on_click(move || {
+                *lobby_request.borrow_mut() = Some(LobbyRequest::Leave);
+                Self::show_screen(&ui_actions, "multiplayer");
+                true
+            });
+        }
+    }
+    
     /// Attach settings menu button callbacks
     fn attach_settings_menu_callbacks(&self, ui_manager: &mut UiManager) {
         let game_state_clone = Rc::clone(&self.game_state);
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
-
+        
+        // Master volume slider
+        if let Some(master_volume) = ui_manager.get_element_mut("settings_master_volume") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
+            master_volume.set_on_change(move |volume| {
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.master_volume = volume);
+                true
+            });
+        }
+        
         // Music volume slider
         if let Some(music_volume) = ui_manager.get_element_mut("settings_music_volume") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
             music_volume.set_on_change(move |volume| {
-                // Update audio settings
-                println!("Music volume: {}", volume);
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.music_volume = volume);
                 true
             });
         }
-
+        
         // Sound effects volume slider
         if let Some(sfx_volume) = ui_manager.get_element_mut("settings_sfx_volume") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
             sfx_volume.set_on_change(move |volume| {
-                // Update audio settings
-                println!("SFX volume: {}", volume);
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.sfx_volume = volume);
                 true
             });
         }
-
-        // Fullscreen checkbox
-        if let Some(fullscreen) = ui_manager.get_element_mut("settings_fullscreen") {
-            fullscreen.set_on_change(move |checked| {
-                // Toggle fullscreen
-                println!("Fullscreen: {}", checked);
+        
+        // Master mute checkbox
+        if let Some(master_mute) = ui_manager.get_element_mut("settings_master_mute") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
+            master_mute.set_on_change(move |checked| {
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.master_muted = checked);
                 true
             });
         }
-
+        
+        // Music mute checkbox
+        if let Some(music_mute) = ui_manager.get_element_mut("settings_music_mute") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
+            music_mute.set_on_change(move |checked| {
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.music_muted = checked);
+                true
+            });
+        }
+        
+        // Sound effects mute checkbox
+        if let Some(sfx_mute) = ui_manager.get_element_mut("settings_sfx_mute") {
+            let audio_settings = Rc::clone(&self.audio_settings);
+            let game_state = Rc::clone(&self.game_state);
+            sfx_mute.set_on_change(move |checked| {
+                Self::update_audio_settings(&audio_settings, &game_state, |audio| audio.sfx_muted = checked);
+                true
+            });
+        }
+        
+        // V-Sync checkbox
+        if let Some(vsync) = ui_manager.get_element_mut("settings_vsync") {
+            let game_state = Rc::clone(&self.game_state);
+            vsync.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.video.vsync = checked;
+                true
+            });
+        }
+        
+        // Fog of war checkbox
+        if let Some(fog_of_war) = ui_manager.get_element_mut("settings_fog_of_war") {
+            let game_state = Rc::clone(&self.game_state);
+            fog_of_war.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.fog_of_war_enabled = checked;
+                true
+            });
+        }
+        
+        // Performance overlay checkbox
+        if let Some(show_fps) = ui_manager.get_element_mut("settings_show_fps") {
+            let game_state = Rc::clone(&self.game_state);
+            show_fps.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.show_fps = checked;
+                true
+            });
+        }
+        
+        // Combat text checkbox
+        if let Some(combat_text) = ui_manager.get_element_mut("settings_combat_text") {
+            let game_state = Rc::clone(&self.game_state);
+            combat_text.set_on_change(move |checked| {
+                game_state.borrow_mut().settings.combat_text = checked;
+                true
+            });
+        }
+        
+        // Game speed slider
+        if let Some(game_speed) = ui_manager.get_element_mut("settings_game_speed") {
+            let game_state = Rc::clone(&self.game_state);
+            game_speed.set_on_change(move |value| {
+                game_state.borrow_mut().settings.game_speed = game_speed_from_slider(value);
+                true
+            });
+        }
+        
+        // Theme dropdown, applied by the engine on the next frame
+        let themes = ui_manager.theme_names();
+        if let Some(theme) = ui_manager.get_element_mut("settings_theme") {
+            let game_state = Rc::clone(&self.game_state);
+            theme.set_on_change(move |index| {
+                if let Some(name) = themes.get(index) {
+                    game_state.borrow_mut().settings.ui_theme = name.clone();
+                }
+                true
+            });
+        }
+        
+        // Language dropdown, applied by the engine on the next frame
+        let languages = i18n::languages();
+        if let Some(language) = ui_manager.get_element_mut("settings_language") {
+            let game_state = Rc::clone(&self.game_state);
+            language.set_on_change(move |index| {
+                if let Some((code, _)) = languages.get(index) {
+                    game_state.borrow_mut().settings.language = code.clone();
+                }
+                true
+            });
+        }
+        
+        // Controls button
+        if let Some(controls_button) = ui_manager.get_element_mut("settings_controls_button") {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            controls_button.set_on_click(move || {
+                Self::show_screen(&ui_actions, "controls");
+                true
+            });
+        }
+        
         // Save button
         if let Some(save_button) = ui_manager.get_element_mut("settings_save_button") {
+            let game_state = Rc::clone(&self.game_state);
+            let ui_actions = Rc::clone(&self.ui_actions);
             save_button.set_on_click(move || {
-                // Save settings
-                println!("Settings saved");
+                let game_state = Rc::clone(&game_state);
+                Self::defer(&ui_actions, move |ui_manager| {
+                    Self::read_display_settings(ui_manager, &mut game_state.borrow_mut());
+                    Self::read_player_name(ui_manager, &mut game_state.borrow_mut());
+                    match settings::save_settings(&game_state.borrow().settings) {
+                        Ok(()) => info!("Settings saved"),
+                        Err(e) => error!("Failed to save settings: {}", e),
+                    }
+                });
                 true
             });
         }
-
+        
         // Back button
         if let Some(back_button) = ui_manager.get_element_mut("settings_back_button") {
-            let ui_manager_clone = Rc::clone(&self.ui_manager);
+            let ui_actions = Rc::clone(&self.ui_actions);
             back_button.set_on_click(move || {
-                ui_manager_clone.borrow_mut().set_active_screen("main_menu");
+                Self::show_screen(&ui_actions, "main_menu");
                 true
             });
         }
     }
+/// Attach key binding menu button callbacks
+fn attach_controls_menu_callbacks(&self, ui_manager: &mut UiManager) {
+    // Each key button waits for a new key for its action
+    for action in KeyAction::all() {
+        if let Some(bind_button) = ui_manager.get_element_mut(&format!("controls_bind_{}", action.id())) {
+            let keybinding_request = Rc::clone(&self.keybinding_request);
+            bind_button.set_on_click(move || {
+                *keybinding_request.borrow_mut() = Some(KeybindingRequest::Rebind(action));
+                true
+            });
+        }
+    }
+    
+    // Reset button
+    if let Some(reset_button) = ui_manager.get_element_mut("controls_reset_button") {
+        let keybinding_request = Rc::clone(&self.keybinding_request);
+        reset_button.set_on_click(move || {
+            *keybinding_request.borrow_mut() = Some(KeybindingRequest::ResetDefaults);
+            true
+        });
+    }
+    
+    // Back button
+    if let Some(back_button) = ui_manager.get_element_mut("controls_back_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
+        back_button.set_on_click(move || {
+            Self::show_screen(&ui_actions, "settings");
+            true
+        });
+    }
+}
+
 /// Attach game setup menu button callbacks
 fn attach_game_setup_callbacks(&self, ui_manager: &mut UiManager) {
-    let game_state_clone = Rc::clone(&self.game_state);
-    let ui_manager_clone = Rc::clone(&self.ui_manager);
-
     // Start game button
     if let Some(start_button) = ui_manager.get_element_mut("game_setup_start_button") {
+        let game_state = Rc::clone(&self.game_state);
+        let ui_actions = Rc::clone(&self.ui_actions);
         start_button.set_on_click(move || {
-            // Get selected options from dropdowns
-            let mut game_state = game_state_clone.borrow_mut();
-            
-            // Set game phase to playing
-            game_state.phase = GamePhase::Playing;
-
-            // Start the game
-            game_state.start_game(
-                false, // Single player by default
-                2,     // Default 2 players
-                12345  // Default seed
-            );
-
-            ui_manager_clone.borrow_mut().set_active_screen("game");
+            let game_state = Rc::clone(&game_state);
+            Self::defer(&ui_actions, move |ui_manager| {
+                // Get selected options from dropdowns
+                let mut game_state = game_state.borrow_mut();
+                
+                // One AI opponent per slot picked in the AI dropdown
+                let ai_count = ui_manager
+                    .get_element("game_setup_ai_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(1);
+                // Free for all, or two teams with players alternating between them
+                let two_teams = ui_manager
+                    .get_element("game_setup_teams_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index() == 1)
+                    .unwrap_or(false);
+                let team_for = |player_id: u8| if two_teams { Some(player_id % 2) } else { None };
+                
+                // Every opponent plays at the chosen difficulty; only Hard ones take the resource bonus
+                let difficulty_index = ui_manager
+                    .get_element("game_setup_difficulty_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index());
+                let difficulty = difficulty_index.and_then(|index| AiDifficulty::ALL.get(index)).copied().unwrap_or_default();
+                let bonus_index = ui_manager
+                    .get_element("game_setup_bonus_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(0);
+                let resource_bonus = match difficulty {
+                    AiDifficulty::Hard => RESOURCE_BONUS_OPTIONS.get(bonus_index).copied().unwrap_or(0.0),
+                    _ => 0.0,
+                };
+                
+                // Procedural size or custom map, in the order the dropdown lists them
+                let map_index = ui_manager
+                    .get_element("game_setup_map_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(0);
+                if let Some(map) = map_files::setup_map_choices().into_iter().nth(map_index) {
+                    game_state.settings.map = map;
+                }
+                
+                // Terrain preset and symmetry, used if the map is a random one
+                let preset_index = ui_manager
+                    .get_element("game_setup_terrain_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(0);
+                game_state.settings.map_preset = MapPreset::ALL.get(preset_index).copied().unwrap_or_default();
+                let symmetry_index = ui_manager
+                    .get_element("game_setup_symmetry_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(0);
+                game_state.settings.map_symmetry = MapSymmetry::ALL.get(symmetry_index).copied().unwrap_or_default();
+                
+                // Annihilation, Time Limit or Resource Control
+                let victory_index = ui_manager
+                    .get_element("game_setup_victory_dropdown")
+                    .and_then(|e| e.as_any().downcast_ref::<Dropdown>())
+                    .map(|d| d.get_selected_index())
+                    .unwrap_or(0);
+                game_state.settings.victory_condition = VictoryConditionType::SETUP_OPTIONS
+                    .get(victory_index)
+                    .copied()
+                    .unwrap_or_default();
+                
+                game_state.settings.player_team = team_for(0);
+                game_state.settings.ai_slots = (1..=ai_count as u8)
+                    .map(|player_id| AiSlotConfig {
+                        player_id,
+                        difficulty,
+                        personality: AiPersonality::Balanced,
+                        team: team_for(player_id),
+                        resource_bonus,
+                    })
+                    .collect();
+                
+                // Set game phase to playing
+                game_state.phase = GamePhase::Playing;
+                
+                // Start the game
+                let player_count = game_state.settings.ai_slots.len() as u8 + 1;
+                game_state.start_game(
+                    false, // Single player by default
+                    player_count,
+                    12345  // Default seed
+                );
+                
+                ui_manager.set_active_screen("game");
+            });
             true
         });
     }
-
+    
+    // Shared vision checkbox
+    if let Some(shared_vision) = ui_manager.get_element_mut("game_setup_shared_vision") {
+        let game_state = Rc::clone(&self.game_state);
+        shared_vision.set_on_change(move |checked| {
+            game_state.borrow_mut().settings.shared_vision = checked;
+            true
+        });
+    }
+    
+    // Day and night checkbox; custom maps may turn the cycle on even when it is left unticked
+    if let Some(day_night) = ui_manager.get_element_mut("game_setup_day_night") {
+        let game_state = Rc::clone(&self.game_state);
+        day_night.set_on_change(move |checked| {
+            game_state.borrow_mut().settings.day_night_cycle = checked;
+            true
+        });
+    }
+    
     // Back button
     if let Some(back_button) = ui_manager.get_element_mut("game_setup_back_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
         back_button.set_on_click(move || {
-            ui_manager_clone.borrow_mut().set_active_screen("main_menu");
+            Self::show_screen(&ui_actions, "main_menu");
             true
         });
     }
@@ -197,59 +796,154 @@ fn attach_game_setup_callbacks(&self, ui_manager: &mut UiManager) {
 /// Attach pause menu button callbacks
 fn attach_pause_menu_callbacks(&self, ui_manager: &mut UiManager) {
     let game_state_clone = Rc::clone(&self.game_state);
-    let ui_manager_clone = Rc::clone(&self.ui_manager);
-
+    
     // Resume button
     if let Some(resume_button) = ui_manager.get_element_mut("pause_resume_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
         resume_button.set_on_click(move || {
             let mut game_state = game_state_clone.borrow_mut();
             game_state.resume();
-            ui_manager_clone.borrow_mut().set_active_screen("game");
+            Self::show_screen(&ui_actions, "game");
             true
         });
     }
-
+    
     // Settings button
     if let Some(settings_button) = ui_manager.get_element_mut("pause_settings_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
         settings_button.set_on_click(move || {
-            ui_manager_clone.borrow_mut().set_active_screen("settings");
+            Self::show_screen(&ui_actions, "settings");
             true
         });
     }
-
+    
+    // Codex button
+    if let Some(codex_button) = ui_manager.get_element_mut("pause_codex_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
+        codex_button.set_on_click(move || {
+            Self::show_screen(&ui_actions, CodexPage::Units.screen());
+            true
+        });
+    }
+    
+    // Save game button
+    if let Some(save_button) = ui_manager.get_element_mut("pause_save_button") {
+        let save_request = Rc::clone(&self.save_request);
+        save_button.set_on_click(move || {
+            *save_request.borrow_mut() = Some(SaveRequest::Save(0));
+            true
+        });
+    }
+    
+    // Load game button
+    if let Some(load_button) = ui_manager.get_element_mut("pause_load_button") {
+        let save_request = Rc::clone(&self.save_request);
+        load_button.set_on_click(move || {
+            *save_request.borrow_mut() = Some(SaveRequest::Load(0));
+            true
+        });
+    }
+    
     // Quit to main menu button
     if let Some(quit_button) = ui_manager.get_element_mut("pause_quit_button") {
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
+        let ui_actions = Rc::clone(&self.ui_actions);
         let game_state_clone = Rc::clone(&self.game_state);
         
         quit_button.set_on_click(move || {
             let mut game_state = game_state_clone.borrow_mut();
             game_state.phase = GamePhase::MainMenu;
-            ui_manager_clone.borrow_mut().set_active_screen("main_menu");
+            Self::show_screen(&ui_actions, "main_menu");
+            true
+        });
+    }
+}
+
+/// Attach codex tab and back button callbacks on every codex page
+fn attach_codex_callbacks(&self, ui_manager: &mut UiManager) {
+    for page in CodexPage::ALL {
+        // Every page has a tab for every page
+        for tab in CodexPage::ALL {
+            if let Some(tab_button) = ui_manager.get_element_mut(&format!("{}_{}_tab", page.screen(), tab.screen())) {
+                let ui_actions = Rc::clone(&self.ui_actions);
+                tab_button.set_on_click(move || {
+                    Self::show_screen(&ui_actions, tab.screen());
+                    true
+                });
+            }
+        }
+        
+        // Back to the pause menu when opened from a paused game, else to the main menu
+        if let Some(back_button) = ui_manager.get_element_mut(&format!("{}_back_button", page.screen())) {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            let game_state_clone = Rc::clone(&self.game_state);
+            back_button.set_on_click(move || {
+                let screen = if game_state_clone.borrow().phase == GamePhase::Paused { "pause" } else { "main_menu" };
+                Self::show_screen(&ui_actions, screen);
+                true
+            });
+        }
+    }
+}
+
+/// Attach campaign screen and mission briefing callbacks
+fn attach_campaign_callbacks(&self, ui_manager: &mut UiManager) {
+    if let Some(back_button) = ui_manager.get_element_mut("campaign_back_button") {
+        let ui_actions = Rc::clone(&self.ui_actions);
+        back_button.set_on_click(move || {
+            Self::show_screen(&ui_actions, "main_menu");
             true
         });
     }
+    
+    for index in 0..Campaign::load_or_default().missions.len() {
+        let briefing = briefing_screen(index);
+        
+        // Locked missions have their buttons disabled, so any click opens a playable briefing
+        if let Some(mission_button) = ui_manager.get_element_mut(&format!("campaign_mission_{}_button", index)) {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            let briefing = briefing.clone();
+            mission_button.set_on_click(move || {
+                Self::show_screen(&ui_actions, &briefing);
+                true
+            });
+        }
+        
+        if let Some(start_button) = ui_manager.get_element_mut(&format!("{}_start_button", briefing)) {
+            let campaign_request = Rc::clone(&self.campaign_request);
+            start_button.set_on_click(move || {
+                *campaign_request.borrow_mut() = Some(index);
+                true
+            });
+        }
+        
+        if let Some(back_button) = ui_manager.get_element_mut(&format!("{}_back_button", briefing)) {
+            let ui_actions = Rc::clone(&self.ui_actions);
+            back_button.set_on_click(move || {
+                Self::show_screen(&ui_actions, "campaign");
+                true
+            });
+        }
+    }
 }
 
 /// Attach game over menu button callbacks
 fn attach_game_over_menu_callbacks(&self, ui_manager: &mut UiManager) {
     let game_state_clone = Rc::clone(&self.game_state);
-    let ui_manager_clone = Rc::clone(&self.ui_manager);
-
+    
     // Replay button
     if let Some(replay_button) = ui_manager.get_element_mut("game_over_replay_button") {
         replay_button.set_on_click(move || {
             // Open replay viewer
-            println!("View replay clicked");
+            debug!("View replay clicked");
             true
         });
     }
-
+    
     // Rematch button
     if let Some(rematch_button) = ui_manager.get_element_mut("game_over_rematch_button") {
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
+        let ui_actions = Rc::clone(&self.ui_actions);
         let game_state_clone = Rc::clone(&self.game_state);
-
+        
         rematch_button.set_on_click(move || {
             let mut game_state = game_state_clone.borrow_mut();
             
@@ -264,21 +958,21 @@ fn attach_game_over_menu_callbacks(&self, ui_manager: &mut UiManager) {
                     .unwrap_or_default()
                     .as_secs()
             );
-
-            ui_manager_clone.borrow_mut().set_active_screen("game");
+            
+            Self::show_screen(&ui_actions, "game");
             true
         });
     }
-
+    
     // Main menu button
     if let Some(main_menu_button) = ui_manager.get_element_mut("game_over_main_menu_button") {
-        let ui_manager_clone = Rc::clone(&self.ui_manager);
+        let ui_actions = Rc::clone(&self.ui_actions);
         let game_state_clone = Rc::clone(&self.game_state);
-
+        
         main_menu_button.set_on_click(move || {
             let mut game_state = game_state_clone.borrow_mut();
             game_state.phase = GamePhase::MainMenu;
-            ui_manager_clone.borrow_mut().set_active_screen("main_menu");
+            Self::show_screen(&ui_actions, "main_menu");
             true
         });
     }
@@ -301,7 +995,7 @@ fn validate_game_setup(&self, ui_manager: &UiManager) -> bool {
     // Ensure all dropdowns have valid selections
     match (map_dropdown, player_dropdown, ai_dropdown) {
         (Some(map), Some(players), Some(ai)) => {
-            map.get_selected_index() < 3 &&  // Validate map selection
+            map.get_selected_index() < map_files::setup_map_choices().len() &&  // Validate map selection
             players.get_selected_index() < 3 &&  // Validate player count
             ai.get_selected_index() < 4  // Validate AI count
         },
@@ -319,26 +1013,21 @@ fn apply_settings(&self, ui_manager: &UiManager) {
     let sfx_volume = ui_manager.get_element("settings_sfx_volume")
         .and_then(|e| e.as_any().downcast_ref::<Slider>());
     
-    // Fullscreen checkbox
-    let fullscreen = ui_manager.get_element("settings_fullscreen")
-        .and_then(|e| e.as_any().downcast_ref::<Checkbox>());
-    
     // Apply audio settings
     if let Some(music_vol) = music_volume {
-        // TODO: Integrate with audio system
-        println!("Setting music volume to {}", music_vol.get_value());
+        Self::update_audio_settings(&self.audio_settings, &self.game_state, |audio| {
+            audio.music_volume = music_vol.get_value();
+        });
     }
     
     if let Some(sfx_vol) = sfx_volume {
-        // TODO: Integrate with audio system
-        println!("Setting SFX volume to {}", sfx_vol.get_value());
+        Self::update_audio_settings(&self.audio_settings, &self.game_state, |audio| {
+            audio.sfx_volume = sfx_vol.get_value();
+        });
     }
     
     // Apply display settings
-    if let Some(fs) = fullscreen {
-        // TODO: Integrate with window/display system
-        println!("Fullscreen: {}", fs.is_checked());
-    }
+    Self::read_display_settings(ui_manager, &mut self.game_state.borrow_mut());
 /// Defines common interactions for menu elements
 pub trait MenuInteraction {
     /// Handle navigation between menu screens
@@ -384,28 +1073,28 @@ impl DefaultMenuInteractions {
 
 impl MenuInteraction for DefaultMenuInteractions {
     fn navigate(&mut self, from: MenuScreen, to: MenuScreen) -> bool {
-        println!("Navigating from {:?} to {:?}", from, to);
+        debug!("Navigating from {:?} to {:?}", from, to);
         self.current_screen = to;
         true
     }
     
     fn select_dropdown(&mut self, dropdown: MenuScreen, index: usize) -> bool {
-        println!("Dropdown {:?} selected index {}", dropdown, index);
+        debug!("Dropdown {:?} selected index {}", dropdown, index);
         true
     }
     
     fn toggle_checkbox(&mut self, checkbox: MenuScreen, checked: bool) -> bool {
-        println!("Checkbox {:?} toggled to {}", checkbox, checked);
+        debug!("Checkbox {:?} toggled to {}", checkbox, checked);
         true
     }
     
     fn adjust_slider(&mut self, slider: MenuScreen, value: f32) -> bool {
-        println!("Slider {:?} adjusted to {}", slider, value);
+        debug!("Slider {:?} adjusted to {}", slider, value);
         true
     }
     
     fn click_button(&mut self, button: MenuScreen) -> bool {
-        println!("Button {:?} clicked", button);
+        debug!("Button {:?} clicked", button);
         match button {
             MenuScreen::MainMenu => {
                 // Specific logic for main menu button
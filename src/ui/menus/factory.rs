@@ -1,7 +1,46 @@
 use glam::Vec2;
 use std::collections::HashMap;
 
+use crate::ecs::resources::PlayerInfo;
+use crate::engine::input::{KeyAction, Keybindings};
+use crate::engine::renderer::{WindowMode, RESOLUTIONS};
+use crate::game::ai::{AiDifficulty, RESOURCE_BONUS_OPTIONS};
+use crate::game::campaign::{Campaign, CampaignProgress};
+use crate::game::data;
+use crate::game::map::{MapPreset, MapSymmetry};
+use crate::game::map_files::MapChoice;
+use crate::game::settings::{self, MAX_GAME_SPEED, MAX_PLAYER_NAME_LENGTH, MIN_GAME_SPEED};
+use crate::game::stats::GameStats;
+use crate::game::victory::VictoryConditionType;
+use crate::networking::chat::{ChatChannel, ChatMessage, MAX_CHAT_LENGTH};
+use crate::networking::latency::ConnectionQuality;
+use crate::networking::lockstep::{LobbySlot, MAX_LOBBY_PLAYERS, PLAYER_COLORS};
 use crate::ui::{UiElement, UiColorScheme, UiAlignment};
+use crate::ui::codex::{self, CodexPage};
+use crate::ui::i18n::{self, tr, tr_args};
+use crate::ui::layout::{Layout, Stack, StackDirection};
+use crate::ui::menus::{LineGraph, PingBars, ServerList, TextInput};
+
+/// String keys of the lobby color names, in `PLAYER_COLORS` order
+const PLAYER_COLOR_KEYS: [&str; PLAYER_COLORS.len()] = [
+    "color.blue",
+    "color.red",
+    "color.green",
+    "color.yellow",
+    "color.orange",
+    "color.purple",
+];
+
+/// Chat lines shown in the lobby
+const LOBBY_CHAT_LINES: usize = 8;
+
+/// Top-level elements of a menu screen by id, each with the layout that keeps it in place on resize
+pub type MenuElements = HashMap<String, (Box<dyn UiElement>, Layout)>;
+
+/// Whether text could be (part of) a host address: an IP or host name, optionally with a port
+fn is_address_text(text: &str) -> bool {
+    text.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '[' | ']'))
+}
 
 /// Creates pre-defined menu layouts
 pub struct MenuFactory {
@@ -10,7 +49,7 @@ pub struct MenuFactory {
 }
 
 impl MenuFactory {
-    /// Create a new menu factory with given screen dimensions
+    /// Create a new menu factory with given screen dimensions, in logical pixels
     pub fn new(color_scheme: UiColorScheme, screen_width: u32, screen_height: u32) -> Self {
         Self {
             color_scheme,
@@ -18,79 +57,85 @@ impl MenuFactory {
         }
     }
 
-    /// Create main menu screen elements
-    pub fn create_main_menu(&self) -> HashMap<String, Box<dyn UiElement>> {
-        let mut elements = HashMap::new();
-
-        // Title
-        elements.insert("title".to_string(), Box::new(Label::new(
-            Vec2::new(self.screen_size.x / 2.0 - 200.0, 100.0),
-            Vec2::new(400.0, 80.0),
-            "Rusty RTS",
-            &self.color_scheme,
-        ).with_font_size(48.0)));
+    /// A screen title centered across the top, `y` pixels down
+    fn title(&self, text: &str, y: f32) -> (Box<dyn UiElement>, Layout) {
+        let layout = Layout::aligned(UiAlignment::Top, Vec2::new(0.0, y), Vec2::new(400.0, 50.0));
+        let (position, size) = layout.place(self.screen_size);
+        (Box::new(Label::new(position, size, text, &self.color_scheme).with_font_size(32.0)), layout)
+    }
 
-        // Play button
-        elements.insert("play_button".to_string(), Box::new(UiButton::new(
-            Vec2::new(self.screen_size.x / 2.0 - 100.0, 250.0),
-            Vec2::new(200.0, 50.0),
-            "New Game",
-            &self.color_scheme,
-        )));
+    /// A screen's main panel centered across the top, `y` pixels down; its elements are placed inside it
+    fn panel(&self, y: f32, size: Vec2) -> (Panel, Layout) {
+        let layout = Layout::aligned(UiAlignment::Top, Vec2::new(0.0, y), size);
+        let (position, size) = layout.place(self.screen_size);
+        (Panel::new(position, size, &self.color_scheme), layout)
+    }
 
-        // Multiplayer button
-        elements.insert("multiplayer_button".to_string(), Box::new(UiButton::new(
-            Vec2::new(self.screen_size.x / 2.0 - 100.0, 320.0),
-            Vec2::new(200.0, 50.0),
-            "Multiplayer",
-            &self.color_scheme,
-        )));
+    /// Create main menu screen elements
+    pub fn create_main_menu(&self) -> MenuElements {
+        let mut elements = MenuElements::new();
 
-        // Settings button
-        elements.insert("settings_button".to_string(), Box::new(UiButton::new(
-            Vec2::new(self.screen_size.x / 2.0 - 100.0, 390.0),
-            Vec2::new(200.0, 50.0),
-            "Settings",
+        // Title
+        let title_layout = Layout::aligned(UiAlignment::Top, Vec2::new(0.0, 100.0), Vec2::new(400.0, 80.0));
+        let (position, size) = title_layout.place(self.screen_size);
+        elements.insert("title".to_string(), (Box::new(Label::new(
+            position,
+            size,
+            &tr("menu.title"),
             &self.color_scheme,
-        )));
+        ).with_font_size(48.0)), title_layout));
 
-        // Exit button
-        elements.insert("exit_button".to_string(), Box::new(UiButton::new(
-            Vec2::new(self.screen_size.x / 2.0 - 100.0, 460.0),
-            Vec2::new(200.0, 50.0),
-            "Exit Game",
-            &self.color_scheme,
-        )));
+        // Buttons in a column down the middle
+        let buttons = [
+            ("play_button", "menu.new_game"),
+            ("campaign_button", "menu.campaign"),
+            ("tutorial_button", "menu.tutorial"),
+            ("multiplayer_button", "menu.multiplayer"),
+            ("settings_button", "menu.settings"),
+            ("codex_button", "menu.codex"),
+            ("exit_button", "menu.exit"),
+        ];
+        let column = Stack::new(
+            Layout::aligned(UiAlignment::Top, Vec2::new(0.0, 220.0), Vec2::ZERO),
+            StackDirection::Column,
+            20.0,
+        );
+        let layouts = column.layouts(&[Vec2::new(200.0, 50.0); 7]);
+        for ((id, key), layout) in buttons.iter().zip(layouts) {
+            let (position, size) = layout.place(self.screen_size);
+            let button = UiButton::new(position, size, &tr(key), &self.color_scheme);
+            elements.insert(id.to_string(), (Box::new(button), layout));
+        }
 
         elements
     }
 
-    /// Create settings menu screen elements
-    pub fn create_settings_menu(&self) -> HashMap<String, Box<dyn UiElement>> {
-        let mut elements = HashMap::new();
+    /// Create settings menu screen elements; `themes` fills the theme dropdown
+    pub fn create_settings_menu(&self, themes: &[String]) -> MenuElements {
+        let mut elements = MenuElements::new();
 
         // Title
-        elements.insert("title".to_string(), Box::new(Label::new(
-            Vec2::new(self.screen_size.x / 2.0 - 200.0, 50.0),
-            Vec2::new(400.0, 50.0),
-            "Settings",
-            &self.color_scheme,
-        ).with_font_size(32.0)));
+        elements.insert("title".to_string(), self.title(&tr("menu.settings"), 50.0));
 
         // Settings panel
-        let mut panel = Panel::new(
-            Vec2::new(self.screen_size.x / 2.0 - 250.0, 120.0),
-            Vec2::new(500.0, 400.0),
+        let (mut panel, panel_layout) = self.panel(120.0, Vec2::new(500.0, 590.0));
+
+        // Master volume slider
+        let master_volume_slider = Slider::new(
+            Vec2::new(150.0, 20.0),
+            Vec2::new(250.0, 30.0),
             &self.color_scheme,
-        );
+        )
+        .with_label(&tr("settings.master_volume"))
+        .with_value(1.0);
 
         // Music volume slider
         let music_volume_slider = Slider::new(
-            Vec2::new(150.0, 50.0),
+            Vec2::new(150.0, 60.0),
             Vec2::new(250.0, 30.0),
             &self.color_scheme,
         )
-        .with_label("Music Volume")
+        .with_label(&tr("settings.music_volume"))
         .with_value(0.7);
 
         // Sound effects volume slider
@@ -99,63 +144,864 @@ impl MenuFactory {
             Vec2::new(250.0, 30.0),
             &self.color_scheme,
         )
-        .with_label("Sound Effects Volume")
+        .with_label(&tr("settings.sfx_volume"))
         .with_value(0.8);
 
-        // Fullscreen checkbox
-        let fullscreen_checkbox = Checkbox::new(
+        // Mute checkboxes next to each slider
+        let master_mute_checkbox = Checkbox::new(
+            Vec2::new(410.0, 20.0),
+            Vec2::new(80.0, 30.0),
+            &tr("settings.mute"),
+            &self.color_scheme,
+        );
+        let music_mute_checkbox = Checkbox::new(
+            Vec2::new(410.0, 60.0),
+            Vec2::new(80.0, 30.0),
+            &tr("settings.mute"),
+            &self.color_scheme,
+        );
+        let sfx_mute_checkbox = Checkbox::new(
+            Vec2::new(410.0, 100.0),
+            Vec2::new(80.0, 30.0),
+            &tr("settings.mute"),
+            &self.color_scheme,
+        );
+
+        // Display mode dropdown
+        let display_mode_dropdown = Dropdown::new(
             Vec2::new(150.0, 150.0),
-            Vec2::new(250.0, 30.0),
-            "Fullscreen",
+            Vec2::new(120.0, 30.0),
+            WindowMode::ALL.iter().map(|mode| mode.name().to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        // Resolution dropdown
+        let resolution_dropdown = Dropdown::new(
+            Vec2::new(280.0, 150.0),
+            Vec2::new(120.0, 30.0),
+            RESOLUTIONS.iter().map(|(width, height)| format!("{}x{}", width, height)).collect(),
             &self.color_scheme,
         );
 
         // V-Sync checkbox
         let vsync_checkbox = Checkbox::new(
             Vec2::new(150.0, 200.0),
-            Vec2::new(250.0, 30.0),
-            "V-Sync",
+            Vec2::new(120.0, 30.0),
+            &tr("settings.vsync"),
+            &self.color_scheme,
+        );
+
+        // Fog of war checkbox
+        let fog_of_war_checkbox = Checkbox::new(
+            Vec2::new(280.0, 200.0),
+            Vec2::new(120.0, 30.0),
+            &tr("settings.fog_of_war"),
             &self.color_scheme,
         );
 
+        // Floating damage and heal numbers over units
+        let combat_text_checkbox = Checkbox::new(
+            Vec2::new(410.0, 200.0),
+            Vec2::new(80.0, 30.0),
+            &tr("settings.combat_text"),
+            &self.color_scheme,
+        );
+
+        // Game speed slider, starting at normal speed
+        let game_speed_slider = Slider::new(
+            Vec2::new(150.0, 250.0),
+            Vec2::new(250.0, 30.0),
+            &self.color_scheme,
+        )
+        .with_label(&tr("settings.game_speed"))
+        .with_value((1.0 - MIN_GAME_SPEED) / (MAX_GAME_SPEED - MIN_GAME_SPEED));
+
         // Difficulty dropdown
         let difficulty_dropdown = Dropdown::new(
-            Vec2::new(150.0, 250.0),
+            Vec2::new(150.0, 300.0),
+            Vec2::new(120.0, 30.0),
+            AiDifficulty::ALL.iter().map(|difficulty| tr(difficulty.name_key())).collect(),
+            &self.color_scheme,
+        );
+
+        // Performance overlay checkbox, also toggled in game with F3
+        let show_fps_checkbox = Checkbox::new(
+            Vec2::new(280.0, 300.0),
+            Vec2::new(120.0, 30.0),
+            &tr("settings.show_fps"),
+            &self.color_scheme,
+        );
+
+        // Opens the key binding page
+        let controls_button = UiButton::new(
+            Vec2::new(150.0, 350.0),
+            Vec2::new(120.0, 40.0),
+            &tr("menu.controls"),
+            &self.color_scheme,
+        );
+
+        // UI theme, applied as soon as it is picked
+        let theme_dropdown = Dropdown::new(
+            Vec2::new(280.0, 355.0),
+            Vec2::new(120.0, 30.0),
+            themes.to_vec(),
+            &self.color_scheme,
+        );
+
+        // Name other players see in multiplayer lobbies
+        let player_name_input = TextInput::new(
+            Vec2::new(150.0, 410.0),
+            Vec2::new(250.0, 30.0),
+            &self.color_scheme,
+        )
+        .with_placeholder(&tr("settings.player_name"))
+        .with_max_length(MAX_PLAYER_NAME_LENGTH)
+        .with_validator(|name| name.is_empty() || settings::is_valid_player_name(name));
+
+        // Language of the menus and HUD, applied as soon as it is picked
+        let language_dropdown = Dropdown::new(
+            Vec2::new(150.0, 460.0),
             Vec2::new(250.0, 30.0),
-            vec![
-                "Easy".to_string(),
-                "Normal".to_string(),
-                "Hard".to_string(),
-            ],
+            i18n::languages().into_iter().map(|(_, name)| name).collect(),
             &self.color_scheme,
         );
 
-        // Save and Back buttons
+        // Apply (saves the settings file) and Back buttons
         let save_button = UiButton::new(
-            Vec2::new(150.0, 350.0),
+            Vec2::new(150.0, 520.0),
             Vec2::new(120.0, 40.0),
-            "Save",
+            &tr("settings.apply"),
             &self.color_scheme,
         );
 
         let back_button = UiButton::new(
-            Vec2::new(280.0, 350.0),
+            Vec2::new(280.0, 520.0),
             Vec2::new(120.0, 40.0),
-            "Back",
+            &tr("menu.back"),
             &self.color_scheme,
         );
 
         // Add elements to panel
+        panel.add_element("master_volume", Box::new(master_volume_slider));
         panel.add_element("music_volume", Box::new(music_volume_slider));
         panel.add_element("sfx_volume", Box::new(sfx_volume_slider));
-        panel.add_element("fullscreen", Box::new(fullscreen_checkbox));
+        panel.add_element("master_mute", Box::new(master_mute_checkbox));
+        panel.add_element("music_mute", Box::new(music_mute_checkbox));
+        panel.add_element("sfx_mute", Box::new(sfx_mute_checkbox));
+        panel.add_element("display_mode", Box::new(display_mode_dropdown));
+        panel.add_element("resolution", Box::new(resolution_dropdown));
         panel.add_element("vsync", Box::new(vsync_checkbox));
+        panel.add_element("fog_of_war", Box::new(fog_of_war_checkbox));
+        panel.add_element("combat_text", Box::new(combat_text_checkbox));
+        panel.add_element("game_speed", Box::new(game_speed_slider));
         panel.add_element("difficulty", Box::new(difficulty_dropdown));
+        panel.add_element("show_fps", Box::new(show_fps_checkbox));
+        panel.add_element("controls_button", Box::new(controls_button));
+        panel.add_element("theme", Box::new(theme_dropdown));
+        panel.add_element("player_name", Box::new(player_name_input));
+        panel.add_element("language", Box::new(language_dropdown));
         panel.add_element("save_button", Box::new(save_button));
         panel.add_element("back_button", Box::new(back_button));
 
         // Add panel to elements
-        elements.insert("settings_panel".to_string(), Box::new(panel));
+        elements.insert("settings_panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the key binding screen: one row per action, clicking its key button waits for a new key
+    pub fn create_controls_menu(&self, keybindings: &Keybindings) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.controls"), 50.0));
+
+        let (mut panel, panel_layout) = self.panel(120.0, Vec2::new(700.0, 460.0));
+
+        // Two columns of action name and bound key
+        let actions = KeyAction::all();
+        let rows_per_column = (actions.len() + 1) / 2;
+        for (index, action) in actions.into_iter().enumerate() {
+            let x = 20.0 + (index / rows_per_column) as f32 * 340.0;
+            let y = 20.0 + (index % rows_per_column) as f32 * 34.0;
+
+            let label = Label::new(
+                Vec2::new(x, y),
+                Vec2::new(180.0, 30.0),
+                &action.name(),
+                &self.color_scheme,
+            );
+            let key = keybindings.binding(action).map(|binding| binding.label()).unwrap_or_default();
+            let key_button = UiButton::new(
+                Vec2::new(x + 190.0, y),
+                Vec2::new(120.0, 30.0),
+                &key,
+                &self.color_scheme,
+            );
+
+            panel.add_element(&format!("label_{}", action.id()), Box::new(label));
+            panel.add_element(&format!("bind_{}", action.id()), Box::new(key_button));
+        }
+
+        // Reset and Back buttons
+        let reset_button = UiButton::new(
+            Vec2::new(220.0, 410.0),
+            Vec2::new(120.0, 40.0),
+            &tr("controls.defaults"),
+            &self.color_scheme,
+        );
+
+        let back_button = UiButton::new(
+            Vec2::new(360.0, 410.0),
+            Vec2::new(120.0, 40.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("reset_button", Box::new(reset_button));
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("controls_panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the multiplayer screen: host a lobby, or type a host's IP and join theirs
+    pub fn create_multiplayer_menu(&self) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.multiplayer"), 50.0));
+
+        let (mut panel, panel_layout) = self.panel(120.0, Vec2::new(400.0, 490.0));
+
+        let host_button = UiButton::new(
+            Vec2::new(100.0, 20.0),
+            Vec2::new(200.0, 50.0),
+            &tr("multiplayer.host"),
+            &self.color_scheme,
+        );
+
+        // Games on the local network, filled in as hosts announce themselves
+        let server_list = ServerList::new(
+            Vec2::new(20.0, 90.0),
+            Vec2::new(360.0, 160.0),
+            &self.color_scheme,
+        )
+        .with_empty_text(&tr("multiplayer.no_games"));
+
+        let refresh_button = UiButton::new(
+            Vec2::new(240.0, 260.0),
+            Vec2::new(140.0, 30.0),
+            &tr("multiplayer.refresh"),
+            &self.color_scheme,
+        );
+
+        // Host IP address, with an optional port, for games outside the local network
+        let address_input = TextInput::new(
+            Vec2::new(50.0, 310.0),
+            Vec2::new(300.0, 40.0),
+            &self.color_scheme,
+        )
+        .with_placeholder(&tr("multiplayer.address"))
+        .with_validator(is_address_text);
+
+        let join_button = UiButton::new(
+            Vec2::new(100.0, 360.0),
+            Vec2::new(200.0, 50.0),
+            &tr("multiplayer.join"),
+            &self.color_scheme,
+        );
+
+        let back_button = UiButton::new(
+            Vec2::new(140.0, 430.0),
+            Vec2::new(120.0, 40.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("host_button", Box::new(host_button));
+        panel.add_element("server_list", Box::new(server_list));
+        panel.add_element("refresh_button", Box::new(refresh_button));
+        panel.add_element("address", Box::new(address_input));
+        panel.add_element("join_button", Box::new(join_button));
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("multiplayer_panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the game setup screen for a game against the computer; `maps` fills the map dropdown
+    pub fn create_game_setup_menu(&self, maps: &[MapChoice]) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.new_game"), 50.0));
+
+        let (mut panel, panel_layout) = self.panel(120.0, Vec2::new(500.0, 580.0));
+
+        let rows = ["map", "terrain", "symmetry", "opponents", "difficulty", "ai_bonus", "teams", "victory"];
+        for (index, row) in rows.iter().enumerate() {
+            panel.add_element(&format!("{}_label", row), Box::new(Label::new(
+                Vec2::new(20.0, 20.0 + index as f32 * 50.0),
+                Vec2::new(150.0, 30.0),
+                &tr(&format!("setup.{}", row)),
+                &self.color_scheme,
+            )));
+        }
+
+        let map_dropdown = Dropdown::new(
+            Vec2::new(180.0, 20.0),
+            Vec2::new(300.0, 30.0),
+            maps.iter().map(|map| map.name().to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        // Terrain and symmetry only shape random maps
+        let terrain_dropdown = Dropdown::new(
+            Vec2::new(180.0, 70.0),
+            Vec2::new(300.0, 30.0),
+            MapPreset::ALL.iter().map(|preset| preset.name().to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        let symmetry_dropdown = Dropdown::new(
+            Vec2::new(180.0, 120.0),
+            Vec2::new(300.0, 30.0),
+            MapSymmetry::ALL.iter().map(|symmetry| symmetry.name().to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        // The selected index is the number of AI opponents
+        let ai_dropdown = Dropdown::new(
+            Vec2::new(180.0, 170.0),
+            Vec2::new(300.0, 30.0),
+            (0..MAX_LOBBY_PLAYERS).map(|count| count.to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        let difficulty_dropdown = Dropdown::new(
+            Vec2::new(180.0, 220.0),
+            Vec2::new(300.0, 30.0),
+            AiDifficulty::ALL.iter().map(|difficulty| tr(difficulty.name_key())).collect(),
+            &self.color_scheme,
+        );
+
+        // Extra resources only go to Hard opponents
+        let bonus_dropdown = Dropdown::new(
+            Vec2::new(180.0, 270.0),
+            Vec2::new(300.0, 30.0),
+            RESOURCE_BONUS_OPTIONS
+                .iter()
+                .map(|&bonus| if bonus > 0.0 { format!("+{:.0}%", bonus * 100.0) } else { tr("setup.no_bonus") })
+                .collect(),
+            &self.color_scheme,
+        );
+
+        let teams_dropdown = Dropdown::new(
+            Vec2::new(180.0, 320.0),
+            Vec2::new(300.0, 30.0),
+            vec![tr("setup.free_for_all"), tr("setup.two_teams")],
+            &self.color_scheme,
+        );
+
+        let victory_dropdown = Dropdown::new(
+            Vec2::new(180.0, 370.0),
+            Vec2::new(300.0, 30.0),
+            VictoryConditionType::SETUP_OPTIONS.iter().map(|condition| condition.name().to_string()).collect(),
+            &self.color_scheme,
+        );
+
+        let shared_vision_checkbox = Checkbox::new(
+            Vec2::new(20.0, 420.0),
+            Vec2::new(200.0, 30.0),
+            &tr("setup.shared_vision"),
+            &self.color_scheme,
+        );
+
+        let day_night_checkbox = Checkbox::new(
+            Vec2::new(240.0, 420.0),
+            Vec2::new(240.0, 30.0),
+            &tr("setup.day_night"),
+            &self.color_scheme,
+        );
+
+        let start_button = UiButton::new(
+            Vec2::new(20.0, 510.0),
+            Vec2::new(160.0, 50.0),
+            &tr("setup.start"),
+            &self.color_scheme,
+        );
+
+        let back_button = UiButton::new(
+            Vec2::new(360.0, 510.0),
+            Vec2::new(120.0, 50.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("map_dropdown", Box::new(map_dropdown));
+        panel.add_element("terrain_dropdown", Box::new(terrain_dropdown));
+        panel.add_element("symmetry_dropdown", Box::new(symmetry_dropdown));
+        panel.add_element("ai_dropdown", Box::new(ai_dropdown));
+        panel.add_element("difficulty_dropdown", Box::new(difficulty_dropdown));
+        panel.add_element("bonus_dropdown", Box::new(bonus_dropdown));
+        panel.add_element("teams_dropdown", Box::new(teams_dropdown));
+        panel.add_element("victory_dropdown", Box::new(victory_dropdown));
+        panel.add_element("shared_vision", Box::new(shared_vision_checkbox));
+        panel.add_element("day_night", Box::new(day_night_checkbox));
+        panel.add_element("start_button", Box::new(start_button));
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("game_setup_panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the lobby screen: a row per player slot, the chat, and ready/start controls.
+    /// Only the local player's row has color and team pickers, and only rows with a measured
+    /// connection have ping bars.
+    pub fn create_lobby_menu(
+        &self,
+        slots: &[LobbySlot],
+        chat: &[ChatMessage],
+        connections: &HashMap<u8, ConnectionQuality>,
+        local_player_id: u8,
+        is_host: bool,
+    ) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.lobby"), 50.0));
+
+        let (mut panel, panel_layout) = self.panel(120.0, Vec2::new(700.0, 500.0));
+
+        // Player slots: name, color, team, ready, connection
+        for index in 0..MAX_LOBBY_PLAYERS {
+            let y = 20.0 + index as f32 * 40.0;
+            let slot = slots.get(index);
+
+            let name = slot.map(|slot| slot.name.clone()).unwrap_or_else(|| tr("lobby.open"));
+            panel.add_element(&format!("slot_{}_name", index), Box::new(Label::new(
+                Vec2::new(20.0, y),
+                Vec2::new(220.0, 30.0),
+                &name,
+                &self.color_scheme,
+            )));
+
+            let slot = match slot {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            if slot.player_id == local_player_id {
+                let color_dropdown = Dropdown::new(
+                    Vec2::new(250.0, y),
+                    Vec2::new(120.0, 30.0),
+                    PLAYER_COLOR_KEYS.iter().map(|key| tr(key)).collect(),
+                    &self.color_scheme,
+                );
+                let team_dropdown = Dropdown::new(
+                    Vec2::new(380.0, y),
+                    Vec2::new(120.0, 30.0),
+                    (1..=MAX_LOBBY_PLAYERS).map(|team| tr_args("lobby.team", &[("number", &team.to_string())])).collect(),
+                    &self.color_scheme,
+                );
+                panel.add_element("color", Box::new(color_dropdown));
+                panel.add_element("team", Box::new(team_dropdown));
+            } else {
+                let color_name = PLAYER_COLOR_KEYS.get(slot.color as usize).map_or_else(|| "?".to_string(), |key| tr(key));
+                panel.add_element(&format!("slot_{}_color", index), Box::new(Label::new(
+                    Vec2::new(250.0, y),
+                    Vec2::new(120.0, 30.0),
+                    &color_name,
+                    &self.color_scheme,
+                )));
+                panel.add_element(&format!("slot_{}_team", index), Box::new(Label::new(
+                    Vec2::new(380.0, y),
+                    Vec2::new(120.0, 30.0),
+                    &tr_args("lobby.team", &[("number", &(slot.team + 1).to_string())]),
+                    &self.color_scheme,
+                )));
+            }
+
+            panel.add_element(&format!("slot_{}_ready", index), Box::new(Label::new(
+                Vec2::new(520.0, y),
+                Vec2::new(110.0, 30.0),
+                &tr(if slot.ready { "lobby.ready" } else { "lobby.not_ready" }),
+                &self.color_scheme,
+            )));
+
+            if let Some(&quality) = connections.get(&slot.player_id) {
+                let ping_bars = PingBars::new(
+                    Vec2::new(640.0, y + 5.0),
+                    Vec2::new(40.0, 20.0),
+                    &self.color_scheme,
+                )
+                .with_quality(Some(quality));
+                panel.add_element(&format!("slot_{}_ping", index), Box::new(ping_bars));
+            }
+        }
+
+        // Chat log, newest line last
+        let chat_text = chat
+            .iter()
+            .skip(chat.len().saturating_sub(LOBBY_CHAT_LINES))
+            .map(|message| {
+                if message.channel == ChatChannel::System {
+                    return message.text.clone();
+                }
+                let sender = slots
+                    .iter()
+                    .find(|slot| slot.player_id == message.player_id)
+                    .map(|slot| slot.name.as_str())
+                    .unwrap_or("?");
+                format!("{}: {}", sender, message.text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chat_log = Label::new(
+            Vec2::new(20.0, 190.0),
+            Vec2::new(660.0, 180.0),
+            &chat_text,
+            &self.color_scheme,
+        )
+        .with_alignment(UiAlignment::TopLeft);
+
+        let chat_input = TextInput::new(
+            Vec2::new(20.0, 380.0),
+            Vec2::new(530.0, 30.0),
+            &self.color_scheme,
+        )
+        .with_placeholder(&tr("lobby.say"))
+        .with_max_length(MAX_CHAT_LENGTH);
+
+        let send_button = UiButton::new(
+            Vec2::new(560.0, 380.0),
+            Vec2::new(120.0, 30.0),
+            &tr("lobby.send"),
+            &self.color_scheme,
+        );
+
+        // The host is always ready and starts the game instead
+        if is_host {
+            let start_button = UiButton::new(
+                Vec2::new(20.0, 440.0),
+                Vec2::new(160.0, 40.0),
+                &tr("lobby.start_game"),
+                &self.color_scheme,
+            );
+            let shared_vision_checkbox = Checkbox::new(
+                Vec2::new(200.0, 440.0),
+                Vec2::new(180.0, 40.0),
+                &tr("setup.shared_vision"),
+                &self.color_scheme,
+            );
+            let day_night_checkbox = Checkbox::new(
+                Vec2::new(390.0, 440.0),
+                Vec2::new(160.0, 40.0),
+                &tr("setup.day_night"),
+                &self.color_scheme,
+            );
+            panel.add_element("start_button", Box::new(start_button));
+            panel.add_element("shared_vision", Box::new(shared_vision_checkbox));
+            panel.add_element("day_night", Box::new(day_night_checkbox));
+        } else {
+            let ready_checkbox = Checkbox::new(
+                Vec2::new(20.0, 440.0),
+                Vec2::new(160.0, 40.0),
+                &tr("lobby.ready"),
+                &self.color_scheme,
+            );
+            panel.add_element("ready", Box::new(ready_checkbox));
+        }
+
+        let leave_button = UiButton::new(
+            Vec2::new(560.0, 440.0),
+            Vec2::new(120.0, 40.0),
+            &tr("lobby.leave"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("chat_log", Box::new(chat_log));
+        panel.add_element("chat_input", Box::new(chat_input));
+        panel.add_element("send_button", Box::new(send_button));
+        panel.add_element("leave_button", Box::new(leave_button));
+
+        elements.insert("lobby_panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the post-game summary: the result, a row of statistics per player and graphs over the game
+    pub fn create_game_over_menu(
+        &self,
+        result: &str,
+        stats: &GameStats,
+        player_info: &PlayerInfo,
+    ) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(result, 30.0));
+
+        let (mut panel, panel_layout) = self.panel(100.0, Vec2::new(900.0, 600.0));
+
+        // Statistics table
+        let columns = [
+            ("game_over.player", 20.0),
+            ("game_over.score", 180.0),
+            ("game_over.units_made", 260.0),
+            ("game_over.units_lost", 355.0),
+            ("game_over.killed", 410.0),
+            ("game_over.promoted", 470.0),
+            ("game_over.built", 555.0),
+            ("game_over.buildings_lost", 610.0),
+            ("game_over.razed", 665.0),
+            ("game_over.gathered", 730.0),
+            ("game_over.spent", 815.0),
+        ];
+        for (index, (heading, x)) in columns.iter().enumerate() {
+            panel.add_element(&format!("heading_{}", index), Box::new(Label::new(
+                Vec2::new(*x, 20.0),
+                Vec2::new(90.0, 30.0),
+                &tr(heading),
+                &self.color_scheme,
+            )));
+        }
+
+        let player_name = |player_id: u8| {
+            player_info.player_names.get(&player_id).cloned().unwrap_or_else(|| tr_args("game_over.player_number", &[("number", &(player_id + 1).to_string())]))
+        };
+        for (row, (player_id, player_stats)) in stats.players().enumerate() {
+            let y = 60.0 + row as f32 * 35.0;
+            let values = [
+                player_name(player_id),
+                player_stats.score().to_string(),
+                player_stats.units_produced.to_string(),
+                player_stats.units_lost.to_string(),
+                player_stats.units_killed.to_string(),
+                player_stats.units_promoted.to_string(),
+                player_stats.buildings_constructed.to_string(),
+                player_stats.buildings_lost.to_string(),
+                player_stats.buildings_razed.to_string(),
+                format!("{:.0}", player_stats.resources_gathered),
+                format!("{:.0}", player_stats.resources_spent),
+            ];
+            for (column, (value, (_, x))) in values.iter().zip(columns.iter()).enumerate() {
+                panel.add_element(&format!("row_{}_{}", row, column), Box::new(Label::new(
+                    Vec2::new(*x, y),
+                    Vec2::new(if column == 0 { 150.0 } else { 75.0 }, 30.0),
+                    value,
+                    &self.color_scheme,
+                )));
+            }
+        }
+
+        // Banked resources and army value over the game, one line per player in their color
+        let mut resource_graph = LineGraph::new(
+            Vec2::new(20.0, 250.0),
+            Vec2::new(420.0, 260.0),
+            &tr("game_over.resources"),
+            &self.color_scheme,
+        );
+        let mut army_graph = LineGraph::new(
+            Vec2::new(460.0, 250.0),
+            Vec2::new(420.0, 260.0),
+            &tr("game_over.army_value"),
+            &self.color_scheme,
+        );
+        for (player_id, player_stats) in stats.players() {
+            let name = player_name(player_id);
+            let color = player_info.player_colors.get(&player_id).copied().unwrap_or([255, 255, 255, 255]);
+            resource_graph = resource_graph.with_series(&name, color, player_stats.resource_curve.clone());
+            army_graph = army_graph.with_series(&name, color, player_stats.army_value_curve.clone());
+        }
+
+        let rematch_button = UiButton::new(
+            Vec2::new(20.0, 540.0),
+            Vec2::new(160.0, 40.0),
+            &tr("game_over.rematch"),
+            &self.color_scheme,
+        );
+
+        let main_menu_button = UiButton::new(
+            Vec2::new(720.0, 540.0),
+            Vec2::new(160.0, 40.0),
+            &tr("game_over.main_menu"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("resource_graph", Box::new(resource_graph));
+        panel.add_element("army_graph", Box::new(army_graph));
+        panel.add_element("rematch_button", Box::new(rematch_button));
+        panel.add_element("main_menu_button", Box::new(main_menu_button));
+
+        elements.insert("panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create one page of the codex: a tab per page, the page's table and a back button.
+    /// The table is built from the loaded definitions each time the menus are built
+    pub fn create_codex_menu(&self, page: CodexPage) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.codex"), 30.0));
+
+        let (mut panel, panel_layout) = self.panel(100.0, Vec2::new(900.0, 560.0));
+
+        for (index, tab) in CodexPage::ALL.iter().enumerate() {
+            let mut tab_button = UiButton::new(
+                Vec2::new(20.0 + index as f32 * 150.0, 20.0),
+                Vec2::new(140.0, 35.0),
+                &tr(tab.title_key()),
+                &self.color_scheme,
+            );
+            tab_button.set_enabled(*tab != page);
+            panel.add_element(&format!("{}_tab", tab.screen()), Box::new(tab_button));
+        }
+
+        // The first column holds names, the rest share the width left
+        let table = codex::page_table(page);
+        let name_width = 140.0;
+        let column_width = (860.0 - name_width) / (table.headings.len().max(2) - 1) as f32;
+        let column_x = |column: usize| if column == 0 { 20.0 } else { 20.0 + name_width + (column - 1) as f32 * column_width };
+        let cell_width = |column: usize| if column == 0 { name_width } else { column_width } - 5.0;
+
+        for (column, heading) in table.headings.iter().enumerate() {
+            panel.add_element(&format!("heading_{}", column), Box::new(Label::new(
+                Vec2::new(column_x(column), 70.0),
+                Vec2::new(cell_width(column), 30.0),
+                heading,
+                &self.color_scheme,
+            )));
+        }
+        for (row, cells) in table.rows.iter().enumerate() {
+            let y = 105.0 + row as f32 * 34.0;
+            for (column, cell) in cells.iter().enumerate() {
+                panel.add_element(&format!("row_{}_{}", row, column), Box::new(Label::new(
+                    Vec2::new(column_x(column), y),
+                    Vec2::new(cell_width(column), 30.0),
+                    cell,
+                    &self.color_scheme,
+                )));
+            }
+        }
+
+        let back_button = UiButton::new(
+            Vec2::new(720.0, 500.0),
+            Vec2::new(160.0, 40.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create the campaign screen: a button per mission in order, locked ones disabled, and a back button
+    pub fn create_campaign_menu(&self, campaign: &Campaign, progress: &CampaignProgress) -> MenuElements {
+        let mut elements = MenuElements::new();
+
+        // Title
+        elements.insert("title".to_string(), self.title(&tr("menu.campaign"), 30.0));
+
+        let panel_height = 100.0 + campaign.missions.len() as f32 * 50.0;
+        let (mut panel, panel_layout) = self.panel(100.0, Vec2::new(600.0, panel_height));
+
+        for (index, mission) in campaign.missions.iter().enumerate() {
+            let y = 20.0 + index as f32 * 50.0;
+            let mut mission_button = UiButton::new(
+                Vec2::new(20.0, y),
+                Vec2::new(400.0, 40.0),
+                &format!("{}. {}", index + 1, mission.title),
+                &self.color_scheme,
+            );
+            let available = progress.is_available(campaign, index);
+            mission_button.set_enabled(available);
+            panel.add_element(&format!("mission_{}_button", index), Box::new(mission_button));
+
+            let status = if progress.is_completed(mission) {
+                tr("campaign.completed")
+            } else if available {
+                String::new()
+            } else {
+                tr("campaign.locked")
+            };
+            panel.add_element(&format!("mission_{}_status", index), Box::new(Label::new(
+                Vec2::new(440.0, y),
+                Vec2::new(140.0, 40.0),
+                &status,
+                &self.color_scheme,
+            )));
+        }
+
+        let back_button = UiButton::new(
+            Vec2::new(420.0, panel_height - 60.0),
+            Vec2::new(160.0, 40.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("panel".to_string(), (Box::new(panel), panel_layout));
+
+        elements
+    }
+
+    /// Create a mission's briefing: its story, what winning it unlocks, and buttons to start or go back
+    pub fn create_briefing_menu(&self, campaign: &Campaign, index: usize) -> MenuElements {
+        let mut elements = MenuElements::new();
+        let mission = match campaign.missions.get(index) {
+            Some(mission) => mission,
+            None => return elements,
+        };
+
+        // Title
+        elements.insert("title".to_string(), self.title(&mission.title, 30.0));
+
+        let (mut panel, panel_layout) = self.panel(100.0, Vec2::new(600.0, 400.0));
+
+        panel.add_element("briefing", Box::new(Label::new(
+            Vec2::new(20.0, 20.0),
+            Vec2::new(560.0, 240.0),
+            &mission.briefing,
+            &self.color_scheme,
+        )));
+
+        if !mission.unlocks.is_empty() {
+            let techs: Vec<String> = mission.unlocks.iter().map(|&tech| data::game_data().tech(tech).name.clone()).collect();
+            panel.add_element("unlocks", Box::new(Label::new(
+                Vec2::new(20.0, 280.0),
+                Vec2::new(560.0, 30.0),
+                &tr_args("campaign.unlocks", &[("techs", &techs.join(", "))]),
+                &self.color_scheme,
+            )));
+        }
+
+        let start_button = UiButton::new(
+            Vec2::new(20.0, 340.0),
+            Vec2::new(160.0, 40.0),
+            &tr("campaign.start"),
+            &self.color_scheme,
+        );
+
+        let back_button = UiButton::new(
+            Vec2::new(420.0, 340.0),
+            Vec2::new(160.0, 40.0),
+            &tr("menu.back"),
+            &self.color_scheme,
+        );
+
+        panel.add_element("start_button", Box::new(start_button));
+        panel.add_element("back_button", Box::new(back_button));
+
+        elements.insert("panel".to_string(), (Box::new(panel), panel_layout));
 
         elements
     }
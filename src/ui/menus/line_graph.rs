@@ -0,0 +1,167 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::any::Any;
+
+use crate::ui::{UiElement, UiElementType, UiPipeline, UiColorScheme, UiQuad};
+use crate::ui::font::{self, SMALL_TEXT_HEIGHT, TEXT_HEIGHT};
+
+/// Thickness of the axes and the lines
+const LINE_THICKNESS: f32 = 2.0;
+
+/// Gap between the graph's edges and its title, legend and max value
+const TEXT_PADDING: f32 = 6.0;
+
+/// One line on a graph
+pub struct GraphSeries {
+    pub label: String,
+    pub color: Vec4,
+    pub values: Vec<f32>,
+}
+
+/// Line chart of values sampled at regular intervals, such as a player's army value over a game
+pub struct LineGraph {
+    position: Vec2,
+    size: Vec2,
+    title: String,
+    visible: bool,
+    series: Vec<GraphSeries>,
+    background_color: Vec4,
+    axis_color: Vec4,
+    text_color: Vec4,
+}
+
+impl LineGraph {
+    pub fn new(position: Vec2, size: Vec2, title: &str, color_scheme: &UiColorScheme) -> Self {
+        Self {
+            position,
+            size,
+            title: title.to_string(),
+            visible: true,
+            series: Vec::new(),
+            background_color: color_scheme.foreground,
+            axis_color: color_scheme.border,
+            text_color: color_scheme.text,
+        }
+    }
+    
+    pub fn with_series(mut self, label: &str, color: [u8; 4], values: Vec<f32>) -> Self {
+        self.series.push(GraphSeries {
+            label: label.to_string(),
+            color: Vec4::new(color[0] as f32, color[1] as f32, color[2] as f32, color[3] as f32) / 255.0,
+            values,
+        });
+        self
+    }
+    
+    pub fn series(&self) -> &[GraphSeries] {
+        &self.series
+    }
+    
+    /// Largest value on any line, which the top of the graph stands for
+    pub fn max_value(&self) -> f32 {
+        self.series
+            .iter()
+            .flat_map(|series| series.values.iter().copied())
+            .fold(0.0, f32::max)
+    }
+    
+    /// Screen position of a sample, with the first sample on the left edge and the last on the right
+    pub fn point_position(&self, index: usize, value: f32) -> Vec2 {
+        let samples = self.series.iter().map(|series| series.values.len()).max().unwrap_or(0);
+        let x = if samples > 1 { index as f32 / (samples - 1) as f32 } else { 0.0 };
+        let max_value = self.max_value();
+        let y = if max_value > 0.0 { value / max_value } else { 0.0 };
+        
+        Vec2::new(
+            self.position.x + x * self.size.x,
+            self.position.y + (1.0 - y) * self.size.y,
+        )
+    }
+}
+
+impl UiElement for LineGraph {
+    fn get_type(&self) -> UiElementType {
+        UiElementType::Graph
+    }
+    
+    fn get_position(&self) -> Vec2 {
+        self.position
+    }
+    
+    fn get_size(&self) -> Vec2 {
+        self.size
+    }
+    
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+    
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.visible &&
+        point.x >= self.position.x &&
+        point.x <= self.position.x + self.size.x &&
+        point.y >= self.position.y &&
+        point.y <= self.position.y + self.size.y
+    }
+    
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let mut quads = vec![UiQuad::rect(self.position, self.size, self.background_color)];
+        
+        // Axes along the left and bottom edges
+        let bottom_left = self.position + Vec2::new(0.0, self.size.y);
+        quads.push(UiQuad::line(self.position, bottom_left, LINE_THICKNESS, self.axis_color));
+        quads.push(UiQuad::line(bottom_left, self.position + self.size, LINE_THICKNESS, self.axis_color));
+        
+        for series in &self.series {
+            for (index, pair) in series.values.windows(2).enumerate() {
+                let from = self.point_position(index, pair[0]);
+                let to = self.point_position(index + 1, pair[1]);
+                quads.push(UiQuad::line(from, to, LINE_THICKNESS, series.color));
+            }
+        }
+        
+        // Title and the value the top stands for in the top-left corner, the legend down the right
+        let text_position = self.position + Vec2::splat(TEXT_PADDING);
+        font::push_text(&mut quads, &self.title, text_position, TEXT_HEIGHT, self.text_color);
+        let max_value = format!("{:.0}", self.max_value());
+        let max_position = text_position + Vec2::new(0.0, TEXT_HEIGHT + TEXT_PADDING);
+        font::push_text(&mut quads, &max_value, max_position, SMALL_TEXT_HEIGHT, self.text_color);
+        for (row, series) in self.series.iter().enumerate() {
+            let x = self.position.x + self.size.x - TEXT_PADDING - font::text_width(&series.label, SMALL_TEXT_HEIGHT);
+            let y = text_position.y + row as f32 * (SMALL_TEXT_HEIGHT + TEXT_PADDING);
+            font::push_text(&mut quads, &series.label, Vec2::new(x, y), SMALL_TEXT_HEIGHT, series.color);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+    
+    fn handle_click(&mut self, _position: Vec2) -> bool {
+        false
+    }
+    
+    fn set_bounds(&mut self, position: Vec2, size: Vec2) {
+        self.position = position;
+        self.size = size;
+    }
+    
+    fn apply_color_scheme(&mut self, color_scheme: &UiColorScheme) {
+        self.background_color = color_scheme.foreground;
+        self.axis_color = color_scheme.border;
+        self.text_color = color_scheme.text;
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
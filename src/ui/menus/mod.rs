@@ -1,21 +1,48 @@
 mod factory;
 mod callbacks;
+mod line_graph;
+mod ping_bars;
+mod server_list;
+mod text_input;
 
-pub use factory::MenuFactory;
-pub use callbacks::MenuCallbacks;
+pub use factory::{MenuElements, MenuFactory};
+pub use line_graph::{GraphSeries, LineGraph};
+pub use ping_bars::PingBars;
+pub use server_list::ServerList;
+pub use text_input::TextInput;
+pub use callbacks::{KeybindingRequest, LobbyRequest, MenuCallbacks, SaveRequest};
 
+use glam::Vec2;
 use std::collections::HashMap;
 
+use crate::ecs::resources::PlayerInfo;
+use crate::engine::input::Keybindings;
+use crate::game::campaign::{Campaign, CampaignProgress};
+use crate::game::map_files;
+use crate::game::stats::GameStats;
+use crate::networking::lockstep::LockstepNetwork;
+
 use crate::ui::{
     UiElement, 
     UiColorScheme
 };
+use crate::ui::codex::CodexPage;
+use crate::ui::layout::Layout;
+use crate::ui::theme::Themes;
+
+/// Name of the menu screen briefing a campaign mission
+pub fn briefing_screen(index: usize) -> String {
+    format!("briefing_{}", index)
+}
 
 /// Creates pre-defined menu layouts and manages menu interactions
 pub struct MenuManager {
     color_scheme: UiColorScheme,
     current_screen: String,
     elements: HashMap<String, Box<dyn UiElement>>,
+    layouts: HashMap<String, Layout>, // Where each element goes, for laying the menus out again on resize
+    screen_width: u32,  // Logical pixels
+    screen_height: u32, // Logical pixels
 }
 
 impl MenuManager {
@@ -27,6 +54,9 @@ impl MenuManager {
             color_scheme,
             current_screen: "main_menu".to_string(),
             elements: HashMap::new(),
+            layouts: HashMap::new(),
+            screen_width,
+            screen_height,
         };
 
         // Populate initial menu screens
@@ -38,20 +68,114 @@ impl MenuManager {
     /// Populate menu screens using the menu factory
     fn populate_menus(&mut self, factory: &MenuFactory) {
         // Create and add main menu elements
-        let main_menu_elements = factory.create_main_menu();
-        for (id, element) in main_menu_elements {
-            self.elements.insert(format!("main_menu_{}", id), element);
-        }
+        self.add_screen("main_menu", factory.create_main_menu());
 
         // Create and add other menu screens similarly
-        let settings_elements = factory.create_settings_menu();
-        for (id, element) in settings_elements {
-            self.elements.insert(format!("settings_{}", id), element);
+        self.add_screen("settings", factory.create_settings_menu(&Themes::load_or_default().names()));
+
+        // Key binding page, showing the bindings saved from earlier sessions
+        self.add_screen("controls", factory.create_controls_menu(&Keybindings::load_or_default()));
+
+        self.add_screen("multiplayer", factory.create_multiplayer_menu());
+
+        // Custom maps are picked up from the maps directory each time the menus are built
+        self.add_screen("game_setup", factory.create_game_setup_menu(&map_files::setup_map_choices()));
+
+        // A screen per codex page, built from the loaded definitions
+        for page in CodexPage::ALL {
+            self.add_screen(page.screen(), factory.create_codex_menu(page));
         }
 
+        self.refresh_campaign(factory);
+
         // Add other menu screens...
     }
 
+    /// Rebuild the campaign screen and mission briefings from the campaign file and saved progress,
+    /// so missions won since the menus were built show as completed and the next ones open up
+    pub fn refresh_campaign(&mut self, factory: &MenuFactory) {
+        let campaign = Campaign::load_or_default();
+        let progress = CampaignProgress::load_or_default();
+        self.add_screen("campaign", factory.create_campaign_menu(&campaign, &progress));
+        for index in 0..campaign.missions.len() {
+            self.add_screen(&briefing_screen(index), factory.create_briefing_menu(&campaign, index));
+        }
+    }
+
+    /// Replace a screen's elements, each id prefixed with the screen name; they start out hidden
+    /// unless the screen is showing
+    fn add_screen(&mut self, screen_name: &str, elements: MenuElements) {
+        let prefix = format!("{}_", screen_name);
+        self.elements.retain(|id, _| !id.starts_with(&prefix));
+        self.layouts.retain(|id, _| !id.starts_with(&prefix));
+
+        let visible = self.current_screen == screen_name;
+        for (id, (mut element, layout)) in elements {
+            element.set_visible(visible);
+            self.elements.insert(format!("{}{}", prefix, id), element);
+            self.layouts.insert(format!("{}{}", prefix, id), layout);
+        }
+    }
+
+    /// Rebuild the lobby screen from the latest slots, chat and connection quality the network session has
+    pub fn refresh_lobby(&mut self, network: &LockstepNetwork) {
+        let factory = MenuFactory::new(self.color_scheme.clone(), self.screen_width, self.screen_height);
+        let chat: Vec<_> = network.chat_log().cloned().collect();
+        let connections: HashMap<_, _> = network
+            .lobby_slots()
+            .iter()
+            .filter_map(|slot| Some((slot.player_id, network.connection_quality(slot.player_id)?)))
+            .collect();
+        let lobby_elements = factory.create_lobby_menu(
+            network.lobby_slots(),
+            &chat,
+            &connections,
+            network.local_player_id(),
+            network.is_host(),
+        );
+
+        self.add_screen("lobby", lobby_elements);
+    }
+
+    /// Build the post-game summary for a finished game and switch to it
+    pub fn show_game_over(&mut self, result: &str, stats: &GameStats, player_info: &PlayerInfo) {
+        let factory = MenuFactory::new(self.color_scheme.clone(), self.screen_width, self.screen_height);
+        let game_over_elements = factory.create_game_over_menu(result, stats, player_info);
+
+        self.add_screen("game_over", game_over_elements);
+        self.set_screen("game_over");
+    }
+
+    /// Lay every screen out again for a new screen size in logical pixels
+    pub fn resize(&mut self, screen_width: u32, screen_height: u32) {
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+
+        let screen_size = Vec2::new(screen_width as f32, screen_height as f32);
+        for (id, layout) in &self.layouts {
+            if let Some(element) = self.elements.get_mut(id) {
+                let (position, size) = layout.place(screen_size);
+                element.set_bounds(position, size);
+            }
+        }
+    }
+
+    /// Rebuild the menu screens in the current language. The lobby and post-game screens follow on their
+    /// next refresh, since they are built from the session's state
+    pub fn retranslate(&mut self) {
+        let factory = MenuFactory::new(self.color_scheme.clone(), self.screen_width, self.screen_height);
+        self.populate_menus(&factory);
+        self.resize(self.screen_width, self.screen_height);
+    }
+
+    /// Recolor every screen in a new theme's colors; screens built later use them too
+    pub fn set_color_scheme(&mut self, color_scheme: UiColorScheme) {
+        for element in self.elements.values_mut() {
+            element.apply_color_scheme(&color_scheme);
+        }
+        self.color_scheme = color_scheme;
+    }
+
     /// Switch to a specific menu screen
     pub fn set_screen(&mut self, screen_name: &str) {
         // Hide all elements
@@ -0,0 +1,165 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::any::Any;
+
+use crate::networking::latency::ConnectionQuality;
+use crate::ui::{UiElement, UiElementType, UiPipeline, UiColorScheme, UiQuad};
+use crate::ui::font::{self, SMALL_TEXT_HEIGHT};
+use crate::ui::i18n::tr_args;
+
+/// Bars in a full-strength indicator
+const BAR_COUNT: u8 = 4;
+
+/// Gap between neighbouring bars, as a share of the indicator's width
+const BAR_GAP: f32 = 0.1;
+
+/// Gap between the bars and the tooltip above them
+const TOOLTIP_GAP: f32 = 4.0;
+
+const TOOLTIP_BACKGROUND: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.75);
+
+/// Connection quality as rising signal bars, with the round trip time and packet loss on hover
+pub struct PingBars {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    quality: Option<ConnectionQuality>, // None until measured; drawn as empty bars
+    hovered: bool,
+    lit_color: Vec4,
+    unlit_color: Vec4,
+    text_color: Vec4,
+}
+
+impl PingBars {
+    pub fn new(position: Vec2, size: Vec2, color_scheme: &UiColorScheme) -> Self {
+        Self {
+            position,
+            size,
+            visible: true,
+            quality: None,
+            hovered: false,
+            lit_color: color_scheme.accent,
+            unlit_color: color_scheme.border,
+            text_color: color_scheme.text,
+        }
+    }
+    
+    pub fn with_quality(mut self, quality: Option<ConnectionQuality>) -> Self {
+        self.quality = quality;
+        self
+    }
+    
+    pub fn set_quality(&mut self, quality: Option<ConnectionQuality>) {
+        self.quality = quality;
+    }
+    
+    /// Bars drawn lit; 0 while the connection hasn't been measured
+    pub fn lit_bars(&self) -> u8 {
+        self.quality.map_or(0, |quality| quality.bars())
+    }
+    
+    /// Screen position and size of a bar, shortest on the left
+    pub fn bar_rect(&self, index: u8) -> (Vec2, Vec2) {
+        let slot = self.size.x / BAR_COUNT as f32;
+        let height = self.size.y * (index + 1) as f32 / BAR_COUNT as f32;
+        (
+            Vec2::new(self.position.x + index as f32 * slot, self.position.y + self.size.y - height),
+            Vec2::new(slot * (1.0 - BAR_GAP), height),
+        )
+    }
+    
+    /// Hover text, e.g. "85 ms, 5% loss"
+    pub fn tooltip(&self) -> Option<String> {
+        let quality = self.quality?;
+        Some(tr_args("net.quality", &[
+            ("ping", &quality.rtt_ms.to_string()),
+            ("loss", &((quality.loss * 100.0).round() as u32).to_string()),
+        ]))
+    }
+}
+
+impl UiElement for PingBars {
+    fn get_type(&self) -> UiElementType {
+        UiElementType::Image
+    }
+    
+    fn get_position(&self) -> Vec2 {
+        self.position
+    }
+    
+    fn get_size(&self) -> Vec2 {
+        self.size
+    }
+    
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if !visible {
+            self.hovered = false;
+        }
+    }
+    
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.visible &&
+        point.x >= self.position.x &&
+        point.x <= self.position.x + self.size.x &&
+        point.y >= self.position.y &&
+        point.y <= self.position.y + self.size.y
+    }
+    
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let mut quads: Vec<UiQuad> = (0..BAR_COUNT)
+            .map(|index| {
+                let (position, size) = self.bar_rect(index);
+                let color = if index < self.lit_bars() { self.lit_color } else { self.unlit_color };
+                UiQuad::rect(position, size, color)
+            })
+            .collect();
+        
+        // Right-aligned with the bars so it stays on screen in the corner they sit in
+        if let Some(tooltip) = self.tooltip().filter(|_| self.hovered) {
+            let size = Vec2::new(font::text_width(&tooltip, SMALL_TEXT_HEIGHT), SMALL_TEXT_HEIGHT) + Vec2::splat(TOOLTIP_GAP * 2.0);
+            let position = Vec2::new(self.position.x + self.size.x - size.x, self.position.y - TOOLTIP_GAP - size.y);
+            quads.push(UiQuad::rect(position, size, TOOLTIP_BACKGROUND));
+            font::push_text(&mut quads, &tooltip, position + Vec2::splat(TOOLTIP_GAP), SMALL_TEXT_HEIGHT, self.text_color);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+    
+    fn handle_click(&mut self, _position: Vec2) -> bool {
+        false
+    }
+    
+    fn handle_hover(&mut self, position: Vec2) -> bool {
+        let hovered = self.contains_point(position);
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+    
+    fn set_bounds(&mut self, position: Vec2, size: Vec2) {
+        self.position = position;
+        self.size = size;
+    }
+    
+    fn apply_color_scheme(&mut self, color_scheme: &UiColorScheme) {
+        self.lit_color = color_scheme.accent;
+        self.unlit_color = color_scheme.border;
+        self.text_color = color_scheme.text;
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,198 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::any::Any;
+
+use crate::networking::discovery::DiscoveredGame;
+use crate::ui::{UiAlignment, UiElement, UiElementType, UiPipeline, UiColorScheme, UiQuad};
+use crate::ui::font::{self, TEXT_HEIGHT};
+
+/// Height of one game's row, in logical pixels
+const ROW_HEIGHT: f32 = 28.0;
+
+/// Gap between a row's left edge and its text
+const TEXT_PADDING: f32 = 8.0;
+
+/// Thickness of the border
+const BORDER_WIDTH: f32 = 1.0;
+
+/// Games found on the local network, one per row; clicking a joinable game joins it
+pub struct ServerList {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    games: Vec<DiscoveredGame>,
+    hovered: Option<usize>, // Row under the cursor
+    empty_text: String,     // Shown while no games have been found
+    background_color: Vec4,
+    border_color: Vec4,
+    hover_color: Vec4,
+    text_color: Vec4,
+    disabled_text_color: Vec4, // For games that can't be joined
+    on_join: Option<Box<dyn Fn(&DiscoveredGame) -> bool + 'static>>,
+}
+
+impl ServerList {
+    pub fn new(position: Vec2, size: Vec2, color_scheme: &UiColorScheme) -> Self {
+        Self {
+            position,
+            size,
+            visible: true,
+            games: Vec::new(),
+            hovered: None,
+            empty_text: String::new(),
+            background_color: color_scheme.foreground,
+            border_color: color_scheme.border,
+            hover_color: color_scheme.button_hover,
+            text_color: color_scheme.text,
+            disabled_text_color: color_scheme.text * Vec4::new(1.0, 1.0, 1.0, 0.5),
+            on_join: None,
+        }
+    }
+    
+    pub fn with_empty_text(mut self, text: &str) -> Self {
+        self.empty_text = text.to_string();
+        self
+    }
+    
+    /// Called with a joinable game when its row is clicked; returns whether the game was taken
+    pub fn set_on_join<F: Fn(&DiscoveredGame) -> bool + 'static>(&mut self, callback: F) {
+        self.on_join = Some(Box::new(callback));
+    }
+    
+    /// Replace the listed games, e.g. when the LAN browser hears from a new host
+    pub fn set_games(&mut self, games: Vec<DiscoveredGame>) {
+        self.games = games;
+        self.hovered = None;
+    }
+    
+    pub fn games(&self) -> &[DiscoveredGame] {
+        &self.games
+    }
+    
+    /// Rows that fit in the list; games past these are left off
+    pub fn visible_rows(&self) -> usize {
+        (self.size.y / ROW_HEIGHT).floor().max(0.0) as usize
+    }
+    
+    /// Text of a game's row: its name, how full it is and where it is
+    pub fn row_text(&self, index: usize) -> Option<String> {
+        let game = self.games.get(index)?;
+        let announcement = &game.announcement;
+        Some(format!(
+            "{}  {}/{}  {}",
+            announcement.name, announcement.players, announcement.max_players, game.address
+        ))
+    }
+    
+    /// Index of the game whose row is at a point, if any
+    fn row_at(&self, point: Vec2) -> Option<usize> {
+        if !self.contains_point(point) {
+            return None;
+        }
+        
+        let index = ((point.y - self.position.y) / ROW_HEIGHT) as usize;
+        (index < self.games.len().min(self.visible_rows())).then_some(index)
+    }
+}
+
+impl UiElement for ServerList {
+    fn get_type(&self) -> UiElementType {
+        UiElementType::List
+    }
+    
+    fn get_position(&self) -> Vec2 {
+        self.position
+    }
+    
+    fn get_size(&self) -> Vec2 {
+        self.size
+    }
+    
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if !visible {
+            self.hovered = None;
+        }
+    }
+    
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.visible &&
+        point.x >= self.position.x &&
+        point.x <= self.position.x + self.size.x &&
+        point.y >= self.position.y &&
+        point.y <= self.position.y + self.size.y
+    }
+    
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let mut quads = vec![UiQuad::rect(self.position, self.size, self.background_color)];
+        quads.extend(UiQuad::outline(self.position, self.size, BORDER_WIDTH, self.border_color));
+        
+        if self.games.is_empty() {
+            let position = font::align_text(&self.empty_text, TEXT_HEIGHT, self.position, self.size, UiAlignment::Center, 0.0);
+            font::push_text(&mut quads, &self.empty_text, position, TEXT_HEIGHT, self.disabled_text_color);
+        }
+        
+        let max_chars = font::chars_fitting(self.size.x - TEXT_PADDING * 2.0, TEXT_HEIGHT);
+        for (index, game) in self.games.iter().enumerate().take(self.visible_rows()) {
+            let row_position = self.position + Vec2::new(0.0, index as f32 * ROW_HEIGHT);
+            let row_size = Vec2::new(self.size.x, ROW_HEIGHT);
+            if self.hovered == Some(index) && game.is_joinable() {
+                quads.push(UiQuad::rect(row_position, row_size, self.hover_color));
+            }
+            
+            let color = if game.is_joinable() { self.text_color } else { self.disabled_text_color };
+            let text: String = self.row_text(index).unwrap_or_default().chars().take(max_chars).collect();
+            let position = font::align_text(&text, TEXT_HEIGHT, row_position, row_size, UiAlignment::Left, TEXT_PADDING);
+            font::push_text(&mut quads, &text, position, TEXT_HEIGHT, color);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+    
+    fn handle_click(&mut self, position: Vec2) -> bool {
+        let game = match self.row_at(position).and_then(|index| self.games.get(index)) {
+            Some(game) if game.is_joinable() => game,
+            _ => return false,
+        };
+        
+        match &self.on_join {
+            Some(on_join) => on_join(game),
+            None => false,
+        }
+    }
+    
+    fn handle_hover(&mut self, position: Vec2) -> bool {
+        let hovered = self.row_at(position);
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+    
+    fn set_bounds(&mut self, position: Vec2, size: Vec2) {
+        self.position = position;
+        self.size = size;
+    }
+    
+    fn apply_color_scheme(&mut self, color_scheme: &UiColorScheme) {
+        self.background_color = color_scheme.foreground;
+        self.border_color = color_scheme.border;
+        self.hover_color = color_scheme.button_hover;
+        self.text_color = color_scheme.text;
+        self.disabled_text_color = color_scheme.text * Vec4::new(1.0, 1.0, 1.0, 0.5);
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,370 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::any::Any;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::ui::{UiElement, UiElementType, UiPipeline, UiColorScheme, UiQuad};
+use crate::ui::font::{self, TEXT_HEIGHT};
+
+/// Gap between the field's left edge and its text
+const TEXT_PADDING: f32 = 6.0;
+
+/// Thickness of the border and the cursor
+const BORDER_WIDTH: f32 = 1.0;
+const CURSOR_WIDTH: f32 = 2.0;
+
+/// Single-line text field that takes keyboard focus when clicked
+pub struct TextInput {
+    position: Vec2,
+    size: Vec2,
+    text: String,
+    placeholder: String,
+    visible: bool,
+    focused: bool,
+    cursor: usize,                   // Character index the next typed character goes in at
+    selection_anchor: Option<usize>, // Other end of the selection, the cursor being one end
+    max_length: Option<usize>,       // In characters
+    background_color: Vec4,
+    border_color: Vec4,
+    focused_border_color: Vec4,
+    text_color: Vec4,
+    placeholder_color: Vec4,
+    validator: Option<Box<dyn Fn(&str) -> bool + 'static>>,
+    on_change: Option<Box<dyn Fn(&str) + 'static>>,
+    on_submit: Option<Box<dyn Fn(&str) -> bool + 'static>>,
+}
+
+impl TextInput {
+    pub fn new(position: Vec2, size: Vec2, color_scheme: &UiColorScheme) -> Self {
+        Self {
+            position,
+            size,
+            text: String::new(),
+            placeholder: String::new(),
+            visible: true,
+            focused: false,
+            cursor: 0,
+            selection_anchor: None,
+            max_length: None,
+            background_color: color_scheme.foreground,
+            border_color: color_scheme.border,
+            focused_border_color: color_scheme.accent,
+            text_color: color_scheme.text,
+            placeholder_color: color_scheme.text * Vec4::new(1.0, 1.0, 1.0, 0.5),
+            validator: None,
+            on_change: None,
+            on_submit: None,
+        }
+    }
+    
+    /// Grey hint shown while the field is empty
+    pub fn with_placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+    
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.set_text(text);
+        self
+    }
+    
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+    
+    /// Only allow edits that leave text the validator accepts
+    pub fn with_validator<F: Fn(&str) -> bool + 'static>(mut self, validator: F) -> Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+    
+    /// Called with the new text after every accepted edit
+    pub fn set_on_change<F: Fn(&str) + 'static>(&mut self, callback: F) {
+        self.on_change = Some(Box::new(callback));
+    }
+    
+    /// Called with the text when Enter is pressed; returns whether the text was taken
+    pub fn set_on_submit<F: Fn(&str) -> bool + 'static>(&mut self, callback: F) {
+        self.on_submit = Some(Box::new(callback));
+    }
+    
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+    
+    /// Replace the text, bypassing the validator, and put the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = match self.max_length {
+            Some(max_length) => text.chars().take(max_length).collect(),
+            None => text.to_string(),
+        };
+        self.cursor = self.char_count();
+        self.selection_anchor = None;
+    }
+    
+    pub fn clear(&mut self) {
+        self.set_text("");
+    }
+    
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+    
+    /// Character index of the cursor
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    
+    /// Selected character range, if any text is selected
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.selection_anchor {
+            Some(anchor) if anchor != self.cursor => Some((anchor.min(self.cursor), anchor.max(self.cursor))),
+            _ => None,
+        }
+    }
+    
+    pub fn selected_text(&self) -> &str {
+        match self.selection() {
+            Some((start, end)) => &self.text[self.byte_index(start)..self.byte_index(end)],
+            None => "",
+        }
+    }
+    
+    pub fn select_all(&mut self) {
+        self.selection_anchor = Some(0);
+        self.cursor = self.char_count();
+    }
+    
+    /// Insert text at the cursor, replacing the selection; refused if it breaks the length limit or validator
+    pub fn insert_text(&mut self, inserted: &str) -> bool {
+        let (start, end) = self.selection().unwrap_or((self.cursor, self.cursor));
+        
+        let mut text = self.text.clone();
+        text.replace_range(self.byte_index(start)..self.byte_index(end), inserted);
+        self.apply_edit(text, start + inserted.chars().count())
+    }
+    
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+    
+    /// Byte offset of a character index
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map(|(index, _)| index)
+            .unwrap_or(self.text.len())
+    }
+    
+    /// Take an edited text if it passes the length limit and validator
+    fn apply_edit(&mut self, text: String, cursor: usize) -> bool {
+        if self.max_length.map_or(false, |max_length| text.chars().count() > max_length) {
+            return false;
+        }
+        if let Some(validator) = &self.validator {
+            if !validator(&text) {
+                return false;
+            }
+        }
+        
+        self.text = text;
+        self.cursor = cursor;
+        self.selection_anchor = None;
+        
+        if let Some(on_change) = &self.on_change {
+            on_change(&self.text);
+        }
+        true
+    }
+    
+    /// Delete the selection, or the character before (`forward == false`) or after the cursor
+    fn delete(&mut self, forward: bool) -> bool {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None if forward && self.cursor < self.char_count() => (self.cursor, self.cursor + 1),
+            None if !forward && self.cursor > 0 => (self.cursor - 1, self.cursor),
+            None => return false,
+        };
+        
+        let mut text = self.text.clone();
+        text.replace_range(self.byte_index(start)..self.byte_index(end), "");
+        self.apply_edit(text, start)
+    }
+    
+    /// Move the cursor, growing the selection while Shift is held
+    fn move_cursor(&mut self, cursor: usize, extend_selection: bool) {
+        if extend_selection {
+            self.selection_anchor.get_or_insert(self.cursor);
+        } else {
+            self.selection_anchor = None;
+        }
+        self.cursor = cursor.min(self.char_count());
+    }
+}
+
+impl UiElement for TextInput {
+    fn get_type(&self) -> UiElementType {
+        UiElementType::TextInput
+    }
+    
+    fn get_position(&self) -> Vec2 {
+        self.position
+    }
+    
+    fn get_size(&self) -> Vec2 {
+        self.size
+    }
+    
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if !visible {
+            self.focused = false;
+        }
+    }
+    
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.visible &&
+        point.x >= self.position.x &&
+        point.x <= self.position.x + self.size.x &&
+        point.y >= self.position.y &&
+        point.y <= self.position.y + self.size.y
+    }
+    
+    fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let border_color = if self.focused { self.focused_border_color } else { self.border_color };
+        let mut quads = vec![UiQuad::rect(self.position, self.size, self.background_color)];
+        quads.extend(UiQuad::outline(self.position, self.size, BORDER_WIDTH, border_color));
+        
+        // Text past the right edge scrolls left far enough to keep the cursor in view
+        let visible_chars = font::chars_fitting(self.size.x - TEXT_PADDING * 2.0, TEXT_HEIGHT);
+        let first = self.cursor.saturating_sub(visible_chars);
+        let char_width = font::text_width(" ", TEXT_HEIGHT);
+        let text_position = self.position + Vec2::new(TEXT_PADDING, (self.size.y - TEXT_HEIGHT) * 0.5);
+        let char_x = |index: usize| text_position.x + (index - first) as f32 * char_width;
+        
+        if let Some((start, end)) = self.selection() {
+            let (start, end) = (start.max(first), end.min(first + visible_chars));
+            if start < end {
+                let highlight = Vec2::new(char_x(start), text_position.y);
+                let size = Vec2::new((end - start) as f32 * char_width, TEXT_HEIGHT);
+                quads.push(UiQuad::rect(highlight, size, self.focused_border_color * Vec4::new(1.0, 1.0, 1.0, 0.4)));
+            }
+        }
+        
+        if self.text.is_empty() {
+            let placeholder: String = self.placeholder.chars().take(visible_chars).collect();
+            font::push_text(&mut quads, &placeholder, text_position, TEXT_HEIGHT, self.placeholder_color);
+        } else {
+            let shown: String = self.text.chars().skip(first).take(visible_chars).collect();
+            font::push_text(&mut quads, &shown, text_position, TEXT_HEIGHT, self.text_color);
+        }
+        
+        if self.focused {
+            let cursor = Vec2::new(char_x(self.cursor), text_position.y);
+            quads.push(UiQuad::rect(cursor, Vec2::new(CURSOR_WIDTH, TEXT_HEIGHT), self.text_color));
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+    
+    fn handle_click(&mut self, _position: Vec2) -> bool {
+        if !self.visible {
+            return false;
+        }
+        
+        self.focused = true;
+        true
+    }
+    
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+    
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        if !focused {
+            self.selection_anchor = None;
+        }
+    }
+    
+    fn handle_char(&mut self, c: char) -> bool {
+        if !self.focused {
+            return false;
+        }
+        
+        // Backspace, Enter and the like arrive as key presses too and are handled there
+        if !c.is_control() {
+            self.insert_text(c.encode_utf8(&mut [0; 4]));
+        }
+        true
+    }
+    
+    fn handle_key(&mut self, key: VirtualKeyCode, modifiers: ModifiersState) -> bool {
+        if !self.focused {
+            return false;
+        }
+        
+        let shift = modifiers.shift();
+        match key {
+            VirtualKeyCode::Back => { self.delete(false); }
+            VirtualKeyCode::Delete => { self.delete(true); }
+            VirtualKeyCode::Left => {
+                let cursor = match self.selection() {
+                    Some((start, _)) if !shift => start,
+                    _ => self.cursor.saturating_sub(1),
+                };
+                self.move_cursor(cursor, shift);
+            }
+            VirtualKeyCode::Right => {
+                let cursor = match self.selection() {
+                    Some((_, end)) if !shift => end,
+                    _ => self.cursor + 1,
+                };
+                self.move_cursor(cursor, shift);
+            }
+            VirtualKeyCode::Home => self.move_cursor(0, shift),
+            VirtualKeyCode::End => self.move_cursor(self.char_count(), shift),
+            VirtualKeyCode::A if modifiers.ctrl() => self.select_all(),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                if let Some(on_submit) = &self.on_submit {
+                    on_submit(&self.text);
+                }
+            }
+            VirtualKeyCode::Escape => self.set_focused(false),
+            _ => {}
+        }
+        
+        // Every key belongs to the field while it has focus, so typing doesn't also move the camera
+        true
+    }
+    
+    fn set_bounds(&mut self, position: Vec2, size: Vec2) {
+        self.position = position;
+        self.size = size;
+    }
+    
+    fn apply_color_scheme(&mut self, color_scheme: &UiColorScheme) {
+        self.background_color = color_scheme.foreground;
+        self.border_color = color_scheme.border;
+        self.focused_border_color = color_scheme.accent;
+        self.text_color = color_scheme.text;
+        self.placeholder_color = color_scheme.text * Vec4::new(1.0, 1.0, 1.0, 0.5);
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
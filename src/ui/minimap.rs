@@ -1,49 +1,102 @@
-use glam::{Vec2, Vec4};
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use tracing::warn;
 use wgpu::RenderPass;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
-use crate::game::GameState;
-use crate::ecs::resources::GameMap;
-use crate::ecs::components::{Owner, UnitType, BuildingType};
-use crate::ui::{UiPipeline, UiElement, UiElementType};
+use crate::ecs::resources::{AttackAlerts, GameMap, GameSettings, PlayerInfo, NEUTRAL_PLAYER};
+use crate::ecs::components::{Building, Collider, MinimapMarker, MinimapShape, Owner, Resource, ResourceType, Transform};
+use crate::engine::assets::{self, TextureAsset};
+use crate::game::map::is_position_visible;
+use crate::game::pathfinding::PATH_GRID_SIZE;
+use crate::ui::{UiAlignment, UiPipeline};
+use crate::ui::pipeline::UiTexture;
+use crate::ui::layout::Layout;
+
+/// Color of the camera viewport outline
+const VIEWPORT_COLOR: [u8; 4] = [255, 255, 255, 255];
+/// Brightness of explored tiles that are not currently in sight
+const EXPLORED_BRIGHTNESS: f32 = 0.5;
+/// Color of tiles under building footprints
+const FOOTPRINT_COLOR: [u8; 4] = [90, 90, 90, 255];
+/// Color of neutral creeps and unclaimed structures
+pub const NEUTRAL_COLOR: [u8; 4] = [170, 170, 170, 255];
+/// Color of the rings flashed where the local player is attacked
+const PING_COLOR: [u8; 4] = [255, 40, 40, 255];
+/// How long an attack ping flashes
+const PING_DURATION: Duration = Duration::from_secs(3);
+/// Times a ping flashes on each second
+const PING_FLASHES_PER_SECOND: f32 = 3.0;
+/// Radius in tiles a ping's ring starts at, shrinking to PING_END_RADIUS on the spot attacked
+const PING_START_RADIUS: f32 = 10.0;
+const PING_END_RADIUS: f32 = 2.0;
+/// Attacks this close to a flashing ping don't raise another one, in world units
+const PING_MERGE_DISTANCE: f32 = 96.0;
+/// Radius in tiles of the dots marking resource nodes
+const RESOURCE_DOT_RADIUS: f32 = 1.0;
+/// How often markers, fog and pings are drawn into the minimap image again. Moving the camera
+/// redraws the viewport outline straight away
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Action requested by clicking the minimap, carried out by the engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinimapRequest {
+    /// Center the camera on a world position
+    JumpCamera(Vec2),
+    /// Right click at a world position: order the selection there (move, or attack-move with Alt),
+    /// or cancel a pending order
+    Order(Vec2),
+    /// Give a pending attack-move, patrol or ability its target at a world position
+    Target(Vec2),
+}
 
 /// Minimap for the RTS game
 pub struct Minimap {
     position: Vec2,
     size: Vec2,
     visible: bool,
-    texture_data: Vec<u8>,
+    terrain_data: Vec<u8>, // One RGBA pixel per map tile, from generate_minimap
+    texture_data: Vec<u8>, // Terrain with fog, markers and viewport drawn on top
     texture_width: u32,
     texture_height: u32,
     camera_position: Vec2,
     camera_size: Vec2,
     map_width: u32,
     map_height: u32,
-    unit_markers: Vec<UnitMarker>,
-    building_markers: Vec<BuildingMarker>,
+    visible_tiles: Option<HashSet<u32>>, // None when fog of war is off
+    explored_tiles: HashSet<u32>,
+    occupied_tiles: HashSet<u32>, // Tiles under building footprints
+    markers: Vec<MarkerIcon>,
+    resource_dots: Vec<(Vec2, [u8; 4])>, // Explored resource nodes, world position and color
+    pings: Vec<Ping>,
+    terrain_only: bool, // Hide units, buildings and resources, leaving terrain, fog and pings
+    last_refresh: Option<Instant>,
+    texture: Option<TextureAsset>, // texture_data on the GPU
+    ui_texture: Option<UiTexture>, // texture bound for drawing as a quad
+    texture_dirty: bool,           // texture_data changed since it was last uploaded
     player_colors: HashMap<u8, [u8; 4]>,
+    requests: Vec<MinimapRequest>,
+    dragging: bool, // Left button held after pressing it on the minimap
 }
 
-/// Marker for units on the minimap
-struct UnitMarker {
-    position: Vec2,
-    color: [u8; 4],
-    unit_type: UnitType,
-    entity_id: u32,
+/// Ring flashed on the minimap where the local player came under attack
+struct Ping {
+    position: Vec2, // World position
+    started: Instant,
 }
 
-/// Marker for buildings on the minimap
-struct BuildingMarker {
+/// Marker drawn on the minimap for a unit or building
+struct MarkerIcon {
     position: Vec2,
-    size: Vec2,
+    radius: f32, // World units
     color: [u8; 4],
-    building_type: BuildingType,
-    entity_id: u32,
+    shape: MinimapShape,
 }
 
 impl Minimap {
     pub fn new() -> Self {
-        // Default player colors
+        // Default player colors, used for entities without a minimap marker
         let mut player_colors = HashMap::new();
         player_colors.insert(0, [0, 0, 255, 255]);     // Blue
         player_colors.insert(1, [255, 0, 0, 255]);     // Red
@@ -53,11 +106,13 @@ impl Minimap {
         player_colors.insert(5, [0, 255, 255, 255]);   // Cyan
         player_colors.insert(6, [255, 128, 0, 255]);   // Orange
         player_colors.insert(7, [255, 0, 255, 255]);   // Magenta
+        player_colors.insert(NEUTRAL_PLAYER, NEUTRAL_COLOR);
         
         Self {
             position: Vec2::new(0.0, 0.0),
             size: Vec2::new(150.0, 150.0),
             visible: true,
+            terrain_data: Vec::new(),
             texture_data: Vec::new(),
             texture_width: 256,
             texture_height: 256,
@@ -65,15 +120,173 @@ impl Minimap {
             camera_size: Vec2::new(0.0, 0.0),
             map_width: 256,
             map_height: 256,
-            unit_markers: Vec::new(),
-            building_markers: Vec::new(),
+            visible_tiles: None,
+            explored_tiles: HashSet::new(),
+            occupied_tiles: HashSet::new(),
+            markers: Vec::new(),
+            resource_dots: Vec::new(),
+            pings: Vec::new(),
+            terrain_only: false,
+            last_refresh: None,
+            texture: None,
+            ui_texture: None,
+            texture_dirty: false,
             player_colors,
+            requests: Vec::new(),
+            dragging: false,
+        }
+    }
+    
+    /// Ping new attacks on the local player, then redraw the minimap image if the camera moved or
+    /// markers, fog and pings are due a refresh from the world
+    pub fn update(&mut self, world: &mut World, view_min: Vec2, view_max: Vec2) {
+        let now = Instant::now();
+        let local_player_id = world.get_resource::<PlayerInfo>()
+            .map(|info| info.local_player_id)
+            .unwrap_or(0);
+        let fog_enabled = world.get_resource::<GameSettings>()
+            .map(|settings| settings.fog_of_war_enabled)
+            .unwrap_or(true);
+        
+        // Alerts are taken every frame so they can't pile up between refreshes
+        let alerts = world.get_resource_mut::<AttackAlerts>()
+            .map(|mut attack_alerts| std::mem::take(&mut attack_alerts.alerts))
+            .unwrap_or_default();
+        for alert in alerts.into_iter().filter(|alert| alert.player_id == local_player_id) {
+            self.ping(alert.position, now);
+        }
+        
+        let camera_position = (view_min + view_max) * 0.5;
+        let camera_size = view_max - view_min;
+        let camera_moved = camera_position != self.camera_position || camera_size != self.camera_size;
+        let refresh_due = self.last_refresh.map_or(true, |last| now.duration_since(last) >= REFRESH_INTERVAL);
+        if !camera_moved && !refresh_due {
+            return;
+        }
+        self.set_camera(camera_position, camera_size.x, camera_size.y);
+        if !refresh_due {
+            self.redraw();
+            return;
+        }
+        self.last_refresh = Some(now);
+        self.pings.retain(|ping| now.duration_since(ping.started) < PING_DURATION);
+        
+        let mut query = world.query::<(&Transform, &Owner, Option<&MinimapMarker>, Option<&Collider>, Option<&Building>)>();
+        let mut resource_query = world.query::<(&Transform, &Resource)>();
+        let map = match world.get_resource::<GameMap>() {
+            Some(map) => map,
+            None => return,
+        };
+        
+        // Terrain only changes when a new map is loaded
+        if self.terrain_data.is_empty() || map.width != self.map_width || map.height != self.map_height {
+            self.set_map_data(map);
+        }
+        
+        // Fog of war for the local player
+        self.visible_tiles = if fog_enabled {
+            map.fog_of_war.get(&local_player_id).cloned()
+        } else {
+            None
+        };
+        if let Some(visible_tiles) = &self.visible_tiles {
+            self.explored_tiles.extend(visible_tiles.iter().copied());
+        }
+        
+        // Footprints only change when buildings are placed or destroyed
+        if self.occupied_tiles != map.occupied_tiles {
+            self.occupied_tiles.clone_from(&map.occupied_tiles);
+        }
+        
+        // Own entities are always shown; everyone else's only inside the local player's vision
+        self.markers.clear();
+        for (transform, owner, marker, collider, building) in query.iter(world) {
+            if owner.0 != local_player_id && fog_enabled &&
+               !is_position_visible(map, local_player_id, transform.position, PATH_GRID_SIZE) {
+                continue;
+            }
+            
+            let (color, shape) = match (marker, building) {
+                (Some(marker), _) => (marker.color, marker.shape),
+                (None, Some(_)) => (
+                    self.player_colors.get(&owner.0).copied().unwrap_or([255, 255, 255, 255]),
+                    MinimapShape::Square,
+                ),
+                (None, None) => continue,
+            };
+            
+            self.markers.push(MarkerIcon {
+                position: transform.position,
+                radius: collider.map(|c| c.radius).unwrap_or(0.0),
+                color,
+                shape,
+            });
         }
+        
+        // Resource nodes never move, so they stay marked once their tile has been explored
+        self.resource_dots.clear();
+        for (transform, resource) in resource_query.iter(world) {
+            let explored = self.visible_tiles.is_none() ||
+                self.tile_index(transform.position).map_or(false, |tile_index| self.explored_tiles.contains(&tile_index));
+            if explored {
+                self.resource_dots.push((transform.position, resource_color(resource.resource_type)));
+            }
+        }
+        
+        self.redraw();
+    }
+    
+    /// Flash a ring where the local player is attacked, unless one is already flashing close by
+    fn ping(&mut self, position: Vec2, now: Instant) {
+        let flashing_nearby = self.pings.iter().any(|ping| {
+            now.duration_since(ping.started) < PING_DURATION && ping.position.distance(position) < PING_MERGE_DISTANCE
+        });
+        if !flashing_nearby {
+            self.pings.push(Ping { position, started: now });
+            self.last_refresh = None;
+        }
+    }
+    
+    /// Show only terrain, fog and attack pings, or everything again
+    pub fn toggle_terrain_only(&mut self) {
+        self.terrain_only = !self.terrain_only;
+        self.last_refresh = None;
+    }
+    
+    pub fn is_terrain_only(&self) -> bool {
+        self.terrain_only
     }
     
-    pub fn update(&mut self, game_state: &GameState) {
-        // In a real implementation, this would update unit and building markers
-        // from the ECS world
+    /// Send the minimap image to the GPU if it was redrawn since the last upload
+    pub fn upload_texture(&mut self, ui_pipeline: &UiPipeline) {
+        if !self.texture_dirty || self.texture_data.is_empty() {
+            return;
+        }
+        self.texture_dirty = false;
+        
+        // A new map may be a different size, which needs a texture to match
+        match &self.texture {
+            Some(texture) if texture.width == self.texture_width && texture.height == self.texture_height => {
+                assets::update_texture(&ui_pipeline.queue, texture, &self.texture_data);
+            }
+            _ => {
+                let texture = assets::create_texture(
+                    &ui_pipeline.device,
+                    &ui_pipeline.queue,
+                    &self.texture_data,
+                    self.texture_width,
+                    self.texture_height,
+                    Some("minimap"),
+                );
+                match texture {
+                    Ok(texture) => {
+                        self.ui_texture = Some(ui_pipeline.create_texture(&texture));
+                        self.texture = Some(texture);
+                    }
+                    Err(e) => warn!(target: "render", "Failed to create the minimap texture: {}", e),
+                }
+            }
+        }
     }
     
     pub fn set_map_data(&mut self, map: &GameMap) {
@@ -81,9 +294,32 @@ impl Minimap {
         self.map_height = map.height;
         
         // Generate minimap texture from map data
-        self.texture_data = crate::game::map::generate_minimap(map);
+        self.terrain_data = crate::game::map::generate_minimap(map);
         self.texture_width = map.width;
         self.texture_height = map.height;
+        self.explored_tiles.clear();
+        self.redraw();
+    }
+    
+    /// Forget the current map and explored area, e.g. when a save is loaded
+    pub fn reset(&mut self) {
+        self.terrain_data.clear();
+        self.texture_data.clear();
+        self.explored_tiles.clear();
+        self.occupied_tiles.clear();
+        self.markers.clear();
+        self.resource_dots.clear();
+        self.pings.clear();
+        self.last_refresh = None;
+    }
+    
+    /// Mark every tile of the map as explored
+    pub fn reveal(&mut self, map: &GameMap) {
+        if self.terrain_data.is_empty() || map.width != self.map_width || map.height != self.map_height {
+            self.set_map_data(map);
+        }
+        self.explored_tiles.extend(0..map.width * map.height);
+        self.redraw();
     }
     
     pub fn set_camera(&mut self, position: Vec2, view_width: f32, view_height: f32) {
@@ -91,95 +327,260 @@ impl Minimap {
         self.camera_size = Vec2::new(view_width, view_height);
     }
     
-    pub fn update_unit_positions(&mut self, units: &[(u32, UnitType, Vec2, u8)]) {
-        // Clear existing markers
-        self.unit_markers.clear();
-        
-        // Add new markers
-        for &(entity_id, unit_type, position, owner) in units {
-            let color = self.player_colors.get(&owner).copied().unwrap_or([255, 255, 255, 255]);
-            
-            self.unit_markers.push(UnitMarker {
-                position,
-                color,
-                unit_type,
-                entity_id,
-            });
+    /// Whether a screen position lies on the minimap
+    pub fn contains_point(&self, position: Vec2) -> bool {
+        self.visible &&
+        position.x >= self.position.x &&
+        position.x <= self.position.x + self.size.x &&
+        position.y >= self.position.y &&
+        position.y <= self.position.y + self.size.y
+    }
+    
+    /// Pressing the left button jumps the camera to the map location under the cursor, and keeps it
+    /// there as the cursor is dragged until the button is released
+    pub fn begin_drag(&mut self, position: Vec2) -> bool {
+        if !self.contains_point(position) {
+            return false;
         }
+        
+        self.dragging = true;
+        self.requests.push(MinimapRequest::JumpCamera(self.convert_minimap_to_world(position)));
+        true
     }
     
-    pub fn update_building_positions(&mut self, buildings: &[(u32, BuildingType, Vec2, Vec2, u8)]) {
-        // Clear existing markers
-        self.building_markers.clear();
+    /// Follow the cursor with the camera during a drag. Past the minimap's edges the camera stops at
+    /// the edge of the map instead of the drag ending
+    pub fn drag(&mut self, position: Vec2) {
+        if !self.dragging {
+            return;
+        }
         
-        // Add new markers
-        for &(entity_id, building_type, position, size, owner) in buildings {
-            let color = self.player_colors.get(&owner).copied().unwrap_or([255, 255, 255, 255]);
-            
-            self.building_markers.push(BuildingMarker {
-                position,
-                size,
-                color,
-                building_type,
-                entity_id,
-            });
+        let position = position.clamp(self.position, self.position + self.size);
+        self.requests.push(MinimapRequest::JumpCamera(self.convert_minimap_to_world(position)));
+    }
+    
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+    
+    /// Stop dragging; returns whether a drag was under way, so the release belongs to the minimap
+    pub fn end_drag(&mut self) -> bool {
+        std::mem::take(&mut self.dragging)
+    }
+    
+    /// Left click while an order waits for its target gives it the clicked map location
+    pub fn handle_target_click(&mut self, position: Vec2) -> bool {
+        if !self.contains_point(position) {
+            return false;
         }
+        
+        let world_position = self.convert_minimap_to_world(position);
+        self.requests.push(MinimapRequest::Target(world_position));
+        true
     }
     
-    pub fn handle_input(&mut self, position: Vec2) -> bool {
-        // Check if click is within minimap
-        if position.x >= self.position.x && 
-           position.x <= self.position.x + self.size.x &&
-           position.y >= self.position.y && 
-           position.y <= self.position.y + self.size.y {
-            
-            // Convert click to map coordinates
-            let relative_x = (position.x - self.position.x) / self.size.x;
-            let relative_y = (position.y - self.position.y) / self.size.y;
-            
-            let map_x = relative_x * self.map_width as f32;
-            let map_y = relative_y * self.map_height as f32;
-            
-            // This would normally issue a command to move the camera to this location
-            println!("Minimap clicked at map coordinates ({}, {})", map_x, map_y);
-            
-            return true;
+    /// Right click orders the selection to the clicked map location
+    pub fn handle_right_click(&mut self, position: Vec2) -> bool {
+        if !self.contains_point(position) {
+            return false;
         }
         
-        false
+        let world_position = self.convert_minimap_to_world(position);
+        self.requests.push(MinimapRequest::Order(world_position));
+        true
     }
     
-    pub fn resize(&mut self, screen_width: u32, screen_height: u32) {
+    /// Take the requests raised by minimap clicks since the last call
+    pub fn take_requests(&mut self) -> Vec<MinimapRequest> {
+        std::mem::take(&mut self.requests)
+    }
+    
+    pub fn resize(&mut self, screen_size: Vec2) {
         // Position minimap at bottom right
-        self.position = Vec2::new(
-            screen_width as f32 - self.size.x - 10.0,
-            screen_height as f32 - self.size.y - 10.0,
-        );
+        let layout = Layout::aligned(UiAlignment::BottomRight, Vec2::new(-10.0, -10.0), self.size);
+        self.position = layout.place(screen_size).0;
     }
     
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
-        if !self.visible {
+        if !self.visible || self.texture_data.is_empty() {
+            return;
+        }
+        
+        // Image rows run south to north, so they are flipped to put north at the top
+        if let Some(ui_texture) = &self.ui_texture {
+            ui_pipeline.draw_texture(render_pass, ui_texture, self.position, self.size, true);
+        }
+    }
+    
+    /// Compose the minimap image: terrain and building footprints, then fog, then resources and markers,
+    /// then attack pings, then the camera viewport
+    fn redraw(&mut self) {
+        self.texture_data.clone_from(&self.terrain_data);
+        if self.texture_data.is_empty() {
+            return;
+        }
+        self.texture_dirty = true;
+        
+        // Footprints sit under the fog, so unexplored buildings stay hidden
+        if !self.terrain_only {
+            for &tile_index in &self.occupied_tiles {
+                let pixel = tile_index as usize * 4;
+                if let Some(texture_pixel) = self.texture_data.get_mut(pixel..pixel + 4) {
+                    texture_pixel.copy_from_slice(&FOOTPRINT_COLOR);
+                }
+            }
+        }
+        
+        // Darken explored tiles and black out unexplored ones
+        if let Some(visible_tiles) = &self.visible_tiles {
+            for tile_index in 0..self.texture_width * self.texture_height {
+                if visible_tiles.contains(&tile_index) {
+                    continue;
+                }
+                
+                let brightness = if self.explored_tiles.contains(&tile_index) { EXPLORED_BRIGHTNESS } else { 0.0 };
+                let pixel = tile_index as usize * 4;
+                for channel in &mut self.texture_data[pixel..pixel + 3] {
+                    *channel = (*channel as f32 * brightness) as u8;
+                }
+            }
+        }
+        
+        if !self.terrain_only {
+            for i in 0..self.resource_dots.len() {
+                let (position, color) = self.resource_dots[i];
+                self.draw_marker(position / PATH_GRID_SIZE, RESOURCE_DOT_RADIUS, color, MinimapShape::Circle);
+            }
+            
+            for i in 0..self.markers.len() {
+                let marker = &self.markers[i];
+                let (center, radius, color, shape) = (
+                    marker.position / PATH_GRID_SIZE,
+                    (marker.radius / PATH_GRID_SIZE).max(1.0), // At least a few pixels so units stay visible
+                    marker.color,
+                    marker.shape,
+                );
+                self.draw_marker(center, radius, color, shape);
+            }
+        }
+        
+        // Pings shrink onto the spot attacked, flashing on and off, and show even over terrain only
+        let now = Instant::now();
+        for i in 0..self.pings.len() {
+            let ping = &self.pings[i];
+            let age = now.duration_since(ping.started).as_secs_f32();
+            let flash_on = (age * PING_FLASHES_PER_SECOND * 2.0) as u32 % 2 == 0;
+            if !flash_on {
+                continue;
+            }
+            let progress = (age / PING_DURATION.as_secs_f32()).min(1.0);
+            let radius = PING_START_RADIUS + (PING_END_RADIUS - PING_START_RADIUS) * progress;
+            self.draw_ring(ping.position / PATH_GRID_SIZE, radius, PING_COLOR);
+        }
+        
+        let half_size = self.camera_size * 0.5;
+        let view_min = (self.camera_position - half_size) / PATH_GRID_SIZE;
+        let view_max = (self.camera_position + half_size) / PATH_GRID_SIZE;
+        self.draw_rect_outline(view_min, view_max, VIEWPORT_COLOR);
+    }
+    
+    /// Fill the pixels of a marker shape centered on a tile-space position
+    fn draw_marker(&mut self, center: Vec2, radius: f32, color: [u8; 4], shape: MinimapShape) {
+        let min_x = (center.x - radius).floor() as i32;
+        let max_x = (center.x + radius).ceil() as i32;
+        let min_y = (center.y - radius).floor() as i32;
+        let max_y = (center.y + radius).ceil() as i32;
+        
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                let inside = match shape {
+                    MinimapShape::Square => offset.x.abs() <= radius && offset.y.abs() <= radius,
+                    MinimapShape::Circle => offset.length_squared() <= radius * radius,
+                    MinimapShape::Diamond => offset.x.abs() + offset.y.abs() <= radius,
+                    // Point faces north (+y), narrowing towards the top
+                    MinimapShape::Triangle => {
+                        offset.y.abs() <= radius && offset.x.abs() <= (radius - offset.y) * 0.5
+                    }
+                };
+                if inside {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+    
+    /// Draw a one pixel wide circle around a tile-space position
+    fn draw_ring(&mut self, center: Vec2, radius: f32, color: [u8; 4]) {
+        let min_x = (center.x - radius - 1.0).floor() as i32;
+        let max_x = (center.x + radius + 1.0).ceil() as i32;
+        let min_y = (center.y - radius - 1.0).floor() as i32;
+        let max_y = (center.y + radius + 1.0).ceil() as i32;
+        
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let distance = (Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center).length();
+                if (distance - radius).abs() <= 0.5 {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+    
+    /// Draw the border of a tile-space rectangle, clipped to the map
+    fn draw_rect_outline(&mut self, min: Vec2, max: Vec2, color: [u8; 4]) {
+        let (min_x, min_y) = (min.x.floor() as i32, min.y.floor() as i32);
+        let (max_x, max_y) = (max.x.ceil() as i32, max.y.ceil() as i32);
+        
+        for x in min_x..=max_x {
+            self.set_pixel(x, min_y, color);
+            self.set_pixel(x, max_y, color);
+        }
+        for y in min_y..=max_y {
+            self.set_pixel(min_x, y, color);
+            self.set_pixel(max_x, y, color);
+        }
+    }
+    
+    fn set_pixel(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= self.texture_width as i32 || y >= self.texture_height as i32 {
             return;
         }
         
-        // In a real implementation, this would render:
-        // 1. The minimap background texture
-        // 2. Unit and building markers
-        // 3. Camera view rectangle
-        // 4. Fog of war overlay
+        let pixel = (y as usize * self.texture_width as usize + x as usize) * 4;
+        self.texture_data[pixel..pixel + 4].copy_from_slice(&color);
+    }
+    
+    /// Index of the map tile a world position lies on, if it lies on the map
+    fn tile_index(&self, position: Vec2) -> Option<u32> {
+        let tile = (position / PATH_GRID_SIZE).floor();
+        if tile.x < 0.0 || tile.y < 0.0 || tile.x >= self.map_width as f32 || tile.y >= self.map_height as f32 {
+            return None;
+        }
+        Some(tile.y as u32 * self.map_width + tile.x as u32)
     }
     
     fn convert_world_to_minimap(&self, world_pos: Vec2) -> Vec2 {
-        // Convert from world coordinates to minimap coordinates
-        let minimap_x = (world_pos.x / self.map_width as f32) * self.size.x + self.position.x;
-        let minimap_y = (world_pos.y / self.map_height as f32) * self.size.y + self.position.y;
+        // Convert from world coordinates to minimap coordinates; world y points north, screen y down
+        let relative = world_pos / (Vec2::new(self.map_width as f32, self.map_height as f32) * PATH_GRID_SIZE);
+        let minimap_x = relative.x * self.size.x + self.position.x;
+        let minimap_y = (1.0 - relative.y) * self.size.y + self.position.y;
         Vec2::new(minimap_x, minimap_y)
     }
     
     fn convert_minimap_to_world(&self, minimap_pos: Vec2) -> Vec2 {
         // Convert from minimap coordinates to world coordinates
-        let world_x = ((minimap_pos.x - self.position.x) / self.size.x) * self.map_width as f32;
-        let world_y = ((minimap_pos.y - self.position.y) / self.size.y) * self.map_height as f32;
-        Vec2::new(world_x, world_y)
+        let relative_x = (minimap_pos.x - self.position.x) / self.size.x;
+        let relative_y = 1.0 - (minimap_pos.y - self.position.y) / self.size.y;
+        Vec2::new(relative_x * self.map_width as f32, relative_y * self.map_height as f32) * PATH_GRID_SIZE
     }
-}
\ No newline at end of file
+}
+
+/// Color of the dot marking a resource node
+fn resource_color(resource_type: ResourceType) -> [u8; 4] {
+    match resource_type {
+        ResourceType::Mineral => [90, 200, 255, 255],
+        ResourceType::Gas => [60, 220, 120, 255],
+        ResourceType::Energy => [255, 220, 60, 255],
+    }
+}
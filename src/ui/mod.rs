@@ -1,14 +1,43 @@
+pub mod codex;
+pub mod console;
+pub mod font;
 pub mod hud;
+pub mod i18n;
+pub mod layout;
+pub mod log_viewer;
 pub mod minimap;
+pub mod perf_overlay;
+pub mod pipeline;
+pub mod theme;
 pub mod menus;
 
+pub use pipeline::{UiPipeline, UiQuad};
+
 use anyhow::Result;
+use bevy_ecs::world::World;
 use glam::{Vec2, Vec4};
+use serde::Deserialize;
 use wgpu::{Device, Queue, RenderPass, TextureFormat};
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use winit::event::{ModifiersState, VirtualKeyCode};
+use tracing::warn;
 
-use crate::engine::assets::TextureAsset;
+use crate::networking::chat::{ChatChannel, MAX_CHAT_LENGTH};
+use crate::networking::discovery::DiscoveredGame;
+use crate::networking::latency::ConnectionQuality;
 use crate::game::GameState;
+use crate::ecs::components::UnitType;
+use crate::ecs::resources::GameMap;
+use crate::ui::i18n::tr;
+use crate::ui::layout::Layout;
+use crate::ui::menus::{ServerList, TextInput};
+
+/// Element id of the in-game chat box
+const CHAT_INPUT_ID: &str = "chat_input";
+
+/// Element id of the developer console's input line
+const CONSOLE_INPUT_ID: &str = "console_input";
 
 /// UI Element types
 pub enum UiElementType {
@@ -17,9 +46,13 @@ pub enum UiElementType {
     Text,
     Image,
     ProgressBar,
+    TextInput,
+    Graph,
+    List,
 }
 
 /// UI Element alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UiAlignment {
     TopLeft,
     Top,
@@ -32,8 +65,25 @@ pub enum UiAlignment {
     BottomRight,
 }
 
-/// UI color scheme
-#[derive(Clone)]
+impl UiAlignment {
+    /// Point of a rectangle this names, as a fraction of its size
+    pub fn fraction(self) -> Vec2 {
+        match self {
+            UiAlignment::TopLeft => Vec2::new(0.0, 0.0),
+            UiAlignment::Top => Vec2::new(0.5, 0.0),
+            UiAlignment::TopRight => Vec2::new(1.0, 0.0),
+            UiAlignment::Left => Vec2::new(0.0, 0.5),
+            UiAlignment::Center => Vec2::new(0.5, 0.5),
+            UiAlignment::Right => Vec2::new(1.0, 0.5),
+            UiAlignment::BottomLeft => Vec2::new(0.0, 1.0),
+            UiAlignment::Bottom => Vec2::new(0.5, 1.0),
+            UiAlignment::BottomRight => Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+/// UI color scheme, the colors of one theme
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct UiColorScheme {
     pub background: Vec4,
     pub foreground: Vec4,
@@ -70,27 +120,71 @@ pub trait UiElement {
     fn contains_point(&self, point: Vec2) -> bool;
     fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline);
     fn handle_click(&mut self, position: Vec2) -> bool;
-}
-
-/// UI Pipeline for rendering UI elements
-pub struct UiPipeline {
-    device: Device,
-    queue: Queue,
-    pipeline: wgpu::RenderPipeline,
-    bind_group_layout: wgpu::BindGroupLayout,
-    text_atlas: Option<TextureAsset>,
-    ui_textures: HashMap<String, TextureAsset>,
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    
+    /// Move and resize the element, e.g. when its layout is resolved for a new window size
+    fn set_bounds(&mut self, _position: Vec2, _size: Vec2) {}
+    
+    /// Take on a new theme's colors in place
+    fn apply_color_scheme(&mut self, _color_scheme: &UiColorScheme) {}
+    
+    /// Whether clicking the element gives it keyboard focus
+    fn accepts_focus(&self) -> bool {
+        false
+    }
+    
+    fn set_focused(&mut self, _focused: bool) {}
+    
+    /// A typed character, for the focused element; returns whether it was used
+    fn handle_char(&mut self, _c: char) -> bool {
+        false
+    }
+    
+    /// A key press, for the focused element; returns whether it was used
+    fn handle_key(&mut self, _key: VirtualKeyCode, _modifiers: ModifiersState) -> bool {
+        false
+    }
+    
+    /// The cursor moved, over the element or not; hover states and drags follow it.
+    /// Returns whether the element's look changed
+    fn handle_hover(&mut self, _position: Vec2) -> bool {
+        false
+    }
+    
+    /// The left mouse button went down over the element; returns whether it was used
+    fn handle_press(&mut self, _position: Vec2) -> bool {
+        false
+    }
+    
+    /// The left mouse button came up, over the element or not, ending any press or drag; returns whether it was used
+    fn handle_release(&mut self, _position: Vec2) -> bool {
+        false
+    }
+    
+    /// A click landed somewhere else, so open popups such as a dropdown's list close
+    fn handle_click_outside(&mut self) {}
 }
 
 /// UI Manager to handle all UI elements
 pub struct UiManager {
-    screen_size: Vec2,
+    screen_size: Vec2, // Logical pixels, which every UI position and size is in
+    scale_factor: f32, // Physical window pixels per logical pixel
     ui_elements: HashMap<String, Box<dyn UiElement>>,
+    layouts: HashMap<String, Layout>, // Elements placed relative to the screen, laid out again on resize
     ui_pipeline: UiPipeline,
     color_scheme: UiColorScheme,
+    themes: theme::Themes,
+    theme_name: String, // Theme color_scheme came from
     active_screen: String,
+    focused_element: Option<String>, // Element that keyboard input goes to
+    chat_channel: Option<ChatChannel>, // Channel of the open chat box
+    outgoing_chat: Vec<(ChatChannel, String)>,
+    console: console::Console,
     hud: hud::Hud,
     minimap: minimap::Minimap,
+    perf_overlay: perf_overlay::PerfOverlay,
+    log_viewer: log_viewer::LogViewer,
 }
 
 impl UiManager {
@@ -99,18 +193,40 @@ impl UiManager {
         queue: Queue,
         screen_width: u32,
         screen_height: u32,
+        scale_factor: f32,
         surface_format: TextureFormat,
     ) -> Result<Self> {
         let ui_pipeline = UiPipeline::new(device, queue, surface_format)?;
+        let screen_size = Vec2::new(screen_width as f32, screen_height as f32) / scale_factor;
+        ui_pipeline.set_screen_size(screen_size);
+        
+        let themes = theme::Themes::load_or_default();
+        let color_scheme = themes.get(theme::DEFAULT_THEME).colors.clone();
+        
+        let mut hud = hud::Hud::new();
+        hud.resize(screen_size);
+        hud.set_color_scheme(color_scheme.clone());
+        let mut minimap = minimap::Minimap::new();
+        minimap.resize(screen_size);
         
         Ok(Self {
-            screen_size: Vec2::new(screen_width as f32, screen_height as f32),
+            screen_size,
+            scale_factor,
             ui_elements: HashMap::new(),
+            layouts: HashMap::new(),
             ui_pipeline,
-            color_scheme: UiColorScheme::default(),
+            color_scheme,
+            themes,
+            theme_name: theme::DEFAULT_THEME.to_string(),
             active_screen: "game".to_string(),
-            hud: hud::Hud::new(),
-            minimap: minimap::Minimap::new(),
+            focused_element: None,
+            chat_channel: None,
+            outgoing_chat: Vec::new(),
+            console: console::Console::new(screen_size),
+            hud,
+            minimap,
+            perf_overlay: perf_overlay::PerfOverlay::new(screen_size),
+            log_viewer: log_viewer::LogViewer::new(screen_size),
         })
     }
     
@@ -118,42 +234,351 @@ impl UiManager {
         self.ui_elements.insert(id.to_string(), element);
     }
     
+    /// Add an element placed by a layout, which keeps it in place as the window is resized
+    pub fn add_element_with_layout(&mut self, id: &str, mut element: Box<dyn UiElement>, layout: Layout) {
+        let (position, size) = layout.place(self.screen_size);
+        element.set_bounds(position, size);
+        self.ui_elements.insert(id.to_string(), element);
+        self.layouts.insert(id.to_string(), layout);
+    }
+    
     pub fn remove_element(&mut self, id: &str) {
         self.ui_elements.remove(id);
+        self.layouts.remove(id);
+        if self.focused_element.as_deref() == Some(id) {
+            self.focused_element = None;
+        }
+    }
+    
+    pub fn get_element(&self, id: &str) -> Option<&dyn UiElement> {
+        self.ui_elements.get(id).map(|element| element.as_ref())
+    }
+    
+    pub fn get_element_mut(&mut self, id: &str) -> Option<&mut Box<dyn UiElement>> {
+        self.ui_elements.get_mut(id)
+    }
+    
+    /// Convert a cursor position in physical window pixels, as winit reports it, to the UI's logical pixels
+    fn to_logical(&self, position: Vec2) -> Vec2 {
+        position / self.scale_factor
     }
     
     pub fn handle_input(&mut self, position: Vec2) -> bool {
+        let position = self.to_logical(position);
+        
+        // Clicking anywhere takes focus away from the focused element
+        self.set_focus(None);
+        if self.is_chat_open() {
+            self.close_chat();
+        }
+        if self.is_console_open() {
+            self.close_console();
+        }
+        
         // Check if any UI element was clicked
+        let clicked = self.ui_elements
+            .iter()
+            .find(|(_, element)| element.is_visible() && element.contains_point(position))
+            .map(|(id, _)| id.clone());
+        // Open dropdowns close when the click lands anywhere else
+        for (id, element) in self.ui_elements.iter_mut() {
+            if clicked.as_ref() != Some(id) {
+                element.handle_click_outside();
+            }
+        }
+        
+        if let Some(id) = clicked {
+            let element = self.ui_elements.get_mut(&id).unwrap();
+            let handled = element.handle_click(position);
+            if element.accepts_focus() {
+                self.set_focus(Some(id));
+            }
+            return handled;
+        }
+        
+        // Check HUD elements; the engine hands minimap clicks to the minimap itself
+        self.hud.handle_input(position)
+    }
+    
+    /// Pass a cursor move to every visible element, so buttons show hover and sliders follow drags
+    pub fn handle_mouse_move(&mut self, position: Vec2) {
+        let position = self.to_logical(position);
+        for element in self.ui_elements.values_mut().filter(|element| element.is_visible()) {
+            element.handle_hover(position);
+        }
+    }
+    
+    /// Press the element under the cursor; returns whether one took the press
+    pub fn handle_press(&mut self, position: Vec2) -> bool {
+        let position = self.to_logical(position);
+        self.ui_elements
+            .values_mut()
+            .find(|element| element.is_visible() && element.contains_point(position))
+            .map_or(false, |element| element.handle_press(position))
+    }
+    
+    /// Let go of the left mouse button on every element, ending presses and drags wherever the cursor is
+    pub fn handle_release(&mut self, position: Vec2) {
+        let position = self.to_logical(position);
         for element in self.ui_elements.values_mut() {
-            if element.is_visible() && element.contains_point(position) {
-                return element.handle_click(position);
+            element.handle_release(position);
+        }
+    }
+    
+    /// Give keyboard focus to an element, or to nothing
+    pub fn set_focus(&mut self, id: Option<String>) {
+        if let Some(element) = self.focused_element.take().and_then(|old| self.ui_elements.get_mut(&old)) {
+            element.set_focused(false);
+        }
+        
+        if let Some(id) = id {
+            if let Some(element) = self.ui_elements.get_mut(&id) {
+                element.set_focused(true);
+                self.focused_element = Some(id);
+            }
+        }
+    }
+    
+    /// Whether a text field has keyboard focus, so key presses shouldn't reach the game
+    pub fn has_keyboard_focus(&self) -> bool {
+        self.focused_element.is_some()
+    }
+    
+    /// Send a typed character to the focused element; returns whether it was used
+    pub fn handle_char(&mut self, c: char) -> bool {
+        match self.focused_element.as_ref().and_then(|id| self.ui_elements.get_mut(id)) {
+            Some(element) => element.handle_char(c),
+            None => false,
+        }
+    }
+    
+    /// Send a key press to the focused element; returns whether it was used
+    pub fn handle_key(&mut self, key: VirtualKeyCode, modifiers: ModifiersState) -> bool {
+        // Enter runs what was typed into the console, the arrows recall earlier commands and backtick or Escape close it
+        if self.console.is_open() {
+            match key {
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    let text = self.console_input().map(|input| input.get_text().trim().to_string()).unwrap_or_default();
+                    if !text.is_empty() {
+                        self.console.submit(text);
+                    }
+                    if let Some(input) = self.console_input() {
+                        input.clear();
+                    }
+                    return true;
+                }
+                VirtualKeyCode::Up | VirtualKeyCode::Down => {
+                    let recalled = self.console.recall(key == VirtualKeyCode::Up).unwrap_or_default().to_string();
+                    if let Some(input) = self.console_input() {
+                        input.set_text(&recalled);
+                    }
+                    return true;
+                }
+                VirtualKeyCode::Grave | VirtualKeyCode::Escape => {
+                    self.close_console();
+                    return true;
+                }
+                _ => {}
             }
         }
         
-        // Check HUD elements
-        if self.hud.handle_input(position) {
-            return true;
+        // Enter sends what was typed in the chat box and Escape throws it away
+        if let Some(channel) = self.chat_channel {
+            match key {
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    let text = self.get_element(CHAT_INPUT_ID)
+                        .and_then(|e| e.as_any().downcast_ref::<TextInput>())
+                        .map(|input| input.get_text().trim().to_string())
+                        .unwrap_or_default();
+                    if !text.is_empty() {
+                        self.outgoing_chat.push((channel, text));
+                    }
+                    self.close_chat();
+                    return true;
+                }
+                VirtualKeyCode::Escape => {
+                    self.close_chat();
+                    return true;
+                }
+                _ => {}
+            }
         }
         
-        // Check minimap
-        if self.minimap.handle_input(position) {
-            return true;
+        let handled = match self.focused_element.as_ref().and_then(|id| self.ui_elements.get_mut(id)) {
+            Some(element) => element.handle_key(key, modifiers),
+            None => return false,
+        };
+        
+        // Escape gives up focus
+        if key == VirtualKeyCode::Escape {
+            self.set_focus(None);
         }
+        handled
+    }
+    
+    /// Open the chat box above the bottom panels, with keyboard focus
+    pub fn open_chat(&mut self, channel: ChatChannel) {
+        let placeholder = match channel {
+            ChatChannel::Allies => tr("chat.to_allies"),
+            _ => tr("chat.to_all"),
+        };
+        let layout = Layout::aligned(UiAlignment::BottomLeft, Vec2::new(10.0, -130.0), Vec2::new(400.0, 30.0));
+        let chat_input = TextInput::new(Vec2::ZERO, layout.size, &self.color_scheme)
+            .with_placeholder(&placeholder)
+            .with_max_length(MAX_CHAT_LENGTH);
         
-        false
+        self.add_element_with_layout(CHAT_INPUT_ID, Box::new(chat_input), layout);
+        self.set_focus(Some(CHAT_INPUT_ID.to_string()));
+        self.chat_channel = Some(channel);
+        self.hud.set_chat_open(true);
     }
     
-    pub fn update(&mut self, game_state: &GameState) {
-        // Update HUD with game state
-        self.hud.update(game_state);
+    pub fn close_chat(&mut self) {
+        self.remove_element(CHAT_INPUT_ID);
+        self.chat_channel = None;
+        self.hud.set_chat_open(false);
+    }
+    
+    pub fn is_chat_open(&self) -> bool {
+        self.chat_channel.is_some()
+    }
+    
+    /// Drop the developer console down over the top of the screen, with keyboard focus
+    pub fn open_console(&mut self) {
+        let (position, size) = self.console.input_rect();
+        let console_input = TextInput::new(position, size, &self.color_scheme)
+            .with_placeholder(&tr("console.placeholder"));
         
-        // Update minimap
-        self.minimap.update(game_state);
+        self.add_element(CONSOLE_INPUT_ID, Box::new(console_input));
+        self.set_focus(Some(CONSOLE_INPUT_ID.to_string()));
+        self.console.set_open(true);
+    }
+    
+    pub fn close_console(&mut self) {
+        self.remove_element(CONSOLE_INPUT_ID);
+        self.console.set_open(false);
+    }
+    
+    pub fn is_console_open(&self) -> bool {
+        self.console.is_open()
+    }
+    
+    /// Take the commands entered into the console since the last call
+    pub fn take_console_commands(&mut self) -> Vec<String> {
+        self.console.take_submitted()
+    }
+    
+    /// Show a line of output in the console
+    pub fn console_print(&mut self, line: String) {
+        self.console.print(line);
+    }
+    
+    pub fn set_perf_overlay_visible(&mut self, visible: bool) {
+        self.perf_overlay.set_visible(visible);
+    }
+    
+    pub fn is_perf_overlay_visible(&self) -> bool {
+        self.perf_overlay.is_visible()
+    }
+    
+    /// Add a frame's measurements to the performance overlay
+    pub fn record_frame_stats(&mut self, stats: perf_overlay::FrameStats) {
+        self.perf_overlay.record(stats);
+    }
+    
+    pub fn set_log_viewer_visible(&mut self, visible: bool) {
+        self.log_viewer.set_visible(visible);
+    }
+    
+    pub fn is_log_viewer_visible(&self) -> bool {
+        self.log_viewer.is_visible()
+    }
+    
+    /// List only warnings and errors logged under the given target in the log viewer, or all of them
+    pub fn set_log_viewer_target(&mut self, target: Option<String>) {
+        self.log_viewer.set_target(target);
+    }
+    
+    fn console_input(&mut self) -> Option<&mut TextInput> {
+        self.ui_elements
+            .get_mut(CONSOLE_INPUT_ID)
+            .and_then(|element| element.as_any_mut().downcast_mut::<TextInput>())
+    }
+    
+    /// Take the chat lines sent from the chat box since the last call
+    pub fn take_outgoing_chat(&mut self) -> Vec<(ChatChannel, String)> {
+        std::mem::take(&mut self.outgoing_chat)
+    }
+    
+    /// Show a line in the HUD message log; `system` marks notices not written by a player
+    pub fn add_chat_line(&mut self, text: String, system: bool) {
+        self.hud.add_chat_line(text, system);
+    }
+    
+    /// Handle a right click on the UI; only the minimap reacts to it
+    pub fn handle_right_click(&mut self, position: Vec2) -> bool {
+        self.minimap.handle_right_click(self.to_logical(position))
+    }
+    
+    /// Start moving the camera with the left button held on the minimap; returns whether the press was on it
+    pub fn begin_minimap_drag(&mut self, position: Vec2) -> bool {
+        self.minimap.begin_drag(self.to_logical(position))
+    }
+    
+    /// Move the camera along with the cursor while the minimap is being dragged
+    pub fn drag_minimap(&mut self, position: Vec2) {
+        self.minimap.drag(self.to_logical(position));
+    }
+    
+    pub fn is_dragging_minimap(&self) -> bool {
+        self.minimap.is_dragging()
+    }
+    
+    /// Stop a minimap drag; returns whether one was under way
+    pub fn end_minimap_drag(&mut self) -> bool {
+        self.minimap.end_drag()
+    }
+    
+    /// Left click on the minimap while an order waits for its target
+    pub fn handle_minimap_target_click(&mut self, position: Vec2) -> bool {
+        self.minimap.handle_target_click(self.to_logical(position))
+    }
+    
+    /// Whether a screen position lies on the minimap
+    pub fn is_over_minimap(&self, position: Vec2) -> bool {
+        self.minimap.contains_point(self.to_logical(position))
+    }
+    
+    pub fn update(&mut self, game_state: &GameState, local_player_id: u8) {
+        // Update HUD with game state
+        self.hud.update(game_state, local_player_id);
+    }
+    
+    /// Redraw the minimap from the world and the camera's visible world rectangle, and upload it if it changed
+    pub fn update_minimap(&mut self, world: &mut World, view_bounds: (Vec2, Vec2)) {
+        self.minimap.update(world, view_bounds.0, view_bounds.1);
+        self.minimap.upload_texture(&self.ui_pipeline);
+    }
+    
+    /// Show only terrain, fog and attack pings on the minimap, or everything again; returns whether it is terrain only now
+    pub fn toggle_minimap_terrain_only(&mut self) -> bool {
+        self.minimap.toggle_terrain_only();
+        self.minimap.is_terrain_only()
+    }
+    
+    /// Show the whole map as explored on the minimap
+    pub fn reveal_minimap(&mut self, map: &GameMap) {
+        self.minimap.reveal(map);
+    }
+    
+    /// Forget the minimap's map and explored area, e.g. when a save is loaded
+    pub fn reset_minimap(&mut self) {
+        self.minimap.reset();
     }
     
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
-        // Set pipeline
-        render_pass.set_pipeline(&self.ui_pipeline.pipeline);
+        // Bind the pipeline; its projection maps logical pixels to the window by scale_factor
+        self.ui_pipeline.begin_frame(render_pass);
         
         // Render all visible UI elements
         for element in self.ui_elements.values() {
@@ -167,6 +592,113 @@ impl UiManager {
         
         // Render minimap
         self.minimap.render(render_pass, &self.ui_pipeline);
+        
+        // Performance overlay
+        self.perf_overlay.render(render_pass, &self.ui_pipeline);
+        
+        // Recent warnings and errors
+        self.log_viewer.render(render_pass, &self.ui_pipeline);
+        
+        // The console drops down over everything else
+        self.console.render(render_pass, &self.ui_pipeline);
+    }
+    
+    /// Press the HUD action button a hotkey grid key stands for; returns whether one did
+    pub fn handle_hotkey(&mut self, key: VirtualKeyCode) -> bool {
+        self.hud.handle_hotkey(key)
+    }
+    
+    /// Take the requests raised by HUD buttons since the last call
+    pub fn take_hud_requests(&mut self) -> Vec<hud::HudRequest> {
+        self.hud.take_requests()
+    }
+    
+    /// Take the requests raised by minimap clicks since the last call
+    pub fn take_minimap_requests(&mut self) -> Vec<minimap::MinimapRequest> {
+        self.minimap.take_requests()
+    }
+    
+    /// Show a banner message on the HUD
+    pub fn show_alert(&mut self, message: String) {
+        self.hud.show_alert(message);
+    }
+    
+    pub fn clear_alert(&mut self) {
+        self.hud.clear_alert();
+    }
+    
+    /// Briefly show a gameplay warning on the HUD
+    pub fn show_warning(&mut self, message: String) {
+        self.hud.show_warning(message);
+    }
+    
+    /// Show badges for the control groups the current selection belongs to
+    pub fn set_control_groups(&mut self, groups: Vec<u8>) {
+        self.hud.set_control_groups(groups);
+    }
+    
+    /// Set the technologies shown in the tech tree panel
+    pub fn set_tech_tree(&mut self, nodes: Vec<hud::TechNode>) {
+        self.hud.set_tech_tree(nodes);
+    }
+    
+    /// Show the selected units, offering the commands of the active subgroup's unit type
+    pub fn set_selected_units(&mut self, units: Vec<hud::UnitInfo>, active_unit_type: Option<UnitType>) {
+        self.hud.set_selected_units(units, active_unit_type);
+    }
+    
+    /// Show the cargo slots of the selected transport, filled with the units aboard it
+    pub fn set_cargo(&mut self, capacity: u32, passengers: Vec<hud::UnitInfo>) {
+        self.hud.set_cargo(capacity, passengers);
+    }
+    
+    /// Show the total cost of the building or wall row being placed
+    pub fn set_placement_cost(&mut self, cost: Option<String>) {
+        self.hud.set_placement_cost(cost);
+    }
+    
+    /// Show our connection quality in the HUD during multiplayer games; None hides it
+    pub fn set_connection_quality(&mut self, quality: Option<ConnectionQuality>) {
+        self.hud.set_connection_quality(quality);
+    }
+    
+    /// Keys the bindings use, which the HUD's hotkey grid leaves to them
+    pub fn set_bound_keys(&mut self, keys: HashSet<VirtualKeyCode>) {
+        self.hud.set_bound_keys(keys);
+    }
+    
+    /// Offer the HUD's surrender button, during multiplayer games
+    pub fn set_surrender_available(&mut self, available: bool) {
+        self.hud.set_surrender_available(available);
+    }
+    
+    /// Show how many of the local player's workers are idle on the idle worker button
+    pub fn set_idle_workers(&mut self, count: usize) {
+        self.hud.set_idle_workers(count);
+    }
+    
+    /// Offer the abilities of the active subgroup on the command card
+    pub fn set_abilities(&mut self, abilities: Vec<hud::AbilityInfo>) {
+        self.hud.set_abilities(abilities);
+    }
+    
+    /// Show the selected building, including its production queue, in the info panel
+    pub fn set_selected_building(&mut self, building: Option<hud::BuildingInfo>) {
+        self.hud.set_selected_building(building);
+    }
+    
+    pub fn active_screen(&self) -> &str {
+        &self.active_screen
+    }
+    
+    /// Show the games found on the local network in the multiplayer menu's list
+    pub fn set_discovered_games(&mut self, games: Vec<DiscoveredGame>) {
+        if let Some(server_list) = self.ui_elements
+            .get_mut("multiplayer_server_list")
+            .and_then(|element| element.as_any_mut().downcast_mut::<ServerList>())
+        {
+            server_list.set_games(games);
+        }
     }
     
     pub fn set_active_screen(&mut self, screen_id: &str) {
@@ -176,15 +708,70 @@ impl UiManager {
         for (id, element) in self.ui_elements.iter_mut() {
             element.set_visible(id.starts_with(&format!("{}_", screen_id)));
         }
+        
+        // Hidden fields can't be typed into
+        self.set_focus(None);
+    }
+    
+    /// Names of the themes the settings menu offers
+    pub fn theme_names(&self) -> Vec<String> {
+        self.themes.names()
+    }
+    
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
+    
+    /// Switch to a theme by name, recoloring every existing element rather than rebuilding them.
+    /// Unknown names get the first theme
+    pub fn set_theme(&mut self, name: &str) {
+        let theme = self.themes.get(name);
+        if theme.name != name {
+            warn!("Unknown UI theme {:?}, using {}", name, theme.name);
+        }
+        self.color_scheme = theme.colors.clone();
+        self.theme_name = name.to_string();
+        
+        for element in self.ui_elements.values_mut() {
+            element.apply_color_scheme(&self.color_scheme);
+        }
+        self.hud.set_color_scheme(self.color_scheme.clone());
+    }
+    
+    /// Rebuild the HUD text after the language changes; the chat and console boxes pick it up when next opened
+    pub fn retranslate(&mut self) {
+        self.hud.retranslate();
     }
     
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.screen_size = Vec2::new(width as f32, height as f32);
+    /// Lay the UI out again for a new window size in physical pixels, or a new DPI scale
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+        self.screen_size = Vec2::new(width as f32, height as f32) / scale_factor;
+        self.ui_pipeline.set_screen_size(self.screen_size);
+        
+        // Elements placed by a layout
+        for (id, layout) in &self.layouts {
+            if let Some(element) = self.ui_elements.get_mut(id) {
+                let (position, size) = layout.place(self.screen_size);
+                element.set_bounds(position, size);
+            }
+        }
         
         // Update minimap position
-        self.minimap.resize(width, height);
+        self.minimap.resize(self.screen_size);
         
         // Update HUD layout
-        self.hud.resize(width, height);
+        self.hud.resize(self.screen_size);
+        
+        // Update console layout, keeping an open console's input line on its bottom edge
+        self.console.resize(self.screen_size);
+        if let Some(input) = self.ui_elements.get_mut(CONSOLE_INPUT_ID) {
+            let (position, size) = self.console.input_rect();
+            input.set_bounds(position, size);
+        }
+        
+        // Update performance overlay position
+        self.perf_overlay.resize(self.screen_size);
+        self.log_viewer.resize(self.screen_size);
     }
 }
\ No newline at end of file
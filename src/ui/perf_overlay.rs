@@ -0,0 +1,194 @@
+use glam::{Vec2, Vec4};
+use wgpu::RenderPass;
+use std::collections::VecDeque;
+
+use crate::ecs::resources::TICK_DELTA;
+use crate::engine::renderer::CullStats;
+use crate::networking::bandwidth::BandwidthStats;
+use crate::ui::{UiAlignment, UiPipeline, UiQuad};
+use crate::ui::font::{self, SMALL_TEXT_HEIGHT};
+use crate::ui::layout::Layout;
+
+/// Frames of frame and tick time history the graph shows
+const HISTORY_LENGTH: usize = 240;
+
+/// Slowest systems listed below the graph
+const LISTED_SYSTEMS: usize = 8;
+
+/// Size of the overlay panel
+const OVERLAY_SIZE: Vec2 = Vec2::new(320.0, 305.0);
+
+/// Gap between the overlay and the screen edges
+const MARGIN: f32 = 10.0;
+
+/// Gap between the panel's edges and what is drawn on it
+const PADDING: f32 = 8.0;
+
+/// Height of the frame and tick time graph
+const GRAPH_HEIGHT: f32 = 80.0;
+
+/// Distance from one summary line to the next
+const LINE_SPACING: f32 = SMALL_TEXT_HEIGHT + 3.0;
+
+const PANEL_COLOR: Vec4 = Vec4::new(0.0, 0.0, 0.0, 0.6);
+const GRAPH_COLOR: Vec4 = Vec4::new(1.0, 1.0, 1.0, 0.08);
+const FRAME_TIME_COLOR: Vec4 = Vec4::new(0.3, 0.9, 0.3, 1.0);
+const TICK_TIME_COLOR: Vec4 = Vec4::new(1.0, 0.6, 0.2, 1.0);
+const TICK_INTERVAL_COLOR: Vec4 = Vec4::new(1.0, 1.0, 1.0, 0.35);
+const TEXT_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.9, 1.0);
+
+/// Measurements the engine takes each frame for the performance overlay
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    pub frame_time: f32,        // Milliseconds
+    pub tick_time: Option<f32>, // Milliseconds spent in simulation ticks this frame; None when no tick ran
+    pub ticks: u32,             // Simulation ticks run this frame
+    pub entities: u32,
+    pub units: u32,
+    pub buildings: u32,
+    pub draw_calls: u32,
+    pub culling: CullStats,
+    pub system_times: Vec<(String, f32)>, // Milliseconds each system took in the latest tick, slowest first
+    pub network: Option<BandwidthStats>,  // None outside multiplayer games
+}
+
+/// Corner panel with frame and tick time graphs, entity, culling and draw call counts, network traffic
+/// and the slowest systems
+pub struct PerfOverlay {
+    position: Vec2,
+    size: Vec2,
+    visible: bool,
+    frame_times: VecDeque<f32>, // Oldest first
+    tick_times: VecDeque<f32>,  // Oldest first; 0 for frames that ran no tick
+    latest: FrameStats,
+}
+
+impl PerfOverlay {
+    pub fn new(screen_size: Vec2) -> Self {
+        let mut overlay = Self {
+            position: Vec2::ZERO,
+            size: OVERLAY_SIZE,
+            visible: false,
+            frame_times: VecDeque::with_capacity(HISTORY_LENGTH),
+            tick_times: VecDeque::with_capacity(HISTORY_LENGTH),
+            latest: FrameStats::default(),
+        };
+        overlay.resize(screen_size);
+        overlay
+    }
+    
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    
+    /// Show or hide the overlay; the history starts over each time it is shown
+    pub fn set_visible(&mut self, visible: bool) {
+        if visible && !self.visible {
+            self.frame_times.clear();
+            self.tick_times.clear();
+        }
+        self.visible = visible;
+    }
+    
+    /// Add a frame's measurements to the graphs
+    pub fn record(&mut self, mut stats: FrameStats) {
+        self.frame_times.push_back(stats.frame_time);
+        self.tick_times.push_back(stats.tick_time.unwrap_or(0.0));
+        if self.frame_times.len() > HISTORY_LENGTH {
+            self.frame_times.pop_front();
+            self.tick_times.pop_front();
+        }
+        
+        stats.system_times.truncate(LISTED_SYSTEMS);
+        self.latest = stats;
+    }
+    
+    /// Slowest frame time in the history, which sets the graph's vertical scale
+    pub fn peak_frame_time(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0.0, f32::max)
+    }
+    
+    /// Frames per second averaged over the history
+    pub fn fps(&self) -> f32 {
+        let total: f32 = self.frame_times.iter().sum();
+        if total > 0.0 {
+            self.frame_times.len() as f32 * 1000.0 / total
+        } else {
+            0.0
+        }
+    }
+    
+    /// Lines of text shown next to the graph
+    pub fn summary_lines(&self) -> Vec<String> {
+        let stats = &self.latest;
+        let mut lines = vec![
+            format!("{:.0} FPS  {:.2} ms/frame", self.fps(), stats.frame_time),
+            match stats.tick_time {
+                Some(tick_time) => format!("Ticks: {}  {:.2} ms", stats.ticks, tick_time),
+                None => "Ticks: 0".to_string(),
+            },
+            format!("Entities: {}  ({} units, {} buildings)", stats.entities, stats.units, stats.buildings),
+            format!("Draw calls: {}", stats.draw_calls),
+            format!(
+                "Drawn: {} ({} markers)  culled: {} off screen, {} in fog",
+                stats.culling.drawn, stats.culling.markers, stats.culling.off_screen, stats.culling.fogged,
+            ),
+        ];
+        if let Some(network) = &stats.network {
+            lines.push(format!(
+                "Net: {:.1} KB/s up  {:.1} KB/s down",
+                network.sent as f32 / 1024.0,
+                network.received as f32 / 1024.0,
+            ));
+            lines.push(format!(
+                "Commands: {} B/s  ({:.0}% saved by batching)",
+                network.commands,
+                network.batching_savings() * 100.0,
+            ));
+        }
+        lines.extend(stats.system_times.iter().map(|(name, time)| format!("{:>7.3} ms  {}", time, name)));
+        lines
+    }
+    
+    pub fn resize(&mut self, screen_size: Vec2) {
+        // Top-right corner, clear of the minimap and command card at the bottom
+        let layout = Layout::aligned(UiAlignment::TopRight, Vec2::new(-MARGIN, MARGIN), self.size);
+        self.position = layout.place(screen_size).0;
+    }
+    
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, ui_pipeline: &'a UiPipeline) {
+        if !self.visible {
+            return;
+        }
+        
+        let mut quads = vec![UiQuad::rect(self.position, self.size, PANEL_COLOR)];
+        
+        // Both histories share the scale, which always reaches the tick interval so its line shows
+        let graph_position = self.position + Vec2::splat(PADDING);
+        let graph_size = Vec2::new(self.size.x - PADDING * 2.0, GRAPH_HEIGHT);
+        let tick_interval = TICK_DELTA * 1000.0;
+        let scale = self.peak_frame_time().max(tick_interval);
+        quads.push(UiQuad::rect(graph_position, graph_size, GRAPH_COLOR));
+        let interval_y = graph_position.y + graph_size.y * (1.0 - tick_interval / scale);
+        quads.push(UiQuad::rect(Vec2::new(graph_position.x, interval_y), Vec2::new(graph_size.x, 1.0), TICK_INTERVAL_COLOR));
+        
+        for (history, color) in [(&self.tick_times, TICK_TIME_COLOR), (&self.frame_times, FRAME_TIME_COLOR)] {
+            let point = |index: usize, time: f32| {
+                let x = index as f32 / (HISTORY_LENGTH - 1) as f32;
+                graph_position + Vec2::new(x * graph_size.x, (1.0 - time / scale) * graph_size.y)
+            };
+            for (index, (&from, &to)) in history.iter().zip(history.iter().skip(1)).enumerate() {
+                quads.push(UiQuad::line(point(index, from), point(index + 1, to), 1.0, color));
+            }
+        }
+        
+        let max_chars = font::chars_fitting(graph_size.x, SMALL_TEXT_HEIGHT);
+        let text_top = graph_position.y + GRAPH_HEIGHT + PADDING;
+        for (row, line) in self.summary_lines().iter().enumerate() {
+            let line: String = line.chars().take(max_chars).collect();
+            let position = Vec2::new(graph_position.x, text_top + row as f32 * LINE_SPACING);
+            font::push_text(&mut quads, &line, position, SMALL_TEXT_HEIGHT, TEXT_COLOR);
+        }
+        ui_pipeline.draw(render_pass, &quads);
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use glam::{Vec2, Vec4};
+use std::cell::Cell;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPass, RenderPipeline, TextureFormat};
+
+use crate::engine::assets::{self, TextureAsset};
+use crate::ui::font;
+
+/// Quads the UI can draw in one frame; any past this are left out
+const MAX_UI_QUADS: usize = 16384;
+
+// Vertex format for the shared unit quad
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct QuadVertex {
+    corner: [f32; 2], // 0..1 from the top-left corner
+}
+
+/// Projection uniform: the screen size in logical pixels, padded to 16 bytes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    padding: [f32; 2],
+}
+
+/// Per-instance data for one rectangle of the UI, in logical pixels with y down
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiQuad {
+    pub position: [f32; 2], // Top-left corner before rotating
+    pub size: [f32; 2],
+    pub rotation: f32,      // Radians about the quad's center
+    pub uv_rect: [f32; 4],  // Texture region: offset in xy, size in zw
+    pub color: [f32; 4],    // Multiplies the texture
+}
+
+impl UiQuad {
+    /// Rectangle filled with one color
+    pub fn rect(position: Vec2, size: Vec2, color: Vec4) -> Self {
+        Self::textured(position, size, font::white_uv(), color)
+    }
+    
+    /// Rectangle sampling a region of the bound texture, tinted by a color
+    pub fn textured(position: Vec2, size: Vec2, uv_rect: [f32; 4], color: Vec4) -> Self {
+        Self {
+            position: position.to_array(),
+            size: size.to_array(),
+            rotation: 0.0,
+            uv_rect,
+            color: color.to_array(),
+        }
+    }
+    
+    /// Straight line between two points, e.g. a segment of a graph
+    pub fn line(from: Vec2, to: Vec2, thickness: f32, color: Vec4) -> Self {
+        let delta = to - from;
+        let length = delta.length();
+        let center = (from + to) * 0.5;
+        Self {
+            rotation: delta.y.atan2(delta.x),
+            ..Self::rect(center - Vec2::new(length, thickness) * 0.5, Vec2::new(length, thickness), color)
+        }
+    }
+    
+    /// Border just inside a rectangle's edges
+    pub fn outline(position: Vec2, size: Vec2, thickness: f32, color: Vec4) -> [Self; 4] {
+        let side = Vec2::new(thickness, size.y);
+        let top = Vec2::new(size.x, thickness);
+        [
+            Self::rect(position, top, color),
+            Self::rect(position + Vec2::new(0.0, size.y - thickness), top, color),
+            Self::rect(position, side, color),
+            Self::rect(position + Vec2::new(size.x - thickness, 0.0), side, color),
+        ]
+    }
+}
+
+/// Texture bound for one `draw_texture`, e.g. the minimap image
+pub struct UiTexture {
+    bind_group: BindGroup,
+}
+
+/// Instanced quad renderer for the UI. Elements draw while the render pass is being recorded: each
+/// draw copies its quads into its own range of the instance buffer, so draws in one frame never
+/// overwrite each other, and uses the font atlas unless given a texture of its own
+pub struct UiPipeline {
+    pub(super) device: Device,
+    pub(super) queue: Queue,
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout, // Texture and sampler
+    screen_buffer: Buffer,
+    screen_bind_group: BindGroup,
+    text_atlas: UiTexture, // Glyphs, and a white cell untextured quads sample
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    instance_buffer: Buffer,
+    drawn: Cell<u32>, // Quads written to the instance buffer so far this frame
+}
+
+impl UiPipeline {
+    pub fn new(device: Device, queue: Queue, format: TextureFormat) -> Result<Self> {
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("UI Screen Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("UI Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("UI Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/shaders/ui.wgsl").into()),
+        });
+        
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("UI Pipeline Layout"),
+            bind_group_layouts: &[&screen_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("UI Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<QuadVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x2, // Corner
+                        ],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<UiQuad>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            1 => Float32x2, // Position
+                            2 => Float32x2, // Size
+                            3 => Float32,   // Rotation
+                            4 => Float32x4, // Texture region
+                            5 => Float32x4, // Color
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("UI Screen Buffer"),
+            contents: bytemuck::bytes_of(&ScreenUniform { size: [1.0, 1.0], padding: [0.0; 2] }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("UI Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        
+        // Sampled without filtering so glyphs keep their hard pixel edges at any size
+        let atlas = assets::create_texture(
+            &device,
+            &queue,
+            &font::atlas_pixels(),
+            font::ATLAS_WIDTH,
+            font::ATLAS_HEIGHT,
+            Some("UI Text Atlas"),
+        )?;
+        let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("UI Text Atlas Sampler"),
+            ..Default::default()
+        });
+        let text_atlas = UiTexture {
+            bind_group: create_texture_bind_group(&device, &bind_group_layout, &atlas.view, &atlas_sampler),
+        };
+        
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("UI Quad Vertex Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("UI Quad Index Buffer"),
+            contents: bytemuck::cast_slice(&QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Instance Buffer"),
+            size: (MAX_UI_QUADS * std::mem::size_of::<UiQuad>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            bind_group_layout,
+            screen_buffer,
+            screen_bind_group,
+            text_atlas,
+            vertex_buffer,
+            index_buffer,
+            instance_buffer,
+            drawn: Cell::new(0),
+        })
+    }
+    
+    /// Size of the screen in logical pixels, which quad positions are given in
+    pub fn set_screen_size(&self, size: Vec2) {
+        let uniform = ScreenUniform { size: size.to_array(), padding: [0.0; 2] };
+        self.queue.write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+    
+    /// Texture an element draws with `draw_texture`, e.g. one it redraws on the CPU
+    pub fn create_texture(&self, texture: &TextureAsset) -> UiTexture {
+        UiTexture {
+            bind_group: create_texture_bind_group(&self.device, &self.bind_group_layout, &texture.view, &texture.sampler),
+        }
+    }
+    
+    /// Start the frame's UI: bind the pipeline, screen, font atlas and buffers every draw shares
+    pub fn begin_frame<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        self.drawn.set(0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.text_atlas.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    }
+    
+    /// Draw quads sampling the font atlas: solid rectangles, lines and text, in order
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, quads: &[UiQuad]) {
+        let start = self.drawn.get();
+        let count = quads.len().min(MAX_UI_QUADS - start as usize);
+        if count == 0 {
+            return;
+        }
+        
+        let offset = (start as usize * std::mem::size_of::<UiQuad>()) as wgpu::BufferAddress;
+        self.queue.write_buffer(&self.instance_buffer, offset, bytemuck::cast_slice(&quads[..count]));
+        render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, start..start + count as u32);
+        self.drawn.set(start + count as u32);
+    }
+    
+    /// Draw a whole texture stretched over a rectangle. `flip_y` draws its last row at the top
+    pub fn draw_texture<'a>(&'a self, render_pass: &mut RenderPass<'a>, texture: &'a UiTexture, position: Vec2, size: Vec2, flip_y: bool) {
+        let uv_rect = if flip_y { [0.0, 1.0, 1.0, -1.0] } else { [0.0, 0.0, 1.0, 1.0] };
+        render_pass.set_bind_group(1, &texture.bind_group, &[]);
+        self.draw(render_pass, &[UiQuad::textured(position, size, uv_rect, Vec4::ONE)]);
+        render_pass.set_bind_group(1, &self.text_atlas.bind_group, &[]);
+    }
+}
+
+// Unit quad from the top-left corner; instances scale, rotate and move it
+const QUAD_VERTICES: [QuadVertex; 4] = [
+    QuadVertex { corner: [0.0, 0.0] },
+    QuadVertex { corner: [1.0, 0.0] },
+    QuadVertex { corner: [1.0, 1.0] },
+    QuadVertex { corner: [0.0, 1.0] },
+];
+
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("UI Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
@@ -0,0 +1,75 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::ui::UiColorScheme;
+
+/// File the UI themes are defined in
+pub const THEMES_FILE: &str = "assets/ui/themes.ron";
+
+/// Theme used until the settings pick one
+pub const DEFAULT_THEME: &str = "Dark";
+
+/// Named color scheme from the themes file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub colors: UiColorScheme,
+}
+
+/// Themes the settings menu offers, in the order the themes file lists them
+#[derive(Debug, Clone)]
+pub struct Themes {
+    themes: Vec<Theme>, // Never empty
+}
+
+impl Default for Themes {
+    /// Only the built-in dark theme
+    fn default() -> Self {
+        Self {
+            themes: vec![Theme {
+                name: DEFAULT_THEME.to_string(),
+                colors: UiColorScheme::default(),
+            }],
+        }
+    }
+}
+
+impl Themes {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let themes: Vec<Theme> = ron::from_str(&text)?;
+        if themes.is_empty() {
+            bail!("no themes defined");
+        }
+        Ok(Self { themes })
+    }
+    
+    /// The themes file, or only the built-in dark theme if it can't be read
+    pub fn load_or_default() -> Self {
+        match Self::load(THEMES_FILE) {
+            Ok(themes) => themes,
+            Err(e) => {
+                warn!("Failed to load UI themes, using the default: {}", e);
+                Self::default()
+            }
+        }
+    }
+    
+    /// Theme names for the settings dropdown
+    pub fn names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme.name.clone()).collect()
+    }
+    
+    /// Position of a theme in `names()`
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.themes.iter().position(|theme| theme.name == name)
+    }
+    
+    /// A theme by name, falling back to the first one for names the file doesn't have
+    pub fn get(&self, name: &str) -> &Theme {
+        self.themes.iter().find(|theme| theme.name == name).unwrap_or(&self.themes[0])
+    }
+}